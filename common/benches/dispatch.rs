@@ -0,0 +1,180 @@
+//! Measures logging throughput as the number of threads logging concurrently grows, against a
+//! baseline that dispatches the way the logger used to: every record locks one
+//! `Mutex<HashMap<TypeId, Box<dyn Sink>>>` and calls the sinks while holding it.
+//!
+//! Both visit events the same way, so only dispatch differs; the logger's records go through
+//! [`log::submit`].
+//!
+//! Run with `cargo bench -p common --bench dispatch`.
+
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    fmt::Write,
+    hint::black_box,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use common::{
+    error::Error,
+    log::{self, Field, FieldValue, LogRecord, Sink},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tracing::{field::Visit, info, level_filters::LevelFilter, Dispatch, Subscriber};
+use tracing_subscriber::{layer::Context, prelude::*, registry, Layer};
+
+const RECORDS_PER_THREAD: u64 = 10_000;
+
+#[derive(Clone, Default)]
+struct CountingSink {
+    count: Arc<AtomicU64>,
+}
+
+impl Sink for CountingSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        black_box(record);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct NullSink;
+
+impl Sink for NullSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        black_box(record);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct RecordVisitor {
+    message: String,
+    fields: Vec<Field>,
+}
+
+impl Visit for RecordVisitor {
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.fields.push(Field {
+            name: field.name(),
+            value: FieldValue::U64(value),
+        });
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            _ = write!(&mut self.message, "{:?}", value);
+        }
+    }
+}
+
+fn record(event: &tracing::Event<'_>) -> LogRecord {
+    let mut visitor = RecordVisitor::default();
+    event.record(&mut visitor);
+
+    let metadata = event.metadata();
+    LogRecord {
+        fields: visitor.fields,
+        file: metadata.file(),
+        line: metadata.line(),
+        ..LogRecord::new(*metadata.level(), metadata.target(), visitor.message)
+    }
+}
+
+/// Dispatches under the global mutex the logger used before.
+#[derive(Default)]
+struct MutexLayer {
+    sinks: Mutex<HashMap<TypeId, Box<dyn Sink>>>,
+}
+
+impl MutexLayer {
+    fn add_sink<S: Sink + 'static>(&self, sink: S) {
+        self.sinks
+            .lock()
+            .unwrap()
+            .insert(TypeId::of::<S>(), Box::new(sink));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for MutexLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let record = record(event);
+        for sink in self.sinks.lock().unwrap().values() {
+            _ = sink.log(&record);
+        }
+    }
+}
+
+/// Hands records to the logger, which dispatches by copy-on-write.
+struct SubmitLayer;
+
+impl<S: Subscriber> Layer<S> for SubmitLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        log::submit(record(event));
+    }
+}
+
+/// Logs `iters` rounds of `RECORDS_PER_THREAD` records from each of `threads` threads at once.
+fn log_from_threads(dispatch: &Dispatch, threads: u64, iters: u64) -> Duration {
+    let start = Instant::now();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                tracing::dispatcher::with_default(dispatch, || {
+                    for i in 0..iters * RECORDS_PER_THREAD {
+                        info!(i, "benchmark record");
+                    }
+                })
+            });
+        }
+    });
+    start.elapsed()
+}
+
+fn dispatch(c: &mut Criterion) {
+    log::startup(LevelFilter::TRACE).expect("failed to start logger");
+    log::add_sink(&CountingSink::default());
+    log::add_sink(&NullSink);
+
+    let mutex = MutexLayer::default();
+    mutex.add_sink(CountingSink::default());
+    mutex.add_sink(NullSink);
+
+    let mutex = Dispatch::new(registry().with(LevelFilter::TRACE).with(mutex));
+    let copy_on_write = Dispatch::new(registry().with(LevelFilter::TRACE).with(SubmitLayer));
+
+    let mut group = c.benchmark_group("dispatch");
+    for threads in [1, 2, 4, 8] {
+        group.throughput(Throughput::Elements(threads * RECORDS_PER_THREAD));
+        group.bench_with_input(
+            BenchmarkId::new("mutex", threads),
+            &threads,
+            |b, &threads| b.iter_custom(|iters| log_from_threads(&mutex, threads, iters)),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("copy-on-write", threads),
+            &threads,
+            |b, &threads| b.iter_custom(|iters| log_from_threads(&copy_on_write, threads, iters)),
+        );
+    }
+    group.finish();
+
+    log::shutdown();
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+//! Measures time and heap allocations per event on the synchronous logging path, against a
+//! baseline that visits events the way the logger used to: a new message `String` and field
+//! `Vec` for every event, with the message formatted into a temporary `String` first.
+//! Both deliver records through the same logger and sink, the baseline with [`log::submit`].
+//!
+//! Run with `cargo bench -p common --bench visit`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    fmt::Write,
+    hint::black_box,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use common::{
+    error::Error,
+    log::{self, Field, FieldValue, LogRecord, Sink},
+};
+use criterion::{
+    criterion_group, criterion_main, measurement::WallTime, BenchmarkGroup, Criterion,
+};
+use tracing::{field::Visit, info, level_filters::LevelFilter, Subscriber};
+use tracing_subscriber::{layer::Context, prelude::*, registry, Layer};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[derive(Clone)]
+struct NullSink;
+
+impl Sink for NullSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        black_box(record);
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// The visitor before event buffers were reused.
+#[derive(Default)]
+struct BaselineVisitor {
+    message: String,
+    fields: Vec<Field>,
+}
+
+impl BaselineVisitor {
+    fn record_value(&mut self, field: &tracing::field::Field, value: FieldValue) {
+        if field.name() == "message" {
+            _ = write!(&mut self.message, "{}", value);
+        } else {
+            self.fields.push(Field {
+                name: field.name(),
+                value,
+            });
+        }
+    }
+}
+
+impl Visit for BaselineVisitor {
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.record_value(field, FieldValue::U64(value))
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        self.record_value(field, FieldValue::Bool(value))
+    }
+
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.record_value(field, FieldValue::Str(value.to_string()))
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.record_value(field, FieldValue::Debug(format!("{:?}", value)))
+    }
+}
+
+/// Visits each event with [`BaselineVisitor`] and submits the record to the logger.
+struct BaselineLayer;
+
+impl<S: Subscriber> Layer<S> for BaselineLayer {
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = BaselineVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        let record = LogRecord {
+            fields: visitor.fields,
+            file: metadata.file(),
+            line: metadata.line(),
+            ..LogRecord::new(*metadata.level(), metadata.target(), visitor.message)
+        };
+        log::submit(record);
+    }
+}
+
+fn events(group: &mut BenchmarkGroup<'_, WallTime>, path: &str) {
+    bench_event(group, path, "message", |_| info!("a plain message"));
+    bench_event(group, path, "formatted message", |i| {
+        info!("frame {i} took {}ms", i % 17)
+    });
+    bench_event(group, path, "message and fields", |i| {
+        info!(frame = i, ok = true, "frame done")
+    });
+}
+
+fn bench_event(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    path: &str,
+    name: &str,
+    mut log: impl FnMut(u64),
+) {
+    // Warm up so one-off allocations (thread locals, callsite registration) aren't counted.
+    for i in 0..1000 {
+        log(i);
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    for i in 0..10_000 {
+        log(i);
+    }
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{path}/{name}: {:.2} allocations/event",
+        allocations as f64 / 10_000.0
+    );
+
+    let mut i = 0;
+    group.bench_function(format!("{path}/{name}"), |b| {
+        b.iter(|| {
+            log(i);
+            i += 1;
+        })
+    });
+}
+
+fn visit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("visit");
+
+    log::startup(LevelFilter::TRACE).expect("failed to start logger");
+    log::add_sink(&NullSink);
+
+    let baseline = registry().with(LevelFilter::TRACE).with(BaselineLayer);
+    tracing::subscriber::with_default(baseline, || events(&mut group, "baseline"));
+    events(&mut group, "reused buffers");
+    log::shutdown();
+
+    group.finish();
+}
+
+criterion_group!(benches, visit);
+criterion_main!(benches);
@@ -1,16 +1,96 @@
-use std::fmt::Display;
+use std::{
+    any::Any,
+    backtrace::{Backtrace, BacktraceStatus},
+    fmt::{Display, Write},
+};
 
+use crate::json;
+
+/// A broad category of failure, for callers that handle some errors differently from others.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ErrorKind {
+    #[default]
+    Other,
+    Io,
+    /// An OS or windowing API failed.
+    Platform,
+    Graphics,
+    Audio,
+    /// Malformed input, e.g. a file in the wrong format.
+    Parse,
+    /// Settings that parse but are invalid.
+    Config,
+    NotFound,
+    Unsupported,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Other => "other",
+            ErrorKind::Io => "io",
+            ErrorKind::Platform => "platform",
+            ErrorKind::Graphics => "graphics",
+            ErrorKind::Audio => "audio",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Config => "config",
+            ErrorKind::NotFound => "not found",
+            ErrorKind::Unsupported => "unsupported",
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error message with an optional source, category and code.
+///
+/// A backtrace is captured when the error is created if `RUST_LIB_BACKTRACE` or `RUST_BACKTRACE`
+/// is set, or always with the `error_backtraces` feature. The alternate format, `{:#}`, prints it
+/// after the message.
 #[derive(Debug)]
 pub struct Error {
     message: String,
+    kind: ErrorKind,
+    code: Option<i64>,
     source: Option<Box<dyn std::error::Error>>,
+    errors: Vec<Error>,
+    // note: boxed and only kept when captured, so `Result<_, Error>` stays small.
+    backtrace: Option<Box<Backtrace>>,
 }
 
 impl Error {
     pub fn new<S: Into<String>>(message: S) -> Self {
         Self {
             message: message.into(),
+            kind: ErrorKind::Other,
+            code: None,
             source: None,
+            errors: Vec::new(),
+            backtrace: capture_backtrace(),
+        }
+    }
+
+    /// Combines several failures into one, e.g. from shutting down every subsystem or loading a
+    /// batch of assets. It displays as a numbered list, and takes the errors' kind if they all
+    /// share one.
+    pub fn aggregate(errors: Vec<Error>) -> Self {
+        let kind = match errors.split_first() {
+            Some((first, rest)) if rest.iter().all(|err| err.kind == first.kind) => first.kind,
+            _ => ErrorKind::Other,
+        };
+        let message = match errors.len() {
+            1 => "1 error".to_string(),
+            count => format!("{count} errors"),
+        };
+
+        Self {
+            kind,
+            errors,
+            ..Self::new(message)
         }
     }
 
@@ -20,11 +100,141 @@ impl Error {
             ..self
         }
     }
+
+    /// Replaces the backtrace captured by `new`, e.g. with one taken where a panic happened.
+    pub fn with_backtrace(self, backtrace: Backtrace) -> Self {
+        Self {
+            backtrace: Some(Box::new(backtrace)),
+            ..self
+        }
+    }
+
+    pub fn with_kind(self, kind: ErrorKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    /// A numeric code from wherever the error came from, e.g. a Win32 error code or an HRESULT.
+    pub fn with_code(self, code: i64) -> Self {
+        Self {
+            code: Some(code),
+            ..self
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn code(&self) -> Option<i64> {
+        self.code
+    }
+
+    /// The errors combined by [`aggregate`](Error::aggregate), or none for any other error.
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    /// This error followed by each of its sources in turn.
+    pub fn chain(&self) -> Chain<'_> {
+        Chain::new(self)
+    }
+
+    /// The messages of the whole chain joined with `: `, e.g.
+    /// `failed to init renderer: device removed: DXGI_ERROR_DEVICE_HUNG`.
+    pub fn full_message(&self) -> String {
+        full_message(self)
+    }
+
+    /// The first source in the chain of type `E`, skipping this error itself.
+    pub fn downcast_source<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        self.chain().skip(1).find_map(|err| err.downcast_ref::<E>())
+    }
+
+    /// The error as a JSON object for crash reports and log shipping, e.g.
+    /// `{"message":"failed to init renderer","kind":"graphics","code":2289696773,"sources":[...]}`.
+    /// `sources` holds the rest of the chain, outermost first, `errors` the errors of any
+    /// aggregate, and `backtrace` is only present if one was captured.
+    pub fn to_json(&self) -> String {
+        let mut out = String::with_capacity(256);
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        write_json_fields(out, self);
+
+        out.push_str(",\"sources\":[");
+        for (i, source) in self.chain().skip(1).enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            match source.downcast_ref::<Error>() {
+                Some(source) => write_json_fields(out, source),
+                None => {
+                    out.push_str("{\"message\":");
+                    json::write_str(out, &source.to_string());
+                }
+            }
+            out.push('}');
+        }
+        out.push(']');
+
+        if let Some(backtrace) = self.backtrace() {
+            out.push_str(",\"backtrace\":");
+            json::write_str(out, &backtrace.to_string());
+        }
+        out.push('}');
+    }
+
+    /// Where the error was created, if a backtrace was captured.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_deref()
+    }
+}
+
+/// Opens an object with the error's own message, kind, code and aggregated errors, leaving it open
+/// for more fields.
+fn write_json_fields(out: &mut String, err: &Error) {
+    out.push_str("{\"message\":");
+    json::write_str(out, &err.message);
+    out.push_str(",\"kind\":");
+    json::write_str(out, err.kind.as_str());
+    if let Some(code) = err.code {
+        _ = write!(out, ",\"code\":{code}");
+    }
+    if !err.errors.is_empty() {
+        out.push_str(",\"errors\":[");
+        for (i, err) in err.errors.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            err.write_json(out);
+        }
+        out.push(']');
+    }
+}
+
+fn capture_backtrace() -> Option<Box<Backtrace>> {
+    let backtrace = if cfg!(feature = "error_backtraces") {
+        Backtrace::force_capture()
+    } else {
+        Backtrace::capture()
+    };
+
+    (backtrace.status() == BacktraceStatus::Captured).then(|| Box::new(backtrace))
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", &self.message)
+        write!(f, "{}", &self.message)?;
+        for (i, err) in self.errors.iter().enumerate() {
+            write!(f, "\n  {}. {}", i + 1, err.full_message())?;
+        }
+        if let Some(backtrace) = self.backtrace().filter(|_| f.alternate()) {
+            write!(f, "\n\nstack backtrace:\n{backtrace}")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -33,3 +243,197 @@ impl std::error::Error for Error {
         self.source.as_deref()
     }
 }
+
+/// Iterates over an error and its sources; see [`Error::chain`].
+#[derive(Clone)]
+pub struct Chain<'a> {
+    next: Option<&'a (dyn std::error::Error + 'static)>,
+}
+
+impl<'a> Chain<'a> {
+    /// Works for any error, not only [`Error`].
+    pub fn new(err: &'a (dyn std::error::Error + 'static)) -> Self {
+        Self { next: Some(err) }
+    }
+}
+
+impl<'a> Iterator for Chain<'a> {
+    type Item = &'a (dyn std::error::Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let err = self.next?;
+        self.next = err.source();
+        Some(err)
+    }
+}
+
+/// Joins the messages of `err` and its sources, since `Error`'s `Display` only shows its own.
+pub(crate) fn full_message(err: &(dyn std::error::Error + 'static)) -> String {
+    let mut message = String::new();
+    for (i, err) in Chain::new(err).enumerate() {
+        if i > 0 {
+            message.push_str(": ");
+        }
+        _ = write!(message, "{err}");
+    }
+
+    message
+}
+
+/// Wraps failures in an [`Error`] describing what was being done, e.g.
+/// `fs::read_to_string(path).context("failed to load settings")?`. Wrapping an `Error` keeps its
+/// kind and code.
+pub trait Context<T> {
+    fn context<S: Into<String>>(self, message: S) -> Result<T, Error>;
+
+    /// Like [`context`](Context::context), only building the message on failure.
+    fn with_context<S: Into<String>, F: FnOnce() -> S>(self, message: F) -> Result<T, Error>;
+}
+
+impl<T, E: std::error::Error + 'static> Context<T> for Result<T, E> {
+    fn context<S: Into<String>>(self, message: S) -> Result<T, Error> {
+        self.map_err(|err| wrap(message.into(), err))
+    }
+
+    fn with_context<S: Into<String>, F: FnOnce() -> S>(self, message: F) -> Result<T, Error> {
+        self.map_err(|err| wrap(message().into(), err))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    fn context<S: Into<String>>(self, message: S) -> Result<T, Error> {
+        self.ok_or_else(|| Error::new(message))
+    }
+
+    fn with_context<S: Into<String>, F: FnOnce() -> S>(self, message: F) -> Result<T, Error> {
+        self.ok_or_else(|| Error::new(message()))
+    }
+}
+
+fn wrap<E: std::error::Error + 'static>(message: String, source: E) -> Error {
+    let mut error = Error::new(message);
+    if let Some(inner) = (&source as &dyn Any).downcast_ref::<Error>() {
+        error.kind = inner.kind;
+        error.code = inner.code;
+    }
+
+    error.with_source(source)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+
+    fn messages(err: &Error) -> Vec<String> {
+        err.chain().map(|err| err.to_string()).collect()
+    }
+
+    #[test]
+    fn chain_goes_from_outermost_to_root_cause() {
+        let err = Error::new("failed to start").with_source(
+            Error::new("failed to load settings").with_source(io::Error::new(
+                io::ErrorKind::NotFound,
+                "settings.toml not found",
+            )),
+        );
+
+        assert_eq!(
+            messages(&err),
+            [
+                "failed to start",
+                "failed to load settings",
+                "settings.toml not found"
+            ]
+        );
+        assert_eq!(
+            err.full_message(),
+            "failed to start: failed to load settings: settings.toml not found"
+        );
+        assert_eq!(
+            err.downcast_source::<io::Error>().map(io::Error::kind),
+            Some(io::ErrorKind::NotFound)
+        );
+        assert!(err.downcast_source::<Error>().is_some());
+    }
+
+    #[test]
+    fn aggregate_lists_each_error_with_its_chain() {
+        let err = Error::aggregate(vec![
+            Error::new("failed to stop audio").with_kind(ErrorKind::Audio),
+            Error::new("failed to stop renderer")
+                .with_kind(ErrorKind::Graphics)
+                .with_source(Error::new("device removed")),
+        ]);
+
+        assert_eq!(
+            err.to_string(),
+            "2 errors\n  1. failed to stop audio\n  2. failed to stop renderer: device removed"
+        );
+        assert_eq!(err.errors().len(), 2);
+        // The kinds differ, so the aggregate's is left as other.
+        assert_eq!(err.kind(), ErrorKind::Other);
+        // The aggregated errors aren't its sources.
+        assert_eq!(err.chain().count(), 1);
+    }
+
+    #[test]
+    fn aggregate_takes_a_shared_kind() {
+        let err = Error::aggregate(vec![
+            Error::new("missing ship.png").with_kind(ErrorKind::NotFound)
+        ]);
+        assert_eq!(err.to_string(), "1 error\n  1. missing ship.png");
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn context_wraps_the_error_and_keeps_its_kind_and_code() {
+        let inner: Result<(), Error> = Err(Error::new("device removed")
+            .with_kind(ErrorKind::Graphics)
+            .with_code(0x887a0005));
+        let err = inner.context("failed to present").unwrap_err();
+
+        assert_eq!(messages(&err), ["failed to present", "device removed"]);
+        assert_eq!(err.kind(), ErrorKind::Graphics);
+        assert_eq!(err.code(), Some(0x887a0005));
+    }
+
+    #[test]
+    fn context_on_other_errors_and_options() {
+        let io: Result<(), io::Error> = Err(io::Error::other("disk full"));
+        let err = io
+            .with_context(|| "failed to save".to_string())
+            .unwrap_err();
+        assert_eq!(messages(&err), ["failed to save", "disk full"]);
+        assert_eq!(err.kind(), ErrorKind::Other);
+        assert_eq!(err.code(), None);
+
+        let err = None::<()>.context("no adapter").unwrap_err();
+        assert_eq!(messages(&err), ["no adapter"]);
+        assert_eq!(Some(1).context("no adapter").unwrap(), 1);
+    }
+
+    #[test]
+    fn to_json_lists_the_chain_with_escaped_messages() {
+        let mut err = Error::new("failed to load \"ship.png\"")
+            .with_kind(ErrorKind::Graphics)
+            .with_code(2289696773)
+            .with_source(
+                Error::new("bad header\n\tat C:\\assets")
+                    .with_kind(ErrorKind::Parse)
+                    .with_source(io::Error::other("unexpected end of file")),
+            );
+        // note: cleared so the output doesn't depend on RUST_BACKTRACE.
+        err.backtrace = None;
+
+        assert_eq!(
+            err.to_json(),
+            concat!(
+                r#"{"message":"failed to load \"ship.png\"","kind":"graphics","code":2289696773,"#,
+                r#""sources":[{"message":"bad header\n\tat C:\\assets","kind":"parse"},"#,
+                r#"{"message":"unexpected end of file"}]}"#,
+            )
+        );
+    }
+}
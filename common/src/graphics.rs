@@ -0,0 +1,360 @@
+//! The interface renderers draw through, so a game can pick a graphics backend at startup and
+//! the rest of it doesn't care which:
+//!
+//! ```
+//! use common::{
+//!     error::Error,
+//!     graphics::{
+//!         Backend, ColorSpace, CommandList, GraphicsDevice, Image, PresentOptions, TextureFilter,
+//!         TextureId, Vertex,
+//!     },
+//! };
+//!
+//! fn draw(device: &mut dyn GraphicsDevice) -> Result<(), Error> {
+//!     let commands = device.begin_frame()?;
+//!     commands.clear([0.1, 0.2, 0.3, 1.0]);
+//!     device.end_frame()
+//! }
+//!
+//! #[derive(Default)]
+//! struct Recorder(Vec<[f32; 4]>, usize, PresentOptions, Vec<Image>);
+//!
+//! impl CommandList for Recorder {
+//!     fn clear(&mut self, color: [f32; 4]) {
+//!         self.0.push(color);
+//!     }
+//!
+//!     fn draw_quads(&mut self, _: TextureId, _: &[[f32; 4]; 4], _: &[Vertex]) {}
+//! }
+//!
+//! impl GraphicsDevice for Recorder {
+//!     fn backend(&self) -> Backend {
+//!         Backend::D3D11
+//!     }
+//!
+//!     fn resize(&mut self, _width: u32, _height: u32) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//!
+//!     fn present_options(&self) -> PresentOptions {
+//!         self.2
+//!     }
+//!
+//!     fn set_present_options(&mut self, options: PresentOptions) -> Result<(), Error> {
+//!         // An SDR display, without variable refresh.
+//!         self.2 = PresentOptions {
+//!             allow_tearing: false,
+//!             color_space: ColorSpace::Srgb,
+//!             ..options
+//!         };
+//!         Ok(())
+//!     }
+//!
+//!     fn create_texture(&mut self, image: Image, _: TextureFilter) -> Result<TextureId, Error> {
+//!         self.3.push(image);
+//!         Ok(TextureId::from_index(self.3.len() - 1))
+//!     }
+//!
+//!     fn update_texture(
+//!         &mut self,
+//!         texture: TextureId,
+//!         origin: [u32; 2],
+//!         image: &Image,
+//!     ) -> Result<(), Error> {
+//!         self.3[texture.index()].copy_from(origin, image)
+//!     }
+//!
+//!     fn destroy_texture(&mut self, _texture: TextureId) {}
+//!
+//!     fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error> {
+//!         Ok(self)
+//!     }
+//!
+//!     fn end_frame(&mut self) -> Result<(), Error> {
+//!         self.1 += 1;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut device = Recorder::default();
+//! draw(&mut device)?;
+//! assert_eq!((device.0.len(), device.1), (1, 1));
+//!
+//! device.set_present_options(PresentOptions {
+//!     vsync: false,
+//!     color_space: ColorSpace::Hdr10,
+//!     ..PresentOptions::default()
+//! })?;
+//! assert!(!device.present_options().vsync);
+//! assert_eq!(device.present_options().color_space, ColorSpace::Srgb);
+//!
+//! let white = device.create_texture(Image::solid(2, 2, [255; 4]), TextureFilter::Nearest)?;
+//! assert_eq!(white.index(), 0);
+//! device.update_texture(white, [1, 0], &Image::solid(1, 2, [255, 0, 0, 255]))?;
+//! assert_eq!(device.3[0].pixels()[4..8], [255, 0, 0, 255]);
+//! assert!(device.update_texture(white, [1, 1], &Image::solid(2, 2, [0; 4])).is_err());
+//! assert!(Image::new(2, 2, vec![0; 12]).is_err());
+//! assert_eq!("d3d12".parse::<Backend>()?, Backend::D3D12);
+//! # Ok::<(), Error>(())
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::error::{Error, ErrorKind};
+
+/// A graphics API a [`GraphicsDevice`] is implemented with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    D3D11,
+    D3D12,
+    /// wgpu, on whichever of Vulkan, Metal, D3D12 or GL it picks for the platform.
+    Wgpu,
+}
+
+impl Backend {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Backend::D3D11 => "d3d11",
+            Backend::D3D12 => "d3d12",
+            Backend::Wgpu => "wgpu",
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Backend {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "d3d11" => Ok(Backend::D3D11),
+            "d3d12" => Ok(Backend::D3D12),
+            "wgpu" => Ok(Backend::Wgpu),
+            _ => Err(Error::new(format!(
+                "unknown graphics backend {s:?}, expected \"d3d11\", \"d3d12\" or \"wgpu\""
+            ))
+            .with_kind(ErrorKind::Parse)),
+        }
+    }
+}
+
+/// The color space of a swap chain's back buffers, and so of what's drawn to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorSpace {
+    /// 8-bit sRGB, for SDR displays.
+    #[default]
+    Srgb,
+    /// 10-bit Rec. 2100 PQ, for HDR displays.
+    Hdr10,
+    /// 16-bit float linear Rec. 709, where 1.0 is 80 nits and brighter goes above it, for HDR
+    /// displays.
+    ScRgb,
+}
+
+/// How a swap chain presents frames. They can be changed at any time with
+/// [`GraphicsDevice::set_present_options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PresentOptions {
+    /// Whether presenting waits for the display's vertical blank. Defaults to on.
+    pub vsync: bool,
+    /// Whether frames without vsync may be shown mid-refresh, which variable refresh rate
+    /// displays need to match the game's frame rate. Defaults to on.
+    pub allow_tearing: bool,
+    /// How many frames can be queued ahead of the display, from 1 to 16. Lower is less input
+    /// latency, higher is smoother when frame times vary. Defaults to 2.
+    pub max_frame_latency: u32,
+    /// Defaults to [`ColorSpace::Srgb`].
+    pub color_space: ColorSpace,
+}
+
+impl Default for PresentOptions {
+    fn default() -> Self {
+        Self {
+            vsync: true,
+            allow_tearing: true,
+            max_frame_latency: 2,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+/// A texture made with [`GraphicsDevice::create_texture`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TextureId(u32);
+
+impl TextureId {
+    /// For backends, which number their textures from zero.
+    pub fn from_index(index: usize) -> Self {
+        Self(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// How a texture is sampled between its texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextureFilter {
+    /// Blends the nearest texels, for smooth scaling.
+    #[default]
+    Linear,
+    /// Takes the nearest texel, keeping pixel art crisp.
+    Nearest,
+}
+
+/// 8-bit RGBA pixels with straight alpha, in rows from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Image {
+    /// Fails unless there are `width * height` pixels, and at least one.
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, Error> {
+        let expected = width as usize * height as usize * 4;
+        if expected == 0 || pixels.len() != expected {
+            return Err(Error::new(format!(
+                "a {width}x{height} image needs {expected} bytes of pixels, got {}",
+                pixels.len()
+            ))
+            .with_kind(ErrorKind::Parse));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// An image of one color, e.g. white to draw tinted rectangles with. A zero size is taken as
+    /// one.
+    pub fn solid(width: u32, height: u32, color: [u8; 4]) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        Self {
+            width,
+            height,
+            pixels: color.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Copies `image` over this one with its top left at `origin`, failing if it doesn't fit.
+    pub fn copy_from(&mut self, origin: [u32; 2], image: &Image) -> Result<(), Error> {
+        let [x, y] = origin.map(|coordinate| coordinate as usize);
+        let (width, height) = (image.width as usize, image.height as usize);
+        if x + width > self.width as usize || y + height > self.height as usize {
+            return Err(Error::new(format!(
+                "a {width}x{height} image doesn't fit in a {}x{} one at {x}, {y}",
+                self.width, self.height
+            )));
+        }
+
+        let stride = self.width as usize * 4;
+        for (row, source) in image.pixels.chunks_exact(width * 4).enumerate() {
+            let start = (y + row) * stride + x * 4;
+            self.pixels[start..start + width * 4].copy_from_slice(source);
+        }
+        Ok(())
+    }
+}
+
+/// A corner of a quad drawn with [`CommandList::draw_quads`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vertex {
+    pub position: [f32; 2],
+    /// Where on the texture, from `[0.0, 0.0]` at its top left to `[1.0, 1.0]` at its bottom
+    /// right.
+    pub uv: [f32; 2],
+    /// RGBA the texture's color is multiplied by.
+    pub color: [f32; 4],
+}
+
+/// Commands recorded for a frame, from [`GraphicsDevice::begin_frame`].
+pub trait CommandList {
+    /// Clears the frame's render target to `color`, as linear RGBA.
+    fn clear(&mut self, color: [f32; 4]);
+
+    /// Draws textured quads from `vertices`, four corners in order around each one, alpha
+    /// blended over what's there. `transform` takes their positions to clip space, as a 4x4
+    /// matrix in columns. Quads aren't culled whichever way they face, and an unknown texture
+    /// draws nothing.
+    fn draw_quads(&mut self, texture: TextureId, transform: &[[f32; 4]; 4], vertices: &[Vertex]);
+}
+
+/// A GPU and the swap chain on the window it presents to.
+///
+/// If the GPU is lost, implementations recreate what they need and log why, rather than failing
+/// every frame after.
+pub trait GraphicsDevice {
+    fn backend(&self) -> Backend;
+
+    /// Resizes the swap chain to the window's new size, in physical pixels. Call it on every
+    /// resize event. A zero size, as when the window is minimized, is ignored.
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error>;
+
+    /// The present options in effect, which differ from those last set if the display or driver
+    /// doesn't support some of them.
+    fn present_options(&self) -> PresentOptions;
+
+    /// Changes how frames are presented from the next one on. Options the display doesn't
+    /// support, e.g. HDR on an SDR monitor, fall back to ones it does, with a warning.
+    fn set_present_options(&mut self, options: PresentOptions) -> Result<(), Error>;
+
+    /// Uploads `image` to the GPU to draw with. Implementations keep the image, so textures
+    /// survive the GPU being lost.
+    fn create_texture(&mut self, image: Image, filter: TextureFilter) -> Result<TextureId, Error>;
+
+    /// Replaces the part of `texture` at `origin`, in texels from its top left, with `image`,
+    /// e.g. to add glyphs to an atlas. Fails if `texture` doesn't exist or `image` doesn't fit.
+    ///
+    /// note: draws already recorded this frame may see the new texels.
+    fn update_texture(
+        &mut self,
+        texture: TextureId,
+        origin: [u32; 2],
+        image: &Image,
+    ) -> Result<(), Error>;
+
+    /// Frees `texture`. Its id may be reused by a later texture.
+    fn destroy_texture(&mut self, texture: TextureId);
+
+    /// Replaces the sprite shader with `source`, e.g. an edited copy of the built-in one being
+    /// hot reloaded. It needs the built-in one's entry points, inputs and bindings. If it fails
+    /// to compile, the shader in use is kept. Backends that don't draw with HLSL fail with
+    /// [`ErrorKind::Unsupported`].
+    fn set_sprite_shader(&mut self, source: &str) -> Result<(), Error> {
+        _ = source;
+        Err(Error::new(format!(
+            "the {} renderer doesn't take HLSL sprite shaders",
+            self.backend()
+        ))
+        .with_kind(ErrorKind::Unsupported))
+    }
+
+    /// Starts recording the next frame, waiting if the GPU is too far behind.
+    fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error>;
+
+    /// Submits the frame's commands and presents it.
+    fn end_frame(&mut self) -> Result<(), Error>;
+}
@@ -0,0 +1,502 @@
+//! A small gzip encoder: LZ77 with a hash chain, written as a single fixed-Huffman DEFLATE block
+//! (RFC 1951, RFC 1952). Log batches are repetitive enough that this gets most of the benefit of
+//! a full encoder. Data that would grow, e.g. already compressed, is written as stored blocks
+//! instead. The same DEFLATE stream backs zip entries.
+
+const WINDOW_SIZE: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const MAX_CHAIN: usize = 32;
+const HASH_BITS: u32 = 15;
+const MAX_STORED: usize = u16::MAX as usize;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// Returns `data` compressed as a gzip member.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() / 3 + 32);
+    bytes.extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]);
+    deflate_into(data, &mut bytes);
+    bytes.extend_from_slice(&crc32(data).to_le_bytes());
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes
+}
+
+/// Returns `data` as a raw DEFLATE stream, as zip entries store it.
+pub(crate) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(data.len() / 3 + 16);
+    deflate_into(data, &mut bytes);
+    bytes
+}
+
+fn deflate_into(data: &[u8], bytes: &mut Vec<u8>) {
+    let start = bytes.len();
+    let mut out = BitWriter {
+        bytes: std::mem::take(bytes),
+        bits: 0,
+        count: 0,
+    };
+
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman codes).
+    out.write_bits(1, 1);
+    out.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW_SIZE];
+    let mut pos = 0;
+    while pos < data.len() {
+        let (length, distance) = longest_match(data, pos, &head, &prev);
+        if length >= MIN_MATCH {
+            write_match(&mut out, length, distance);
+            for p in pos..pos + length {
+                insert(data, p, &mut head, &mut prev);
+            }
+            pos += length;
+        } else {
+            write_literal(&mut out, u16::from(data[pos]));
+            insert(data, pos, &mut head, &mut prev);
+            pos += 1;
+        }
+    }
+    write_literal(&mut out, 256);
+
+    *bytes = out.finish();
+
+    // Each stored block costs 5 bytes on top of its data.
+    let stored_len = data.len() + 5 * data.len().div_ceil(MAX_STORED).max(1);
+    if bytes.len() - start > stored_len {
+        bytes.truncate(start);
+        write_stored(data, bytes);
+    }
+}
+
+fn write_stored(data: &[u8], bytes: &mut Vec<u8>) {
+    let mut chunks = data.chunks(MAX_STORED).peekable();
+    loop {
+        // Empty data is still a final, empty block.
+        let chunk = chunks.next().unwrap_or_default();
+        let last = chunks.peek().is_none();
+        let len = chunk.len() as u16;
+
+        // BFINAL, BTYPE = 00 (stored), padded to a byte, then the length and its complement.
+        bytes.push(u8::from(last));
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&(!len).to_le_bytes());
+        bytes.extend_from_slice(chunk);
+        if last {
+            return;
+        }
+    }
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let value =
+        u32::from(data[pos]) << 16 | u32::from(data[pos + 1]) << 8 | u32::from(data[pos + 2]);
+    (value.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash(data, pos);
+        prev[pos % WINDOW_SIZE] = head[h];
+        head[h] = pos;
+    }
+}
+
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> (usize, usize) {
+    if pos + MIN_MATCH > data.len() {
+        return (0, 0);
+    }
+
+    let max_length = (data.len() - pos).min(MAX_MATCH);
+    let (mut best_length, mut best_distance) = (0, 0);
+    let mut candidate = head[hash(data, pos)];
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW_SIZE {
+            break;
+        }
+
+        let length = data[candidate..]
+            .iter()
+            .zip(&data[pos..pos + max_length])
+            .take_while(|(a, b)| a == b)
+            .count();
+        if length > best_length {
+            best_length = length;
+            best_distance = pos - candidate;
+            if length == max_length {
+                break;
+            }
+        }
+
+        let next = prev[candidate % WINDOW_SIZE];
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+
+    (best_length, best_distance)
+}
+
+fn write_literal(out: &mut BitWriter, symbol: u16) {
+    let (code, length) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    out.write_code(code, length);
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let index = LENGTH_BASE
+        .iter()
+        .rposition(|base| usize::from(*base) <= length)
+        .unwrap();
+    write_literal(out, 257 + index as u16);
+    out.write_bits(
+        (length - usize::from(LENGTH_BASE[index])) as u32,
+        u32::from(LENGTH_EXTRA[index]),
+    );
+
+    let index = DISTANCE_BASE
+        .iter()
+        .rposition(|base| usize::from(*base) <= distance)
+        .unwrap();
+    out.write_code(index as u16, 5);
+    out.write_bits(
+        (distance - usize::from(DISTANCE_BASE[index])) as u32,
+        u32::from(DISTANCE_EXTRA[index]),
+    );
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Writes `count` bits of `value`, least significant first.
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are packed most significant bit first.
+    fn write_code(&mut self, code: u16, length: u32) {
+        let reversed = u32::from(code.reverse_bits()) >> (16 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+
+    !data.iter().fold(!0u32, |crc, byte| {
+        TABLE[((crc ^ u32::from(*byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A DEFLATE decoder written from RFC 1951 rather than sharing the encoder's tables, for
+    /// stored and fixed-Huffman blocks.
+    fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut input = BitReader { data, pos: 0 };
+        let mut out = Vec::new();
+
+        // The fixed literal/length code, then the distance code, as code lengths (3.2.6).
+        let mut lengths = [8; 288];
+        lengths[144..256].fill(9);
+        lengths[256..280].fill(7);
+        let literals = Huffman::new(&lengths);
+        let distances = Huffman::new(&[5; 30]);
+
+        // Lengths 3 to 258 and distances 1 to 32768, with the extra bits each code takes (3.2.5).
+        let length_extra = |code: usize| {
+            if code < 8 || code == 28 {
+                0
+            } else {
+                code / 4 - 1
+            }
+        };
+        let distance_extra = |code: usize| if code < 2 { 0 } else { code / 2 - 1 };
+        let base = |extra: &dyn Fn(usize) -> usize, first: usize, code: usize| {
+            (0..code).fold(first, |base, code| base + (1 << extra(code)))
+        };
+
+        loop {
+            let last = input.bits(1)? == 1;
+            match input.bits(2)? {
+                0 => {
+                    input.align();
+                    let len = input.u16()?;
+                    if input.u16()? != !len {
+                        return Err("stored length doesn't match its complement".into());
+                    }
+                    out.extend_from_slice(input.bytes(usize::from(len))?);
+                }
+                1 => loop {
+                    let symbol = literals.decode(&mut input)?;
+                    if symbol < 256 {
+                        out.push(symbol as u8);
+                        continue;
+                    }
+                    if symbol == 256 {
+                        break;
+                    }
+
+                    let code = symbol - 257;
+                    if code >= 29 {
+                        return Err(format!("invalid length symbol {symbol}"));
+                    }
+                    let length = if code == 28 {
+                        258
+                    } else {
+                        base(&length_extra, 3, code) + input.bits(length_extra(code))?
+                    };
+                    let code = distances.decode(&mut input)?;
+                    if code >= 30 {
+                        return Err(format!("invalid distance symbol {code}"));
+                    }
+                    let distance =
+                        base(&distance_extra, 1, code) + input.bits(distance_extra(code))?;
+                    if distance > out.len() {
+                        return Err(format!("distance {distance} is before the start"));
+                    }
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                },
+                block => return Err(format!("unexpected block type {block}")),
+            }
+
+            if last {
+                return Ok(out);
+            }
+        }
+    }
+
+    fn gunzip(data: &[u8]) -> Result<Vec<u8>, String> {
+        let (Some(header), Some(trailer)) = (data.get(..10), data.len().checked_sub(8)) else {
+            return Err("too short for a gzip member".into());
+        };
+        if header[..4] != [0x1f, 0x8b, 8, 0] {
+            return Err(format!("unexpected header {header:02x?}"));
+        }
+
+        let out = inflate(&data[10..trailer])?;
+        let crc = u32::from_le_bytes(data[trailer..trailer + 4].try_into().unwrap());
+        let len = u32::from_le_bytes(data[trailer + 4..].try_into().unwrap());
+        if crc != crc32(&out) || len != out.len() as u32 {
+            return Err("trailer doesn't match the data".into());
+        }
+        Ok(out)
+    }
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        /// In bits.
+        pos: usize,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: usize) -> Result<usize, String> {
+            let mut value = 0;
+            for i in 0..count {
+                let byte = self
+                    .data
+                    .get(self.pos / 8)
+                    .ok_or("unexpected end of data")?;
+                value |= usize::from(byte >> (self.pos % 8) & 1) << i;
+                self.pos += 1;
+            }
+            Ok(value)
+        }
+
+        fn align(&mut self) {
+            self.pos = self.pos.next_multiple_of(8);
+        }
+
+        fn bytes(&mut self, len: usize) -> Result<&[u8], String> {
+            let start = self.pos / 8;
+            let bytes = self
+                .data
+                .get(start..start + len)
+                .ok_or("unexpected end of data")?;
+            self.pos += len * 8;
+            Ok(bytes)
+        }
+
+        fn u16(&mut self) -> Result<u16, String> {
+            Ok(u16::from_le_bytes(self.bytes(2)?.try_into().unwrap()))
+        }
+    }
+
+    /// A canonical Huffman code, decoded a bit at a time.
+    struct Huffman {
+        /// The number of codes of each length.
+        counts: [usize; 16],
+        /// Symbols ordered by code.
+        symbols: Vec<usize>,
+    }
+
+    impl Huffman {
+        fn new(lengths: &[usize]) -> Self {
+            let mut counts = [0; 16];
+            for &length in lengths {
+                counts[length] += 1;
+            }
+            counts[0] = 0;
+
+            let mut symbols: Vec<_> = (0..lengths.len()).filter(|&s| lengths[s] > 0).collect();
+            symbols.sort_by_key(|&symbol| lengths[symbol]);
+            Self { counts, symbols }
+        }
+
+        fn decode(&self, input: &mut BitReader) -> Result<usize, String> {
+            // The first code of each length follows the last of the one before, shifted left.
+            let (mut code, mut first, mut index) = (0, 0, 0);
+            for &count in &self.counts[1..] {
+                code |= input.bits(1)?;
+                if code < first + count {
+                    return Ok(self.symbols[index + code - first]);
+                }
+                index += count;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            Err("invalid code".into())
+        }
+    }
+
+    /// Bytes that don't compress, from a xorshift generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        assert_eq!(gunzip(&compress(&[])).unwrap(), []);
+        assert_eq!(inflate(&deflate(&[])).unwrap(), []);
+        // Just the end of block code.
+        assert_eq!(deflate(&[]).len(), 2);
+    }
+
+    #[test]
+    fn round_trips_log_lines() {
+        let data: String = (0..500)
+            .map(|i| {
+                format!(
+                    "[2024-01-01T00:00:{:02}Z][INFO][game::physics] step {i} took 16ms\n",
+                    i % 60
+                )
+            })
+            .collect();
+        let compressed = compress(data.as_bytes());
+        assert_eq!(gunzip(&compressed).unwrap(), data.as_bytes());
+        assert!(
+            compressed.len() < data.len() / 4,
+            "{} bytes",
+            compressed.len()
+        );
+    }
+
+    #[test]
+    fn round_trips_long_runs() {
+        for len in [1, 3, 258, 259, 1000, WINDOW_SIZE + 1, 100_000] {
+            let data = vec![b'a'; len];
+            assert_eq!(inflate(&deflate(&data)).unwrap(), data, "{len} bytes");
+        }
+
+        // Matches as far back as the window reaches. Bytes below 144 take 8 bits as literals,
+        // so the block isn't stored.
+        let mut data: Vec<_> = noise(WINDOW_SIZE).iter().map(|b| b & 0x7f).collect();
+        data.extend_from_within(..600);
+        let deflated = deflate(&data);
+        assert_eq!(inflate(&deflated).unwrap(), data);
+        assert!(
+            deflated.len() < WINDOW_SIZE + 20,
+            "{} bytes",
+            deflated.len()
+        );
+
+        assert!(deflate(&[0; 100_000]).len() < 1000);
+    }
+
+    #[test]
+    fn stores_incompressible_data() {
+        for len in [1, 2, 100, MAX_STORED, MAX_STORED + 1, 3 * MAX_STORED + 10] {
+            let data = noise(len);
+            let deflated = deflate(&data);
+            assert_eq!(inflate(&deflated).unwrap(), data, "{len} bytes");
+            assert_eq!(gunzip(&compress(&data)).unwrap(), data, "{len} bytes");
+            if len > 2 {
+                assert_eq!(deflated[0] & 0b110, 0, "{len} bytes should be stored");
+                assert_eq!(deflated.len(), len + 5 * len.div_ceil(MAX_STORED));
+            }
+        }
+    }
+
+    #[test]
+    fn crc32_matches_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(&[]), 0);
+    }
+}
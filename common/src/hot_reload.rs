@@ -0,0 +1,221 @@
+//! Assets that are reloaded when their files change, so shaders, textures and configs can be
+//! edited while the game runs. A file watcher reports the changes, e.g. `win32`'s `FileWatcher`,
+//! and each [`Reloadable`] picks out its own:
+//!
+//! ```
+//! use std::{fs, path::Path};
+//!
+//! use common::{error::Error, hot_reload::Reloadable};
+//!
+//! fn load_speed(path: &Path) -> Result<f32, Error> {
+//!     let text = fs::read_to_string(path).map_err(|err| Error::new("unreadable").with_source(err))?;
+//!     text.trim().parse().map_err(|_| Error::new("not a number"))
+//! }
+//!
+//! let root = std::env::temp_dir().join("galleon-hot-reload-doctest");
+//! fs::create_dir_all(&root).unwrap();
+//! let path = root.join("speed.txt");
+//! fs::write(&path, "1.5").unwrap();
+//!
+//! let mut speed = Reloadable::new(&path, load_speed)?;
+//! assert_eq!(*speed.get(), 1.5);
+//!
+//! fs::write(&path, "2.5").unwrap();
+//! assert!(!speed.file_changed(&root.join("other.txt")));
+//! assert!(speed.file_changed(&path));
+//! assert_eq!(*speed.get(), 2.5);
+//!
+//! // A bad edit is logged and the last good version kept.
+//! fs::write(&path, "fast").unwrap();
+//! assert!(!speed.file_changed(&path));
+//! assert_eq!(*speed.get(), 2.5);
+//! # fs::remove_dir_all(&root).unwrap();
+//! # Ok::<(), Error>(())
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use tracing::{error, info};
+
+use crate::error::Error;
+
+type Loader<T> = Box<dyn FnMut(&Path) -> Result<T, Error>>;
+
+/// A value loaded from a file, along with what it takes to load it again.
+pub struct Reloadable<T> {
+    value: T,
+    /// As given, to load and show.
+    path: PathBuf,
+    /// The path and dependencies resolved, to compare changes with.
+    resolved: PathBuf,
+    dependencies: Vec<PathBuf>,
+    load: Loader<T>,
+}
+
+impl<T> Reloadable<T> {
+    /// Loads `path` with `load`, failing if that does.
+    pub fn new<P, F>(path: P, mut load: F) -> Result<Self, Error>
+    where
+        P: Into<PathBuf>,
+        F: FnMut(&Path) -> Result<T, Error> + 'static,
+    {
+        let path = path.into();
+        let value = load(&path)?;
+        Ok(Self {
+            value,
+            resolved: resolve(&path),
+            path,
+            dependencies: Vec::new(),
+            load: Box::new(load),
+        })
+    }
+
+    /// Also reloads when a file at or under `path` changes, e.g. the directory of the headers a
+    /// shader includes.
+    pub fn depends_on<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.dependencies.push(resolve(&path.into()));
+        self
+    }
+
+    /// The latest version that loaded.
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reloads if `changed` is the file or one of its dependencies, returning whether a new
+    /// version was swapped in. A version that fails to load is logged and the previous one kept.
+    ///
+    /// note: paths are resolved before they're compared, so `changed` can be relative to another
+    /// directory than the file's path, or go through links. A removed file is resolved through its
+    /// directory.
+    pub fn file_changed(&mut self, changed: &Path) -> bool {
+        let changed = resolve(changed);
+        let watched = changed == self.resolved
+            || self
+                .dependencies
+                .iter()
+                .any(|dependency| changed.starts_with(dependency));
+        if !watched {
+            return false;
+        }
+
+        match self.reload() {
+            Ok(()) => {
+                info!("reloaded {}", self.path.display());
+                true
+            }
+            Err(err) => {
+                error!(
+                    "failed to reload {}, keeping the previous version: {}",
+                    self.path.display(),
+                    err.full_message()
+                );
+                false
+            }
+        }
+    }
+
+    /// Loads the file again, keeping the previous version if it fails.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        self.value = (self.load)(&self.path)?;
+        Ok(())
+    }
+}
+
+/// `path` made absolute, with links, `.` and `..` resolved. If it doesn't exist, e.g. it was just
+/// removed, its directory is resolved instead, and failing that it's only made absolute.
+fn resolve(path: &Path) -> PathBuf {
+    if let Ok(resolved) = fs::canonicalize(path) {
+        return resolved;
+    }
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty());
+    let resolved = match (parent, path.file_name()) {
+        (Some(parent), Some(name)) => fs::canonicalize(parent).map(|parent| parent.join(name)),
+        (None, Some(name)) => fs::canonicalize(".").map(|dir| dir.join(name)),
+        _ => return std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+    };
+    resolved
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the temp dir, for a test's files.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("galleon-hot-reload-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        dir
+    }
+
+    fn load_text(path: &Path) -> Result<String, Error> {
+        fs::read_to_string(path).map_err(|err| Error::new("unreadable").with_source(err))
+    }
+
+    #[test]
+    fn matches_paths_spelled_differently() {
+        let root = test_dir("spelling");
+        let path = root.join("sub").join("..").join("a.txt");
+        fs::write(&path, "1").unwrap();
+        let mut text = Reloadable::new(&path, load_text).unwrap();
+
+        fs::write(&path, "2").unwrap();
+        assert!(text.file_changed(&root.join("a.txt")));
+        assert_eq!(text.get(), "2");
+        assert_eq!(text.path(), path);
+
+        fs::write(&path, "3").unwrap();
+        assert!(text.file_changed(&root.join(".").join("sub").join("..").join("a.txt")));
+        assert_eq!(text.get(), "3");
+        assert!(!text.file_changed(&root.join("sub").join("a.txt")));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn matches_dependencies_spelled_differently() {
+        let root = test_dir("dependencies");
+        fs::write(root.join("a.txt"), "1").unwrap();
+        let mut text = Reloadable::new(root.join("a.txt"), load_text)
+            .unwrap()
+            .depends_on(root.join("sub").join(".."));
+
+        fs::write(root.join("sub").join("b.txt"), "").unwrap();
+        fs::write(root.join("a.txt"), "2").unwrap();
+        assert!(text.file_changed(&root.join("sub").join("b.txt")));
+        assert_eq!(text.get(), "2");
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn matches_removed_files() {
+        let root = test_dir("removed");
+        let path = root.join("sub").join("a.txt");
+        fs::write(&path, "1").unwrap();
+        let loads = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = loads.clone();
+        let mut text = Reloadable::new(&path, move |path| {
+            counter.set(counter.get() + 1);
+            load_text(path)
+        })
+        .unwrap();
+
+        fs::remove_file(&path).unwrap();
+        // It's tried again, but fails to load, so the last version is kept.
+        assert!(!text.file_changed(&root.join("sub").join("..").join("sub").join("a.txt")));
+        assert_eq!(loads.get(), 2);
+        assert_eq!(text.get(), "1");
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
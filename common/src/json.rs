@@ -0,0 +1,27 @@
+use std::fmt::Write;
+
+/// Appends `value` to `out` as a quoted JSON string.
+pub(crate) fn write_str(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => _ = write!(out, "\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Appends `value` to `out` as a JSON number, or `null` if it is not finite.
+pub(crate) fn write_f64(out: &mut String, value: f64) {
+    if value.is_finite() {
+        _ = write!(out, "{value}");
+    } else {
+        out.push_str("null");
+    }
+}
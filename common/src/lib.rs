@@ -1,5 +1,17 @@
 pub mod error;
+pub mod graphics;
+pub mod hot_reload;
 pub mod log;
+pub mod metrics;
+pub mod sprite;
+pub mod surface;
+pub mod text;
+pub mod verify;
+pub mod zip;
+
+mod gzip;
+mod json;
+mod macros;
 
 pub fn greet(who: &str) -> String {
     format!("Ahoy, {who}!")
@@ -1,172 +1,738 @@
 use std::{
-    any::TypeId,
-    collections::HashMap,
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
     fmt::{Display, Write},
-    sync::{Arc, Mutex, OnceLock},
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use tracing::{
-    error, field::Visit, level_filters::LevelFilter, subscriber::SetGlobalDefaultError, Level,
+    field::Visit,
+    span::{Attributes, Id},
+    subscriber::{DefaultGuard, SetGlobalDefaultError},
     Subscriber,
 };
 use tracing_subscriber::{
-    layer::SubscriberExt,
+    filter::Targets,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
     reload::{self, Handle},
     Layer, Registry,
 };
 
-use crate::error::Error;
+use crate::error::{self, Error};
 
-// note: this does not currently handle spans. see https://burgers.io/custom-logging-in-rust-using-tracing-part-2
+use self::{
+    context::append_context,
+    dedup::Dedup,
+    format::{Formatter, Utf16Line},
+    health::HealthState,
+    pipeline::Pipeline,
+    sampling::{SampleRule, Sampler},
+    startup::StartupBuffer,
+};
+
+pub use self::{
+    builder::{Logger, LoggerBuilder, LoggerGuard},
+    config::{apply_config_file, configure_from_file, ConfigWatcher},
+    context::{push_context, ContextGuard},
+    emergency::emergency_flush,
+    health::SinkHealth,
+    panic::install_panic_hook,
+    pipeline::{AsyncConfig, OverflowPolicy},
+    record::{Field, FieldValue, LogRecord, SpanRecord},
+    redact::Redactor,
+    sink_config::SinkConfig,
+};
+
+// The logging macros and level types, so crates can log through this module without depending on
+// `tracing` directly.
+pub use tracing::{
+    debug, debug_span, error, error_span, info, info_span, level_filters::LevelFilter, trace,
+    trace_span, warn, warn_span, Level,
+};
+
+pub mod binary;
+pub mod format;
+pub mod rate_limit;
+pub mod sinks;
+pub mod testing;
+pub mod timestamp;
+
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+mod builder;
+mod config;
+mod context;
+mod dedup;
+mod emergency;
+mod fallback;
+mod health;
+mod panic;
+mod pipeline;
+mod record;
+mod redact;
+mod sampling;
+mod sink_config;
+mod startup;
+
+// note: spans are forwarded to sinks on enter and exit, but their fields are not attached to the
+// events recorded inside them. see https://burgers.io/custom-logging-in-rust-using-tracing-part-2
+
+static LOGGER: OnceLock<LoggerState> = OnceLock::new();
+static NEXT_SINK_ID: AtomicU64 = AtomicU64::new(0);
+const WORKER_THREAD_NAME: &str = "galleon-logger";
+
+/// Identifies a sink registered with [`add_sink`] so the same instance can later be removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SinkId(u64);
+
+impl SinkId {
+    pub(crate) fn next() -> Self {
+        Self(NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Event buffers larger than this are dropped rather than kept for reuse.
+const MAX_RETAINED_MESSAGE: usize = 4096;
+
+thread_local! {
+    static DELIVERING: Cell<bool> = const { Cell::new(false) };
 
-static LOGGER: OnceLock<Logger> = OnceLock::new();
+    /// Message and field buffers reused between events on the same thread when records are
+    /// delivered synchronously. Empty while an event is being visited, so a sink that logs gets
+    /// fresh buffers.
+    static EVENT_BUFFERS: Cell<Option<RecordVisitor>> = const { Cell::new(None) };
+
+    /// Shared by every sink that asks for UTF-16 lines, see [`Sink::utf16_formatter`].
+    static UTF16_LINE: RefCell<Utf16Line> = RefCell::new(Utf16Line::new());
+
+    /// Loggers started with `LoggerBuilder::init_scoped` on this thread, innermost last.
+    static SCOPED: RefCell<Vec<LoggerState>> = const { RefCell::new(Vec::new()) };
+}
 
 #[derive(Clone)]
-struct Logger {
-    inner: Arc<Mutex<LoggerInner>>,
+struct LoggerState {
+    inner: Arc<LoggerInner>,
+    pipeline: Option<Arc<Pipeline>>,
 }
 
 struct LoggerInner {
-    reload_handle: Option<Handle<LevelFilter, Registry>>,
-    sinks: HashMap<TypeId, Box<dyn Sink>>,
+    reload_handle: Option<Handle<Targets, Registry>>,
+    // note: copy-on-write, so logging only holds the lock long enough to clone the `Arc` and sinks
+    // are called with no lock held. Adding or reconfiguring a sink copies the map.
+    sinks: RwLock<Arc<SinkMap>>,
+    dedup: Mutex<Option<Dedup>>,
+    dedup_enabled: AtomicBool,
+    redactor: RwLock<Option<Arc<Redactor>>>,
+    sampler: Sampler,
+    /// Set once the first sink is added, which retires the fallback output and startup buffer.
+    had_sink: AtomicBool,
+    startup: Mutex<StartupBuffer>,
+    /// The sinks named by the last config file applied, so the next can restore any it drops.
+    configured_sinks: Mutex<Vec<String>>,
+}
+
+type SinkMap = HashMap<SinkId, SinkEntry>;
+
+impl LoggerInner {
+    fn sinks(&self) -> Arc<SinkMap> {
+        self.sinks.read().unwrap().clone()
+    }
+
+    fn update_sinks<F: FnOnce(&mut SinkMap)>(&self, update: F) {
+        let mut sinks = self.sinks.write().unwrap();
+        update(Arc::make_mut(&mut sinks));
+    }
+
+    /// Calls `f` for every sink, marking this thread as inside a sink for the panic hook. Returns
+    /// whether any sink is registered.
+    fn for_each_sink<F: FnMut(&SinkEntry)>(&self, mut f: F) -> bool {
+        let sinks = self.sinks();
+        let delivering = DELIVERING.replace(true);
+        for entry in sinks.values().filter(|entry| !entry.health.is_disabled()) {
+            f(entry);
+        }
+        DELIVERING.set(delivering);
+
+        !sinks.is_empty()
+    }
+
+    fn deliver(&self, record: &LogRecord) {
+        let mut disabled = Vec::new();
+        let any_sinks = self.for_each_sink(|entry| {
+            if entry.config.accepts(record) && entry.sink.enabled(&record.level) {
+                disabled.extend(entry.report(log_to_sink(&*entry.sink, record)));
+            }
+        });
+        if !any_sinks {
+            self.deliver_without_sinks(record);
+        }
+
+        // note: the disabled sink is skipped, so this can't recurse more than once per sink.
+        for notice in disabled {
+            self.deliver(&LogRecord::new(Level::WARN, module_path!(), notice));
+        }
+    }
+
+    fn deliver_without_sinks(&self, record: &LogRecord) {
+        if self.had_sink.load(Ordering::Acquire) {
+            fallback::warn_dropped();
+            return;
+        }
+
+        let mut startup = self.startup.lock().unwrap();
+        if self.had_sink.load(Ordering::Acquire) {
+            // The first sink was added since this record missed it.
+            drop(startup);
+            self.deliver(record);
+            return;
+        }
+        startup.push(record);
+        drop(startup);
+
+        fallback::log(record);
+    }
+}
+
+#[derive(Clone)]
+struct SinkEntry {
+    sink: Arc<dyn Sink>,
+    config: SinkConfig,
+    health: Arc<HealthState>,
+    /// Whether the sink is enabled and its level as set in code. A config file overrides them,
+    /// and they're restored once it stops naming the sink.
+    defaults: (bool, LevelFilter),
 }
 
-unsafe impl Send for LoggerInner {}
-unsafe impl Sync for LoggerInner {}
+impl SinkEntry {
+    fn report(&self, result: Result<(), Error>) -> Option<String> {
+        self.health.record(result, self.config.disable_after)
+    }
+}
 
-impl Logger {
-    fn new(reload_handle: Handle<LevelFilter, Registry>) -> Self {
+impl LoggerState {
+    fn new(reload_handle: Handle<Targets, Registry>, pipeline: Option<Arc<Pipeline>>) -> Self {
         let inner = LoggerInner {
             reload_handle: Some(reload_handle),
-            sinks: HashMap::new(),
+            sinks: RwLock::new(Arc::new(HashMap::new())),
+            dedup: Mutex::new(None),
+            dedup_enabled: AtomicBool::new(false),
+            redactor: RwLock::new(None),
+            sampler: Sampler::new(),
+            had_sink: AtomicBool::new(false),
+            startup: Mutex::new(StartupBuffer::new(startup::DEFAULT_CAPACITY)),
+            configured_sinks: Mutex::new(Vec::new()),
         };
 
         Self {
-            inner: Arc::new(Mutex::new(inner)),
+            inner: Arc::new(inner),
+            pipeline,
+        }
+    }
+
+    fn spawn_worker(&self) -> Result<(), LoggerError> {
+        if let Some(pipeline) = self.pipeline.as_ref() {
+            let logger = self.clone();
+            let worker = thread::Builder::new()
+                .name(WORKER_THREAD_NAME.to_string())
+                .spawn(move || logger.run_worker())
+                .map_err(|_| LoggerError::WorkerSpawnFailed)?;
+            pipeline.set_worker(worker);
+        }
+
+        Ok(())
+    }
+
+    fn is_worker_thread() -> bool {
+        thread::current().name() == Some(WORKER_THREAD_NAME)
+    }
+
+    /// Whether this thread is currently inside a sink, which may be holding its own locks.
+    fn is_delivering() -> bool {
+        DELIVERING.get()
+    }
+
+    fn run_worker(&self) {
+        if let Some(pipeline) = self.pipeline.as_ref() {
+            while let Some(batch) = pipeline.pop_all() {
+                for record in batch {
+                    self.dispatch(&record);
+                }
+            }
         }
     }
 
     fn set_max_level(&self, level: LevelFilter) {
-        if let Some(reload_handle) = self.inner.lock().unwrap().reload_handle.as_mut() {
-            if let Err(err) = reload_handle.modify(|max_level| *max_level = level) {
+        if let Some(reload_handle) = self.inner.reload_handle.as_ref() {
+            if let Err(err) = reload_handle.modify(|targets| {
+                *targets = std::mem::take(targets).with_default(level);
+            }) {
                 error!("failed to set max logging level: {err}");
             }
         }
     }
 
-    fn add_sink<S: Sink + Clone + 'static>(&self, sink: &S) {
+    fn set_directives(&self, directives: Targets) {
+        if let Some(reload_handle) = self.inner.reload_handle.as_ref() {
+            if let Err(err) = reload_handle.reload(directives) {
+                error!("failed to set logging directives: {err}");
+            }
+        }
+    }
+
+    fn default_level(&self) -> Option<LevelFilter> {
         self.inner
-            .lock()
-            .unwrap()
-            .sinks
-            .insert(TypeId::of::<S>(), Box::new(sink.clone()));
+            .reload_handle
+            .as_ref()?
+            .with_current(|targets| targets.default_level())
+            .ok()
+            .flatten()
     }
 
-    fn remove_sink<S: Sink + Clone + 'static>(&self, _sink: &S) {
-        self.inner.lock().unwrap().sinks.remove(&TypeId::of::<S>());
+    fn would_log(&self, target: &str, level: &Level) -> bool {
+        self.inner
+            .reload_handle
+            .as_ref()
+            .and_then(|handle| {
+                handle
+                    .with_current(|targets| targets.would_enable(target, level))
+                    .ok()
+            })
+            .unwrap_or(true)
     }
 
-    fn log(
-        &self,
-        level: &Level,
-        msg: &str,
-        args: Option<&str>,
-        file: Option<&str>,
-        line: Option<u32>,
-    ) {
-        for sink in self.inner.lock().unwrap().sinks.values() {
-            if sink.enabled(level) {
-                sink.log(level, msg, args, file, line);
+    fn add_sink(&self, id: SinkId, sink: Arc<dyn Sink>, config: SinkConfig) {
+        let entry = SinkEntry {
+            health: Arc::new(HealthState::new(id, config.name.as_deref())),
+            defaults: (config.enabled, config.level),
+            sink,
+            config,
+        };
+
+        // note: holding the startup buffer while the sink is added means a record logged
+        // concurrently is either buffered before the replay below or sees the new sink.
+        let startup = self.inner.startup.lock().unwrap();
+        self.inner.update_sinks(|sinks| {
+            sinks.insert(id, entry.clone());
+        });
+        self.inner.had_sink.store(true, Ordering::Release);
+
+        if startup.records().is_empty() {
+            return;
+        }
+        let missed = startup.missed();
+        let missed = (missed > 0).then(|| {
+            LogRecord::new(
+                Level::WARN,
+                module_path!(),
+                format!("{missed} records from before the first sink was added were dropped"),
+            )
+        });
+
+        let delivering = DELIVERING.replace(true);
+        for record in startup.records().iter().chain(missed.as_ref()) {
+            if entry.config.accepts(record) && entry.sink.enabled(&record.level) {
+                _ = entry.report(log_to_sink(&*entry.sink, record));
+            }
+        }
+        DELIVERING.set(delivering);
+    }
+
+    fn set_startup_buffer(&self, capacity: usize) {
+        self.inner.startup.lock().unwrap().set_capacity(capacity);
+    }
+
+    fn set_sink_level(&self, id: SinkId, level: LevelFilter) {
+        self.inner.update_sinks(|sinks| {
+            if let Some(entry) = sinks.get_mut(&id) {
+                entry.config.level = level;
+                entry.defaults.1 = level;
+            }
+        });
+    }
+
+    /// Overrides whether the sinks called `name` are enabled and their level. `None` restores
+    /// what was set in code.
+    fn configure_named_sink(&self, name: &str, enabled: Option<bool>, level: Option<LevelFilter>) {
+        self.inner.update_sinks(|sinks| {
+            for entry in sinks.values_mut() {
+                if entry.config.name.as_deref() == Some(name) {
+                    entry.config.enabled = enabled.unwrap_or(entry.defaults.0);
+                    entry.config.level = level.unwrap_or(entry.defaults.1);
+                }
             }
+        });
+    }
+
+    fn remove_sink(&self, id: SinkId) {
+        self.inner.update_sinks(|sinks| {
+            sinks.remove(&id);
+        });
+    }
+
+    fn redactor(&self) -> Option<Arc<Redactor>> {
+        self.inner.redactor.read().unwrap().clone()
+    }
+
+    fn set_redactor(&self, redactor: Option<Redactor>) {
+        *self.inner.redactor.write().unwrap() = redactor.map(Arc::new);
+    }
+
+    fn log(&self, record: LogRecord) {
+        match self.pipeline.as_ref() {
+            Some(pipeline) => {
+                if let Some(record) = pipeline.push(record) {
+                    self.dispatch(&record);
+                }
+            }
+            None => self.dispatch(&record),
         }
     }
 
+    fn dispatch(&self, record: &LogRecord) {
+        if !self.inner.dedup_enabled.load(Ordering::Acquire) {
+            self.inner.deliver(record);
+            return;
+        }
+
+        // note: the lock is released before delivering, so a slow sink doesn't stall every
+        // thread that logs. Records from different threads may then reach sinks out of order.
+        let (summary, emit) = match self.inner.dedup.lock().unwrap().as_mut() {
+            Some(dedup) => dedup.observe(record),
+            None => (None, true),
+        };
+
+        if let Some(summary) = summary {
+            self.inner.deliver(&summary);
+        }
+        if emit {
+            self.inner.deliver(record);
+        }
+    }
+
+    fn set_dedup_window(&self, window: Option<Duration>) {
+        let summary = {
+            let mut dedup = self.inner.dedup.lock().unwrap();
+            let summary = dedup.as_mut().and_then(Dedup::take_summary);
+            *dedup = window.map(Dedup::new);
+            self.inner
+                .dedup_enabled
+                .store(dedup.is_some(), Ordering::Release);
+            summary
+        };
+        if let Some(summary) = summary {
+            self.inner.deliver(&summary);
+        }
+    }
+
+    fn span_enter(&self, span: &SpanRecord) {
+        self.inner.for_each_sink(|entry| {
+            if entry.config.accepts_span(span) && entry.sink.enabled(&span.level) {
+                entry.sink.span_enter(span);
+            }
+        });
+    }
+
+    fn span_exit(&self, span: &SpanRecord) {
+        self.inner.for_each_sink(|entry| {
+            if entry.config.accepts_span(span) && entry.sink.enabled(&span.level) {
+                entry.sink.span_exit(span);
+            }
+        });
+    }
+
+    fn frame_mark(&self) {
+        self.inner.for_each_sink(|entry| {
+            if entry.config.enabled {
+                entry.sink.frame_mark();
+            }
+        });
+    }
+
+    fn plot(&self, name: &'static str, value: f64) {
+        self.inner.for_each_sink(|entry| {
+            if entry.config.enabled {
+                entry.sink.plot(name, value);
+            }
+        });
+    }
+
     fn clear(&self) {
-        let mut inner = self.inner.lock().unwrap();
-        inner.sinks.clear();
-        inner.sinks.shrink_to(0);
+        *self.inner.sinks.write().unwrap() = Arc::new(HashMap::new());
+    }
+
+    fn shutdown(&self) {
+        if let Some(pipeline) = self.pipeline.as_ref() {
+            pipeline.close();
+            let dropped = pipeline.dropped();
+            if dropped > 0 {
+                self.dispatch(&LogRecord::new(
+                    Level::WARN,
+                    module_path!(),
+                    format!("async logger dropped {dropped} records"),
+                ));
+            }
+        }
+
+        self.flush();
+        self.clear();
+        self.set_max_level(LevelFilter::OFF);
     }
 
     fn flush(&self) {
-        for sink in self.inner.lock().unwrap().sinks.values() {
-            sink.flush();
+        if let Some(pipeline) = self.pipeline.as_ref() {
+            pipeline.wait_idle();
+        }
+
+        let summary = self
+            .inner
+            .dedup
+            .lock()
+            .unwrap()
+            .as_mut()
+            .and_then(Dedup::take_summary);
+        if let Some(summary) = summary {
+            self.inner.deliver(&summary);
+        }
+
+        let mut disabled = Vec::new();
+        self.inner
+            .for_each_sink(|entry| disabled.extend(entry.report(entry.sink.flush())));
+        for notice in disabled {
+            self.inner
+                .deliver(&LogRecord::new(Level::WARN, module_path!(), notice));
+        }
+    }
+
+    fn sink_health(&self) -> Vec<SinkHealth> {
+        let mut health: Vec<_> = self
+            .inner
+            .sinks()
+            .iter()
+            .map(|(id, entry)| entry.health.snapshot(*id, entry.config.name.clone()))
+            .collect();
+        health.sort_by_key(|health| health.id);
+        health
+    }
+
+    fn reset_sink_health(&self, id: SinkId) {
+        if let Some(entry) = self.inner.sinks().get(&id) {
+            entry.health.reset();
         }
     }
 }
 
-impl<S> Layer<S> for Logger
+/// Span fields captured when the span is created, stored in the registry's span extensions.
+struct SpanFields(Arc<Vec<Field>>);
+
+impl LoggerState {
+    fn span_record<S>(&self, id: &Id, ctx: &Context<'_, S>) -> Option<SpanRecord>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        let span = ctx.span(id)?;
+        let metadata = span.metadata();
+        let fields = span
+            .extensions()
+            .get::<SpanFields>()
+            .map(|fields| fields.0.clone())
+            .unwrap_or_default();
+
+        Some(SpanRecord {
+            id: id.into_u64(),
+            name: metadata.name(),
+            level: *metadata.level(),
+            target: metadata.target(),
+            timestamp: Instant::now(),
+            fields,
+            file: metadata.file(),
+            line: metadata.line(),
+            thread_id: thread::current().id(),
+        })
+    }
+}
+
+impl<S> Layer<S> for LoggerState
 where
-    S: Subscriber,
+    S: Subscriber + for<'a> LookupSpan<'a>,
 {
-    fn on_event(
-        &self,
-        event: &tracing::Event<'_>,
-        _ctx: tracing_subscriber::layer::Context<'_, S>,
-    ) {
-        let mut visitor = StringVisitor::default(); // note: could use a fixed size buffer here.
-        event.record(&mut visitor);
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = RecordVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(redactor) = self.redactor() {
+            redactor.redact_fields(&mut visitor.fields);
+        }
 
-        let level = event.metadata().level();
-        let file = event.metadata().file();
-        let line = event.metadata().line();
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut()
+                .insert(SpanFields(Arc::new(visitor.fields)));
+        }
+    }
 
-        let msg = &visitor.msg;
-        let args = if visitor.args.is_empty() {
-            None
-        } else {
-            Some(visitor.args.as_str())
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = self.span_record(id, &ctx) {
+            self.span_enter(&span);
+        }
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = self.span_record(id, &ctx) {
+            self.span_exit(&span);
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // Bridged `log` records only know their real target once visited.
+        let bridged = metadata.target() == "log";
+        let sampler = &self.inner.sampler;
+        if !bridged
+            && !sampler.keep(
+                metadata.target(),
+                metadata.level(),
+                (metadata as *const _ as usize, 0),
+            )
+        {
+            return;
+        }
+
+        // Queued records must own their buffers, so only the synchronous path reuses them.
+        let mut visitor = match self.pipeline {
+            Some(_) => RecordVisitor::default(),
+            None => EVENT_BUFFERS
+                .try_with(Cell::take)
+                .ok()
+                .flatten()
+                .unwrap_or_default(),
+        };
+        event.record(&mut visitor);
+        append_context(&mut visitor.fields);
+
+        let mut record = LogRecord {
+            fields: visitor.fields,
+            file: visitor.log_file.or(metadata.file()),
+            line: visitor.log_line.or(metadata.line()),
+            ..LogRecord::new(
+                *metadata.level(),
+                visitor.log_target.unwrap_or(metadata.target()),
+                visitor.message,
+            )
         };
+        if bridged
+            && sampler.is_enabled()
+            && !sampler.keep(
+                record.target,
+                &record.level,
+                (record.target.as_ptr() as usize, record.line.unwrap_or(0)),
+            )
+        {
+            if self.pipeline.is_none() {
+                recycle_buffers(record);
+            }
+            return;
+        }
+
+        // note: redacted before queueing, so unredacted values never wait in the pipeline.
+        if let Some(redactor) = self.redactor() {
+            redactor.redact(&mut record);
+        }
+
+        if self.pipeline.is_some() {
+            self.log(record);
+            return;
+        }
+
+        self.dispatch(&record);
+        recycle_buffers(record);
+    }
+}
 
-        self.log(level, msg, args, file, line);
+/// Hands a synchronously delivered record's buffers back for the next event on this thread.
+fn recycle_buffers(record: LogRecord) {
+    let LogRecord {
+        mut message,
+        mut fields,
+        ..
+    } = record;
+    if message.capacity() <= MAX_RETAINED_MESSAGE {
+        message.clear();
+        fields.clear();
+        _ = EVENT_BUFFERS.try_with(|buffers| {
+            buffers.set(Some(RecordVisitor {
+                message,
+                fields,
+                ..Default::default()
+            }))
+        });
     }
 }
 
 #[derive(Default)]
-struct StringVisitor {
-    msg: String,
-    args: String,
+struct RecordVisitor {
+    message: String,
+    fields: Vec<Field>,
+    // Records bridged from the `log` crate carry their call site as fields.
+    log_target: Option<&'static str>,
+    log_file: Option<&'static str>,
+    log_line: Option<u32>,
 }
 
-impl StringVisitor {
-    fn record_display(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Display) {
+impl RecordVisitor {
+    fn record_value(&mut self, field: &tracing::field::Field, value: FieldValue) {
         if field.name() == "message" {
-            _ = write!(&mut self.msg, "{}", value);
+            _ = write!(&mut self.message, "{}", value);
         } else {
-            if !self.args.is_empty() {
-                _ = write!(&mut self.args, " ");
-            }
-            _ = write!(&mut self.args, "{}={}", field.name(), value);
+            self.fields.push(Field {
+                name: field.name(),
+                value,
+            });
         }
     }
 }
 
-impl Visit for StringVisitor {
+impl Visit for RecordVisitor {
     fn record_f64(&mut self, field: &tracing::field::Field, value: f64) {
-        self.record_display(field, &value)
+        self.record_value(field, FieldValue::F64(value))
     }
 
     fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
-        self.record_display(field, &value)
+        self.record_value(field, FieldValue::I64(value))
     }
 
     fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
-        self.record_display(field, &value)
+        if field.name() == "log.line" {
+            self.log_line = Some(value as u32);
+            return;
+        }
+        self.record_value(field, FieldValue::U64(value))
     }
 
     fn record_i128(&mut self, field: &tracing::field::Field, value: i128) {
-        self.record_display(field, &value)
+        self.record_value(field, FieldValue::I128(value))
     }
 
     fn record_u128(&mut self, field: &tracing::field::Field, value: u128) {
-        self.record_display(field, &value)
+        self.record_value(field, FieldValue::U128(value))
     }
 
     fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
-        self.record_display(field, &value)
+        self.record_value(field, FieldValue::Bool(value))
     }
 
     fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
-        self.record_display(field, &value)
+        match field.name() {
+            "log.target" => self.log_target = Some(intern(value)),
+            "log.file" => self.log_file = Some(intern(value)),
+            "log.module_path" => {}
+            _ => self.record_value(field, FieldValue::Str(value.to_string())),
+        }
     }
 
     fn record_error(
@@ -174,83 +740,394 @@ impl Visit for StringVisitor {
         field: &tracing::field::Field,
         value: &(dyn std::error::Error + 'static),
     ) {
-        self.record_display(field, &tracing::field::display(value))
+        // note: the whole chain, since the top-level message often only says what was being done.
+        self.record_value(field, FieldValue::Error(error::full_message(value)))
     }
 
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        // The message is usually `format_args!`, so write it straight into the reused buffer.
         if field.name() == "message" {
-            _ = write!(&mut self.msg, "{:?}", value);
+            _ = write!(&mut self.message, "{:?}", value);
         } else {
-            if !self.args.is_empty() {
-                _ = write!(&mut self.args, " ");
-            }
-            _ = write!(&mut self.args, "{}={:?}", field.name(), value);
+            self.record_value(field, FieldValue::Debug(format!("{:?}", value)))
         }
     }
 }
 
-pub trait Sink {
-    fn enabled(&self, level: &Level) -> bool;
+/// Targets and files of records bridged from the `log` crate are only borrowed for the call, so
+/// each distinct one is leaked once to fit the `'static` strings of `LogRecord`.
+pub(crate) fn intern(s: &str) -> &'static str {
+    static STRINGS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+    let mut strings = STRINGS.get_or_init(Default::default).lock().unwrap();
+    if let Some(s) = strings.get(s) {
+        return s;
+    }
+
+    let s: &'static str = Box::leak(s.into());
+    strings.insert(s);
+    s
+}
+
+/// Sends `record` to `sink`, formatting it into this thread's UTF-16 buffer if the sink asks for
+/// one.
+pub(crate) fn log_to_sink(sink: &dyn Sink, record: &LogRecord) -> Result<(), Error> {
+    let Some(formatter) = sink.utf16_formatter() else {
+        return sink.log(record);
+    };
+
+    UTF16_LINE.with(|line| match line.try_borrow_mut() {
+        Ok(mut line) => sink.log_utf16(record, line.format(formatter, record)),
+        // note: a sink that logs from inside `log_utf16` gets a buffer of its own.
+        Err(_) => sink.log_utf16(record, Utf16Line::new().format(formatter, record)),
+    })
+}
+
+/// A destination for log records. Sinks are called from whichever thread logs, and from the
+/// worker thread when logging asynchronously, so they must be `Send + Sync`:
+///
+/// ```compile_fail
+/// use std::{cell::RefCell, rc::Rc};
+/// use common::{error::Error, log::{LogRecord, Sink}};
+///
+/// #[derive(Clone)]
+/// struct RcSink(Rc<RefCell<Vec<String>>>);
+///
+/// impl Sink for RcSink {
+///     fn log(&self, record: &LogRecord) -> Result<(), Error> {
+///         self.0.borrow_mut().push(record.message.clone());
+///         Ok(())
+///     }
+///
+///     fn flush(&self) -> Result<(), Error> {
+///         Ok(())
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use std::cell::Cell;
+/// use common::{error::Error, log::{LogRecord, Sink}};
+///
+/// #[derive(Clone, Default)]
+/// struct CellSink(Cell<u64>);
+///
+/// impl Sink for CellSink {
+///     fn log(&self, _record: &LogRecord) -> Result<(), Error> {
+///         self.0.set(self.0.get() + 1);
+///         Ok(())
+///     }
+///
+///     fn flush(&self) -> Result<(), Error> {
+///         Ok(())
+///     }
+/// }
+///
+/// common::log::add_sink(&CellSink::default());
+/// ```
+pub trait Sink: Send + Sync {
+    /// Lets a sink reject levels it never handles. Per-registration filtering belongs in
+    /// [`SinkConfig`] instead.
+    fn enabled(&self, _level: &Level) -> bool {
+        true
+    }
+
+    /// Errors are counted per sink, see [`sink_health`], and can disable a persistently failing
+    /// sink with [`SinkConfig::disable_after`].
+    fn log(&self, record: &LogRecord) -> Result<(), Error>;
 
-    fn log(
-        &self,
-        level: &Level,
-        msg: &str,
-        args: Option<&str>,
-        file: Option<&str>,
-        line: Option<u32>,
-    );
+    /// Sinks that pass text to wide-string APIs return their formatter here to have records
+    /// delivered through [`log_utf16`](Sink::log_utf16) instead of [`log`](Sink::log), formatted
+    /// into a UTF-16 buffer the logger reuses between records.
+    fn utf16_formatter(&self) -> Option<&dyn Formatter> {
+        None
+    }
+
+    /// Receives `line`, the record formatted by [`utf16_formatter`](Sink::utf16_formatter) with no
+    /// newline or nul terminator. Sinks may append to it, e.g. to terminate it in place.
+    fn log_utf16(&self, record: &LogRecord, _line: &mut Vec<u16>) -> Result<(), Error> {
+        self.log(record)
+    }
+
+    /// Called synchronously when a span is entered, even when the logger runs asynchronously.
+    fn span_enter(&self, _span: &SpanRecord) {}
 
-    fn flush(&self);
+    /// Called synchronously when a span is exited, even when the logger runs asynchronously.
+    fn span_exit(&self, _span: &SpanRecord) {}
+
+    /// Called by [`frame_mark`] at the end of every frame, for profilers that group work by
+    /// frame.
+    fn frame_mark(&self) {}
+
+    /// Called by [`plot`] with a sampled value, e.g. to graph memory use alongside spans.
+    fn plot(&self, _name: &'static str, _value: f64) {}
+
+    fn flush(&self) -> Result<(), Error>;
 }
 
 pub fn startup(max_level: LevelFilter) -> Result<(), LoggerError> {
-    let (max_level, reload_handle) = reload::Layer::new(max_level);
-    let logger = LOGGER.get_or_init(|| Logger::new(reload_handle));
+    init(Targets::new().with_default(max_level), None)
+}
+
+/// Starts the logger filtered by `RUST_LOG`-style directives, e.g.
+/// `galleon::renderer=trace,wgpu=warn,info`. A bare level sets the default for unmatched targets.
+pub fn startup_with_directives(directives: &str) -> Result<(), LoggerError> {
+    init(parse_directives(directives)?, None)
+}
+
+/// Starts the logger with directives from `GALLEON_LOG`, falling back to `RUST_LOG`, so verbosity
+/// can be changed without rebuilding. If neither is set the default level is `INFO`.
+pub fn startup_from_env() -> Result<(), LoggerError> {
+    match std::env::var("GALLEON_LOG").or_else(|_| std::env::var("RUST_LOG")) {
+        Ok(directives) if !directives.trim().is_empty() => startup_with_directives(&directives),
+        _ => startup(LevelFilter::INFO),
+    }
+}
+
+/// Starts the logger with sinks driven from a background thread, so logging call sites only pay
+/// for pushing a record onto a bounded queue. Queued records are drained by `shutdown()`.
+pub fn startup_async(max_level: LevelFilter, config: AsyncConfig) -> Result<(), LoggerError> {
+    init(
+        Targets::new().with_default(max_level),
+        Some(Arc::new(Pipeline::new(config))),
+    )
+}
+
+pub(crate) fn init(
+    directives: Targets,
+    pipeline: Option<Arc<Pipeline>>,
+) -> Result<(), LoggerError> {
+    if LOGGER.get().is_some() {
+        return Err(LoggerError::AlreadyInitialized);
+    }
+
+    let (directives, reload_handle) = reload::Layer::new(directives);
+    let logger = LOGGER.get_or_init(|| LoggerState::new(reload_handle, pipeline));
     let subscriber = tracing_subscriber::registry()
-        .with(max_level)
+        .with(directives)
         .with(logger.clone());
 
     tracing::subscriber::set_global_default(subscriber)?;
+    // note: this only fails if another `log` logger is installed, in which case `log` records
+    // keep going there.
+    _ = tracing_log::LogTracer::init();
+    logger.spawn_worker()?;
 
     Ok(())
 }
 
+/// Starts a logger that only receives events from the calling thread, see
+/// [`LoggerBuilder::init_scoped`].
+pub(crate) fn init_scoped(
+    directives: Targets,
+    pipeline: Option<Arc<Pipeline>>,
+) -> Result<ScopedLogger, LoggerError> {
+    let (directives, reload_handle) = reload::Layer::new(directives);
+    let logger = LoggerState::new(reload_handle, pipeline);
+    logger.spawn_worker()?;
+
+    let subscriber = tracing_subscriber::registry()
+        .with(directives)
+        .with(logger.clone());
+    let dispatch = tracing::subscriber::set_default(subscriber);
+    _ = tracing_log::LogTracer::init();
+    SCOPED.with(|scoped| scoped.borrow_mut().push(logger.clone()));
+
+    Ok(ScopedLogger {
+        logger,
+        _dispatch: dispatch,
+        _not_send: PhantomData,
+    })
+}
+
+/// A logger for the current thread only, e.g. one per test. Dropping it flushes and shuts it
+/// down, and the thread goes back to whichever logger was active before.
+#[must_use = "the logger shuts down as soon as it is dropped"]
+pub struct ScopedLogger {
+    logger: LoggerState,
+    _dispatch: DefaultGuard,
+    // Scoped loggers are tracked per thread, so must be dropped on the thread that started them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopedLogger {
+    fn drop(&mut self) {
+        self.logger.shutdown();
+        _ = SCOPED.try_with(|scoped| scoped.borrow_mut().pop());
+    }
+}
+
+/// The logger that calls on this thread apply to: the innermost scoped logger, if any, otherwise
+/// the global one.
+fn current() -> Option<LoggerState> {
+    SCOPED
+        .try_with(|scoped| scoped.borrow().last().cloned())
+        .ok()
+        .flatten()
+        .or_else(|| LOGGER.get().cloned())
+}
+
 pub fn shutdown() {
     if let Some(logger) = LOGGER.get() {
-        logger.flush();
-        logger.clear();
-        logger.set_max_level(LevelFilter::OFF);
+        sinks::span_stats::dump_all();
+        logger.shutdown();
     }
 }
 
-pub fn add_sink<S: Sink + Clone + 'static>(sink: &S) {
-    if let Some(logger) = LOGGER.get() {
-        logger.add_sink(sink);
+pub fn add_sink<S: Sink + Clone + 'static>(sink: &S) -> SinkId {
+    add_sink_with(sink, SinkConfig::new())
+}
+
+pub fn add_sink_with<S: Sink + Clone + 'static>(sink: &S, config: SinkConfig) -> SinkId {
+    let id = SinkId::next();
+    if let Some(logger) = current() {
+        logger.add_sink(id, Arc::new(sink.clone()), config);
     }
+
+    id
 }
 
-pub fn remove_sink<S: Sink + Clone + 'static>(sink: &S) {
-    if let Some(logger) = LOGGER.get() {
-        logger.remove_sink(sink);
+pub fn set_sink_level(id: SinkId, level: LevelFilter) {
+    if let Some(logger) = current() {
+        logger.set_sink_level(id, level);
+    }
+}
+
+pub fn remove_sink(id: SinkId) {
+    if let Some(logger) = current() {
+        logger.remove_sink(id);
     }
 }
 
 pub fn set_max_level(level: LevelFilter) {
-    if let Some(logger) = LOGGER.get() {
+    if let Some(logger) = current() {
         logger.set_max_level(level);
     }
 }
 
+/// Collapses identical consecutive records. Repeats within `window` of the last emitted copy are
+/// suppressed and reported as a single "last message repeated N times" record once the run ends,
+/// the window lapses or the logger is flushed. `None` turns deduplication off.
+pub fn set_dedup_window(window: Option<Duration>) {
+    if let Some(logger) = current() {
+        logger.set_dedup_window(window);
+    }
+}
+
+/// Error counts and status for every registered sink, in the order they were added.
+pub fn sink_health() -> Vec<SinkHealth> {
+    current()
+        .map(|logger| logger.sink_health())
+        .unwrap_or_default()
+}
+
+/// Clears a sink's consecutive error count and re-enables it if `SinkConfig::disable_after`
+/// disabled it.
+pub fn reset_sink_health(id: SinkId) {
+    if let Some(logger) = current() {
+        logger.reset_sink_health(id);
+    }
+}
+
+/// Marks the end of a frame for sinks that profile per frame, e.g. [`sinks::TraceExportSink`].
+/// Call it once per iteration of the main loop.
+pub fn frame_mark() {
+    if let Some(logger) = current() {
+        logger.frame_mark();
+    }
+}
+
+/// Records a sample of a named value, such as allocated bytes or entity count, for sinks that can
+/// graph it over time.
+pub fn plot(name: &'static str, value: f64) {
+    if let Some(logger) = current() {
+        logger.plot(name, value);
+    }
+}
+
+/// Keeps only one in every `keep_one_in` events at `level` or more verbose from `target` and its
+/// children, counted per call site, e.g. `set_sampling("renderer::submit", Level::TRACE, 100)`.
+/// The first event from each call site is always kept. A rate of 0 or 1 removes the rule.
+pub fn set_sampling(target: &str, level: Level, keep_one_in: u64) {
+    if let Some(logger) = current() {
+        logger.inner.sampler.set(SampleRule {
+            target: target.to_string(),
+            level,
+            keep_one_in,
+        });
+    }
+}
+
+/// Removes every sampling rule.
+pub fn clear_sampling() {
+    if let Some(logger) = current() {
+        logger.inner.sampler.replace(Vec::new());
+    }
+}
+
+/// Logs the table of span timings from every [`sinks::SpanStatsSink`] at INFO.
+pub fn dump_span_stats() {
+    sinks::span_stats::dump_all();
+}
+
+/// Delivers a record that didn't come through `tracing`, e.g. one forwarded from a child process.
+/// It's filtered by the current directives and redacted like any other record.
+pub fn submit(mut record: LogRecord) {
+    if let Some(logger) = current() {
+        if !logger.would_log(record.target, &record.level) {
+            return;
+        }
+        if let Some(redactor) = logger.redactor() {
+            redactor.redact(&mut record);
+        }
+        logger.log(record);
+    }
+}
+
+/// Masks sensitive fields in every record and span before they reach any sink. `None` turns
+/// redaction off.
+pub fn set_redactor(redactor: Option<Redactor>) {
+    if let Some(logger) = current() {
+        logger.set_redactor(redactor);
+    }
+}
+
+/// Replaces all per-target directives, including the default level.
+pub fn set_directives(directives: &str) -> Result<(), LoggerError> {
+    let directives = parse_directives(directives)?;
+    if let Some(logger) = current() {
+        logger.set_directives(directives);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn parse_directives(directives: &str) -> Result<Targets, LoggerError> {
+    if directives.trim().is_empty() {
+        return Ok(Targets::new());
+    }
+
+    directives
+        .trim()
+        .parse()
+        .map_err(|err: tracing_subscriber::filter::ParseError| {
+            LoggerError::InvalidDirectives(err.to_string())
+        })
+}
+
 #[derive(Debug)]
 pub enum LoggerError {
     AlreadyInitialized,
+    WorkerSpawnFailed,
+    InvalidDirectives(String),
 }
 
 impl Display for LoggerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LoggerError::AlreadyInitialized => write!(f, "logger is already initialized"),
+            LoggerError::WorkerSpawnFailed => write!(f, "failed to spawn logger worker thread"),
+            LoggerError::InvalidDirectives(err) => write!(f, "invalid logging directives: {err}"),
         }
     }
 }
@@ -0,0 +1,595 @@
+//! A compact binary encoding for log records.
+//!
+//! A stream starts with the magic bytes `GLOG` and a version byte, followed by frames. Each frame
+//! is a tag byte and a body:
+//!
+//! - `STRING`: `varint id, varint len, bytes` defines an interned string.
+//! - `RECORD`: a record whose strings are references to earlier definitions or inline bytes.
+//!
+//! String references are a varint `id << 1` for an interned string or `len << 1 | 1` followed by
+//! `len` bytes for an inline one. Targets, files, field names, thread names and messages are
+//! interned, up to a limit; field values are always inline.
+
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tracing::Level;
+
+use crate::{
+    error::{Error, ErrorKind},
+    log::{intern, Field, FieldValue, LogRecord},
+};
+
+const MAGIC: &[u8; 4] = b"GLOG";
+const VERSION: u8 = 1;
+
+const TAG_STRING: u8 = 1;
+const TAG_RECORD: u8 = 2;
+
+const VALUE_F64: u8 = 0;
+const VALUE_I64: u8 = 1;
+const VALUE_U64: u8 = 2;
+const VALUE_I128: u8 = 3;
+const VALUE_U128: u8 = 4;
+const VALUE_BOOL: u8 = 5;
+const VALUE_STR: u8 = 6;
+const VALUE_ERROR: u8 = 7;
+const VALUE_DEBUG: u8 = 8;
+
+/// Interning more strings than this stops, so that unbounded distinct messages can't grow the
+/// table forever; later strings are written inline.
+const DEFAULT_MAX_INTERNED: usize = 16 * 1024;
+
+pub struct BinaryEncoder {
+    strings: HashMap<String, u64>,
+    max_interned: usize,
+    body: Vec<u8>,
+}
+
+impl BinaryEncoder {
+    pub fn new() -> Self {
+        Self {
+            strings: HashMap::new(),
+            max_interned: DEFAULT_MAX_INTERNED,
+            body: Vec::with_capacity(256),
+        }
+    }
+
+    /// Writes the stream header. Call once before the first record.
+    pub fn header(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+    }
+
+    /// Appends `record` to `out`, preceded by definitions for any strings not yet interned.
+    pub fn encode(&mut self, record: &LogRecord, out: &mut Vec<u8>) {
+        let mut body = std::mem::take(&mut self.body);
+        body.clear();
+
+        let micros = record
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        write_varint(&mut body, micros);
+        body.push(level_to_u8(&record.level));
+        self.write_str(record.target, &mut body, out);
+        match record.file {
+            Some(file) => {
+                body.push(1);
+                self.write_str(file, &mut body, out);
+            }
+            None => body.push(0),
+        }
+        write_varint(&mut body, record.line.map_or(0, |line| u64::from(line) + 1));
+        self.write_str(&format!("{:?}", record.thread_id), &mut body, out);
+        self.write_str(&record.message, &mut body, out);
+
+        write_varint(&mut body, record.fields.len() as u64);
+        for field in &record.fields {
+            self.write_str(field.name, &mut body, out);
+            match &field.value {
+                FieldValue::F64(value) => {
+                    body.push(VALUE_F64);
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::I64(value) => {
+                    body.push(VALUE_I64);
+                    write_varint(&mut body, zigzag(*value));
+                }
+                FieldValue::U64(value) => {
+                    body.push(VALUE_U64);
+                    write_varint(&mut body, *value);
+                }
+                FieldValue::I128(value) => {
+                    body.push(VALUE_I128);
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::U128(value) => {
+                    body.push(VALUE_U128);
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+                FieldValue::Bool(value) => {
+                    body.push(VALUE_BOOL);
+                    body.push(u8::from(*value));
+                }
+                FieldValue::Str(value) => {
+                    body.push(VALUE_STR);
+                    write_inline(&mut body, value);
+                }
+                FieldValue::Error(value) => {
+                    body.push(VALUE_ERROR);
+                    write_inline(&mut body, value);
+                }
+                FieldValue::Debug(value) => {
+                    body.push(VALUE_DEBUG);
+                    write_inline(&mut body, value);
+                }
+            }
+        }
+
+        out.push(TAG_RECORD);
+        out.extend_from_slice(&body);
+        self.body = body;
+    }
+
+    fn write_str(&mut self, s: &str, body: &mut Vec<u8>, out: &mut Vec<u8>) {
+        if let Some(id) = self.strings.get(s) {
+            write_varint(body, id << 1);
+            return;
+        }
+
+        if self.strings.len() >= self.max_interned {
+            write_inline(body, s);
+            return;
+        }
+
+        let id = self.strings.len() as u64;
+        self.strings.insert(s.to_string(), id);
+        out.push(TAG_STRING);
+        write_varint(out, id);
+        write_varint(out, s.len() as u64);
+        out.extend_from_slice(s.as_bytes());
+
+        write_varint(body, id << 1);
+    }
+}
+
+impl Default for BinaryEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A record read back from the binary format. Strings are owned, since the `'static` strings of
+/// the process that wrote them are long gone.
+#[derive(Debug, Clone)]
+pub struct BinaryRecord {
+    pub level: Level,
+    pub target: String,
+    pub timestamp: SystemTime,
+    pub message: String,
+    pub fields: Vec<(String, FieldValue)>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub thread: String,
+}
+
+impl BinaryRecord {
+    /// Converts back into a record for this process's sinks. Targets, files and field names are
+    /// interned, so this is meant for a bounded set of sources rather than arbitrary input. The
+    /// thread is dropped, since `LogRecord` can only name threads of this process.
+    pub fn into_log_record(self) -> LogRecord {
+        LogRecord {
+            level: self.level,
+            target: intern(&self.target),
+            timestamp: self.timestamp,
+            message: self.message,
+            fields: self
+                .fields
+                .into_iter()
+                .map(|(name, value)| Field {
+                    name: intern(&name),
+                    value,
+                })
+                .collect(),
+            file: self.file.as_deref().map(intern),
+            line: self.line,
+            thread_id: thread::current().id(),
+        }
+    }
+}
+
+pub struct BinaryDecoder<R> {
+    reader: R,
+    strings: HashMap<u64, String>,
+}
+
+impl<R: Read> BinaryDecoder<R> {
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header).map_err(|err| {
+            Error::new("failed to read binary log header")
+                .with_kind(ErrorKind::Io)
+                .with_source(err)
+        })?;
+        if &header[..4] != MAGIC {
+            return Err(Error::new("not a binary log: bad magic").with_kind(ErrorKind::Parse));
+        }
+        if header[4] != VERSION {
+            return Err(
+                Error::new(format!("unsupported binary log version {}", header[4]))
+                    .with_kind(ErrorKind::Parse),
+            );
+        }
+
+        Ok(Self {
+            reader,
+            strings: HashMap::new(),
+        })
+    }
+
+    /// Returns the next record, or `None` at the end of the stream.
+    pub fn next_record(&mut self) -> Result<Option<BinaryRecord>, Error> {
+        loop {
+            let mut tag = [0; 1];
+            match self.reader.read(&mut tag) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(read_error(err)),
+            }
+
+            match tag[0] {
+                TAG_STRING => {
+                    let id = self.varint()?;
+                    let s = self.inline_str()?;
+                    self.strings.insert(id, s);
+                }
+                TAG_RECORD => return self.record().map(Some),
+                tag => {
+                    return Err(Error::new(format!("unknown binary log frame tag {tag}"))
+                        .with_kind(ErrorKind::Parse))
+                }
+            }
+        }
+    }
+
+    fn record(&mut self) -> Result<BinaryRecord, Error> {
+        let micros = self.varint()?;
+        let level = level_from_u8(self.byte()?)?;
+        let target = self.string()?;
+        let file = match self.byte()? {
+            0 => None,
+            _ => Some(self.string()?),
+        };
+        let line = match self.varint()? {
+            0 => None,
+            line => Some((line - 1) as u32),
+        };
+        let thread = self.string()?;
+        let message = self.string()?;
+
+        let count = self.varint()?;
+        let mut fields = Vec::with_capacity(count.min(64) as usize);
+        for _ in 0..count {
+            let name = self.string()?;
+            let value = match self.byte()? {
+                VALUE_F64 => FieldValue::F64(f64::from_le_bytes(self.array()?)),
+                VALUE_I64 => FieldValue::I64(unzigzag(self.varint()?)),
+                VALUE_U64 => FieldValue::U64(self.varint()?),
+                VALUE_I128 => FieldValue::I128(i128::from_le_bytes(self.array()?)),
+                VALUE_U128 => FieldValue::U128(u128::from_le_bytes(self.array()?)),
+                VALUE_BOOL => FieldValue::Bool(self.byte()? != 0),
+                VALUE_STR => FieldValue::Str(self.string()?),
+                VALUE_ERROR => FieldValue::Error(self.string()?),
+                VALUE_DEBUG => FieldValue::Debug(self.string()?),
+                kind => {
+                    return Err(Error::new(format!("unknown binary log value type {kind}"))
+                        .with_kind(ErrorKind::Parse))
+                }
+            };
+            fields.push((name, value));
+        }
+
+        Ok(BinaryRecord {
+            level,
+            target,
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            message,
+            fields,
+            file,
+            line,
+            thread,
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let mut bytes = [0; N];
+        self.reader.read_exact(&mut bytes).map_err(read_error)?;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::new("malformed varint in binary log").with_kind(ErrorKind::Parse))
+    }
+
+    fn inline_str(&mut self) -> Result<String, Error> {
+        let len = self.varint()? as usize;
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes).map_err(read_error)?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let reference = self.varint()?;
+        if reference & 1 == 1 {
+            let len = (reference >> 1) as usize;
+            let mut bytes = vec![0; len];
+            self.reader.read_exact(&mut bytes).map_err(read_error)?;
+            return Ok(String::from_utf8_lossy(&bytes).into_owned());
+        }
+
+        self.strings.get(&(reference >> 1)).cloned().ok_or_else(|| {
+            Error::new(format!("undefined string {} in binary log", reference >> 1))
+                .with_kind(ErrorKind::Parse)
+        })
+    }
+}
+
+impl<R: Read> Iterator for BinaryDecoder<R> {
+    type Item = Result<BinaryRecord, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn read_error(err: io::Error) -> Error {
+    Error::new("failed to read binary log")
+        .with_kind(ErrorKind::Io)
+        .with_source(err)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn write_inline(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, (s.len() as u64) << 1 | 1);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn level_to_u8(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+fn level_from_u8(level: u8) -> Result<Level, Error> {
+    match level {
+        1 => Ok(Level::ERROR),
+        2 => Ok(Level::WARN),
+        3 => Ok(Level::INFO),
+        4 => Ok(Level::DEBUG),
+        5 => Ok(Level::TRACE),
+        level => {
+            Err(Error::new(format!("unknown level {level} in binary log"))
+                .with_kind(ErrorKind::Parse))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(message: &str, fields: Vec<Field>) -> LogRecord {
+        LogRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            fields,
+            file: Some("src/game.rs"),
+            line: Some(42),
+            ..LogRecord::new(Level::WARN, "game::physics", message)
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Vec<BinaryRecord>, Error> {
+        BinaryDecoder::new(bytes)?.collect()
+    }
+
+    #[test]
+    fn round_trips_every_field_type() {
+        let fields = vec![
+            Field {
+                name: "f64",
+                value: FieldValue::F64(-1.5),
+            },
+            Field {
+                name: "i64",
+                value: FieldValue::I64(i64::MIN),
+            },
+            Field {
+                name: "u64",
+                value: FieldValue::U64(u64::MAX),
+            },
+            Field {
+                name: "i128",
+                value: FieldValue::I128(i128::MIN),
+            },
+            Field {
+                name: "u128",
+                value: FieldValue::U128(u128::MAX),
+            },
+            Field {
+                name: "bool",
+                value: FieldValue::Bool(true),
+            },
+            Field {
+                name: "str",
+                value: FieldValue::Str("ünïcode".into()),
+            },
+            Field {
+                name: "error",
+                value: FieldValue::Error("disk full".into()),
+            },
+            Field {
+                name: "debug",
+                value: FieldValue::Debug("Some(3)".into()),
+            },
+        ];
+        let original = record("step took too long", fields);
+
+        let mut encoder = BinaryEncoder::new();
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes);
+        encoder.encode(&original, &mut bytes);
+
+        let [decoded] = decode(&bytes).unwrap().try_into().unwrap();
+        assert_eq!(decoded.level, Level::WARN);
+        assert_eq!(decoded.target, "game::physics");
+        assert_eq!(decoded.timestamp, original.timestamp);
+        assert_eq!(decoded.message, "step took too long");
+        assert_eq!(decoded.file.as_deref(), Some("src/game.rs"));
+        assert_eq!(decoded.line, Some(42));
+        assert_eq!(decoded.thread, format!("{:?}", original.thread_id));
+        let fields: Vec<_> = original
+            .fields
+            .iter()
+            .map(|field| (field.name.to_string(), field.value.clone()))
+            .collect();
+        assert_eq!(decoded.fields, fields);
+    }
+
+    #[test]
+    fn round_trips_missing_file_and_line() {
+        let original = LogRecord::new(Level::TRACE, "game", "");
+        let mut encoder = BinaryEncoder::new();
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes);
+        encoder.encode(&original, &mut bytes);
+
+        let [decoded] = decode(&bytes).unwrap().try_into().unwrap();
+        assert_eq!((decoded.file, decoded.line), (None, None));
+        assert_eq!(decoded.level, Level::TRACE);
+    }
+
+    #[test]
+    fn interns_repeated_strings_once() {
+        let mut encoder = BinaryEncoder::new();
+        let mut first = Vec::new();
+        encoder.encode(&record("tick", Vec::new()), &mut first);
+        let mut second = Vec::new();
+        encoder.encode(&record("tick", Vec::new()), &mut second);
+
+        assert!(first.iter().filter(|&&byte| byte == TAG_STRING).count() >= 4);
+        assert_eq!(second[0], TAG_RECORD);
+        assert!(second.len() < first.len());
+
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes);
+        bytes.extend_from_slice(&first);
+        bytes.extend_from_slice(&second);
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(decoded.len(), 2);
+        assert!(decoded.iter().all(|record| record.message == "tick"));
+    }
+
+    #[test]
+    fn writes_strings_inline_past_the_intern_limit() {
+        let mut encoder = BinaryEncoder {
+            max_interned: 2,
+            ..BinaryEncoder::new()
+        };
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes);
+        for message in ["one", "two", "three"] {
+            encoder.encode(&record(message, Vec::new()), &mut bytes);
+        }
+
+        assert_eq!(encoder.strings.len(), 2);
+        let messages: Vec<_> = decode(&bytes)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.message)
+            .collect();
+        assert_eq!(messages, ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert_eq!(decode(b"GLOX\x01").unwrap_err().kind(), ErrorKind::Parse);
+        assert_eq!(decode(b"GLOG\x09").unwrap_err().kind(), ErrorKind::Parse);
+        assert_eq!(decode(b"GL").unwrap_err().kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn rejects_malformed_frames() {
+        let mut bytes = Vec::new();
+        BinaryEncoder::new().header(&mut bytes);
+
+        let mut unknown_tag = bytes.clone();
+        unknown_tag.push(9);
+        assert_eq!(decode(&unknown_tag).unwrap_err().kind(), ErrorKind::Parse);
+
+        // A record that refers to a string that was never defined.
+        let mut undefined = bytes.clone();
+        undefined.extend_from_slice(&[TAG_RECORD, 0, 3, 7 << 1]);
+        assert_eq!(decode(&undefined).unwrap_err().kind(), ErrorKind::Parse);
+
+        let mut encoder = BinaryEncoder::new();
+        let mut truncated = bytes.clone();
+        encoder.encode(&record("cut short", Vec::new()), &mut truncated);
+        truncated.pop();
+        assert_eq!(decode(&truncated).unwrap_err().kind(), ErrorKind::Io);
+    }
+
+    #[test]
+    fn varints_and_zigzag_round_trip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            let mut decoder = BinaryDecoder {
+                reader: bytes.as_slice(),
+                strings: HashMap::new(),
+            };
+            assert_eq!(decoder.varint().unwrap(), value);
+        }
+        for value in [0, -1, 1, i64::MIN, i64::MAX] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+
+use crate::log::{
+    init, init_scoped, parse_directives, pipeline::Pipeline, shutdown, AsyncConfig, LoggerError,
+    ScopedLogger, Sink, SinkConfig, SinkId, LOGGER,
+};
+
+/// Entry point for configuring the global logger, e.g.
+/// `Logger::builder().max_level(LevelFilter::INFO).with_sink(&sink).init()?`.
+pub struct Logger;
+
+impl Logger {
+    pub fn builder() -> LoggerBuilder {
+        LoggerBuilder::new()
+    }
+}
+
+pub struct LoggerBuilder {
+    max_level: Option<LevelFilter>,
+    directives: Option<String>,
+    asynchronous: Option<AsyncConfig>,
+    startup_buffer: Option<usize>,
+    sinks: Vec<(Arc<dyn Sink>, SinkConfig)>,
+}
+
+impl LoggerBuilder {
+    fn new() -> Self {
+        Self {
+            max_level: None,
+            directives: None,
+            asynchronous: None,
+            startup_buffer: None,
+            sinks: Vec::new(),
+        }
+    }
+
+    /// The default level for targets without a directive. Defaults to `INFO`.
+    pub fn max_level(self, max_level: LevelFilter) -> Self {
+        Self {
+            max_level: Some(max_level),
+            ..self
+        }
+    }
+
+    /// `RUST_LOG`-style per-target directives, e.g. `galleon::renderer=trace,wgpu=warn`. A level
+    /// set with `max_level` overrides any bare level here.
+    pub fn directives<S: Into<String>>(self, directives: S) -> Self {
+        Self {
+            directives: Some(directives.into()),
+            ..self
+        }
+    }
+
+    /// Delivers records to sinks from a background thread. See `startup_async`.
+    pub fn asynchronous(self, config: AsyncConfig) -> Self {
+        Self {
+            asynchronous: Some(config),
+            ..self
+        }
+    }
+
+    /// How many records logged before the first sink is added are kept to replay to each sink
+    /// as it's added. Defaults to 256; 0 turns buffering off.
+    pub fn startup_buffer(self, capacity: usize) -> Self {
+        Self {
+            startup_buffer: Some(capacity),
+            ..self
+        }
+    }
+
+    pub fn with_sink<S: Sink + Clone + 'static>(self, sink: &S) -> Self {
+        self.with_sink_config(sink, SinkConfig::new())
+    }
+
+    pub fn with_sink_config<S: Sink + Clone + 'static>(self, sink: &S, config: SinkConfig) -> Self {
+        let mut sinks = self.sinks;
+        sinks.push((Arc::new(sink.clone()), config));
+        Self { sinks, ..self }
+    }
+
+    /// Starts the global logger. It runs until the returned guard is dropped.
+    pub fn init(self) -> Result<LoggerGuard, LoggerError> {
+        let (directives, pipeline) = self.filter_and_pipeline()?;
+        init(directives, pipeline)?;
+
+        if let Some(logger) = LOGGER.get() {
+            if let Some(capacity) = self.startup_buffer {
+                logger.set_startup_buffer(capacity);
+            }
+            for (sink, config) in self.sinks {
+                logger.add_sink(SinkId::next(), sink, config);
+            }
+        }
+
+        Ok(LoggerGuard { _private: () })
+    }
+
+    /// Starts a logger that only sees events from the calling thread, alongside the global
+    /// logger if there is one. Functions like [`add_sink`](crate::log::add_sink) and
+    /// [`set_max_level`](crate::log::set_max_level) apply to it on this thread until it's dropped. Unlike
+    /// [`init`](Self::init) it can be called any number of times, so each test can have its own.
+    /// A config file from [`configure_from_file`](crate::log::configure_from_file) only applies to
+    /// the global logger, not this one.
+    ///
+    /// ```
+    /// use common::log::{sinks::RingBufferSink, Logger};
+    ///
+    /// for run in 0..2 {
+    ///     let sink = RingBufferSink::new(16);
+    ///     let _logger = Logger::builder().with_sink(&sink).init_scoped()?;
+    ///     common::log::info!(run, "starting");
+    ///     assert_eq!(sink.snapshot().len(), 1);
+    /// }
+    /// # Ok::<(), common::log::LoggerError>(())
+    /// ```
+    pub fn init_scoped(self) -> Result<ScopedLogger, LoggerError> {
+        let (directives, pipeline) = self.filter_and_pipeline()?;
+        let logger = init_scoped(directives, pipeline)?;
+        if let Some(capacity) = self.startup_buffer {
+            logger.logger.set_startup_buffer(capacity);
+        }
+        for (sink, config) in self.sinks {
+            logger.logger.add_sink(SinkId::next(), sink, config);
+        }
+
+        Ok(logger)
+    }
+
+    fn filter_and_pipeline(&self) -> Result<(Targets, Option<Arc<Pipeline>>), LoggerError> {
+        let mut directives = match self.directives.as_deref() {
+            Some(directives) => parse_directives(directives)?,
+            None => Targets::new().with_default(LevelFilter::INFO),
+        };
+        if let Some(max_level) = self.max_level {
+            directives = directives.with_default(max_level);
+        }
+
+        let pipeline = self
+            .asynchronous
+            .map(|config| Arc::new(Pipeline::new(config)));
+
+        Ok((directives, pipeline))
+    }
+}
+
+/// Flushes and shuts down the global logger when dropped, so early returns don't lose buffered
+/// output.
+#[must_use = "the logger shuts down as soon as the guard is dropped"]
+pub struct LoggerGuard {
+    _private: (),
+}
+
+impl Drop for LoggerGuard {
+    fn drop(&mut self) {
+        shutdown();
+    }
+}
@@ -0,0 +1,524 @@
+//! Logging configuration loaded from a file and reapplied whenever the file changes.
+//!
+//! The file is a small subset of TOML:
+//!
+//! ```toml
+//! # The default level for every target.
+//! level = "info"
+//!
+//! # Per-target overrides.
+//! [targets]
+//! "galleon::render" = "debug"
+//! wgpu_core = "warn"
+//!
+//! # Named sinks (see `SinkConfig::name`): `true`/`false` to enable or disable, or a level.
+//! [sinks]
+//! console = false
+//! file = "warn"
+//!
+//! # Keep one in N events at a level and more verbose, per call site (see `set_sampling`). When
+//! # present, this section replaces every sampling rule, even if it's empty.
+//! [sampling]
+//! "renderer::submit" = "trace:100"
+//! ```
+//!
+//! Each load is applied over the settings made in code, not over the previous file, so removing a
+//! sink's line puts it back how it was added.
+//!
+//! note: only the global logger is configured. A logger from
+//! [`LoggerBuilder::init_scoped`](crate::log::LoggerBuilder::init_scoped) belongs to the thread
+//! that started it, which isn't the one watching the file, so it keeps the settings it was built
+//! with.
+
+use std::{
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
+};
+
+use tracing::{error, info, level_filters::LevelFilter};
+use tracing_subscriber::filter::Targets;
+
+use crate::{
+    error::{Error, ErrorKind},
+    log::{sampling::SampleRule, LoggerState, LOGGER},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Keeps the config file watched. Dropping it stops watching; the last applied settings stay.
+pub struct ConfigWatcher {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        // Dropping the sender wakes the watcher thread immediately.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+/// Applies the logging config at `path` and watches it for changes. A file that fails to load on
+/// reload is reported and ignored, leaving the previous settings in place.
+pub fn configure_from_file<P: AsRef<Path>>(path: P) -> Result<ConfigWatcher, Error> {
+    let path = path.as_ref().to_path_buf();
+    let mut modified = modified_time(&path);
+    load(&path)?.apply();
+
+    let (stop, stopped) = mpsc::channel::<()>();
+    let thread = thread::Builder::new()
+        .name("galleon-log-config".to_string())
+        .spawn(move || loop {
+            match stopped.recv_timeout(POLL_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+
+            let current = modified_time(&path);
+            if current == modified {
+                continue;
+            }
+            modified = current;
+
+            match load(&path) {
+                Ok(config) => {
+                    config.apply();
+                    info!("reloaded logging config from {}", path.display());
+                }
+                Err(err) => error!("failed to reload logging config: {err}"),
+            }
+        })
+        .map_err(|err| Error::new("failed to spawn logging config watcher").with_source(err))?;
+
+    Ok(ConfigWatcher {
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
+
+/// Applies the logging config at `path` once, without watching it, e.g. from a
+/// [`Reloadable`](crate::hot_reload::Reloadable) when a file watcher reports it changed.
+pub fn apply_config_file<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    load(path.as_ref())?.apply();
+    Ok(())
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load(path: &Path) -> Result<LogConfig, Error> {
+    let source = std::fs::read_to_string(path).map_err(|err| {
+        Error::new(format!("failed to read logging config {}", path.display()))
+            .with_kind(ErrorKind::Io)
+            .with_source(err)
+    })?;
+
+    LogConfig::parse(&source).map_err(|err| {
+        Error::new(format!("invalid logging config {}: {err}", path.display()))
+            .with_kind(ErrorKind::Config)
+    })
+}
+
+#[derive(Debug, Default)]
+struct LogConfig {
+    level: Option<LevelFilter>,
+    targets: Vec<(String, LevelFilter)>,
+    sinks: Vec<(String, SinkSetting)>,
+    sampling: Option<Vec<SampleRule>>,
+}
+
+#[derive(Debug)]
+enum SinkSetting {
+    Enabled(bool),
+    Level(LevelFilter),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Root,
+    Targets,
+    Sinks,
+    Sampling,
+}
+
+enum Value {
+    Str(String),
+    Bool(bool),
+}
+
+impl LogConfig {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut config = LogConfig::default();
+        let mut section = Section::Root;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[') {
+                section = match name.strip_suffix(']').map(str::trim) {
+                    Some("targets") => Section::Targets,
+                    Some("sinks") => Section::Sinks,
+                    Some("sampling") => {
+                        config.sampling.get_or_insert_with(Vec::new);
+                        Section::Sampling
+                    }
+                    _ => return Err(format!("line {line_number}: unknown section {line}")),
+                };
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("line {line_number}: expected key = value"));
+            };
+            let key = unquote(key.trim());
+            let value = parse_value(value.trim())
+                .ok_or_else(|| format!("line {line_number}: invalid value for {key}"))?;
+
+            match (section, value) {
+                (Section::Root, Value::Str(level)) if key == "level" => {
+                    config.level = Some(parse_level(&level, line_number)?);
+                }
+                (Section::Root, _) => {
+                    return Err(format!("line {line_number}: unknown setting {key}"));
+                }
+                (Section::Targets, Value::Str(level)) => {
+                    let level = parse_level(&level, line_number)?;
+                    config.targets.push((key.to_string(), level));
+                }
+                (Section::Targets, Value::Bool(_)) => {
+                    return Err(format!("line {line_number}: expected a level for {key}"));
+                }
+                (Section::Sinks, Value::Bool(enabled)) => {
+                    config
+                        .sinks
+                        .push((key.to_string(), SinkSetting::Enabled(enabled)));
+                }
+                (Section::Sinks, Value::Str(level)) => {
+                    let level = parse_level(&level, line_number)?;
+                    config
+                        .sinks
+                        .push((key.to_string(), SinkSetting::Level(level)));
+                }
+                (Section::Sampling, Value::Str(rate)) => {
+                    let rule = parse_sample_rule(key, &rate, line_number)?;
+                    config.sampling.get_or_insert_with(Vec::new).push(rule);
+                }
+                (Section::Sampling, Value::Bool(_)) => {
+                    return Err(format!(
+                        "line {line_number}: expected \"level:n\" for {key}"
+                    ));
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn apply(&self) {
+        if let Some(logger) = LOGGER.get() {
+            self.apply_to(logger);
+        }
+    }
+
+    fn apply_to(&self, logger: &LoggerState) {
+        let mut directives = Targets::new().with_targets(
+            self.targets
+                .iter()
+                .map(|(target, level)| (target.clone(), *level)),
+        );
+        // note: without a `level` the current default is kept rather than falling back to OFF.
+        if let Some(level) = self.level.or_else(|| logger.default_level()) {
+            directives = directives.with_default(level);
+        }
+        logger.set_directives(directives);
+
+        if let Some(sampling) = self.sampling.as_ref() {
+            logger.inner.sampler.replace(sampling.clone());
+        }
+
+        // note: settings are applied over what was set in code rather than the previous file, so
+        // a sink whose line was removed or changed doesn't keep the old setting.
+        let mut configured = logger.inner.configured_sinks.lock().unwrap();
+        for name in configured.iter() {
+            if !self.sinks.iter().any(|(configured, _)| configured == name) {
+                logger.configure_named_sink(name, None, None);
+            }
+        }
+        for (name, setting) in &self.sinks {
+            match setting {
+                SinkSetting::Enabled(enabled) => {
+                    logger.configure_named_sink(name, Some(*enabled), None)
+                }
+                SinkSetting::Level(level) => logger.configure_named_sink(
+                    name,
+                    Some(*level != LevelFilter::OFF),
+                    Some(*level),
+                ),
+            }
+        }
+        *configured = self.sinks.iter().map(|(name, _)| name.clone()).collect();
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..index],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+fn parse_value(s: &str) -> Option<Value> {
+    match s {
+        "true" => Some(Value::Bool(true)),
+        "false" => Some(Value::Bool(false)),
+        _ => s
+            .strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .map(|s| Value::Str(s.to_string())),
+    }
+}
+
+fn parse_sample_rule(target: &str, rate: &str, line_number: usize) -> Result<SampleRule, String> {
+    let invalid =
+        || format!("line {line_number}: invalid sampling rate {rate:?}, expected \"level:n\"");
+    let (level, keep_one_in) = rate.split_once(':').ok_or_else(invalid)?;
+    let level = level.trim().parse().map_err(|_| invalid())?;
+    let keep_one_in = keep_one_in.trim().parse().map_err(|_| invalid())?;
+
+    Ok(SampleRule {
+        target: target.to_string(),
+        level,
+        keep_one_in,
+    })
+}
+
+fn parse_level(level: &str, line_number: usize) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("line {line_number}: invalid level {level:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::*;
+    use crate::log::{sinks::RingBufferSink, Logger, SinkConfig};
+
+    fn parse_err(source: &str) -> String {
+        LogConfig::parse(source).expect_err("config should be rejected")
+    }
+
+    #[test]
+    fn parses_every_section() {
+        let config = LogConfig::parse(
+            r#"
+            # The default level.
+            level = "info" # trailing comment
+
+            [targets]
+            "galleon::render" = "debug"
+            wgpu_core = "warn"
+
+            [sinks]
+            console = false
+            file = "warn"
+
+            [sampling]
+            "renderer::submit" = "trace:100"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.level, Some(LevelFilter::INFO));
+        assert_eq!(
+            config.targets,
+            [
+                ("galleon::render".to_string(), LevelFilter::DEBUG),
+                ("wgpu_core".to_string(), LevelFilter::WARN),
+            ]
+        );
+        assert!(matches!(
+            config.sinks.as_slice(),
+            [(console, SinkSetting::Enabled(false)), (file, SinkSetting::Level(LevelFilter::WARN))]
+                if console == "console" && file == "file"
+        ));
+        let sampling = config.sampling.unwrap();
+        assert_eq!(sampling.len(), 1);
+        assert_eq!(sampling[0].target, "renderer::submit");
+        assert_eq!(sampling[0].level, Level::TRACE);
+        assert_eq!(sampling[0].keep_one_in, 100);
+    }
+
+    #[test]
+    fn keeps_targets_in_order_for_overrides() {
+        let config = LogConfig::parse(
+            r#"
+            level = "warn"
+            [targets]
+            galleon = "info"
+            "galleon::render" = "trace"
+            galleon = "error"
+            "#,
+        )
+        .unwrap();
+
+        // `Targets` picks the most specific target, and the last of equal ones.
+        let targets = config.targets.iter().cloned().collect::<Targets>();
+        assert!(targets.would_enable("galleon::render::pass", &Level::TRACE));
+        assert!(targets.would_enable("galleon::audio", &Level::ERROR));
+        assert!(!targets.would_enable("galleon::audio", &Level::WARN));
+    }
+
+    #[test]
+    fn empty_or_missing_sections_keep_current_settings() {
+        let config = LogConfig::parse("# nothing here\n\n").unwrap();
+        assert_eq!(config.level, None);
+        assert!(config.targets.is_empty());
+        assert!(config.sinks.is_empty());
+        assert!(config.sampling.is_none());
+
+        // An empty sampling section clears the rules, so it isn't the same as a missing one.
+        let config = LogConfig::parse("[sampling]").unwrap();
+        assert!(config.sampling.is_some_and(|rules| rules.is_empty()));
+    }
+
+    #[test]
+    fn keeps_unknown_sinks_by_name() {
+        // Sinks are matched by name when applied, so a name no sink has yet isn't an error.
+        let config = LogConfig::parse("[sinks]\nnot_registered = true").unwrap();
+        assert!(matches!(
+            config.sinks.as_slice(),
+            [(name, SinkSetting::Enabled(true))] if name == "not_registered"
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse_err("level"), "line 1: expected key = value");
+        assert_eq!(
+            parse_err("\nlevel = info"),
+            "line 2: invalid value for level"
+        );
+        assert_eq!(
+            parse_err("level = \"info"),
+            "line 1: invalid value for level"
+        );
+        assert_eq!(parse_err("[filters]"), "line 1: unknown section [filters]");
+        assert_eq!(parse_err("[targets"), "line 1: unknown section [targets");
+        assert_eq!(parse_err("colour = true"), "line 1: unknown setting colour");
+    }
+
+    #[test]
+    fn rejects_unknown_levels() {
+        assert_eq!(
+            parse_err("level = \"loud\""),
+            "line 1: invalid level \"loud\""
+        );
+        assert_eq!(
+            parse_err("[targets]\ngalleon = \"verbose\""),
+            "line 2: invalid level \"verbose\""
+        );
+        assert_eq!(
+            parse_err("[targets]\ngalleon = true"),
+            "line 2: expected a level for galleon"
+        );
+        assert_eq!(
+            parse_err("[sinks]\nfile = \"chatty\""),
+            "line 2: invalid level \"chatty\""
+        );
+    }
+
+    #[test]
+    fn rejects_bad_sampling_rates() {
+        for rate in ["\"trace\"", "\"loud:10\"", "\"debug:often\""] {
+            let err = parse_err(&format!("[sampling]\ngame = {rate}"));
+            assert!(err.starts_with("line 2: invalid sampling rate"), "{err}");
+        }
+        assert_eq!(
+            parse_err("[sampling]\ngame = false"),
+            "line 2: expected \"level:n\" for game"
+        );
+    }
+
+    /// Whether each sink is enabled and its level, by name.
+    fn sink_settings(logger: &LoggerState) -> Vec<(String, bool, LevelFilter)> {
+        let mut settings: Vec<_> = logger
+            .inner
+            .sinks()
+            .values()
+            .map(|entry| {
+                let name = entry.config.name.clone().unwrap_or_default();
+                (name, entry.config.enabled, entry.config.level)
+            })
+            .collect();
+        settings.sort_by(|a, b| a.0.cmp(&b.0));
+        settings
+    }
+
+    #[test]
+    fn reapplying_restores_sinks_the_file_stops_naming() {
+        let sink = RingBufferSink::new(1);
+        let scoped = Logger::builder()
+            .with_sink_config(&sink, SinkConfig::new().name("console"))
+            .with_sink_config(
+                &sink,
+                SinkConfig::new().name("file").level(LevelFilter::INFO),
+            )
+            .init_scoped()
+            .unwrap();
+        let logger = &scoped.logger;
+        let apply = |source| LogConfig::parse(source).unwrap().apply_to(logger);
+
+        apply("[sinks]\nconsole = false\nfile = \"warn\"");
+        assert_eq!(
+            sink_settings(logger),
+            [
+                ("console".to_string(), false, LevelFilter::TRACE),
+                ("file".to_string(), true, LevelFilter::WARN),
+            ]
+        );
+
+        // Enabling the file sink without a level drops the old file's level too.
+        apply("[sinks]\nfile = true");
+        assert_eq!(
+            sink_settings(logger),
+            [
+                ("console".to_string(), true, LevelFilter::TRACE),
+                ("file".to_string(), true, LevelFilter::INFO),
+            ]
+        );
+
+        apply("[sinks]\nconsole = \"off\"");
+        apply("");
+        assert_eq!(
+            sink_settings(logger),
+            [
+                ("console".to_string(), true, LevelFilter::TRACE),
+                ("file".to_string(), true, LevelFilter::INFO),
+            ]
+        );
+    }
+}
@@ -0,0 +1,47 @@
+use std::{cell::RefCell, marker::PhantomData};
+
+use crate::log::{Field, FieldValue};
+
+thread_local! {
+    static CONTEXT: RefCell<Vec<Field>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Removes the field added by [`push_context`], and any pushed after it, when dropped.
+#[must_use = "the context is removed as soon as the guard is dropped"]
+pub struct ContextGuard {
+    depth: usize,
+    // The context is per thread, so the guard must be dropped on the thread that created it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        _ = CONTEXT.try_with(|context| context.borrow_mut().truncate(self.depth));
+    }
+}
+
+/// Attaches `name = value` to every record logged on this thread until the guard is dropped, e.g.
+/// `let _player = log::push_context("player_id", id);` around a player's update.
+pub fn push_context<V: Into<FieldValue>>(name: &'static str, value: V) -> ContextGuard {
+    let field = Field {
+        name,
+        value: value.into(),
+    };
+    let depth = CONTEXT
+        .try_with(|context| {
+            let mut context = context.borrow_mut();
+            context.push(field);
+            context.len() - 1
+        })
+        .unwrap_or(0);
+
+    ContextGuard {
+        depth,
+        _not_send: PhantomData,
+    }
+}
+
+/// Appends this thread's context fields, outermost first.
+pub(crate) fn append_context(fields: &mut Vec<Field>) {
+    _ = CONTEXT.try_with(|context| fields.extend(context.borrow().iter().cloned()));
+}
@@ -0,0 +1,157 @@
+use std::time::{Duration, Instant};
+
+use crate::log::LogRecord;
+
+/// Collapses runs of identical consecutive records into a single "repeated" summary.
+pub(crate) struct Dedup {
+    window: Duration,
+    last: Option<LogRecord>,
+    window_start: Instant,
+    repeated: u64,
+}
+
+impl Dedup {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            last: None,
+            window_start: Instant::now(),
+            repeated: 0,
+        }
+    }
+
+    /// Returns a summary to emit ahead of `record`, if a run just ended, and whether `record`
+    /// itself should be emitted.
+    pub(crate) fn observe(&mut self, record: &LogRecord) -> (Option<LogRecord>, bool) {
+        let now = Instant::now();
+        let is_repeat = self.last.as_ref().is_some_and(|last| is_same(last, record));
+
+        if is_repeat && now.duration_since(self.window_start) < self.window {
+            self.repeated += 1;
+            return (None, false);
+        }
+
+        let summary = self.take_summary();
+        if !is_repeat {
+            self.last = Some(record.clone());
+        }
+        self.window_start = now;
+
+        (summary, true)
+    }
+
+    /// Ends the current run, returning its summary if any records were suppressed.
+    pub(crate) fn take_summary(&mut self) -> Option<LogRecord> {
+        if self.repeated == 0 {
+            return None;
+        }
+
+        let repeated = std::mem::take(&mut self.repeated);
+        self.last.as_ref().map(|last| LogRecord {
+            file: last.file,
+            line: last.line,
+            ..LogRecord::new(
+                last.level,
+                last.target,
+                format!("last message repeated {repeated} times"),
+            )
+        })
+    }
+}
+
+fn is_same(a: &LogRecord, b: &LogRecord) -> bool {
+    a.level == b.level
+        && a.line == b.line
+        && a.file == b.file
+        && a.target == b.target
+        && a.message == b.message
+        && a.fields == b.fields
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::*;
+    use crate::log::{Field, FieldValue};
+
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    fn record(message: &str) -> LogRecord {
+        LogRecord {
+            file: Some("game.rs"),
+            line: Some(7),
+            ..LogRecord::new(Level::WARN, "game", message)
+        }
+    }
+
+    /// Whether each of `messages` is emitted, and the summaries emitted ahead of them.
+    fn observe(dedup: &mut Dedup, messages: &[&str]) -> (Vec<bool>, Vec<String>) {
+        let mut emitted = Vec::new();
+        let mut summaries = Vec::new();
+        for message in messages {
+            let (summary, emit) = dedup.observe(&record(message));
+            emitted.push(emit);
+            summaries.extend(summary.map(|summary| summary.message));
+        }
+        (emitted, summaries)
+    }
+
+    #[test]
+    fn collapses_identical_records() {
+        let mut dedup = Dedup::new(WINDOW);
+        let (emitted, summaries) = observe(&mut dedup, &["hot", "hot", "hot", "hot", "cold"]);
+        assert_eq!(emitted, [true, false, false, false, true]);
+        assert_eq!(summaries, ["last message repeated 3 times"]);
+        assert!(dedup.take_summary().is_none());
+    }
+
+    #[test]
+    fn summary_keeps_the_repeated_record_source() {
+        let mut dedup = Dedup::new(WINDOW);
+        observe(&mut dedup, &["hot", "hot"]);
+        let summary = dedup.take_summary().unwrap();
+        assert_eq!(summary.message, "last message repeated 1 times");
+        assert_eq!(summary.level, Level::WARN);
+        assert_eq!(summary.target, "game");
+        assert_eq!((summary.file, summary.line), (Some("game.rs"), Some(7)));
+        assert!(dedup.take_summary().is_none());
+    }
+
+    #[test]
+    fn keeps_interleaved_records() {
+        let mut dedup = Dedup::new(WINDOW);
+        let (emitted, summaries) = observe(&mut dedup, &["a", "b", "a", "b", "a"]);
+        assert_eq!(emitted, [true; 5]);
+        assert!(summaries.is_empty());
+    }
+
+    #[test]
+    fn distinguishes_records_by_fields() {
+        let mut dedup = Dedup::new(WINDOW);
+        let mut with_field = record("hot");
+        with_field.fields.push(Field {
+            name: "frame",
+            value: FieldValue::U64(1),
+        });
+        assert!(dedup.observe(&record("hot")).1);
+        assert!(dedup.observe(&with_field).1);
+        assert!(dedup.take_summary().is_none());
+    }
+
+    #[test]
+    fn emits_a_repeat_once_the_window_expires() {
+        let mut dedup = Dedup::new(WINDOW);
+        let (emitted, _) = observe(&mut dedup, &["hot", "hot", "hot"]);
+        assert_eq!(emitted, [true, false, false]);
+
+        dedup.window_start -= WINDOW;
+        let (emitted, summaries) = observe(&mut dedup, &["hot", "hot"]);
+        assert_eq!(emitted, [true, false]);
+        assert_eq!(summaries, ["last message repeated 2 times"]);
+        assert_eq!(
+            dedup.take_summary().map(|summary| summary.message),
+            Some("last message repeated 1 times".to_string())
+        );
+    }
+}
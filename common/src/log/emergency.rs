@@ -0,0 +1,85 @@
+use std::{
+    sync::{TryLockError, TryLockResult},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::log::{log_to_sink, LogRecord, LoggerState, DELIVERING, LOGGER};
+
+/// The longest any single lock is waited for before that step is skipped.
+const LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Pushes any records still queued for the async worker straight to the sinks and flushes them,
+/// for use from a panic hook or an unhandled exception filter. Returns whether everything was
+/// delivered.
+///
+/// None of the logger's locks are waited on indefinitely: they're only tried until a short
+/// timeout, so a crashing thread that holds one can't deadlock the flush. Sinks still run their
+/// normal `log` and `flush`, and are skipped entirely when the crash happened inside a sink on
+/// this thread, since it may hold the sink's own locks.
+///
+/// note: this allocates, since sinks format records into strings and buffers of their own, so
+/// it's only safe where the heap still is, as in a panic hook. It must not be called from a
+/// signal handler or vectored exception handler that may have interrupted the allocator.
+pub fn emergency_flush() -> bool {
+    match LOGGER.get() {
+        Some(logger) => deliver(logger, None),
+        None => true,
+    }
+}
+
+/// Delivers queued records and then `record`, if any, as `emergency_flush` does. `record` skips
+/// per-sink config so it reaches every sink.
+pub(crate) fn deliver(logger: &LoggerState, record: Option<&LogRecord>) -> bool {
+    if LoggerState::is_delivering() {
+        return false;
+    }
+
+    let deadline = Instant::now() + LOCK_TIMEOUT;
+    let Some(sinks) = try_lock_until(deadline, || logger.inner.sinks.try_read()) else {
+        return false;
+    };
+    // note: cloning the Arc only bumps a count; the lock is released before calling sinks.
+    let sinks = sinks.clone();
+
+    let delivering = DELIVERING.replace(true);
+    let send = |record: &LogRecord, filtered: bool| {
+        for entry in sinks.values().filter(|entry| !entry.health.is_disabled()) {
+            if (!filtered || entry.config.accepts(record)) && entry.sink.enabled(&record.level) {
+                _ = log_to_sink(&*entry.sink, record);
+            }
+        }
+    };
+
+    let drained = match logger.pipeline.as_ref() {
+        Some(pipeline) => {
+            pipeline.try_drain(Instant::now() + LOCK_TIMEOUT, |record| send(record, true))
+        }
+        None => true,
+    };
+    if let Some(record) = record {
+        send(record, false);
+    }
+    for entry in sinks.values() {
+        _ = entry.sink.flush();
+    }
+    DELIVERING.set(delivering);
+
+    drained
+}
+
+/// Retries `try_lock` until it succeeds or `deadline` passes. A poisoned lock is still taken;
+/// whatever panicked while holding it is the reason we're here.
+pub(crate) fn try_lock_until<G>(
+    deadline: Instant,
+    mut try_lock: impl FnMut() -> TryLockResult<G>,
+) -> Option<G> {
+    loop {
+        match try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(TryLockError::Poisoned(err)) => return Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) if Instant::now() < deadline => thread::yield_now(),
+            Err(TryLockError::WouldBlock) => return None,
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! Output for records logged before any sink has been added, so a missing `add_sink` doesn't make
+//! everything disappear silently.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::log::{
+    format::{CompactFormatter, Formatter},
+    LogRecord,
+};
+
+static ANNOUNCED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(windows)]
+extern "system" {
+    fn OutputDebugStringW(output: *const u16);
+}
+
+/// Writes `record` to stderr, or the debugger output on Windows where GUI-subsystem binaries have
+/// no stderr.
+pub(crate) fn log(record: &LogRecord) {
+    let mut line = String::with_capacity(128);
+    if !ANNOUNCED.swap(true, Ordering::Relaxed) {
+        line.push_str("no log sinks registered; writing records here until one is added\n");
+    }
+    CompactFormatter.format(record, &mut line);
+    line.push('\n');
+
+    write(&line);
+}
+
+/// Reports, once, that records are being dropped because every sink was removed.
+pub(crate) fn warn_dropped() {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        write("all log sinks were removed; records are being dropped\n");
+    }
+}
+
+#[cfg(windows)]
+fn write(text: &str) {
+    let text: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe { OutputDebugStringW(text.as_ptr()) };
+}
+
+#[cfg(not(windows))]
+fn write(text: &str) {
+    use std::io::Write;
+
+    _ = std::io::stderr().lock().write_all(text.as_bytes());
+}
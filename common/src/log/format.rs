@@ -0,0 +1,219 @@
+use std::{
+    fmt::Write,
+    path::{Path, PathBuf, MAIN_SEPARATOR},
+};
+
+use crate::{
+    json,
+    log::{timestamp::Timestamp, Field, FieldValue, LogRecord},
+};
+
+/// Turns a record into a single line of text. Sinks decide where the line goes; formatters decide
+/// what it looks like. The line is written without a trailing newline.
+pub trait Formatter: Send + Sync {
+    fn format(&self, record: &LogRecord, out: &mut String);
+
+    /// Prefixes each line with the record's target, e.g. `[galleon::renderer]`.
+    fn with_target(self) -> WithTarget<Self>
+    where
+        Self: Sized,
+    {
+        WithTarget(self)
+    }
+}
+
+/// `[target]` followed by the wrapped formatter's line; see [`Formatter::with_target`]. Not for
+/// [`VisualStudioFormatter`], whose lines have to start with the source location.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WithTarget<F>(pub F);
+
+impl<F: Formatter> Formatter for WithTarget<F> {
+    fn format(&self, record: &LogRecord, out: &mut String) {
+        _ = write!(out, "[{}]", record.target);
+        self.0.format(record, out);
+    }
+}
+
+/// Text buffers kept between records beyond this many bytes are shrunk back.
+const MAX_RETAINED_LINE: usize = 4096;
+
+/// A record formatted as UTF-16 for sinks that hand text to wide-string APIs. Both buffers keep
+/// their capacity between records, so formatting and converting a line doesn't allocate once they
+/// have grown to fit.
+#[derive(Debug, Default)]
+pub struct Utf16Line {
+    text: String,
+    wide: Vec<u16>,
+}
+
+impl Utf16Line {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Formats `record` with `formatter` and returns the line as UTF-16, without a newline or nul
+    /// terminator. The caller may append to it; it's cleared on the next call.
+    pub fn format(&mut self, formatter: &dyn Formatter, record: &LogRecord) -> &mut Vec<u16> {
+        self.text.clear();
+        self.text.shrink_to(MAX_RETAINED_LINE);
+        self.wide.clear();
+        self.wide.shrink_to(MAX_RETAINED_LINE);
+
+        formatter.format(record, &mut self.text);
+        self.wide.extend(self.text.encode_utf16());
+        &mut self.wide
+    }
+}
+
+/// `[LEVEL][file:line] message key=value`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompactFormatter;
+
+impl Formatter for CompactFormatter {
+    fn format(&self, record: &LogRecord, out: &mut String) {
+        _ = write!(out, "[{}]", record.level);
+        write_location(out, record);
+        _ = write!(out, " {}", record.message);
+        write_fields(out, &record.fields);
+    }
+}
+
+/// `[timestamp][LEVEL][file:line] message key=value`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FullFormatter;
+
+impl Formatter for FullFormatter {
+    fn format(&self, record: &LogRecord, out: &mut String) {
+        _ = write!(
+            out,
+            "[{}][{}]",
+            Timestamp::from_system_time(record.timestamp),
+            record.level
+        );
+        write_location(out, record);
+        _ = write!(out, " {}", record.message);
+        write_fields(out, &record.fields);
+    }
+}
+
+/// `file(line): [LEVEL] message key=value`, which Visual Studio's Output window turns into a link
+/// to the source line.
+///
+/// `file!()` paths are relative to the workspace they were compiled in, so Visual Studio can only
+/// resolve them once they are joined onto a source root.
+#[derive(Debug, Clone, Default)]
+pub struct VisualStudioFormatter {
+    source_root: Option<PathBuf>,
+}
+
+impl VisualStudioFormatter {
+    pub fn new() -> Self {
+        Self { source_root: None }
+    }
+
+    /// Relative file paths are made absolute by joining them onto `source_root`.
+    pub fn with_source_root<P: Into<PathBuf>>(self, source_root: P) -> Self {
+        Self {
+            source_root: Some(source_root.into()),
+        }
+    }
+
+    fn write_path(&self, out: &mut String, file: &str) {
+        let path = Path::new(file);
+        let path = match self.source_root.as_ref() {
+            Some(root) if path.is_relative() => root.join(path),
+            _ => path.to_path_buf(),
+        };
+
+        for c in path.to_string_lossy().chars() {
+            out.push(if c == '/' || c == '\\' {
+                MAIN_SEPARATOR
+            } else {
+                c
+            });
+        }
+    }
+}
+
+impl Formatter for VisualStudioFormatter {
+    fn format(&self, record: &LogRecord, out: &mut String) {
+        match (record.file, record.line) {
+            (Some(file), Some(line)) => {
+                self.write_path(out, file);
+                _ = write!(out, "({line}): ");
+            }
+            _ => out.push_str("unknown(0): "),
+        }
+        _ = write!(out, "[{}] {}", record.level, record.message);
+        write_fields(out, &record.fields);
+    }
+}
+
+/// One JSON object per record with the fields kept as typed values.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JsonFormatter;
+
+impl Formatter for JsonFormatter {
+    fn format(&self, record: &LogRecord, out: &mut String) {
+        out.push_str("{\"timestamp\":");
+        json::write_str(
+            out,
+            &Timestamp::from_system_time(record.timestamp).to_string(),
+        );
+        out.push_str(",\"level\":");
+        json::write_str(out, record.level.as_str());
+        out.push_str(",\"target\":");
+        json::write_str(out, record.target);
+        out.push_str(",\"message\":");
+        json::write_str(out, &record.message);
+
+        out.push_str(",\"fields\":{");
+        for (i, field) in record.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json::write_str(out, field.name);
+            out.push(':');
+            write_json_value(out, &field.value);
+        }
+        out.push('}');
+
+        if let Some(file) = record.file {
+            out.push_str(",\"file\":");
+            json::write_str(out, file);
+        }
+        if let Some(line) = record.line {
+            _ = write!(out, ",\"line\":{line}");
+        }
+        out.push_str(",\"thread\":");
+        json::write_str(out, &format!("{:?}", record.thread_id));
+        out.push('}');
+    }
+}
+
+fn write_location(out: &mut String, record: &LogRecord) {
+    match (record.file, record.line) {
+        (Some(file), Some(line)) => _ = write!(out, "[{file}:{line}]"),
+        _ => out.push_str("[unknown:unknown]"),
+    }
+}
+
+pub(crate) fn write_fields(out: &mut String, fields: &[Field]) {
+    for field in fields {
+        _ = write!(out, " {}={}", field.name, field.value);
+    }
+}
+
+pub(crate) fn write_json_value(out: &mut String, value: &FieldValue) {
+    match value {
+        FieldValue::F64(value) => json::write_f64(out, *value),
+        FieldValue::I64(value) => _ = write!(out, "{value}"),
+        FieldValue::U64(value) => _ = write!(out, "{value}"),
+        FieldValue::I128(value) => _ = write!(out, "{value}"),
+        FieldValue::U128(value) => _ = write!(out, "{value}"),
+        FieldValue::Bool(value) => _ = write!(out, "{value}"),
+        FieldValue::Str(value) | FieldValue::Error(value) | FieldValue::Debug(value) => {
+            json::write_str(out, value)
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex,
+};
+
+use crate::{error::Error, log::SinkId};
+
+/// A snapshot of how a sink has been doing, from [`sink_health`](crate::log::sink_health).
+#[derive(Debug, Clone)]
+pub struct SinkHealth {
+    pub id: SinkId,
+    pub name: Option<String>,
+    /// Failed `log` and `flush` calls since the sink was added.
+    pub errors: u64,
+    /// Failures since the last success.
+    pub consecutive_errors: u64,
+    pub last_error: Option<String>,
+    /// Whether the sink was disabled by `SinkConfig::disable_after`.
+    pub disabled: bool,
+}
+
+/// Error counters shared by every copy of a sink's entry.
+pub(crate) struct HealthState {
+    label: String,
+    errors: AtomicU64,
+    consecutive_errors: AtomicU64,
+    disabled: AtomicBool,
+    last_error: Mutex<Option<String>>,
+}
+
+impl HealthState {
+    pub(crate) fn new(id: SinkId, name: Option<&str>) -> Self {
+        Self {
+            label: name.map_or_else(|| format!("{id:?}"), str::to_string),
+            errors: AtomicU64::new(0),
+            consecutive_errors: AtomicU64::new(0),
+            disabled: AtomicBool::new(false),
+            last_error: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn is_disabled(&self) -> bool {
+        self.disabled.load(Ordering::Relaxed)
+    }
+
+    /// Records the outcome of a sink call. Returns a notice to log if this failure disabled the
+    /// sink.
+    pub(crate) fn record(
+        &self,
+        result: Result<(), Error>,
+        disable_after: Option<u64>,
+    ) -> Option<String> {
+        let err = match result {
+            Ok(()) => {
+                self.consecutive_errors.store(0, Ordering::Relaxed);
+                return None;
+            }
+            Err(err) => err.full_message(),
+        };
+
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        let consecutive_errors = self.consecutive_errors.fetch_add(1, Ordering::Relaxed) + 1;
+        let notice = match disable_after {
+            Some(limit)
+                if consecutive_errors >= limit && !self.disabled.swap(true, Ordering::Relaxed) =>
+            {
+                Some(format!(
+                    "sink {} disabled after {consecutive_errors} consecutive errors: {err}",
+                    self.label
+                ))
+            }
+            _ => None,
+        };
+        *self.last_error.lock().unwrap() = Some(err);
+
+        notice
+    }
+
+    pub(crate) fn reset(&self) {
+        self.consecutive_errors.store(0, Ordering::Relaxed);
+        self.disabled.store(false, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, id: SinkId, name: Option<String>) -> SinkHealth {
+        SinkHealth {
+            id,
+            name,
+            errors: self.errors.load(Ordering::Relaxed),
+            consecutive_errors: self.consecutive_errors.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().unwrap().clone(),
+            disabled: self.disabled.load(Ordering::Relaxed),
+        }
+    }
+}
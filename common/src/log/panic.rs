@@ -0,0 +1,70 @@
+use std::{backtrace::Backtrace, panic::PanicHookInfo, time::Duration};
+
+use tracing::Level;
+
+use crate::log::{emergency, Field, FieldValue, LogRecord, LoggerState, LOGGER};
+
+/// How long the hook waits for the async worker to drain queued records before giving up.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Installs a panic hook that writes the panic message, location and backtrace through every sink
+/// at ERROR level and flushes them, then chains to the previously installed hook.
+///
+/// Without this, a panic in the GUI-subsystem binary has nowhere to print and is lost.
+pub fn install_panic_hook() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if let Some(logger) = LOGGER.get() {
+            log_panic(logger, info);
+        }
+
+        previous(info);
+    }));
+}
+
+fn log_panic(logger: &LoggerState, info: &PanicHookInfo<'_>) {
+    let payload = info.payload();
+    let message = if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.as_str()
+    } else {
+        "Box<dyn Any>"
+    };
+
+    let thread = std::thread::current();
+    let mut record = LogRecord::new(
+        Level::ERROR,
+        "panic",
+        format!(
+            "thread '{}' panicked: {message}",
+            thread.name().unwrap_or("<unnamed>")
+        ),
+    );
+    if let Some(location) = info.location() {
+        record.fields.push(Field {
+            name: "location",
+            value: FieldValue::Str(location.to_string()),
+        });
+    }
+    record.fields.push(Field {
+        name: "backtrace",
+        value: FieldValue::Str(Backtrace::force_capture().to_string()),
+    });
+
+    // The worker can't drain its own queue, and a sink that panicked may still hold its own locks,
+    // so nothing here is allowed to block indefinitely.
+    if let Some(pipeline) = logger.pipeline.as_ref() {
+        if !LoggerState::is_worker_thread() {
+            pipeline.wait_idle_timeout(DRAIN_TIMEOUT);
+        }
+    }
+
+    if LoggerState::is_delivering() {
+        eprintln!("{}", record.message);
+        return;
+    }
+
+    // Anything the worker didn't get to is written ahead of the panic itself.
+    emergency::deliver(logger, Some(&record));
+}
@@ -0,0 +1,297 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::log::{emergency::try_lock_until, LogRecord};
+
+/// What to do with a record when the async queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the logging thread until the worker makes room.
+    Block,
+    /// Discard the oldest queued record to make room for the new one.
+    DropOldest,
+    /// Discard the new record.
+    DropNewest,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AsyncConfig {
+    pub(crate) capacity: usize,
+    pub(crate) overflow: OverflowPolicy,
+}
+
+impl AsyncConfig {
+    pub fn new() -> Self {
+        Self {
+            capacity: 1024,
+            overflow: OverflowPolicy::Block,
+        }
+    }
+
+    pub fn capacity(self, capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ..self
+        }
+    }
+
+    pub fn overflow(self, overflow: OverflowPolicy) -> Self {
+        Self { overflow, ..self }
+    }
+}
+
+impl Default for AsyncConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) struct Pipeline {
+    config: AsyncConfig,
+    state: Mutex<PipelineState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    idle: Condvar,
+    dropped: AtomicU64,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct PipelineState {
+    queue: VecDeque<LogRecord>,
+    busy: bool,
+    closed: bool,
+}
+
+impl Pipeline {
+    pub(crate) fn new(config: AsyncConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(PipelineState {
+                queue: VecDeque::with_capacity(config.capacity),
+                busy: false,
+                closed: false,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            idle: Condvar::new(),
+            dropped: AtomicU64::new(0),
+            worker: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn set_worker(&self, worker: JoinHandle<()>) {
+        *self.worker.lock().unwrap() = Some(worker);
+    }
+
+    /// Queues a record for the worker. Returns the record if the pipeline is closed so the caller
+    /// can deliver it synchronously instead.
+    pub(crate) fn push(&self, record: LogRecord) -> Option<LogRecord> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Some(record);
+        }
+
+        if state.queue.len() >= self.config.capacity {
+            match self.config.overflow {
+                OverflowPolicy::Block => {
+                    while state.queue.len() >= self.config.capacity && !state.closed {
+                        state = self.not_full.wait(state).unwrap();
+                    }
+                    if state.closed {
+                        return Some(record);
+                    }
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return None;
+                }
+            }
+        }
+
+        state.queue.push_back(record);
+        self.not_empty.notify_one();
+
+        None
+    }
+
+    /// Blocks until there are records to process, returning them all at once. Returns `None` once
+    /// the pipeline is closed and fully drained.
+    pub(crate) fn pop_all(&self) -> Option<VecDeque<LogRecord>> {
+        let mut state = self.state.lock().unwrap();
+        state.busy = false;
+        self.idle.notify_all();
+
+        while state.queue.is_empty() {
+            if state.closed {
+                return None;
+            }
+            state = self.not_empty.wait(state).unwrap();
+        }
+
+        state.busy = true;
+        let batch = std::mem::replace(
+            &mut state.queue,
+            VecDeque::with_capacity(self.config.capacity),
+        );
+        self.not_full.notify_all();
+
+        Some(batch)
+    }
+
+    /// Blocks until every queued record has been handed to the sinks.
+    pub(crate) fn wait_idle(&self) {
+        let mut state = self.state.lock().unwrap();
+        while (!state.queue.is_empty() || state.busy) && !state.closed {
+            state = self.idle.wait(state).unwrap();
+        }
+    }
+
+    /// Like `wait_idle`, but gives up after `timeout`. Returns whether the queue drained.
+    pub(crate) fn wait_idle_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.state.lock().unwrap();
+        while (!state.queue.is_empty() || state.busy) && !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            state = self.idle.wait_timeout(state, deadline - now).unwrap().0;
+        }
+
+        true
+    }
+
+    /// Hands every queued record to `deliver` on this thread, without waiting for the queue lock
+    /// past `deadline`. Returns whether the lock was taken.
+    pub(crate) fn try_drain<F: FnMut(&LogRecord)>(
+        &self,
+        deadline: Instant,
+        mut deliver: F,
+    ) -> bool {
+        let Some(mut state) = try_lock_until(deadline, || self.state.try_lock()) else {
+            return false;
+        };
+
+        // note: records are delivered while the lock is held. Taking the queue would replace it
+        // with a new allocation.
+        for record in state.queue.drain(..) {
+            deliver(&record);
+        }
+        self.not_full.notify_all();
+
+        true
+    }
+
+    /// Stops accepting records, drains the queue and joins the worker.
+    pub(crate) fn close(&self) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.closed = true;
+            self.not_empty.notify_all();
+            self.not_full.notify_all();
+            self.idle.notify_all();
+        }
+
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            _ = worker.join();
+        }
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use tracing::Level;
+
+    use super::*;
+
+    fn full_pipeline(overflow: OverflowPolicy) -> Pipeline {
+        let pipeline = Pipeline::new(AsyncConfig::new().capacity(3).overflow(overflow));
+        for i in 0..3 {
+            assert!(pipeline.push(record(i)).is_none());
+        }
+        pipeline
+    }
+
+    fn record(i: usize) -> LogRecord {
+        LogRecord::new(Level::INFO, "test", i.to_string())
+    }
+
+    fn messages(records: VecDeque<LogRecord>) -> Vec<String> {
+        records.into_iter().map(|record| record.message).collect()
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest_records() {
+        let pipeline = full_pipeline(OverflowPolicy::DropOldest);
+        for i in 3..5 {
+            assert!(pipeline.push(record(i)).is_none());
+        }
+
+        assert_eq!(messages(pipeline.pop_all().unwrap()), ["2", "3", "4"]);
+        assert_eq!(pipeline.dropped(), 2);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_earliest_records() {
+        let pipeline = full_pipeline(OverflowPolicy::DropNewest);
+        for i in 3..5 {
+            assert!(pipeline.push(record(i)).is_none());
+        }
+
+        assert_eq!(messages(pipeline.pop_all().unwrap()), ["0", "1", "2"]);
+        assert_eq!(pipeline.dropped(), 2);
+    }
+
+    #[test]
+    fn block_waits_for_room_and_keeps_everything() {
+        let pipeline = Arc::new(full_pipeline(OverflowPolicy::Block));
+        let producer = thread::spawn({
+            let pipeline = pipeline.clone();
+            move || {
+                for i in 3..5 {
+                    assert!(pipeline.push(record(i)).is_none());
+                }
+            }
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!producer.is_finished());
+
+        assert_eq!(messages(pipeline.pop_all().unwrap()), ["0", "1", "2"]);
+        producer.join().unwrap();
+        assert_eq!(messages(pipeline.pop_all().unwrap()), ["3", "4"]);
+        assert_eq!(pipeline.dropped(), 0);
+    }
+
+    #[test]
+    fn closing_hands_blocked_records_back() {
+        let pipeline = Arc::new(full_pipeline(OverflowPolicy::Block));
+        let producer = thread::spawn({
+            let pipeline = pipeline.clone();
+            move || pipeline.push(record(3))
+        });
+
+        thread::sleep(Duration::from_millis(50));
+        pipeline.close();
+        let returned = producer.join().unwrap();
+        assert_eq!(returned.map(|record| record.message).as_deref(), Some("3"));
+        assert_eq!(pipeline.dropped(), 0);
+    }
+}
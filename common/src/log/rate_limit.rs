@@ -0,0 +1,81 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+/// Per-callsite state behind `log_once!` and friends.
+pub struct Once(AtomicBool);
+
+impl Once {
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    /// Returns true the first time it is called.
+    pub fn first(&self) -> bool {
+        !self.0.swap(true, Ordering::Relaxed)
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-callsite state behind `log_every!` and friends.
+pub struct EveryN(AtomicU64);
+
+impl EveryN {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    /// Returns true on the first call and every `n`th call after it.
+    pub fn tick(&self, n: u64) -> bool {
+        self.0
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(n.max(1))
+    }
+}
+
+impl Default for EveryN {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Per-callsite state behind `log_throttled!` and friends.
+pub struct Throttle(AtomicU64);
+
+const NEVER: u64 = u64::MAX;
+
+impl Throttle {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(NEVER))
+    }
+
+    /// Returns true if at least `interval` has passed since it last returned true.
+    pub fn ready(&self, interval: Duration) -> bool {
+        static EPOCH: OnceLock<Instant> = OnceLock::new();
+        let now = EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64;
+
+        let last = self.0.load(Ordering::Relaxed);
+        if last != NEVER && now.saturating_sub(last) < interval.as_nanos() as u64 {
+            return false;
+        }
+
+        self.0
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    }
+}
+
+impl Default for Throttle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
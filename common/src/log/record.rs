@@ -0,0 +1,164 @@
+use std::{
+    fmt::Display,
+    sync::Arc,
+    thread::{self, ThreadId},
+    time::{Instant, SystemTime},
+};
+
+use tracing::Level;
+
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: &'static str,
+    pub timestamp: SystemTime,
+    pub message: String,
+    pub fields: Vec<Field>,
+    pub file: Option<&'static str>,
+    pub line: Option<u32>,
+    pub thread_id: ThreadId,
+}
+
+impl LogRecord {
+    pub fn new<S: Into<String>>(level: Level, target: &'static str, message: S) -> Self {
+        Self {
+            level,
+            target,
+            timestamp: SystemTime::now(),
+            message: message.into(),
+            fields: Vec::new(),
+            file: None,
+            line: None,
+            thread_id: thread::current().id(),
+        }
+    }
+
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        self.fields
+            .iter()
+            .find(|field| field.name == name)
+            .map(|field| &field.value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub value: FieldValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    F64(f64),
+    I64(i64),
+    U64(u64),
+    I128(i128),
+    U128(u128),
+    Bool(bool),
+    Str(String),
+    Error(String),
+    Debug(String),
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::F64(value) => write!(f, "{value}"),
+            FieldValue::I64(value) => write!(f, "{value}"),
+            FieldValue::U64(value) => write!(f, "{value}"),
+            FieldValue::I128(value) => write!(f, "{value}"),
+            FieldValue::U128(value) => write!(f, "{value}"),
+            FieldValue::Bool(value) => write!(f, "{value}"),
+            FieldValue::Str(value) => write!(f, "{value}"),
+            FieldValue::Error(value) => write!(f, "{value}"),
+            FieldValue::Debug(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(value: f64) -> Self {
+        FieldValue::F64(value)
+    }
+}
+
+impl From<f32> for FieldValue {
+    fn from(value: f32) -> Self {
+        FieldValue::F64(value.into())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(value: i64) -> Self {
+        FieldValue::I64(value)
+    }
+}
+
+impl From<i32> for FieldValue {
+    fn from(value: i32) -> Self {
+        FieldValue::I64(value.into())
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(value: u64) -> Self {
+        FieldValue::U64(value)
+    }
+}
+
+impl From<u32> for FieldValue {
+    fn from(value: u32) -> Self {
+        FieldValue::U64(value.into())
+    }
+}
+
+impl From<usize> for FieldValue {
+    fn from(value: usize) -> Self {
+        FieldValue::U64(value as u64)
+    }
+}
+
+impl From<i128> for FieldValue {
+    fn from(value: i128) -> Self {
+        FieldValue::I128(value)
+    }
+}
+
+impl From<u128> for FieldValue {
+    fn from(value: u128) -> Self {
+        FieldValue::U128(value)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(value: bool) -> Self {
+        FieldValue::Bool(value)
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(value: String) -> Self {
+        FieldValue::Str(value)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(value: &str) -> Self {
+        FieldValue::Str(value.to_string())
+    }
+}
+
+/// A span being entered or exited. Span fields are captured once when the span is created, so
+/// every enter and exit of the same span shares them.
+#[derive(Debug, Clone)]
+pub struct SpanRecord {
+    pub id: u64,
+    pub name: &'static str,
+    pub level: Level,
+    pub target: &'static str,
+    pub timestamp: Instant,
+    pub fields: Arc<Vec<Field>>,
+    pub file: Option<&'static str>,
+    pub line: Option<u32>,
+    pub thread_id: ThreadId,
+}
@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use crate::log::{Field, FieldValue, LogRecord};
+
+type Scrubber = Arc<dyn Fn(&mut LogRecord) + Send + Sync>;
+
+/// Masks sensitive fields before records reach any sink, e.g. so network sinks don't ship player
+/// PII. Install it with [`set_redactor`](crate::log::set_redactor).
+#[derive(Clone)]
+pub struct Redactor {
+    patterns: Vec<String>,
+    mask: String,
+    scrubbers: Vec<Scrubber>,
+}
+
+impl Redactor {
+    pub fn new() -> Self {
+        Self {
+            patterns: Vec::new(),
+            mask: "[REDACTED]".to_string(),
+            scrubbers: Vec::new(),
+        }
+    }
+
+    /// Masks fields whose name matches `pattern`, ignoring case. `*` matches any run of
+    /// characters, so `*token*` catches `auth_token` and `TokenId`.
+    pub fn key<S: Into<String>>(self, pattern: S) -> Self {
+        let mut patterns = self.patterns;
+        patterns.push(pattern.into().to_ascii_lowercase());
+        Self { patterns, ..self }
+    }
+
+    /// The value masked fields are replaced with. Defaults to `[REDACTED]`.
+    pub fn mask<S: Into<String>>(self, mask: S) -> Self {
+        Self {
+            mask: mask.into(),
+            ..self
+        }
+    }
+
+    /// Runs `scrub` on every record after key masking, for redaction that field names can't
+    /// express, such as emails inside messages.
+    pub fn scrub<F: Fn(&mut LogRecord) + Send + Sync + 'static>(self, scrub: F) -> Self {
+        let mut scrubbers = self.scrubbers;
+        scrubbers.push(Arc::new(scrub));
+        Self { scrubbers, ..self }
+    }
+
+    pub(crate) fn redact(&self, record: &mut LogRecord) {
+        self.redact_fields(&mut record.fields);
+        for scrub in &self.scrubbers {
+            scrub(record);
+        }
+    }
+
+    pub(crate) fn redact_fields(&self, fields: &mut [Field]) {
+        for field in fields {
+            if self.matches(field.name) {
+                field.value = FieldValue::Str(self.mask.clone());
+            }
+        }
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        let name = name.to_ascii_lowercase();
+        self.patterns
+            .iter()
+            .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+    }
+}
+
+impl Default for Redactor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of bytes.
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        glob_match(pattern.as_bytes(), text.as_bytes())
+    }
+
+    #[test]
+    fn matches_stars_anywhere() {
+        assert!(matches("*token", "access_token"));
+        assert!(matches("*token", "token"));
+        assert!(matches("pass*word", "password"));
+        assert!(matches("pass*word", "pass_hash_word"));
+        assert!(matches("secret*", "secret_key"));
+        assert!(matches("*key*", "api_key_id"));
+        assert!(matches("*", "anything"));
+    }
+
+    #[test]
+    fn matches_consecutive_stars_as_one() {
+        assert!(matches("a**b", "ab"));
+        assert!(matches("a**b", "a_x_b"));
+        assert!(matches("**", "ab"));
+        assert!(!matches("a**b", "a_x_c"));
+    }
+
+    #[test]
+    fn matches_empty_pattern_and_text() {
+        assert!(matches("", ""));
+        assert!(!matches("", "token"));
+        assert!(matches("*", ""));
+        assert!(matches("**", ""));
+        assert!(!matches("token", ""));
+        assert!(!matches("*a", ""));
+    }
+
+    #[test]
+    fn rejects_non_matches() {
+        assert!(!matches("token", "tokens"));
+        assert!(!matches("token", "toke"));
+        assert!(!matches("*token", "token_id"));
+        assert!(!matches("secret*", "my_secret"));
+        assert!(!matches("pass*word", "password_hint"));
+        assert!(!matches("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn backtracks_past_partial_matches() {
+        assert!(matches("*ab", "aab"));
+        assert!(matches("*abc", "ababc"));
+        assert!(matches("a*bc", "abcbc"));
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+use tracing::Level;
+
+/// Keeps one in every `keep_one_in` events per call site for targets matching a rule.
+pub(crate) struct Sampler {
+    enabled: AtomicBool,
+    rules: RwLock<Vec<SampleRule>>,
+    counts: Mutex<HashMap<CallsiteKey, u64>>,
+}
+
+/// Identifies a call site: its metadata address for `tracing` events, or the interned target and
+/// line for records bridged from the `log` crate, which all share a few callsites.
+pub(crate) type CallsiteKey = (usize, u32);
+
+#[derive(Debug, Clone)]
+pub(crate) struct SampleRule {
+    pub(crate) target: String,
+    pub(crate) level: Level,
+    pub(crate) keep_one_in: u64,
+}
+
+impl Sampler {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            rules: RwLock::new(Vec::new()),
+            counts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Adds or replaces the rule for `target`. A rate of 0 or 1 removes it.
+    pub(crate) fn set(&self, rule: SampleRule) {
+        let mut rules = self.rules.write().unwrap();
+        rules.retain(|existing| existing.target != rule.target);
+        if rule.keep_one_in > 1 {
+            rules.push(rule);
+        }
+        self.enabled.store(!rules.is_empty(), Ordering::Release);
+        self.counts.lock().unwrap().clear();
+    }
+
+    pub(crate) fn replace(&self, new_rules: Vec<SampleRule>) {
+        let mut rules = self.rules.write().unwrap();
+        *rules = new_rules;
+        rules.retain(|rule| rule.keep_one_in > 1);
+        self.enabled.store(!rules.is_empty(), Ordering::Release);
+        self.counts.lock().unwrap().clear();
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Whether an event at `level` from `target` should be kept. The first event from each call
+    /// site is always kept.
+    pub(crate) fn keep(&self, target: &str, level: &Level, callsite: CallsiteKey) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        // Like directives, the most specific matching target wins.
+        let rules = self.rules.read().unwrap();
+        let Some(rule) = rules
+            .iter()
+            .filter(|rule| matches_target(&rule.target, target))
+            .max_by_key(|rule| rule.target.len())
+        else {
+            return true;
+        };
+        // More verbose levels compare greater, so a TRACE rule leaves DEBUG alone.
+        if *level < rule.level {
+            return true;
+        }
+
+        let mut counts = self.counts.lock().unwrap();
+        let count = counts.entry(callsite).or_insert(0);
+        let keep = count.is_multiple_of(rule.keep_one_in);
+        *count += 1;
+        keep
+    }
+}
+
+/// Whether `target` is `prefix` or one of its children, matching on `::` boundaries.
+pub(crate) fn matches_target(prefix: &str, target: &str) -> bool {
+    match target.strip_prefix(prefix) {
+        Some(rest) => prefix.is_empty() || rest.is_empty() || rest.starts_with("::"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampler(keep_one_in: u64) -> Sampler {
+        let sampler = Sampler::new();
+        sampler.set(SampleRule {
+            target: "game::physics".to_string(),
+            level: Level::DEBUG,
+            keep_one_in,
+        });
+        sampler
+    }
+
+    /// Which of `events` debug events from one call site are kept.
+    fn kept(sampler: &Sampler, events: usize) -> Vec<bool> {
+        (0..events)
+            .map(|_| sampler.keep("game::physics::step", &Level::DEBUG, (1, 0)))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_one_in_n_starting_with_the_first() {
+        let kept = kept(&sampler(3), 10);
+        assert_eq!(
+            kept,
+            [true, false, false, true, false, false, true, false, false, true]
+        );
+        assert_eq!(kept.iter().filter(|kept| **kept).count(), 4);
+        assert_eq!(kept.iter().filter(|kept| !**kept).count(), 6);
+    }
+
+    #[test]
+    fn keeps_everything_at_rates_zero_and_one() {
+        for keep_one_in in [0, 1] {
+            let sampler = sampler(keep_one_in);
+            assert!(!sampler.is_enabled());
+            assert!(kept(&sampler, 10).iter().all(|kept| *kept));
+        }
+    }
+
+    #[test]
+    fn counts_each_call_site_separately() {
+        let sampler = sampler(2);
+        let keep = |callsite| sampler.keep("game::physics", &Level::DEBUG, callsite);
+        assert_eq!(
+            [keep((1, 0)), keep((2, 0)), keep((1, 0)), keep((2, 0))],
+            [true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn leaves_other_targets_and_less_verbose_levels_alone() {
+        let sampler = sampler(1000);
+        for _ in 0..10 {
+            assert!(sampler.keep("game::physicsx", &Level::DEBUG, (1, 0)));
+            assert!(sampler.keep("game::physics", &Level::INFO, (2, 0)));
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use tracing::level_filters::LevelFilter;
+
+use crate::log::{sampling::matches_target, LogRecord, SpanRecord};
+
+type RecordFilter = Arc<dyn Fn(&LogRecord) -> bool + Send + Sync>;
+
+/// Per-sink filtering applied by the logger before a record reaches the sink.
+#[derive(Clone)]
+pub struct SinkConfig {
+    pub(crate) name: Option<String>,
+    pub(crate) enabled: bool,
+    pub(crate) level: LevelFilter,
+    pub(crate) filter: Option<RecordFilter>,
+    pub(crate) targets: Vec<String>,
+    pub(crate) disable_after: Option<u64>,
+}
+
+impl SinkConfig {
+    pub fn new() -> Self {
+        Self {
+            name: None,
+            enabled: true,
+            level: LevelFilter::TRACE,
+            filter: None,
+            targets: Vec::new(),
+            disable_after: None,
+        }
+    }
+
+    /// Names the sink so runtime configuration can refer to it, e.g. `console = false` under
+    /// `[sinks]` in a logging config file.
+    pub fn name<S: Into<String>>(self, name: S) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    pub fn enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    pub fn level(self, level: LevelFilter) -> Self {
+        Self { level, ..self }
+    }
+
+    /// Only records for which `filter` returns true are passed to the sink. Spans are filtered by
+    /// level alone.
+    pub fn filter<F>(self, filter: F) -> Self
+    where
+        F: Fn(&LogRecord) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            filter: Some(Arc::new(filter)),
+            ..self
+        }
+    }
+
+    /// Only passes records and spans whose target is `target` or one of its children, e.g.
+    /// `galleon::renderer` also matches `galleon::renderer::pass`. Call it once per target to
+    /// allow several.
+    pub fn target<S: Into<String>>(self, target: S) -> Self {
+        let mut targets = self.targets;
+        targets.push(target.into());
+        Self { targets, ..self }
+    }
+
+    /// Stops sending records to the sink after `failures` consecutive errors. It stays disabled
+    /// until `reset_sink_health` is called for it.
+    pub fn disable_after(self, failures: u64) -> Self {
+        Self {
+            disable_after: Some(failures.max(1)),
+            ..self
+        }
+    }
+
+    pub(crate) fn accepts_span(&self, span: &SpanRecord) -> bool {
+        self.enabled && span.level <= self.level && self.accepts_target(span.target)
+    }
+
+    pub(crate) fn accepts(&self, record: &LogRecord) -> bool {
+        self.enabled
+            && record.level <= self.level
+            && self.accepts_target(record.target)
+            && self.filter.as_ref().is_none_or(|filter| filter(record))
+    }
+
+    fn accepts_target(&self, target: &str) -> bool {
+        self.targets.is_empty()
+            || self
+                .targets
+                .iter()
+                .any(|prefix| matches_target(prefix, target))
+    }
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
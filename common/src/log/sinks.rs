@@ -0,0 +1,26 @@
+pub use self::{
+    binary::BinarySink,
+    console::ConsoleSink,
+    file::{FileSink, FileSinkBuilder, Rotation},
+    http::{HttpFormat, HttpSink, HttpSinkBuilder},
+    json::JsonSink,
+    net::{NetEncoding, NetProtocol, NetSink, NetSinkBuilder},
+    ring_buffer::RingBufferSink,
+    span_stats::{SpanStats, SpanStatsSink},
+    trace_export::TraceExportSink,
+};
+
+#[cfg(feature = "tracy")]
+pub use self::tracy::TracySink;
+
+mod binary;
+mod console;
+mod file;
+mod http;
+mod json;
+mod net;
+mod ring_buffer;
+pub(crate) mod span_stats;
+mod trace_export;
+#[cfg(feature = "tracy")]
+mod tracy;
@@ -0,0 +1,67 @@
+use std::{
+    io::{BufWriter, Write},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::{Context, Error},
+    log::{binary::BinaryEncoder, LogRecord, Sink},
+};
+
+/// Writes records in the compact binary format. Decode the output with `galleon-logcat`.
+#[derive(Clone)]
+pub struct BinarySink {
+    inner: Arc<Mutex<BinarySinkInner>>,
+}
+
+struct BinarySinkInner {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    encoder: BinaryEncoder,
+    buffer: Vec<u8>,
+}
+
+impl BinarySink {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        let encoder = BinaryEncoder::new();
+        let mut buffer = Vec::with_capacity(512);
+        encoder.header(&mut buffer);
+
+        let mut writer = BufWriter::new(Box::new(writer) as Box<dyn Write + Send>);
+        _ = writer.write_all(&buffer);
+        buffer.clear();
+
+        Self {
+            inner: Arc::new(Mutex::new(BinarySinkInner {
+                writer,
+                encoder,
+                buffer,
+            })),
+        }
+    }
+}
+
+impl Sink for BinarySink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let BinarySinkInner {
+            writer,
+            encoder,
+            buffer,
+        } = &mut *inner;
+
+        buffer.clear();
+        encoder.encode(record, buffer);
+        writer
+            .write_all(buffer)
+            .context("failed to write binary log record")
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        self.inner
+            .lock()
+            .unwrap()
+            .writer
+            .flush()
+            .context("failed to flush binary log")
+    }
+}
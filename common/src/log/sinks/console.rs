@@ -0,0 +1,92 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use tracing::Level;
+
+use crate::{
+    error::{Context, Error},
+    log::{
+        format::{CompactFormatter, Formatter},
+        LogRecord, Sink,
+    },
+};
+
+/// Writes records to stdout, or stderr for WARN and ERROR, colored by level when the output is a
+/// terminal.
+#[derive(Clone)]
+pub struct ConsoleSink {
+    formatter: Arc<dyn Formatter>,
+    colors: Arc<AtomicBool>,
+}
+
+impl ConsoleSink {
+    pub fn new() -> Self {
+        Self {
+            formatter: Arc::new(CompactFormatter),
+            colors: Arc::new(AtomicBool::new(io::stdout().is_terminal())),
+        }
+    }
+
+    pub fn with_formatter<F: Formatter + 'static>(self, formatter: F) -> Self {
+        Self {
+            formatter: Arc::new(formatter),
+            ..self
+        }
+    }
+
+    /// Overrides terminal detection, e.g. when the console only supports escape sequences after
+    /// virtual terminal processing has been enabled.
+    pub fn set_colors(&self, enabled: bool) {
+        self.colors.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl Default for ConsoleSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sink for ConsoleSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let colors = self.colors.load(Ordering::Relaxed);
+        let mut line = String::with_capacity(128);
+        if colors {
+            line.push_str(color(&record.level));
+        }
+        self.formatter.format(record, &mut line);
+        if colors {
+            line.push_str("\x1b[0m");
+        }
+        line.push('\n');
+
+        let written = if record.level <= Level::WARN {
+            io::stderr().lock().write_all(line.as_bytes())
+        } else {
+            io::stdout().lock().write_all(line.as_bytes())
+        };
+        written.context("failed to write to the console")
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        io::stdout()
+            .flush()
+            .and_then(|()| io::stderr().flush())
+            .context("failed to flush the console")
+    }
+}
+
+fn color(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "\x1b[31m",
+        Level::WARN => "\x1b[33m",
+        Level::INFO => "\x1b[32m",
+        Level::DEBUG => "\x1b[34m",
+        Level::TRACE => "\x1b[35m",
+    }
+}
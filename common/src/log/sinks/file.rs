@@ -0,0 +1,301 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::Error,
+    log::{
+        format::{Formatter, FullFormatter},
+        timestamp::Timestamp,
+        LogRecord, Sink,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    Daily,
+}
+
+pub struct FileSinkBuilder {
+    directory: PathBuf,
+    prefix: String,
+    max_file_size: Option<u64>,
+    rotation: Rotation,
+    max_files: usize,
+    formatter: Arc<dyn Formatter>,
+}
+
+impl FileSinkBuilder {
+    pub fn max_file_size(self, bytes: u64) -> Self {
+        Self {
+            max_file_size: Some(bytes),
+            ..self
+        }
+    }
+
+    pub fn rotation(self, rotation: Rotation) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// The number of rotated files kept alongside the active log file.
+    pub fn max_files(self, max_files: usize) -> Self {
+        Self { max_files, ..self }
+    }
+
+    pub fn formatter<F: Formatter + 'static>(self, formatter: F) -> Self {
+        Self {
+            formatter: Arc::new(formatter),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<FileSink, Error> {
+        fs::create_dir_all(&self.directory).map_err(|err| {
+            Error::new(format!(
+                "failed to create log directory {}",
+                self.directory.display()
+            ))
+            .with_source(err)
+        })?;
+
+        let path = self.directory.join(format!("{}.log", self.prefix));
+        let (writer, size) = open(&path)?;
+        let inner = FileSinkInner {
+            directory: self.directory,
+            prefix: self.prefix,
+            max_file_size: self.max_file_size,
+            rotation: self.rotation,
+            max_files: self.max_files,
+            writer: Some(writer),
+            size,
+            date: Timestamp::from_system_time(std::time::SystemTime::now()).date(),
+        };
+
+        Ok(FileSink {
+            formatter: self.formatter,
+            inner: Arc::new(Mutex::new(inner)),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct FileSink {
+    formatter: Arc<dyn Formatter>,
+    inner: Arc<Mutex<FileSinkInner>>,
+}
+
+struct FileSinkInner {
+    directory: PathBuf,
+    prefix: String,
+    max_file_size: Option<u64>,
+    rotation: Rotation,
+    max_files: usize,
+    writer: Option<BufWriter<File>>,
+    size: u64,
+    date: (i64, u32, u32),
+}
+
+impl FileSink {
+    pub fn builder<P: Into<PathBuf>, S: Into<String>>(directory: P, prefix: S) -> FileSinkBuilder {
+        FileSinkBuilder {
+            directory: directory.into(),
+            prefix: prefix.into(),
+            max_file_size: None,
+            rotation: Rotation::Never,
+            max_files: 5,
+            formatter: Arc::new(FullFormatter),
+        }
+    }
+}
+
+impl FileSinkInner {
+    fn path(&self, index: usize) -> PathBuf {
+        if index == 0 {
+            self.directory.join(format!("{}.log", self.prefix))
+        } else {
+            self.directory
+                .join(format!("{}.{}.log", self.prefix, index))
+        }
+    }
+
+    fn needs_rotation(&self, date: (i64, u32, u32), incoming: u64) -> bool {
+        let size_exceeded =
+            matches!(self.max_file_size, Some(max) if self.size > 0 && self.size + incoming > max);
+        let day_changed = self.rotation == Rotation::Daily && date != self.date;
+
+        size_exceeded || day_changed
+    }
+
+    fn rotate(&mut self) -> Result<(), Error> {
+        if let Some(mut writer) = self.writer.take() {
+            _ = writer.flush();
+        }
+
+        if self.max_files == 0 {
+            _ = fs::remove_file(self.path(0));
+        } else {
+            _ = fs::remove_file(self.path(self.max_files));
+            for index in (0..self.max_files).rev() {
+                let from = self.path(index);
+                if from.exists() {
+                    _ = fs::rename(&from, self.path(index + 1));
+                }
+            }
+        }
+
+        let (writer, size) = open(&self.path(0))?;
+        self.writer = Some(writer);
+        self.size = size;
+
+        Ok(())
+    }
+
+    fn write(&mut self, line: &str, date: (i64, u32, u32)) -> Result<(), Error> {
+        let rotate = self.needs_rotation(date, line.len() as u64);
+        self.date = date;
+        if rotate {
+            self.rotate()?;
+        }
+
+        let Some(writer) = self.writer.as_mut() else {
+            return Err(Error::new(format!(
+                "log file {} is not open",
+                self.path(0).display()
+            )));
+        };
+        writer
+            .write_all(line.as_bytes())
+            .map_err(|err| Error::new("failed to write to log file").with_source(err))?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+}
+
+impl Sink for FileSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let mut line = String::with_capacity(256);
+        self.formatter.format(record, &mut line);
+        line.push('\n');
+
+        let date = Timestamp::from_system_time(record.timestamp).date();
+        self.inner.lock().unwrap().write(&line, date)
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        match self.inner.lock().unwrap().writer.as_mut() {
+            Some(writer) => writer
+                .flush()
+                .map_err(|err| Error::new("failed to flush log file").with_source(err)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<(BufWriter<File>, u64), Error> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| {
+            Error::new(format!("failed to open log file {}", path.display())).with_source(err)
+        })?;
+    let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+    Ok((BufWriter::new(file), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use super::*;
+
+    /// Writes only the message, so each line's size is known.
+    struct MessageFormatter;
+
+    impl Formatter for MessageFormatter {
+        fn format(&self, record: &LogRecord, out: &mut String) {
+            out.push_str(&record.message);
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("galleon-file-sink-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = test_dir("size");
+        let sink = FileSink::builder(&dir, "game")
+            .max_file_size(100)
+            .max_files(2)
+            .formatter(MessageFormatter)
+            .build()
+            .unwrap();
+
+        // 30 bytes a line with the newline, so three fit in each file.
+        for i in 0..10 {
+            sink.log(&LogRecord::new(
+                Level::INFO,
+                "test",
+                format!("line {i:02}{}", "-".repeat(22)),
+            ))
+            .unwrap();
+        }
+        sink.flush().unwrap();
+
+        assert_eq!(file_names(&dir), ["game.1.log", "game.2.log", "game.log"]);
+        let lines = |name: &str| -> Vec<String> {
+            fs::read_to_string(dir.join(name))
+                .unwrap()
+                .lines()
+                .map(|line| line[..7].to_string())
+                .collect()
+        };
+        assert_eq!(lines("game.log"), ["line 09"]);
+        assert_eq!(lines("game.1.log"), ["line 06", "line 07", "line 08"]);
+        assert_eq!(lines("game.2.log"), ["line 03", "line 04", "line 05"]);
+
+        drop(sink);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotation_without_retained_files_truncates() {
+        let dir = test_dir("no-retained");
+        let sink = FileSink::builder(&dir, "game")
+            .max_file_size(10)
+            .max_files(0)
+            .formatter(MessageFormatter)
+            .build()
+            .unwrap();
+
+        for message in ["first", "second", "third"] {
+            sink.log(&LogRecord::new(Level::INFO, "test", message))
+                .unwrap();
+        }
+        sink.flush().unwrap();
+
+        assert_eq!(file_names(&dir), ["game.log"]);
+        assert_eq!(fs::read_to_string(dir.join("game.log")).unwrap(), "third\n");
+
+        drop(sink);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
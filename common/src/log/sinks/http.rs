@@ -0,0 +1,818 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+
+use tracing::Level;
+
+use crate::{
+    error::Error,
+    gzip, json,
+    log::{
+        format::{Formatter, JsonFormatter},
+        FieldValue, LogRecord, Sink,
+    },
+};
+
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a dropped sink keeps sending what's still queued.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The log shipping API to POST batches to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpFormat {
+    /// Grafana Loki's push API, e.g. `http://localhost:3100/loki/api/v1/push`. Each record is a
+    /// JSON line in a single stream with the configured labels.
+    Loki,
+    /// OTLP logs over HTTP with JSON encoding, e.g. `http://localhost:4318/v1/logs`. Labels become
+    /// resource attributes.
+    Otlp,
+}
+
+pub struct HttpSinkBuilder {
+    url: String,
+    format: HttpFormat,
+    labels: Vec<(String, String)>,
+    batch_size: usize,
+    batch_interval: Duration,
+    max_queued: usize,
+    max_retries: u32,
+    compress: bool,
+}
+
+impl HttpSinkBuilder {
+    /// Adds a Loki stream label or OTLP resource attribute. Without any, the service name is set to
+    /// `galleon`.
+    pub fn label<K: Into<String>, V: Into<String>>(self, key: K, value: V) -> Self {
+        let mut labels = self.labels;
+        labels.push((key.into(), value.into()));
+        Self { labels, ..self }
+    }
+
+    /// Sends a batch once this many records are queued.
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    /// Sends whatever is queued at least this often.
+    pub fn batch_interval(self, batch_interval: Duration) -> Self {
+        Self {
+            batch_interval,
+            ..self
+        }
+    }
+
+    /// The number of records kept while the endpoint is unreachable. The oldest are dropped once
+    /// it's full.
+    pub fn max_queued(self, max_queued: usize) -> Self {
+        Self {
+            max_queued: max_queued.max(1),
+            ..self
+        }
+    }
+
+    /// How many times a failed batch is retried before it's dropped.
+    pub fn max_retries(self, max_retries: u32) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    /// Whether request bodies are gzip compressed. On by default.
+    pub fn compress(self, compress: bool) -> Self {
+        Self { compress, ..self }
+    }
+
+    pub fn build(self) -> Result<HttpSink, Error> {
+        let endpoint = Endpoint::parse(&self.url)?;
+        let labels = if self.labels.is_empty() {
+            let key = match self.format {
+                HttpFormat::Loki => "service_name",
+                HttpFormat::Otlp => "service.name",
+            };
+            vec![(key.to_string(), "galleon".to_string())]
+        } else {
+            self.labels
+        };
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(BatchState {
+                queue: VecDeque::with_capacity(self.batch_size),
+                sending: false,
+                flush_requested: false,
+                drain_deadline: None,
+                failure: None,
+            }),
+            changed: Condvar::new(),
+            batch_size: self.batch_size,
+            max_queued: self.max_queued,
+        });
+
+        let worker = {
+            let shared = shared.clone();
+            let shipper = Shipper {
+                endpoint,
+                format: self.format,
+                labels,
+                batch_interval: self.batch_interval,
+                max_retries: self.max_retries,
+                compress: self.compress,
+            };
+            thread::Builder::new()
+                .name("galleon-http-sink".to_string())
+                .spawn(move || shipper.run(&shared))
+                .map_err(|err| Error::new("failed to spawn http sink thread").with_source(err))?
+        };
+
+        Ok(HttpSink {
+            inner: Arc::new(HttpSinkInner {
+                shared,
+                worker: Some(worker),
+            }),
+        })
+    }
+}
+
+/// Batches records and POSTs them to a Loki or OTLP endpoint from a background thread, retrying
+/// failed batches. On Windows requests go through WinHTTP, so `https://` works with the system's
+/// certificates and proxy; elsewhere only plain `http://` is supported.
+#[derive(Clone)]
+pub struct HttpSink {
+    inner: Arc<HttpSinkInner>,
+}
+
+impl HttpSink {
+    pub fn builder<S: Into<String>>(url: S, format: HttpFormat) -> HttpSinkBuilder {
+        HttpSinkBuilder {
+            url: url.into(),
+            format,
+            labels: Vec::new(),
+            batch_size: 256,
+            batch_interval: Duration::from_secs(2),
+            max_queued: 8192,
+            max_retries: 3,
+            compress: true,
+        }
+    }
+}
+
+struct HttpSinkInner {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for HttpSinkInner {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().drain_deadline = Some(Instant::now() + DRAIN_TIMEOUT);
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<BatchState>,
+    changed: Condvar,
+    batch_size: usize,
+    max_queued: usize,
+}
+
+impl Shared {
+    /// Waits `delay` before a retry, returning early with the drain deadline if the sink is
+    /// dropped meanwhile.
+    fn backoff(&self, delay: Duration) -> Option<Instant> {
+        let state = self.state.lock().unwrap();
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, delay, |state| state.drain_deadline.is_none())
+            .unwrap();
+        state.drain_deadline
+    }
+}
+
+struct BatchState {
+    queue: VecDeque<LogRecord>,
+    sending: bool,
+    flush_requested: bool,
+    /// Set when the sink is dropped. Whatever's queued is sent until then, without retries.
+    drain_deadline: Option<Instant>,
+    /// Why the last batch was dropped, reported by the next flush.
+    failure: Option<String>,
+}
+
+impl Sink for HttpSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let shared = &self.inner.shared;
+        let mut state = shared.state.lock().unwrap();
+        let overflowed = state.queue.len() >= shared.max_queued;
+        if overflowed {
+            state.queue.pop_front();
+        }
+        state.queue.push_back(record.clone());
+        if state.queue.len() >= shared.batch_size {
+            shared.changed.notify_all();
+        }
+
+        if overflowed {
+            return Err(Error::new(
+                "http sink queue is full, dropped the oldest record",
+            ));
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        let shared = &self.inner.shared;
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let mut state = shared.state.lock().unwrap();
+        state.flush_requested = true;
+        shared.changed.notify_all();
+        while (!state.queue.is_empty() || state.sending) && state.drain_deadline.is_none() {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::new("timed out flushing http sink"));
+            }
+            state = shared
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        match state.failure.take() {
+            Some(failure) => Err(Error::new(failure)),
+            None => Ok(()),
+        }
+    }
+}
+
+struct Shipper {
+    endpoint: Endpoint,
+    format: HttpFormat,
+    labels: Vec<(String, String)>,
+    batch_interval: Duration,
+    max_retries: u32,
+    compress: bool,
+}
+
+impl Shipper {
+    fn run(&self, shared: &Shared) {
+        loop {
+            let (batch, drain_deadline) = {
+                let deadline = Instant::now() + self.batch_interval;
+                let mut state = shared.state.lock().unwrap();
+                while state.queue.len() < shared.batch_size
+                    && !state.flush_requested
+                    && state.drain_deadline.is_none()
+                {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    state = shared
+                        .changed
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+
+                state.flush_requested = false;
+                state.sending = true;
+                let count = state.queue.len().min(shared.batch_size);
+                let batch: Vec<_> = state.queue.drain(..count).collect();
+                (batch, state.drain_deadline)
+            };
+
+            // note: failures aren't logged, since they'd be queued for this sink too. They're
+            // reported from the next flush instead.
+            let result = if batch.is_empty() {
+                Ok(())
+            } else {
+                self.send(shared, &batch, drain_deadline)
+            };
+
+            let mut state = shared.state.lock().unwrap();
+            state.sending = false;
+            if let Err(err) = result {
+                state.failure = Some(format!(
+                    "dropped {} records: {}",
+                    batch.len(),
+                    err.full_message()
+                ));
+            }
+            shared.changed.notify_all();
+            if let Some(deadline) = drain_deadline {
+                if state.queue.is_empty() || Instant::now() >= deadline {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Posts `batch`, retrying failures with backoff. Once the sink is closed, it's only tried
+    /// once more, within the drain deadline.
+    fn send(
+        &self,
+        shared: &Shared,
+        batch: &[LogRecord],
+        drain_deadline: Option<Instant>,
+    ) -> Result<(), Error> {
+        let body = match self.format {
+            HttpFormat::Loki => self.loki_body(batch),
+            HttpFormat::Otlp => self.otlp_body(batch),
+        };
+        let body = if self.compress {
+            gzip::compress(body.as_bytes())
+        } else {
+            body.into_bytes()
+        };
+
+        let mut attempt = 0;
+        let mut drain_deadline = drain_deadline;
+        loop {
+            let timeout = match drain_deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => IO_TIMEOUT,
+            };
+            if timeout.is_zero() {
+                return Err(Error::new("timed out sending logs while closing"));
+            }
+
+            let err = match self
+                .endpoint
+                .post(&body, self.compress, timeout.min(IO_TIMEOUT))
+            {
+                Ok(status) if (200..300).contains(&status) => return Ok(()),
+                // Client errors other than rate limiting won't succeed on retry.
+                Ok(status) if (400..500).contains(&status) && status != 429 => {
+                    return Err(Error::new(format!("log endpoint rejected batch: {status}")));
+                }
+                Ok(status) => Error::new(format!("log endpoint returned {status}")),
+                Err(err) => err,
+            };
+            if attempt >= self.max_retries || drain_deadline.is_some() {
+                return Err(err);
+            }
+
+            drain_deadline = shared.backoff(RETRY_BACKOFF * 2u32.pow(attempt));
+            attempt += 1;
+        }
+    }
+
+    fn loki_body(&self, batch: &[LogRecord]) -> String {
+        let mut body = String::with_capacity(256 * batch.len());
+        let mut line = String::with_capacity(256);
+        body.push_str("{\"streams\":[{\"stream\":{");
+        for (i, (key, value)) in self.labels.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            json::write_str(&mut body, key);
+            body.push(':');
+            json::write_str(&mut body, value);
+        }
+        body.push_str("},\"values\":[");
+        for (i, record) in batch.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            line.clear();
+            JsonFormatter.format(record, &mut line);
+            _ = write!(body, "[\"{}\",", unix_nanos(record));
+            json::write_str(&mut body, &line);
+            body.push(']');
+        }
+        body.push_str("]}]}");
+        body
+    }
+
+    fn otlp_body(&self, batch: &[LogRecord]) -> String {
+        let mut body = String::with_capacity(256 * batch.len());
+        body.push_str("{\"resourceLogs\":[{\"resource\":{\"attributes\":[");
+        for (i, (key, value)) in self.labels.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            write_otlp_attribute(&mut body, key, &FieldValue::Str(value.clone()));
+        }
+        body.push_str("]},\"scopeLogs\":[{\"scope\":{\"name\":\"galleon\"},\"logRecords\":[");
+        for (i, record) in batch.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            let (severity, text) = otlp_severity(&record.level);
+            _ = write!(
+                body,
+                "{{\"timeUnixNano\":\"{}\",\"severityNumber\":{severity},\"severityText\":\"{text}\",\"body\":{{\"stringValue\":",
+                unix_nanos(record)
+            );
+            json::write_str(&mut body, &record.message);
+            body.push_str("},\"attributes\":[");
+            write_otlp_attribute(
+                &mut body,
+                "code.namespace",
+                &FieldValue::Str(record.target.to_string()),
+            );
+            if let Some(file) = record.file {
+                body.push(',');
+                write_otlp_attribute(&mut body, "code.filepath", &FieldValue::Str(file.into()));
+            }
+            if let Some(line) = record.line {
+                body.push(',');
+                write_otlp_attribute(&mut body, "code.lineno", &FieldValue::U64(line.into()));
+            }
+            for field in &record.fields {
+                body.push(',');
+                write_otlp_attribute(&mut body, field.name, &field.value);
+            }
+            body.push_str("]}");
+        }
+        body.push_str("]}]}]}");
+        body
+    }
+}
+
+fn unix_nanos(record: &LogRecord) -> u128 {
+    record
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+}
+
+fn otlp_severity(level: &Level) -> (u8, &'static str) {
+    match *level {
+        Level::TRACE => (1, "TRACE"),
+        Level::DEBUG => (5, "DEBUG"),
+        Level::INFO => (9, "INFO"),
+        Level::WARN => (13, "WARN"),
+        Level::ERROR => (17, "ERROR"),
+    }
+}
+
+fn write_otlp_attribute(out: &mut String, key: &str, value: &FieldValue) {
+    out.push_str("{\"key\":");
+    json::write_str(out, key);
+    out.push_str(",\"value\":{");
+    match value {
+        // note: int64 values are strings in the protobuf JSON mapping.
+        FieldValue::I64(value) => _ = write!(out, "\"intValue\":\"{value}\""),
+        FieldValue::U64(value) if *value <= i64::MAX as u64 => {
+            _ = write!(out, "\"intValue\":\"{value}\"")
+        }
+        FieldValue::F64(value) => {
+            out.push_str("\"doubleValue\":");
+            json::write_f64(out, *value);
+        }
+        FieldValue::Bool(value) => _ = write!(out, "\"boolValue\":{value}"),
+        value => {
+            out.push_str("\"stringValue\":");
+            json::write_str(out, &value.to_string());
+        }
+    }
+    out.push_str("}}");
+}
+
+/// An `http://` or, on Windows, `https://` URL: `scheme://host[:port]/path`.
+struct Endpoint {
+    // note: always false where `https://` urls are rejected.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    secure: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Self, Error> {
+        let (secure, rest) = if let Some(rest) = url.strip_prefix("http://") {
+            (false, rest)
+        } else if let Some(rest) = url.strip_prefix("https://") {
+            if !cfg!(windows) {
+                return Err(Error::new(format!(
+                    "unsupported log endpoint {url}: https:// urls are only supported on windows"
+                )));
+            }
+            (true, rest)
+        } else {
+            return Err(Error::new(format!(
+                "unsupported log endpoint {url}: only http:// and https:// urls are supported"
+            )));
+        };
+
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        // IPv6 hosts are bracketed, e.g. `[::1]:4318`.
+        let port_separator = match authority.rfind(']') {
+            Some(bracket) => authority[bracket..].find(':').map(|index| bracket + index),
+            None => authority.rfind(':'),
+        };
+        let (host, port) = match port_separator {
+            Some(index) => {
+                let port = authority[index + 1..]
+                    .parse()
+                    .map_err(|_| Error::new(format!("invalid port in log endpoint {url}")))?;
+                (&authority[..index], port)
+            }
+            None => (authority, if secure { 443 } else { 80 }),
+        };
+        if host.is_empty() {
+            return Err(Error::new(format!("missing host in log endpoint {url}")));
+        }
+
+        Ok(Self {
+            secure,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn headers(&self, gzip: bool) -> &'static str {
+        if gzip {
+            "Content-Type: application/json\r\nContent-Encoding: gzip\r\n"
+        } else {
+            "Content-Type: application/json\r\n"
+        }
+    }
+
+    /// Sends one request on a fresh connection and returns the response status.
+    #[cfg(not(windows))]
+    fn post(&self, body: &[u8], gzip: bool, timeout: Duration) -> Result<u16, Error> {
+        use std::{
+            io::{Read, Write},
+            net::{TcpStream, ToSocketAddrs},
+        };
+
+        let request_error =
+            |err| Error::new(format!("failed to post logs to {}", self.host)).with_source(err);
+
+        let address = (self.host.trim_matches(['[', ']']), self.port)
+            .to_socket_addrs()
+            .map_err(request_error)?
+            .next()
+            .ok_or_else(|| Error::new(format!("no addresses found for {}", self.host)))?;
+        let mut stream = TcpStream::connect_timeout(&address, timeout).map_err(request_error)?;
+        _ = stream.set_read_timeout(Some(timeout));
+        _ = stream.set_write_timeout(Some(timeout));
+
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            self.host,
+            self.port,
+            self.headers(gzip),
+            body.len()
+        );
+        stream
+            .write_all(head.as_bytes())
+            .and_then(|_| stream.write_all(body))
+            .map_err(request_error)?;
+
+        // Only the status line matters; the rest of the response is discarded with the connection.
+        let mut response = [0; 64];
+        let mut len = 0;
+        while len < response.len() {
+            match stream.read(&mut response[len..]).map_err(request_error)? {
+                0 => break,
+                n => len += n,
+            }
+            if response[..len].contains(&b'\n') {
+                break;
+            }
+        }
+
+        std::str::from_utf8(&response[..len])
+            .ok()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| Error::new(format!("malformed response from {}", self.host)))
+    }
+
+    /// Sends one request with its own WinHTTP session and returns the response status.
+    #[cfg(windows)]
+    fn post(&self, body: &[u8], gzip: bool, timeout: Duration) -> Result<u16, Error> {
+        use winhttp::*;
+
+        let request_error = |action: &str| {
+            Error::new(format!("failed to {action} {}", self.host))
+                .with_source(std::io::Error::last_os_error())
+        };
+        let agent = wide("Galleon");
+        let host = wide(self.host.trim_matches(['[', ']']));
+        let verb = wide("POST");
+        let path = wide(&self.path);
+        let headers = wide(self.headers(gzip));
+        let timeout = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let flags = if self.secure { WINHTTP_FLAG_SECURE } else { 0 };
+
+        unsafe {
+            let session = Internet(WinHttpOpen(
+                agent.as_ptr(),
+                WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+                std::ptr::null(),
+                std::ptr::null(),
+                0,
+            ));
+            if session.0.is_null() {
+                return Err(request_error("open an http session for"));
+            }
+            WinHttpSetTimeouts(session.0, timeout, timeout, timeout, timeout);
+
+            let connection = Internet(WinHttpConnect(session.0, host.as_ptr(), self.port, 0));
+            if connection.0.is_null() {
+                return Err(request_error("connect to"));
+            }
+
+            let request = Internet(WinHttpOpenRequest(
+                connection.0,
+                verb.as_ptr(),
+                path.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                flags,
+            ));
+            if request.0.is_null() {
+                return Err(request_error("open a request to"));
+            }
+
+            if WinHttpSendRequest(
+                request.0,
+                headers.as_ptr(),
+                u32::MAX,
+                body.as_ptr().cast(),
+                body.len() as u32,
+                body.len() as u32,
+                0,
+            ) == 0
+                || WinHttpReceiveResponse(request.0, std::ptr::null_mut()) == 0
+            {
+                return Err(request_error("post logs to"));
+            }
+
+            let mut status = 0u32;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            if WinHttpQueryHeaders(
+                request.0,
+                WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+                std::ptr::null(),
+                (&mut status as *mut u32).cast(),
+                &mut size,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                return Err(request_error("read the response from"));
+            }
+
+            Ok(status as u16)
+        }
+    }
+}
+
+#[cfg(windows)]
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+#[cfg(windows)]
+mod winhttp {
+    use std::ffi::c_void;
+
+    pub(super) const WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY: u32 = 4;
+    pub(super) const WINHTTP_FLAG_SECURE: u32 = 0x0080_0000;
+    pub(super) const WINHTTP_QUERY_STATUS_CODE: u32 = 19;
+    pub(super) const WINHTTP_QUERY_FLAG_NUMBER: u32 = 0x2000_0000;
+
+    #[link(name = "winhttp")]
+    extern "system" {
+        pub(super) fn WinHttpOpen(
+            agent: *const u16,
+            access_type: u32,
+            proxy: *const u16,
+            proxy_bypass: *const u16,
+            flags: u32,
+        ) -> *mut c_void;
+        pub(super) fn WinHttpSetTimeouts(
+            handle: *mut c_void,
+            resolve: i32,
+            connect: i32,
+            send: i32,
+            receive: i32,
+        ) -> i32;
+        pub(super) fn WinHttpConnect(
+            session: *mut c_void,
+            server: *const u16,
+            port: u16,
+            reserved: u32,
+        ) -> *mut c_void;
+        pub(super) fn WinHttpOpenRequest(
+            connection: *mut c_void,
+            verb: *const u16,
+            object: *const u16,
+            version: *const u16,
+            referrer: *const u16,
+            accept_types: *const *const u16,
+            flags: u32,
+        ) -> *mut c_void;
+        pub(super) fn WinHttpSendRequest(
+            request: *mut c_void,
+            headers: *const u16,
+            headers_len: u32,
+            optional: *const c_void,
+            optional_len: u32,
+            total_len: u32,
+            context: usize,
+        ) -> i32;
+        pub(super) fn WinHttpReceiveResponse(request: *mut c_void, reserved: *mut c_void) -> i32;
+        pub(super) fn WinHttpQueryHeaders(
+            request: *mut c_void,
+            info_level: u32,
+            name: *const u16,
+            buffer: *mut c_void,
+            buffer_len: *mut u32,
+            index: *mut u32,
+        ) -> i32;
+        fn WinHttpCloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    /// Closes a WinHTTP handle on drop.
+    pub(super) struct Internet(pub(super) *mut c_void);
+
+    impl Drop for Internet {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                unsafe { WinHttpCloseHandle(self.0) };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn drop_stops_retrying() {
+        // Nothing listens on the port once the listener is dropped, so every post fails at once.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let sink = HttpSink::builder(format!("http://127.0.0.1:{port}/"), HttpFormat::Loki)
+            .batch_size(1)
+            .max_retries(10)
+            .build()
+            .unwrap();
+        sink.log(&LogRecord::new(Level::INFO, "game", "hello"))
+            .unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let start = Instant::now();
+        drop(sink);
+        assert!(
+            start.elapsed() < Duration::from_secs(1),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+
+    #[test]
+    fn parses_endpoints() {
+        let endpoint = Endpoint::parse("http://[::1]:4318/v1/logs").unwrap();
+        assert!(!endpoint.secure);
+        assert_eq!(
+            (
+                endpoint.host.as_str(),
+                endpoint.port,
+                endpoint.path.as_str()
+            ),
+            ("[::1]", 4318, "/v1/logs")
+        );
+        let endpoint = Endpoint::parse("http://localhost").unwrap();
+        assert_eq!((endpoint.port, endpoint.path.as_str()), (80, "/"));
+
+        assert_eq!(
+            Endpoint::parse("https://logs.example.com").is_ok(),
+            cfg!(windows)
+        );
+        assert!(Endpoint::parse("ftp://logs.example.com").is_err());
+        assert!(Endpoint::parse("http://:80/").is_err());
+        assert!(Endpoint::parse("http://localhost:port/").is_err());
+    }
+}
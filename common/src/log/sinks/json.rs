@@ -0,0 +1,48 @@
+use std::{
+    io::Write,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    error::{Context, Error},
+    log::{
+        format::{Formatter, JsonFormatter},
+        LogRecord, Sink,
+    },
+};
+
+/// Writes one JSON object per record, newline delimited, for ingestion by log aggregators.
+#[derive(Clone)]
+pub struct JsonSink {
+    writer: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl JsonSink {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(Box::new(writer))),
+        }
+    }
+}
+
+impl Sink for JsonSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let mut line = String::with_capacity(256);
+        JsonFormatter.format(record, &mut line);
+        line.push('\n');
+
+        self.writer
+            .lock()
+            .unwrap()
+            .write_all(line.as_bytes())
+            .context("failed to write json log record")
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        self.writer
+            .lock()
+            .unwrap()
+            .flush()
+            .context("failed to flush json log")
+    }
+}
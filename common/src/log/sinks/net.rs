@@ -0,0 +1,359 @@
+use std::{
+    collections::VecDeque,
+    io::Write,
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::Error,
+    log::{
+        binary::BinaryEncoder,
+        format::{Formatter, JsonFormatter},
+        LogRecord, Sink,
+    },
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(2);
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetProtocol {
+    Tcp,
+    Udp,
+}
+
+/// How records are framed on the wire.
+///
+/// Over TCP, `Json` sends each record as a big-endian `u32` byte length followed by a JSON
+/// object, and `Binary` sends a binary log stream that `galleon-logcat` can decode. A binary
+/// stream restarts with a fresh header on every connection. Over UDP every datagram holds one
+/// record: a JSON object, or a complete binary stream of one record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetEncoding {
+    Json,
+    Binary,
+}
+
+pub struct NetSinkBuilder {
+    address: String,
+    protocol: NetProtocol,
+    encoding: NetEncoding,
+    queue_capacity: usize,
+}
+
+impl NetSinkBuilder {
+    pub fn protocol(self, protocol: NetProtocol) -> Self {
+        Self { protocol, ..self }
+    }
+
+    pub fn encoding(self, encoding: NetEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    /// The number of records kept while disconnected. The oldest are dropped once it's full.
+    pub fn queue_capacity(self, queue_capacity: usize) -> Self {
+        Self {
+            queue_capacity: queue_capacity.max(1),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<NetSink, Error> {
+        resolve(&self.address)?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(QueueState {
+                queue: VecDeque::with_capacity(self.queue_capacity),
+                sending: false,
+                connected: false,
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            capacity: self.queue_capacity,
+        });
+
+        let worker = {
+            let shared = shared.clone();
+            let connection = Connection {
+                address: self.address,
+                protocol: self.protocol,
+                encoding: self.encoding,
+                transport: None,
+                encoder: BinaryEncoder::new(),
+                buffer: Vec::with_capacity(512),
+                line: String::with_capacity(256),
+            };
+            thread::Builder::new()
+                .name("galleon-net-sink".to_string())
+                .spawn(move || run(&shared, connection))
+                .map_err(|err| Error::new("failed to spawn net sink thread").with_source(err))?
+        };
+
+        Ok(NetSink {
+            inner: Arc::new(NetSinkInner {
+                shared,
+                worker: Some(worker),
+            }),
+        })
+    }
+}
+
+/// Streams records to a remote host, e.g. a dev machine watching a console-less test device.
+/// Records are queued and sent from a background thread that reconnects with backoff, so a slow
+/// or missing receiver never blocks logging.
+#[derive(Clone)]
+pub struct NetSink {
+    inner: Arc<NetSinkInner>,
+}
+
+impl NetSink {
+    pub fn builder<S: Into<String>>(address: S) -> NetSinkBuilder {
+        NetSinkBuilder {
+            address: address.into(),
+            protocol: NetProtocol::Tcp,
+            encoding: NetEncoding::Json,
+            queue_capacity: 4096,
+        }
+    }
+}
+
+struct NetSinkInner {
+    shared: Arc<Shared>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Drop for NetSinkInner {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+struct Shared {
+    state: Mutex<QueueState>,
+    changed: Condvar,
+    capacity: usize,
+}
+
+struct QueueState {
+    queue: VecDeque<LogRecord>,
+    sending: bool,
+    connected: bool,
+    closed: bool,
+}
+
+impl Sink for NetSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let shared = &self.inner.shared;
+        let mut state = shared.state.lock().unwrap();
+        let overflowed = state.queue.len() >= shared.capacity;
+        if overflowed {
+            state.queue.pop_front();
+        }
+        state.queue.push_back(record.clone());
+        shared.changed.notify_all();
+
+        if overflowed {
+            return Err(Error::new(
+                "net sink queue is full, dropped the oldest record",
+            ));
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Only wait while there's a connection to drain into, and never for long.
+        let shared = &self.inner.shared;
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        let mut state = shared.state.lock().unwrap();
+        while (!state.queue.is_empty() || state.sending) && state.connected && !state.closed {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::new("timed out flushing net sink"));
+            }
+            state = shared
+                .changed
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+
+        if !state.connected && !state.queue.is_empty() {
+            return Err(Error::new(format!(
+                "net sink is disconnected with {} records queued",
+                state.queue.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+enum Transport {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+struct Connection {
+    address: String,
+    protocol: NetProtocol,
+    encoding: NetEncoding,
+    transport: Option<Transport>,
+    encoder: BinaryEncoder,
+    buffer: Vec<u8>,
+    line: String,
+}
+
+impl Connection {
+    fn connect(&mut self) -> Result<(), Error> {
+        let address = resolve(&self.address)?;
+        let transport = match self.protocol {
+            NetProtocol::Tcp => {
+                let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+                    .map_err(|err| connect_error(&self.address, err))?;
+                _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                _ = stream.set_nodelay(true);
+                Transport::Tcp(stream)
+            }
+            NetProtocol::Udp => {
+                let local: SocketAddr = if address.is_ipv4() {
+                    ([0, 0, 0, 0], 0).into()
+                } else {
+                    ([0u16; 8], 0).into()
+                };
+                let socket =
+                    UdpSocket::bind(local).map_err(|err| connect_error(&self.address, err))?;
+                socket
+                    .connect(address)
+                    .map_err(|err| connect_error(&self.address, err))?;
+                Transport::Udp(socket)
+            }
+        };
+
+        self.encoder = BinaryEncoder::new();
+        if let (Transport::Tcp(stream), NetEncoding::Binary) = (&transport, self.encoding) {
+            self.buffer.clear();
+            self.encoder.header(&mut self.buffer);
+            (&*stream)
+                .write_all(&self.buffer)
+                .map_err(|err| connect_error(&self.address, err))?;
+        }
+        self.transport = Some(transport);
+
+        Ok(())
+    }
+
+    fn send(&mut self, record: &LogRecord) -> std::io::Result<()> {
+        self.buffer.clear();
+        match (self.encoding, self.protocol) {
+            (NetEncoding::Json, protocol) => {
+                self.line.clear();
+                JsonFormatter.format(record, &mut self.line);
+                if protocol == NetProtocol::Tcp {
+                    self.buffer
+                        .extend_from_slice(&(self.line.len() as u32).to_be_bytes());
+                }
+                self.buffer.extend_from_slice(self.line.as_bytes());
+            }
+            (NetEncoding::Binary, NetProtocol::Tcp) => {
+                self.encoder.encode(record, &mut self.buffer)
+            }
+            (NetEncoding::Binary, NetProtocol::Udp) => {
+                let mut encoder = BinaryEncoder::new();
+                encoder.header(&mut self.buffer);
+                encoder.encode(record, &mut self.buffer);
+            }
+        }
+
+        match self.transport.as_mut() {
+            Some(Transport::Tcp(stream)) => stream.write_all(&self.buffer),
+            Some(Transport::Udp(socket)) => socket.send(&self.buffer).map(|_| ()),
+            None => Err(std::io::ErrorKind::NotConnected.into()),
+        }
+    }
+}
+
+fn run(shared: &Shared, mut connection: Connection) {
+    let mut backoff = MIN_BACKOFF;
+
+    loop {
+        if connection.transport.is_none() {
+            match connection.connect() {
+                Ok(()) => {
+                    backoff = MIN_BACKOFF;
+                    shared.state.lock().unwrap().connected = true;
+                    shared.changed.notify_all();
+                }
+                Err(_) => {
+                    // note: errors aren't logged, since they'd be queued for this sink too.
+                    let state = shared.state.lock().unwrap();
+                    if state.closed {
+                        return;
+                    }
+                    _ = shared.changed.wait_timeout(state, backoff).unwrap();
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            }
+        }
+
+        let batch = {
+            let mut state = shared.state.lock().unwrap();
+            while state.queue.is_empty() && !state.closed {
+                state = shared.changed.wait(state).unwrap();
+            }
+            if state.queue.is_empty() {
+                return;
+            }
+            state.sending = true;
+            std::mem::take(&mut state.queue)
+        };
+
+        let mut unsent = None;
+        for (index, record) in batch.iter().enumerate() {
+            if connection.send(record).is_err() {
+                unsent = Some(index);
+                break;
+            }
+        }
+
+        let mut state = shared.state.lock().unwrap();
+        state.sending = false;
+        if let Some(index) = unsent {
+            // Put what wasn't sent back in front of anything queued since, then reconnect. The
+            // record that failed may have been partially written, so it's not retried.
+            connection.transport = None;
+            state.connected = false;
+            for record in batch.into_iter().skip(index + 1).rev() {
+                state.queue.push_front(record);
+            }
+            while state.queue.len() > shared.capacity {
+                state.queue.pop_front();
+            }
+        }
+        if state.closed && (state.queue.is_empty() || !state.connected) {
+            return;
+        }
+        shared.changed.notify_all();
+    }
+}
+
+fn resolve(address: &str) -> Result<SocketAddr, Error> {
+    address
+        .to_socket_addrs()
+        .map_err(|err| Error::new(format!("failed to resolve {address}")).with_source(err))?
+        .next()
+        .ok_or_else(|| Error::new(format!("no addresses found for {address}")))
+}
+
+fn connect_error(address: &str, err: std::io::Error) -> Error {
+    Error::new(format!("failed to connect to {address}")).with_source(err)
+}
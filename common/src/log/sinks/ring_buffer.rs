@@ -0,0 +1,71 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use crate::{
+    error::Error,
+    log::{LogRecord, Sink},
+};
+
+/// Retains the most recent records in memory, e.g. for crash reports or an in-game console.
+///
+/// Each slot has its own lock and writers claim slots through an atomic cursor, so concurrent
+/// loggers only contend when they wrap onto the same slot.
+#[derive(Clone)]
+pub struct RingBufferSink {
+    inner: Arc<RingBuffer>,
+}
+
+struct RingBuffer {
+    slots: Box<[Mutex<Option<LogRecord>>]>,
+    next: AtomicUsize,
+}
+
+impl RingBufferSink {
+    pub fn new(capacity: usize) -> Self {
+        let slots = (0..capacity.max(1)).map(|_| Mutex::new(None)).collect();
+
+        Self {
+            inner: Arc::new(RingBuffer {
+                slots,
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.slots.len()
+    }
+
+    /// Returns the retained records, oldest first.
+    pub fn snapshot(&self) -> Vec<LogRecord> {
+        let capacity = self.capacity();
+        let next = self.inner.next.load(Ordering::Acquire);
+        let start = next.saturating_sub(capacity);
+
+        (start..next)
+            .filter_map(|index| self.inner.slots[index % capacity].lock().unwrap().clone())
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        for slot in self.inner.slots.iter() {
+            *slot.lock().unwrap() = None;
+        }
+    }
+}
+
+impl Sink for RingBufferSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let index = self.inner.next.fetch_add(1, Ordering::AcqRel);
+        let slot = &self.inner.slots[index % self.capacity()];
+        *slot.lock().unwrap() = Some(record.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Nothing to do here.
+        Ok(())
+    }
+}
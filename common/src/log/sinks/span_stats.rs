@@ -0,0 +1,195 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Arc, Mutex, Weak},
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+use tracing::info;
+
+use crate::{
+    error::Error,
+    log::{LogRecord, Sink, SpanRecord},
+};
+
+/// Every live `SpanStatsSink`, so `dump_span_stats()` can find them without going through the
+/// logger's type-erased sinks.
+static SINKS: Mutex<Vec<Weak<Mutex<SpanStatsState>>>> = Mutex::new(Vec::new());
+
+/// Timing for one span name, as reported by [`SpanStatsSink::stats`].
+#[derive(Debug, Clone)]
+pub struct SpanStats {
+    pub target: &'static str,
+    pub name: &'static str,
+    pub count: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl SpanStats {
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(self.total.as_secs_f64() / self.count as f64)
+        }
+    }
+}
+
+/// Accumulates how long spans are entered for, per span name: a poor man's profiler. Each enter
+/// to exit counts once, so a span entered repeatedly reports its busy time rather than its
+/// lifetime.
+///
+/// The table is logged at INFO by `dump_span_stats()` and again at `shutdown()`.
+#[derive(Clone)]
+pub struct SpanStatsSink {
+    state: Arc<Mutex<SpanStatsState>>,
+}
+
+#[derive(Default)]
+struct SpanStatsState {
+    entered: HashMap<(ThreadId, u64), Instant>,
+    stats: HashMap<(&'static str, &'static str), SpanStats>,
+}
+
+impl SpanStatsSink {
+    pub fn new() -> Self {
+        let state = Arc::new(Mutex::new(SpanStatsState::default()));
+
+        let mut sinks = SINKS.lock().unwrap();
+        sinks.retain(|sink| sink.strong_count() > 0);
+        sinks.push(Arc::downgrade(&state));
+
+        Self { state }
+    }
+
+    /// The statistics so far, by descending total time.
+    pub fn stats(&self) -> Vec<SpanStats> {
+        sorted(&self.state.lock().unwrap())
+    }
+
+    pub fn reset(&self) {
+        self.state.lock().unwrap().stats.clear();
+    }
+
+    /// Logs the statistics as a table at INFO.
+    pub fn dump(&self) {
+        dump(&self.state);
+    }
+}
+
+impl Default for SpanStatsSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sink for SpanStatsSink {
+    fn log(&self, _record: &LogRecord) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn span_enter(&self, span: &SpanRecord) {
+        self.state
+            .lock()
+            .unwrap()
+            .entered
+            .insert((span.thread_id, span.id), span.timestamp);
+    }
+
+    fn span_exit(&self, span: &SpanRecord) {
+        let mut state = self.state.lock().unwrap();
+        let Some(entered) = state.entered.remove(&(span.thread_id, span.id)) else {
+            return;
+        };
+
+        let elapsed = span.timestamp.saturating_duration_since(entered);
+        let stats = state
+            .stats
+            .entry((span.target, span.name))
+            .or_insert(SpanStats {
+                target: span.target,
+                name: span.name,
+                count: 0,
+                total: Duration::ZERO,
+                min: Duration::MAX,
+                max: Duration::ZERO,
+            });
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.min = stats.min.min(elapsed);
+        stats.max = stats.max.max(elapsed);
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Nothing to do here.
+        Ok(())
+    }
+}
+
+/// Logs the table of every live `SpanStatsSink`.
+pub(crate) fn dump_all() {
+    let sinks: Vec<_> = SINKS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for state in sinks {
+        dump(&state);
+    }
+}
+
+fn sorted(state: &SpanStatsState) -> Vec<SpanStats> {
+    let mut stats: Vec<_> = state.stats.values().cloned().collect();
+    stats.sort_by(|a, b| b.total.cmp(&a.total).then(a.name.cmp(b.name)));
+    stats
+}
+
+fn dump(state: &Mutex<SpanStatsState>) {
+    // note: the table is built before logging, since this sink sees the record too.
+    let stats = sorted(&state.lock().unwrap());
+    if stats.is_empty() {
+        return;
+    }
+
+    let width = stats
+        .iter()
+        .map(|stats| stats.target.len() + stats.name.len() + 2)
+        .max()
+        .unwrap_or(0)
+        .max(4);
+    let mut table = String::with_capacity(80 * (stats.len() + 2));
+    _ = write!(
+        table,
+        "span timings\n{:<width$} {:>8} {:>12} {:>12} {:>12} {:>12}",
+        "span", "count", "total", "mean", "min", "max"
+    );
+    for stats in &stats {
+        let name = format!("{}::{}", stats.target, stats.name);
+        _ = write!(
+            table,
+            "\n{name:<width$} {:>8} {:>12} {:>12} {:>12} {:>12}",
+            stats.count,
+            format_duration(stats.total),
+            format_duration(stats.mean()),
+            format_duration(stats.min),
+            format_duration(stats.max)
+        );
+    }
+
+    info!("{table}");
+}
+
+fn format_duration(duration: Duration) -> String {
+    let micros = duration.as_secs_f64() * 1_000_000.0;
+    if micros < 1000.0 {
+        format!("{micros:.1}us")
+    } else if micros < 1_000_000.0 {
+        format!("{:.2}ms", micros / 1000.0)
+    } else {
+        format!("{:.3}s", micros / 1_000_000.0)
+    }
+}
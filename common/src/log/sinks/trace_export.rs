@@ -0,0 +1,275 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread::{self, ThreadId},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    error::Error,
+    json,
+    log::{format::write_json_value, Field, FieldValue, LogRecord, Sink, SpanRecord},
+};
+
+/// Records span enter and exit times and writes them as a Chrome trace event file, which can be
+/// opened in `chrome://tracing` or <https://ui.perfetto.dev> to inspect frame spikes on a
+/// timeline. Records logged to the sink appear as instant events on their thread, `frame_mark()`
+/// as a line across every thread and `plot()` as a counter track.
+///
+/// The file is written when the last clone of the sink is dropped, normally when `shutdown()`
+/// removes it from the logger, or on demand with [`save`](Self::save).
+#[derive(Clone)]
+pub struct TraceExportSink {
+    inner: Arc<TraceExportInner>,
+}
+
+struct TraceExportInner {
+    path: PathBuf,
+    max_events: usize,
+    started: Instant,
+    started_at: SystemTime,
+    state: Mutex<TraceState>,
+}
+
+#[derive(Default)]
+struct TraceState {
+    events: Vec<TraceEvent>,
+    threads: HashMap<ThreadId, Track>,
+    dropped: u64,
+}
+
+struct Track {
+    tid: u64,
+    name: Option<String>,
+    /// Spans entered on this thread whose enter was recorded.
+    open: usize,
+}
+
+struct TraceEvent {
+    phase: char,
+    name: Cow<'static, str>,
+    category: &'static str,
+    time: Duration,
+    tid: u64,
+    args: Arc<Vec<Field>>,
+    /// Whether an instant event is drawn across every thread rather than only its own, as frame
+    /// marks are.
+    global: bool,
+}
+
+impl TraceExportSink {
+    /// Keeps at most a million events, about the first few minutes of an instrumented frame loop.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self::with_max_events(path, 1_000_000)
+    }
+
+    /// Keeps at most `max_events` events. Later events are dropped, except for span exits that
+    /// close an already recorded enter.
+    pub fn with_max_events<P: Into<PathBuf>>(path: P, max_events: usize) -> Self {
+        Self {
+            inner: Arc::new(TraceExportInner {
+                path: path.into(),
+                max_events,
+                started: Instant::now(),
+                started_at: SystemTime::now(),
+                state: Mutex::new(TraceState::default()),
+            }),
+        }
+    }
+
+    /// Writes everything recorded so far to the trace file, replacing it.
+    pub fn save(&self) -> Result<(), Error> {
+        self.inner.save()
+    }
+}
+
+impl TraceExportInner {
+    fn push(&self, thread_id: ThreadId, event: impl FnOnce(u64) -> TraceEvent) {
+        let mut state = self.state.lock().unwrap();
+        let full = state.events.len() >= self.max_events;
+
+        let next_tid = state.threads.len() as u64 + 1;
+        let track = state.threads.entry(thread_id).or_insert_with(|| Track {
+            tid: next_tid,
+            name: None,
+            open: 0,
+        });
+        // note: records can be delivered from the async worker, so only name the track from its
+        // own thread.
+        if track.name.is_none() && thread::current().id() == thread_id {
+            track.name = thread::current().name().map(str::to_string);
+        }
+
+        let event = event(track.tid);
+        match event.phase {
+            'E' if track.open > 0 => track.open -= 1,
+            _ if full => {
+                state.dropped += 1;
+                return;
+            }
+            'B' => track.open += 1,
+            _ => {}
+        }
+        state.events.push(event);
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let state = self.state.lock().unwrap();
+        let pid = std::process::id();
+
+        let mut out = String::with_capacity(128 * state.events.len() + 64);
+        out.push_str("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[");
+        let mut first = true;
+        for track in state.threads.values() {
+            let Some(name) = track.name.as_deref() else {
+                continue;
+            };
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            _ = write!(
+                out,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{pid},\"tid\":{},\"args\":{{\"name\":",
+                track.tid
+            );
+            json::write_str(&mut out, name);
+            out.push_str("}}");
+        }
+
+        for event in &state.events {
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            out.push_str("{\"name\":");
+            json::write_str(&mut out, &event.name);
+            out.push_str(",\"cat\":");
+            json::write_str(&mut out, event.category);
+            _ = write!(
+                out,
+                ",\"ph\":\"{}\",\"ts\":{:.3},\"pid\":{pid},\"tid\":{}",
+                event.phase,
+                event.time.as_secs_f64() * 1_000_000.0,
+                event.tid
+            );
+            if event.phase == 'i' {
+                let scope = if event.global { 'g' } else { 't' };
+                _ = write!(out, ",\"s\":\"{scope}\"");
+            }
+            if !event.args.is_empty() {
+                out.push_str(",\"args\":{");
+                for (i, field) in event.args.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    json::write_str(&mut out, field.name);
+                    out.push(':');
+                    write_json_value(&mut out, &field.value);
+                }
+                out.push('}');
+            }
+            out.push('}');
+        }
+        _ = write!(
+            out,
+            "],\"otherData\":{{\"dropped_events\":{}}}}}",
+            state.dropped
+        );
+
+        fs::write(&self.path, out).map_err(|err| {
+            Error::new(format!("failed to write trace {}", self.path.display())).with_source(err)
+        })
+    }
+}
+
+impl Drop for TraceExportInner {
+    fn drop(&mut self) {
+        // note: the logger may already be gone, so this is the only place left to report it.
+        if let Err(err) = self.save() {
+            eprintln!("{err}");
+        }
+    }
+}
+
+impl Sink for TraceExportSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let time = record
+            .timestamp
+            .duration_since(self.inner.started_at)
+            .unwrap_or_default();
+        self.inner.push(record.thread_id, |tid| TraceEvent {
+            phase: 'i',
+            name: Cow::Owned(record.message.clone()),
+            category: record.target,
+            time,
+            tid,
+            args: Arc::new(record.fields.clone()),
+            global: false,
+        });
+
+        Ok(())
+    }
+
+    fn span_enter(&self, span: &SpanRecord) {
+        self.inner.push(span.thread_id, |tid| TraceEvent {
+            phase: 'B',
+            name: Cow::Borrowed(span.name),
+            category: span.target,
+            time: span.timestamp.saturating_duration_since(self.inner.started),
+            tid,
+            args: span.fields.clone(),
+            global: false,
+        });
+    }
+
+    fn span_exit(&self, span: &SpanRecord) {
+        self.inner.push(span.thread_id, |tid| TraceEvent {
+            phase: 'E',
+            name: Cow::Borrowed(span.name),
+            category: span.target,
+            time: span.timestamp.saturating_duration_since(self.inner.started),
+            tid,
+            args: Arc::default(),
+            global: false,
+        });
+    }
+
+    fn frame_mark(&self) {
+        let time = self.inner.started.elapsed();
+        self.inner.push(thread::current().id(), |tid| TraceEvent {
+            phase: 'i',
+            name: Cow::Borrowed("frame"),
+            category: "frame",
+            time,
+            tid,
+            args: Arc::default(),
+            global: true,
+        });
+    }
+
+    fn plot(&self, name: &'static str, value: f64) {
+        let time = self.inner.started.elapsed();
+        self.inner.push(thread::current().id(), |tid| TraceEvent {
+            phase: 'C',
+            name: Cow::Borrowed(name),
+            category: "plot",
+            time,
+            tid,
+            args: Arc::new(vec![Field {
+                name,
+                value: FieldValue::F64(value),
+            }]),
+            global: false,
+        });
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // The trace is only complete once recording stops, so it's written on drop instead.
+        Ok(())
+    }
+}
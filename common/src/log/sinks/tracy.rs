@@ -0,0 +1,157 @@
+//! Forwards to a running Tracy profiler through its C API, so the existing `tracing`
+//! instrumentation shows up live: spans as zones, records as messages, [`frame_mark`] as frames
+//! and [`plot`] as plots.
+//!
+//! [`frame_mark`]: crate::log::frame_mark
+//! [`plot`]: crate::log::plot
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{c_char, c_int, CStr, CString},
+    sync::{Mutex, OnceLock},
+};
+
+use tracing::Level;
+
+use crate::{
+    error::Error,
+    log::{
+        format::{write_fields, CompactFormatter, Formatter},
+        LogRecord, Sink, SpanRecord,
+    },
+};
+
+/// A zone's handle, `TracyCZoneCtx`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ZoneContext {
+    id: u32,
+    active: c_int,
+}
+
+// note: these are Tracy 0.11's, where source locations take a color.
+#[link(name = "TracyClient")]
+extern "C" {
+    fn ___tracy_alloc_srcloc_name(
+        line: u32,
+        source: *const c_char,
+        source_len: usize,
+        function: *const c_char,
+        function_len: usize,
+        name: *const c_char,
+        name_len: usize,
+        color: u32,
+    ) -> u64;
+    fn ___tracy_emit_zone_begin_alloc(srcloc: u64, active: c_int) -> ZoneContext;
+    fn ___tracy_emit_zone_text(ctx: ZoneContext, text: *const c_char, len: usize);
+    fn ___tracy_emit_zone_end(ctx: ZoneContext);
+    fn ___tracy_emit_messageC(text: *const c_char, len: usize, color: u32, callstack: c_int);
+    fn ___tracy_emit_frame_mark(name: *const c_char);
+    fn ___tracy_emit_plot(name: *const c_char, value: f64);
+}
+
+/// Nul-terminated copies of plot names. Tracy tells plots apart by the name's address, so they
+/// live as long as the process.
+static PLOT_NAMES: OnceLock<Mutex<HashMap<&'static str, &'static CStr>>> = OnceLock::new();
+
+thread_local! {
+    /// Zones open on this thread, innermost last, with the span each is for.
+    static ZONES: RefCell<Vec<(u64, ZoneContext)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Sends spans, records, frame marks and plots to Tracy. Needs the `tracy` feature, and the
+/// Tracy 0.11 client library built with `TRACY_ENABLE` to link against, see `common/Cargo.toml`.
+///
+/// note: zones are opened and closed on the thread that enters the span, as Tracy requires, but
+/// records reach the sink from the logger's worker when it runs asynchronously, so their
+/// messages are timestamped on delivery.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracySink;
+
+impl TracySink {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Sink for TracySink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let mut text = String::with_capacity(128);
+        CompactFormatter.format(record, &mut text);
+        unsafe {
+            ___tracy_emit_messageC(text.as_ptr().cast(), text.len(), color(&record.level), 0)
+        };
+        Ok(())
+    }
+
+    fn span_enter(&self, span: &SpanRecord) {
+        let file = span.file.unwrap_or("unknown");
+        let ctx = unsafe {
+            let srcloc = ___tracy_alloc_srcloc_name(
+                span.line.unwrap_or(0),
+                file.as_ptr().cast(),
+                file.len(),
+                span.target.as_ptr().cast(),
+                span.target.len(),
+                span.name.as_ptr().cast(),
+                span.name.len(),
+                0,
+            );
+            ___tracy_emit_zone_begin_alloc(srcloc, 1)
+        };
+
+        if !span.fields.is_empty() {
+            let mut text = String::with_capacity(64);
+            write_fields(&mut text, &span.fields);
+            let text = text.trim_start();
+            unsafe { ___tracy_emit_zone_text(ctx, text.as_ptr().cast(), text.len()) };
+        }
+
+        ZONES.with_borrow_mut(|zones| zones.push((span.id, ctx)));
+    }
+
+    fn span_exit(&self, span: &SpanRecord) {
+        // Tracy zones must end in the order they began, so an exit out of order, or of a span
+        // entered before the sink was added, is skipped.
+        let ctx = ZONES.with_borrow_mut(|zones| match zones.last() {
+            Some(&(id, ctx)) if id == span.id => {
+                zones.pop();
+                Some(ctx)
+            }
+            _ => None,
+        });
+        if let Some(ctx) = ctx {
+            unsafe { ___tracy_emit_zone_end(ctx) };
+        }
+    }
+
+    fn frame_mark(&self) {
+        // A null name marks the main frame.
+        unsafe { ___tracy_emit_frame_mark(std::ptr::null()) };
+    }
+
+    fn plot(&self, name: &'static str, value: f64) {
+        let names = PLOT_NAMES.get_or_init(Mutex::default);
+        let name = *names.lock().unwrap().entry(name).or_insert_with(|| {
+            let name = CString::new(name.replace('\0', "")).unwrap_or_default();
+            Box::leak(name.into_boxed_c_str())
+        });
+        unsafe { ___tracy_emit_plot(name.as_ptr(), value) };
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// `0xRRGGBB`, where 0 is Tracy's default.
+fn color(level: &Level) -> u32 {
+    match *level {
+        Level::ERROR => 0xff_40_40,
+        Level::WARN => 0xff_c0_40,
+        Level::INFO => 0,
+        Level::DEBUG => 0x80_80_ff,
+        Level::TRACE => 0x80_80_80,
+    }
+}
@@ -0,0 +1,48 @@
+use crate::log::LogRecord;
+
+/// The number of records kept from before the first sink is added, unless set with
+/// `LoggerBuilder::startup_buffer`.
+pub(crate) const DEFAULT_CAPACITY: usize = 256;
+
+/// The first records logged before any sink is added, replayed to every sink as it's added so
+/// early startup output isn't lost.
+pub(crate) struct StartupBuffer {
+    records: Vec<LogRecord>,
+    capacity: usize,
+    missed: u64,
+}
+
+impl StartupBuffer {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            records: Vec::new(),
+            capacity,
+            missed: 0,
+        }
+    }
+
+    pub(crate) fn set_capacity(&mut self, capacity: usize) {
+        if self.records.len() > capacity {
+            self.missed += (self.records.len() - capacity) as u64;
+            self.records.truncate(capacity);
+        }
+        self.capacity = capacity;
+    }
+
+    pub(crate) fn push(&mut self, record: &LogRecord) {
+        if self.records.len() < self.capacity {
+            self.records.push(record.clone());
+        } else {
+            self.missed += 1;
+        }
+    }
+
+    pub(crate) fn records(&self) -> &[LogRecord] {
+        &self.records
+    }
+
+    /// The number of records that didn't fit.
+    pub(crate) fn missed(&self) -> u64 {
+        self.missed
+    }
+}
@@ -0,0 +1,154 @@
+//! Capturing log records in tests.
+//!
+//! ```
+//! use common::{assert_logged, assert_not_logged, log::{self, testing, Level}};
+//!
+//! let _capture = testing::test_capture();
+//! log::warn!("swapchain out of date, recreating");
+//!
+//! assert_logged!(Level::WARN, contains "swapchain");
+//! assert_not_logged!(Level::ERROR);
+//! ```
+
+use std::{
+    cell::RefCell,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use tracing::{level_filters::LevelFilter, Level};
+
+use crate::{
+    error::Error,
+    log::{add_sink_with, current, remove_sink, startup, LogRecord, Sink, SinkConfig, SinkId},
+};
+
+thread_local! {
+    static CURRENT: RefCell<Vec<TestSink>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps every record it's given in memory.
+#[derive(Clone, Default)]
+pub struct TestSink {
+    records: Arc<Mutex<Vec<LogRecord>>>,
+}
+
+impl TestSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().unwrap().clone()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    /// Whether a record at `level`, if given, with a message containing `needle`, if given, was
+    /// logged.
+    pub fn contains(&self, level: Option<Level>, needle: Option<&str>) -> bool {
+        self.records.lock().unwrap().iter().any(|record| {
+            level.is_none_or(|level| record.level == level)
+                && needle.is_none_or(|needle| record.message.contains(needle))
+        })
+    }
+}
+
+impl Sink for TestSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        self.records.lock().unwrap().push(record.clone());
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Records everything logged on the current thread until dropped; see [`test_capture`].
+#[must_use = "records are only captured until the capture is dropped"]
+pub struct LogCapture {
+    sink: TestSink,
+    id: SinkId,
+    // Captures are tracked per thread, so must be dropped on the thread that created them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl LogCapture {
+    pub fn sink(&self) -> &TestSink {
+        &self.sink
+    }
+
+    pub fn records(&self) -> Vec<LogRecord> {
+        flush();
+        self.sink.records()
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        remove_sink(self.id);
+        _ = CURRENT.try_with(|current| current.borrow_mut().pop());
+    }
+}
+
+/// Starts the logger at `TRACE` if it isn't running and captures records logged on this thread.
+/// Tests run on their own threads, so parallel tests don't see each other's records. The
+/// `assert_logged!` family checks the innermost capture on the calling thread. Within a
+/// [`ScopedLogger`](crate::log::ScopedLogger) the capture is added to that logger instead.
+pub fn test_capture() -> LogCapture {
+    if current().is_none() {
+        _ = startup(LevelFilter::TRACE);
+    }
+
+    let sink = TestSink::new();
+    let thread_id = thread::current().id();
+    let id = add_sink_with(
+        &sink,
+        SinkConfig::new().filter(move |record| record.thread_id == thread_id),
+    );
+    CURRENT.with(|current| current.borrow_mut().push(sink.clone()));
+
+    LogCapture {
+        sink,
+        id,
+        _not_send: PhantomData,
+    }
+}
+
+fn flush() {
+    if let Some(logger) = current() {
+        logger.flush();
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_logged(level: Option<Level>, needle: Option<&str>, expected: bool) {
+    flush();
+    let Some(sink) = CURRENT.with(|current| current.borrow().last().cloned()) else {
+        panic!("no log capture on this thread; call `testing::test_capture()` first");
+    };
+
+    if sink.contains(level, needle) != expected {
+        let records: Vec<_> = sink
+            .records()
+            .iter()
+            .map(|record| format!("  [{}] {}", record.level, record.message))
+            .collect();
+        panic!(
+            "expected {}a record{}{}, captured:\n{}",
+            if expected { "" } else { "no " },
+            level
+                .map(|level| format!(" at {level}"))
+                .unwrap_or_default(),
+            needle
+                .map(|needle| format!(" containing {needle:?}"))
+                .unwrap_or_default(),
+            records.join("\n")
+        );
+    }
+}
@@ -0,0 +1,262 @@
+/// Logs at most once per call site.
+#[macro_export]
+macro_rules! log_once {
+    ($lvl:expr, $($arg:tt)+) => {{
+        static ONCE: $crate::log::rate_limit::Once = $crate::log::rate_limit::Once::new();
+        if ONCE.first() {
+            $crate::log::__tracing::event!($lvl, $($arg)+);
+        }
+    }};
+}
+
+/// Logs the first time and then every `n`th time a call site is reached.
+#[macro_export]
+macro_rules! log_every {
+    ($n:expr, $lvl:expr, $($arg:tt)+) => {{
+        static EVERY: $crate::log::rate_limit::EveryN = $crate::log::rate_limit::EveryN::new();
+        if EVERY.tick($n) {
+            $crate::log::__tracing::event!($lvl, $($arg)+);
+        }
+    }};
+}
+
+/// Logs at most once per `interval` per call site.
+#[macro_export]
+macro_rules! log_throttled {
+    ($interval:expr, $lvl:expr, $($arg:tt)+) => {{
+        static THROTTLE: $crate::log::rate_limit::Throttle =
+            $crate::log::rate_limit::Throttle::new();
+        if THROTTLE.ready($interval) {
+            $crate::log::__tracing::event!($lvl, $($arg)+);
+        }
+    }};
+}
+
+#[macro_export]
+macro_rules! trace_once {
+    ($($arg:tt)+) => {
+        $crate::log_once!($crate::log::__tracing::Level::TRACE, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! trace_every {
+    ($n:expr, $($arg:tt)+) => {
+        $crate::log_every!($n, $crate::log::__tracing::Level::TRACE, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! trace_throttled {
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_throttled!($interval, $crate::log::__tracing::Level::TRACE, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! debug_once {
+    ($($arg:tt)+) => {
+        $crate::log_once!($crate::log::__tracing::Level::DEBUG, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! debug_every {
+    ($n:expr, $($arg:tt)+) => {
+        $crate::log_every!($n, $crate::log::__tracing::Level::DEBUG, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! debug_throttled {
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_throttled!($interval, $crate::log::__tracing::Level::DEBUG, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! info_once {
+    ($($arg:tt)+) => {
+        $crate::log_once!($crate::log::__tracing::Level::INFO, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! info_every {
+    ($n:expr, $($arg:tt)+) => {
+        $crate::log_every!($n, $crate::log::__tracing::Level::INFO, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! info_throttled {
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_throttled!($interval, $crate::log::__tracing::Level::INFO, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! warn_once {
+    ($($arg:tt)+) => {
+        $crate::log_once!($crate::log::__tracing::Level::WARN, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! warn_every {
+    ($n:expr, $($arg:tt)+) => {
+        $crate::log_every!($n, $crate::log::__tracing::Level::WARN, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! warn_throttled {
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_throttled!($interval, $crate::log::__tracing::Level::WARN, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! error_once {
+    ($($arg:tt)+) => {
+        $crate::log_once!($crate::log::__tracing::Level::ERROR, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! error_every {
+    ($n:expr, $($arg:tt)+) => {
+        $crate::log_every!($n, $crate::log::__tracing::Level::ERROR, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! error_throttled {
+    ($interval:expr, $($arg:tt)+) => {
+        $crate::log_throttled!($interval, $crate::log::__tracing::Level::ERROR, $($arg)+)
+    };
+}
+
+/// Asserts that a record at a level, optionally containing some text, was logged on this thread
+/// since [`test_capture`](crate::log::testing::test_capture), e.g.
+/// `assert_logged!(Level::WARN, contains "swapchain")`.
+#[macro_export]
+macro_rules! assert_logged {
+    ($level:expr) => {
+        $crate::log::testing::__assert_logged(Some($level), None, true)
+    };
+    ($level:expr, contains $needle:expr) => {
+        $crate::log::testing::__assert_logged(Some($level), Some($needle), true)
+    };
+}
+
+/// The inverse of [`assert_logged!`].
+#[macro_export]
+macro_rules! assert_not_logged {
+    ($level:expr) => {
+        $crate::log::testing::__assert_logged(Some($level), None, false)
+    };
+    ($level:expr, contains $needle:expr) => {
+        $crate::log::testing::__assert_logged(Some($level), Some($needle), false)
+    };
+}
+
+/// Checks a condition without panicking. On failure it logs at ERROR with the call site, then
+/// breaks into the debugger or continues depending on [`verify::on_failure`](crate::verify).
+/// Evaluates to whether the condition held.
+#[macro_export]
+macro_rules! verify {
+    ($cond:expr $(,)?) => {{
+        let passed: bool = $cond;
+        if !passed {
+            $crate::log::__tracing::error!("verification failed: {}", ::core::stringify!($cond));
+            $crate::verify::__failed();
+        }
+        passed
+    }};
+    ($cond:expr, $($arg:tt)+) => {{
+        let passed: bool = $cond;
+        if !passed {
+            $crate::log::__tracing::error!(condition = ::core::stringify!($cond), $($arg)+);
+            $crate::verify::__failed();
+        }
+        passed
+    }};
+}
+
+/// Like [`verify!`], checking that two values are equal and logging both when they aren't.
+#[macro_export]
+macro_rules! verify_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::verify_eq!(
+            $left,
+            $right,
+            "verification failed: {} == {}",
+            ::core::stringify!($left),
+            ::core::stringify!($right)
+        )
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                let passed = *left == *right;
+                if !passed {
+                    $crate::log::__tracing::error!(left = ?left, right = ?right, $($arg)+);
+                    $crate::verify::__failed();
+                }
+                passed
+            }
+        }
+    };
+}
+
+/// Returns early with an [`Error`](crate::error::Error) built from a format string, e.g.
+/// `bail!("unsupported texture format {format:?}")`.
+#[macro_export]
+macro_rules! bail {
+    ($($arg:tt)+) => {
+        return ::core::result::Result::Err(
+            $crate::error::Error::new(::std::format!($($arg)+)).into(),
+        )
+    };
+}
+
+/// Returns early with an [`Error`](crate::error::Error) unless a condition holds, e.g.
+/// `ensure!(width > 0, "window width must be positive")`.
+#[macro_export]
+macro_rules! ensure {
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!($($arg)+);
+        }
+    };
+}
+
+/// Returns the `metrics::Counter` named by a string literal, looked up once per call site.
+#[macro_export]
+macro_rules! counter {
+    ($name:literal) => {{
+        static METRIC: ::std::sync::OnceLock<$crate::metrics::Counter> =
+            ::std::sync::OnceLock::new();
+        METRIC.get_or_init(|| $crate::metrics::counter($name))
+    }};
+}
+
+/// Returns the `metrics::Gauge` named by a string literal, looked up once per call site.
+#[macro_export]
+macro_rules! gauge {
+    ($name:literal) => {{
+        static METRIC: ::std::sync::OnceLock<$crate::metrics::Gauge> = ::std::sync::OnceLock::new();
+        METRIC.get_or_init(|| $crate::metrics::gauge($name))
+    }};
+}
+
+/// Returns the `metrics::Histogram` named by a string literal, looked up once per call site.
+#[macro_export]
+macro_rules! histogram {
+    ($name:literal) => {{
+        static METRIC: ::std::sync::OnceLock<$crate::metrics::Histogram> =
+            ::std::sync::OnceLock::new();
+        METRIC.get_or_init(|| $crate::metrics::histogram($name))
+    }};
+}
@@ -0,0 +1,528 @@
+//! Counters, gauges and histograms, aggregated per frame and exported periodically.
+//!
+//! ```
+//! use std::time::Duration;
+//! use common::{counter, histogram, metrics::{self, exporters::LogExporter}};
+//!
+//! metrics::add_exporter(LogExporter::new(), Duration::from_secs(10));
+//!
+//! counter!("draw_calls").add(12);
+//! histogram!("frame_time_ms").record(16.4);
+//! metrics::end_frame();
+//! ```
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::error::Error;
+
+pub mod exporters;
+
+/// 8 buckets per power of two covering 2^-30 to 2^34, so percentiles are within about 9%.
+const BUCKETS_PER_OCTAVE: f64 = 8.0;
+const MIN_EXPONENT: f64 = -30.0;
+const BUCKET_COUNT: usize = 512;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(|| Registry {
+        started: Instant::now(),
+        state: Mutex::new(RegistryState {
+            metrics: Vec::new(),
+            frames: 0,
+        }),
+        exporters: Mutex::new(Vec::new()),
+    })
+}
+
+struct Registry {
+    started: Instant,
+    state: Mutex<RegistryState>,
+    exporters: Mutex<Vec<ExporterEntry>>,
+}
+
+struct RegistryState {
+    metrics: Vec<(&'static str, Metric)>,
+    frames: u64,
+}
+
+#[derive(Clone)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+    Histogram(Histogram),
+}
+
+struct ExporterEntry {
+    exporter: Box<dyn Exporter>,
+    interval: Duration,
+    last_export: Instant,
+}
+
+/// Receives a snapshot of every metric, see [`add_exporter`].
+pub trait Exporter: Send {
+    fn export(&mut self, snapshot: &MetricsSnapshot) -> Result<(), Error>;
+}
+
+/// Count, sum and range of a series of values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    const EMPTY: Summary = Summary {
+        count: 0,
+        sum: 0.0,
+        min: f64::INFINITY,
+        max: f64::NEG_INFINITY,
+    };
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// The average value, or 0 if there are none.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum / self.count as f64
+        }
+    }
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::EMPTY
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    /// Time since the first metric was used.
+    pub elapsed: Duration,
+    /// Calls to [`end_frame`] so far.
+    pub frames: u64,
+    pub metrics: Vec<MetricSnapshot>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MetricSnapshot {
+    pub name: &'static str,
+    pub value: MetricValue,
+}
+
+#[derive(Debug, Clone)]
+pub enum MetricValue {
+    /// `per_frame` summarises how much the counter grew in each frame.
+    Counter { total: u64, per_frame: Summary },
+    /// `per_frame` summarises the value at the end of each frame.
+    Gauge { value: f64, per_frame: Summary },
+    Histogram {
+        summary: Summary,
+        p50: f64,
+        p95: f64,
+        p99: f64,
+    },
+}
+
+/// A monotonically increasing count, e.g. draw calls or bytes uploaded.
+#[derive(Clone)]
+pub struct Counter {
+    inner: Arc<CounterInner>,
+}
+
+struct CounterInner {
+    value: AtomicU64,
+    frames: Mutex<FrameSeries>,
+}
+
+struct FrameSeries {
+    last: u64,
+    per_frame: Summary,
+}
+
+impl Counter {
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    pub fn add(&self, n: u64) {
+        self.inner.value.fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn get(&self) -> u64 {
+        self.inner.value.load(Ordering::Relaxed)
+    }
+
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(CounterInner {
+                value: AtomicU64::new(0),
+                frames: Mutex::new(FrameSeries {
+                    last: 0,
+                    per_frame: Summary::EMPTY,
+                }),
+            }),
+        }
+    }
+
+    fn end_frame(&self) {
+        let value = self.get();
+        let mut frames = self.inner.frames.lock().unwrap();
+        let delta = value - frames.last;
+        frames.last = value;
+        frames.per_frame.add(delta as f64);
+    }
+
+    fn snapshot(&self) -> MetricValue {
+        MetricValue::Counter {
+            total: self.get(),
+            per_frame: self.inner.frames.lock().unwrap().per_frame,
+        }
+    }
+}
+
+/// A value that goes up and down, e.g. live entities or allocated bytes.
+#[derive(Clone)]
+pub struct Gauge {
+    inner: Arc<GaugeInner>,
+}
+
+struct GaugeInner {
+    bits: AtomicU64,
+    per_frame: Mutex<Summary>,
+}
+
+impl Gauge {
+    pub fn set(&self, value: f64) {
+        self.inner.bits.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add(&self, delta: f64) {
+        _ = self
+            .inner
+            .bits
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            });
+    }
+
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.inner.bits.load(Ordering::Relaxed))
+    }
+
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(GaugeInner {
+                bits: AtomicU64::new(0f64.to_bits()),
+                per_frame: Mutex::new(Summary::EMPTY),
+            }),
+        }
+    }
+
+    fn end_frame(&self) {
+        let value = self.get();
+        self.inner.per_frame.lock().unwrap().add(value);
+    }
+
+    fn snapshot(&self) -> MetricValue {
+        MetricValue::Gauge {
+            value: self.get(),
+            per_frame: *self.inner.per_frame.lock().unwrap(),
+        }
+    }
+}
+
+/// The distribution of a sampled value, e.g. frame time. Percentiles are approximate.
+#[derive(Clone)]
+pub struct Histogram {
+    inner: Arc<Mutex<HistogramState>>,
+}
+
+struct HistogramState {
+    summary: Summary,
+    buckets: Box<[u64; BUCKET_COUNT]>,
+}
+
+impl Histogram {
+    pub fn record(&self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+
+        let mut state = self.inner.lock().unwrap();
+        state.summary.add(value);
+        state.buckets[bucket(value)] += 1;
+    }
+
+    /// Records `duration` in milliseconds.
+    pub fn record_duration(&self, duration: Duration) {
+        self.record(duration.as_secs_f64() * 1000.0);
+    }
+
+    fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(HistogramState {
+                summary: Summary::EMPTY,
+                buckets: Box::new([0; BUCKET_COUNT]),
+            })),
+        }
+    }
+
+    fn snapshot(&self) -> MetricValue {
+        let state = self.inner.lock().unwrap();
+        let percentile = |q: f64| {
+            let rank = ((q * state.summary.count as f64).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (index, count) in state.buckets.iter().enumerate() {
+                seen += count;
+                if seen >= rank {
+                    return bucket_upper(index).clamp(state.summary.min, state.summary.max);
+                }
+            }
+            state.summary.max
+        };
+
+        let (p50, p95, p99) = if state.summary.count == 0 {
+            (0.0, 0.0, 0.0)
+        } else {
+            (percentile(0.5), percentile(0.95), percentile(0.99))
+        };
+        MetricValue::Histogram {
+            summary: state.summary,
+            p50,
+            p95,
+            p99,
+        }
+    }
+}
+
+fn bucket(value: f64) -> usize {
+    if value <= 0.0 {
+        return 0;
+    }
+
+    let index = ((value.log2() - MIN_EXPONENT) * BUCKETS_PER_OCTAVE).floor();
+    index.clamp(0.0, (BUCKET_COUNT - 1) as f64) as usize
+}
+
+fn bucket_upper(index: usize) -> f64 {
+    ((index + 1) as f64 / BUCKETS_PER_OCTAVE + MIN_EXPONENT).exp2()
+}
+
+fn register(name: &'static str, make: fn() -> Metric) -> Metric {
+    let mut state = registry().state.lock().unwrap();
+    if let Some((_, metric)) = state.metrics.iter().find(|(n, _)| *n == name) {
+        let expected = make();
+        if std::mem::discriminant(metric) == std::mem::discriminant(&expected) {
+            return metric.clone();
+        }
+        // note: a detached metric keeps the caller working without corrupting the registered one.
+        warn!("metric {name} is already registered as a different kind");
+        return expected;
+    }
+
+    let metric = make();
+    state.metrics.push((name, metric.clone()));
+    metric
+}
+
+/// Returns the counter called `name`, registering it on first use. Prefer the `counter!` macro,
+/// which caches the lookup per call site.
+pub fn counter(name: &'static str) -> Counter {
+    match register(name, || Metric::Counter(Counter::new())) {
+        Metric::Counter(counter) => counter,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the gauge called `name`, registering it on first use. See `gauge!`.
+pub fn gauge(name: &'static str) -> Gauge {
+    match register(name, || Metric::Gauge(Gauge::new())) {
+        Metric::Gauge(gauge) => gauge,
+        _ => unreachable!(),
+    }
+}
+
+/// Returns the histogram called `name`, registering it on first use. See `histogram!`.
+pub fn histogram(name: &'static str) -> Histogram {
+    match register(name, || Metric::Histogram(Histogram::new())) {
+        Metric::Histogram(histogram) => histogram,
+        _ => unreachable!(),
+    }
+}
+
+/// Sends a snapshot to `exporter` every `interval`, checked from [`end_frame`], and from
+/// [`export`].
+pub fn add_exporter<E: Exporter + 'static>(exporter: E, interval: Duration) {
+    registry().exporters.lock().unwrap().push(ExporterEntry {
+        exporter: Box::new(exporter),
+        interval,
+        last_export: Instant::now(),
+    });
+}
+
+/// Closes the current frame: counters record how much they grew and gauges their current value.
+/// Exporters whose interval has elapsed are run afterwards. Call it once per frame.
+pub fn end_frame() {
+    let registry = registry();
+    {
+        let mut state = registry.state.lock().unwrap();
+        state.frames += 1;
+        for (_, metric) in &state.metrics {
+            match metric {
+                Metric::Counter(counter) => counter.end_frame(),
+                Metric::Gauge(gauge) => gauge.end_frame(),
+                Metric::Histogram(_) => {}
+            }
+        }
+    }
+
+    let now = Instant::now();
+    let mut exporters = registry.exporters.lock().unwrap();
+    let mut snapshot = None;
+    for entry in exporters
+        .iter_mut()
+        .filter(|entry| now.duration_since(entry.last_export) >= entry.interval)
+    {
+        entry.last_export = now;
+        let snapshot = snapshot.get_or_insert_with(self::snapshot);
+        if let Err(err) = entry.exporter.export(snapshot) {
+            warn!("failed to export metrics: {err}");
+        }
+    }
+}
+
+/// Sends a snapshot to every exporter now, e.g. before exiting.
+pub fn export() {
+    let snapshot = snapshot();
+    let mut exporters = registry().exporters.lock().unwrap();
+    for entry in exporters.iter_mut() {
+        entry.last_export = Instant::now();
+        if let Err(err) = entry.exporter.export(&snapshot) {
+            warn!("failed to export metrics: {err}");
+        }
+    }
+}
+
+/// The current value of every metric, in the order they were registered.
+pub fn snapshot() -> MetricsSnapshot {
+    let registry = registry();
+    let state = registry.state.lock().unwrap();
+
+    MetricsSnapshot {
+        elapsed: registry.started.elapsed(),
+        frames: state.frames,
+        metrics: state
+            .metrics
+            .iter()
+            .map(|(name, metric)| MetricSnapshot {
+                name,
+                value: match metric {
+                    Metric::Counter(counter) => counter.snapshot(),
+                    Metric::Gauge(gauge) => gauge.snapshot(),
+                    Metric::Histogram(histogram) => histogram.snapshot(),
+                },
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The summary and p50, p95 and p99 of `histogram`.
+    fn percentiles(histogram: &Histogram) -> (Summary, [f64; 3]) {
+        match histogram.snapshot() {
+            MetricValue::Histogram {
+                summary,
+                p50,
+                p95,
+                p99,
+            } => (summary, [p50, p95, p99]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn empty_histogram_reports_zeroes() {
+        let (summary, percentiles) = percentiles(&Histogram::new());
+        assert_eq!(summary, Summary::EMPTY);
+        assert_eq!(summary.mean(), 0.0);
+        assert_eq!(percentiles, [0.0; 3]);
+    }
+
+    #[test]
+    fn single_sample_is_every_percentile() {
+        let histogram = Histogram::new();
+        histogram.record(16.4);
+        // A NaN would poison the summary, so it's not recorded.
+        histogram.record(f64::NAN);
+
+        let (summary, percentiles) = percentiles(&histogram);
+        assert_eq!(summary.count, 1);
+        assert_eq!(
+            (summary.min, summary.max, summary.mean()),
+            (16.4, 16.4, 16.4)
+        );
+        assert_eq!(percentiles, [16.4; 3]);
+    }
+
+    #[test]
+    fn percentiles_are_within_a_bucket_of_the_exact_values() {
+        let histogram = Histogram::new();
+        // Recorded out of order, since buckets shouldn't care.
+        for value in (1..=1000).rev() {
+            histogram.record(value as f64);
+        }
+
+        let (summary, [p50, p95, p99]) = percentiles(&histogram);
+        assert_eq!(summary.count, 1000);
+        assert_eq!((summary.min, summary.max), (1.0, 1000.0));
+        assert_eq!(summary.mean(), 500.5);
+
+        // Each percentile is its bucket's upper bound, which is at most 2^(1/8) above the value.
+        let bucket_width = (1.0 / BUCKETS_PER_OCTAVE).exp2();
+        for (percentile, exact) in [(p50, 500.0), (p95, 950.0), (p99, 990.0)] {
+            assert!(
+                (exact..=exact * bucket_width).contains(&percentile),
+                "{percentile} isn't within a bucket of {exact}"
+            );
+        }
+        assert!(p50 < p95 && p95 <= p99);
+    }
+
+    #[test]
+    fn percentiles_of_a_skewed_distribution() {
+        let histogram = Histogram::new();
+        // 98 fast frames and 2 slow ones: the median ignores the spikes, p99 lands on them.
+        for _ in 0..98 {
+            histogram.record(16.0);
+        }
+        histogram.record(100.0);
+        histogram.record(100.0);
+
+        let (_, [p50, p95, p99]) = percentiles(&histogram);
+        let bucket_width = (1.0 / BUCKETS_PER_OCTAVE).exp2();
+        assert!((16.0..=16.0 * bucket_width).contains(&p50));
+        assert!((16.0..=16.0 * bucket_width).contains(&p95));
+        assert_eq!(p99, 100.0);
+    }
+}
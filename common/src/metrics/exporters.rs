@@ -0,0 +1,5 @@
+pub use self::{csv::CsvExporter, log::LogExporter, net::NetExporter};
+
+mod csv;
+mod log;
+mod net;
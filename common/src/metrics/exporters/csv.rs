@@ -0,0 +1,101 @@
+use std::{
+    fmt::Write as _,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    error::{Context, Error},
+    metrics::{Exporter, MetricValue, MetricsSnapshot},
+};
+
+const HEADER: &str = "elapsed_s,frames,name,kind,value,count,mean,min,max,p50,p95,p99\n";
+
+/// Appends a row per metric to a CSV file on every export, for comparing sessions in a
+/// spreadsheet. Columns that don't apply to a metric's kind are left empty.
+pub struct CsvExporter {
+    writer: BufWriter<File>,
+    row: String,
+}
+
+impl CsvExporter {
+    /// Creates the file at `path`, replacing any previous contents.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut writer = File::create(path).map(BufWriter::new).map_err(|err| {
+            Error::new(format!("failed to create metrics file {}", path.display())).with_source(err)
+        })?;
+        writer
+            .write_all(HEADER.as_bytes())
+            .context("failed to write metrics file")?;
+
+        Ok(Self {
+            writer,
+            row: String::with_capacity(128),
+        })
+    }
+}
+
+impl Exporter for CsvExporter {
+    fn export(&mut self, snapshot: &MetricsSnapshot) -> Result<(), Error> {
+        let elapsed = snapshot.elapsed.as_secs_f64();
+        for metric in &snapshot.metrics {
+            let row = &mut self.row;
+            row.clear();
+            _ = write!(row, "{elapsed:.3},{},{},", snapshot.frames, metric.name);
+            match &metric.value {
+                MetricValue::Counter { total, per_frame } => {
+                    _ = write!(
+                        row,
+                        "counter,{total},{},{},{},{},,,",
+                        per_frame.count,
+                        per_frame.mean(),
+                        finite(per_frame.min),
+                        finite(per_frame.max)
+                    )
+                }
+                MetricValue::Gauge { value, per_frame } => {
+                    _ = write!(
+                        row,
+                        "gauge,{value},{},{},{},{},,,",
+                        per_frame.count,
+                        per_frame.mean(),
+                        finite(per_frame.min),
+                        finite(per_frame.max)
+                    )
+                }
+                MetricValue::Histogram {
+                    summary,
+                    p50,
+                    p95,
+                    p99,
+                } => {
+                    _ = write!(
+                        row,
+                        "histogram,,{},{},{},{},{p50},{p95},{p99}",
+                        summary.count,
+                        summary.mean(),
+                        finite(summary.min),
+                        finite(summary.max)
+                    )
+                }
+            }
+            row.push('\n');
+            self.writer
+                .write_all(row.as_bytes())
+                .context("failed to write metrics file")?;
+        }
+
+        self.writer.flush().context("failed to flush metrics file")
+    }
+}
+
+/// Empty summaries have infinite bounds, which are left blank rather than written as `inf`.
+fn finite(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::new()
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt::Write;
+
+use tracing::info;
+
+use crate::{
+    error::Error,
+    metrics::{Exporter, MetricValue, MetricsSnapshot},
+};
+
+/// Logs a one line summary per metric at INFO under the `metrics` target.
+#[derive(Default)]
+pub struct LogExporter {
+    line: String,
+}
+
+impl LogExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Exporter for LogExporter {
+    fn export(&mut self, snapshot: &MetricsSnapshot) -> Result<(), Error> {
+        for metric in &snapshot.metrics {
+            self.line.clear();
+            let line = &mut self.line;
+            match &metric.value {
+                MetricValue::Counter { total, per_frame } => {
+                    _ = write!(
+                        line,
+                        "{} total={total} per_frame(mean={:.2} min={} max={})",
+                        metric.name,
+                        per_frame.mean(),
+                        per_frame.min,
+                        per_frame.max
+                    )
+                }
+                MetricValue::Gauge { value, per_frame } => {
+                    _ = write!(
+                        line,
+                        "{} value={value} per_frame(mean={:.2} min={} max={})",
+                        metric.name,
+                        per_frame.mean(),
+                        per_frame.min,
+                        per_frame.max
+                    )
+                }
+                MetricValue::Histogram {
+                    summary,
+                    p50,
+                    p95,
+                    p99,
+                } => {
+                    _ = write!(
+                    line,
+                    "{} count={} mean={:.2} min={} max={} p50={p50:.2} p95={p95:.2} p99={p99:.2}",
+                    metric.name,
+                    summary.count,
+                    summary.mean(),
+                    summary.min,
+                    summary.max
+                )
+                }
+            }
+            info!(target: "metrics", frames = snapshot.frames, "{line}");
+        }
+
+        Ok(())
+    }
+}
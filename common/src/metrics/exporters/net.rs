@@ -0,0 +1,108 @@
+use std::{
+    fmt::Write,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use crate::{
+    error::Error,
+    json,
+    metrics::{Exporter, MetricValue, MetricsSnapshot, Summary},
+};
+
+/// Sends each snapshot as one JSON datagram over UDP, e.g. to a dashboard on a dev machine:
+/// `{"elapsed_s":..,"frames":..,"metrics":[{"name":..,"kind":"counter","total":..},..]}`.
+pub struct NetExporter {
+    socket: UdpSocket,
+    body: String,
+}
+
+impl NetExporter {
+    pub fn new(address: &str) -> Result<Self, Error> {
+        let remote = address
+            .to_socket_addrs()
+            .map_err(|err| Error::new(format!("failed to resolve {address}")).with_source(err))?
+            .next()
+            .ok_or_else(|| Error::new(format!("no addresses found for {address}")))?;
+        let local: SocketAddr = if remote.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(remote).map(|()| socket))
+            .map_err(|err| {
+                Error::new(format!("failed to connect to {address}")).with_source(err)
+            })?;
+
+        Ok(Self {
+            socket,
+            body: String::with_capacity(1024),
+        })
+    }
+}
+
+impl Exporter for NetExporter {
+    fn export(&mut self, snapshot: &MetricsSnapshot) -> Result<(), Error> {
+        let body = &mut self.body;
+        body.clear();
+        _ = write!(
+            body,
+            "{{\"elapsed_s\":{:.3},\"frames\":{},\"metrics\":[",
+            snapshot.elapsed.as_secs_f64(),
+            snapshot.frames
+        );
+        for (i, metric) in snapshot.metrics.iter().enumerate() {
+            if i > 0 {
+                body.push(',');
+            }
+            body.push_str("{\"name\":");
+            json::write_str(body, metric.name);
+            match &metric.value {
+                MetricValue::Counter { total, per_frame } => {
+                    _ = write!(
+                        body,
+                        ",\"kind\":\"counter\",\"total\":{total},\"per_frame\":"
+                    );
+                    write_summary(body, per_frame);
+                }
+                MetricValue::Gauge { value, per_frame } => {
+                    body.push_str(",\"kind\":\"gauge\",\"value\":");
+                    json::write_f64(body, *value);
+                    body.push_str(",\"per_frame\":");
+                    write_summary(body, per_frame);
+                }
+                MetricValue::Histogram {
+                    summary,
+                    p50,
+                    p95,
+                    p99,
+                } => {
+                    body.push_str(",\"kind\":\"histogram\",\"summary\":");
+                    write_summary(body, summary);
+                    for (name, value) in [("p50", p50), ("p95", p95), ("p99", p99)] {
+                        _ = write!(body, ",\"{name}\":");
+                        json::write_f64(body, *value);
+                    }
+                }
+            }
+            body.push('}');
+        }
+        body.push_str("]}");
+
+        self.socket
+            .send(body.as_bytes())
+            .map(|_| ())
+            .map_err(|err| Error::new("failed to send metrics").with_source(err))
+    }
+}
+
+fn write_summary(out: &mut String, summary: &Summary) {
+    _ = write!(out, "{{\"count\":{},\"mean\":", summary.count);
+    json::write_f64(out, summary.mean());
+    out.push_str(",\"min\":");
+    json::write_f64(out, summary.min);
+    out.push_str(",\"max\":");
+    json::write_f64(out, summary.max);
+    out.push('}');
+}
@@ -0,0 +1,245 @@
+//! 2D sprites on any [`GraphicsDevice`](crate::graphics::GraphicsDevice): textured quads with a
+//! tint, rotation and scale, queued in a [`SpriteBatch`] and drawn through a [`Camera2d`] in as
+//! few draws as their textures allow.
+//!
+//! ```
+//! use common::{
+//!     graphics::{CommandList, TextureId, Vertex},
+//!     sprite::{Camera2d, Sprite, SpriteBatch},
+//! };
+//!
+//! #[derive(Default)]
+//! struct Recorder(Vec<(TextureId, usize)>);
+//!
+//! impl CommandList for Recorder {
+//!     fn clear(&mut self, _: [f32; 4]) {}
+//!
+//!     fn draw_quads(&mut self, texture: TextureId, _: &[[f32; 4]; 4], vertices: &[Vertex]) {
+//!         self.0.push((texture, vertices.len() / 4));
+//!     }
+//! }
+//!
+//! let (ship, rock) = (TextureId::from_index(0), TextureId::from_index(1));
+//! let camera = Camera2d::new(1280.0, 720.0);
+//! let mut batch = SpriteBatch::new();
+//! batch.draw(Sprite::new(rock, [100.0, 100.0], [32.0, 32.0]));
+//! batch.draw(
+//!     Sprite::new(ship, [640.0, 360.0], [64.0, 64.0])
+//!         .rotation(std::f32::consts::FRAC_PI_2)
+//!         .layer(1),
+//! );
+//! batch.draw(Sprite::new(rock, [200.0, 150.0], [32.0, 32.0]).color([1.0, 0.5, 0.5, 1.0]));
+//!
+//! // Both rocks go in one draw, under the ship on the layer above.
+//! let mut commands = Recorder::default();
+//! assert_eq!(batch.flush(&mut commands, &camera), 2);
+//! assert_eq!(commands.0, [(rock, 2), (ship, 1)]);
+//! assert!(batch.is_empty());
+//! assert_eq!(camera.screen_to_world([0.0, 0.0]), [0.0, 0.0]);
+//! ```
+
+use crate::graphics::{CommandList, TextureId, Vertex};
+
+/// An orthographic camera over a 2D world whose y axis points down, as on screen. At a zoom of 1
+/// a world unit is a pixel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2d {
+    /// The point in the world at the middle of the view.
+    pub position: [f32; 2],
+    /// How many pixels a world unit covers.
+    pub zoom: f32,
+    /// Radians the camera is turned clockwise, which turns the world the other way on screen.
+    pub rotation: f32,
+    /// The size of the view in pixels, usually the window's. Update it on resize.
+    pub viewport: [f32; 2],
+}
+
+impl Camera2d {
+    /// A camera with the world's origin at the top left of a `width` by `height` view.
+    pub fn new(width: f32, height: f32) -> Self {
+        Self {
+            position: [width / 2.0, height / 2.0],
+            zoom: 1.0,
+            rotation: 0.0,
+            viewport: [width, height],
+        }
+    }
+
+    /// The matrix from world to clip space, in columns, for [`CommandList::draw_quads`].
+    pub fn view_projection(&self) -> [[f32; 4]; 4] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x_scale = 2.0 * self.zoom / self.viewport[0].max(1.0);
+        let y_scale = -2.0 * self.zoom / self.viewport[1].max(1.0);
+        let [x, y] = self.position;
+        [
+            [x_scale * cos, -y_scale * sin, 0.0, 0.0],
+            [x_scale * sin, y_scale * cos, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [
+                -x_scale * (cos * x + sin * y),
+                -y_scale * (cos * y - sin * x),
+                0.0,
+                1.0,
+            ],
+        ]
+    }
+
+    /// The point in the world under `point`, in pixels from the view's top left, e.g. to find
+    /// what the mouse is over.
+    pub fn screen_to_world(&self, point: [f32; 2]) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = (point[0] - self.viewport[0] / 2.0) / self.zoom;
+        let y = (point[1] - self.viewport[1] / 2.0) / self.zoom;
+        [
+            self.position[0] + cos * x - sin * y,
+            self.position[1] + sin * x + cos * y,
+        ]
+    }
+
+    /// Where `point` in the world is in the view, in pixels from its top left.
+    pub fn world_to_screen(&self, point: [f32; 2]) -> [f32; 2] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let x = point[0] - self.position[0];
+        let y = point[1] - self.position[1];
+        [
+            (cos * x + sin * y) * self.zoom + self.viewport[0] / 2.0,
+            (cos * y - sin * x) * self.zoom + self.viewport[1] / 2.0,
+        ]
+    }
+}
+
+/// A textured quad to draw with a [`SpriteBatch`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprite {
+    texture: TextureId,
+    position: [f32; 2],
+    size: [f32; 2],
+    origin: [f32; 2],
+    rotation: f32,
+    scale: [f32; 2],
+    region: [f32; 4],
+    color: [f32; 4],
+    layer: i32,
+}
+
+impl Sprite {
+    /// The whole of `texture`, `size` world units big and centered on `position`, untinted.
+    pub fn new(texture: TextureId, position: [f32; 2], size: [f32; 2]) -> Self {
+        Self {
+            texture,
+            position,
+            size,
+            origin: [0.5, 0.5],
+            rotation: 0.0,
+            scale: [1.0, 1.0],
+            region: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0; 4],
+            layer: 0,
+        }
+    }
+
+    /// The point the sprite is placed and turned around, from `[0.0, 0.0]` at its top left to
+    /// `[1.0, 1.0]` at its bottom right. Defaults to its middle.
+    pub fn origin(self, origin: [f32; 2]) -> Self {
+        Self { origin, ..self }
+    }
+
+    /// Radians clockwise.
+    pub fn rotation(self, rotation: f32) -> Self {
+        Self { rotation, ..self }
+    }
+
+    /// Multiplies the size. A negative scale flips the sprite on that axis.
+    pub fn scale(self, scale: [f32; 2]) -> Self {
+        Self { scale, ..self }
+    }
+
+    /// The part of the texture drawn, as `[left, top, right, bottom]` from 0 to 1, e.g. one
+    /// frame of a sprite sheet. Defaults to all of it.
+    pub fn region(self, region: [f32; 4]) -> Self {
+        Self { region, ..self }
+    }
+
+    /// RGBA the texture's color is multiplied by. Defaults to white, which leaves it as it is.
+    pub fn color(self, color: [f32; 4]) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Sprites on higher layers are drawn over those on lower ones. Defaults to 0.
+    pub fn layer(self, layer: i32) -> Self {
+        Self { layer, ..self }
+    }
+
+    /// The corners, in order around the sprite from its top left.
+    fn corners(&self) -> [Vertex; 4] {
+        let (sin, cos) = self.rotation.sin_cos();
+        let [left, top, right, bottom] = self.region;
+        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]].map(|[u, v]| {
+            let x = (u - self.origin[0]) * self.size[0] * self.scale[0];
+            let y = (v - self.origin[1]) * self.size[1] * self.scale[1];
+            Vertex {
+                position: [
+                    self.position[0] + cos * x - sin * y,
+                    self.position[1] + sin * x + cos * y,
+                ],
+                uv: [left + u * (right - left), top + v * (bottom - top)],
+                color: self.color,
+            }
+        })
+    }
+}
+
+/// Queues sprites for a frame and draws them together. Keep one around, so its buffers are
+/// reused from frame to frame.
+#[derive(Debug, Default)]
+pub struct SpriteBatch {
+    sprites: Vec<Sprite>,
+    vertices: Vec<Vertex>,
+}
+
+impl SpriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `sprite` to be drawn at the next [`flush`](SpriteBatch::flush).
+    pub fn draw(&mut self, sprite: Sprite) {
+        self.sprites.push(sprite);
+    }
+
+    /// The number of sprites queued.
+    pub fn len(&self) -> usize {
+        self.sprites.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sprites.is_empty()
+    }
+
+    /// Draws the queued sprites through `camera` and empties the queue, returning how many
+    /// draws it took. Layers are drawn from the lowest up, and within a layer sprites are
+    /// grouped by texture, in the order they were queued.
+    ///
+    /// note: grouping reorders sprites with different textures, so ones on the same layer that
+    /// overlap may be drawn in either order. Put them on different layers if it matters.
+    pub fn flush(&mut self, commands: &mut dyn CommandList, camera: &Camera2d) -> usize {
+        self.sprites
+            .sort_by_key(|sprite| (sprite.layer, sprite.texture));
+        let transform = camera.view_projection();
+
+        let mut draws = 0;
+        for group in self
+            .sprites
+            .chunk_by(|a, b| (a.layer, a.texture) == (b.layer, b.texture))
+        {
+            self.vertices.clear();
+            self.vertices
+                .extend(group.iter().flat_map(|sprite| sprite.corners()));
+            commands.draw_quads(group[0].texture, &transform, &self.vertices);
+            draws += 1;
+        }
+
+        self.sprites.clear();
+        draws
+    }
+}
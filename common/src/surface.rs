@@ -0,0 +1,56 @@
+//! What a renderer needs from a window to present to it, so the renderer can be written against
+//! these traits rather than a platform crate's window type:
+//!
+//! ```
+//! use common::surface::{HasWindowHandle, RawWindowHandle, Surface};
+//!
+//! fn create_swap_chain(surface: &dyn Surface) -> String {
+//!     let (width, height) = surface.surface_size();
+//!     match surface.window_handle() {
+//!         RawWindowHandle::Win32 { hwnd, .. } => format!("{width}x{height} on {hwnd:#x}"),
+//!         _ => unimplemented!(),
+//!     }
+//! }
+//!
+//! struct Headless;
+//!
+//! impl HasWindowHandle for Headless {
+//!     fn window_handle(&self) -> RawWindowHandle {
+//!         RawWindowHandle::Win32 { hwnd: 0x1234, hinstance: 0 }
+//!     }
+//! }
+//!
+//! impl Surface for Headless {
+//!     fn surface_size(&self) -> (u32, u32) {
+//!         (1280, 720)
+//!     }
+//!
+//!     fn scale_factor(&self) -> f64 {
+//!         1.0
+//!     }
+//! }
+//!
+//! assert_eq!(create_swap_chain(&Headless), "1280x720 on 0x1234");
+//! ```
+
+/// A platform window handle, as swap chains and Vulkan surfaces are created from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RawWindowHandle {
+    /// An `HWND`, and the `HINSTANCE` of the module that registered its window class.
+    Win32 { hwnd: isize, hinstance: isize },
+}
+
+pub trait HasWindowHandle {
+    /// note: the handle is only valid while the window is, which the borrow doesn't enforce.
+    fn window_handle(&self) -> RawWindowHandle;
+}
+
+/// A window a renderer can present to.
+pub trait Surface: HasWindowHandle {
+    /// The size of the area to render to, in physical pixels. Zero while minimized.
+    fn surface_size(&self) -> (u32, u32);
+
+    /// Physical pixels per logical pixel, e.g. to scale UI.
+    fn scale_factor(&self) -> f64;
+}
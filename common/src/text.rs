@@ -0,0 +1,332 @@
+//! Text drawn through a [`SpriteBatch`], e.g. for the debug overlay and UI. A [`GlyphRasterizer`]
+//! such as `win32`'s `DirectWrite` turns characters into glyphs, falling back to other fonts for
+//! those a font lacks, and a [`TextRenderer`] packs each glyph into an atlas texture the first
+//! time it's drawn:
+//!
+//! ```
+//! use common::{
+//!     error::Error,
+//!     graphics::{
+//!         Backend, CommandList, GraphicsDevice, Image, PresentOptions, TextureFilter, TextureId,
+//!         Vertex,
+//!     },
+//!     sprite::SpriteBatch,
+//!     text::{FaceId, Font, Glyph, GlyphBitmap, GlyphRasterizer, LineMetrics, TextRenderer},
+//! };
+//!
+//! /// Every glyph is 8 pixels wide, and characters past ASCII come from a fallback face.
+//! struct Monospace;
+//!
+//! impl GlyphRasterizer for Monospace {
+//!     fn glyphs(&mut self, _: &Font, text: &str, glyphs: &mut Vec<Glyph>) -> Result<(), Error> {
+//!         glyphs.extend(text.chars().map(|c| Glyph {
+//!             face: FaceId::from_index(!c.is_ascii() as usize),
+//!             index: c as u16,
+//!             advance: 8.0,
+//!         }));
+//!         Ok(())
+//!     }
+//!
+//!     fn line_metrics(&mut self, _: &Font) -> Result<LineMetrics, Error> {
+//!         Ok(LineMetrics { ascent: 10.0, descent: 3.0, line_gap: 1.0 })
+//!     }
+//!
+//!     fn rasterize(&mut self, _: FaceId, glyph: u16, _: f32) -> Result<GlyphBitmap, Error> {
+//!         let (width, height) = if glyph == ' ' as u16 { (0, 0) } else { (6, 10) };
+//!         Ok(GlyphBitmap {
+//!             width,
+//!             height,
+//!             offset: [1, -10],
+//!             coverage: vec![255; (width * height) as usize],
+//!         })
+//!     }
+//! }
+//!
+//! #[derive(Default)]
+//! struct Recorder(Vec<Image>);
+//!
+//! impl CommandList for Recorder {
+//!     fn clear(&mut self, _: [f32; 4]) {}
+//!
+//!     fn draw_quads(&mut self, _: TextureId, _: &[[f32; 4]; 4], _: &[Vertex]) {}
+//! }
+//!
+//! impl GraphicsDevice for Recorder {
+//!     fn backend(&self) -> Backend {
+//!         Backend::D3D11
+//!     }
+//!
+//!     fn resize(&mut self, _width: u32, _height: u32) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//!
+//!     fn present_options(&self) -> PresentOptions {
+//!         PresentOptions::default()
+//!     }
+//!
+//!     fn set_present_options(&mut self, _: PresentOptions) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//!
+//!     fn create_texture(&mut self, image: Image, _: TextureFilter) -> Result<TextureId, Error> {
+//!         self.0.push(image);
+//!         Ok(TextureId::from_index(self.0.len() - 1))
+//!     }
+//!
+//!     fn update_texture(
+//!         &mut self,
+//!         texture: TextureId,
+//!         origin: [u32; 2],
+//!         image: &Image,
+//!     ) -> Result<(), Error> {
+//!         self.0[texture.index()].copy_from(origin, image)
+//!     }
+//!
+//!     fn destroy_texture(&mut self, _texture: TextureId) {}
+//!
+//!     fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error> {
+//!         Ok(self)
+//!     }
+//!
+//!     fn end_frame(&mut self) -> Result<(), Error> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut device = Recorder::default();
+//! let mut text = TextRenderer::new(&mut device, Monospace, 256)?;
+//! let font = Font::new("Segoe UI", 14.0);
+//! let mut batch = SpriteBatch::new();
+//!
+//! // A sprite for each glyph that isn't blank, and the size of the text.
+//! let size = text.draw(&mut batch, &font, "Hi there\n漢字", [10.0, 10.0], [1.0; 4], 0)?;
+//! assert_eq!(size, [64.0, 28.0]);
+//! assert_eq!(batch.len(), 9);
+//! assert_eq!(text.measure(&font, "Hi there\n漢字")?, size);
+//!
+//! // New glyphs reach the atlas texture when it's uploaded, before the batch is flushed.
+//! assert!(device.0[text.texture().index()].pixels().iter().skip(3).step_by(4).all(|&a| a == 0));
+//! text.upload(&mut device)?;
+//! assert!(device.0[text.texture().index()].pixels().iter().skip(3).step_by(4).any(|&a| a == 255));
+//! # Ok::<(), Error>(())
+//! ```
+
+use crate::{
+    error::Error,
+    graphics::{GraphicsDevice, TextureId},
+    sprite::{Sprite, SpriteBatch},
+};
+
+use self::atlas::GlyphAtlas;
+
+mod atlas;
+
+/// A font family at a size, which a [`GlyphRasterizer`] picks the face for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+    pub family: String,
+    /// Pixels per em.
+    pub size: f32,
+    /// From 100 for thin to 900 for black, where 400 is regular and 700 bold.
+    pub weight: u16,
+    pub italic: bool,
+}
+
+impl Font {
+    /// The regular weight of `family`, upright, `size` pixels per em.
+    pub fn new<S: Into<String>>(family: S, size: f32) -> Self {
+        Self {
+            family: family.into(),
+            size,
+            weight: 400,
+            italic: false,
+        }
+    }
+}
+
+/// Names a font face a [`GlyphRasterizer`] has loaded, e.g. a font's or one it falls back to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FaceId(u32);
+
+impl FaceId {
+    /// For use by [`GlyphRasterizer`] implementations, which decide what indices mean.
+    pub fn from_index(index: usize) -> Self {
+        Self(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A character's glyph in the face that has it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glyph {
+    pub face: FaceId,
+    /// The glyph's index in its face.
+    pub index: u16,
+    /// Pixels to move the pen right by after it.
+    pub advance: f32,
+}
+
+/// A rasterized glyph's coverage, a byte per pixel in rows from the top.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GlyphBitmap {
+    pub width: u32,
+    pub height: u32,
+    /// Pixels from the pen on the baseline to the bitmap's top left, so usually up.
+    pub offset: [i32; 2],
+    pub coverage: Vec<u8>,
+}
+
+/// How a font's lines are spaced, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LineMetrics {
+    /// From the top of the line to the baseline.
+    pub ascent: f32,
+    /// From the baseline to the bottom of the line.
+    pub descent: f32,
+    /// Extra space between lines.
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    /// From one baseline to the next.
+    pub fn line_height(&self) -> f32 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
+
+/// Turns text into glyphs and glyphs into bitmaps, for a [`TextRenderer`].
+pub trait GlyphRasterizer {
+    /// Appends a glyph for each character of `text`, which is one line, in `font` or whichever
+    /// fallback face has it.
+    fn glyphs(&mut self, font: &Font, text: &str, glyphs: &mut Vec<Glyph>) -> Result<(), Error>;
+
+    /// How lines of `font` are spaced.
+    fn line_metrics(&mut self, font: &Font) -> Result<LineMetrics, Error>;
+
+    /// Rasterizes `glyph` of `face` at `size` pixels per em. Blank glyphs, e.g. spaces, are
+    /// empty.
+    fn rasterize(&mut self, face: FaceId, glyph: u16, size: f32) -> Result<GlyphBitmap, Error>;
+}
+
+/// Lays out and draws text with the glyphs of a [`GlyphRasterizer`], cached in an atlas
+/// texture. Keep one around, so glyphs are only rasterized once.
+pub struct TextRenderer {
+    rasterizer: Box<dyn GlyphRasterizer>,
+    atlas: GlyphAtlas,
+    /// The glyphs of the line being laid out, kept to reuse.
+    glyphs: Vec<Glyph>,
+}
+
+impl TextRenderer {
+    /// Creates an `atlas_size` pixels square atlas texture on `device` for the glyphs of
+    /// `rasterizer`. 1024 holds thousands of glyphs at UI sizes.
+    pub fn new(
+        device: &mut dyn GraphicsDevice,
+        rasterizer: impl GlyphRasterizer + 'static,
+        atlas_size: u32,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            rasterizer: Box::new(rasterizer),
+            atlas: GlyphAtlas::new(device, atlas_size)?,
+            glyphs: Vec::new(),
+        })
+    }
+
+    /// The atlas texture glyphs are drawn from.
+    pub fn texture(&self) -> TextureId {
+        self.atlas.texture()
+    }
+
+    /// Queues a sprite for each glyph of `text` on `batch`, with its top left at `position` and
+    /// tinted `color`, returning its width and height. Lines are split at `'\n'`.
+    ///
+    /// note: glyphs that don't fit in the atlas are left out this frame, and the atlas is
+    /// cleared at the next [`upload`](TextRenderer::upload) to make room.
+    pub fn draw(
+        &mut self,
+        batch: &mut SpriteBatch,
+        font: &Font,
+        text: &str,
+        position: [f32; 2],
+        color: [f32; 4],
+        layer: i32,
+    ) -> Result<[f32; 2], Error> {
+        let texture = self.atlas.texture();
+        let atlas = &mut self.atlas;
+        layout(
+            self.rasterizer.as_mut(),
+            &mut self.glyphs,
+            font,
+            text,
+            position,
+            |rasterizer, glyph, pen| {
+                let Some(glyph) = atlas.get(rasterizer, glyph, font.size)? else {
+                    return Ok(());
+                };
+                let position = [
+                    pen[0].round() + glyph.offset[0],
+                    pen[1].round() + glyph.offset[1],
+                ];
+                batch.draw(
+                    Sprite::new(texture, position, glyph.size)
+                        .origin([0.0, 0.0])
+                        .region(glyph.region)
+                        .color(color)
+                        .layer(layer),
+                );
+                Ok(())
+            },
+        )
+    }
+
+    /// The width and height `text` would be drawn at.
+    pub fn measure(&mut self, font: &Font, text: &str) -> Result<[f32; 2], Error> {
+        layout(
+            self.rasterizer.as_mut(),
+            &mut self.glyphs,
+            font,
+            text,
+            [0.0, 0.0],
+            |_, _, _| Ok(()),
+        )
+    }
+
+    /// Copies the glyphs added since the last upload to the atlas texture. Call it each frame
+    /// after drawing text and before flushing the batch.
+    pub fn upload(&mut self, device: &mut dyn GraphicsDevice) -> Result<(), Error> {
+        self.atlas.upload(device)
+    }
+}
+
+/// Calls `place` with each glyph of `text` and where the pen is on its baseline, returning the
+/// text's width and height.
+fn layout(
+    rasterizer: &mut dyn GlyphRasterizer,
+    glyphs: &mut Vec<Glyph>,
+    font: &Font,
+    text: &str,
+    position: [f32; 2],
+    mut place: impl FnMut(&mut dyn GlyphRasterizer, Glyph, [f32; 2]) -> Result<(), Error>,
+) -> Result<[f32; 2], Error> {
+    let metrics = rasterizer.line_metrics(font)?;
+    let mut width: f32 = 0.0;
+    let mut lines = 0;
+    for line in text.split('\n') {
+        glyphs.clear();
+        rasterizer.glyphs(font, line.trim_end_matches('\r'), glyphs)?;
+
+        let baseline = position[1] + metrics.ascent + lines as f32 * metrics.line_height();
+        let mut pen = 0.0;
+        for glyph in glyphs.iter() {
+            place(rasterizer, *glyph, [position[0] + pen, baseline])?;
+            pen += glyph.advance;
+        }
+        width = width.max(pen);
+        lines += 1;
+    }
+
+    Ok([width, lines as f32 * metrics.line_height()])
+}
@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+
+use crate::{
+    error::Error,
+    graphics::{GraphicsDevice, Image, TextureFilter, TextureId},
+    text::{FaceId, Glyph, GlyphRasterizer},
+};
+
+/// Pixels left empty right of and below each glyph, so linear filtering doesn't bleed its
+/// neighbors in.
+const PADDING: u32 = 1;
+
+/// Shelves are made in multiples of this height, so glyphs of similar heights share them.
+const SHELF_STEP: u32 = 4;
+
+/// Where a glyph is in the atlas and how to place it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct AtlasGlyph {
+    /// `[left, top, right, bottom]` in the atlas texture, from 0 to 1.
+    pub(super) region: [f32; 4],
+    /// From the pen on the baseline to the glyph's top left.
+    pub(super) offset: [f32; 2],
+    pub(super) size: [f32; 2],
+}
+
+/// A row of glyphs, filled from the left.
+#[derive(Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+/// Glyph bitmaps packed into shelves of a texture as they're first drawn. Each is white, with
+/// its coverage as alpha, so sprites tint it.
+pub(super) struct GlyphAtlas {
+    texture: TextureId,
+    /// The texture's pixels, which are copied to it as they change.
+    image: Image,
+    shelves: Vec<Shelf>,
+    /// Each glyph by face, index and size bits, or `None` if it's blank or too big to fit.
+    glyphs: HashMap<(FaceId, u16, u32), Option<AtlasGlyph>>,
+    /// `[left, top, right, bottom]` in pixels of what's changed since the last upload.
+    dirty: Option<[u32; 4]>,
+    /// Whether a glyph didn't fit, so the atlas is cleared after the next upload.
+    full: bool,
+}
+
+impl GlyphAtlas {
+    pub(super) fn new(device: &mut dyn GraphicsDevice, size: u32) -> Result<Self, Error> {
+        let image = Image::solid(size, size, [255, 255, 255, 0]);
+        let texture = device.create_texture(image.clone(), TextureFilter::Linear)?;
+        Ok(Self {
+            texture,
+            image,
+            shelves: Vec::new(),
+            glyphs: HashMap::new(),
+            dirty: None,
+            full: false,
+        })
+    }
+
+    pub(super) fn texture(&self) -> TextureId {
+        self.texture
+    }
+
+    /// Where `glyph` at `size` is, rasterizing it into the atlas the first time. `None` if it's
+    /// blank, or there's no room for it.
+    pub(super) fn get(
+        &mut self,
+        rasterizer: &mut dyn GlyphRasterizer,
+        glyph: Glyph,
+        size: f32,
+    ) -> Result<Option<AtlasGlyph>, Error> {
+        let key = (glyph.face, glyph.index, size.to_bits());
+        if let Some(entry) = self.glyphs.get(&key) {
+            return Ok(*entry);
+        }
+        if self.full {
+            return Ok(None);
+        }
+
+        let bitmap = rasterizer.rasterize(glyph.face, glyph.index, size)?;
+        let (width, height) = (bitmap.width, bitmap.height);
+        let atlas_size = self.image.width();
+        let too_big = width + PADDING > atlas_size
+            || (height + PADDING).next_multiple_of(SHELF_STEP) > atlas_size;
+        if width == 0 || height == 0 || too_big {
+            self.glyphs.insert(key, None);
+            return Ok(None);
+        }
+        let Some([x, y]) = self.allocate(width + PADDING, height + PADDING) else {
+            self.full = true;
+            return Ok(None);
+        };
+
+        let pixels = bitmap
+            .coverage
+            .iter()
+            .flat_map(|&coverage| [255, 255, 255, coverage])
+            .collect();
+        self.image
+            .copy_from([x, y], &Image::new(width, height, pixels)?)?;
+        let [left, top, right, bottom] = self.dirty.unwrap_or([x, y, x, y]);
+        self.dirty = Some([
+            left.min(x),
+            top.min(y),
+            right.max(x + width),
+            bottom.max(y + height),
+        ]);
+
+        let scale = 1.0 / atlas_size as f32;
+        let entry = AtlasGlyph {
+            region: [x, y, x + width, y + height].map(|edge| edge as f32 * scale),
+            offset: bitmap.offset.map(|offset| offset as f32),
+            size: [width as f32, height as f32],
+        };
+        self.glyphs.insert(key, Some(entry));
+        Ok(Some(entry))
+    }
+
+    /// Copies what's changed to the texture, then clears the atlas if it filled up, for the
+    /// glyphs drawn next to be packed again.
+    pub(super) fn upload(&mut self, device: &mut dyn GraphicsDevice) -> Result<(), Error> {
+        if let Some([left, top, right, bottom]) = self.dirty.take() {
+            let stride = self.image.width() as usize * 4;
+            let pixels = (top..bottom)
+                .flat_map(|y| {
+                    let start = y as usize * stride + left as usize * 4;
+                    &self.image.pixels()[start..start + (right - left) as usize * 4]
+                })
+                .copied()
+                .collect();
+            let region = Image::new(right - left, bottom - top, pixels)?;
+            device.update_texture(self.texture, [left, top], &region)?;
+        }
+
+        if self.full {
+            let size = self.image.width();
+            self.image = Image::solid(size, size, [255, 255, 255, 0]);
+            self.shelves.clear();
+            self.glyphs.clear();
+            self.dirty = Some([0, 0, size, size]);
+            self.full = false;
+        }
+        Ok(())
+    }
+
+    /// The top left of a free `width` by `height` space, on the first shelf of its height with
+    /// room or a new one below the others.
+    fn allocate(&mut self, width: u32, height: u32) -> Option<[u32; 2]> {
+        let size = self.image.width();
+        let height = height.next_multiple_of(SHELF_STEP);
+        if let Some(shelf) = self
+            .shelves
+            .iter_mut()
+            .find(|shelf| shelf.height == height && shelf.x + width <= size)
+        {
+            shelf.x += width;
+            return Some([shelf.x - width, shelf.y]);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        if y + height > size {
+            return None;
+        }
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some([0, y])
+    }
+}
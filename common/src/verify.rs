@@ -0,0 +1,74 @@
+//! What `verify!` and `verify_eq!` do after logging a failed check.
+//!
+//! Unlike `assert!`, a failed check doesn't panic: it logs at ERROR, so the failure reaches every
+//! sink, and then either breaks into an attached debugger or carries on. Both macros return
+//! whether the check passed so the caller can recover:
+//!
+//! ```
+//! fn set_volume(volume: f32) {
+//!     if !common::verify!((0.0..=1.0).contains(&volume), "volume {volume} out of range") {
+//!         return;
+//!     }
+//!     // ...
+//! }
+//! # set_volume(2.0);
+//! ```
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::log::emergency_flush;
+
+/// Breaking is the default in builds with debug assertions, continuing otherwise.
+static BREAK_ON_FAILURE: AtomicBool = AtomicBool::new(cfg!(debug_assertions));
+
+#[cfg(windows)]
+extern "system" {
+    fn IsDebuggerPresent() -> i32;
+    fn DebugBreak();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFailure {
+    /// Breaks into the debugger if one is attached, on Windows. Elsewhere this continues.
+    Break,
+    Continue,
+}
+
+pub fn set_on_failure(on_failure: OnFailure) {
+    BREAK_ON_FAILURE.store(on_failure == OnFailure::Break, Ordering::Relaxed);
+}
+
+pub fn on_failure() -> OnFailure {
+    if BREAK_ON_FAILURE.load(Ordering::Relaxed) {
+        OnFailure::Break
+    } else {
+        OnFailure::Continue
+    }
+}
+
+#[doc(hidden)]
+pub fn __failed() {
+    if on_failure() == OnFailure::Break && debugger_present() {
+        // Get the failure into the debugger's output before stopping there.
+        emergency_flush();
+        debug_break();
+    }
+}
+
+#[cfg(windows)]
+fn debugger_present() -> bool {
+    unsafe { IsDebuggerPresent() != 0 }
+}
+
+#[cfg(not(windows))]
+fn debugger_present() -> bool {
+    false
+}
+
+#[cfg(windows)]
+fn debug_break() {
+    unsafe { DebugBreak() };
+}
+
+#[cfg(not(windows))]
+fn debug_break() {}
@@ -0,0 +1,125 @@
+//! A minimal zip archive writer for bundling files into one upload, e.g. crash reports.
+//!
+//! ```
+//! use common::zip::ZipWriter;
+//!
+//! let mut zip = ZipWriter::new();
+//! zip.add("notes.txt", b"ahoy");
+//! let bytes = zip.finish();
+//! assert_eq!(&bytes[..4], b"PK\x03\x04");
+//! ```
+
+use std::time::SystemTime;
+
+use crate::{gzip, log::timestamp::Timestamp};
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const VERSION: u16 = 20;
+/// Names are UTF-8.
+const FLAG_UTF8: u16 = 0x0800;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Builds a zip archive in memory. Entries are deflated unless that doesn't make them smaller.
+///
+/// note: there's no zip64 support, so entries and the archive must stay under 4 GiB and 65535
+/// entries.
+pub struct ZipWriter {
+    bytes: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+    time: u16,
+    date: u16,
+}
+
+impl ZipWriter {
+    /// Entries are stamped with the current UTC time.
+    pub fn new() -> Self {
+        let now = Timestamp::from_system_time(SystemTime::now());
+        Self {
+            bytes: Vec::new(),
+            central: Vec::new(),
+            entries: 0,
+            time: ((now.hour << 11) | (now.minute << 5) | (now.second / 2)) as u16,
+            date: (((now.year.max(1980) - 1980) << 9) | i64::from((now.month << 5) | now.day))
+                as u16,
+        }
+    }
+
+    /// Adds a file. `name` is a relative path with `/` separators.
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        let crc = gzip::crc32(data);
+        let deflated = gzip::deflate(data);
+        let (method, stored) = if deflated.len() < data.len() {
+            (DEFLATED, deflated.as_slice())
+        } else {
+            (STORED, data)
+        };
+        let offset = self.bytes.len() as u32;
+
+        let header = |out: &mut Vec<u8>| {
+            put_u16(out, FLAG_UTF8);
+            put_u16(out, method);
+            put_u16(out, self.time);
+            put_u16(out, self.date);
+            put_u32(out, crc);
+            put_u32(out, stored.len() as u32);
+            put_u32(out, data.len() as u32);
+            put_u16(out, name.len() as u16);
+            put_u16(out, 0);
+        };
+
+        put_u32(&mut self.bytes, LOCAL_HEADER);
+        put_u16(&mut self.bytes, VERSION);
+        header(&mut self.bytes);
+        self.bytes.extend_from_slice(name.as_bytes());
+        self.bytes.extend_from_slice(stored);
+
+        put_u32(&mut self.central, CENTRAL_HEADER);
+        put_u16(&mut self.central, VERSION);
+        put_u16(&mut self.central, VERSION);
+        header(&mut self.central);
+        // Comment length, disk number, internal and external attributes.
+        put_u16(&mut self.central, 0);
+        put_u16(&mut self.central, 0);
+        put_u16(&mut self.central, 0);
+        put_u32(&mut self.central, 0);
+        put_u32(&mut self.central, offset);
+        self.central.extend_from_slice(name.as_bytes());
+
+        self.entries += 1;
+    }
+
+    /// Appends the central directory and returns the archive.
+    pub fn finish(self) -> Vec<u8> {
+        let mut bytes = self.bytes;
+        let offset = bytes.len() as u32;
+        bytes.extend_from_slice(&self.central);
+
+        put_u32(&mut bytes, END_OF_CENTRAL_DIRECTORY);
+        put_u16(&mut bytes, 0);
+        put_u16(&mut bytes, 0);
+        put_u16(&mut bytes, self.entries);
+        put_u16(&mut bytes, self.entries);
+        put_u32(&mut bytes, self.central.len() as u32);
+        put_u32(&mut bytes, offset);
+        put_u16(&mut bytes, 0);
+        bytes
+    }
+}
+
+impl Default for ZipWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
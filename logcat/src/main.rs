@@ -0,0 +1,117 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    process::ExitCode,
+};
+
+use common::log::{
+    binary::{BinaryDecoder, BinaryRecord},
+    timestamp::Timestamp,
+};
+
+fn main() -> ExitCode {
+    let Some(path) = std::env::args().nth(1) else {
+        eprintln!("usage: galleon-logcat <file | ->");
+        return ExitCode::FAILURE;
+    };
+
+    let reader: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        match File::open(&path) {
+            Ok(file) => Box::new(file),
+            Err(err) => {
+                eprintln!("failed to open {path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+    };
+
+    match run(BufReader::new(reader)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run<R: Read>(reader: R) -> Result<(), common::error::Error> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    for record in BinaryDecoder::new(reader)? {
+        if writeln!(out, "{}", format(&record?)).is_err() {
+            // The reader went away, e.g. the output was piped into `head`.
+            return Ok(());
+        }
+    }
+    _ = out.flush();
+
+    Ok(())
+}
+
+fn format(record: &BinaryRecord) -> String {
+    let mut line = format!(
+        "[{}][{}][{}][{}:{}] {}",
+        Timestamp::from_system_time(record.timestamp),
+        record.level,
+        record.target,
+        record.file.as_deref().unwrap_or("unknown"),
+        record
+            .line
+            .map_or_else(|| "unknown".to_string(), |line| line.to_string()),
+        record.message
+    );
+    for (name, value) in &record.fields {
+        line.push_str(&format!(" {name}={value}"));
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use common::log::{
+        binary::{BinaryDecoder, BinaryEncoder},
+        Field, FieldValue, Level, LogRecord,
+    };
+
+    use super::format;
+
+    #[test]
+    fn formats_decoded_records() {
+        let record = LogRecord {
+            timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_123_456),
+            fields: vec![
+                Field {
+                    name: "frame",
+                    value: FieldValue::U64(7),
+                },
+                Field {
+                    name: "path",
+                    value: FieldValue::Str("assets/ship.png".into()),
+                },
+            ],
+            file: Some("src/assets.rs"),
+            line: Some(12),
+            ..LogRecord::new(Level::ERROR, "game::assets", "failed to load texture")
+        };
+        let mut encoder = BinaryEncoder::new();
+        let mut bytes = Vec::new();
+        encoder.header(&mut bytes);
+        encoder.encode(&record, &mut bytes);
+        encoder.encode(&LogRecord::new(Level::INFO, "game", "started"), &mut bytes);
+
+        let lines: Vec<_> = BinaryDecoder::new(bytes.as_slice())
+            .unwrap()
+            .map(|record| format(&record.unwrap()))
+            .collect();
+        assert_eq!(
+            lines[0],
+            "[2023-11-14T22:13:20.123456Z][ERROR][game::assets][src/assets.rs:12] \
+             failed to load texture frame=7 path=assets/ship.png"
+        );
+        assert!(lines[1].ends_with("][INFO][game][unknown:unknown] started"));
+    }
+}
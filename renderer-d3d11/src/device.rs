@@ -0,0 +1,180 @@
+//! The D3D11 device, its immediate context and the DXGI factory that made its adapter.
+
+use std::ffi::c_void;
+
+use common::error::Error;
+use tracing::{debug, error, info, warn};
+use win32_base::{com::ComPtr, error::Hresult};
+
+use crate::ffi::{
+    D3D11CreateDevice, ID3D11DeviceContextVtbl, ID3D11DeviceVtbl, ID3D11InfoQueueVtbl,
+    IDXGIDeviceVtbl, IDXGIObjectVtbl, Message, D3D11_CREATE_DEVICE_BGRA_SUPPORT,
+    D3D11_CREATE_DEVICE_DEBUG, D3D11_MESSAGE_SEVERITY_CORRUPTION, D3D11_MESSAGE_SEVERITY_ERROR,
+    D3D11_MESSAGE_SEVERITY_INFO, D3D11_MESSAGE_SEVERITY_WARNING, D3D11_SDK_VERSION,
+    D3D_DRIVER_TYPE_HARDWARE, D3D_FEATURE_LEVEL_10_0, D3D_FEATURE_LEVEL_10_1,
+    D3D_FEATURE_LEVEL_11_0, DXGI_ERROR_SDK_COMPONENT_MISSING, IID_ID3D11_INFO_QUEUE,
+    IID_IDXGI_DEVICE, IID_IDXGI_FACTORY_2,
+};
+
+const FEATURE_LEVELS: [i32; 3] = [
+    D3D_FEATURE_LEVEL_11_0,
+    D3D_FEATURE_LEVEL_10_1,
+    D3D_FEATURE_LEVEL_10_0,
+];
+
+pub(crate) struct Device {
+    pub(crate) device: ComPtr,
+    pub(crate) context: ComPtr,
+    pub(crate) factory: ComPtr,
+    /// The debug layer's messages, if it's on.
+    info_queue: Option<ComPtr>,
+    feature_level: i32,
+}
+
+impl Device {
+    /// Creates a device on the default adapter. With `debug`, the debug layer is enabled if it's
+    /// installed.
+    pub(crate) fn new(debug: bool) -> Result<Self, Error> {
+        let (device, context, feature_level) = match create(debug) {
+            Err(err)
+                if debug
+                    && err.downcast_source()
+                        == Some(&Hresult(DXGI_ERROR_SDK_COMPONENT_MISSING)) =>
+            {
+                warn!("the D3D11 debug layer isn't installed, continuing without it");
+                create(false)?
+            }
+            result => result?,
+        };
+
+        let dxgi_device = device.query(&IID_IDXGI_DEVICE, "failed to get IDXGIDevice")?;
+        let mut adapter = std::ptr::null_mut();
+        let hr = unsafe {
+            (dxgi_device.vtbl::<IDXGIDeviceVtbl>().get_adapter)(dxgi_device.as_raw(), &mut adapter)
+        };
+        let adapter = unsafe { ComPtr::from_call(hr, adapter, "failed to get the DXGI adapter") }?;
+        let mut factory = std::ptr::null_mut();
+        let hr = unsafe {
+            (adapter.vtbl::<IDXGIObjectVtbl>().get_parent)(
+                adapter.as_raw(),
+                &IID_IDXGI_FACTORY_2,
+                &mut factory,
+            )
+        };
+        let factory = unsafe { ComPtr::from_call(hr, factory, "failed to get IDXGIFactory2") }?;
+
+        // note: only there when the debug layer is.
+        let info_queue = device
+            .query(&IID_ID3D11_INFO_QUEUE, "failed to get ID3D11InfoQueue")
+            .ok();
+        let device = Self {
+            device,
+            context,
+            factory,
+            info_queue,
+            feature_level,
+        };
+        let (major, minor) = device.feature_level();
+        info!(
+            debug_layer = device.info_queue.is_some(),
+            "created D3D11 device with feature level {major}.{minor}"
+        );
+
+        Ok(device)
+    }
+
+    /// The feature level the device was created with, as `(major, minor)`.
+    pub(crate) fn feature_level(&self) -> (u32, u32) {
+        let level = self.feature_level as u32;
+        (level >> 12, (level >> 8) & 0xf)
+    }
+
+    /// Why the device was lost, after a call failed with `DXGI_ERROR_DEVICE_REMOVED`.
+    pub(crate) fn removed_reason(&self) -> Hresult {
+        let hr = unsafe {
+            (self
+                .device
+                .vtbl::<ID3D11DeviceVtbl>()
+                .get_device_removed_reason)(self.device.as_raw())
+        };
+        Hresult(hr)
+    }
+
+    pub(crate) fn context_vtbl(&self) -> &ID3D11DeviceContextVtbl {
+        unsafe { self.context.vtbl() }
+    }
+
+    /// Logs and clears the debug layer's messages.
+    pub(crate) fn drain_messages(&self) {
+        let Some(queue) = &self.info_queue else {
+            return;
+        };
+
+        let vtbl = unsafe { queue.vtbl::<ID3D11InfoQueueVtbl>() };
+        let count = unsafe { (vtbl.get_num_stored_messages)(queue.as_raw()) };
+        // note: u64s to align the `Message` at the start of the buffer.
+        let mut buffer: Vec<u64> = Vec::new();
+        for index in 0..count {
+            let mut len = 0;
+            let hr = unsafe {
+                (vtbl.get_message)(queue.as_raw(), index, std::ptr::null_mut(), &mut len)
+            };
+            if hr < 0 || len < size_of::<Message>() {
+                continue;
+            }
+
+            buffer.resize(len.div_ceil(size_of::<u64>()), 0);
+            let message = buffer.as_mut_ptr().cast::<Message>();
+            let hr = unsafe { (vtbl.get_message)(queue.as_raw(), index, message, &mut len) };
+            if hr < 0 {
+                continue;
+            }
+
+            let message = unsafe { &*message };
+            let description =
+                unsafe { std::slice::from_raw_parts(message.description, message.description_len) };
+            let description = String::from_utf8_lossy(description);
+            let description = description.trim_end_matches('\0');
+            let id = message.id;
+            match message.severity {
+                D3D11_MESSAGE_SEVERITY_CORRUPTION | D3D11_MESSAGE_SEVERITY_ERROR => {
+                    error!(id, "{description}")
+                }
+                D3D11_MESSAGE_SEVERITY_WARNING => warn!(id, "{description}"),
+                D3D11_MESSAGE_SEVERITY_INFO => info!(id, "{description}"),
+                _ => debug!(id, "{description}"),
+            }
+        }
+        unsafe { (vtbl.clear_stored_messages)(queue.as_raw()) };
+    }
+}
+
+fn create(debug: bool) -> Result<(ComPtr, ComPtr, i32), Error> {
+    let mut flags = D3D11_CREATE_DEVICE_BGRA_SUPPORT;
+    if debug {
+        flags |= D3D11_CREATE_DEVICE_DEBUG;
+    }
+
+    let mut device: *mut c_void = std::ptr::null_mut();
+    let mut context: *mut c_void = std::ptr::null_mut();
+    let mut feature_level = 0;
+    let hr = unsafe {
+        D3D11CreateDevice(
+            std::ptr::null_mut(),
+            D3D_DRIVER_TYPE_HARDWARE,
+            0,
+            flags,
+            FEATURE_LEVELS.as_ptr(),
+            FEATURE_LEVELS.len() as u32,
+            D3D11_SDK_VERSION,
+            &mut device,
+            &mut feature_level,
+            &mut context,
+        )
+    };
+    // note: take ownership of both before checking, so neither leaks if only one was returned.
+    let device = unsafe { ComPtr::from_call(hr, device, "failed to create D3D11 device") };
+    let context = unsafe { ComPtr::from_call(hr, context, "failed to create D3D11 device") };
+
+    Ok((device?, context?, feature_level))
+}
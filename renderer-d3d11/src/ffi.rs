@@ -0,0 +1,617 @@
+//! The parts of D3D11 and DXGI the renderer calls. windows-sys 0.52 has no `Direct3D` or `Dxgi`
+//! modules at all, only the `windows` crate does, so the constants and structs are here as well
+//! as the vtables. Vtables list the slots that get called, with the ones before them padded out.
+//!
+//! note: the structs' sizes, and the offsets of the fields after padding, are checked against
+//! the x64 SDK headers at the bottom, so a slip fails the build instead of the driver's reads.
+
+use std::ffi::c_void;
+
+use win32_base::com::IUnknownVtbl;
+use windows_sys::{
+    core::{GUID, HRESULT},
+    Win32::Foundation::{BOOL, HANDLE, HMODULE, HWND, RECT},
+};
+
+pub(crate) const IID_ID3D11_INFO_QUEUE: GUID =
+    GUID::from_u128(0x6543dbb6_1b48_42f5_ab82_e97ec74326f6);
+pub(crate) const IID_ID3D11_TEXTURE_2D: GUID =
+    GUID::from_u128(0x6f15aaf2_d208_4e89_9ab4_489535d34f9c);
+pub(crate) const IID_IDXGI_DEVICE: GUID = GUID::from_u128(0x54ec77fa_1377_44e6_8c32_88fd5f44c84c);
+pub(crate) const IID_IDXGI_FACTORY_2: GUID =
+    GUID::from_u128(0x50c83a1c_e072_4c48_87b0_3630fa36a6d0);
+pub(crate) const IID_IDXGI_FACTORY_5: GUID =
+    GUID::from_u128(0x7632e1f5_ee65_4dca_87fd_84cd75f8838d);
+pub(crate) const IID_IDXGI_OUTPUT_6: GUID = GUID::from_u128(0x068346e8_aaec_4b84_add7_137f513f77a1);
+pub(crate) const IID_IDXGI_SWAP_CHAIN_3: GUID =
+    GUID::from_u128(0x94d99bdb_f1f8_4ab0_b236_7da0170edab1);
+
+pub(crate) const D3D11_SDK_VERSION: u32 = 7;
+pub(crate) const D3D_DRIVER_TYPE_HARDWARE: i32 = 1;
+pub(crate) const D3D11_CREATE_DEVICE_DEBUG: u32 = 0x2;
+pub(crate) const D3D11_CREATE_DEVICE_BGRA_SUPPORT: u32 = 0x20;
+pub(crate) const D3D_FEATURE_LEVEL_11_0: i32 = 0xb000;
+pub(crate) const D3D_FEATURE_LEVEL_10_1: i32 = 0xa100;
+pub(crate) const D3D_FEATURE_LEVEL_10_0: i32 = 0xa000;
+
+pub(crate) const D3D11_USAGE_DEFAULT: u32 = 0;
+pub(crate) const D3D11_USAGE_IMMUTABLE: u32 = 1;
+pub(crate) const D3D11_USAGE_DYNAMIC: u32 = 2;
+pub(crate) const D3D11_BIND_VERTEX_BUFFER: u32 = 0x1;
+pub(crate) const D3D11_BIND_INDEX_BUFFER: u32 = 0x2;
+pub(crate) const D3D11_BIND_CONSTANT_BUFFER: u32 = 0x4;
+pub(crate) const D3D11_BIND_SHADER_RESOURCE: u32 = 0x8;
+pub(crate) const D3D11_CPU_ACCESS_WRITE: u32 = 0x10000;
+pub(crate) const D3D11_MAP_WRITE_DISCARD: u32 = 4;
+pub(crate) const D3D11_INPUT_PER_VERTEX_DATA: u32 = 0;
+pub(crate) const D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST: u32 = 4;
+pub(crate) const D3D11_BLEND_ONE: u32 = 2;
+pub(crate) const D3D11_BLEND_SRC_ALPHA: u32 = 5;
+pub(crate) const D3D11_BLEND_INV_SRC_ALPHA: u32 = 6;
+pub(crate) const D3D11_BLEND_OP_ADD: u32 = 1;
+pub(crate) const D3D11_COLOR_WRITE_ENABLE_ALL: u8 = 0xf;
+pub(crate) const D3D11_FILL_SOLID: u32 = 3;
+pub(crate) const D3D11_CULL_NONE: u32 = 1;
+pub(crate) const D3D11_FILTER_MIN_MAG_MIP_POINT: u32 = 0;
+pub(crate) const D3D11_FILTER_MIN_MAG_MIP_LINEAR: u32 = 0x15;
+pub(crate) const D3D11_TEXTURE_ADDRESS_CLAMP: u32 = 3;
+pub(crate) const D3D11_COMPARISON_NEVER: u32 = 1;
+
+pub(crate) const D3D11_MESSAGE_SEVERITY_CORRUPTION: i32 = 0;
+pub(crate) const D3D11_MESSAGE_SEVERITY_ERROR: i32 = 1;
+pub(crate) const D3D11_MESSAGE_SEVERITY_WARNING: i32 = 2;
+pub(crate) const D3D11_MESSAGE_SEVERITY_INFO: i32 = 3;
+
+pub(crate) const DXGI_FORMAT_UNKNOWN: u32 = 0;
+pub(crate) const DXGI_FORMAT_R32G32B32A32_FLOAT: u32 = 2;
+pub(crate) const DXGI_FORMAT_R16G16B16A16_FLOAT: u32 = 10;
+pub(crate) const DXGI_FORMAT_R10G10B10A2_UNORM: u32 = 24;
+pub(crate) const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+pub(crate) const DXGI_FORMAT_R32G32_FLOAT: u32 = 16;
+pub(crate) const DXGI_FORMAT_R32_UINT: u32 = 42;
+pub(crate) const DXGI_FORMAT_B8G8R8A8_UNORM: u32 = 87;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709: u32 = 0;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709: u32 = 1;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020: u32 = 12;
+pub(crate) const DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT: u32 = 0x1;
+pub(crate) const DXGI_USAGE_RENDER_TARGET_OUTPUT: u32 = 0x20;
+pub(crate) const DXGI_SCALING_STRETCH: u32 = 0;
+pub(crate) const DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL: u32 = 3;
+pub(crate) const DXGI_SWAP_EFFECT_FLIP_DISCARD: u32 = 4;
+pub(crate) const DXGI_ALPHA_MODE_IGNORE: u32 = 3;
+pub(crate) const DXGI_MWA_NO_ALT_ENTER: u32 = 0x2;
+pub(crate) const DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT: u32 = 0x40;
+pub(crate) const DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING: u32 = 0x800;
+pub(crate) const DXGI_PRESENT_ALLOW_TEARING: u32 = 0x200;
+pub(crate) const DXGI_FEATURE_PRESENT_ALLOW_TEARING: i32 = 0;
+
+pub(crate) const DXGI_ERROR_DEVICE_REMOVED: HRESULT = 0x887a0005_u32 as i32;
+pub(crate) const DXGI_ERROR_DEVICE_RESET: HRESULT = 0x887a0007_u32 as i32;
+pub(crate) const DXGI_ERROR_SDK_COMPONENT_MISSING: HRESULT = 0x887a002d_u32 as i32;
+
+#[link(name = "d3d11")]
+extern "system" {
+    pub(crate) fn D3D11CreateDevice(
+        adapter: *mut c_void,
+        driver_type: i32,
+        software: HMODULE,
+        flags: u32,
+        feature_levels: *const i32,
+        feature_level_count: u32,
+        sdk_version: u32,
+        device: *mut *mut c_void,
+        feature_level: *mut i32,
+        immediate_context: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+#[repr(C)]
+pub(crate) struct SampleDesc {
+    pub(crate) count: u32,
+    pub(crate) quality: u32,
+}
+
+#[repr(C)]
+pub(crate) struct SwapChainDesc1 {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: u32,
+    pub(crate) stereo: BOOL,
+    pub(crate) sample_desc: SampleDesc,
+    pub(crate) buffer_usage: u32,
+    pub(crate) buffer_count: u32,
+    pub(crate) scaling: u32,
+    pub(crate) swap_effect: u32,
+    pub(crate) alpha_mode: u32,
+    pub(crate) flags: u32,
+}
+
+/// `DXGI_OUTPUT_DESC1`.
+#[repr(C)]
+pub(crate) struct OutputDesc1 {
+    _device_name: [u16; 32],
+    _desktop_coordinates: RECT,
+    _attached_to_desktop: BOOL,
+    _rotation: u32,
+    _monitor: isize,
+    _bits_per_color: u32,
+    pub(crate) color_space: u32,
+    /// The red, green and blue primaries and the white point, as `[x, y]`.
+    _primaries: [[f32; 2]; 4],
+    _min_luminance: f32,
+    pub(crate) max_luminance: f32,
+    _max_full_frame_luminance: f32,
+}
+
+#[repr(C)]
+pub(crate) struct BufferDesc {
+    pub(crate) byte_width: u32,
+    pub(crate) usage: u32,
+    pub(crate) bind_flags: u32,
+    pub(crate) cpu_access_flags: u32,
+    pub(crate) misc_flags: u32,
+    pub(crate) structure_byte_stride: u32,
+}
+
+#[repr(C)]
+pub(crate) struct Texture2dDesc {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) mip_levels: u32,
+    pub(crate) array_size: u32,
+    pub(crate) format: u32,
+    pub(crate) sample_desc: SampleDesc,
+    pub(crate) usage: u32,
+    pub(crate) bind_flags: u32,
+    pub(crate) cpu_access_flags: u32,
+    pub(crate) misc_flags: u32,
+}
+
+/// `D3D11_BOX`.
+#[repr(C)]
+pub(crate) struct Region {
+    pub(crate) left: u32,
+    pub(crate) top: u32,
+    pub(crate) front: u32,
+    pub(crate) right: u32,
+    pub(crate) bottom: u32,
+    pub(crate) back: u32,
+}
+
+#[repr(C)]
+pub(crate) struct SubresourceData {
+    pub(crate) sys_mem: *const c_void,
+    pub(crate) sys_mem_pitch: u32,
+    pub(crate) sys_mem_slice_pitch: u32,
+}
+
+#[repr(C)]
+pub(crate) struct MappedSubresource {
+    pub(crate) data: *mut c_void,
+    pub(crate) row_pitch: u32,
+    pub(crate) depth_pitch: u32,
+}
+
+#[repr(C)]
+pub(crate) struct InputElementDesc {
+    pub(crate) semantic_name: *const u8,
+    pub(crate) semantic_index: u32,
+    pub(crate) format: u32,
+    pub(crate) input_slot: u32,
+    pub(crate) aligned_byte_offset: u32,
+    pub(crate) input_slot_class: u32,
+    pub(crate) instance_data_step_rate: u32,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct RenderTargetBlendDesc {
+    pub(crate) blend_enable: BOOL,
+    pub(crate) src_blend: u32,
+    pub(crate) dest_blend: u32,
+    pub(crate) blend_op: u32,
+    pub(crate) src_blend_alpha: u32,
+    pub(crate) dest_blend_alpha: u32,
+    pub(crate) blend_op_alpha: u32,
+    pub(crate) render_target_write_mask: u8,
+}
+
+#[repr(C)]
+pub(crate) struct BlendDesc {
+    pub(crate) alpha_to_coverage_enable: BOOL,
+    pub(crate) independent_blend_enable: BOOL,
+    pub(crate) render_target: [RenderTargetBlendDesc; 8],
+}
+
+#[repr(C)]
+pub(crate) struct RasterizerDesc {
+    pub(crate) fill_mode: u32,
+    pub(crate) cull_mode: u32,
+    pub(crate) front_counter_clockwise: BOOL,
+    pub(crate) depth_bias: i32,
+    pub(crate) depth_bias_clamp: f32,
+    pub(crate) slope_scaled_depth_bias: f32,
+    pub(crate) depth_clip_enable: BOOL,
+    pub(crate) scissor_enable: BOOL,
+    pub(crate) multisample_enable: BOOL,
+    pub(crate) antialiased_line_enable: BOOL,
+}
+
+#[repr(C)]
+pub(crate) struct SamplerDesc {
+    pub(crate) filter: u32,
+    pub(crate) address_u: u32,
+    pub(crate) address_v: u32,
+    pub(crate) address_w: u32,
+    pub(crate) mip_lod_bias: f32,
+    pub(crate) max_anisotropy: u32,
+    pub(crate) comparison_func: u32,
+    pub(crate) border_color: [f32; 4],
+    pub(crate) min_lod: f32,
+    pub(crate) max_lod: f32,
+}
+
+#[repr(C)]
+pub(crate) struct Viewport {
+    pub(crate) top_left_x: f32,
+    pub(crate) top_left_y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) min_depth: f32,
+    pub(crate) max_depth: f32,
+}
+
+#[repr(C)]
+pub(crate) struct Message {
+    pub(crate) category: i32,
+    pub(crate) severity: i32,
+    pub(crate) id: i32,
+    pub(crate) description: *const u8,
+    pub(crate) description_len: usize,
+}
+
+/// `IDXGIObject`, the base of the DXGI interfaces.
+#[repr(C)]
+pub(crate) struct IDXGIObjectVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _set_private_data: usize,
+    _set_private_data_interface: usize,
+    _get_private_data: usize,
+    pub(crate) get_parent: unsafe extern "system" fn(
+        this: *mut c_void,
+        iid: *const GUID,
+        parent: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIDeviceVtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    pub(crate) get_adapter:
+        unsafe extern "system" fn(this: *mut c_void, adapter: *mut *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIFactory2Vtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    _enum_adapters: usize,
+    pub(crate) make_window_association:
+        unsafe extern "system" fn(this: *mut c_void, hwnd: HWND, flags: u32) -> HRESULT,
+    _get_window_association: usize,
+    _create_swap_chain: usize,
+    _create_software_adapter: usize,
+    _enum_adapters1: usize,
+    _is_current: usize,
+    _is_windowed_stereo_enabled: usize,
+    pub(crate) create_swap_chain_for_hwnd: unsafe extern "system" fn(
+        this: *mut c_void,
+        device: *mut c_void,
+        hwnd: HWND,
+        desc: *const SwapChainDesc1,
+        fullscreen_desc: *const c_void,
+        restrict_to_output: *mut c_void,
+        swap_chain: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIFactory5Vtbl {
+    pub(crate) base: IDXGIFactory2Vtbl,
+    /// `CreateSwapChainForCoreWindow` to `EnumWarpAdapter`.
+    _factory_2_to_4: [usize; 12],
+    pub(crate) check_feature_support: unsafe extern "system" fn(
+        this: *mut c_void,
+        feature: i32,
+        support: *mut c_void,
+        size: u32,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIOutput6Vtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    /// `IDXGIOutput`'s methods to `IDXGIOutput5`'s.
+    _output_to_output_5: [usize; 20],
+    pub(crate) get_desc1:
+        unsafe extern "system" fn(this: *mut c_void, desc: *mut OutputDesc1) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGISwapChainVtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    _get_device: usize,
+    pub(crate) present:
+        unsafe extern "system" fn(this: *mut c_void, sync_interval: u32, flags: u32) -> HRESULT,
+    pub(crate) get_buffer: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer: u32,
+        iid: *const GUID,
+        surface: *mut *mut c_void,
+    ) -> HRESULT,
+    _set_fullscreen_state: usize,
+    _get_fullscreen_state: usize,
+    _get_desc: usize,
+    pub(crate) resize_buffers: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer_count: u32,
+        width: u32,
+        height: u32,
+        format: u32,
+        flags: u32,
+    ) -> HRESULT,
+    _resize_target: usize,
+    pub(crate) get_containing_output:
+        unsafe extern "system" fn(this: *mut c_void, output: *mut *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGISwapChain3Vtbl {
+    pub(crate) base: IDXGISwapChainVtbl,
+    /// `GetFrameStatistics` to `IDXGISwapChain1::GetRotation`.
+    _swap_chain_1: [usize; 13],
+    _set_source_size: usize,
+    _get_source_size: usize,
+    pub(crate) set_maximum_frame_latency:
+        unsafe extern "system" fn(this: *mut c_void, max_latency: u32) -> HRESULT,
+    _get_maximum_frame_latency: usize,
+    pub(crate) get_frame_latency_waitable_object:
+        unsafe extern "system" fn(this: *mut c_void) -> HANDLE,
+    _set_matrix_transform: usize,
+    _get_matrix_transform: usize,
+    _get_current_back_buffer_index: usize,
+    pub(crate) check_color_space_support: unsafe extern "system" fn(
+        this: *mut c_void,
+        color_space: u32,
+        support: *mut u32,
+    ) -> HRESULT,
+    pub(crate) set_color_space1:
+        unsafe extern "system" fn(this: *mut c_void, color_space: u32) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D11DeviceVtbl {
+    pub(crate) base: IUnknownVtbl,
+    pub(crate) create_buffer: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const BufferDesc,
+        initial_data: *const SubresourceData,
+        buffer: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_texture_1d: usize,
+    pub(crate) create_texture_2d: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const Texture2dDesc,
+        initial_data: *const SubresourceData,
+        texture: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_texture_3d: usize,
+    pub(crate) create_shader_resource_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        desc: *const c_void,
+        view: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_unordered_access_view: usize,
+    pub(crate) create_render_target_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        desc: *const c_void,
+        view: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_depth_stencil_view: usize,
+    pub(crate) create_input_layout: unsafe extern "system" fn(
+        this: *mut c_void,
+        descs: *const InputElementDesc,
+        count: u32,
+        bytecode: *const c_void,
+        bytecode_len: usize,
+        layout: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) create_vertex_shader: unsafe extern "system" fn(
+        this: *mut c_void,
+        bytecode: *const c_void,
+        bytecode_len: usize,
+        class_linkage: *mut c_void,
+        shader: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_geometry_shader: usize,
+    _create_geometry_shader_with_stream_output: usize,
+    pub(crate) create_pixel_shader: unsafe extern "system" fn(
+        this: *mut c_void,
+        bytecode: *const c_void,
+        bytecode_len: usize,
+        class_linkage: *mut c_void,
+        shader: *mut *mut c_void,
+    ) -> HRESULT,
+    /// `CreateHullShader` to `CreateClassLinkage`.
+    _create_shaders: [usize; 4],
+    pub(crate) create_blend_state: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const BlendDesc,
+        state: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_depth_stencil_state: usize,
+    pub(crate) create_rasterizer_state: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const RasterizerDesc,
+        state: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) create_sampler_state: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const SamplerDesc,
+        state: *mut *mut c_void,
+    ) -> HRESULT,
+    /// `CreateQuery` to `GetCreationFlags`.
+    _create_and_query: [usize; 15],
+    pub(crate) get_device_removed_reason: unsafe extern "system" fn(this: *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D11DeviceContextVtbl {
+    pub(crate) base: IUnknownVtbl,
+    /// `ID3D11DeviceChild`'s methods.
+    _device_child: [usize; 4],
+    pub(crate) vs_set_constant_buffers: unsafe extern "system" fn(
+        this: *mut c_void,
+        start_slot: u32,
+        count: u32,
+        buffers: *const *mut c_void,
+    ),
+    pub(crate) ps_set_shader_resources: unsafe extern "system" fn(
+        this: *mut c_void,
+        start_slot: u32,
+        count: u32,
+        views: *const *mut c_void,
+    ),
+    pub(crate) ps_set_shader: unsafe extern "system" fn(
+        this: *mut c_void,
+        shader: *mut c_void,
+        class_instances: *const *mut c_void,
+        class_instance_count: u32,
+    ),
+    pub(crate) ps_set_samplers: unsafe extern "system" fn(
+        this: *mut c_void,
+        start_slot: u32,
+        count: u32,
+        samplers: *const *mut c_void,
+    ),
+    pub(crate) vs_set_shader: unsafe extern "system" fn(
+        this: *mut c_void,
+        shader: *mut c_void,
+        class_instances: *const *mut c_void,
+        class_instance_count: u32,
+    ),
+    pub(crate) draw_indexed: unsafe extern "system" fn(
+        this: *mut c_void,
+        index_count: u32,
+        start_index: u32,
+        base_vertex: i32,
+    ),
+    _draw: usize,
+    pub(crate) map: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        subresource: u32,
+        map_type: u32,
+        flags: u32,
+        mapped: *mut MappedSubresource,
+    ) -> HRESULT,
+    pub(crate) unmap:
+        unsafe extern "system" fn(this: *mut c_void, resource: *mut c_void, subresource: u32),
+    _ps_set_constant_buffers: usize,
+    pub(crate) ia_set_input_layout:
+        unsafe extern "system" fn(this: *mut c_void, layout: *mut c_void),
+    pub(crate) ia_set_vertex_buffers: unsafe extern "system" fn(
+        this: *mut c_void,
+        start_slot: u32,
+        count: u32,
+        buffers: *const *mut c_void,
+        strides: *const u32,
+        offsets: *const u32,
+    ),
+    pub(crate) ia_set_index_buffer:
+        unsafe extern "system" fn(this: *mut c_void, buffer: *mut c_void, format: u32, offset: u32),
+    /// `DrawIndexedInstanced` to `GSSetShader`.
+    _instanced_and_geometry: [usize; 4],
+    pub(crate) ia_set_primitive_topology:
+        unsafe extern "system" fn(this: *mut c_void, topology: u32),
+    /// `VSSetShaderResources` to `GSSetSamplers`.
+    _resources_to_samplers: [usize; 8],
+    pub(crate) om_set_render_targets: unsafe extern "system" fn(
+        this: *mut c_void,
+        count: u32,
+        views: *const *mut c_void,
+        depth_stencil_view: *mut c_void,
+    ),
+    _om_set_render_targets_and_unordered_access_views: usize,
+    pub(crate) om_set_blend_state: unsafe extern "system" fn(
+        this: *mut c_void,
+        state: *mut c_void,
+        blend_factor: *const [f32; 4],
+        sample_mask: u32,
+    ),
+    /// `OMSetDepthStencilState` to `DispatchIndirect`.
+    _depth_stencil_to_dispatch: [usize; 7],
+    pub(crate) rs_set_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void),
+    pub(crate) rs_set_viewports:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, viewports: *const Viewport),
+    /// `RSSetScissorRects` to `CopyResource`.
+    _scissor_to_copy: [usize; 3],
+    pub(crate) update_subresource: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        subresource: u32,
+        region: *const Region,
+        data: *const c_void,
+        row_pitch: u32,
+        depth_pitch: u32,
+    ),
+    _copy_structure_count: usize,
+    pub(crate) clear_render_target_view:
+        unsafe extern "system" fn(this: *mut c_void, view: *mut c_void, color: *const [f32; 4]),
+    /// `ClearUnorderedAccessViewUint` to `CSGetConstantBuffers`.
+    _clear_to_getters: [usize; 59],
+    pub(crate) clear_state: unsafe extern "system" fn(this: *mut c_void),
+    pub(crate) flush: unsafe extern "system" fn(this: *mut c_void),
+}
+
+#[repr(C)]
+pub(crate) struct ID3D11InfoQueueVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _set_message_count_limit: usize,
+    pub(crate) clear_stored_messages: unsafe extern "system" fn(this: *mut c_void),
+    pub(crate) get_message: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: u64,
+        message: *mut Message,
+        len: *mut usize,
+    ) -> HRESULT,
+    _get_num_messages_allowed_by_storage_filter: usize,
+    _get_num_messages_denied_by_storage_filter: usize,
+    pub(crate) get_num_stored_messages: unsafe extern "system" fn(this: *mut c_void) -> u64,
+}
+
+// The sizes and offsets in the x64 SDK headers.
+#[cfg(target_pointer_width = "64")]
+const _: () = {
+    use std::mem::offset_of;
+
+    assert!(size_of::<SampleDesc>() == 8);
+    assert!(size_of::<SwapChainDesc1>() == 48);
+    assert!(size_of::<OutputDesc1>() == 152);
+    assert!(offset_of!(OutputDesc1, color_space) == 100);
+    assert!(offset_of!(OutputDesc1, max_luminance) == 140);
+    assert!(size_of::<BufferDesc>() == 24);
+    assert!(size_of::<Texture2dDesc>() == 44);
+    assert!(size_of::<Region>() == 24);
+    assert!(size_of::<SubresourceData>() == 16);
+    assert!(size_of::<MappedSubresource>() == 16);
+    assert!(size_of::<InputElementDesc>() == 32);
+    assert!(size_of::<RenderTargetBlendDesc>() == 32);
+    assert!(size_of::<BlendDesc>() == 264);
+    assert!(size_of::<RasterizerDesc>() == 40);
+    assert!(size_of::<SamplerDesc>() == 52);
+    assert!(size_of::<Viewport>() == 24);
+    assert!(size_of::<Message>() == 32);
+    assert!(offset_of!(Message, description) == 16);
+};
@@ -0,0 +1,422 @@
+//! A Direct3D 11 [`GraphicsDevice`] for any [`Surface`]: a device on the default adapter, a
+//! flip-model swap chain on the window, and frames recorded straight to the immediate context.
+//!
+//! ```no_run
+//! use common::{graphics::GraphicsDevice, surface::Surface};
+//! use renderer_d3d11::Renderer;
+//!
+//! fn run(window: &dyn Surface) -> Result<(), common::error::Error> {
+//!     let mut renderer = Renderer::builder()
+//!         .clear_color([0.1, 0.2, 0.3, 1.0])
+//!         .build(window)?;
+//!
+//!     // On each resize event:
+//!     let (width, height) = window.surface_size();
+//!     renderer.resize(width, height)?;
+//!
+//!     // Once a frame:
+//!     renderer.render()
+//! }
+//! ```
+//!
+//! Quads drawn with [`CommandList::draw_quads`] go through a small pipeline whose shaders are
+//! compiled when the device is created, with the `d3dcompiler_47.dll` that comes with Windows.
+//!
+//! If the GPU is lost, e.g. to a driver update or a TDR, the device, swap chain and textures are
+//! recreated on the next frame and the reason is logged. In debug builds the D3D11 debug layer is
+//! on, if it's installed, and its messages are logged each frame.
+
+#[allow(clippy::non_minimal_cfg)]
+#[cfg(all(not(target_os = "windows")))]
+compile_error!("only windows is supported");
+
+mod device;
+mod ffi;
+mod sprite;
+mod swap_chain;
+
+use std::marker::PhantomData;
+
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{
+        Backend, CommandList, GraphicsDevice, Image, PresentOptions, TextureFilter, TextureId,
+        Vertex,
+    },
+    surface::{RawWindowHandle, Surface},
+};
+use tracing::warn;
+use win32_base::error::Hresult;
+use windows_sys::Win32::Foundation::HWND;
+
+use crate::{
+    device::Device,
+    ffi::{Viewport, DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET},
+    sprite::{SpritePipeline, Texture, SHADER},
+    swap_chain::SwapChain,
+};
+
+pub struct RendererBuilder {
+    debug: bool,
+    present: PresentOptions,
+    clear_color: [f32; 4],
+}
+
+impl RendererBuilder {
+    /// Whether to enable the D3D11 debug layer. Defaults to on in debug builds. Ignored with a
+    /// warning if the layer isn't installed.
+    pub fn debug(self, debug: bool) -> Self {
+        Self { debug, ..self }
+    }
+
+    /// Whether presenting waits for the display's vertical blank. Defaults to on.
+    pub fn vsync(self, vsync: bool) -> Self {
+        Self {
+            present: PresentOptions {
+                vsync,
+                ..self.present
+            },
+            ..self
+        }
+    }
+
+    /// How the swap chain presents. See [`PresentOptions`] for the defaults.
+    pub fn present_options(self, present: PresentOptions) -> Self {
+        Self { present, ..self }
+    }
+
+    /// The color the back buffer is cleared to each frame, as linear RGBA. Defaults to black.
+    pub fn clear_color(self, clear_color: [f32; 4]) -> Self {
+        Self {
+            clear_color,
+            ..self
+        }
+    }
+
+    /// Creates the device and a swap chain the size of `surface`.
+    pub fn build(self, surface: &dyn Surface) -> Result<Renderer, Error> {
+        let hwnd = match surface.window_handle() {
+            RawWindowHandle::Win32 { hwnd, .. } => hwnd,
+            handle => {
+                return Err(
+                    Error::new(format!("can't create a D3D11 swap chain for {handle:?}"))
+                        .with_kind(ErrorKind::Unsupported),
+                )
+            }
+        };
+
+        let size = surface.surface_size();
+        Ok(Renderer {
+            gpu: Some(Gpu::new(hwnd, self.debug, size, self.present, &[], SHADER)?),
+            hwnd,
+            size,
+            debug: self.debug,
+            present: self.present,
+            clear_color: self.clear_color,
+            images: Vec::new(),
+            sprite_shader: None,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+/// Draws to a window with Direct3D 11. Must be used on the thread that runs the window's event
+/// loop, since DXGI sends the window messages when presenting.
+pub struct Renderer {
+    /// `None` after the device was lost, until it's recreated.
+    gpu: Option<Gpu>,
+    hwnd: HWND,
+    size: (u32, u32),
+    debug: bool,
+    /// The present options asked for, which are reapplied if the device is recreated.
+    present: PresentOptions,
+    clear_color: [f32; 4],
+    /// Each texture's image, indexed by its id, to upload again if the device is recreated.
+    images: Vec<Option<(Image, TextureFilter)>>,
+    /// The sprite shader's source if it was replaced, to compile again if the device is
+    /// recreated.
+    sprite_shader: Option<String>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Renderer {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder {
+            debug: cfg!(debug_assertions),
+            present: PresentOptions::default(),
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+
+    /// The D3D feature level of the device, as `(major, minor)`, e.g. `(11, 0)`. `None` while
+    /// the device is lost.
+    pub fn feature_level(&self) -> Option<(u32, u32)> {
+        self.gpu.as_ref().map(|gpu| gpu.device.feature_level())
+    }
+
+    pub fn clear_color(&self) -> [f32; 4] {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: [f32; 4]) {
+        self.clear_color = clear_color;
+    }
+
+    /// Clears the back buffer to the [clear color](Renderer::clear_color) and presents it.
+    pub fn render(&mut self) -> Result<(), Error> {
+        let clear_color = self.clear_color;
+        self.begin_frame()?.clear(clear_color);
+        self.end_frame()
+    }
+
+    fn recover(&mut self, err: Error) -> Result<(), Error> {
+        if let Some(gpu) = &self.gpu {
+            let reason = gpu.device.removed_reason();
+            warn!("{err}: the GPU device was lost ({reason}), recreating it");
+        }
+
+        self.recreate().map(|_| ())
+    }
+
+    fn recreate(&mut self) -> Result<&mut Gpu, Error> {
+        // note: a window can only have one flip-model swap chain, so the old one has to go first.
+        self.gpu = None;
+        let gpu = Gpu::new(
+            self.hwnd,
+            self.debug,
+            self.size,
+            self.present,
+            &self.images,
+            self.sprite_shader.as_deref().unwrap_or(SHADER),
+        )?;
+        Ok(self.gpu.insert(gpu))
+    }
+}
+
+impl GraphicsDevice for Renderer {
+    fn backend(&self) -> Backend {
+        Backend::D3D11
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 || (width, height) == self.size {
+            return Ok(());
+        }
+
+        self.size = (width, height);
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        match gpu.swap_chain.resize(&gpu.device, width, height) {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+
+    fn present_options(&self) -> PresentOptions {
+        self.gpu
+            .as_ref()
+            .map_or(self.present, |gpu| gpu.swap_chain.options())
+    }
+
+    fn set_present_options(&mut self, options: PresentOptions) -> Result<(), Error> {
+        self.present = options;
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        match gpu.swap_chain.configure(&gpu.device, options) {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+
+    fn create_texture(&mut self, image: Image, filter: TextureFilter) -> Result<TextureId, Error> {
+        let index = self
+            .images
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.images.len());
+        if let Some(gpu) = &mut self.gpu {
+            let texture = Texture::new(&gpu.device, &image, filter)?;
+            if index >= gpu.textures.len() {
+                gpu.textures.resize_with(index + 1, || None);
+            }
+            gpu.textures[index] = Some(texture);
+        }
+
+        if index == self.images.len() {
+            self.images.push(None);
+        }
+        self.images[index] = Some((image, filter));
+        Ok(TextureId::from_index(index))
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: TextureId,
+        origin: [u32; 2],
+        image: &Image,
+    ) -> Result<(), Error> {
+        let Some(Some((stored, _))) = self.images.get_mut(texture.index()) else {
+            return Err(
+                Error::new(format!("there's no texture {}", texture.index()))
+                    .with_kind(ErrorKind::NotFound),
+            );
+        };
+        stored.copy_from(origin, image)?;
+        if let Some(gpu) = &self.gpu {
+            if let Some(Some(texture)) = gpu.textures.get(texture.index()) {
+                texture.update(&gpu.device, origin, image);
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy_texture(&mut self, texture: TextureId) {
+        if let Some(image) = self.images.get_mut(texture.index()) {
+            *image = None;
+        }
+        if let Some(gpu) = &mut self.gpu {
+            if let Some(texture) = gpu.textures.get_mut(texture.index()) {
+                *texture = None;
+            }
+        }
+    }
+
+    fn set_sprite_shader(&mut self, source: &str) -> Result<(), Error> {
+        match &mut self.gpu {
+            Some(gpu) => gpu.sprites.set_shader(&gpu.device, source)?,
+            // note: checked now, so recreating the device doesn't fail on it later.
+            None => _ = sprite::compile(source)?,
+        }
+        self.sprite_shader = Some(source.to_string());
+        Ok(())
+    }
+
+    /// Waits for room in the present queue and binds the back buffer, recreating the device
+    /// first if it was lost.
+    fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error> {
+        let gpu = match self.gpu {
+            Some(ref mut gpu) => gpu,
+            None => self.recreate()?,
+        };
+        gpu.swap_chain.wait();
+        gpu.bind();
+        Ok(gpu)
+    }
+
+    fn end_frame(&mut self) -> Result<(), Error> {
+        let Some(gpu) = &self.gpu else {
+            return Ok(());
+        };
+
+        let result = gpu.swap_chain.present();
+        gpu.device.drain_messages();
+        match result {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+}
+
+/// Everything that's recreated when the device is lost. Commands go straight to the immediate
+/// context, so it's also the frame's command list.
+struct Gpu {
+    // note: declared first so they're released before the device.
+    swap_chain: SwapChain,
+    sprites: SpritePipeline,
+    /// Indexed by texture id.
+    textures: Vec<Option<Texture>>,
+    device: Device,
+}
+
+impl Gpu {
+    fn new(
+        hwnd: HWND,
+        debug: bool,
+        (width, height): (u32, u32),
+        present: PresentOptions,
+        images: &[Option<(Image, TextureFilter)>],
+        shader: &str,
+    ) -> Result<Self, Error> {
+        let device = Device::new(debug)?;
+        let swap_chain = SwapChain::new(&device, hwnd, width, height, present);
+        // note: log why the swap chain failed, if the debug layer knows.
+        device.drain_messages();
+        let swap_chain = swap_chain?;
+
+        let sprites = SpritePipeline::new(&device, shader)?;
+        let textures = images
+            .iter()
+            .map(|image| {
+                image
+                    .as_ref()
+                    .map(|(image, filter)| Texture::new(&device, image, *filter))
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            swap_chain,
+            sprites,
+            textures,
+            device,
+        })
+    }
+
+    fn bind(&self) {
+        // note: only missing if a resize failed, which was reported then.
+        let Some(view) = self.swap_chain.view() else {
+            return;
+        };
+
+        let context = self.device.context_vtbl();
+        let raw = self.device.context.as_raw();
+        let (width, height) = self.swap_chain.size();
+        let viewport = Viewport {
+            top_left_x: 0.0,
+            top_left_y: 0.0,
+            width: width as f32,
+            height: height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        };
+        // note: the flip model unbinds the back buffer on present, so it's bound every frame.
+        unsafe { (context.om_set_render_targets)(raw, 1, &view.as_raw(), std::ptr::null_mut()) };
+        unsafe { (context.rs_set_viewports)(raw, 1, &viewport) };
+    }
+}
+
+impl CommandList for Gpu {
+    fn clear(&mut self, color: [f32; 4]) {
+        if let Some(view) = self.swap_chain.view() {
+            let context = self.device.context_vtbl();
+            unsafe {
+                (context.clear_render_target_view)(
+                    self.device.context.as_raw(),
+                    view.as_raw(),
+                    &color,
+                )
+            };
+        }
+    }
+
+    fn draw_quads(&mut self, texture: TextureId, transform: &[[f32; 4]; 4], vertices: &[Vertex]) {
+        let Some(Some(texture)) = self.textures.get(texture.index()) else {
+            return;
+        };
+        if let Err(err) = self
+            .sprites
+            .draw(&self.device, texture, transform, vertices)
+        {
+            warn!("{}", err.full_message());
+        }
+    }
+}
+
+fn is_device_lost(err: &Error) -> bool {
+    matches!(
+        err.downcast_source(),
+        Some(Hresult(DXGI_ERROR_DEVICE_REMOVED | DXGI_ERROR_DEVICE_RESET))
+    )
+}
@@ -0,0 +1,32 @@
+// Textured, tinted quads for `CommandList::draw_quads`.
+
+cbuffer Camera : register(b0) {
+    float4x4 transform;
+};
+
+Texture2D sprite_texture : register(t0);
+SamplerState sprite_sampler : register(s0);
+
+struct VertexInput {
+    float2 position : POSITION;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+struct PixelInput {
+    float4 position : SV_Position;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+PixelInput vs_main(VertexInput input) {
+    PixelInput output;
+    output.position = mul(transform, float4(input.position, 0.0, 1.0));
+    output.uv = input.uv;
+    output.color = input.color;
+    return output;
+}
+
+float4 ps_main(PixelInput input) : SV_Target {
+    return sprite_texture.Sample(sprite_sampler, input.uv) * input.color;
+}
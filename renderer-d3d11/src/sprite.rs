@@ -0,0 +1,443 @@
+//! The pipeline [`CommandList::draw_quads`](common::graphics::CommandList::draw_quads) draws
+//! with, and the textures it samples.
+
+use std::ffi::c_void;
+
+use common::{
+    error::Error,
+    graphics::{Image, TextureFilter, Vertex},
+};
+use win32_base::{com::ComPtr, error::Hresult, fxc};
+use windows_sys::core::HRESULT;
+
+use crate::{
+    device::Device,
+    ffi::{
+        BlendDesc, BufferDesc, ID3D11DeviceVtbl, InputElementDesc, MappedSubresource,
+        RasterizerDesc, Region, RenderTargetBlendDesc, SampleDesc, SamplerDesc, SubresourceData,
+        Texture2dDesc, D3D11_BIND_CONSTANT_BUFFER, D3D11_BIND_INDEX_BUFFER,
+        D3D11_BIND_SHADER_RESOURCE, D3D11_BIND_VERTEX_BUFFER, D3D11_BLEND_INV_SRC_ALPHA,
+        D3D11_BLEND_ONE, D3D11_BLEND_OP_ADD, D3D11_BLEND_SRC_ALPHA, D3D11_COLOR_WRITE_ENABLE_ALL,
+        D3D11_COMPARISON_NEVER, D3D11_CPU_ACCESS_WRITE, D3D11_CULL_NONE, D3D11_FILL_SOLID,
+        D3D11_FILTER_MIN_MAG_MIP_LINEAR, D3D11_FILTER_MIN_MAG_MIP_POINT,
+        D3D11_INPUT_PER_VERTEX_DATA, D3D11_MAP_WRITE_DISCARD,
+        D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST, D3D11_TEXTURE_ADDRESS_CLAMP, D3D11_USAGE_DEFAULT,
+        D3D11_USAGE_DYNAMIC, D3D11_USAGE_IMMUTABLE, DXGI_FORMAT_R32G32B32A32_FLOAT,
+        DXGI_FORMAT_R32G32_FLOAT, DXGI_FORMAT_R32_UINT, DXGI_FORMAT_R8G8B8A8_UNORM,
+    },
+};
+
+pub(crate) const SHADER: &str = include_str!("sprite.hlsl");
+
+/// The fewest quads the buffers are made for, so small scenes don't regrow them.
+const MIN_QUADS: usize = 1024;
+
+/// A texture, and the shader resource view draws sample it through.
+pub(crate) struct Texture {
+    texture: ComPtr,
+    view: ComPtr,
+    filter: TextureFilter,
+}
+
+impl Texture {
+    pub(crate) fn new(
+        device: &Device,
+        image: &Image,
+        filter: TextureFilter,
+    ) -> Result<Self, Error> {
+        let vtbl = unsafe { device.device.vtbl::<ID3D11DeviceVtbl>() };
+        let raw = device.device.as_raw();
+        let desc = Texture2dDesc {
+            width: image.width(),
+            height: image.height(),
+            mip_levels: 1,
+            array_size: 1,
+            format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            sample_desc: SampleDesc {
+                count: 1,
+                quality: 0,
+            },
+            usage: D3D11_USAGE_DEFAULT,
+            bind_flags: D3D11_BIND_SHADER_RESOURCE,
+            cpu_access_flags: 0,
+            misc_flags: 0,
+        };
+        let data = SubresourceData {
+            sys_mem: image.pixels().as_ptr().cast(),
+            sys_mem_pitch: image.width() * 4,
+            sys_mem_slice_pitch: 0,
+        };
+        let context = format!(
+            "failed to create a {}x{} texture",
+            image.width(),
+            image.height()
+        );
+        let texture = create(&context, |texture| unsafe {
+            (vtbl.create_texture_2d)(raw, &desc, &data, texture)
+        })?;
+        let view = create("failed to create a texture view", |view| unsafe {
+            (vtbl.create_shader_resource_view)(raw, texture.as_raw(), std::ptr::null(), view)
+        })?;
+
+        Ok(Self {
+            texture,
+            view,
+            filter,
+        })
+    }
+
+    /// Replaces the texels at `origin` with `image`, which must fit.
+    pub(crate) fn update(&self, device: &Device, origin: [u32; 2], image: &Image) {
+        let [x, y] = origin;
+        let region = Region {
+            left: x,
+            top: y,
+            front: 0,
+            right: x + image.width(),
+            bottom: y + image.height(),
+            back: 1,
+        };
+        unsafe {
+            (device.context_vtbl().update_subresource)(
+                device.context.as_raw(),
+                self.texture.as_raw(),
+                0,
+                &region,
+                image.pixels().as_ptr().cast(),
+                image.width() * 4,
+                0,
+            )
+        };
+    }
+}
+
+pub(crate) struct SpritePipeline {
+    vertex_shader: ComPtr,
+    pixel_shader: ComPtr,
+    input_layout: ComPtr,
+    blend_state: ComPtr,
+    rasterizer_state: ComPtr,
+    /// Linear, then nearest.
+    samplers: [ComPtr; 2],
+    constants: ComPtr,
+    vertices: ComPtr,
+    indices: ComPtr,
+    /// The number of quads the vertex and index buffers hold.
+    capacity: usize,
+}
+
+impl SpritePipeline {
+    /// Compiles `shader`, the source of a sprite shader like [`SHADER`].
+    pub(crate) fn new(device: &Device, shader: &str) -> Result<Self, Error> {
+        let (vertex_shader, pixel_shader, input_layout) = create_shaders(device, shader)?;
+
+        let vtbl = unsafe { device.device.vtbl::<ID3D11DeviceVtbl>() };
+        let raw = device.device.as_raw();
+
+        let mut blend = BlendDesc {
+            alpha_to_coverage_enable: 0,
+            independent_blend_enable: 0,
+            render_target: [RenderTargetBlendDesc::default(); 8],
+        };
+        blend.render_target[0] = RenderTargetBlendDesc {
+            blend_enable: 1,
+            src_blend: D3D11_BLEND_SRC_ALPHA,
+            dest_blend: D3D11_BLEND_INV_SRC_ALPHA,
+            blend_op: D3D11_BLEND_OP_ADD,
+            src_blend_alpha: D3D11_BLEND_ONE,
+            dest_blend_alpha: D3D11_BLEND_INV_SRC_ALPHA,
+            blend_op_alpha: D3D11_BLEND_OP_ADD,
+            render_target_write_mask: D3D11_COLOR_WRITE_ENABLE_ALL,
+        };
+        let blend_state = create("failed to create the sprite blend state", |state| unsafe {
+            (vtbl.create_blend_state)(raw, &blend, state)
+        })?;
+
+        let rasterizer = RasterizerDesc {
+            fill_mode: D3D11_FILL_SOLID,
+            // note: flipped sprites face away.
+            cull_mode: D3D11_CULL_NONE,
+            front_counter_clockwise: 0,
+            depth_bias: 0,
+            depth_bias_clamp: 0.0,
+            slope_scaled_depth_bias: 0.0,
+            depth_clip_enable: 1,
+            scissor_enable: 0,
+            multisample_enable: 0,
+            antialiased_line_enable: 0,
+        };
+        let rasterizer_state = create(
+            "failed to create the sprite rasterizer state",
+            |state| unsafe { (vtbl.create_rasterizer_state)(raw, &rasterizer, state) },
+        )?;
+
+        let samplers = [
+            D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            D3D11_FILTER_MIN_MAG_MIP_POINT,
+        ]
+        .map(|filter| {
+            let desc = SamplerDesc {
+                filter,
+                address_u: D3D11_TEXTURE_ADDRESS_CLAMP,
+                address_v: D3D11_TEXTURE_ADDRESS_CLAMP,
+                address_w: D3D11_TEXTURE_ADDRESS_CLAMP,
+                mip_lod_bias: 0.0,
+                max_anisotropy: 1,
+                comparison_func: D3D11_COMPARISON_NEVER,
+                border_color: [0.0; 4],
+                min_lod: 0.0,
+                max_lod: f32::MAX,
+            };
+            create("failed to create a sprite sampler", |sampler| unsafe {
+                (vtbl.create_sampler_state)(raw, &desc, sampler)
+            })
+        });
+        let [linear, nearest] = samplers;
+
+        let constants = create_buffer(
+            device,
+            size_of::<[[f32; 4]; 4]>(),
+            D3D11_BIND_CONSTANT_BUFFER,
+            None,
+        )?;
+        let (vertices, indices) = create_quad_buffers(device, MIN_QUADS)?;
+
+        Ok(Self {
+            vertex_shader,
+            pixel_shader,
+            input_layout,
+            blend_state,
+            rasterizer_state,
+            samplers: [linear?, nearest?],
+            constants,
+            vertices,
+            indices,
+            capacity: MIN_QUADS,
+        })
+    }
+
+    /// Compiles `shader` and draws with it from then on, keeping the current shaders if it fails.
+    pub(crate) fn set_shader(&mut self, device: &Device, shader: &str) -> Result<(), Error> {
+        (self.vertex_shader, self.pixel_shader, self.input_layout) =
+            create_shaders(device, shader)?;
+        Ok(())
+    }
+
+    /// Draws the whole quads in `vertices` to the bound render target.
+    pub(crate) fn draw(
+        &mut self,
+        device: &Device,
+        texture: &Texture,
+        transform: &[[f32; 4]; 4],
+        vertices: &[Vertex],
+    ) -> Result<(), Error> {
+        let quads = vertices.len() / 4;
+        if quads == 0 {
+            return Ok(());
+        }
+        if quads > self.capacity {
+            let capacity = quads.next_power_of_two();
+            (self.vertices, self.indices) = create_quad_buffers(device, capacity)?;
+            self.capacity = capacity;
+        }
+
+        upload(device, &self.constants, std::slice::from_ref(transform))?;
+        upload(device, &self.vertices, &vertices[..quads * 4])?;
+
+        let context = device.context_vtbl();
+        let raw = device.context.as_raw();
+        let sampler = match texture.filter {
+            TextureFilter::Linear => &self.samplers[0],
+            TextureFilter::Nearest => &self.samplers[1],
+        };
+        let stride = size_of::<Vertex>() as u32;
+        unsafe {
+            (context.ia_set_input_layout)(raw, self.input_layout.as_raw());
+            (context.ia_set_vertex_buffers)(raw, 0, 1, &self.vertices.as_raw(), &stride, &0);
+            (context.ia_set_index_buffer)(raw, self.indices.as_raw(), DXGI_FORMAT_R32_UINT, 0);
+            (context.ia_set_primitive_topology)(raw, D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            (context.vs_set_shader)(raw, self.vertex_shader.as_raw(), std::ptr::null(), 0);
+            (context.vs_set_constant_buffers)(raw, 0, 1, &self.constants.as_raw());
+            (context.rs_set_state)(raw, self.rasterizer_state.as_raw());
+            (context.ps_set_shader)(raw, self.pixel_shader.as_raw(), std::ptr::null(), 0);
+            (context.ps_set_shader_resources)(raw, 0, 1, &texture.view.as_raw());
+            (context.ps_set_samplers)(raw, 0, 1, &sampler.as_raw());
+            (context.om_set_blend_state)(raw, self.blend_state.as_raw(), &[0.0; 4], u32::MAX);
+            (context.draw_indexed)(raw, quads as u32 * 6, 0, 0);
+        }
+
+        Ok(())
+    }
+}
+
+/// The vertex shader, pixel shader and input layout of the sprite shader `source`, which has
+/// `vs_main` and `ps_main` entry points.
+fn create_shaders(device: &Device, source: &str) -> Result<(ComPtr, ComPtr, ComPtr), Error> {
+    let (vertex_code, pixel_code) = compile(source)?;
+
+    let vtbl = unsafe { device.device.vtbl::<ID3D11DeviceVtbl>() };
+    let raw = device.device.as_raw();
+    let vertex_shader = create(
+        "failed to create the sprite vertex shader",
+        |shader| unsafe {
+            (vtbl.create_vertex_shader)(
+                raw,
+                vertex_code.as_ptr().cast(),
+                vertex_code.len(),
+                std::ptr::null_mut(),
+                shader,
+            )
+        },
+    )?;
+    let pixel_shader = create(
+        "failed to create the sprite pixel shader",
+        |shader| unsafe {
+            (vtbl.create_pixel_shader)(
+                raw,
+                pixel_code.as_ptr().cast(),
+                pixel_code.len(),
+                std::ptr::null_mut(),
+                shader,
+            )
+        },
+    )?;
+
+    let elements = [
+        (c"POSITION", DXGI_FORMAT_R32G32_FLOAT, 0),
+        (c"TEXCOORD", DXGI_FORMAT_R32G32_FLOAT, 8),
+        (c"COLOR", DXGI_FORMAT_R32G32B32A32_FLOAT, 16),
+    ]
+    .map(|(name, format, offset)| InputElementDesc {
+        semantic_name: name.as_ptr().cast(),
+        semantic_index: 0,
+        format,
+        input_slot: 0,
+        aligned_byte_offset: offset,
+        input_slot_class: D3D11_INPUT_PER_VERTEX_DATA,
+        instance_data_step_rate: 0,
+    });
+    let input_layout = create(
+        "failed to create the sprite input layout",
+        |layout| unsafe {
+            (vtbl.create_input_layout)(
+                raw,
+                elements.as_ptr(),
+                elements.len() as u32,
+                vertex_code.as_ptr().cast(),
+                vertex_code.len(),
+                layout,
+            )
+        },
+    )?;
+
+    Ok((vertex_shader, pixel_shader, input_layout))
+}
+
+/// The vertex and pixel shader code of the sprite shader `source`.
+pub(crate) fn compile(source: &str) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    Ok((
+        fxc::compile(source, c"sprite.hlsl", c"vs_main", c"vs_4_0")?,
+        fxc::compile(source, c"sprite.hlsl", c"ps_main", c"ps_4_0")?,
+    ))
+}
+
+/// A dynamic vertex buffer for `quads` quads, and the indices that split each into two
+/// triangles.
+fn create_quad_buffers(device: &Device, quads: usize) -> Result<(ComPtr, ComPtr), Error> {
+    let vertices = create_buffer(
+        device,
+        quads * 4 * size_of::<Vertex>(),
+        D3D11_BIND_VERTEX_BUFFER,
+        None,
+    )?;
+    let indices: Vec<u32> = (0..quads as u32 * 4)
+        .step_by(4)
+        .flat_map(|first| [first, first + 1, first + 2, first, first + 2, first + 3])
+        .collect();
+    let indices = create_buffer(
+        device,
+        size_of_val(indices.as_slice()),
+        D3D11_BIND_INDEX_BUFFER,
+        Some(indices.as_ptr().cast()),
+    )?;
+
+    Ok((vertices, indices))
+}
+
+/// A buffer that's immutable if it has `data`, and otherwise written by the CPU each use.
+fn create_buffer(
+    device: &Device,
+    size: usize,
+    bind_flags: u32,
+    data: Option<*const c_void>,
+) -> Result<ComPtr, Error> {
+    let (usage, cpu_access_flags) = match data {
+        Some(_) => (D3D11_USAGE_IMMUTABLE, 0),
+        None => (D3D11_USAGE_DYNAMIC, D3D11_CPU_ACCESS_WRITE),
+    };
+    let desc = BufferDesc {
+        byte_width: size as u32,
+        usage,
+        bind_flags,
+        cpu_access_flags,
+        misc_flags: 0,
+        structure_byte_stride: 0,
+    };
+    let initial_data = data.map(|sys_mem| SubresourceData {
+        sys_mem,
+        sys_mem_pitch: 0,
+        sys_mem_slice_pitch: 0,
+    });
+    let vtbl = unsafe { device.device.vtbl::<ID3D11DeviceVtbl>() };
+    create(
+        &format!("failed to create a {size} byte buffer"),
+        |buffer| unsafe {
+            (vtbl.create_buffer)(
+                device.device.as_raw(),
+                &desc,
+                initial_data
+                    .as_ref()
+                    .map_or(std::ptr::null(), |data| data as *const SubresourceData),
+                buffer,
+            )
+        },
+    )
+}
+
+/// Replaces the contents of a dynamic buffer with `data`.
+fn upload<T: Copy>(device: &Device, buffer: &ComPtr, data: &[T]) -> Result<(), Error> {
+    let context = device.context_vtbl();
+    let raw = device.context.as_raw();
+    let mut mapped = MappedSubresource {
+        data: std::ptr::null_mut(),
+        row_pitch: 0,
+        depth_pitch: 0,
+    };
+    let hr = unsafe {
+        (context.map)(
+            raw,
+            buffer.as_raw(),
+            0,
+            D3D11_MAP_WRITE_DISCARD,
+            0,
+            &mut mapped,
+        )
+    };
+    Hresult(hr).check("failed to map a buffer")?;
+    unsafe {
+        std::ptr::copy_nonoverlapping(
+            data.as_ptr().cast::<u8>(),
+            mapped.data.cast::<u8>(),
+            size_of_val(data),
+        );
+        (context.unmap)(raw, buffer.as_raw(), 0);
+    }
+
+    Ok(())
+}
+
+fn create(context: &str, call: impl FnOnce(*mut *mut c_void) -> HRESULT) -> Result<ComPtr, Error> {
+    let mut object = std::ptr::null_mut();
+    let hr = call(&mut object);
+    unsafe { ComPtr::from_call(hr, object, context) }
+}
@@ -0,0 +1,410 @@
+//! A flip-model swap chain on a window, the render target view of its back buffer, and how it
+//! presents.
+
+use common::{
+    error::Error,
+    graphics::{ColorSpace, PresentOptions},
+};
+use tracing::{debug, info, warn};
+use win32_base::{com::ComPtr, error::Hresult};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, BOOL, HANDLE, HWND},
+    System::Threading::WaitForSingleObject,
+};
+
+use crate::{
+    device::Device,
+    ffi::{
+        ID3D11DeviceVtbl, IDXGIFactory2Vtbl, IDXGIFactory5Vtbl, IDXGIOutput6Vtbl,
+        IDXGISwapChain3Vtbl, IDXGISwapChainVtbl, OutputDesc1, SampleDesc, SwapChainDesc1,
+        DXGI_ALPHA_MODE_IGNORE, DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+        DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020, DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+        DXGI_FEATURE_PRESENT_ALLOW_TEARING, DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_UNKNOWN,
+        DXGI_MWA_NO_ALT_ENTER, DXGI_PRESENT_ALLOW_TEARING, DXGI_SCALING_STRETCH,
+        DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT, DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING,
+        DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT, DXGI_SWAP_EFFECT_FLIP_DISCARD,
+        DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL, DXGI_USAGE_RENDER_TARGET_OUTPUT, IID_ID3D11_TEXTURE_2D,
+        IID_IDXGI_FACTORY_5, IID_IDXGI_OUTPUT_6, IID_IDXGI_SWAP_CHAIN_3,
+    },
+};
+
+const BUFFER_COUNT: u32 = 2;
+
+/// The frame latency DXGI uses when it can't be changed.
+const DEFAULT_FRAME_LATENCY: u32 = 3;
+
+pub(crate) struct SwapChain {
+    // note: declared first so the view of the back buffer is released before the swap chain.
+    view: Option<ComPtr>,
+    swap_chain: ComPtr,
+    /// `None` before Windows 10, where the frame latency and color space can't be changed.
+    swap_chain3: Option<ComPtr>,
+    /// Signaled when the present queue has room for another frame. `0` without `swap_chain3`.
+    waitable: HANDLE,
+    /// The flags it was created with, which resizes have to keep.
+    flags: u32,
+    /// The options in effect.
+    options: PresentOptions,
+    width: u32,
+    height: u32,
+}
+
+impl SwapChain {
+    pub(crate) fn new(
+        device: &Device,
+        hwnd: HWND,
+        width: u32,
+        height: u32,
+        options: PresentOptions,
+    ) -> Result<Self, Error> {
+        // note: a zero size would make DXGI use the window's, which is also zero when minimized.
+        let (width, height) = (width.max(1), height.max(1));
+        let factory = unsafe { device.factory.vtbl::<IDXGIFactory2Vtbl>() };
+        let create = |swap_effect, flags| {
+            let desc = SwapChainDesc1 {
+                width,
+                height,
+                format: DXGI_FORMAT_B8G8R8A8_UNORM,
+                stereo: 0,
+                sample_desc: SampleDesc {
+                    count: 1,
+                    quality: 0,
+                },
+                buffer_usage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+                buffer_count: BUFFER_COUNT,
+                scaling: DXGI_SCALING_STRETCH,
+                swap_effect,
+                alpha_mode: DXGI_ALPHA_MODE_IGNORE,
+                flags,
+            };
+            let mut swap_chain = std::ptr::null_mut();
+            let hr = unsafe {
+                (factory.create_swap_chain_for_hwnd)(
+                    device.factory.as_raw(),
+                    device.device.as_raw(),
+                    hwnd,
+                    &desc,
+                    std::ptr::null(),
+                    std::ptr::null_mut(),
+                    &mut swap_chain,
+                )
+            };
+            unsafe { ComPtr::from_call(hr, swap_chain, "failed to create swap chain") }
+                .map(|swap_chain| (swap_chain, flags))
+        };
+
+        // Flip discard, the waitable object and tearing need Windows 10. The flags can't be
+        // changed later, so they're always set when supported.
+        let mut flags = DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT;
+        if supports_tearing(device) {
+            flags |= DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING;
+        }
+        let (swap_chain, flags) = create(DXGI_SWAP_EFFECT_FLIP_DISCARD, flags).or_else(|err| {
+            debug!("{err}, retrying with DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL");
+            create(DXGI_SWAP_EFFECT_FLIP_SEQUENTIAL, 0)
+        })?;
+        let swap_chain3 = match flags {
+            0 => None,
+            _ => Some(swap_chain.query(&IID_IDXGI_SWAP_CHAIN_3, "failed to get IDXGISwapChain3")?),
+        };
+        let waitable = match &swap_chain3 {
+            Some(swap_chain3) => unsafe {
+                (swap_chain3
+                    .vtbl::<IDXGISwapChain3Vtbl>()
+                    .get_frame_latency_waitable_object)(swap_chain3.as_raw())
+            },
+            None => 0,
+        };
+
+        // Alt+Enter is left to the game, which knows its display settings.
+        let hr = unsafe {
+            (factory.make_window_association)(device.factory.as_raw(), hwnd, DXGI_MWA_NO_ALT_ENTER)
+        };
+        Hresult(hr).check("failed to set the swap chain's window association")?;
+
+        let mut swap_chain = Self {
+            view: None,
+            swap_chain,
+            swap_chain3,
+            waitable,
+            flags,
+            options: PresentOptions::default(),
+            width,
+            height,
+        };
+        swap_chain.create_view(device)?;
+        swap_chain.configure(device, options)?;
+
+        Ok(swap_chain)
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub(crate) fn view(&self) -> Option<&ComPtr> {
+        self.view.as_ref()
+    }
+
+    pub(crate) fn options(&self) -> PresentOptions {
+        self.options
+    }
+
+    /// Applies `options`, falling back from the ones that aren't supported.
+    pub(crate) fn configure(
+        &mut self,
+        device: &Device,
+        options: PresentOptions,
+    ) -> Result<(), Error> {
+        let mut options = PresentOptions {
+            max_frame_latency: options.max_frame_latency.clamp(1, 16),
+            ..options
+        };
+
+        if options.allow_tearing && self.flags & DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING == 0 {
+            if !options.vsync {
+                warn!("the display doesn't support tearing, presenting without it");
+            }
+            options.allow_tearing = false;
+        }
+
+        match &self.swap_chain3 {
+            Some(swap_chain3) => {
+                let hr = unsafe {
+                    (swap_chain3
+                        .vtbl::<IDXGISwapChain3Vtbl>()
+                        .set_maximum_frame_latency)(
+                        swap_chain3.as_raw(), options.max_frame_latency
+                    )
+                };
+                Hresult(hr).check("failed to set the maximum frame latency")?;
+            }
+            None => options.max_frame_latency = DEFAULT_FRAME_LATENCY,
+        }
+
+        if !self.supports(options.color_space) {
+            warn!(
+                "the display doesn't support {:?}, falling back to sRGB",
+                options.color_space
+            );
+            options.color_space = ColorSpace::Srgb;
+        }
+        // note: SDR and HDR back buffers have different formats.
+        if options.color_space != self.options.color_space {
+            self.release_view(device);
+            let hr = unsafe {
+                (self.vtbl().resize_buffers)(
+                    self.swap_chain.as_raw(),
+                    0,
+                    self.width,
+                    self.height,
+                    dxgi_format(options.color_space),
+                    self.flags,
+                )
+            };
+            Hresult(hr).check("failed to change the swap chain's format")?;
+            self.create_view(device)?;
+        }
+        if let Some(swap_chain3) = &self.swap_chain3 {
+            let hr = unsafe {
+                (swap_chain3.vtbl::<IDXGISwapChain3Vtbl>().set_color_space1)(
+                    swap_chain3.as_raw(),
+                    dxgi_color_space(options.color_space),
+                )
+            };
+            Hresult(hr).check("failed to set the swap chain's color space")?;
+        }
+
+        if options != self.options {
+            info!(?options, "configured the swap chain");
+        }
+        self.options = options;
+        Ok(())
+    }
+
+    pub(crate) fn resize(&mut self, device: &Device, width: u32, height: u32) -> Result<(), Error> {
+        self.release_view(device);
+        let hr = unsafe {
+            (self.vtbl().resize_buffers)(
+                self.swap_chain.as_raw(),
+                0,
+                width,
+                height,
+                DXGI_FORMAT_UNKNOWN,
+                self.flags,
+            )
+        };
+        Hresult(hr).check(&format!("failed to resize swap chain to {width}x{height}"))?;
+
+        self.width = width;
+        self.height = height;
+        self.create_view(device)
+    }
+
+    /// Waits until the present queue has room for another frame, so it's drawn with the latest
+    /// input rather than queued behind the others.
+    pub(crate) fn wait(&self) {
+        if self.waitable != 0 {
+            // note: times out rather than hang if presents stop, e.g. while the device is lost.
+            unsafe { WaitForSingleObject(self.waitable, 1000) };
+        }
+    }
+
+    pub(crate) fn present(&self) -> Result<(), Error> {
+        let (sync_interval, flags) = match self.options {
+            PresentOptions { vsync: true, .. } => (1, 0),
+            PresentOptions {
+                allow_tearing: true,
+                ..
+            } => (0, DXGI_PRESENT_ALLOW_TEARING),
+            _ => (0, 0),
+        };
+        let hr = unsafe { (self.vtbl().present)(self.swap_chain.as_raw(), sync_interval, flags) };
+        Hresult(hr).check("failed to present")
+    }
+
+    /// Whether the swap chain and the display it's mostly on can show `color_space`. HDR ones
+    /// need HDR turned on in the display settings.
+    fn supports(&self, color_space: ColorSpace) -> bool {
+        let Some(swap_chain3) = &self.swap_chain3 else {
+            return color_space == ColorSpace::Srgb;
+        };
+
+        let mut support = 0;
+        let hr = unsafe {
+            (swap_chain3
+                .vtbl::<IDXGISwapChain3Vtbl>()
+                .check_color_space_support)(
+                swap_chain3.as_raw(),
+                dxgi_color_space(color_space),
+                &mut support,
+            )
+        };
+        if hr < 0 || support & DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT == 0 {
+            return false;
+        }
+        if color_space == ColorSpace::Srgb {
+            return true;
+        }
+
+        match self.output_desc() {
+            Ok(desc) if desc.color_space == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020 => {
+                debug!("the display is in HDR, up to {} nits", desc.max_luminance);
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                debug!("{}", err.full_message());
+                false
+            }
+        }
+    }
+
+    fn output_desc(&self) -> Result<OutputDesc1, Error> {
+        let mut output = std::ptr::null_mut();
+        let hr =
+            unsafe { (self.vtbl().get_containing_output)(self.swap_chain.as_raw(), &mut output) };
+        let output = unsafe { ComPtr::from_call(hr, output, "failed to get the display") }?;
+        let output = output.query(&IID_IDXGI_OUTPUT_6, "failed to get IDXGIOutput6")?;
+
+        let mut desc = unsafe { std::mem::zeroed::<OutputDesc1>() };
+        let hr =
+            unsafe { (output.vtbl::<IDXGIOutput6Vtbl>().get_desc1)(output.as_raw(), &mut desc) };
+        Hresult(hr).check("failed to describe the display")?;
+        Ok(desc)
+    }
+
+    /// Releases every reference to the back buffers, including the context's binding, so they
+    /// can be resized.
+    fn release_view(&mut self, device: &Device) {
+        self.view = None;
+        let context = device.context_vtbl();
+        unsafe { (context.clear_state)(device.context.as_raw()) };
+        unsafe { (context.flush)(device.context.as_raw()) };
+    }
+
+    fn create_view(&mut self, device: &Device) -> Result<(), Error> {
+        let mut buffer = std::ptr::null_mut();
+        let hr = unsafe {
+            (self.vtbl().get_buffer)(
+                self.swap_chain.as_raw(),
+                0,
+                &IID_ID3D11_TEXTURE_2D,
+                &mut buffer,
+            )
+        };
+        let buffer = unsafe { ComPtr::from_call(hr, buffer, "failed to get the back buffer") }?;
+
+        let mut view = std::ptr::null_mut();
+        let hr = unsafe {
+            (device
+                .device
+                .vtbl::<ID3D11DeviceVtbl>()
+                .create_render_target_view)(
+                device.device.as_raw(),
+                buffer.as_raw(),
+                std::ptr::null(),
+                &mut view,
+            )
+        };
+        self.view = Some(unsafe {
+            ComPtr::from_call(
+                hr,
+                view,
+                "failed to create the back buffer's render target view",
+            )
+        }?);
+
+        Ok(())
+    }
+
+    fn vtbl(&self) -> &IDXGISwapChainVtbl {
+        unsafe { self.swap_chain.vtbl() }
+    }
+}
+
+impl Drop for SwapChain {
+    fn drop(&mut self) {
+        if self.waitable != 0 {
+            unsafe { CloseHandle(self.waitable) };
+        }
+    }
+}
+
+/// Whether the driver and display can show frames mid-refresh, for variable refresh rates.
+fn supports_tearing(device: &Device) -> bool {
+    let Ok(factory) = device
+        .factory
+        .query(&IID_IDXGI_FACTORY_5, "failed to get IDXGIFactory5")
+    else {
+        return false;
+    };
+
+    let mut supported: BOOL = 0;
+    let hr = unsafe {
+        (factory.vtbl::<IDXGIFactory5Vtbl>().check_feature_support)(
+            factory.as_raw(),
+            DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+            (&mut supported as *mut BOOL).cast(),
+            std::mem::size_of::<BOOL>() as u32,
+        )
+    };
+    hr >= 0 && supported != 0
+}
+
+fn dxgi_format(color_space: ColorSpace) -> u32 {
+    match color_space {
+        ColorSpace::Srgb => DXGI_FORMAT_B8G8R8A8_UNORM,
+        ColorSpace::Hdr10 => DXGI_FORMAT_R10G10B10A2_UNORM,
+        ColorSpace::ScRgb => DXGI_FORMAT_R16G16B16A16_FLOAT,
+    }
+}
+
+fn dxgi_color_space(color_space: ColorSpace) -> u32 {
+    match color_space {
+        ColorSpace::Srgb => DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+        ColorSpace::Hdr10 => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+        ColorSpace::ScRgb => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+    }
+}
@@ -0,0 +1,223 @@
+//! The D3D12 device, its direct queue and the fence that tracks what the queue has finished.
+
+use std::ffi::c_void;
+
+use common::error::{Error, ErrorKind};
+use tracing::{debug, error, info, warn};
+use win32_base::{com::ComPtr, error::Hresult};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    System::Threading::{CreateEventW, WaitForSingleObject, INFINITE},
+};
+
+use crate::ffi::{
+    CommandQueueDesc, CreateDXGIFactory2, D3D12CreateDevice, D3D12GetDebugInterface,
+    ID3D12CommandQueueVtbl, ID3D12DebugVtbl, ID3D12DeviceVtbl, ID3D12FenceVtbl,
+    ID3D12InfoQueueVtbl, Message, D3D12_COMMAND_LIST_TYPE_DIRECT,
+    D3D12_MESSAGE_SEVERITY_CORRUPTION, D3D12_MESSAGE_SEVERITY_ERROR, D3D12_MESSAGE_SEVERITY_INFO,
+    D3D12_MESSAGE_SEVERITY_WARNING, D3D_FEATURE_LEVEL_11_0, DXGI_CREATE_FACTORY_DEBUG,
+    IID_ID3D12_COMMAND_QUEUE, IID_ID3D12_DEBUG, IID_ID3D12_DEVICE, IID_ID3D12_FENCE,
+    IID_ID3D12_INFO_QUEUE, IID_IDXGI_FACTORY_2,
+};
+
+pub(crate) struct Device {
+    pub(crate) device: ComPtr,
+    pub(crate) queue: ComPtr,
+    pub(crate) factory: ComPtr,
+    /// The debug layer's messages, if it's on.
+    info_queue: Option<ComPtr>,
+    fence: ComPtr,
+    fence_event: HANDLE,
+    /// The value the queue signals the fence with next.
+    next_fence_value: u64,
+}
+
+impl Device {
+    /// Creates a device on the default adapter. With `debug`, the debug layer is enabled if it's
+    /// installed.
+    pub(crate) fn new(debug: bool) -> Result<Self, Error> {
+        let debug = debug && enable_debug_layer();
+        let mut factory = std::ptr::null_mut();
+        let flags = if debug { DXGI_CREATE_FACTORY_DEBUG } else { 0 };
+        let hr = unsafe { CreateDXGIFactory2(flags, &IID_IDXGI_FACTORY_2, &mut factory) };
+        let factory = unsafe { ComPtr::from_call(hr, factory, "failed to create DXGI factory") }?;
+
+        let mut device = std::ptr::null_mut();
+        let hr = unsafe {
+            D3D12CreateDevice(
+                std::ptr::null_mut(),
+                D3D_FEATURE_LEVEL_11_0,
+                &IID_ID3D12_DEVICE,
+                &mut device,
+            )
+        };
+        let device = unsafe { ComPtr::from_call(hr, device, "failed to create D3D12 device") }?;
+        let vtbl = unsafe { device.vtbl::<ID3D12DeviceVtbl>() };
+
+        let desc = CommandQueueDesc {
+            kind: D3D12_COMMAND_LIST_TYPE_DIRECT,
+            priority: 0,
+            flags: 0,
+            node_mask: 0,
+        };
+        let mut queue = std::ptr::null_mut();
+        let hr = unsafe {
+            (vtbl.create_command_queue)(
+                device.as_raw(),
+                &desc,
+                &IID_ID3D12_COMMAND_QUEUE,
+                &mut queue,
+            )
+        };
+        let queue = unsafe { ComPtr::from_call(hr, queue, "failed to create command queue") }?;
+
+        let mut fence = std::ptr::null_mut();
+        let hr =
+            unsafe { (vtbl.create_fence)(device.as_raw(), 0, 0, &IID_ID3D12_FENCE, &mut fence) };
+        let fence = unsafe { ComPtr::from_call(hr, fence, "failed to create fence") }?;
+        let fence_event = unsafe { CreateEventW(std::ptr::null(), 0, 0, std::ptr::null()) };
+        if fence_event == 0 {
+            return Err(Error::new("failed to create fence event")
+                .with_kind(ErrorKind::Graphics)
+                .with_source(std::io::Error::last_os_error()));
+        }
+
+        // note: only there when the debug layer is.
+        let info_queue = device
+            .query(&IID_ID3D12_INFO_QUEUE, "failed to get ID3D12InfoQueue")
+            .ok();
+        info!(debug_layer = info_queue.is_some(), "created D3D12 device");
+
+        Ok(Self {
+            device,
+            queue,
+            factory,
+            info_queue,
+            fence,
+            fence_event,
+            next_fence_value: 1,
+        })
+    }
+
+    /// Why the device was lost, after a call failed with `DXGI_ERROR_DEVICE_REMOVED`.
+    pub(crate) fn removed_reason(&self) -> Hresult {
+        let hr = unsafe {
+            (self
+                .device
+                .vtbl::<ID3D12DeviceVtbl>()
+                .get_device_removed_reason)(self.device.as_raw())
+        };
+        Hresult(hr)
+    }
+
+    /// Has the queue signal the fence once the work submitted so far is done, returning the
+    /// value to wait for.
+    pub(crate) fn signal(&mut self) -> Result<u64, Error> {
+        let value = self.next_fence_value;
+        let hr = unsafe {
+            (self.queue.vtbl::<ID3D12CommandQueueVtbl>().signal)(
+                self.queue.as_raw(),
+                self.fence.as_raw(),
+                value,
+            )
+        };
+        Hresult(hr).check("failed to signal fence")?;
+
+        self.next_fence_value += 1;
+        Ok(value)
+    }
+
+    /// Whether the queue has signaled `value` yet.
+    pub(crate) fn is_complete(&self, value: u64) -> bool {
+        let vtbl = unsafe { self.fence.vtbl::<ID3D12FenceVtbl>() };
+        unsafe { (vtbl.get_completed_value)(self.fence.as_raw()) >= value }
+    }
+
+    /// Blocks until the queue has signaled `value`.
+    pub(crate) fn wait(&self, value: u64) -> Result<(), Error> {
+        // note: a lost device reports every value as reached, so this can't hang.
+        if self.is_complete(value) {
+            return Ok(());
+        }
+
+        let vtbl = unsafe { self.fence.vtbl::<ID3D12FenceVtbl>() };
+        let hr =
+            unsafe { (vtbl.set_event_on_completion)(self.fence.as_raw(), value, self.fence_event) };
+        Hresult(hr).check("failed to wait for fence")?;
+        unsafe { WaitForSingleObject(self.fence_event, INFINITE) };
+
+        Ok(())
+    }
+
+    /// Blocks until the queue has finished everything submitted to it.
+    pub(crate) fn wait_idle(&mut self) -> Result<(), Error> {
+        let value = self.signal()?;
+        self.wait(value)
+    }
+
+    /// Logs and clears the debug layer's messages.
+    pub(crate) fn drain_messages(&self) {
+        let Some(queue) = &self.info_queue else {
+            return;
+        };
+
+        let vtbl = unsafe { queue.vtbl::<ID3D12InfoQueueVtbl>() };
+        let count = unsafe { (vtbl.get_num_stored_messages)(queue.as_raw()) };
+        // note: u64s to align the `Message` at the start of the buffer.
+        let mut buffer: Vec<u64> = Vec::new();
+        for index in 0..count {
+            let mut len = 0;
+            let hr = unsafe {
+                (vtbl.get_message)(queue.as_raw(), index, std::ptr::null_mut(), &mut len)
+            };
+            if hr < 0 || len < size_of::<Message>() {
+                continue;
+            }
+
+            buffer.resize(len.div_ceil(size_of::<u64>()), 0);
+            let message = buffer.as_mut_ptr().cast::<Message>();
+            let hr = unsafe { (vtbl.get_message)(queue.as_raw(), index, message, &mut len) };
+            if hr < 0 {
+                continue;
+            }
+
+            let message = unsafe { &*message };
+            let description =
+                unsafe { std::slice::from_raw_parts(message.description, message.description_len) };
+            let description = String::from_utf8_lossy(description);
+            let description = description.trim_end_matches('\0');
+            let id = message.id;
+            match message.severity {
+                D3D12_MESSAGE_SEVERITY_CORRUPTION | D3D12_MESSAGE_SEVERITY_ERROR => {
+                    error!(id, "{description}")
+                }
+                D3D12_MESSAGE_SEVERITY_WARNING => warn!(id, "{description}"),
+                D3D12_MESSAGE_SEVERITY_INFO => info!(id, "{description}"),
+                _ => debug!(id, "{description}"),
+            }
+        }
+        unsafe { (vtbl.clear_stored_messages)(queue.as_raw()) };
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.fence_event) };
+    }
+}
+
+/// Turns on the debug layer for devices created after, returning whether it's installed.
+fn enable_debug_layer() -> bool {
+    let mut debug = std::ptr::null_mut::<c_void>();
+    let hr = unsafe { D3D12GetDebugInterface(&IID_ID3D12_DEBUG, &mut debug) };
+    match unsafe { ComPtr::from_call(hr, debug, "failed to get ID3D12Debug") } {
+        Ok(debug) => {
+            unsafe { (debug.vtbl::<ID3D12DebugVtbl>().enable_debug_layer)(debug.as_raw()) };
+            true
+        }
+        Err(_) => {
+            warn!("the D3D12 debug layer isn't installed, continuing without it");
+            false
+        }
+    }
+}
@@ -0,0 +1,908 @@
+//! The parts of D3D12 and DXGI the renderer calls. windows-sys 0.52 has no `Direct3D` or `Dxgi`
+//! modules at all, only the `windows` crate does, so the constants and structs are here as well
+//! as the vtables. Vtables list the slots that get called, with the ones before them padded out.
+//!
+//! note: the structs' sizes, and the offsets of the fields after padding, are checked against
+//! the x64 SDK headers at the bottom, so a slip fails the build instead of the driver's reads.
+
+use std::ffi::c_void;
+
+use win32_base::com::IUnknownVtbl;
+use windows_sys::{
+    core::{GUID, HRESULT},
+    Win32::Foundation::{BOOL, HANDLE, HWND, RECT},
+};
+
+pub(crate) const IID_ID3D12_COMMAND_ALLOCATOR: GUID =
+    GUID::from_u128(0x6102dee4_af59_4b09_b999_b44d73f09b24);
+pub(crate) const IID_ID3D12_COMMAND_QUEUE: GUID =
+    GUID::from_u128(0x0ec870a6_5d7e_4c22_8cfc_5baae07616ed);
+pub(crate) const IID_ID3D12_DEBUG: GUID = GUID::from_u128(0x344488b7_6846_474b_b989_f027448245e0);
+pub(crate) const IID_ID3D12_DESCRIPTOR_HEAP: GUID =
+    GUID::from_u128(0x8efb471d_616c_4f49_90f7_127bb763fa51);
+pub(crate) const IID_ID3D12_DEVICE: GUID = GUID::from_u128(0x189819f1_1db6_4b57_be54_1821339b85f7);
+pub(crate) const IID_ID3D12_FENCE: GUID = GUID::from_u128(0x0a753dcf_c4d8_4b91_adf6_be5a60d95a76);
+pub(crate) const IID_ID3D12_GRAPHICS_COMMAND_LIST: GUID =
+    GUID::from_u128(0x5b160d0f_ac1b_4185_8ba8_b3ae42a5a455);
+pub(crate) const IID_ID3D12_PIPELINE_STATE: GUID =
+    GUID::from_u128(0x765a30f3_f624_4c6f_a828_ace948622445);
+pub(crate) const IID_ID3D12_ROOT_SIGNATURE: GUID =
+    GUID::from_u128(0xc54a6b66_72df_4ee8_8be5_a946a1429214);
+pub(crate) const IID_ID3D12_INFO_QUEUE: GUID =
+    GUID::from_u128(0x0742a90b_c387_483f_b946_30a7e4e61458);
+pub(crate) const IID_ID3D12_RESOURCE: GUID =
+    GUID::from_u128(0x696442be_a72e_4059_bc79_5b5c98040fad);
+pub(crate) const IID_IDXGI_FACTORY_2: GUID =
+    GUID::from_u128(0x50c83a1c_e072_4c48_87b0_3630fa36a6d0);
+pub(crate) const IID_IDXGI_FACTORY_5: GUID =
+    GUID::from_u128(0x7632e1f5_ee65_4dca_87fd_84cd75f8838d);
+pub(crate) const IID_IDXGI_OUTPUT_6: GUID = GUID::from_u128(0x068346e8_aaec_4b84_add7_137f513f77a1);
+pub(crate) const IID_IDXGI_SWAP_CHAIN_3: GUID =
+    GUID::from_u128(0x94d99bdb_f1f8_4ab0_b236_7da0170edab1);
+
+pub(crate) const D3D_FEATURE_LEVEL_11_0: i32 = 0xb000;
+pub(crate) const D3D12_COMMAND_LIST_TYPE_DIRECT: i32 = 0;
+pub(crate) const D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV: i32 = 0;
+pub(crate) const D3D12_DESCRIPTOR_HEAP_TYPE_RTV: i32 = 2;
+pub(crate) const D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE: u32 = 0x1;
+pub(crate) const D3D12_RESOURCE_BARRIER_TYPE_TRANSITION: i32 = 0;
+pub(crate) const D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES: u32 = 0xffffffff;
+pub(crate) const D3D12_RESOURCE_STATE_PRESENT: u32 = 0;
+pub(crate) const D3D12_RESOURCE_STATE_RENDER_TARGET: u32 = 0x4;
+pub(crate) const D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE: u32 = 0x80;
+pub(crate) const D3D12_RESOURCE_STATE_COPY_DEST: u32 = 0x400;
+pub(crate) const D3D12_RESOURCE_STATE_GENERIC_READ: u32 = 0xac3;
+pub(crate) const D3D12_HEAP_TYPE_DEFAULT: i32 = 1;
+pub(crate) const D3D12_HEAP_TYPE_UPLOAD: i32 = 2;
+pub(crate) const D3D12_RESOURCE_DIMENSION_BUFFER: i32 = 1;
+pub(crate) const D3D12_RESOURCE_DIMENSION_TEXTURE2D: i32 = 3;
+pub(crate) const D3D12_TEXTURE_LAYOUT_UNKNOWN: i32 = 0;
+pub(crate) const D3D12_TEXTURE_LAYOUT_ROW_MAJOR: i32 = 1;
+pub(crate) const D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX: i32 = 0;
+pub(crate) const D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT: i32 = 1;
+pub(crate) const D3D12_TEXTURE_DATA_PITCH_ALIGNMENT: u32 = 256;
+pub(crate) const D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT: u32 = 0x1;
+pub(crate) const D3D_ROOT_SIGNATURE_VERSION_1: i32 = 0x1;
+pub(crate) const D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE: i32 = 0;
+pub(crate) const D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS: i32 = 1;
+pub(crate) const D3D12_DESCRIPTOR_RANGE_TYPE_SRV: i32 = 0;
+pub(crate) const D3D12_SHADER_VISIBILITY_ALL: i32 = 0;
+pub(crate) const D3D12_SHADER_VISIBILITY_PIXEL: i32 = 5;
+pub(crate) const D3D12_FILTER_MIN_MAG_MIP_POINT: i32 = 0;
+pub(crate) const D3D12_FILTER_MIN_MAG_MIP_LINEAR: i32 = 0x15;
+pub(crate) const D3D12_TEXTURE_ADDRESS_MODE_CLAMP: i32 = 3;
+pub(crate) const D3D12_COMPARISON_FUNC_NEVER: i32 = 1;
+pub(crate) const D3D12_COMPARISON_FUNC_ALWAYS: i32 = 8;
+pub(crate) const D3D12_STENCIL_OP_KEEP: i32 = 1;
+pub(crate) const D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK: i32 = 0;
+pub(crate) const D3D12_BLEND_ONE: i32 = 2;
+pub(crate) const D3D12_BLEND_SRC_ALPHA: i32 = 5;
+pub(crate) const D3D12_BLEND_INV_SRC_ALPHA: i32 = 6;
+pub(crate) const D3D12_BLEND_OP_ADD: i32 = 1;
+pub(crate) const D3D12_LOGIC_OP_NOOP: i32 = 4;
+pub(crate) const D3D12_COLOR_WRITE_ENABLE_ALL: u8 = 0xf;
+pub(crate) const D3D12_FILL_MODE_SOLID: i32 = 3;
+pub(crate) const D3D12_CULL_MODE_NONE: i32 = 1;
+pub(crate) const D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA: i32 = 0;
+pub(crate) const D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE: i32 = 3;
+pub(crate) const D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST: i32 = 4;
+
+pub(crate) const D3D12_MESSAGE_SEVERITY_CORRUPTION: i32 = 0;
+pub(crate) const D3D12_MESSAGE_SEVERITY_ERROR: i32 = 1;
+pub(crate) const D3D12_MESSAGE_SEVERITY_WARNING: i32 = 2;
+pub(crate) const D3D12_MESSAGE_SEVERITY_INFO: i32 = 3;
+
+pub(crate) const DXGI_CREATE_FACTORY_DEBUG: u32 = 0x1;
+pub(crate) const DXGI_FORMAT_UNKNOWN: u32 = 0;
+pub(crate) const DXGI_FORMAT_R32G32B32A32_FLOAT: u32 = 2;
+pub(crate) const DXGI_FORMAT_R16G16B16A16_FLOAT: u32 = 10;
+pub(crate) const DXGI_FORMAT_R10G10B10A2_UNORM: u32 = 24;
+pub(crate) const DXGI_FORMAT_R8G8B8A8_UNORM: u32 = 28;
+pub(crate) const DXGI_FORMAT_R32G32_FLOAT: u32 = 16;
+pub(crate) const DXGI_FORMAT_R32_UINT: u32 = 42;
+pub(crate) const DXGI_FORMAT_B8G8R8A8_UNORM: u32 = 87;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709: u32 = 0;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709: u32 = 1;
+pub(crate) const DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020: u32 = 12;
+pub(crate) const DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT: u32 = 0x1;
+pub(crate) const DXGI_USAGE_RENDER_TARGET_OUTPUT: u32 = 0x20;
+pub(crate) const DXGI_SCALING_STRETCH: u32 = 0;
+pub(crate) const DXGI_SWAP_EFFECT_FLIP_DISCARD: u32 = 4;
+pub(crate) const DXGI_ALPHA_MODE_IGNORE: u32 = 3;
+pub(crate) const DXGI_MWA_NO_ALT_ENTER: u32 = 0x2;
+pub(crate) const DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT: u32 = 0x40;
+pub(crate) const DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING: u32 = 0x800;
+pub(crate) const DXGI_PRESENT_ALLOW_TEARING: u32 = 0x200;
+pub(crate) const DXGI_FEATURE_PRESENT_ALLOW_TEARING: i32 = 0;
+
+pub(crate) const DXGI_ERROR_DEVICE_REMOVED: HRESULT = 0x887a0005_u32 as i32;
+pub(crate) const DXGI_ERROR_DEVICE_RESET: HRESULT = 0x887a0007_u32 as i32;
+
+#[link(name = "d3d12")]
+extern "system" {
+    pub(crate) fn D3D12CreateDevice(
+        adapter: *mut c_void,
+        minimum_feature_level: i32,
+        iid: *const GUID,
+        device: *mut *mut c_void,
+    ) -> HRESULT;
+
+    pub(crate) fn D3D12GetDebugInterface(iid: *const GUID, debug: *mut *mut c_void) -> HRESULT;
+
+    pub(crate) fn D3D12SerializeRootSignature(
+        desc: *const RootSignatureDesc,
+        version: i32,
+        blob: *mut *mut c_void,
+        error_blob: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+#[link(name = "dxgi")]
+extern "system" {
+    pub(crate) fn CreateDXGIFactory2(
+        flags: u32,
+        iid: *const GUID,
+        factory: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+#[repr(C)]
+pub(crate) struct SampleDesc {
+    pub(crate) count: u32,
+    pub(crate) quality: u32,
+}
+
+#[repr(C)]
+pub(crate) struct SwapChainDesc1 {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) format: u32,
+    pub(crate) stereo: BOOL,
+    pub(crate) sample_desc: SampleDesc,
+    pub(crate) buffer_usage: u32,
+    pub(crate) buffer_count: u32,
+    pub(crate) scaling: u32,
+    pub(crate) swap_effect: u32,
+    pub(crate) alpha_mode: u32,
+    pub(crate) flags: u32,
+}
+
+/// `DXGI_OUTPUT_DESC1`.
+#[repr(C)]
+pub(crate) struct OutputDesc1 {
+    _device_name: [u16; 32],
+    _desktop_coordinates: RECT,
+    _attached_to_desktop: BOOL,
+    _rotation: u32,
+    _monitor: isize,
+    _bits_per_color: u32,
+    pub(crate) color_space: u32,
+    /// The red, green and blue primaries and the white point, as `[x, y]`.
+    _primaries: [[f32; 2]; 4],
+    _min_luminance: f32,
+    pub(crate) max_luminance: f32,
+    _max_full_frame_luminance: f32,
+}
+
+#[repr(C)]
+pub(crate) struct CommandQueueDesc {
+    pub(crate) kind: i32,
+    pub(crate) priority: i32,
+    pub(crate) flags: u32,
+    pub(crate) node_mask: u32,
+}
+
+#[repr(C)]
+pub(crate) struct DescriptorHeapDesc {
+    pub(crate) kind: i32,
+    pub(crate) num_descriptors: u32,
+    pub(crate) flags: u32,
+    pub(crate) node_mask: u32,
+}
+
+/// `D3D12_CPU_DESCRIPTOR_HANDLE`.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct CpuDescriptorHandle {
+    pub(crate) ptr: usize,
+}
+
+/// `D3D12_GPU_DESCRIPTOR_HANDLE`.
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct GpuDescriptorHandle {
+    pub(crate) ptr: u64,
+}
+
+#[repr(C)]
+pub(crate) struct HeapProperties {
+    pub(crate) kind: i32,
+    pub(crate) cpu_page_property: i32,
+    pub(crate) memory_pool_preference: i32,
+    pub(crate) creation_node_mask: u32,
+    pub(crate) visible_node_mask: u32,
+}
+
+#[repr(C)]
+pub(crate) struct ResourceDesc {
+    pub(crate) dimension: i32,
+    pub(crate) alignment: u64,
+    pub(crate) width: u64,
+    pub(crate) height: u32,
+    pub(crate) depth_or_array_size: u16,
+    pub(crate) mip_levels: u16,
+    pub(crate) format: u32,
+    pub(crate) sample_desc: SampleDesc,
+    pub(crate) layout: i32,
+    pub(crate) flags: u32,
+}
+
+#[repr(C)]
+pub(crate) struct Range {
+    pub(crate) begin: usize,
+    pub(crate) end: usize,
+}
+
+#[repr(C)]
+pub(crate) struct SubresourceFootprint {
+    pub(crate) format: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) depth: u32,
+    pub(crate) row_pitch: u32,
+}
+
+#[repr(C)]
+pub(crate) struct PlacedSubresourceFootprint {
+    pub(crate) offset: u64,
+    pub(crate) footprint: SubresourceFootprint,
+}
+
+/// `D3D12_TEXTURE_COPY_LOCATION`, with the union as the placed footprint, its largest member.
+/// A subresource index overlaps the footprint's offset.
+#[repr(C)]
+pub(crate) struct TextureCopyLocation {
+    pub(crate) resource: *mut c_void,
+    pub(crate) kind: i32,
+    pub(crate) placed_footprint: PlacedSubresourceFootprint,
+}
+
+#[repr(C)]
+pub(crate) struct VertexBufferView {
+    pub(crate) buffer_location: u64,
+    pub(crate) size_in_bytes: u32,
+    pub(crate) stride_in_bytes: u32,
+}
+
+#[repr(C)]
+pub(crate) struct IndexBufferView {
+    pub(crate) buffer_location: u64,
+    pub(crate) size_in_bytes: u32,
+    pub(crate) format: u32,
+}
+
+#[repr(C)]
+pub(crate) struct Viewport {
+    pub(crate) top_left_x: f32,
+    pub(crate) top_left_y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) min_depth: f32,
+    pub(crate) max_depth: f32,
+}
+
+#[repr(C)]
+pub(crate) struct DescriptorRange {
+    pub(crate) range_type: i32,
+    pub(crate) num_descriptors: u32,
+    pub(crate) base_shader_register: u32,
+    pub(crate) register_space: u32,
+    pub(crate) offset_in_descriptors_from_table_start: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct RootDescriptorTable {
+    pub(crate) num_descriptor_ranges: u32,
+    pub(crate) descriptor_ranges: *const DescriptorRange,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct RootConstants {
+    pub(crate) shader_register: u32,
+    pub(crate) register_space: u32,
+    pub(crate) num_32bit_values: u32,
+}
+
+#[repr(C)]
+pub(crate) union RootParameterPayload {
+    pub(crate) descriptor_table: RootDescriptorTable,
+    pub(crate) constants: RootConstants,
+}
+
+#[repr(C)]
+pub(crate) struct RootParameter {
+    pub(crate) parameter_type: i32,
+    pub(crate) payload: RootParameterPayload,
+    pub(crate) shader_visibility: i32,
+}
+
+#[repr(C)]
+pub(crate) struct StaticSamplerDesc {
+    pub(crate) filter: i32,
+    pub(crate) address_u: i32,
+    pub(crate) address_v: i32,
+    pub(crate) address_w: i32,
+    pub(crate) mip_lod_bias: f32,
+    pub(crate) max_anisotropy: u32,
+    pub(crate) comparison_func: i32,
+    pub(crate) border_color: i32,
+    pub(crate) min_lod: f32,
+    pub(crate) max_lod: f32,
+    pub(crate) shader_register: u32,
+    pub(crate) register_space: u32,
+    pub(crate) shader_visibility: i32,
+}
+
+#[repr(C)]
+pub(crate) struct RootSignatureDesc {
+    pub(crate) num_parameters: u32,
+    pub(crate) parameters: *const RootParameter,
+    pub(crate) num_static_samplers: u32,
+    pub(crate) static_samplers: *const StaticSamplerDesc,
+    pub(crate) flags: u32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct ShaderBytecode {
+    pub(crate) bytecode: *const c_void,
+    pub(crate) len: usize,
+}
+
+#[derive(Clone, Copy, Default)]
+#[repr(C)]
+pub(crate) struct RenderTargetBlendDesc {
+    pub(crate) blend_enable: BOOL,
+    pub(crate) logic_op_enable: BOOL,
+    pub(crate) src_blend: i32,
+    pub(crate) dest_blend: i32,
+    pub(crate) blend_op: i32,
+    pub(crate) src_blend_alpha: i32,
+    pub(crate) dest_blend_alpha: i32,
+    pub(crate) blend_op_alpha: i32,
+    pub(crate) logic_op: i32,
+    pub(crate) render_target_write_mask: u8,
+}
+
+#[repr(C)]
+pub(crate) struct BlendDesc {
+    pub(crate) alpha_to_coverage_enable: BOOL,
+    pub(crate) independent_blend_enable: BOOL,
+    pub(crate) render_target: [RenderTargetBlendDesc; 8],
+}
+
+#[repr(C)]
+pub(crate) struct RasterizerDesc {
+    pub(crate) fill_mode: i32,
+    pub(crate) cull_mode: i32,
+    pub(crate) front_counter_clockwise: BOOL,
+    pub(crate) depth_bias: i32,
+    pub(crate) depth_bias_clamp: f32,
+    pub(crate) slope_scaled_depth_bias: f32,
+    pub(crate) depth_clip_enable: BOOL,
+    pub(crate) multisample_enable: BOOL,
+    pub(crate) antialiased_line_enable: BOOL,
+    pub(crate) forced_sample_count: u32,
+    pub(crate) conservative_raster: i32,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub(crate) struct DepthStencilOpDesc {
+    pub(crate) stencil_fail_op: i32,
+    pub(crate) stencil_depth_fail_op: i32,
+    pub(crate) stencil_pass_op: i32,
+    pub(crate) stencil_func: i32,
+}
+
+#[repr(C)]
+pub(crate) struct DepthStencilDesc {
+    pub(crate) depth_enable: BOOL,
+    pub(crate) depth_write_mask: i32,
+    pub(crate) depth_func: i32,
+    pub(crate) stencil_enable: BOOL,
+    pub(crate) stencil_read_mask: u8,
+    pub(crate) stencil_write_mask: u8,
+    pub(crate) front_face: DepthStencilOpDesc,
+    pub(crate) back_face: DepthStencilOpDesc,
+}
+
+#[repr(C)]
+pub(crate) struct InputElementDesc {
+    pub(crate) semantic_name: *const u8,
+    pub(crate) semantic_index: u32,
+    pub(crate) format: u32,
+    pub(crate) input_slot: u32,
+    pub(crate) aligned_byte_offset: u32,
+    pub(crate) input_slot_class: i32,
+    pub(crate) instance_data_step_rate: u32,
+}
+
+#[repr(C)]
+pub(crate) struct InputLayoutDesc {
+    pub(crate) input_element_descs: *const InputElementDesc,
+    pub(crate) num_elements: u32,
+}
+
+/// `D3D12_GRAPHICS_PIPELINE_STATE_DESC`, with the stages and states the renderer doesn't use
+/// left as padding to zero.
+#[repr(C)]
+pub(crate) struct GraphicsPipelineStateDesc {
+    pub(crate) root_signature: *mut c_void,
+    pub(crate) vs: ShaderBytecode,
+    pub(crate) ps: ShaderBytecode,
+    /// The domain, hull and geometry shaders.
+    pub(crate) _other_shaders: [ShaderBytecode; 3],
+    /// `D3D12_STREAM_OUTPUT_DESC`.
+    pub(crate) _stream_output: [usize; 4],
+    pub(crate) blend_state: BlendDesc,
+    pub(crate) sample_mask: u32,
+    pub(crate) rasterizer_state: RasterizerDesc,
+    pub(crate) depth_stencil_state: DepthStencilDesc,
+    pub(crate) input_layout: InputLayoutDesc,
+    pub(crate) ib_strip_cut_value: i32,
+    pub(crate) primitive_topology_type: i32,
+    pub(crate) num_render_targets: u32,
+    pub(crate) rtv_formats: [u32; 8],
+    pub(crate) dsv_format: u32,
+    pub(crate) sample_desc: SampleDesc,
+    pub(crate) node_mask: u32,
+    /// `D3D12_CACHED_PIPELINE_STATE`.
+    pub(crate) _cached_pso: [usize; 2],
+    pub(crate) flags: u32,
+}
+
+/// `D3D12_RESOURCE_BARRIER`, with the union as the transition barrier, its largest member.
+#[repr(C)]
+pub(crate) struct ResourceBarrier {
+    pub(crate) kind: i32,
+    pub(crate) flags: u32,
+    pub(crate) resource: *mut c_void,
+    pub(crate) subresource: u32,
+    pub(crate) state_before: u32,
+    pub(crate) state_after: u32,
+}
+
+#[repr(C)]
+pub(crate) struct Message {
+    pub(crate) category: i32,
+    pub(crate) severity: i32,
+    pub(crate) id: i32,
+    pub(crate) description: *const u8,
+    pub(crate) description_len: usize,
+}
+
+/// `IDXGIObject`, the base of the DXGI interfaces.
+#[repr(C)]
+pub(crate) struct IDXGIObjectVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _set_private_data: usize,
+    _set_private_data_interface: usize,
+    _get_private_data: usize,
+    _get_parent: usize,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIFactory2Vtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    _enum_adapters: usize,
+    pub(crate) make_window_association:
+        unsafe extern "system" fn(this: *mut c_void, hwnd: HWND, flags: u32) -> HRESULT,
+    _get_window_association: usize,
+    _create_swap_chain: usize,
+    _create_software_adapter: usize,
+    _enum_adapters1: usize,
+    _is_current: usize,
+    _is_windowed_stereo_enabled: usize,
+    pub(crate) create_swap_chain_for_hwnd: unsafe extern "system" fn(
+        this: *mut c_void,
+        device: *mut c_void,
+        hwnd: HWND,
+        desc: *const SwapChainDesc1,
+        fullscreen_desc: *const c_void,
+        restrict_to_output: *mut c_void,
+        swap_chain: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIFactory5Vtbl {
+    pub(crate) base: IDXGIFactory2Vtbl,
+    /// `CreateSwapChainForCoreWindow` to `EnumWarpAdapter`.
+    _factory_2_to_4: [usize; 12],
+    pub(crate) check_feature_support: unsafe extern "system" fn(
+        this: *mut c_void,
+        feature: i32,
+        support: *mut c_void,
+        size: u32,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGIOutput6Vtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    /// `IDXGIOutput`'s methods to `IDXGIOutput5`'s.
+    _output_to_output_5: [usize; 20],
+    pub(crate) get_desc1:
+        unsafe extern "system" fn(this: *mut c_void, desc: *mut OutputDesc1) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct IDXGISwapChain3Vtbl {
+    pub(crate) base: IDXGIObjectVtbl,
+    _get_device: usize,
+    pub(crate) present:
+        unsafe extern "system" fn(this: *mut c_void, sync_interval: u32, flags: u32) -> HRESULT,
+    pub(crate) get_buffer: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer: u32,
+        iid: *const GUID,
+        surface: *mut *mut c_void,
+    ) -> HRESULT,
+    _set_fullscreen_state: usize,
+    _get_fullscreen_state: usize,
+    _get_desc: usize,
+    pub(crate) resize_buffers: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer_count: u32,
+        width: u32,
+        height: u32,
+        format: u32,
+        flags: u32,
+    ) -> HRESULT,
+    _resize_target: usize,
+    pub(crate) get_containing_output:
+        unsafe extern "system" fn(this: *mut c_void, output: *mut *mut c_void) -> HRESULT,
+    /// `GetFrameStatistics` to `IDXGISwapChain1::GetRotation`.
+    _swap_chain_1: [usize; 13],
+    _set_source_size: usize,
+    _get_source_size: usize,
+    pub(crate) set_maximum_frame_latency:
+        unsafe extern "system" fn(this: *mut c_void, max_latency: u32) -> HRESULT,
+    _get_maximum_frame_latency: usize,
+    pub(crate) get_frame_latency_waitable_object:
+        unsafe extern "system" fn(this: *mut c_void) -> HANDLE,
+    _set_matrix_transform: usize,
+    _get_matrix_transform: usize,
+    pub(crate) get_current_back_buffer_index: unsafe extern "system" fn(this: *mut c_void) -> u32,
+    pub(crate) check_color_space_support: unsafe extern "system" fn(
+        this: *mut c_void,
+        color_space: u32,
+        support: *mut u32,
+    ) -> HRESULT,
+    pub(crate) set_color_space1:
+        unsafe extern "system" fn(this: *mut c_void, color_space: u32) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12DebugVtbl {
+    pub(crate) base: IUnknownVtbl,
+    pub(crate) enable_debug_layer: unsafe extern "system" fn(this: *mut c_void),
+}
+
+/// `ID3D12Object` and `ID3D12DeviceChild`, the base of the D3D12 interfaces.
+#[repr(C)]
+pub(crate) struct ID3D12DeviceChildVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _get_private_data: usize,
+    _set_private_data: usize,
+    _set_private_data_interface: usize,
+    _set_name: usize,
+    _get_device: usize,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12DeviceVtbl {
+    pub(crate) base: IUnknownVtbl,
+    /// `ID3D12Object`'s methods, then `GetNodeCount`.
+    _object: [usize; 5],
+    pub(crate) create_command_queue: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const CommandQueueDesc,
+        iid: *const GUID,
+        queue: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) create_command_allocator: unsafe extern "system" fn(
+        this: *mut c_void,
+        kind: i32,
+        iid: *const GUID,
+        allocator: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) create_graphics_pipeline_state: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const GraphicsPipelineStateDesc,
+        iid: *const GUID,
+        state: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_compute_pipeline_state: usize,
+    pub(crate) create_command_list: unsafe extern "system" fn(
+        this: *mut c_void,
+        node_mask: u32,
+        kind: i32,
+        allocator: *mut c_void,
+        initial_state: *mut c_void,
+        iid: *const GUID,
+        list: *mut *mut c_void,
+    ) -> HRESULT,
+    _check_feature_support: usize,
+    pub(crate) create_descriptor_heap: unsafe extern "system" fn(
+        this: *mut c_void,
+        desc: *const DescriptorHeapDesc,
+        iid: *const GUID,
+        heap: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) get_descriptor_handle_increment_size:
+        unsafe extern "system" fn(this: *mut c_void, kind: i32) -> u32,
+    pub(crate) create_root_signature: unsafe extern "system" fn(
+        this: *mut c_void,
+        node_mask: u32,
+        blob: *const c_void,
+        blob_len: usize,
+        iid: *const GUID,
+        signature: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_constant_buffer_view: usize,
+    pub(crate) create_shader_resource_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        desc: *const c_void,
+        descriptor: CpuDescriptorHandle,
+    ),
+    _create_unordered_access_view: usize,
+    pub(crate) create_render_target_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        resource: *mut c_void,
+        desc: *const c_void,
+        descriptor: CpuDescriptorHandle,
+    ),
+    /// `CreateDepthStencilView` to `GetCustomHeapProperties`.
+    _create_descriptors: [usize; 6],
+    pub(crate) create_committed_resource: unsafe extern "system" fn(
+        this: *mut c_void,
+        heap_properties: *const HeapProperties,
+        heap_flags: u32,
+        desc: *const ResourceDesc,
+        initial_state: u32,
+        optimized_clear_value: *const c_void,
+        iid: *const GUID,
+        resource: *mut *mut c_void,
+    ) -> HRESULT,
+    /// `CreateHeap` to `Evict`.
+    _create_resources: [usize; 8],
+    pub(crate) create_fence: unsafe extern "system" fn(
+        this: *mut c_void,
+        initial_value: u64,
+        flags: u32,
+        iid: *const GUID,
+        fence: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) get_device_removed_reason: unsafe extern "system" fn(this: *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12CommandQueueVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    _update_tile_mappings: usize,
+    _copy_tile_mappings: usize,
+    pub(crate) execute_command_lists:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, lists: *const *mut c_void),
+    _set_marker: usize,
+    _begin_event: usize,
+    _end_event: usize,
+    pub(crate) signal:
+        unsafe extern "system" fn(this: *mut c_void, fence: *mut c_void, value: u64) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12CommandAllocatorVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    pub(crate) reset: unsafe extern "system" fn(this: *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12FenceVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    pub(crate) get_completed_value: unsafe extern "system" fn(this: *mut c_void) -> u64,
+    pub(crate) set_event_on_completion:
+        unsafe extern "system" fn(this: *mut c_void, value: u64, event: HANDLE) -> HRESULT,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12DescriptorHeapVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    _get_desc: usize,
+    // note: returns the struct through a hidden pointer, as C++ methods returning structs do.
+    pub(crate) get_cpu_descriptor_handle_for_heap_start:
+        unsafe extern "system" fn(
+            this: *mut c_void,
+            handle: *mut CpuDescriptorHandle,
+        ) -> *mut CpuDescriptorHandle,
+    pub(crate) get_gpu_descriptor_handle_for_heap_start:
+        unsafe extern "system" fn(
+            this: *mut c_void,
+            handle: *mut GpuDescriptorHandle,
+        ) -> *mut GpuDescriptorHandle,
+}
+
+/// `ID3D12Resource`, after `ID3D12Pageable`, which adds nothing.
+#[repr(C)]
+pub(crate) struct ID3D12ResourceVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    pub(crate) map: unsafe extern "system" fn(
+        this: *mut c_void,
+        subresource: u32,
+        read_range: *const Range,
+        data: *mut *mut c_void,
+    ) -> HRESULT,
+    pub(crate) unmap:
+        unsafe extern "system" fn(this: *mut c_void, subresource: u32, written_range: *const Range),
+    _get_desc: usize,
+    pub(crate) get_gpu_virtual_address: unsafe extern "system" fn(this: *mut c_void) -> u64,
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12GraphicsCommandListVtbl {
+    pub(crate) base: ID3D12DeviceChildVtbl,
+    _get_type: usize,
+    pub(crate) close: unsafe extern "system" fn(this: *mut c_void) -> HRESULT,
+    pub(crate) reset: unsafe extern "system" fn(
+        this: *mut c_void,
+        allocator: *mut c_void,
+        initial_state: *mut c_void,
+    ) -> HRESULT,
+    _clear_state: usize,
+    _draw_instanced: usize,
+    pub(crate) draw_indexed_instanced: unsafe extern "system" fn(
+        this: *mut c_void,
+        index_count_per_instance: u32,
+        instance_count: u32,
+        start_index_location: u32,
+        base_vertex_location: i32,
+        start_instance_location: u32,
+    ),
+    _dispatch: usize,
+    _copy_buffer_region: usize,
+    pub(crate) copy_texture_region: unsafe extern "system" fn(
+        this: *mut c_void,
+        dst: *const TextureCopyLocation,
+        dst_x: u32,
+        dst_y: u32,
+        dst_z: u32,
+        src: *const TextureCopyLocation,
+        src_box: *const c_void,
+    ),
+    /// `CopyResource` to `ResolveSubresource`.
+    _copy: [usize; 3],
+    pub(crate) ia_set_primitive_topology:
+        unsafe extern "system" fn(this: *mut c_void, topology: i32),
+    pub(crate) rs_set_viewports:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, viewports: *const Viewport),
+    pub(crate) rs_set_scissor_rects:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, rects: *const RECT),
+    _om_set_blend_factor: usize,
+    _om_set_stencil_ref: usize,
+    pub(crate) set_pipeline_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void),
+    pub(crate) resource_barrier:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, barriers: *const ResourceBarrier),
+    _execute_bundle: usize,
+    pub(crate) set_descriptor_heaps:
+        unsafe extern "system" fn(this: *mut c_void, count: u32, heaps: *const *mut c_void),
+    _set_compute_root_signature: usize,
+    pub(crate) set_graphics_root_signature:
+        unsafe extern "system" fn(this: *mut c_void, signature: *mut c_void),
+    _set_compute_root_descriptor_table: usize,
+    pub(crate) set_graphics_root_descriptor_table: unsafe extern "system" fn(
+        this: *mut c_void,
+        parameter: u32,
+        base_descriptor: GpuDescriptorHandle,
+    ),
+    /// `SetComputeRoot32BitConstant` to `SetComputeRoot32BitConstants`.
+    _set_constants: [usize; 3],
+    pub(crate) set_graphics_root_32bit_constants: unsafe extern "system" fn(
+        this: *mut c_void,
+        parameter: u32,
+        count: u32,
+        data: *const c_void,
+        offset: u32,
+    ),
+    /// `SetComputeRootConstantBufferView` to `SetGraphicsRootUnorderedAccessView`.
+    _set_root_views: [usize; 6],
+    pub(crate) ia_set_index_buffer:
+        unsafe extern "system" fn(this: *mut c_void, view: *const IndexBufferView),
+    pub(crate) ia_set_vertex_buffers: unsafe extern "system" fn(
+        this: *mut c_void,
+        start_slot: u32,
+        count: u32,
+        views: *const VertexBufferView,
+    ),
+    _so_set_targets: usize,
+    pub(crate) om_set_render_targets: unsafe extern "system" fn(
+        this: *mut c_void,
+        count: u32,
+        descriptors: *const CpuDescriptorHandle,
+        single_range: BOOL,
+        depth_stencil: *const CpuDescriptorHandle,
+    ),
+    _clear_depth_stencil_view: usize,
+    pub(crate) clear_render_target_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        view: CpuDescriptorHandle,
+        color: *const [f32; 4],
+        rect_count: u32,
+        rects: *const RECT,
+    ),
+}
+
+#[repr(C)]
+pub(crate) struct ID3D12InfoQueueVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _set_message_count_limit: usize,
+    pub(crate) clear_stored_messages: unsafe extern "system" fn(this: *mut c_void),
+    pub(crate) get_message: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: u64,
+        message: *mut Message,
+        len: *mut usize,
+    ) -> HRESULT,
+    _get_num_messages_allowed_by_storage_filter: usize,
+    _get_num_messages_denied_by_storage_filter: usize,
+    pub(crate) get_num_stored_messages: unsafe extern "system" fn(this: *mut c_void) -> u64,
+}
+
+// The sizes and offsets in the x64 SDK headers.
+#[cfg(target_pointer_width = "64")]
+const _: () = {
+    use std::mem::offset_of;
+
+    assert!(size_of::<SampleDesc>() == 8);
+    assert!(size_of::<SwapChainDesc1>() == 48);
+    assert!(size_of::<OutputDesc1>() == 152);
+    assert!(offset_of!(OutputDesc1, color_space) == 100);
+    assert!(offset_of!(OutputDesc1, max_luminance) == 140);
+    assert!(size_of::<CommandQueueDesc>() == 16);
+    assert!(size_of::<DescriptorHeapDesc>() == 16);
+    assert!(size_of::<CpuDescriptorHandle>() == 8);
+    assert!(size_of::<GpuDescriptorHandle>() == 8);
+    assert!(size_of::<HeapProperties>() == 20);
+    assert!(size_of::<ResourceDesc>() == 56);
+    assert!(offset_of!(ResourceDesc, format) == 32);
+    assert!(size_of::<Range>() == 16);
+    assert!(size_of::<SubresourceFootprint>() == 20);
+    assert!(size_of::<PlacedSubresourceFootprint>() == 32);
+    assert!(size_of::<TextureCopyLocation>() == 48);
+    assert!(offset_of!(TextureCopyLocation, placed_footprint) == 16);
+    assert!(size_of::<VertexBufferView>() == 16);
+    assert!(size_of::<IndexBufferView>() == 16);
+    assert!(size_of::<Viewport>() == 24);
+    assert!(size_of::<DescriptorRange>() == 20);
+    assert!(size_of::<RootParameter>() == 32);
+    assert!(offset_of!(RootParameter, shader_visibility) == 24);
+    assert!(size_of::<StaticSamplerDesc>() == 52);
+    assert!(size_of::<RootSignatureDesc>() == 40);
+    assert!(size_of::<ShaderBytecode>() == 16);
+    assert!(size_of::<RenderTargetBlendDesc>() == 40);
+    assert!(size_of::<BlendDesc>() == 328);
+    assert!(size_of::<RasterizerDesc>() == 44);
+    assert!(size_of::<DepthStencilOpDesc>() == 16);
+    assert!(size_of::<DepthStencilDesc>() == 52);
+    assert!(size_of::<InputElementDesc>() == 32);
+    assert!(size_of::<InputLayoutDesc>() == 16);
+    assert!(size_of::<GraphicsPipelineStateDesc>() == 656);
+    assert!(offset_of!(GraphicsPipelineStateDesc, input_layout) == 552);
+    assert!(offset_of!(GraphicsPipelineStateDesc, flags) == 648);
+    assert!(size_of::<ResourceBarrier>() == 32);
+    assert!(size_of::<Message>() == 32);
+    assert!(offset_of!(Message, description) == 16);
+};
@@ -0,0 +1,568 @@
+//! A Direct3D 12 [`GraphicsDevice`] for any [`Surface`]: a device and direct queue on the
+//! default adapter, a flip-model swap chain on the window, and a command allocator per back
+//! buffer so up to three frames are in flight, each reused once the fence says the GPU is done
+//! with it.
+//!
+//! ```no_run
+//! use common::{graphics::GraphicsDevice, surface::Surface};
+//! use renderer_d3d12::Renderer;
+//!
+//! fn run(window: &dyn Surface) -> Result<(), common::error::Error> {
+//!     let mut renderer = Renderer::builder().build(window)?;
+//!
+//!     // On each resize event:
+//!     let (width, height) = window.surface_size();
+//!     renderer.resize(width, height)?;
+//!
+//!     // Once a frame:
+//!     renderer.begin_frame()?.clear([0.1, 0.2, 0.3, 1.0]);
+//!     renderer.end_frame()
+//! }
+//! ```
+//!
+//! Quads drawn with [`CommandList::draw_quads`] go through a small pipeline whose shaders are
+//! compiled when the device is created, with the `d3dcompiler_47.dll` that comes with Windows.
+//! Up to 4096 textures can exist at once.
+//!
+//! If the GPU is lost, e.g. to a driver update or a TDR, the device, swap chain and textures are
+//! recreated on the next frame and the reason is logged. In debug builds the D3D12 debug layer is
+//! on, if it's installed, and its messages are logged each frame.
+
+#[allow(clippy::non_minimal_cfg)]
+#[cfg(all(not(target_os = "windows")))]
+compile_error!("only windows is supported");
+
+mod device;
+mod ffi;
+pub mod shader;
+mod sprite;
+mod swap_chain;
+
+use std::marker::PhantomData;
+
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{
+        Backend, CommandList, GraphicsDevice, Image, PresentOptions, TextureFilter, TextureId,
+        Vertex,
+    },
+    surface::{RawWindowHandle, Surface},
+};
+use tracing::warn;
+use win32_base::{com::ComPtr, error::Hresult};
+use windows_sys::Win32::Foundation::{HWND, RECT};
+
+use crate::{
+    device::Device,
+    ffi::{
+        ID3D12CommandAllocatorVtbl, ID3D12CommandQueueVtbl, ID3D12DeviceVtbl,
+        ID3D12GraphicsCommandListVtbl, ResourceBarrier, Viewport, D3D12_COMMAND_LIST_TYPE_DIRECT,
+        D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES, D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
+        D3D12_RESOURCE_STATE_PRESENT, D3D12_RESOURCE_STATE_RENDER_TARGET,
+        DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET, IID_ID3D12_COMMAND_ALLOCATOR,
+        IID_ID3D12_GRAPHICS_COMMAND_LIST,
+    },
+    sprite::{SpritePipeline, Texture, MAX_TEXTURES, SHADER},
+    swap_chain::SwapChain,
+};
+
+/// The number of back buffers, and so of frames that can be in flight.
+pub(crate) const FRAME_COUNT: usize = 3;
+
+pub struct RendererBuilder {
+    debug: bool,
+    present: PresentOptions,
+}
+
+impl RendererBuilder {
+    /// Whether to enable the D3D12 debug layer. Defaults to on in debug builds. Ignored with a
+    /// warning if the layer isn't installed.
+    pub fn debug(self, debug: bool) -> Self {
+        Self { debug, ..self }
+    }
+
+    /// Whether presenting waits for the display's vertical blank. Defaults to on.
+    pub fn vsync(self, vsync: bool) -> Self {
+        Self {
+            present: PresentOptions {
+                vsync,
+                ..self.present
+            },
+            ..self
+        }
+    }
+
+    /// How the swap chain presents. See [`PresentOptions`] for the defaults.
+    pub fn present_options(self, present: PresentOptions) -> Self {
+        Self { present, ..self }
+    }
+
+    /// Creates the device and a swap chain the size of `surface`.
+    pub fn build(self, surface: &dyn Surface) -> Result<Renderer, Error> {
+        let hwnd = match surface.window_handle() {
+            RawWindowHandle::Win32 { hwnd, .. } => hwnd,
+            handle => {
+                return Err(
+                    Error::new(format!("can't create a D3D12 swap chain for {handle:?}"))
+                        .with_kind(ErrorKind::Unsupported),
+                )
+            }
+        };
+
+        let size = surface.surface_size();
+        Ok(Renderer {
+            gpu: Some(Gpu::new(hwnd, self.debug, size, self.present, &[], SHADER)?),
+            hwnd,
+            size,
+            debug: self.debug,
+            present: self.present,
+            images: Vec::new(),
+            sprite_shader: None,
+            _not_send: PhantomData,
+        })
+    }
+}
+
+/// Draws to a window with Direct3D 12. Must be used on the thread that runs the window's event
+/// loop, since DXGI sends the window messages when presenting.
+pub struct Renderer {
+    /// `None` after the device was lost, until it's recreated.
+    gpu: Option<Gpu>,
+    hwnd: HWND,
+    size: (u32, u32),
+    debug: bool,
+    /// The present options asked for, which are reapplied if the device is recreated.
+    present: PresentOptions,
+    /// Each texture's image, by index, which is uploaded again if the device is recreated.
+    images: Vec<Option<(Image, TextureFilter)>>,
+    /// The sprite shader's source if it was replaced, which is compiled again if the device is
+    /// recreated.
+    sprite_shader: Option<String>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Renderer {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder {
+            debug: cfg!(debug_assertions),
+            present: PresentOptions::default(),
+        }
+    }
+
+    fn recover(&mut self, err: Error) -> Result<(), Error> {
+        if let Some(gpu) = &self.gpu {
+            let reason = gpu.device.removed_reason();
+            warn!("{err}: the GPU device was lost ({reason}), recreating it");
+        }
+
+        self.recreate().map(|_| ())
+    }
+
+    fn recreate(&mut self) -> Result<&mut Gpu, Error> {
+        // note: a window can only have one flip-model swap chain, so the old one has to go first.
+        self.gpu = None;
+        let gpu = Gpu::new(
+            self.hwnd,
+            self.debug,
+            self.size,
+            self.present,
+            &self.images,
+            self.sprite_shader.as_deref().unwrap_or(SHADER),
+        )?;
+        Ok(self.gpu.insert(gpu))
+    }
+}
+
+impl GraphicsDevice for Renderer {
+    fn backend(&self) -> Backend {
+        Backend::D3D12
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 || (width, height) == self.size {
+            return Ok(());
+        }
+
+        self.size = (width, height);
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        match gpu.resize(width, height) {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+
+    fn present_options(&self) -> PresentOptions {
+        self.gpu
+            .as_ref()
+            .map_or(self.present, |gpu| gpu.swap_chain.options())
+    }
+
+    fn set_present_options(&mut self, options: PresentOptions) -> Result<(), Error> {
+        self.present = options;
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+        match gpu.configure(options) {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+
+    fn create_texture(&mut self, image: Image, filter: TextureFilter) -> Result<TextureId, Error> {
+        let index = self
+            .images
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.images.len());
+        if index >= MAX_TEXTURES {
+            return Err(Error::new(format!(
+                "can't have more than {MAX_TEXTURES} textures at once"
+            ))
+            .with_kind(ErrorKind::Unsupported));
+        }
+        if let Some(gpu) = &mut self.gpu {
+            let texture = gpu
+                .sprites
+                .create_texture(&mut gpu.device, index, &image, filter)?;
+            if index >= gpu.textures.len() {
+                gpu.textures.resize_with(index + 1, || None);
+            }
+            gpu.textures[index] = Some(texture);
+        }
+
+        if index == self.images.len() {
+            self.images.push(None);
+        }
+        self.images[index] = Some((image, filter));
+        Ok(TextureId::from_index(index))
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: TextureId,
+        origin: [u32; 2],
+        image: &Image,
+    ) -> Result<(), Error> {
+        let Some(Some((stored, _))) = self.images.get_mut(texture.index()) else {
+            return Err(
+                Error::new(format!("there's no texture {}", texture.index()))
+                    .with_kind(ErrorKind::NotFound),
+            );
+        };
+        stored.copy_from(origin, image)?;
+        if let Some(gpu) = &mut self.gpu {
+            if let Some(Some(texture)) = gpu.textures.get(texture.index()) {
+                gpu.sprites
+                    .update_texture(&mut gpu.device, texture, origin, image)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy_texture(&mut self, texture: TextureId) {
+        if let Some(image) = self.images.get_mut(texture.index()) {
+            *image = None;
+        }
+        if let Some(gpu) = &mut self.gpu {
+            // note: frames in flight may still be drawing it.
+            if let Some(texture) = gpu.textures.get_mut(texture.index()).and_then(Option::take) {
+                gpu.sprites.retire(texture.into_resource());
+            }
+        }
+    }
+
+    fn set_sprite_shader(&mut self, source: &str) -> Result<(), Error> {
+        match &mut self.gpu {
+            Some(gpu) => gpu.sprites.set_shader(&gpu.device, source)?,
+            // note: checked now, so recreating the device doesn't fail on it later.
+            None => _ = sprite::compile(source)?,
+        }
+        self.sprite_shader = Some(source.to_string());
+        Ok(())
+    }
+
+    /// Waits for room in the present queue and until the GPU is done with the next back
+    /// buffer's previous frame, then starts recording to it, recreating the device first if it
+    /// was lost.
+    fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error> {
+        let gpu = match self.gpu {
+            Some(ref mut gpu) => gpu,
+            None => self.recreate()?,
+        };
+        gpu.begin()?;
+        Ok(gpu)
+    }
+
+    fn end_frame(&mut self) -> Result<(), Error> {
+        let Some(gpu) = &mut self.gpu else {
+            return Ok(());
+        };
+
+        let result = gpu.end();
+        gpu.device.drain_messages();
+        match result {
+            Err(err) if is_device_lost(&err) => self.recover(err),
+            result => result,
+        }
+    }
+}
+
+/// Everything that's recreated when the device is lost, and the frame being recorded.
+struct Gpu {
+    list: ComPtr,
+    allocators: Vec<ComPtr>,
+    /// The fence value each back buffer's last frame was submitted with, to wait for before its
+    /// allocator is reused.
+    fence_values: [u64; FRAME_COUNT],
+    /// The back buffer being recorded to, between `begin` and `end`.
+    recording: Option<usize>,
+    sprites: SpritePipeline,
+    /// The textures by index, each with its view at the same index in the sprite pipeline's heap.
+    textures: Vec<Option<Texture>>,
+    swap_chain: SwapChain,
+    device: Device,
+}
+
+impl Gpu {
+    fn new(
+        hwnd: HWND,
+        debug: bool,
+        (width, height): (u32, u32),
+        present: PresentOptions,
+        images: &[Option<(Image, TextureFilter)>],
+        shader: &str,
+    ) -> Result<Self, Error> {
+        let mut device = Device::new(debug)?;
+        let swap_chain = SwapChain::new(&device, hwnd, width, height, present);
+        // note: log why the swap chain failed, if the debug layer knows.
+        device.drain_messages();
+        let swap_chain = swap_chain?;
+
+        let vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+        let allocators = (0..FRAME_COUNT)
+            .map(|_| {
+                let mut allocator = std::ptr::null_mut();
+                let hr = unsafe {
+                    (vtbl.create_command_allocator)(
+                        device.device.as_raw(),
+                        D3D12_COMMAND_LIST_TYPE_DIRECT,
+                        &IID_ID3D12_COMMAND_ALLOCATOR,
+                        &mut allocator,
+                    )
+                };
+                unsafe { ComPtr::from_call(hr, allocator, "failed to create command allocator") }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut list = std::ptr::null_mut();
+        let hr = unsafe {
+            (vtbl.create_command_list)(
+                device.device.as_raw(),
+                0,
+                D3D12_COMMAND_LIST_TYPE_DIRECT,
+                allocators[0].as_raw(),
+                std::ptr::null_mut(),
+                &IID_ID3D12_GRAPHICS_COMMAND_LIST,
+                &mut list,
+            )
+        };
+        let list = unsafe { ComPtr::from_call(hr, list, "failed to create command list") }?;
+        // note: lists are created recording, and `begin` resets it.
+        let hr = unsafe { (list.vtbl::<ID3D12GraphicsCommandListVtbl>().close)(list.as_raw()) };
+        Hresult(hr).check("failed to close command list")?;
+
+        let mut sprites = SpritePipeline::new(&device, shader)?;
+        let textures = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                image
+                    .as_ref()
+                    .map(|(image, filter)| {
+                        sprites.create_texture(&mut device, index, image, *filter)
+                    })
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            list,
+            allocators,
+            fence_values: [0; FRAME_COUNT],
+            recording: None,
+            sprites,
+            textures,
+            swap_chain,
+            device,
+        })
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        self.device.wait_idle()?;
+        self.swap_chain.resize(&self.device, width, height)
+    }
+
+    fn configure(&mut self, options: PresentOptions) -> Result<(), Error> {
+        self.device.wait_idle()?;
+        self.swap_chain.configure(&self.device, options)
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.recording.is_some() {
+            return Ok(());
+        }
+
+        self.swap_chain.wait();
+        let index = self.swap_chain.current_index();
+        self.device.wait(self.fence_values[index])?;
+        self.sprites.begin(&self.device, index);
+
+        let allocator = &self.allocators[index];
+        let hr =
+            unsafe { (allocator.vtbl::<ID3D12CommandAllocatorVtbl>().reset)(allocator.as_raw()) };
+        Hresult(hr).check("failed to reset command allocator")?;
+        let list = self.list_vtbl();
+        let hr =
+            unsafe { (list.reset)(self.list.as_raw(), allocator.as_raw(), std::ptr::null_mut()) };
+        Hresult(hr).check("failed to reset command list")?;
+
+        // note: only missing if a resize failed, which was reported then.
+        if let Some((buffer, view)) = self.swap_chain.target(index) {
+            let barrier = transition(
+                buffer,
+                D3D12_RESOURCE_STATE_PRESENT,
+                D3D12_RESOURCE_STATE_RENDER_TARGET,
+            );
+            unsafe { (list.resource_barrier)(self.list.as_raw(), 1, &barrier) };
+            let (width, height) = self.swap_chain.size();
+            let viewport = Viewport {
+                top_left_x: 0.0,
+                top_left_y: 0.0,
+                width: width as f32,
+                height: height as f32,
+                min_depth: 0.0,
+                max_depth: 1.0,
+            };
+            let scissor = RECT {
+                left: 0,
+                top: 0,
+                right: width as i32,
+                bottom: height as i32,
+            };
+            unsafe {
+                (list.om_set_render_targets)(self.list.as_raw(), 1, &view, 0, std::ptr::null());
+                (list.rs_set_viewports)(self.list.as_raw(), 1, &viewport);
+                (list.rs_set_scissor_rects)(self.list.as_raw(), 1, &scissor);
+            };
+        }
+
+        self.recording = Some(index);
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        if let Some(index) = self.recording.take() {
+            let list = self.list_vtbl();
+            if let Some((buffer, _)) = self.swap_chain.target(index) {
+                let barrier = transition(
+                    buffer,
+                    D3D12_RESOURCE_STATE_RENDER_TARGET,
+                    D3D12_RESOURCE_STATE_PRESENT,
+                );
+                unsafe { (list.resource_barrier)(self.list.as_raw(), 1, &barrier) };
+            }
+            let hr = unsafe { (list.close)(self.list.as_raw()) };
+            Hresult(hr).check("failed to close command list")?;
+
+            let queue = &self.device.queue;
+            unsafe {
+                (queue.vtbl::<ID3D12CommandQueueVtbl>().execute_command_lists)(
+                    queue.as_raw(),
+                    1,
+                    &self.list.as_raw(),
+                )
+            };
+        }
+
+        let index = self.swap_chain.current_index();
+        let presented = self.swap_chain.present();
+        let signaled = self.device.signal();
+        presented?;
+        self.fence_values[index] = signaled?;
+        self.sprites.submitted(self.fence_values[index]);
+        Ok(())
+    }
+
+    fn list_vtbl(&self) -> &ID3D12GraphicsCommandListVtbl {
+        unsafe { self.list.vtbl() }
+    }
+}
+
+impl CommandList for Gpu {
+    fn clear(&mut self, color: [f32; 4]) {
+        let Some((_, view)) = self
+            .recording
+            .and_then(|index| self.swap_chain.target(index))
+        else {
+            return;
+        };
+
+        unsafe {
+            (self.list_vtbl().clear_render_target_view)(
+                self.list.as_raw(),
+                view,
+                &color,
+                0,
+                std::ptr::null(),
+            )
+        };
+    }
+
+    fn draw_quads(&mut self, texture: TextureId, transform: &[[f32; 4]; 4], vertices: &[Vertex]) {
+        if self.recording.is_none() {
+            return;
+        }
+        let index = texture.index();
+        let Some(Some(texture)) = self.textures.get(index) else {
+            return;
+        };
+        if let Err(err) = self.sprites.draw(
+            &self.device,
+            &self.list,
+            self.swap_chain.format(),
+            index,
+            texture,
+            transform,
+            vertices,
+        ) {
+            warn!("{}", err.full_message());
+        }
+    }
+}
+
+impl Drop for Gpu {
+    fn drop(&mut self) {
+        // note: the queue may still be using what's released with it.
+        _ = self.device.wait_idle();
+    }
+}
+
+fn transition(resource: &ComPtr, before: u32, after: u32) -> ResourceBarrier {
+    ResourceBarrier {
+        kind: D3D12_RESOURCE_BARRIER_TYPE_TRANSITION,
+        flags: 0,
+        resource: resource.as_raw(),
+        subresource: D3D12_RESOURCE_BARRIER_ALL_SUBRESOURCES,
+        state_before: before,
+        state_after: after,
+    }
+}
+
+fn is_device_lost(err: &Error) -> bool {
+    matches!(
+        err.downcast_source(),
+        Some(Hresult(DXGI_ERROR_DEVICE_REMOVED | DXGI_ERROR_DEVICE_RESET))
+    )
+}
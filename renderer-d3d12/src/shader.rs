@@ -0,0 +1,369 @@
+//! HLSL shaders, compiled to DXIL and cached on disk.
+//!
+//! In debug builds, [`ShaderCache::load`] compiles a shader with DXC if it, or anything it
+//! includes, has changed since it was cached. Release builds don't have the compiler and only
+//! load what's cached, so a game ships the cache directory its debug build filled. Wrapping a
+//! load in a [`Reloadable`](common::hot_reload::Reloadable) that depends on the source directory
+//! recompiles the shader whenever it's edited.
+//!
+//! ```no_run
+//! use renderer_d3d12::shader::{ShaderCache, ShaderStage};
+//!
+//! let shaders = ShaderCache::builder("assets/shaders.cache")
+//!     .source_dir("assets/shaders")
+//!     .define("MAX_LIGHTS", "16")
+//!     .build();
+//! let vertex_shader = shaders.load("sprite.hlsl", "vs_main", ShaderStage::Vertex)?;
+//! let pixel_shader = shaders.load("sprite.hlsl", "ps_main", ShaderStage::Pixel)?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+#[cfg(debug_assertions)]
+mod dxc;
+
+use std::{
+    fs,
+    hash::Hasher,
+    path::{Path, PathBuf},
+};
+
+use common::error::{Error, ErrorKind};
+#[cfg(debug_assertions)]
+use tracing::{debug, warn};
+
+const CACHE_MAGIC: &[u8; 4] = b"GSHD";
+const CACHE_VERSION: u32 = 1;
+const CACHE_EXTENSION: &str = "dxil";
+
+/// The pipeline stage a shader runs in, which picks its DXC target profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Pixel,
+    Compute,
+}
+
+impl ShaderStage {
+    /// The shader model 6.0 profile, e.g. `vs_6_0`.
+    pub fn profile(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "vs_6_0",
+            ShaderStage::Pixel => "ps_6_0",
+            ShaderStage::Compute => "cs_6_0",
+        }
+    }
+}
+
+pub struct ShaderCacheBuilder {
+    cache_dir: PathBuf,
+    source_dir: PathBuf,
+    defines: Vec<(String, String)>,
+    debug_info: bool,
+}
+
+impl ShaderCacheBuilder {
+    /// Where shaders and what they include are read from in debug builds. Defaults to
+    /// `shaders`.
+    pub fn source_dir<P: Into<PathBuf>>(self, source_dir: P) -> Self {
+        Self {
+            source_dir: source_dir.into(),
+            ..self
+        }
+    }
+
+    /// Defines a macro for every shader. Shaders compiled with different defines are cached
+    /// separately.
+    pub fn define<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.defines.push((name.into(), value.into()));
+        self
+    }
+
+    /// Whether compiled shaders embed debug info, for PIX. Defaults to on. Turn it off when
+    /// filling a cache to ship.
+    pub fn debug_info(self, debug_info: bool) -> Self {
+        Self { debug_info, ..self }
+    }
+
+    pub fn build(self) -> ShaderCache {
+        ShaderCache {
+            cache_dir: self.cache_dir,
+            source_dir: self.source_dir,
+            defines: self.defines,
+            debug_info: self.debug_info,
+            #[cfg(debug_assertions)]
+            compiler: std::cell::OnceCell::new(),
+        }
+    }
+}
+
+/// Loads shader bytecode from the cache, compiling it first in debug builds if it's stale.
+// note: release builds only read the cache, so they ignore what it's compiled from.
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+pub struct ShaderCache {
+    cache_dir: PathBuf,
+    source_dir: PathBuf,
+    defines: Vec<(String, String)>,
+    debug_info: bool,
+    /// `None` if DXC couldn't be loaded, in which case what's cached is used as is.
+    #[cfg(debug_assertions)]
+    compiler: std::cell::OnceCell<Option<dxc::Compiler>>,
+}
+
+impl ShaderCache {
+    pub fn builder<P: Into<PathBuf>>(cache_dir: P) -> ShaderCacheBuilder {
+        ShaderCacheBuilder {
+            cache_dir: cache_dir.into(),
+            source_dir: PathBuf::from("shaders"),
+            defines: Vec::new(),
+            debug_info: true,
+        }
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// The DXIL bytecode of `entry_point` in the shader at `path`, relative to the source
+    /// directory.
+    ///
+    /// Compile errors are logged along with DXC's other diagnostics, and returned. In release
+    /// builds, a shader that isn't cached is a [`NotFound`](ErrorKind::NotFound) error.
+    pub fn load(
+        &self,
+        path: &str,
+        entry_point: &str,
+        stage: ShaderStage,
+    ) -> Result<Vec<u8>, Error> {
+        let path = path.replace('\\', "/");
+        let mut key = Fnv1a::default();
+        for part in [path.as_str(), entry_point, stage.profile()] {
+            key.write(part.as_bytes());
+            key.write_u8(0);
+        }
+        for (name, value) in &self.defines {
+            key.write(format!("{name}={value}").as_bytes());
+            key.write_u8(0);
+        }
+        let cache_path = self
+            .cache_dir
+            .join(format!("{:016x}.{CACHE_EXTENSION}", key.finish()));
+
+        #[cfg(debug_assertions)]
+        if let Some(compiler) = self.compiler() {
+            return self.compile_if_stale(compiler, &cache_path, &path, entry_point, stage);
+        }
+
+        match CacheEntry::read(&cache_path)? {
+            Some(entry) => Ok(entry.bytecode),
+            None => Err(Error::new(format!(
+                "{path} ({entry_point}, {}) isn't in the shader cache {}",
+                stage.profile(),
+                self.cache_dir.display()
+            ))
+            .with_kind(ErrorKind::NotFound)),
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    fn compiler(&self) -> Option<&dxc::Compiler> {
+        self.compiler
+            .get_or_init(|| match dxc::Compiler::load() {
+                Ok(compiler) => Some(compiler),
+                Err(err) => {
+                    warn!("{}, using cached shaders as they are", err.full_message());
+                    None
+                }
+            })
+            .as_ref()
+    }
+
+    #[cfg(debug_assertions)]
+    fn compile_if_stale(
+        &self,
+        compiler: &dxc::Compiler,
+        cache_path: &Path,
+        path: &str,
+        entry_point: &str,
+        stage: ShaderStage,
+    ) -> Result<Vec<u8>, Error> {
+        let mut args = vec![
+            "-E".to_owned(),
+            entry_point.to_owned(),
+            "-T".to_owned(),
+            stage.profile().to_owned(),
+            "-HV".to_owned(),
+            "2021".to_owned(),
+        ];
+        for (name, value) in &self.defines {
+            args.push("-D".to_owned());
+            args.push(format!("{name}={value}"));
+        }
+        if self.debug_info {
+            args.extend(["-Zi".to_owned(), "-Qembed_debug".to_owned()]);
+        }
+        let mut options = Fnv1a::default();
+        for arg in &args {
+            options.write(arg.as_bytes());
+            options.write_u8(0);
+        }
+        let options = options.finish();
+
+        match CacheEntry::read(cache_path) {
+            Ok(Some(entry)) if entry.options == options && entry.is_current(&self.source_dir) => {
+                return Ok(entry.bytecode);
+            }
+            Ok(_) => {}
+            Err(err) => debug!("{}, recompiling", err.full_message()),
+        }
+
+        let compiled = compiler.compile(&self.source_dir, path, &args)?;
+        debug!(shader = path, entry_point, "compiled shader");
+        let entry = CacheEntry {
+            options,
+            inputs: compiled.inputs,
+            bytecode: compiled.bytecode,
+        };
+        // note: a shader that can't be cached still works, it's just compiled again next time.
+        if let Err(err) = entry.write(cache_path) {
+            warn!("{}", err.full_message());
+        }
+
+        Ok(entry.bytecode)
+    }
+}
+
+/// A cache file: the bytecode, and what it was compiled from so debug builds can tell when it's
+/// stale.
+#[cfg_attr(not(debug_assertions), allow(dead_code))]
+struct CacheEntry {
+    /// A hash of the compiler arguments.
+    options: u64,
+    /// Each file read to compile it, relative to the source directory, and a hash of its
+    /// contents.
+    inputs: Vec<(String, u64)>,
+    bytecode: Vec<u8>,
+}
+
+impl CacheEntry {
+    /// `None` if there's no entry at `path`.
+    fn read(path: &Path) -> Result<Option<Self>, Error> {
+        let data = match fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(
+                    Error::new(format!("failed to read cached shader {}", path.display()))
+                        .with_kind(ErrorKind::Io)
+                        .with_source(err),
+                )
+            }
+        };
+
+        Self::decode(&data).map(Some).ok_or_else(|| {
+            Error::new(format!("cached shader {} is malformed", path.display()))
+                .with_kind(ErrorKind::Parse)
+        })
+    }
+
+    fn decode(mut data: &[u8]) -> Option<Self> {
+        fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+            let (taken, rest) = data.split_at_checked(len)?;
+            *data = rest;
+            Some(taken)
+        }
+        fn take_u32(data: &mut &[u8]) -> Option<u32> {
+            Some(u32::from_le_bytes(take(data, 4)?.try_into().ok()?))
+        }
+        fn take_u64(data: &mut &[u8]) -> Option<u64> {
+            Some(u64::from_le_bytes(take(data, 8)?.try_into().ok()?))
+        }
+
+        if take(&mut data, 4)? != CACHE_MAGIC || take_u32(&mut data)? != CACHE_VERSION {
+            return None;
+        }
+        let options = take_u64(&mut data)?;
+        let input_count = take_u32(&mut data)?;
+        let inputs = (0..input_count)
+            .map(|_| {
+                let len = take_u32(&mut data)? as usize;
+                let path = String::from_utf8(take(&mut data, len)?.to_vec()).ok()?;
+                Some((path, take_u64(&mut data)?))
+            })
+            .collect::<Option<_>>()?;
+        let len = take_u32(&mut data)? as usize;
+        let bytecode = take(&mut data, len)?.to_vec();
+
+        Some(Self {
+            options,
+            inputs,
+            bytecode,
+        })
+    }
+
+    #[cfg(debug_assertions)]
+    fn is_current(&self, source_dir: &Path) -> bool {
+        self.inputs.iter().all(|(path, hash)| {
+            fs::read(source_dir.join(path)).is_ok_and(|contents| Fnv1a::hash(&contents) == *hash)
+        })
+    }
+
+    #[cfg(debug_assertions)]
+    fn write(&self, path: &Path) -> Result<(), Error> {
+        let mut data = Vec::with_capacity(self.bytecode.len() + 64);
+        data.extend_from_slice(CACHE_MAGIC);
+        data.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        data.extend_from_slice(&self.options.to_le_bytes());
+        data.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for (input, hash) in &self.inputs {
+            data.extend_from_slice(&(input.len() as u32).to_le_bytes());
+            data.extend_from_slice(input.as_bytes());
+            data.extend_from_slice(&hash.to_le_bytes());
+        }
+        data.extend_from_slice(&(self.bytecode.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.bytecode);
+
+        // note: written beside it and renamed over it, so a crash can't leave half an entry.
+        let temp = path.with_extension("tmp");
+        let result = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(&temp, data))
+            .and_then(|_| fs::rename(&temp, path));
+        result.map_err(|err| {
+            Error::new(format!("failed to cache shader {}", path.display()))
+                .with_kind(ErrorKind::Io)
+                .with_source(err)
+        })
+    }
+}
+
+/// 64-bit FNV-1a. Unlike `DefaultHasher` it's the same in every build, so a cache filled by one
+/// can be loaded by another.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    #[cfg(debug_assertions)]
+    fn hash(bytes: &[u8]) -> u64 {
+        let mut hasher = Self::default();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
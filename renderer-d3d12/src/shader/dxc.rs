@@ -0,0 +1,352 @@
+//! DXC, loaded from `dxcompiler.dll` at run time so games run without it, and an include
+//! handler that reads includes from the source directory and records them as inputs.
+
+use std::{
+    cell::RefCell,
+    ffi::c_void,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use common::error::{Error, ErrorKind};
+use tracing::{error, warn};
+use win32_base::{
+    com::{ComPtr, IUnknownVtbl},
+    error::Hresult,
+};
+use windows_sys::{
+    core::{GUID, HRESULT},
+    Win32::{
+        Foundation::{E_FAIL, E_NOINTERFACE, S_OK},
+        System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+    },
+};
+
+use super::Fnv1a;
+
+const CLSID_DXC_COMPILER: GUID = GUID::from_u128(0x73e22d93_e6ce_47f3_b5bf_f0664f39c1b0);
+const CLSID_DXC_UTILS: GUID = GUID::from_u128(0x6245d6af_66e0_48fd_80b4_4d271796748c);
+const IID_IDXC_BLOB: GUID = GUID::from_u128(0x8ba5fb08_5195_40e2_ac58_0d989c3a0102);
+const IID_IDXC_COMPILER_3: GUID = GUID::from_u128(0x228b4687_5a6a_4730_900c_9702b2203f54);
+const IID_IDXC_INCLUDE_HANDLER: GUID = GUID::from_u128(0x7f61fc7d_950d_467f_b3e3_3c02fb49187c);
+const IID_IDXC_RESULT: GUID = GUID::from_u128(0x58346cda_dde7_4497_9461_6f87af5e0659);
+const IID_IDXC_UTILS: GUID = GUID::from_u128(0x4605c4cb_2019_492a_ada4_65f20bb7d67f);
+const IID_IUNKNOWN: GUID = GUID::from_u128(0x00000000_0000_0000_c000_000000000046);
+
+const DXC_CP_UTF8: u32 = 65001;
+const DXC_OUT_OBJECT: u32 = 1;
+const DXC_OUT_ERRORS: u32 = 2;
+
+type DxcCreateInstanceFn = unsafe extern "system" fn(
+    clsid: *const GUID,
+    iid: *const GUID,
+    object: *mut *mut c_void,
+) -> HRESULT;
+
+#[repr(C)]
+struct DxcBuffer {
+    ptr: *const c_void,
+    size: usize,
+    encoding: u32,
+}
+
+#[repr(C)]
+struct IDxcBlobVtbl {
+    base: IUnknownVtbl,
+    get_buffer_pointer: unsafe extern "system" fn(this: *mut c_void) -> *const u8,
+    get_buffer_size: unsafe extern "system" fn(this: *mut c_void) -> usize,
+}
+
+#[repr(C)]
+struct IDxcUtilsVtbl {
+    base: IUnknownVtbl,
+    _create_blob_from_blob: usize,
+    _create_blob_from_pinned: usize,
+    _move_to_blob: usize,
+    create_blob: unsafe extern "system" fn(
+        this: *mut c_void,
+        data: *const c_void,
+        size: u32,
+        code_page: u32,
+        blob: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDxcCompiler3Vtbl {
+    base: IUnknownVtbl,
+    compile: unsafe extern "system" fn(
+        this: *mut c_void,
+        source: *const DxcBuffer,
+        args: *const *const u16,
+        arg_count: u32,
+        include_handler: *mut c_void,
+        iid: *const GUID,
+        result: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDxcResultVtbl {
+    base: IUnknownVtbl,
+    get_status: unsafe extern "system" fn(this: *mut c_void, status: *mut HRESULT) -> HRESULT,
+    _get_result: usize,
+    _get_error_buffer: usize,
+    _has_output: usize,
+    get_output: unsafe extern "system" fn(
+        this: *mut c_void,
+        kind: u32,
+        iid: *const GUID,
+        object: *mut *mut c_void,
+        name: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDxcIncludeHandlerVtbl {
+    base: IUnknownVtbl,
+    load_source: unsafe extern "system" fn(
+        this: *mut c_void,
+        file_name: *const u16,
+        source: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+pub(super) struct Compiled {
+    pub(super) bytecode: Vec<u8>,
+    /// The shader and everything it included, relative to the source directory, with a hash of
+    /// each one's contents.
+    pub(super) inputs: Vec<(String, u64)>,
+}
+
+pub(super) struct Compiler {
+    compiler: ComPtr,
+    utils: ComPtr,
+}
+
+impl Compiler {
+    pub(super) fn load() -> Result<Self, Error> {
+        let name: Vec<u16> = "dxcompiler.dll\0".encode_utf16().collect();
+        let module = unsafe { LoadLibraryW(name.as_ptr()) };
+        let create_instance = (module != 0)
+            .then(|| unsafe { GetProcAddress(module, c"DxcCreateInstance".as_ptr().cast()) })
+            .flatten()
+            .ok_or_else(|| {
+                Error::new("can't compile shaders without dxcompiler.dll")
+                    .with_kind(ErrorKind::NotFound)
+            })?;
+        let create_instance = unsafe {
+            std::mem::transmute::<unsafe extern "system" fn() -> isize, DxcCreateInstanceFn>(
+                create_instance,
+            )
+        };
+
+        let create = |clsid, iid, context| {
+            let mut object = std::ptr::null_mut();
+            let hr = unsafe { create_instance(clsid, iid, &mut object) };
+            unsafe { ComPtr::from_call(hr, object, context) }
+        };
+        Ok(Self {
+            compiler: create(
+                &CLSID_DXC_COMPILER,
+                &IID_IDXC_COMPILER_3,
+                "failed to create the DXC compiler",
+            )?,
+            utils: create(
+                &CLSID_DXC_UTILS,
+                &IID_IDXC_UTILS,
+                "failed to create the DXC utils",
+            )?,
+        })
+    }
+
+    /// Compiles the shader at `path`, relative to `source_dir`. Diagnostics are logged, as
+    /// errors if it failed and warnings if it didn't.
+    pub(super) fn compile(
+        &self,
+        source_dir: &Path,
+        path: &str,
+        args: &[String],
+    ) -> Result<Compiled, Error> {
+        let source = fs::read(source_dir.join(path)).map_err(|err| {
+            Error::new(format!("failed to read shader {path}"))
+                .with_kind(ErrorKind::Io)
+                .with_source(err)
+        })?;
+        let buffer = DxcBuffer {
+            ptr: source.as_ptr().cast(),
+            size: source.len(),
+            encoding: DXC_CP_UTF8,
+        };
+        // note: the first argument names the source in diagnostics.
+        let args: Vec<Vec<u16>> = std::iter::once(path)
+            .chain(args.iter().map(String::as_str))
+            .map(|arg| arg.encode_utf16().chain([0]).collect())
+            .collect();
+        let arg_ptrs: Vec<*const u16> = args.iter().map(|arg| arg.as_ptr()).collect();
+
+        let handler = IncludeHandler {
+            vtbl: &INCLUDE_HANDLER_VTBL,
+            utils: &self.utils,
+            source_dir,
+            inputs: RefCell::new(vec![(path.to_owned(), Fnv1a::hash(&source))]),
+        };
+        let mut result = std::ptr::null_mut();
+        let hr = unsafe {
+            (self.compiler.vtbl::<IDxcCompiler3Vtbl>().compile)(
+                self.compiler.as_raw(),
+                &buffer,
+                arg_ptrs.as_ptr(),
+                arg_ptrs.len() as u32,
+                &handler as *const IncludeHandler as *mut c_void,
+                &IID_IDXC_RESULT,
+                &mut result,
+            )
+        };
+        let result = unsafe { ComPtr::from_call(hr, result, "failed to run DXC") }?;
+
+        let diagnostics = output(&result, DXC_OUT_ERRORS)
+            .map(|blob| {
+                String::from_utf8_lossy(&blob)
+                    .trim_end_matches(['\0', '\n'])
+                    .to_owned()
+            })
+            .unwrap_or_default();
+        let mut status = S_OK;
+        let hr =
+            unsafe { (result.vtbl::<IDxcResultVtbl>().get_status)(result.as_raw(), &mut status) };
+        Hresult(hr).check("failed to get DXC's status")?;
+        if status < 0 {
+            error!(shader = path, "{diagnostics}");
+            return Err(Error::new(format!("failed to compile shader {path}"))
+                .with_kind(ErrorKind::Graphics)
+                .with_source(Hresult(status)));
+        }
+        if !diagnostics.is_empty() {
+            warn!(shader = path, "{diagnostics}");
+        }
+
+        let bytecode = output(&result, DXC_OUT_OBJECT).ok_or_else(|| {
+            Error::new(format!("DXC compiled shader {path} to nothing"))
+                .with_kind(ErrorKind::Graphics)
+        })?;
+        Ok(Compiled {
+            bytecode,
+            inputs: handler.inputs.into_inner(),
+        })
+    }
+}
+
+/// The contents of one of the result's outputs, or `None` if it doesn't have it.
+fn output(result: &ComPtr, kind: u32) -> Option<Vec<u8>> {
+    let mut blob = std::ptr::null_mut();
+    let hr = unsafe {
+        (result.vtbl::<IDxcResultVtbl>().get_output)(
+            result.as_raw(),
+            kind,
+            &IID_IDXC_BLOB,
+            &mut blob,
+            std::ptr::null_mut(),
+        )
+    };
+    let blob = unsafe { ComPtr::from_call(hr, blob, "failed to get DXC output") }.ok()?;
+    let vtbl = unsafe { blob.vtbl::<IDxcBlobVtbl>() };
+    let (data, len) = unsafe {
+        (
+            (vtbl.get_buffer_pointer)(blob.as_raw()),
+            (vtbl.get_buffer_size)(blob.as_raw()),
+        )
+    };
+    (len > 0).then(|| unsafe { std::slice::from_raw_parts(data, len) }.to_vec())
+}
+
+/// A COM object DXC asks for each `#include`. It lives on the stack for one compile, so its
+/// reference count is ignored.
+#[repr(C)]
+struct IncludeHandler<'a> {
+    vtbl: &'static IDxcIncludeHandlerVtbl,
+    utils: &'a ComPtr,
+    source_dir: &'a Path,
+    inputs: RefCell<Vec<(String, u64)>>,
+}
+
+static INCLUDE_HANDLER_VTBL: IDxcIncludeHandlerVtbl = IDxcIncludeHandlerVtbl {
+    base: IUnknownVtbl {
+        query_interface: IncludeHandler::query_interface,
+        add_ref: IncludeHandler::add_ref,
+        release: IncludeHandler::add_ref,
+    },
+    load_source: IncludeHandler::load_source,
+};
+
+impl IncludeHandler<'_> {
+    unsafe extern "system" fn query_interface(
+        this: *mut c_void,
+        iid: *const GUID,
+        object: *mut *mut c_void,
+    ) -> HRESULT {
+        let iid = &*iid;
+        if same_guid(iid, &IID_IUNKNOWN) || same_guid(iid, &IID_IDXC_INCLUDE_HANDLER) {
+            *object = this;
+            S_OK
+        } else {
+            *object = std::ptr::null_mut();
+            E_NOINTERFACE
+        }
+    }
+
+    unsafe extern "system" fn add_ref(_this: *mut c_void) -> u32 {
+        1
+    }
+
+    unsafe extern "system" fn load_source(
+        this: *mut c_void,
+        file_name: *const u16,
+        source: *mut *mut c_void,
+    ) -> HRESULT {
+        let this = &*(this as *const IncludeHandler);
+        let len = (0..).take_while(|&i| *file_name.add(i) != 0).count();
+        let file_name = String::from_utf16_lossy(std::slice::from_raw_parts(file_name, len));
+        *source = std::ptr::null_mut();
+
+        // note: DXC resolves includes against the including file's directory, so names arrive
+        // relative to the source directory, e.g. `./lib/lighting.hlsli`.
+        let path = normalize(&file_name);
+        let Ok(contents) = fs::read(this.source_dir.join(&path)) else {
+            // note: DXC reports the include it couldn't open.
+            return E_FAIL;
+        };
+        let path = path.to_string_lossy().replace('\\', "/");
+        let mut inputs = this.inputs.borrow_mut();
+        if !inputs.iter().any(|(input, _)| *input == path) {
+            inputs.push((path, Fnv1a::hash(&contents)));
+        }
+
+        (this.utils.vtbl::<IDxcUtilsVtbl>().create_blob)(
+            this.utils.as_raw(),
+            contents.as_ptr().cast(),
+            contents.len() as u32,
+            DXC_CP_UTF8,
+            source,
+        )
+    }
+}
+
+/// `path` without `.` components, and with `..` applied, so each file has one name.
+fn normalize(path: &str) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn same_guid(a: &GUID, b: &GUID) -> bool {
+    (a.data1, a.data2, a.data3, a.data4) == (b.data1, b.data2, b.data3, b.data4)
+}
@@ -0,0 +1,41 @@
+// Textured, tinted quads for `CommandList::draw_quads`. The camera is passed as root constants,
+// and the samplers are static, so a draw only binds its texture.
+
+cbuffer Camera : register(b0) {
+    float4x4 transform;
+    uint nearest;
+};
+
+Texture2D sprite_texture : register(t0);
+SamplerState linear_sampler : register(s0);
+SamplerState nearest_sampler : register(s1);
+
+struct VertexInput {
+    float2 position : POSITION;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+struct PixelInput {
+    float4 position : SV_Position;
+    float2 uv : TEXCOORD;
+    float4 color : COLOR;
+};
+
+PixelInput vs_main(VertexInput input) {
+    PixelInput output;
+    output.position = mul(transform, float4(input.position, 0.0, 1.0));
+    output.uv = input.uv;
+    output.color = input.color;
+    return output;
+}
+
+float4 ps_main(PixelInput input) : SV_Target {
+    float4 texel;
+    if (nearest != 0) {
+        texel = sprite_texture.Sample(nearest_sampler, input.uv);
+    } else {
+        texel = sprite_texture.Sample(linear_sampler, input.uv);
+    }
+    return texel * input.color;
+}
@@ -0,0 +1,818 @@
+//! The pipeline [`CommandList::draw_quads`](common::graphics::CommandList::draw_quads) draws
+//! with, and the textures it samples.
+//!
+//! Textures are uploaded through a command list of their own, which is waited on, and their views
+//! live in one shader-visible heap at their texture's index. Each frame's vertices and indices
+//! are written to an upload buffer per back buffer, which is reused once its frame is done.
+//! Resources that might still be in use when they're dropped are retired until the frame that
+//! last used them is.
+
+use std::ffi::c_void;
+
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{Image, TextureFilter, Vertex},
+};
+use win32_base::{com::ComPtr, error::Hresult, fxc};
+use windows_sys::core::HRESULT;
+
+use crate::{
+    device::Device,
+    ffi::{
+        BlendDesc, CpuDescriptorHandle, D3D12SerializeRootSignature, DepthStencilDesc,
+        DepthStencilOpDesc, DescriptorHeapDesc, DescriptorRange, GpuDescriptorHandle,
+        GraphicsPipelineStateDesc, HeapProperties, ID3D12CommandAllocatorVtbl,
+        ID3D12CommandQueueVtbl, ID3D12DescriptorHeapVtbl, ID3D12DeviceVtbl,
+        ID3D12GraphicsCommandListVtbl, ID3D12ResourceVtbl, IndexBufferView, InputElementDesc,
+        InputLayoutDesc, PlacedSubresourceFootprint, Range, RasterizerDesc, RenderTargetBlendDesc,
+        ResourceDesc, RootConstants, RootDescriptorTable, RootParameter, RootParameterPayload,
+        RootSignatureDesc, SampleDesc, ShaderBytecode, StaticSamplerDesc, SubresourceFootprint,
+        TextureCopyLocation, VertexBufferView, D3D12_BLEND_INV_SRC_ALPHA, D3D12_BLEND_ONE,
+        D3D12_BLEND_OP_ADD, D3D12_BLEND_SRC_ALPHA, D3D12_COLOR_WRITE_ENABLE_ALL,
+        D3D12_COMMAND_LIST_TYPE_DIRECT, D3D12_COMPARISON_FUNC_ALWAYS, D3D12_COMPARISON_FUNC_NEVER,
+        D3D12_CULL_MODE_NONE, D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+        D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV, D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        D3D12_FILL_MODE_SOLID, D3D12_FILTER_MIN_MAG_MIP_LINEAR, D3D12_FILTER_MIN_MAG_MIP_POINT,
+        D3D12_HEAP_TYPE_DEFAULT, D3D12_HEAP_TYPE_UPLOAD,
+        D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA, D3D12_LOGIC_OP_NOOP,
+        D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE, D3D12_RESOURCE_DIMENSION_BUFFER,
+        D3D12_RESOURCE_DIMENSION_TEXTURE2D, D3D12_RESOURCE_STATE_COPY_DEST,
+        D3D12_RESOURCE_STATE_GENERIC_READ, D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+        D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS, D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+        D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT, D3D12_SHADER_VISIBILITY_ALL,
+        D3D12_SHADER_VISIBILITY_PIXEL, D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
+        D3D12_STENCIL_OP_KEEP, D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT, D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+        D3D12_TEXTURE_DATA_PITCH_ALIGNMENT, D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        D3D12_TEXTURE_LAYOUT_UNKNOWN, D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+        D3D_ROOT_SIGNATURE_VERSION_1, DXGI_FORMAT_R32G32B32A32_FLOAT, DXGI_FORMAT_R32G32_FLOAT,
+        DXGI_FORMAT_R32_UINT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_UNKNOWN,
+        IID_ID3D12_COMMAND_ALLOCATOR, IID_ID3D12_DESCRIPTOR_HEAP, IID_ID3D12_GRAPHICS_COMMAND_LIST,
+        IID_ID3D12_PIPELINE_STATE, IID_ID3D12_RESOURCE, IID_ID3D12_ROOT_SIGNATURE,
+    },
+    transition, FRAME_COUNT,
+};
+
+pub(crate) const SHADER: &str = include_str!("sprite.hlsl");
+
+/// The fewest quads a frame's buffer is made for, so small scenes don't regrow it.
+const MIN_QUADS: usize = 1024;
+
+/// The most textures there can be at once, which is the size of the heap their views are in.
+pub(crate) const MAX_TEXTURES: usize = 4096;
+
+/// The camera's transform, then whether to sample the nearest texel.
+const ROOT_CONSTANTS: usize = 17;
+
+pub(crate) struct Texture {
+    resource: ComPtr,
+    filter: TextureFilter,
+}
+
+impl Texture {
+    /// Gives up the texture, to be retired.
+    pub(crate) fn into_resource(self) -> ComPtr {
+        self.resource
+    }
+}
+
+/// A frame's upload buffer, mapped for as long as it lives.
+struct FrameBuffer {
+    resource: ComPtr,
+    data: *mut u8,
+    len: usize,
+    address: u64,
+}
+
+pub(crate) struct SpritePipeline {
+    root_signature: ComPtr,
+    vertex_code: Vec<u8>,
+    pixel_code: Vec<u8>,
+    /// A pipeline state for each render target format drawn to so far.
+    pipelines: Vec<(u32, ComPtr)>,
+    /// Holds a view of each texture, at its index.
+    heap: ComPtr,
+    heap_start: CpuDescriptorHandle,
+    heap_gpu_start: GpuDescriptorHandle,
+    descriptor_size: usize,
+    upload_allocator: ComPtr,
+    upload_list: ComPtr,
+    frames: [Option<FrameBuffer>; FRAME_COUNT],
+    /// The back buffer being drawn to, and how much of its frame buffer is used.
+    frame: usize,
+    cursor: usize,
+    /// Resources dropped since the last submission, which it may use.
+    retiring: Vec<ComPtr>,
+    /// Resources waiting for the fence value the submission that last used them signals.
+    retired: Vec<(u64, ComPtr)>,
+}
+
+impl SpritePipeline {
+    /// Compiles `shader`, the source of a sprite shader like [`SHADER`].
+    pub(crate) fn new(device: &Device, shader: &str) -> Result<Self, Error> {
+        let (vertex_code, pixel_code) = compile(shader)?;
+        let root_signature = create_root_signature(device)?;
+
+        let vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+        let raw = device.device.as_raw();
+        let desc = DescriptorHeapDesc {
+            kind: D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV,
+            num_descriptors: MAX_TEXTURES as u32,
+            flags: D3D12_DESCRIPTOR_HEAP_FLAG_SHADER_VISIBLE,
+            node_mask: 0,
+        };
+        let heap = create("failed to create the texture heap", |heap| unsafe {
+            (vtbl.create_descriptor_heap)(raw, &desc, &IID_ID3D12_DESCRIPTOR_HEAP, heap)
+        })?;
+        let heap_vtbl = unsafe { heap.vtbl::<ID3D12DescriptorHeapVtbl>() };
+        let mut heap_start = CpuDescriptorHandle::default();
+        let mut heap_gpu_start = GpuDescriptorHandle::default();
+        unsafe {
+            (heap_vtbl.get_cpu_descriptor_handle_for_heap_start)(heap.as_raw(), &mut heap_start);
+            (heap_vtbl.get_gpu_descriptor_handle_for_heap_start)(
+                heap.as_raw(),
+                &mut heap_gpu_start,
+            );
+        }
+        let descriptor_size = unsafe {
+            (vtbl.get_descriptor_handle_increment_size)(raw, D3D12_DESCRIPTOR_HEAP_TYPE_CBV_SRV_UAV)
+        };
+
+        let upload_allocator = create(
+            "failed to create the upload allocator",
+            |allocator| unsafe {
+                (vtbl.create_command_allocator)(
+                    raw,
+                    D3D12_COMMAND_LIST_TYPE_DIRECT,
+                    &IID_ID3D12_COMMAND_ALLOCATOR,
+                    allocator,
+                )
+            },
+        )?;
+        let upload_list = create("failed to create the upload command list", |list| unsafe {
+            (vtbl.create_command_list)(
+                raw,
+                0,
+                D3D12_COMMAND_LIST_TYPE_DIRECT,
+                upload_allocator.as_raw(),
+                std::ptr::null_mut(),
+                &IID_ID3D12_GRAPHICS_COMMAND_LIST,
+                list,
+            )
+        })?;
+        // note: lists are created recording, and each upload resets it.
+        let hr = unsafe {
+            (upload_list.vtbl::<ID3D12GraphicsCommandListVtbl>().close)(upload_list.as_raw())
+        };
+        Hresult(hr).check("failed to close the upload command list")?;
+
+        Ok(Self {
+            root_signature,
+            vertex_code,
+            pixel_code,
+            pipelines: Vec::new(),
+            heap,
+            heap_start,
+            heap_gpu_start,
+            descriptor_size: descriptor_size as usize,
+            upload_allocator,
+            upload_list,
+            frames: [const { None }; FRAME_COUNT],
+            frame: 0,
+            cursor: 0,
+            retiring: Vec::new(),
+            retired: Vec::new(),
+        })
+    }
+
+    /// Compiles `shader` and recreates the pipeline states with it, keeping the current ones if
+    /// either fails.
+    pub(crate) fn set_shader(&mut self, device: &Device, shader: &str) -> Result<(), Error> {
+        let (vertex_code, pixel_code) = compile(shader)?;
+        let pipelines = self
+            .pipelines
+            .iter()
+            .map(|&(format, _)| {
+                let pipeline = self.create_pipeline(device, format, &vertex_code, &pixel_code)?;
+                Ok((format, pipeline))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        for (_, pipeline) in std::mem::replace(&mut self.pipelines, pipelines) {
+            self.retire(pipeline);
+        }
+        self.vertex_code = vertex_code;
+        self.pixel_code = pixel_code;
+        Ok(())
+    }
+
+    /// Uploads `image` and puts its view at `index` in the heap, which must be less than
+    /// [`MAX_TEXTURES`], waiting until it's copied.
+    pub(crate) fn create_texture(
+        &mut self,
+        device: &mut Device,
+        index: usize,
+        image: &Image,
+        filter: TextureFilter,
+    ) -> Result<Texture, Error> {
+        let (width, height) = (image.width(), image.height());
+        let context = format!("failed to create a {width}x{height} texture");
+        let desc = ResourceDesc {
+            dimension: D3D12_RESOURCE_DIMENSION_TEXTURE2D,
+            alignment: 0,
+            width: u64::from(width),
+            height,
+            depth_or_array_size: 1,
+            mip_levels: 1,
+            format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            sample_desc: SampleDesc {
+                count: 1,
+                quality: 0,
+            },
+            layout: D3D12_TEXTURE_LAYOUT_UNKNOWN,
+            flags: 0,
+        };
+        let resource = create_resource(
+            device,
+            D3D12_HEAP_TYPE_DEFAULT,
+            &desc,
+            D3D12_RESOURCE_STATE_COPY_DEST,
+            &context,
+        )?;
+
+        self.upload(device, &resource, [0, 0], image, false)?;
+
+        let view = CpuDescriptorHandle {
+            ptr: self.heap_start.ptr + index * self.descriptor_size,
+        };
+        unsafe {
+            (device
+                .device
+                .vtbl::<ID3D12DeviceVtbl>()
+                .create_shader_resource_view)(
+                device.device.as_raw(),
+                resource.as_raw(),
+                std::ptr::null(),
+                view,
+            )
+        };
+
+        Ok(Texture { resource, filter })
+    }
+
+    /// Replaces the texels of `texture` at `origin` with `image`, which must fit, waiting until
+    /// it's copied.
+    pub(crate) fn update_texture(
+        &mut self,
+        device: &mut Device,
+        texture: &Texture,
+        origin: [u32; 2],
+        image: &Image,
+    ) -> Result<(), Error> {
+        self.upload(device, &texture.resource, origin, image, true)
+    }
+
+    /// Starts drawing to back buffer `frame`, once the GPU is done with its last frame, and
+    /// releases the retired resources it's done with.
+    pub(crate) fn begin(&mut self, device: &Device, frame: usize) {
+        self.retired
+            .retain(|(value, _)| !device.is_complete(*value));
+        self.frame = frame;
+        self.cursor = 0;
+    }
+
+    /// Keeps `resource` alive until the GPU is done with the work submitted next.
+    pub(crate) fn retire(&mut self, resource: ComPtr) {
+        self.retiring.push(resource);
+    }
+
+    /// Called with the fence value a submission signals, which the resources retired before it
+    /// wait for.
+    pub(crate) fn submitted(&mut self, fence_value: u64) {
+        self.retired.extend(
+            self.retiring
+                .drain(..)
+                .map(|resource| (fence_value, resource)),
+        );
+    }
+
+    /// Copies `image` into `resource` at `origin`, leaving it ready to sample. It's in the copy
+    /// destination state unless it's `sampled` already.
+    fn upload(
+        &mut self,
+        device: &mut Device,
+        resource: &ComPtr,
+        origin: [u32; 2],
+        image: &Image,
+        sampled: bool,
+    ) -> Result<(), Error> {
+        let (width, height) = (image.width(), image.height());
+        let context = format!("failed to upload a {width}x{height} image");
+        // note: copies from buffers need each row aligned.
+        let row_len = width as usize * 4;
+        let row_pitch = row_len.next_multiple_of(D3D12_TEXTURE_DATA_PITCH_ALIGNMENT as usize);
+        let staging = create_buffer(device, row_pitch * height as usize, &context)?;
+        let data = map(&staging, &context)?;
+        for (row, pixels) in image.pixels().chunks_exact(row_len).enumerate() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(pixels.as_ptr(), data.add(row * row_pitch), row_len)
+            };
+        }
+        unsafe {
+            (staging.vtbl::<ID3D12ResourceVtbl>().unmap)(staging.as_raw(), 0, std::ptr::null())
+        };
+
+        let allocator = &self.upload_allocator;
+        let hr =
+            unsafe { (allocator.vtbl::<ID3D12CommandAllocatorVtbl>().reset)(allocator.as_raw()) };
+        Hresult(hr).check("failed to reset the upload allocator")?;
+        let list = unsafe { self.upload_list.vtbl::<ID3D12GraphicsCommandListVtbl>() };
+        let raw = self.upload_list.as_raw();
+        let hr = unsafe { (list.reset)(raw, allocator.as_raw(), std::ptr::null_mut()) };
+        Hresult(hr).check("failed to reset the upload command list")?;
+
+        let destination = TextureCopyLocation {
+            resource: resource.as_raw(),
+            kind: D3D12_TEXTURE_COPY_TYPE_SUBRESOURCE_INDEX,
+            // note: the offset is where the subresource index goes, and it's the first.
+            placed_footprint: PlacedSubresourceFootprint {
+                offset: 0,
+                footprint: SubresourceFootprint {
+                    format: DXGI_FORMAT_UNKNOWN,
+                    width: 0,
+                    height: 0,
+                    depth: 0,
+                    row_pitch: 0,
+                },
+            },
+        };
+        let source = TextureCopyLocation {
+            resource: staging.as_raw(),
+            kind: D3D12_TEXTURE_COPY_TYPE_PLACED_FOOTPRINT,
+            placed_footprint: PlacedSubresourceFootprint {
+                offset: 0,
+                footprint: SubresourceFootprint {
+                    format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                    width,
+                    height,
+                    depth: 1,
+                    row_pitch: row_pitch as u32,
+                },
+            },
+        };
+        let [x, y] = origin;
+        unsafe {
+            if sampled {
+                let barrier = transition(
+                    resource,
+                    D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+                    D3D12_RESOURCE_STATE_COPY_DEST,
+                );
+                (list.resource_barrier)(raw, 1, &barrier);
+            }
+            (list.copy_texture_region)(raw, &destination, x, y, 0, &source, std::ptr::null());
+            let barrier = transition(
+                resource,
+                D3D12_RESOURCE_STATE_COPY_DEST,
+                D3D12_RESOURCE_STATE_PIXEL_SHADER_RESOURCE,
+            );
+            (list.resource_barrier)(raw, 1, &barrier);
+        }
+        let hr = unsafe { (list.close)(raw) };
+        Hresult(hr).check("failed to close the upload command list")?;
+
+        let queue = &device.queue;
+        unsafe {
+            (queue.vtbl::<ID3D12CommandQueueVtbl>().execute_command_lists)(queue.as_raw(), 1, &raw)
+        };
+        // note: also keeps the view from changing under a frame that's still drawing the
+        // texture that was at this index, and the texels under one still sampling them.
+        device.wait_idle()
+    }
+
+    /// Records drawing the whole quads in `vertices` to the bound render target, which is in
+    /// `format`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &Device,
+        list: &ComPtr,
+        format: u32,
+        index: usize,
+        texture: &Texture,
+        transform: &[[f32; 4]; 4],
+        vertices: &[Vertex],
+    ) -> Result<(), Error> {
+        let quads = vertices.len() / 4;
+        if quads == 0 {
+            return Ok(());
+        }
+
+        let vertices = &vertices[..quads * 4];
+        let indices: Vec<u32> = (0..quads as u32 * 4)
+            .step_by(4)
+            .flat_map(|first| [first, first + 1, first + 2, first, first + 2, first + 3])
+            .collect();
+        let vertices_len = size_of_val(vertices);
+        let indices_len = size_of_val(indices.as_slice());
+        let (data, vertex_address) = self.reserve(device, vertices_len + indices_len)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(vertices.as_ptr().cast(), data, vertices_len);
+            std::ptr::copy_nonoverlapping(
+                indices.as_ptr().cast(),
+                data.add(vertices_len),
+                indices_len,
+            );
+        }
+
+        let pipeline = self.pipeline(device, format)?.as_raw();
+        let mut constants = [0u32; ROOT_CONSTANTS];
+        for (constant, value) in constants.iter_mut().zip(transform.as_flattened()) {
+            *constant = value.to_bits();
+        }
+        constants[16] = u32::from(texture.filter == TextureFilter::Nearest);
+        let table = GpuDescriptorHandle {
+            ptr: self.heap_gpu_start.ptr + (index * self.descriptor_size) as u64,
+        };
+        let vertex_buffer = VertexBufferView {
+            buffer_location: vertex_address,
+            size_in_bytes: vertices_len as u32,
+            stride_in_bytes: size_of::<Vertex>() as u32,
+        };
+        let index_buffer = IndexBufferView {
+            buffer_location: vertex_address + vertices_len as u64,
+            size_in_bytes: indices_len as u32,
+            format: DXGI_FORMAT_R32_UINT,
+        };
+
+        let vtbl = unsafe { list.vtbl::<ID3D12GraphicsCommandListVtbl>() };
+        let raw = list.as_raw();
+        unsafe {
+            (vtbl.set_graphics_root_signature)(raw, self.root_signature.as_raw());
+            (vtbl.set_pipeline_state)(raw, pipeline);
+            (vtbl.set_descriptor_heaps)(raw, 1, &self.heap.as_raw());
+            (vtbl.set_graphics_root_32bit_constants)(
+                raw,
+                0,
+                ROOT_CONSTANTS as u32,
+                constants.as_ptr().cast(),
+                0,
+            );
+            (vtbl.set_graphics_root_descriptor_table)(raw, 1, table);
+            (vtbl.ia_set_primitive_topology)(raw, D3D_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            (vtbl.ia_set_vertex_buffers)(raw, 0, 1, &vertex_buffer);
+            (vtbl.ia_set_index_buffer)(raw, &index_buffer);
+            (vtbl.draw_indexed_instanced)(raw, quads as u32 * 6, 1, 0, 0, 0);
+        }
+
+        Ok(())
+    }
+
+    /// Takes `len` bytes of the current frame's buffer, returning where to write them and their
+    /// GPU address. A buffer that's too small is retired and replaced by a bigger one.
+    fn reserve(&mut self, device: &Device, len: usize) -> Result<(*mut u8, u64), Error> {
+        let fits = self.frames[self.frame]
+            .as_ref()
+            .is_some_and(|buffer| self.cursor + len <= buffer.len);
+        if !fits {
+            let min_len = MIN_QUADS * (4 * size_of::<Vertex>() + 6 * size_of::<u32>());
+            let len = (self.cursor + len).next_power_of_two().max(min_len);
+            let context = format!("failed to create a {len} byte sprite buffer");
+            let resource = create_buffer(device, len, &context)?;
+            let data = map(&resource, &context)?;
+            let address = unsafe {
+                (resource
+                    .vtbl::<ID3D12ResourceVtbl>()
+                    .get_gpu_virtual_address)(resource.as_raw())
+            };
+            let buffer = FrameBuffer {
+                resource,
+                data,
+                len,
+                address,
+            };
+            if let Some(old) = self.frames[self.frame].replace(buffer) {
+                self.retire(old.resource);
+            }
+            self.cursor = 0;
+        }
+
+        let Some(buffer) = &self.frames[self.frame] else {
+            unreachable!("the frame's buffer was just created");
+        };
+        let offset = self.cursor;
+        self.cursor += len;
+        Ok((
+            unsafe { buffer.data.add(offset) },
+            buffer.address + offset as u64,
+        ))
+    }
+
+    /// The pipeline state for drawing to render targets in `format`, created the first time.
+    fn pipeline(&mut self, device: &Device, format: u32) -> Result<&ComPtr, Error> {
+        if let Some(index) = self.pipelines.iter().position(|(f, _)| *f == format) {
+            return Ok(&self.pipelines[index].1);
+        }
+
+        let pipeline = self.create_pipeline(device, format, &self.vertex_code, &self.pixel_code)?;
+        self.pipelines.push((format, pipeline));
+        Ok(&self.pipelines[self.pipelines.len() - 1].1)
+    }
+
+    /// A pipeline state for drawing to render targets in `format` with the compiled shaders.
+    fn create_pipeline(
+        &self,
+        device: &Device,
+        format: u32,
+        vertex_code: &[u8],
+        pixel_code: &[u8],
+    ) -> Result<ComPtr, Error> {
+        let elements = [
+            (c"POSITION", DXGI_FORMAT_R32G32_FLOAT, 0),
+            (c"TEXCOORD", DXGI_FORMAT_R32G32_FLOAT, 8),
+            (c"COLOR", DXGI_FORMAT_R32G32B32A32_FLOAT, 16),
+        ]
+        .map(|(name, format, offset)| InputElementDesc {
+            semantic_name: name.as_ptr().cast(),
+            semantic_index: 0,
+            format,
+            input_slot: 0,
+            aligned_byte_offset: offset,
+            input_slot_class: D3D12_INPUT_CLASSIFICATION_PER_VERTEX_DATA,
+            instance_data_step_rate: 0,
+        });
+
+        let mut blend = BlendDesc {
+            alpha_to_coverage_enable: 0,
+            independent_blend_enable: 0,
+            render_target: [RenderTargetBlendDesc::default(); 8],
+        };
+        blend.render_target[0] = RenderTargetBlendDesc {
+            blend_enable: 1,
+            logic_op_enable: 0,
+            src_blend: D3D12_BLEND_SRC_ALPHA,
+            dest_blend: D3D12_BLEND_INV_SRC_ALPHA,
+            blend_op: D3D12_BLEND_OP_ADD,
+            src_blend_alpha: D3D12_BLEND_ONE,
+            dest_blend_alpha: D3D12_BLEND_INV_SRC_ALPHA,
+            blend_op_alpha: D3D12_BLEND_OP_ADD,
+            logic_op: D3D12_LOGIC_OP_NOOP,
+            render_target_write_mask: D3D12_COLOR_WRITE_ENABLE_ALL,
+        };
+        let stencil_op = DepthStencilOpDesc {
+            stencil_fail_op: D3D12_STENCIL_OP_KEEP,
+            stencil_depth_fail_op: D3D12_STENCIL_OP_KEEP,
+            stencil_pass_op: D3D12_STENCIL_OP_KEEP,
+            stencil_func: D3D12_COMPARISON_FUNC_ALWAYS,
+        };
+        let no_shader = ShaderBytecode {
+            bytecode: std::ptr::null(),
+            len: 0,
+        };
+        let mut rtv_formats = [DXGI_FORMAT_UNKNOWN; 8];
+        rtv_formats[0] = format;
+
+        let desc = GraphicsPipelineStateDesc {
+            root_signature: self.root_signature.as_raw(),
+            vs: ShaderBytecode {
+                bytecode: vertex_code.as_ptr().cast(),
+                len: vertex_code.len(),
+            },
+            ps: ShaderBytecode {
+                bytecode: pixel_code.as_ptr().cast(),
+                len: pixel_code.len(),
+            },
+            _other_shaders: [no_shader; 3],
+            _stream_output: [0; 4],
+            blend_state: blend,
+            sample_mask: u32::MAX,
+            rasterizer_state: RasterizerDesc {
+                fill_mode: D3D12_FILL_MODE_SOLID,
+                // note: flipped sprites face away.
+                cull_mode: D3D12_CULL_MODE_NONE,
+                front_counter_clockwise: 0,
+                depth_bias: 0,
+                depth_bias_clamp: 0.0,
+                slope_scaled_depth_bias: 0.0,
+                depth_clip_enable: 1,
+                multisample_enable: 0,
+                antialiased_line_enable: 0,
+                forced_sample_count: 0,
+                conservative_raster: 0,
+            },
+            depth_stencil_state: DepthStencilDesc {
+                depth_enable: 0,
+                depth_write_mask: 0,
+                depth_func: D3D12_COMPARISON_FUNC_ALWAYS,
+                stencil_enable: 0,
+                stencil_read_mask: 0xff,
+                stencil_write_mask: 0xff,
+                front_face: stencil_op,
+                back_face: stencil_op,
+            },
+            input_layout: InputLayoutDesc {
+                input_element_descs: elements.as_ptr(),
+                num_elements: elements.len() as u32,
+            },
+            ib_strip_cut_value: 0,
+            primitive_topology_type: D3D12_PRIMITIVE_TOPOLOGY_TYPE_TRIANGLE,
+            num_render_targets: 1,
+            rtv_formats,
+            dsv_format: DXGI_FORMAT_UNKNOWN,
+            sample_desc: SampleDesc {
+                count: 1,
+                quality: 0,
+            },
+            node_mask: 0,
+            _cached_pso: [0; 2],
+            flags: 0,
+        };
+        let vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+        create(
+            "failed to create the sprite pipeline state",
+            |state| unsafe {
+                (vtbl.create_graphics_pipeline_state)(
+                    device.device.as_raw(),
+                    &desc,
+                    &IID_ID3D12_PIPELINE_STATE,
+                    state,
+                )
+            },
+        )
+    }
+}
+
+/// The vertex and pixel shader code of the sprite shader `source`, which has `vs_main` and
+/// `ps_main` entry points.
+pub(crate) fn compile(source: &str) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    Ok((
+        fxc::compile(source, c"sprite.hlsl", c"vs_main", c"vs_5_0")?,
+        fxc::compile(source, c"sprite.hlsl", c"ps_main", c"ps_5_0")?,
+    ))
+}
+
+/// Root constants for the camera at b0, a table with the texture's view at t0, and the linear
+/// and nearest samplers at s0 and s1.
+fn create_root_signature(device: &Device) -> Result<ComPtr, Error> {
+    let range = DescriptorRange {
+        range_type: D3D12_DESCRIPTOR_RANGE_TYPE_SRV,
+        num_descriptors: 1,
+        base_shader_register: 0,
+        register_space: 0,
+        offset_in_descriptors_from_table_start: 0,
+    };
+    let parameters = [
+        RootParameter {
+            parameter_type: D3D12_ROOT_PARAMETER_TYPE_32BIT_CONSTANTS,
+            payload: RootParameterPayload {
+                constants: RootConstants {
+                    shader_register: 0,
+                    register_space: 0,
+                    num_32bit_values: ROOT_CONSTANTS as u32,
+                },
+            },
+            shader_visibility: D3D12_SHADER_VISIBILITY_ALL,
+        },
+        RootParameter {
+            parameter_type: D3D12_ROOT_PARAMETER_TYPE_DESCRIPTOR_TABLE,
+            payload: RootParameterPayload {
+                descriptor_table: RootDescriptorTable {
+                    num_descriptor_ranges: 1,
+                    descriptor_ranges: &range,
+                },
+            },
+            shader_visibility: D3D12_SHADER_VISIBILITY_PIXEL,
+        },
+    ];
+    let samplers = [
+        (D3D12_FILTER_MIN_MAG_MIP_LINEAR, 0),
+        (D3D12_FILTER_MIN_MAG_MIP_POINT, 1),
+    ]
+    .map(|(filter, shader_register)| StaticSamplerDesc {
+        filter,
+        address_u: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        address_v: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        address_w: D3D12_TEXTURE_ADDRESS_MODE_CLAMP,
+        mip_lod_bias: 0.0,
+        max_anisotropy: 1,
+        comparison_func: D3D12_COMPARISON_FUNC_NEVER,
+        border_color: D3D12_STATIC_BORDER_COLOR_TRANSPARENT_BLACK,
+        min_lod: 0.0,
+        max_lod: f32::MAX,
+        shader_register,
+        register_space: 0,
+        shader_visibility: D3D12_SHADER_VISIBILITY_PIXEL,
+    });
+    let desc = RootSignatureDesc {
+        num_parameters: parameters.len() as u32,
+        parameters: parameters.as_ptr(),
+        num_static_samplers: samplers.len() as u32,
+        static_samplers: samplers.as_ptr(),
+        flags: D3D12_ROOT_SIGNATURE_FLAG_ALLOW_INPUT_ASSEMBLER_INPUT_LAYOUT,
+    };
+
+    let context = "failed to create the sprite root signature";
+    let mut blob = std::ptr::null_mut();
+    let mut errors = std::ptr::null_mut();
+    let hr = unsafe {
+        D3D12SerializeRootSignature(&desc, D3D_ROOT_SIGNATURE_VERSION_1, &mut blob, &mut errors)
+    };
+    let errors = unsafe { ComPtr::from_raw(errors) };
+    let blob = unsafe { ComPtr::from_call(hr, blob, context) }.map_err(|err| {
+        match errors.as_ref().map(fxc::blob_bytes) {
+            Some(message) => Error::new(format!(
+                "{context}: {}",
+                String::from_utf8_lossy(message).trim_end_matches(['\0', '\n'])
+            ))
+            .with_kind(ErrorKind::Graphics),
+            None => err,
+        }
+    })?;
+
+    let bytes = fxc::blob_bytes(&blob);
+    let vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+    create(context, |signature| unsafe {
+        (vtbl.create_root_signature)(
+            device.device.as_raw(),
+            0,
+            bytes.as_ptr().cast(),
+            bytes.len(),
+            &IID_ID3D12_ROOT_SIGNATURE,
+            signature,
+        )
+    })
+}
+
+/// A committed resource on a heap of `heap_type`.
+fn create_resource(
+    device: &Device,
+    heap_type: i32,
+    desc: &ResourceDesc,
+    initial_state: u32,
+    context: &str,
+) -> Result<ComPtr, Error> {
+    let heap_properties = HeapProperties {
+        kind: heap_type,
+        cpu_page_property: 0,
+        memory_pool_preference: 0,
+        creation_node_mask: 0,
+        visible_node_mask: 0,
+    };
+    let vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+    create(context, |resource| unsafe {
+        (vtbl.create_committed_resource)(
+            device.device.as_raw(),
+            &heap_properties,
+            0,
+            desc,
+            initial_state,
+            std::ptr::null(),
+            &IID_ID3D12_RESOURCE,
+            resource,
+        )
+    })
+}
+
+/// A buffer of `len` bytes the CPU writes and the GPU reads.
+fn create_buffer(device: &Device, len: usize, context: &str) -> Result<ComPtr, Error> {
+    let desc = ResourceDesc {
+        dimension: D3D12_RESOURCE_DIMENSION_BUFFER,
+        alignment: 0,
+        width: len as u64,
+        height: 1,
+        depth_or_array_size: 1,
+        mip_levels: 1,
+        format: DXGI_FORMAT_UNKNOWN,
+        sample_desc: SampleDesc {
+            count: 1,
+            quality: 0,
+        },
+        layout: D3D12_TEXTURE_LAYOUT_ROW_MAJOR,
+        flags: 0,
+    };
+    create_resource(
+        device,
+        D3D12_HEAP_TYPE_UPLOAD,
+        &desc,
+        D3D12_RESOURCE_STATE_GENERIC_READ,
+        context,
+    )
+}
+
+/// Maps an upload buffer for writing.
+fn map(buffer: &ComPtr, context: &str) -> Result<*mut u8, Error> {
+    // note: an empty range, since the CPU doesn't read it.
+    let read_range = Range { begin: 0, end: 0 };
+    let mut data = std::ptr::null_mut();
+    let hr = unsafe {
+        (buffer.vtbl::<ID3D12ResourceVtbl>().map)(buffer.as_raw(), 0, &read_range, &mut data)
+    };
+    Hresult(hr).check(context)?;
+    Ok(data.cast())
+}
+
+fn create(context: &str, call: impl FnOnce(*mut *mut c_void) -> HRESULT) -> Result<ComPtr, Error> {
+    let mut object = std::ptr::null_mut();
+    let hr = call(&mut object);
+    unsafe { ComPtr::from_call(hr, object, context) }
+}
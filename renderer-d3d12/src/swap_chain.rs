@@ -0,0 +1,411 @@
+//! A flip-model swap chain on a window, its back buffers and their render target descriptors,
+//! and how it presents.
+
+use common::{
+    error::Error,
+    graphics::{ColorSpace, PresentOptions},
+};
+use tracing::{debug, info, warn};
+use win32_base::{com::ComPtr, error::Hresult};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, BOOL, HANDLE, HWND},
+    System::Threading::WaitForSingleObject,
+};
+
+use crate::{
+    device::Device,
+    ffi::{
+        CpuDescriptorHandle, DescriptorHeapDesc, ID3D12DescriptorHeapVtbl, ID3D12DeviceVtbl,
+        IDXGIFactory2Vtbl, IDXGIFactory5Vtbl, IDXGIOutput6Vtbl, IDXGISwapChain3Vtbl, OutputDesc1,
+        SampleDesc, SwapChainDesc1, D3D12_DESCRIPTOR_HEAP_TYPE_RTV, DXGI_ALPHA_MODE_IGNORE,
+        DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709, DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+        DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709, DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+        DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_R10G10B10A2_UNORM, DXGI_FORMAT_R16G16B16A16_FLOAT,
+        DXGI_FORMAT_UNKNOWN, DXGI_MWA_NO_ALT_ENTER, DXGI_PRESENT_ALLOW_TEARING,
+        DXGI_SCALING_STRETCH, DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT,
+        DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING, DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
+        DXGI_SWAP_EFFECT_FLIP_DISCARD, DXGI_USAGE_RENDER_TARGET_OUTPUT, IID_ID3D12_DESCRIPTOR_HEAP,
+        IID_ID3D12_RESOURCE, IID_IDXGI_FACTORY_5, IID_IDXGI_OUTPUT_6, IID_IDXGI_SWAP_CHAIN_3,
+    },
+    FRAME_COUNT,
+};
+
+pub(crate) struct SwapChain {
+    // note: declared first so the back buffers are released before the swap chain.
+    buffers: Vec<ComPtr>,
+    swap_chain: ComPtr,
+    /// Holds a render target view of each back buffer.
+    _heap: ComPtr,
+    heap_start: CpuDescriptorHandle,
+    descriptor_size: usize,
+    /// Signaled when the present queue has room for another frame.
+    waitable: HANDLE,
+    /// The flags it was created with, which resizes have to keep.
+    flags: u32,
+    /// The options in effect.
+    options: PresentOptions,
+    width: u32,
+    height: u32,
+}
+
+impl SwapChain {
+    pub(crate) fn new(
+        device: &Device,
+        hwnd: HWND,
+        width: u32,
+        height: u32,
+        options: PresentOptions,
+    ) -> Result<Self, Error> {
+        // note: a zero size would make DXGI use the window's, which is also zero when minimized.
+        let (width, height) = (width.max(1), height.max(1));
+        let factory = unsafe { device.factory.vtbl::<IDXGIFactory2Vtbl>() };
+        // note: the flags can't be changed later, so they're always set when supported.
+        let mut flags = DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT;
+        if supports_tearing(device) {
+            flags |= DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING;
+        }
+        let desc = SwapChainDesc1 {
+            width,
+            height,
+            format: DXGI_FORMAT_B8G8R8A8_UNORM,
+            stereo: 0,
+            sample_desc: SampleDesc {
+                count: 1,
+                quality: 0,
+            },
+            buffer_usage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+            buffer_count: FRAME_COUNT as u32,
+            scaling: DXGI_SCALING_STRETCH,
+            // note: D3D12 only supports the flip model, and every version it runs on has discard.
+            swap_effect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
+            alpha_mode: DXGI_ALPHA_MODE_IGNORE,
+            flags,
+        };
+        let mut swap_chain = std::ptr::null_mut();
+        // note: D3D12 swap chains are made from the queue that presents to them.
+        let hr = unsafe {
+            (factory.create_swap_chain_for_hwnd)(
+                device.factory.as_raw(),
+                device.queue.as_raw(),
+                hwnd,
+                &desc,
+                std::ptr::null(),
+                std::ptr::null_mut(),
+                &mut swap_chain,
+            )
+        };
+        let swap_chain =
+            unsafe { ComPtr::from_call(hr, swap_chain, "failed to create swap chain") }?;
+        let swap_chain =
+            swap_chain.query(&IID_IDXGI_SWAP_CHAIN_3, "failed to get IDXGISwapChain3")?;
+        let waitable = unsafe {
+            (swap_chain
+                .vtbl::<IDXGISwapChain3Vtbl>()
+                .get_frame_latency_waitable_object)(swap_chain.as_raw())
+        };
+
+        // Alt+Enter is left to the game, which knows its display settings.
+        let hr = unsafe {
+            (factory.make_window_association)(device.factory.as_raw(), hwnd, DXGI_MWA_NO_ALT_ENTER)
+        };
+        Hresult(hr).check("failed to set the swap chain's window association")?;
+
+        let device_vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+        let desc = DescriptorHeapDesc {
+            kind: D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+            num_descriptors: FRAME_COUNT as u32,
+            flags: 0,
+            node_mask: 0,
+        };
+        let mut heap = std::ptr::null_mut();
+        let hr = unsafe {
+            (device_vtbl.create_descriptor_heap)(
+                device.device.as_raw(),
+                &desc,
+                &IID_ID3D12_DESCRIPTOR_HEAP,
+                &mut heap,
+            )
+        };
+        let heap = unsafe { ComPtr::from_call(hr, heap, "failed to create render target heap") }?;
+        let mut heap_start = CpuDescriptorHandle::default();
+        unsafe {
+            (heap
+                .vtbl::<ID3D12DescriptorHeapVtbl>()
+                .get_cpu_descriptor_handle_for_heap_start)(
+                heap.as_raw(), &mut heap_start
+            )
+        };
+        let descriptor_size = unsafe {
+            (device_vtbl.get_descriptor_handle_increment_size)(
+                device.device.as_raw(),
+                D3D12_DESCRIPTOR_HEAP_TYPE_RTV,
+            )
+        };
+
+        let mut swap_chain = Self {
+            buffers: Vec::new(),
+            swap_chain,
+            _heap: heap,
+            heap_start,
+            descriptor_size: descriptor_size as usize,
+            waitable,
+            flags,
+            options: PresentOptions::default(),
+            width,
+            height,
+        };
+        swap_chain.create_views(device)?;
+        swap_chain.configure(device, options)?;
+
+        Ok(swap_chain)
+    }
+
+    /// The index of the back buffer the next frame renders to.
+    pub(crate) fn current_index(&self) -> usize {
+        unsafe { (self.vtbl().get_current_back_buffer_index)(self.swap_chain.as_raw()) as usize }
+    }
+
+    /// The back buffer at `index`, and its render target view. `None` if a resize failed.
+    pub(crate) fn target(&self, index: usize) -> Option<(&ComPtr, CpuDescriptorHandle)> {
+        let buffer = self.buffers.get(index)?;
+        Some((buffer, self.view(index)))
+    }
+
+    pub(crate) fn options(&self) -> PresentOptions {
+        self.options
+    }
+
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The back buffers' format, which pipelines drawing to them are built for.
+    pub(crate) fn format(&self) -> u32 {
+        dxgi_format(self.options.color_space)
+    }
+
+    /// Applies `options`, falling back from the ones that aren't supported. The queue must be
+    /// idle, since changing the color space may recreate the back buffers.
+    pub(crate) fn configure(
+        &mut self,
+        device: &Device,
+        options: PresentOptions,
+    ) -> Result<(), Error> {
+        let mut options = PresentOptions {
+            max_frame_latency: options.max_frame_latency.clamp(1, 16),
+            ..options
+        };
+
+        if options.allow_tearing && self.flags & DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING == 0 {
+            if !options.vsync {
+                warn!("the display doesn't support tearing, presenting without it");
+            }
+            options.allow_tearing = false;
+        }
+
+        let hr = unsafe {
+            (self.vtbl().set_maximum_frame_latency)(
+                self.swap_chain.as_raw(),
+                options.max_frame_latency,
+            )
+        };
+        Hresult(hr).check("failed to set the maximum frame latency")?;
+
+        if !self.supports(options.color_space) {
+            warn!(
+                "the display doesn't support {:?}, falling back to sRGB",
+                options.color_space
+            );
+            options.color_space = ColorSpace::Srgb;
+        }
+        // note: SDR and HDR back buffers have different formats.
+        if options.color_space != self.options.color_space {
+            self.resize_buffers(device, dxgi_format(options.color_space))?;
+        }
+        let hr = unsafe {
+            (self.vtbl().set_color_space1)(
+                self.swap_chain.as_raw(),
+                dxgi_color_space(options.color_space),
+            )
+        };
+        Hresult(hr).check("failed to set the swap chain's color space")?;
+
+        if options != self.options {
+            info!(?options, "configured the swap chain");
+        }
+        self.options = options;
+        Ok(())
+    }
+
+    /// Resizes the back buffers. The queue must be idle, since it may still be using them.
+    pub(crate) fn resize(&mut self, device: &Device, width: u32, height: u32) -> Result<(), Error> {
+        self.width = width;
+        self.height = height;
+        self.resize_buffers(device, DXGI_FORMAT_UNKNOWN)
+    }
+
+    /// Waits until the present queue has room for another frame, so it's drawn with the latest
+    /// input rather than queued behind the others.
+    pub(crate) fn wait(&self) {
+        // note: times out rather than hang if presents stop, e.g. while the device is lost.
+        unsafe { WaitForSingleObject(self.waitable, 1000) };
+    }
+
+    pub(crate) fn present(&self) -> Result<(), Error> {
+        let (sync_interval, flags) = match self.options {
+            PresentOptions { vsync: true, .. } => (1, 0),
+            PresentOptions {
+                allow_tearing: true,
+                ..
+            } => (0, DXGI_PRESENT_ALLOW_TEARING),
+            _ => (0, 0),
+        };
+        let hr = unsafe { (self.vtbl().present)(self.swap_chain.as_raw(), sync_interval, flags) };
+        Hresult(hr).check("failed to present")
+    }
+
+    /// Whether the swap chain and the display it's mostly on can show `color_space`. HDR ones
+    /// need HDR turned on in the display settings.
+    fn supports(&self, color_space: ColorSpace) -> bool {
+        let mut support = 0;
+        let hr = unsafe {
+            (self.vtbl().check_color_space_support)(
+                self.swap_chain.as_raw(),
+                dxgi_color_space(color_space),
+                &mut support,
+            )
+        };
+        if hr < 0 || support & DXGI_SWAP_CHAIN_COLOR_SPACE_SUPPORT_FLAG_PRESENT == 0 {
+            return false;
+        }
+        if color_space == ColorSpace::Srgb {
+            return true;
+        }
+
+        match self.output_desc() {
+            Ok(desc) if desc.color_space == DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020 => {
+                debug!("the display is in HDR, up to {} nits", desc.max_luminance);
+                true
+            }
+            Ok(_) => false,
+            Err(err) => {
+                debug!("{}", err.full_message());
+                false
+            }
+        }
+    }
+
+    fn output_desc(&self) -> Result<OutputDesc1, Error> {
+        let mut output = std::ptr::null_mut();
+        let hr =
+            unsafe { (self.vtbl().get_containing_output)(self.swap_chain.as_raw(), &mut output) };
+        let output = unsafe { ComPtr::from_call(hr, output, "failed to get the display") }?;
+        let output = output.query(&IID_IDXGI_OUTPUT_6, "failed to get IDXGIOutput6")?;
+
+        let mut desc = unsafe { std::mem::zeroed::<OutputDesc1>() };
+        let hr =
+            unsafe { (output.vtbl::<IDXGIOutput6Vtbl>().get_desc1)(output.as_raw(), &mut desc) };
+        Hresult(hr).check("failed to describe the display")?;
+        Ok(desc)
+    }
+
+    fn resize_buffers(&mut self, device: &Device, format: u32) -> Result<(), Error> {
+        let (width, height) = (self.width, self.height);
+        self.buffers.clear();
+        let hr = unsafe {
+            (self.vtbl().resize_buffers)(
+                self.swap_chain.as_raw(),
+                0,
+                width,
+                height,
+                format,
+                self.flags,
+            )
+        };
+        Hresult(hr).check(&format!("failed to resize swap chain to {width}x{height}"))?;
+
+        self.create_views(device)
+    }
+
+    fn create_views(&mut self, device: &Device) -> Result<(), Error> {
+        let device_vtbl = unsafe { device.device.vtbl::<ID3D12DeviceVtbl>() };
+        let mut buffers = Vec::with_capacity(FRAME_COUNT);
+        for index in 0..FRAME_COUNT {
+            let mut buffer = std::ptr::null_mut();
+            let hr = unsafe {
+                (self.vtbl().get_buffer)(
+                    self.swap_chain.as_raw(),
+                    index as u32,
+                    &IID_ID3D12_RESOURCE,
+                    &mut buffer,
+                )
+            };
+            let buffer = unsafe { ComPtr::from_call(hr, buffer, "failed to get a back buffer") }?;
+            unsafe {
+                (device_vtbl.create_render_target_view)(
+                    device.device.as_raw(),
+                    buffer.as_raw(),
+                    std::ptr::null(),
+                    self.view(index),
+                )
+            };
+            buffers.push(buffer);
+        }
+
+        self.buffers = buffers;
+        Ok(())
+    }
+
+    fn view(&self, index: usize) -> CpuDescriptorHandle {
+        CpuDescriptorHandle {
+            ptr: self.heap_start.ptr + index * self.descriptor_size,
+        }
+    }
+
+    fn vtbl(&self) -> &IDXGISwapChain3Vtbl {
+        unsafe { self.swap_chain.vtbl() }
+    }
+}
+
+impl Drop for SwapChain {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.waitable) };
+    }
+}
+
+/// Whether the driver and display can show frames mid-refresh, for variable refresh rates.
+fn supports_tearing(device: &Device) -> bool {
+    let Ok(factory) = device
+        .factory
+        .query(&IID_IDXGI_FACTORY_5, "failed to get IDXGIFactory5")
+    else {
+        return false;
+    };
+
+    let mut supported: BOOL = 0;
+    let hr = unsafe {
+        (factory.vtbl::<IDXGIFactory5Vtbl>().check_feature_support)(
+            factory.as_raw(),
+            DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+            (&mut supported as *mut BOOL).cast(),
+            std::mem::size_of::<BOOL>() as u32,
+        )
+    };
+    hr >= 0 && supported != 0
+}
+
+fn dxgi_format(color_space: ColorSpace) -> u32 {
+    match color_space {
+        ColorSpace::Srgb => DXGI_FORMAT_B8G8R8A8_UNORM,
+        ColorSpace::Hdr10 => DXGI_FORMAT_R10G10B10A2_UNORM,
+        ColorSpace::ScRgb => DXGI_FORMAT_R16G16B16A16_FLOAT,
+    }
+}
+
+fn dxgi_color_space(color_space: ColorSpace) -> u32 {
+    match color_space {
+        ColorSpace::Srgb => DXGI_COLOR_SPACE_RGB_FULL_G22_NONE_P709,
+        ColorSpace::Hdr10 => DXGI_COLOR_SPACE_RGB_FULL_G2084_NONE_P2020,
+        ColorSpace::ScRgb => DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709,
+    }
+}
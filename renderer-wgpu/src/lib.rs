@@ -0,0 +1,555 @@
+//! A portable [`GraphicsDevice`] on wgpu, for targets without Direct3D and for checking the
+//! renderer against wgpu's validation, which reports misuse the same way on every vendor's
+//! driver.
+//!
+//! ```no_run
+//! use common::{graphics::GraphicsDevice, surface::Surface};
+//! use renderer_wgpu::Renderer;
+//!
+//! fn run(window: &dyn Surface) -> Result<(), common::error::Error> {
+//!     let mut renderer = Renderer::builder().debug(true).build(window)?;
+//!
+//!     // On each resize event:
+//!     let (width, height) = window.surface_size();
+//!     renderer.resize(width, height)?;
+//!
+//!     // Once a frame:
+//!     renderer.begin_frame()?.clear([0.1, 0.2, 0.3, 1.0]);
+//!     renderer.end_frame()
+//! }
+//! ```
+//!
+//! Each frame runs in a validation error scope, and what it catches is logged as an error when
+//! the frame ends. If the GPU is lost, the device, surface and textures are recreated on the next
+//! frame.
+
+mod sprite;
+
+use std::{
+    num::NonZeroIsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{
+        Backend, ColorSpace, CommandList, GraphicsDevice, Image, PresentOptions, TextureFilter,
+        TextureId, Vertex,
+    },
+    surface::{RawWindowHandle, Surface},
+};
+use raw_window_handle::{RawDisplayHandle, Win32WindowHandle, WindowsDisplayHandle};
+use tracing::{debug, error, info, warn};
+
+use crate::sprite::{SpritePipeline, Texture};
+
+pub struct RendererBuilder {
+    debug: bool,
+    present: PresentOptions,
+    backends: wgpu::Backends,
+}
+
+impl RendererBuilder {
+    /// Whether wgpu validates every call and the native API's debug layer is on. Defaults to on
+    /// in debug builds.
+    pub fn debug(self, debug: bool) -> Self {
+        Self { debug, ..self }
+    }
+
+    /// Whether presenting waits for the display's vertical blank. Defaults to on.
+    pub fn vsync(self, vsync: bool) -> Self {
+        Self {
+            present: PresentOptions {
+                vsync,
+                ..self.present
+            },
+            ..self
+        }
+    }
+
+    /// How the surface presents. See [`PresentOptions`] for the defaults.
+    pub fn present_options(self, present: PresentOptions) -> Self {
+        Self { present, ..self }
+    }
+
+    /// The native APIs wgpu may pick from. Defaults to those in `WGPU_BACKEND`, e.g. `vulkan`
+    /// or `dx12,metal`, or the platform's primary ones if it isn't set.
+    pub fn backends(self, backends: wgpu::Backends) -> Self {
+        Self { backends, ..self }
+    }
+
+    /// Creates a device on the fastest adapter that can present to `surface`.
+    ///
+    /// note: the window must outlive the renderer, which the borrow doesn't enforce.
+    pub fn build(self, surface: &dyn Surface) -> Result<Renderer, Error> {
+        let handle = surface.window_handle();
+        let size = surface.surface_size();
+        Ok(Renderer {
+            gpu: Some(Gpu::new(handle, &self, size, &[])?),
+            handle,
+            size,
+            options: self,
+            images: Vec::new(),
+        })
+    }
+}
+
+/// Draws to a window with wgpu.
+pub struct Renderer {
+    /// `None` after the device was lost, until it's recreated.
+    gpu: Option<Gpu>,
+    handle: RawWindowHandle,
+    size: (u32, u32),
+    options: RendererBuilder,
+    /// Each texture's image, by index, which is uploaded again if the device is recreated.
+    images: Vec<Option<(Image, TextureFilter)>>,
+}
+
+impl Renderer {
+    pub fn builder() -> RendererBuilder {
+        RendererBuilder {
+            debug: cfg!(debug_assertions),
+            present: PresentOptions::default(),
+            backends: wgpu::util::backend_bits_from_env().unwrap_or(wgpu::Backends::PRIMARY),
+        }
+    }
+
+    /// The native API wgpu is using. `None` while the device is lost.
+    pub fn native_backend(&self) -> Option<wgpu::Backend> {
+        self.gpu.as_ref().map(|gpu| gpu.native_backend)
+    }
+
+    fn recreate(&mut self) -> Result<&mut Gpu, Error> {
+        // note: the old surface has to go before another is made for the same window.
+        self.gpu = None;
+        let gpu = Gpu::new(self.handle, &self.options, self.size, &self.images)?;
+        Ok(self.gpu.insert(gpu))
+    }
+}
+
+impl GraphicsDevice for Renderer {
+    fn backend(&self) -> Backend {
+        Backend::Wgpu
+    }
+
+    fn resize(&mut self, width: u32, height: u32) -> Result<(), Error> {
+        if width == 0 || height == 0 || (width, height) == self.size {
+            return Ok(());
+        }
+
+        self.size = (width, height);
+        if let Some(gpu) = &mut self.gpu {
+            gpu.config.width = width;
+            gpu.config.height = height;
+            gpu.configure();
+        }
+        Ok(())
+    }
+
+    fn present_options(&self) -> PresentOptions {
+        self.gpu
+            .as_ref()
+            .map_or(self.options.present, |gpu| gpu.present)
+    }
+
+    fn set_present_options(&mut self, options: PresentOptions) -> Result<(), Error> {
+        self.options.present = options;
+        if let Some(gpu) = &mut self.gpu {
+            gpu.set_present_options(options);
+        }
+        Ok(())
+    }
+
+    fn create_texture(&mut self, image: Image, filter: TextureFilter) -> Result<TextureId, Error> {
+        let index = self
+            .images
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.images.len());
+        if let Some(gpu) = &mut self.gpu {
+            let texture = gpu
+                .sprites
+                .create_texture(&gpu.device, &gpu.queue, &image, filter)?;
+            if index >= gpu.textures.len() {
+                gpu.textures.resize_with(index + 1, || None);
+            }
+            gpu.textures[index] = Some(texture);
+        }
+
+        if index == self.images.len() {
+            self.images.push(None);
+        }
+        self.images[index] = Some((image, filter));
+        Ok(TextureId::from_index(index))
+    }
+
+    fn update_texture(
+        &mut self,
+        texture: TextureId,
+        origin: [u32; 2],
+        image: &Image,
+    ) -> Result<(), Error> {
+        let Some(Some((stored, _))) = self.images.get_mut(texture.index()) else {
+            return Err(
+                Error::new(format!("there's no texture {}", texture.index()))
+                    .with_kind(ErrorKind::NotFound),
+            );
+        };
+        stored.copy_from(origin, image)?;
+        if let Some(gpu) = &self.gpu {
+            if let Some(Some(texture)) = gpu.textures.get(texture.index()) {
+                texture.update(&gpu.queue, origin, image);
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy_texture(&mut self, texture: TextureId) {
+        if let Some(image) = self.images.get_mut(texture.index()) {
+            *image = None;
+        }
+        if let Some(gpu) = &mut self.gpu {
+            if let Some(texture) = gpu.textures.get_mut(texture.index()) {
+                *texture = None;
+            }
+        }
+    }
+
+    /// Gets the surface's next texture, recreating the device first if it was lost.
+    fn begin_frame(&mut self) -> Result<&mut dyn CommandList, Error> {
+        if let Some(gpu) = &self.gpu {
+            if gpu.lost.load(Ordering::Relaxed) {
+                self.gpu = None;
+            }
+        }
+        let gpu = match self.gpu {
+            Some(ref mut gpu) => gpu,
+            None => self.recreate()?,
+        };
+        gpu.begin()?;
+        Ok(gpu)
+    }
+
+    /// Submits and presents the frame, and logs the validation errors it caused.
+    fn end_frame(&mut self) -> Result<(), Error> {
+        if let Some(gpu) = &mut self.gpu {
+            gpu.end();
+        }
+        Ok(())
+    }
+}
+
+/// Everything that's recreated when the device is lost, and the frame being recorded.
+struct Gpu {
+    // note: declared first so the frame's texture is released before the surface.
+    frame: Option<Frame>,
+    sprites: SpritePipeline,
+    /// The textures by index.
+    textures: Vec<Option<Texture>>,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    capabilities: wgpu::SurfaceCapabilities,
+    /// The format of the default config, for sRGB.
+    sdr_format: wgpu::TextureFormat,
+    /// The options in effect.
+    present: PresentOptions,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    native_backend: wgpu::Backend,
+    /// Set by wgpu when the device is lost.
+    lost: Arc<AtomicBool>,
+}
+
+impl Gpu {
+    fn new(
+        handle: RawWindowHandle,
+        options: &RendererBuilder,
+        (width, height): (u32, u32),
+        images: &[Option<(Image, TextureFilter)>],
+    ) -> Result<Self, Error> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: options.backends,
+            flags: if options.debug {
+                wgpu::InstanceFlags::debugging()
+            } else {
+                wgpu::InstanceFlags::empty()
+            },
+            ..Default::default()
+        });
+        let surface = unsafe { instance.create_surface_unsafe(surface_target(handle)?) }
+            .map_err(|err| graphics_error("failed to create wgpu surface", err))?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        }))
+        .ok_or_else(|| {
+            Error::new(format!(
+                "no {:?} adapter can present to the window",
+                options.backends
+            ))
+            .with_kind(ErrorKind::Graphics)
+        })?;
+        let adapter_info = adapter.get_info();
+        info!(
+            backend = ?adapter_info.backend,
+            validation = options.debug,
+            "created wgpu device on {}",
+            adapter_info.name
+        );
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("galleon"),
+                ..Default::default()
+            },
+            None,
+        ))
+        .map_err(|err| graphics_error("failed to create wgpu device", err))?;
+        // note: errors outside a frame's error scope, e.g. from creating resources.
+        device.on_uncaptured_error(Box::new(|err| error!("{err}")));
+        let lost = Arc::new(AtomicBool::new(false));
+        device.set_device_lost_callback({
+            let lost = lost.clone();
+            move |reason, message| {
+                // note: also called when the device is dropped, which isn't a loss.
+                if matches!(reason, wgpu::DeviceLostReason::Unknown) {
+                    warn!("the GPU device was lost ({message}), recreating it");
+                    lost.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        // note: a zero size isn't valid, and the window's is zero when minimized.
+        let config = surface
+            .get_default_config(&adapter, width.max(1), height.max(1))
+            .ok_or_else(|| {
+                Error::new("the adapter can't present to the window").with_kind(ErrorKind::Graphics)
+            })?;
+
+        let sprites = SpritePipeline::new(&device);
+        let textures = images
+            .iter()
+            .map(|image| {
+                image
+                    .as_ref()
+                    .map(|(image, filter)| sprites.create_texture(&device, &queue, image, *filter))
+                    .transpose()
+            })
+            .collect::<Result<_, _>>()?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let mut gpu = Self {
+            frame: None,
+            sprites,
+            textures,
+            surface,
+            sdr_format: config.format,
+            config,
+            capabilities,
+            present: PresentOptions::default(),
+            device,
+            queue,
+            native_backend: adapter_info.backend,
+            lost,
+        };
+        gpu.set_present_options(options.present);
+
+        Ok(gpu)
+    }
+
+    fn configure(&self) {
+        self.surface.configure(&self.device, &self.config);
+    }
+
+    /// Applies `options`, falling back from the ones the surface doesn't support.
+    fn set_present_options(&mut self, options: PresentOptions) {
+        let mut options = PresentOptions {
+            max_frame_latency: options.max_frame_latency.clamp(1, 16),
+            ..options
+        };
+
+        // note: immediate is the only mode that tears.
+        let modes: &[wgpu::PresentMode] = match options {
+            PresentOptions { vsync: true, .. } => &[wgpu::PresentMode::Fifo],
+            PresentOptions {
+                allow_tearing: true,
+                ..
+            } => &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox],
+            _ => &[wgpu::PresentMode::Mailbox, wgpu::PresentMode::Immediate],
+        };
+        let mode = modes
+            .iter()
+            .copied()
+            .find(|mode| self.capabilities.present_modes.contains(mode))
+            .unwrap_or(wgpu::PresentMode::Fifo);
+        if !options.vsync && mode == wgpu::PresentMode::Fifo {
+            warn!("the surface can only present with vsync");
+            options.vsync = true;
+        }
+        options.allow_tearing &= mode == wgpu::PresentMode::Immediate;
+
+        // note: wgpu has no way to ask for the HDR10 color space, but presents 16-bit float
+        // surfaces as scRGB.
+        let format = match options.color_space {
+            ColorSpace::Srgb => Some(self.sdr_format),
+            ColorSpace::Hdr10 => None,
+            ColorSpace::ScRgb => Some(wgpu::TextureFormat::Rgba16Float)
+                .filter(|format| self.capabilities.formats.contains(format)),
+        };
+        self.config.format = format.unwrap_or_else(|| {
+            warn!(
+                "the surface doesn't support {:?}, falling back to sRGB",
+                options.color_space
+            );
+            options.color_space = ColorSpace::Srgb;
+            self.sdr_format
+        });
+
+        self.config.present_mode = mode;
+        self.config.desired_maximum_frame_latency = options.max_frame_latency;
+        self.configure();
+        if options != self.present {
+            info!(?options, "configured the surface");
+        }
+        self.present = options;
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.frame.is_some() {
+            return Ok(());
+        }
+
+        let texture = match self.surface.get_current_texture() {
+            Ok(texture) => Some(texture),
+            // The surface no longer matches the window, e.g. after a resize or a mode change.
+            Err(wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost) => {
+                self.configure();
+                let texture = self.surface.get_current_texture();
+                Some(texture.map_err(|err| graphics_error("failed to get the next frame", err))?)
+            }
+            Err(wgpu::SurfaceError::Timeout) => {
+                debug!("timed out waiting for the next frame, skipping it");
+                None
+            }
+            Err(err) => return Err(graphics_error("failed to get the next frame", err)),
+        };
+        let target = texture.map(|texture| {
+            let view = texture.texture.create_view(&Default::default());
+            (texture, view)
+        });
+
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("frame"),
+            });
+        self.frame = Some(Frame { encoder, target });
+        Ok(())
+    }
+
+    fn end(&mut self) {
+        let Some(frame) = self.frame.take() else {
+            return;
+        };
+
+        self.queue.submit([frame.encoder.finish()]);
+        if let Some((texture, _)) = frame.target {
+            texture.present();
+        }
+        if let Some(err) = pollster::block_on(self.device.pop_error_scope()) {
+            error!("{err}");
+        }
+    }
+}
+
+/// A frame's commands, and the texture they draw to.
+struct Frame {
+    encoder: wgpu::CommandEncoder,
+    /// `None` if getting the texture timed out, which skips the frame.
+    target: Option<(wgpu::SurfaceTexture, wgpu::TextureView)>,
+}
+
+impl CommandList for Gpu {
+    fn clear(&mut self, color: [f32; 4]) {
+        let Some(Frame {
+            encoder,
+            target: Some((_, view)),
+        }) = &mut self.frame
+        else {
+            return;
+        };
+
+        let [r, g, b, a] = color.map(f64::from);
+        // note: a pass with nothing in it, so all it does is load the target with the clear.
+        _ = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("clear"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r, g, b, a }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    }
+
+    fn draw_quads(&mut self, texture: TextureId, transform: &[[f32; 4]; 4], vertices: &[Vertex]) {
+        let Some(Frame {
+            encoder,
+            target: Some((_, view)),
+        }) = &mut self.frame
+        else {
+            return;
+        };
+        let Some(Some(texture)) = self.textures.get(texture.index()) else {
+            return;
+        };
+
+        self.sprites.draw(
+            &self.device,
+            encoder,
+            view,
+            self.config.format,
+            texture,
+            transform,
+            vertices,
+        );
+    }
+}
+
+fn surface_target(handle: RawWindowHandle) -> Result<wgpu::SurfaceTargetUnsafe, Error> {
+    match handle {
+        RawWindowHandle::Win32 { hwnd, hinstance } => {
+            let hwnd = NonZeroIsize::new(hwnd).ok_or_else(|| {
+                Error::new("the window handle is null").with_kind(ErrorKind::Graphics)
+            })?;
+            let mut window = Win32WindowHandle::new(hwnd);
+            window.hinstance = NonZeroIsize::new(hinstance);
+
+            Ok(wgpu::SurfaceTargetUnsafe::RawHandle {
+                raw_display_handle: RawDisplayHandle::Windows(WindowsDisplayHandle::new()),
+                raw_window_handle: raw_window_handle::RawWindowHandle::Win32(window),
+            })
+        }
+        handle => Err(
+            Error::new(format!("can't create a wgpu surface for {handle:?}"))
+                .with_kind(ErrorKind::Unsupported),
+        ),
+    }
+}
+
+fn graphics_error<E: std::error::Error + 'static>(context: &str, err: E) -> Error {
+    Error::new(context)
+        .with_kind(ErrorKind::Graphics)
+        .with_source(err)
+}
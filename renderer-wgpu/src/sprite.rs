@@ -0,0 +1,317 @@
+//! The pipeline [`CommandList::draw_quads`](common::graphics::CommandList::draw_quads) draws
+//! with, and the textures it samples. Each draw is a render pass of its own, with its camera and
+//! vertices in buffers made for it.
+
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{Image, TextureFilter, Vertex},
+};
+use wgpu::util::DeviceExt;
+
+/// The fewest quads the index buffer is made for, so small scenes don't regrow it.
+const MIN_QUADS: usize = 1024;
+
+const VERTEX_ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+    wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+/// A texture, bound with the sampler for its filter.
+pub(crate) struct Texture {
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Texture {
+    /// Replaces the texels at `origin` with `image`, which must fit, before the next submission.
+    pub(crate) fn update(&self, queue: &wgpu::Queue, origin: [u32; 2], image: &Image) {
+        let [x, y] = origin;
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x, y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            image.pixels(),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(image.width() * 4),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: image.width(),
+                height: image.height(),
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
+
+pub(crate) struct SpritePipeline {
+    shader: wgpu::ShaderModule,
+    camera_layout: wgpu::BindGroupLayout,
+    texture_layout: wgpu::BindGroupLayout,
+    layout: wgpu::PipelineLayout,
+    /// A pipeline for each surface format drawn to so far.
+    pipelines: Vec<(wgpu::TextureFormat, wgpu::RenderPipeline)>,
+    /// Linear, then nearest.
+    samplers: [wgpu::Sampler; 2],
+    /// Splits each quad into two triangles.
+    indices: wgpu::Buffer,
+    /// The number of quads the index buffer holds.
+    capacity: usize,
+}
+
+impl SpritePipeline {
+    pub(crate) fn new(device: &wgpu::Device) -> Self {
+        let shader = device.create_shader_module(wgpu::include_wgsl!("sprite.wgsl"));
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprite camera"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let texture_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprite texture"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sprites"),
+            bind_group_layouts: &[&camera_layout, &texture_layout],
+            push_constant_ranges: &[],
+        });
+
+        let samplers = [wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest].map(|filter| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some("sprite sampler"),
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: filter,
+                ..Default::default()
+            })
+        });
+
+        Self {
+            shader,
+            camera_layout,
+            texture_layout,
+            layout,
+            pipelines: Vec::new(),
+            samplers,
+            indices: create_indices(device, MIN_QUADS),
+            capacity: MIN_QUADS,
+        }
+    }
+
+    pub(crate) fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        image: &Image,
+        filter: TextureFilter,
+    ) -> Result<Texture, Error> {
+        let max = device.limits().max_texture_dimension_2d;
+        if image.width() > max || image.height() > max {
+            return Err(Error::new(format!(
+                "can't create a {}x{} texture, the most the device supports is {max}x{max}",
+                image.width(),
+                image.height()
+            ))
+            .with_kind(ErrorKind::Unsupported));
+        }
+
+        let texture = device.create_texture_with_data(
+            queue,
+            &wgpu::TextureDescriptor {
+                label: Some("sprite texture"),
+                size: wgpu::Extent3d {
+                    width: image.width(),
+                    height: image.height(),
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8Unorm,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+                view_formats: &[],
+            },
+            wgpu::util::TextureDataOrder::LayerMajor,
+            image.pixels(),
+        );
+        let view = texture.create_view(&Default::default());
+        let sampler = match filter {
+            TextureFilter::Linear => &self.samplers[0],
+            TextureFilter::Nearest => &self.samplers[1],
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite texture"),
+            layout: &self.texture_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+
+        Ok(Texture {
+            texture,
+            bind_group,
+        })
+    }
+
+    /// Records drawing the whole quads in `vertices` to `target`, which is in `format`.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        format: wgpu::TextureFormat,
+        texture: &Texture,
+        transform: &[[f32; 4]; 4],
+        vertices: &[Vertex],
+    ) {
+        let quads = vertices.len() / 4;
+        if quads == 0 {
+            return;
+        }
+        if quads > self.capacity {
+            self.capacity = quads.next_power_of_two();
+            self.indices = create_indices(device, self.capacity);
+        }
+
+        let index = self.pipeline(device, format);
+        let camera = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sprite camera"),
+            contents: bytes(std::slice::from_ref(transform)),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let camera = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite camera"),
+            layout: &self.camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.as_entire_binding(),
+            }],
+        });
+        let vertices = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("sprite vertices"),
+            contents: bytes(&vertices[..quads * 4]),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("sprites"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipelines[index].1);
+        pass.set_bind_group(0, &camera, &[]);
+        pass.set_bind_group(1, &texture.bind_group, &[]);
+        pass.set_vertex_buffer(0, vertices.slice(..));
+        pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..quads as u32 * 6, 0, 0..1);
+    }
+
+    /// The index of the pipeline for drawing to `format`, created the first time.
+    fn pipeline(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) -> usize {
+        if let Some(index) = self.pipelines.iter().position(|(f, _)| *f == format) {
+            return index;
+        }
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sprites"),
+            layout: Some(&self.layout),
+            vertex: wgpu::VertexState {
+                module: &self.shader,
+                entry_point: "vs_main",
+                compilation_options: Default::default(),
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: size_of::<Vertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &VERTEX_ATTRIBUTES,
+                }],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                // note: flipped sprites face away.
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &self.shader,
+                entry_point: "fs_main",
+                compilation_options: Default::default(),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+            cache: None,
+        });
+
+        self.pipelines.push((format, pipeline));
+        self.pipelines.len() - 1
+    }
+}
+
+fn create_indices(device: &wgpu::Device, quads: usize) -> wgpu::Buffer {
+    let indices: Vec<u32> = (0..quads as u32 * 4)
+        .step_by(4)
+        .flat_map(|first| [first, first + 1, first + 2, first, first + 2, first + 3])
+        .collect();
+    device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("sprite indices"),
+        contents: bytes(&indices),
+        usage: wgpu::BufferUsages::INDEX,
+    })
+}
+
+/// Views plain data as the bytes to upload.
+///
+/// note: only for types without padding, such as [`Vertex`], whose bytes are all initialized.
+fn bytes<T: Copy>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr().cast(), size_of_val(data)) }
+}
@@ -0,0 +1,84 @@
+//! Just enough COM to call interfaces that windows-sys only declares as opaque pointers, or not
+//! at all: an owned interface pointer whose vtable is described by a `#[repr(C)]` struct of the
+//! slots that get called, which start with [`IUnknownVtbl`].
+
+use std::{ffi::c_void, ptr::NonNull};
+
+use common::error::Error;
+use windows_sys::core::{GUID, HRESULT};
+
+use crate::error::Hresult;
+
+#[repr(C)]
+pub struct IUnknownVtbl {
+    pub query_interface: unsafe extern "system" fn(
+        this: *mut c_void,
+        iid: *const GUID,
+        object: *mut *mut c_void,
+    ) -> HRESULT,
+    pub add_ref: unsafe extern "system" fn(this: *mut c_void) -> u32,
+    pub release: unsafe extern "system" fn(this: *mut c_void) -> u32,
+}
+
+/// An owned reference to a COM object, released when dropped.
+pub struct ComPtr(NonNull<c_void>);
+
+impl ComPtr {
+    /// Takes ownership of a reference returned through an out parameter. `None` if it's null.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a COM interface pointer the caller owns a reference to.
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Option<Self> {
+        NonNull::new(ptr).map(Self)
+    }
+
+    /// Adds a reference to a pointer passed in by a caller, e.g. the arguments of a callback.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a valid COM interface pointer.
+    pub unsafe fn from_borrowed(ptr: *mut c_void) -> Option<Self> {
+        let ptr = Self::from_raw(ptr)?;
+        (ptr.vtbl::<IUnknownVtbl>().add_ref)(ptr.as_raw());
+        Some(ptr)
+    }
+
+    /// Takes ownership of the reference an `HRESULT`-returning call wrote to `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// As for [`from_raw`](ComPtr::from_raw), if `hr` is a success code.
+    pub unsafe fn from_call(hr: HRESULT, ptr: *mut c_void, context: &str) -> Result<Self, Error> {
+        Hresult(hr).check(context)?;
+        Self::from_raw(ptr).ok_or_else(|| Error::new(format!("{context}: no object returned")))
+    }
+
+    pub fn as_raw(&self) -> *mut c_void {
+        self.0.as_ptr()
+    }
+
+    /// The object's vtable, viewed as `V`.
+    ///
+    /// # Safety
+    ///
+    /// `V` must describe a prefix of the vtable of the interface this pointer is.
+    pub unsafe fn vtbl<V>(&self) -> &V {
+        &**(self.0.as_ptr() as *const *const V)
+    }
+
+    /// Asks the object for another of its interfaces.
+    pub fn query(&self, iid: &GUID, context: &str) -> Result<ComPtr, Error> {
+        let mut object = std::ptr::null_mut();
+        let hr = unsafe {
+            (self.vtbl::<IUnknownVtbl>().query_interface)(self.as_raw(), iid, &mut object)
+        };
+        unsafe { Self::from_call(hr, object, context) }
+    }
+}
+
+impl Drop for ComPtr {
+    fn drop(&mut self) {
+        unsafe { (self.vtbl::<IUnknownVtbl>().release)(self.as_raw()) };
+    }
+}
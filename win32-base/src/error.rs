@@ -0,0 +1,204 @@
+//! Turning Win32 error codes and HRESULTs into [`Error`]s with the system's description of the
+//! failure.
+
+use std::fmt::Display;
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::{
+    Foundation::GetLastError,
+    System::Diagnostics::Debug::{
+        FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+    },
+};
+
+const FACILITY_WIN32: u32 = 7;
+/// The facilities of D3D10, DXGI, DXGI's driver interface, D3D11 and D3D12.
+const GRAPHICS_FACILITIES: [u16; 5] = [0x879, 0x87a, 0x87b, 0x87c, 0x87e];
+
+// note: the codes a lost device fails with, which are named in messages so they're easy to
+// search the logs for.
+const DXGI_ERROR_DEVICE_REMOVED: i32 = 0x887A0005_u32 as i32;
+const DXGI_ERROR_DEVICE_HUNG: i32 = 0x887A0006_u32 as i32;
+const DXGI_ERROR_DEVICE_RESET: i32 = 0x887A0007_u32 as i32;
+const DXGI_ERROR_DRIVER_INTERNAL_ERROR: i32 = 0x887A0020_u32 as i32;
+const DXGI_ERROR_SDK_COMPONENT_MISSING: i32 = 0x887A002D_u32 as i32;
+
+/// Builds an error from `GetLastError`, so it must be called straight after the failed call.
+pub fn last_error(context: &str) -> Error {
+    win32_error(context, unsafe { GetLastError() })
+}
+
+/// Builds an error from a Win32 error code returned directly, as registry and ETW functions do.
+pub fn win32_error(context: &str, code: u32) -> Error {
+    Error::new(context)
+        .with_kind(ErrorKind::Platform)
+        .with_code(i64::from(code))
+        .with_source(Win32Error::new(code))
+}
+
+/// A Win32 error code and the system's description of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Win32Error {
+    code: u32,
+    message: String,
+}
+
+impl Win32Error {
+    pub fn new(code: u32) -> Self {
+        Self {
+            code,
+            message: format_message(code).unwrap_or_else(|| "unknown error".to_string()),
+        }
+    }
+
+    pub fn code(&self) -> u32 {
+        self.code
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl Display for Win32Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (os error {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for Win32Error {}
+
+/// A COM-style status code, as returned by D3D, DXGI, XAudio2 and other COM APIs. Negative values
+/// are failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hresult(pub i32);
+
+impl Hresult {
+    pub const OK: Hresult = Hresult(0);
+
+    pub fn is_ok(self) -> bool {
+        self.0 >= 0
+    }
+
+    pub fn is_err(self) -> bool {
+        !self.is_ok()
+    }
+
+    /// The subsystem the code came from, e.g. 7 for Win32 or 0x87A for DXGI.
+    pub fn facility(self) -> u16 {
+        ((self.0 as u32 >> 16) & 0x1fff) as u16
+    }
+
+    /// The facility-specific part of the code.
+    pub fn code(self) -> u16 {
+        (self.0 as u32 & 0xffff) as u16
+    }
+
+    pub fn message(self) -> String {
+        format_message(self.0 as u32).unwrap_or_else(|| "unknown error".to_string())
+    }
+
+    /// [`ErrorKind::Graphics`] for codes from D3D and DXGI, otherwise
+    /// [`ErrorKind::Platform`].
+    ///
+    /// note: D3D also fails with generic codes, e.g. `E_OUTOFMEMORY`, which are platform
+    /// errors.
+    pub fn kind(self) -> ErrorKind {
+        if GRAPHICS_FACILITIES.contains(&self.facility()) {
+            ErrorKind::Graphics
+        } else {
+            ErrorKind::Platform
+        }
+    }
+
+    /// The constant's name for the codes a lost device fails with, e.g.
+    /// `DXGI_ERROR_DEVICE_REMOVED`.
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self.0 {
+            DXGI_ERROR_DEVICE_REMOVED => "DXGI_ERROR_DEVICE_REMOVED",
+            DXGI_ERROR_DEVICE_HUNG => "DXGI_ERROR_DEVICE_HUNG",
+            DXGI_ERROR_DEVICE_RESET => "DXGI_ERROR_DEVICE_RESET",
+            DXGI_ERROR_DRIVER_INTERNAL_ERROR => "DXGI_ERROR_DRIVER_INTERNAL_ERROR",
+            DXGI_ERROR_SDK_COMPONENT_MISSING => "DXGI_ERROR_SDK_COMPONENT_MISSING",
+            _ => return None,
+        })
+    }
+
+    /// Returns an error of the code's [`kind`](Hresult::kind) for failure codes, e.g.
+    /// `Hresult(hr).check("failed to create device")?`. Success codes other than `OK`, such as
+    /// `S_FALSE` or `DXGI_STATUS_OCCLUDED`, pass.
+    pub fn check(self, context: &str) -> Result<(), Error> {
+        if self.is_ok() {
+            return Ok(());
+        }
+
+        Err(Error::new(context)
+            .with_kind(self.kind())
+            .with_code(i64::from(self.0 as u32))
+            .with_source(self))
+    }
+}
+
+impl Display for Hresult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} ({name}, 0x{:08X})", self.message(), self.0 as u32),
+            None => write!(f, "{} (0x{:08X})", self.message(), self.0 as u32),
+        }
+    }
+}
+
+impl std::error::Error for Hresult {}
+
+impl From<i32> for Hresult {
+    fn from(hr: i32) -> Self {
+        Self(hr)
+    }
+}
+
+impl From<Hresult> for i32 {
+    fn from(hr: Hresult) -> Self {
+        hr.0
+    }
+}
+
+/// `HRESULT_FROM_WIN32`.
+impl From<Win32Error> for Hresult {
+    fn from(err: Win32Error) -> Self {
+        if err.code as i32 <= 0 {
+            return Self(err.code as i32);
+        }
+
+        Self(((err.code & 0xffff) | (FACILITY_WIN32 << 16) | 0x8000_0000) as i32)
+    }
+}
+
+impl From<Hresult> for Error {
+    fn from(hr: Hresult) -> Self {
+        Error::new(hr.message())
+            .with_kind(hr.kind())
+            .with_code(i64::from(hr.0 as u32))
+    }
+}
+
+/// The system message for `code`, without the trailing period and line break.
+fn format_message(code: u32) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            code,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null(),
+        )
+    };
+    if len == 0 {
+        return None;
+    }
+
+    let message = String::from_utf16_lossy(&buffer[..len as usize]);
+    Some(message.trim_end().trim_end_matches('.').to_string())
+}
@@ -0,0 +1,113 @@
+//! FXC, the HLSL compiler Windows ships as `d3dcompiler_47.dll`, loaded at run time to build the
+//! renderers' own shaders. Unlike DXC it's there in release builds too, and D3D11 and D3D12 both
+//! take its shader model 4 and 5 bytecode.
+
+use std::ffi::{c_void, CStr};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::{
+    core::HRESULT,
+    Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+};
+
+use crate::com::{ComPtr, IUnknownVtbl};
+
+const D3DCOMPILE_DEBUG: u32 = 0x1;
+const D3DCOMPILE_SKIP_OPTIMIZATION: u32 = 0x4;
+const D3DCOMPILE_OPTIMIZATION_LEVEL3: u32 = 0x8000;
+
+type D3DCompileFn = unsafe extern "system" fn(
+    source: *const c_void,
+    source_len: usize,
+    source_name: *const u8,
+    defines: *const c_void,
+    include: *mut c_void,
+    entry_point: *const u8,
+    target: *const u8,
+    flags1: u32,
+    flags2: u32,
+    code: *mut *mut c_void,
+    errors: *mut *mut c_void,
+) -> HRESULT;
+
+#[repr(C)]
+pub struct ID3DBlobVtbl {
+    base: IUnknownVtbl,
+    get_buffer_pointer: unsafe extern "system" fn(this: *mut c_void) -> *const u8,
+    get_buffer_size: unsafe extern "system" fn(this: *mut c_void) -> usize,
+}
+
+/// Compiles `entry_point` in `source` for `target`, e.g. `vs_4_0` or `vs_5_0`, returning its
+/// bytecode. `name` is the source's name in diagnostics.
+pub fn compile(
+    source: &str,
+    name: &CStr,
+    entry_point: &CStr,
+    target: &CStr,
+) -> Result<Vec<u8>, Error> {
+    let module_name: Vec<u16> = "d3dcompiler_47.dll\0".encode_utf16().collect();
+    let module = unsafe { LoadLibraryW(module_name.as_ptr()) };
+    let d3d_compile = (module != 0)
+        .then(|| unsafe { GetProcAddress(module, c"D3DCompile".as_ptr().cast()) })
+        .flatten()
+        .ok_or_else(|| {
+            Error::new("can't compile shaders without d3dcompiler_47.dll")
+                .with_kind(ErrorKind::NotFound)
+        })?;
+    let d3d_compile = unsafe {
+        std::mem::transmute::<unsafe extern "system" fn() -> isize, D3DCompileFn>(d3d_compile)
+    };
+
+    let flags = if cfg!(debug_assertions) {
+        D3DCOMPILE_DEBUG | D3DCOMPILE_SKIP_OPTIMIZATION
+    } else {
+        D3DCOMPILE_OPTIMIZATION_LEVEL3
+    };
+    let mut code = std::ptr::null_mut();
+    let mut errors = std::ptr::null_mut();
+    let hr = unsafe {
+        d3d_compile(
+            source.as_ptr().cast(),
+            source.len(),
+            name.as_ptr().cast(),
+            std::ptr::null(),
+            std::ptr::null_mut(),
+            entry_point.as_ptr().cast(),
+            target.as_ptr().cast(),
+            flags,
+            0,
+            &mut code,
+            &mut errors,
+        )
+    };
+    // note: diagnostics come back whether or not it compiled, e.g. warnings.
+    let errors = unsafe { ComPtr::from_raw(errors) };
+    let context = format!(
+        "failed to compile {}:{}",
+        name.to_string_lossy(),
+        entry_point.to_string_lossy()
+    );
+    let code = unsafe { ComPtr::from_call(hr, code, &context) }.map_err(|err| {
+        match errors.as_ref().map(blob_bytes) {
+            Some(diagnostics) => Error::new(format!(
+                "{context}: {}",
+                String::from_utf8_lossy(diagnostics).trim_end_matches(['\0', '\n'])
+            ))
+            .with_kind(ErrorKind::Graphics),
+            None => err,
+        }
+    })?;
+
+    Ok(blob_bytes(&code).to_vec())
+}
+
+/// The contents of an `ID3DBlob`, e.g. compiled code or diagnostics.
+pub fn blob_bytes(blob: &ComPtr) -> &[u8] {
+    let vtbl = unsafe { blob.vtbl::<ID3DBlobVtbl>() };
+    let ptr = unsafe { (vtbl.get_buffer_pointer)(blob.as_raw()) };
+    let len = unsafe { (vtbl.get_buffer_size)(blob.as_raw()) };
+    if ptr.is_null() {
+        return &[];
+    }
+    unsafe { std::slice::from_raw_parts(ptr, len) }
+}
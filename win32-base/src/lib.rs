@@ -0,0 +1,14 @@
+//! The Win32 plumbing `win32` and the D3D renderers share, so there's one of each to audit:
+//! Win32 error codes and HRESULTs as [`Error`](common::error::Error)s, owned COM interface
+//! pointers, and FXC to compile HLSL with.
+//!
+//! It's a crate of its own because the `win32` binary depends on the renderers, so they can't
+//! depend on `win32`.
+
+#[allow(clippy::non_minimal_cfg)]
+#[cfg(all(not(target_os = "windows")))]
+compile_error!("only windows is supported");
+
+pub mod com;
+pub mod error;
+pub mod fxc;
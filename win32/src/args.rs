@@ -0,0 +1,299 @@
+//! The command line, read from Windows directly, and a small parser for engine flags.
+//!
+//! ```
+//! use win32::args::{Args, Flag};
+//!
+//! const FLAGS: &[Flag] = &[
+//!     Flag::switch("console", "open a console window for log output"),
+//!     Flag::value("log-level", "the most verbose level logged, e.g. debug"),
+//! ];
+//!
+//! let args = Args::parse_from(FLAGS, ["--console", "--log-level=info", "save.dat"])?;
+//! assert!(args.is_set("console"));
+//! assert_eq!(args.value("log-level"), Some("info"));
+//! assert_eq!(args.positional(), ["save.dat"]);
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{fmt::Write as _, str::FromStr};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::{
+    Foundation::LocalFree, System::Environment::GetCommandLineW, UI::Shell::CommandLineToArgvW,
+};
+
+use crate::wide::WStr;
+
+/// The process's arguments, including the program name first, decoded from UTF-16 with invalid
+/// code units replaced.
+///
+/// `std::env::args` panics on arguments that aren't valid Unicode and can come back empty when a
+/// GUI-subsystem process is started by some launchers; this reads `GetCommandLineW` instead,
+/// split the same way the C runtime does.
+pub fn args() -> Vec<String> {
+    let mut count = 0;
+    let argv = unsafe { CommandLineToArgvW(GetCommandLineW(), &mut count) };
+    if argv.is_null() {
+        return Vec::new();
+    }
+
+    let args = (0..count as usize)
+        .map(|i| unsafe { WStr::from_ptr(*argv.add(i)) }.to_string_lossy())
+        .collect();
+    unsafe { LocalFree(argv.cast()) };
+
+    args
+}
+
+/// A flag the parser accepts, e.g. `--console` or `--log-level debug`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flag {
+    name: &'static str,
+    takes_value: bool,
+    help: &'static str,
+}
+
+impl Flag {
+    /// A flag that's either present or not, e.g. `--windowed`.
+    pub const fn switch(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            takes_value: false,
+            help,
+        }
+    }
+
+    /// A flag followed by a value, given as `--name value` or `--name=value`.
+    pub const fn value(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            takes_value: true,
+            help,
+        }
+    }
+
+    /// The name without the leading `--`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn takes_value(&self) -> bool {
+        self.takes_value
+    }
+
+    pub fn help(&self) -> &'static str {
+        self.help
+    }
+}
+
+/// A command line parsed against a list of [`Flag`]s. Anything not starting with `--` is a
+/// positional argument, as is everything after a bare `--`. If a flag is given more than once,
+/// the last value wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    switches: Vec<&'static str>,
+    values: Vec<(&'static str, String)>,
+    positional: Vec<String>,
+}
+
+impl Args {
+    /// Parses this process's command line, skipping the program name.
+    pub fn parse(flags: &[Flag]) -> Result<Self, Error> {
+        Self::parse_from(flags, args().into_iter().skip(1))
+    }
+
+    /// Parses `args`, which shouldn't include the program name. Unknown flags and flags missing
+    /// their value are errors, with the accepted flags listed.
+    pub fn parse_from<I, S>(flags: &[Flag], args: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter().map(Into::into);
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                parsed.positional.extend(args);
+                break;
+            }
+            let Some(flag) = arg.strip_prefix("--") else {
+                parsed.positional.push(arg);
+                continue;
+            };
+
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let Some(flag) = flags.iter().find(|flag| flag.name == name) else {
+                return Err(usage_error(format!("unknown flag --{name}"), flags));
+            };
+
+            if flag.takes_value {
+                let Some(value) = inline_value.or_else(|| args.next()) else {
+                    return Err(usage_error(format!("--{name} needs a value"), flags));
+                };
+                parsed.values.retain(|(name, _)| *name != flag.name);
+                parsed.values.push((flag.name, value));
+            } else if inline_value.is_some() {
+                return Err(usage_error(format!("--{name} doesn't take a value"), flags));
+            } else if !parsed.switches.contains(&flag.name) {
+                parsed.switches.push(flag.name);
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    /// Whether a switch was given.
+    pub fn is_set(&self, name: &str) -> bool {
+        self.switches.contains(&name)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(flag, _)| *flag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The value converted with `FromStr`, e.g. `args.parse_value::<u32>("width")?`.
+    pub fn parse_value<T>(&self, name: &str) -> Result<Option<T>, Error>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + 'static,
+    {
+        self.value(name)
+            .map(|value| {
+                value.parse().map_err(|err| {
+                    Error::new(format!("invalid value for --{name}: {value}"))
+                        .with_kind(ErrorKind::Config)
+                        .with_source(err)
+                })
+            })
+            .transpose()
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+}
+
+/// Lists `flags` with their help, one per line, e.g. for `--help` or a parse error.
+pub fn usage(flags: &[Flag]) -> String {
+    let width = flags
+        .iter()
+        .map(|flag| flag.name.len() + if flag.takes_value { 8 } else { 0 })
+        .max()
+        .unwrap_or(0);
+
+    let mut usage = String::from("flags:");
+    for flag in flags {
+        let mut name = format!("--{}", flag.name);
+        if flag.takes_value {
+            name.push_str(" <value>");
+        }
+        _ = write!(usage, "\n  {name:<pad$}  {}", flag.help, pad = width + 2);
+    }
+
+    usage
+}
+
+fn usage_error(message: String, flags: &[Flag]) -> Error {
+    Error::new(format!("{message}\n{}", usage(flags))).with_kind(ErrorKind::Config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAGS: &[Flag] = &[
+        Flag::switch("console", "open a console window for log output"),
+        Flag::switch("windowed", "start in a window rather than fullscreen"),
+        Flag::value("log-level", "the most verbose level logged, e.g. debug"),
+        Flag::value("width", "the window's width in pixels"),
+    ];
+
+    fn parse(args: &[&str]) -> Result<Args, Error> {
+        Args::parse_from(FLAGS, args.iter().copied())
+    }
+
+    #[test]
+    fn parses_switches_values_and_positionals() {
+        let args = parse(&[
+            "save.dat",
+            "--console",
+            "--width",
+            "1280",
+            "--log-level=debug",
+        ])
+        .unwrap();
+        assert!(args.is_set("console"));
+        assert!(!args.is_set("windowed"));
+        assert_eq!(args.value("width"), Some("1280"));
+        assert_eq!(args.value("log-level"), Some("debug"));
+        assert_eq!(args.positional(), ["save.dat"]);
+    }
+
+    #[test]
+    fn last_value_wins() {
+        let args = parse(&["--width=800", "--console", "--width", "1024", "--console"]).unwrap();
+        assert_eq!(args.value("width"), Some("1024"));
+        assert_eq!(args.values.len(), 1);
+        assert_eq!(args.switches, ["console"]);
+    }
+
+    #[test]
+    fn everything_after_a_bare_double_dash_is_positional() {
+        let args = parse(&["--console", "--", "--windowed", "-", "--width=1"]).unwrap();
+        assert!(args.is_set("console"));
+        assert!(!args.is_set("windowed"));
+        assert_eq!(args.value("width"), None);
+        assert_eq!(args.positional(), ["--windowed", "-", "--width=1"]);
+    }
+
+    #[test]
+    fn values_may_be_empty_or_look_like_flags() {
+        let args = parse(&["--log-level=", "--width", "--console"]).unwrap();
+        assert_eq!(args.value("log-level"), Some(""));
+        assert_eq!(args.value("width"), Some("--console"));
+        assert!(!args.is_set("console"));
+    }
+
+    #[test]
+    fn rejects_bad_flags_with_usage() {
+        for (args, message) in [
+            (&["--fullscreen"][..], "unknown flag --fullscreen"),
+            (&["--width"], "--width needs a value"),
+            (&["--console=yes"], "--console doesn't take a value"),
+        ] {
+            let err = parse(args).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::Config);
+            assert_eq!(err.to_string(), format!("{message}\n{}", usage(FLAGS)));
+        }
+    }
+
+    #[test]
+    fn parses_values_with_from_str() {
+        let args = parse(&["--width", "1280", "--log-level", "loud"]).unwrap();
+        assert_eq!(args.parse_value::<u32>("width").unwrap(), Some(1280));
+        assert_eq!(args.parse_value::<u32>("height").unwrap(), None);
+
+        let err = args.parse_value::<u32>("log-level").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Config);
+        assert_eq!(err.to_string(), "invalid value for --log-level: loud");
+    }
+
+    #[test]
+    fn lists_flags_aligned() {
+        assert_eq!(
+            usage(FLAGS),
+            "flags:\n  \
+             --console            open a console window for log output\n  \
+             --windowed           start in a window rather than fullscreen\n  \
+             --log-level <value>  the most verbose level logged, e.g. debug\n  \
+             --width <value>      the window's width in pixels"
+        );
+        assert_eq!(usage(&[]), "flags:");
+    }
+}
@@ -0,0 +1,147 @@
+//! COM initialization for the thread, which the shell, WinRT, WASAPI and DirectWrite all need
+//! before they can be used:
+//!
+//! ```no_run
+//! use win32::com::{Apartment, ComGuard};
+//!
+//! let _com = ComGuard::new(Apartment::MultiThreaded)?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! Guards are counted per thread: COM stays initialized until the last one on the thread is
+//! dropped, so libraries and the game can each hold their own.
+//!
+//! The interface pointers themselves are `win32-base`'s `ComPtr`, which the renderers use too.
+
+use std::{cell::Cell, marker::PhantomData};
+
+use common::error::Error;
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        Foundation::RPC_E_CHANGED_MODE,
+        System::Com::{
+            CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED, COINIT_MULTITHREADED,
+        },
+    },
+};
+
+pub(crate) use win32_base::com::{ComPtr, IUnknownVtbl};
+
+use crate::error::Hresult;
+
+pub(crate) const IID_IUNKNOWN: GUID = GUID::from_u128(0x00000000_0000_0000_c000_000000000046);
+
+thread_local! {
+    /// The guards alive on this thread and the apartment they entered.
+    static ENTERED: Cell<Option<(u32, Apartment)>> = const { Cell::new(None) };
+}
+
+/// windows-sys's `GUID` doesn't implement `PartialEq`.
+pub(crate) fn guid_eq(a: &GUID, b: &GUID) -> bool {
+    a.data1 == b.data1 && a.data2 == b.data2 && a.data3 == b.data3 && a.data4 == b.data4
+}
+
+/// The COM threading model of a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Apartment {
+    /// Objects are called only on the thread that made them, through its message queue. What
+    /// the file dialogs and other UI need.
+    SingleThreaded,
+    /// Objects may be called from any thread, e.g. for WASAPI on an audio thread.
+    MultiThreaded,
+}
+
+/// Keeps COM initialized on the thread that made it.
+pub struct ComGuard {
+    apartment: Apartment,
+    // note: false if COM was initialized on this thread by someone else in the other mode, in
+    // which case it's not ours to uninitialize.
+    counted: bool,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ComGuard {
+    /// Enters `apartment`, or joins it if this thread is already in it. Fails if the thread is
+    /// in the other one.
+    pub fn new(apartment: Apartment) -> Result<Self, Error> {
+        let guard = Self::current_or(apartment)?;
+        if guard.apartment != apartment {
+            Hresult(RPC_E_CHANGED_MODE).check(&format!(
+                "can't enter the {apartment:?} COM apartment on a {:?} thread",
+                guard.apartment
+            ))?;
+        }
+
+        Ok(guard)
+    }
+
+    /// Joins whatever apartment this thread is in, or enters `apartment` if it isn't in one,
+    /// for code that works in either.
+    pub fn current_or(apartment: Apartment) -> Result<Self, Error> {
+        if let Some((guards, current)) = ENTERED.get() {
+            ENTERED.set(Some((guards + 1, current)));
+            return Ok(Self {
+                apartment: current,
+                counted: true,
+                _not_send: PhantomData,
+            });
+        }
+
+        let mode = match apartment {
+            Apartment::SingleThreaded => COINIT_APARTMENTTHREADED,
+            Apartment::MultiThreaded => COINIT_MULTITHREADED,
+        };
+        let hr = unsafe { CoInitializeEx(std::ptr::null(), mode as u32) };
+        if hr == RPC_E_CHANGED_MODE {
+            let other = match apartment {
+                Apartment::SingleThreaded => Apartment::MultiThreaded,
+                Apartment::MultiThreaded => Apartment::SingleThreaded,
+            };
+            return Ok(Self {
+                apartment: other,
+                counted: false,
+                _not_send: PhantomData,
+            });
+        }
+        Hresult(hr).check("failed to initialize COM")?;
+
+        ENTERED.set(Some((1, apartment)));
+        Ok(Self {
+            apartment,
+            counted: true,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// The apartment the thread is in.
+    pub fn apartment(&self) -> Apartment {
+        self.apartment
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if !self.counted {
+            return;
+        }
+
+        match ENTERED.get() {
+            Some((1, _)) => {
+                ENTERED.set(None);
+                unsafe { CoUninitialize() };
+            }
+            Some((guards, apartment)) => ENTERED.set(Some((guards - 1, apartment))),
+            None => {}
+        }
+    }
+}
+
+/// The base of every WinRT interface.
+#[repr(C)]
+pub(crate) struct IInspectableVtbl {
+    pub(crate) base: IUnknownVtbl,
+    _get_iids: usize,
+    _get_runtime_class_name: usize,
+    _get_trust_level: usize,
+}
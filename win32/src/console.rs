@@ -0,0 +1,42 @@
+use common::error::Error;
+use windows_sys::Win32::{
+    Foundation::INVALID_HANDLE_VALUE,
+    System::Console::{
+        AllocConsole, GetConsoleMode, GetStdHandle, SetConsoleMode,
+        ENABLE_VIRTUAL_TERMINAL_PROCESSING, STD_ERROR_HANDLE, STD_HANDLE, STD_OUTPUT_HANDLE,
+    },
+};
+
+use crate::error::last_error;
+
+/// Opens a console window for this process. The binary uses the GUI subsystem, so there is no
+/// console unless one is explicitly allocated.
+pub fn alloc_console() -> Result<(), Error> {
+    if unsafe { AllocConsole() } == 0 {
+        return Err(last_error("failed to allocate console"));
+    }
+
+    Ok(())
+}
+
+/// Enables ANSI escape sequence handling on stdout and stderr. Returns false on consoles that do
+/// not support it, such as those before Windows 10.
+pub fn enable_virtual_terminal() -> bool {
+    enable_virtual_terminal_for(STD_OUTPUT_HANDLE) && enable_virtual_terminal_for(STD_ERROR_HANDLE)
+}
+
+fn enable_virtual_terminal_for(std_handle: STD_HANDLE) -> bool {
+    unsafe {
+        let handle = GetStdHandle(std_handle);
+        if handle == 0 || handle == INVALID_HANDLE_VALUE {
+            return false;
+        }
+
+        let mut mode = 0;
+        if GetConsoleMode(handle, &mut mode) == 0 {
+            return false;
+        }
+
+        SetConsoleMode(handle, mode | ENABLE_VIRTUAL_TERMINAL_PROCESSING) != 0
+    }
+}
@@ -0,0 +1,617 @@
+//! Crash reports: the failure, the most recent log records, a description of the machine and an
+//! optional minidump bundled into a zip. Reports are kept on disk and offered for upload on the
+//! next launch, so nothing leaves the machine while the process is in a bad state or without the
+//! user agreeing to it.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use common::{
+    error::{Context, Error, ErrorKind},
+    log::{
+        self,
+        format::{Formatter, FullFormatter},
+        sinks::RingBufferSink,
+        timestamp::Timestamp,
+    },
+    zip::ZipWriter,
+};
+use windows_sys::Win32::{
+    Graphics::Gdi::{
+        EnumDisplayDevicesW, DISPLAY_DEVICEW, DISPLAY_DEVICE_ATTACHED_TO_DESKTOP,
+        DISPLAY_DEVICE_MIRRORING_DRIVER,
+    },
+    Networking::WinHttp::{
+        WinHttpCloseHandle, WinHttpConnect, WinHttpOpen, WinHttpOpenRequest, WinHttpQueryHeaders,
+        WinHttpReceiveResponse, WinHttpSendRequest, WinHttpSetTimeouts,
+        WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY, WINHTTP_FLAG_SECURE, WINHTTP_QUERY_FLAG_NUMBER,
+        WINHTTP_QUERY_STATUS_CODE,
+    },
+    System::{
+        Diagnostics::Debug::{SetUnhandledExceptionFilter, EXCEPTION_POINTERS},
+        Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_DWORD, RRF_RT_REG_SZ},
+        SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX},
+    },
+};
+
+use tracing::error;
+
+use crate::{
+    dialog::{MessageBox, Response},
+    error::last_error,
+    minidump, wide, wstr,
+};
+
+const REPORT_PREFIX: &str = "crash-";
+const REPORT_EXTENSION: &str = "zip";
+const UPLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+const EXCEPTION_CONTINUE_SEARCH: i32 = 0;
+
+/// The reporter [`CrashReporter::install_exception_filter`] installed, and the filter it replaced.
+static EXCEPTION_REPORTER: OnceLock<CrashReporter> = OnceLock::new();
+static PREVIOUS_FILTER: OnceLock<ExceptionFilter> = OnceLock::new();
+
+type ExceptionFilter = unsafe extern "system" fn(*const EXCEPTION_POINTERS) -> i32;
+
+pub struct CrashReporterBuilder {
+    dir: PathBuf,
+    log: Option<RingBufferSink>,
+    endpoint: Option<String>,
+}
+
+impl CrashReporterBuilder {
+    /// Includes the records retained by `log` in each report. The sink has to be added to the
+    /// logger separately.
+    pub fn log(self, log: RingBufferSink) -> Self {
+        Self {
+            log: Some(log),
+            ..self
+        }
+    }
+
+    /// Where [`upload_pending`](CrashReporter::upload_pending) POSTs reports, e.g.
+    /// `https://crashes.example.com/api/upload`. Without one, reports are only written to disk.
+    pub fn endpoint<S: Into<String>>(self, url: S) -> Self {
+        Self {
+            endpoint: Some(url.into()),
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<CrashReporter, Error> {
+        let endpoint = self.endpoint.as_deref().map(Endpoint::parse).transpose()?;
+
+        Ok(CrashReporter {
+            dir: self.dir,
+            log: self.log,
+            endpoint,
+        })
+    }
+}
+
+/// Writes crash reports into a directory and uploads the ones left by earlier runs:
+///
+/// ```no_run
+/// use win32::crash_report::{self, CrashReporter};
+///
+/// let reporter = CrashReporter::builder(std::env::temp_dir().join("galleon-crashes"))
+///     .endpoint("https://crashes.example.com/upload")
+///     .build()?;
+/// reporter.upload_pending(|reports| crash_report::ask_consent(reports.len()))?;
+/// reporter.install_exception_filter();
+///
+/// if let Err(err) = win32::guard::run_guarded(|| Ok(())) {
+///     reporter.write_with_minidump(&err)?;
+/// }
+/// # Ok::<(), common::error::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct CrashReporter {
+    dir: PathBuf,
+    log: Option<RingBufferSink>,
+    endpoint: Option<Endpoint>,
+}
+
+impl CrashReporter {
+    pub fn builder<P: Into<PathBuf>>(dir: P) -> CrashReporterBuilder {
+        CrashReporterBuilder {
+            dir: dir.into(),
+            log: None,
+            endpoint: None,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Bundles `err`, the retained log records, system info and the minidump at `minidump`, if
+    /// any, into `crash-<time>.zip` in the report directory and returns its path.
+    ///
+    /// The archive holds `error.txt` with the error's chain and backtrace, `error.json` from
+    /// [`Error::to_json`], `log.txt`, `system.txt` and `minidump.dmp`.
+    pub fn write(&self, err: &Error, minidump: Option<&Path>) -> Result<PathBuf, Error> {
+        let mut zip = ZipWriter::new();
+        zip.add("error.txt", format!("{err:#}").as_bytes());
+        zip.add("error.json", err.to_json().as_bytes());
+        if let Some(log) = &self.log {
+            let mut text = String::new();
+            for record in log.snapshot() {
+                FullFormatter.format(&record, &mut text);
+                text.push('\n');
+            }
+            zip.add("log.txt", text.as_bytes());
+        }
+        zip.add("system.txt", SystemInfo::collect().to_string().as_bytes());
+        if let Some(minidump) = minidump {
+            let dump = fs::read(minidump)
+                .with_context(|| format!("failed to read minidump {}", minidump.display()))?;
+            zip.add("minidump.dmp", &dump);
+        }
+
+        let now = Timestamp::from_system_time(SystemTime::now());
+        let path = self.dir.join(format!(
+            "{REPORT_PREFIX}{:04}{:02}{:02}T{:02}{:02}{:02}Z.{REPORT_EXTENSION}",
+            now.year, now.month, now.day, now.hour, now.minute, now.second
+        ));
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!(
+                "failed to create crash report directory {}",
+                self.dir.display()
+            )
+        })?;
+        fs::write(&path, zip.finish())
+            .with_context(|| format!("failed to write crash report {}", path.display()))?;
+
+        Ok(path)
+    }
+
+    /// Like [`write`](Self::write), with a minidump of the process taken now. The dump is
+    /// removed once it's in the report.
+    pub fn write_with_minidump(&self, err: &Error) -> Result<PathBuf, Error> {
+        self.write_dump_report(err, minidump::write)
+    }
+
+    /// Writes a report with a minidump from the thread that raised an exception nothing else
+    /// handles, e.g. an access violation in a driver, which `guard::run_guarded` can't catch.
+    /// The filter that was installed before is called afterwards. Only the first reporter to
+    /// install the filter is used.
+    pub fn install_exception_filter(&self) {
+        if EXCEPTION_REPORTER.set(self.clone()).is_ok() {
+            let previous = unsafe { SetUnhandledExceptionFilter(Some(on_unhandled_exception)) };
+            if let Some(previous) = previous {
+                _ = PREVIOUS_FILTER.set(previous);
+            }
+        }
+    }
+
+    fn write_dump_report<F>(&self, err: &Error, write_dump: F) -> Result<PathBuf, Error>
+    where
+        F: FnOnce(&Path) -> Result<(), Error>,
+    {
+        fs::create_dir_all(&self.dir).with_context(|| {
+            format!(
+                "failed to create crash report directory {}",
+                self.dir.display()
+            )
+        })?;
+        let dump = self
+            .dir
+            .join(format!("{REPORT_PREFIX}{}.dmp", std::process::id()));
+        let report = match write_dump(&dump) {
+            Ok(()) => self.write(err, Some(&dump)),
+            // A report without the dump is better than none.
+            Err(dump_err) => {
+                error!("{}", dump_err.full_message());
+                self.write(err, None)
+            }
+        };
+        _ = fs::remove_file(&dump);
+        report
+    }
+
+    /// Reports written by earlier runs that haven't been uploaded or discarded, oldest first.
+    pub fn pending(&self) -> Result<Vec<PathBuf>, Error> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(Error::new(format!(
+                    "failed to read crash report directory {}",
+                    self.dir.display()
+                ))
+                .with_kind(ErrorKind::Io)
+                .with_source(err))
+            }
+        };
+
+        let mut reports: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().is_some_and(|ext| ext == REPORT_EXTENSION)
+                    && path
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with(REPORT_PREFIX))
+            })
+            .collect();
+        // The names are timestamps, so they sort by age.
+        reports.sort();
+        Ok(reports)
+    }
+
+    /// Offers the pending reports to `consent` and, if it agrees, uploads each one and deletes it
+    /// once the endpoint has accepted it. Declined reports are deleted so the user isn't asked
+    /// about them again. Returns how many were uploaded.
+    ///
+    /// Without an endpoint the reports are left alone and `consent` isn't called.
+    pub fn upload_pending<F>(&self, consent: F) -> Result<usize, Error>
+    where
+        F: FnOnce(&[PathBuf]) -> bool,
+    {
+        let Some(endpoint) = &self.endpoint else {
+            return Ok(0);
+        };
+        let reports = self.pending()?;
+        if reports.is_empty() {
+            return Ok(0);
+        }
+
+        if !consent(&reports) {
+            for report in &reports {
+                _ = fs::remove_file(report);
+            }
+            return Ok(0);
+        }
+
+        let mut uploaded = 0;
+        let mut errors = Vec::new();
+        for report in &reports {
+            let result = fs::read(report)
+                .with_context(|| format!("failed to read crash report {}", report.display()))
+                .and_then(|body| endpoint.post(&body))
+                .and_then(|()| {
+                    fs::remove_file(report).with_context(|| {
+                        format!("failed to delete crash report {}", report.display())
+                    })
+                });
+            match result {
+                Ok(()) => uploaded += 1,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        match errors.len() {
+            0 => Ok(uploaded),
+            1 => Err(errors.remove(0)),
+            _ => Err(Error::aggregate(errors)),
+        }
+    }
+}
+
+/// Writes a crash report for the exception at `exception`, on the thread that raised it.
+unsafe extern "system" fn on_unhandled_exception(exception: *const EXCEPTION_POINTERS) -> i32 {
+    static HANDLING: AtomicBool = AtomicBool::new(false);
+
+    // note: another thread crashing meanwhile, or the report itself faulting, goes straight to
+    // the previous filter.
+    if !HANDLING.swap(true, Ordering::AcqRel) {
+        if let Some(reporter) = EXCEPTION_REPORTER.get() {
+            let record = &*(*exception).ExceptionRecord;
+            let err = Error::new(format!(
+                "unhandled exception {:#010x} at {:p}",
+                record.ExceptionCode as u32, record.ExceptionAddress
+            ))
+            .with_kind(ErrorKind::Platform);
+            error!("{err}");
+
+            match reporter
+                .write_dump_report(&err, |path| minidump::write_for_exception(path, exception))
+            {
+                Ok(path) => error!("wrote crash report to {}", path.display()),
+                Err(err) => error!("{}", err.full_message()),
+            }
+            log::emergency_flush();
+        }
+    }
+
+    match PREVIOUS_FILTER.get() {
+        Some(previous) => previous(exception),
+        None => EXCEPTION_CONTINUE_SEARCH,
+    }
+}
+
+/// Asks the user in a message box whether `count` crash reports may be sent.
+pub fn ask_consent(count: usize) -> bool {
+    let text = match count {
+        1 => "Galleon closed unexpectedly last time. Send the crash report to help fix the problem?"
+            .to_string(),
+        count => format!(
+            "Galleon closed unexpectedly {count} times. Send the crash reports to help fix the problem?"
+        ),
+    };
+    MessageBox::question(text).show() == Response::Yes
+}
+
+/// The machine a report came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemInfo {
+    /// e.g. `Windows 10 Pro 22H2 (build 19045.3803)`.
+    pub os: String,
+    /// The display adapters driving the desktop, primary first.
+    pub gpus: Vec<String>,
+    /// Physical memory in bytes.
+    pub ram: u64,
+    pub cpus: usize,
+}
+
+impl SystemInfo {
+    /// Whatever can't be queried is left empty or zero; a crash report is better incomplete than
+    /// missing.
+    pub fn collect() -> Self {
+        Self {
+            os: os_version(),
+            gpus: gpus(),
+            ram: ram(),
+            cpus: std::thread::available_parallelism().map_or(0, |cpus| cpus.get()),
+        }
+    }
+}
+
+impl std::fmt::Display for SystemInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "os: {}", self.os)?;
+        for gpu in &self.gpus {
+            writeln!(f, "gpu: {gpu}")?;
+        }
+        writeln!(f, "ram: {} MiB", self.ram / (1024 * 1024))?;
+        writeln!(f, "cpus: {}", self.cpus)
+    }
+}
+
+// note: `GetVersionEx` reports 6.2 to processes without a compatibility manifest, so the version
+// comes from the registry instead.
+fn os_version() -> String {
+    const KEY: &str = "SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion";
+
+    let mut os = registry_string(KEY, "ProductName").unwrap_or_else(|| "Windows".to_string());
+    if let Some(display_version) = registry_string(KEY, "DisplayVersion") {
+        _ = write!(os, " {display_version}");
+    }
+    if let Some(build) = registry_string(KEY, "CurrentBuild") {
+        _ = write!(os, " (build {build}");
+        if let Some(revision) = registry_dword(KEY, "UBR") {
+            _ = write!(os, ".{revision}");
+        }
+        os.push(')');
+    }
+
+    os
+}
+
+fn registry_string(key: &str, value: &str) -> Option<String> {
+    let key = wstr!("{key}");
+    let value = wstr!("{value}");
+    let mut buffer = [0u16; 256];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_SZ,
+            std::ptr::null_mut(),
+            buffer.as_mut_ptr().cast(),
+            &mut size,
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+
+    Some(wide::from_wide_lossy(&buffer))
+}
+
+fn registry_dword(key: &str, value: &str) -> Option<u32> {
+    let key = wstr!("{key}");
+    let value = wstr!("{value}");
+    let mut data = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            std::ptr::null_mut(),
+            (&mut data as *mut u32).cast(),
+            &mut size,
+        )
+    };
+
+    (status == 0).then_some(data)
+}
+
+fn gpus() -> Vec<String> {
+    let mut gpus: Vec<String> = Vec::new();
+    for index in 0.. {
+        let mut device: DISPLAY_DEVICEW = unsafe { std::mem::zeroed() };
+        device.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
+        if unsafe { EnumDisplayDevicesW(std::ptr::null(), index, &mut device, 0) } == 0 {
+            break;
+        }
+        if device.StateFlags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP == 0
+            || device.StateFlags & DISPLAY_DEVICE_MIRRORING_DRIVER != 0
+        {
+            continue;
+        }
+
+        // Each output is listed separately, so an adapter driving two monitors appears twice.
+        let name = wide::from_wide_lossy(&device.DeviceString);
+        if !gpus.contains(&name) {
+            gpus.push(name);
+        }
+    }
+
+    gpus
+}
+
+fn ram() -> u64 {
+    let mut status: MEMORYSTATUSEX = unsafe { std::mem::zeroed() };
+    status.dwLength = std::mem::size_of::<MEMORYSTATUSEX>() as u32;
+    if unsafe { GlobalMemoryStatusEx(&mut status) } == 0 {
+        return 0;
+    }
+
+    status.ullTotalPhys
+}
+
+/// An `https://host[:port]/path` URL, POSTed to with WinHTTP so TLS and proxies come from the
+/// system.
+#[derive(Clone)]
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> Result<Self, Error> {
+        let invalid = |reason: &str| {
+            Error::new(format!("invalid crash report endpoint {url}: {reason}"))
+                .with_kind(ErrorKind::Config)
+        };
+
+        let rest = url
+            .strip_prefix("https://")
+            .ok_or_else(|| invalid("only https:// urls are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        // IPv6 hosts are bracketed, e.g. `[::1]:8443`.
+        let port_separator = match authority.rfind(']') {
+            Some(bracket) => authority[bracket..].find(':').map(|index| bracket + index),
+            None => authority.rfind(':'),
+        };
+        let (host, port) = match port_separator {
+            Some(index) => {
+                let port = authority[index + 1..]
+                    .parse()
+                    .map_err(|_| invalid("bad port"))?;
+                (&authority[..index], port)
+            }
+            None => (authority, 443),
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), Error> {
+        let agent = wstr!("Galleon");
+        let host = wstr!("{}", self.host);
+        let verb = wstr!("POST");
+        let path = wstr!("{}", self.path);
+        let headers = wstr!("Content-Type: application/zip\r\n");
+        let timeout = UPLOAD_TIMEOUT.as_millis() as i32;
+
+        unsafe {
+            let session = Internet(WinHttpOpen(
+                agent.as_ptr(),
+                WINHTTP_ACCESS_TYPE_AUTOMATIC_PROXY,
+                std::ptr::null(),
+                std::ptr::null(),
+                0,
+            ));
+            if session.0.is_null() {
+                return Err(last_error("failed to open http session"));
+            }
+            WinHttpSetTimeouts(session.0, timeout, timeout, timeout, timeout);
+
+            let connection = Internet(WinHttpConnect(session.0, host.as_ptr(), self.port, 0));
+            if connection.0.is_null() {
+                return Err(last_error(&format!("failed to connect to {}", self.host)));
+            }
+
+            let request = Internet(WinHttpOpenRequest(
+                connection.0,
+                verb.as_ptr(),
+                path.as_ptr(),
+                std::ptr::null(),
+                std::ptr::null(),
+                std::ptr::null(),
+                WINHTTP_FLAG_SECURE,
+            ));
+            if request.0.is_null() {
+                return Err(last_error("failed to open crash report request"));
+            }
+
+            if WinHttpSendRequest(
+                request.0,
+                headers.as_ptr(),
+                u32::MAX,
+                body.as_ptr().cast(),
+                body.len() as u32,
+                body.len() as u32,
+                0,
+            ) == 0
+                || WinHttpReceiveResponse(request.0, std::ptr::null_mut()) == 0
+            {
+                return Err(last_error(&format!(
+                    "failed to upload crash report to {}",
+                    self.host
+                )));
+            }
+
+            let mut status = 0u32;
+            let mut size = std::mem::size_of::<u32>() as u32;
+            if WinHttpQueryHeaders(
+                request.0,
+                WINHTTP_QUERY_STATUS_CODE | WINHTTP_QUERY_FLAG_NUMBER,
+                std::ptr::null(),
+                (&mut status as *mut u32).cast(),
+                &mut size,
+                std::ptr::null_mut(),
+            ) == 0
+            {
+                return Err(last_error("failed to read crash report response"));
+            }
+
+            if !(200..300).contains(&status) {
+                return Err(Error::new(format!(
+                    "crash report endpoint {} returned {status}",
+                    self.host
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Closes a WinHTTP handle on drop.
+struct Internet(*mut std::ffi::c_void);
+
+impl Drop for Internet {
+    fn drop(&mut self) {
+        if !self.0.is_null() {
+            unsafe { WinHttpCloseHandle(self.0) };
+        }
+    }
+}
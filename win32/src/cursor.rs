@@ -0,0 +1,163 @@
+//! Mouse cursor appearance and confinement.
+
+use std::rc::Rc;
+
+use common::error::Error;
+use windows_sys::{
+    core::PCWSTR,
+    Win32::{
+        Foundation::{HWND, POINT, RECT},
+        Graphics::Gdi::ClientToScreen,
+        System::LibraryLoader::GetModuleHandleW,
+        UI::WindowsAndMessaging::{
+            ClipCursor, DestroyIcon, GetClientRect, LoadCursorW, SetCursorPos, HCURSOR, IDC_ARROW,
+            IDC_CROSS, IDC_HAND, IDC_IBEAM, IDC_NO, IDC_SIZEALL, IDC_SIZENS, IDC_SIZEWE, IDC_WAIT,
+        },
+    },
+};
+
+use crate::{error::last_error, icon};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemCursor {
+    Arrow,
+    /// The I-beam shown over editable text.
+    Text,
+    Wait,
+    Crosshair,
+    Hand,
+    NotAllowed,
+    ResizeHorizontal,
+    ResizeVertical,
+    ResizeAll,
+}
+
+/// A cursor image. Clones share the same image, which is freed when the last one is dropped.
+#[derive(Clone)]
+pub struct Cursor {
+    handle: Rc<CursorHandle>,
+}
+
+struct CursorHandle {
+    hcursor: HCURSOR,
+    // Cursors loaded from resources are shared by the system and must not be destroyed.
+    owned: bool,
+}
+
+impl Drop for CursorHandle {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe { DestroyIcon(self.hcursor) };
+        }
+    }
+}
+
+impl Cursor {
+    pub fn system(cursor: SystemCursor) -> Result<Cursor, Error> {
+        let id = match cursor {
+            SystemCursor::Arrow => IDC_ARROW,
+            SystemCursor::Text => IDC_IBEAM,
+            SystemCursor::Wait => IDC_WAIT,
+            SystemCursor::Crosshair => IDC_CROSS,
+            SystemCursor::Hand => IDC_HAND,
+            SystemCursor::NotAllowed => IDC_NO,
+            SystemCursor::ResizeHorizontal => IDC_SIZEWE,
+            SystemCursor::ResizeVertical => IDC_SIZENS,
+            SystemCursor::ResizeAll => IDC_SIZEALL,
+        };
+
+        Self::load(0, id)
+    }
+
+    /// Loads the cursor resource `id` from the executable.
+    pub fn from_resource(id: u16) -> Result<Cursor, Error> {
+        let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+        Self::load(module, id as usize as PCWSTR)
+    }
+
+    /// Builds a cursor from `width` x `height` RGBA pixels, top row first, with straight (not
+    /// premultiplied) alpha. The hotspot is the pixel that points, e.g. the tip of an arrow.
+    pub fn from_rgba(
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        hotspot_x: u32,
+        hotspot_y: u32,
+    ) -> Result<Cursor, Error> {
+        if hotspot_x >= width || hotspot_y >= height {
+            return Err(Error::new(format!(
+                "cursor hotspot ({hotspot_x}, {hotspot_y}) is outside the {width}x{height} image"
+            )));
+        }
+
+        let hcursor = icon::create(width, height, rgba, Some((hotspot_x, hotspot_y)))?;
+        Ok(Cursor {
+            handle: Rc::new(CursorHandle {
+                hcursor,
+                owned: true,
+            }),
+        })
+    }
+
+    pub fn hcursor(&self) -> HCURSOR {
+        self.handle.hcursor
+    }
+
+    fn load(module: isize, name: PCWSTR) -> Result<Cursor, Error> {
+        let hcursor = unsafe { LoadCursorW(module, name) };
+        if hcursor == 0 {
+            return Err(last_error("failed to load cursor"));
+        }
+
+        Ok(Cursor {
+            handle: Rc::new(CursorHandle {
+                hcursor,
+                owned: false,
+            }),
+        })
+    }
+}
+
+/// Keeps the cursor inside the client area of `hwnd`.
+pub(crate) fn confine(hwnd: HWND) -> Result<(), Error> {
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    let mut top_left = POINT { x: 0, y: 0 };
+    if unsafe { GetClientRect(hwnd, &mut rect) } == 0
+        || unsafe { ClientToScreen(hwnd, &mut top_left) } == 0
+    {
+        return Err(last_error("failed to get window client area"));
+    }
+
+    let rect = RECT {
+        left: top_left.x,
+        top: top_left.y,
+        right: top_left.x + rect.right,
+        bottom: top_left.y + rect.bottom,
+    };
+    if unsafe { ClipCursor(&rect) } == 0 {
+        return Err(last_error("failed to confine cursor"));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn release() {
+    unsafe { ClipCursor(std::ptr::null()) };
+}
+
+/// Moves the cursor to a position in the client area of `hwnd`.
+pub(crate) fn set_position(hwnd: HWND, x: i32, y: i32) -> Result<(), Error> {
+    let mut point = POINT { x, y };
+    if unsafe { ClientToScreen(hwnd, &mut point) } == 0
+        || unsafe { SetCursorPos(point.x, point.y) } == 0
+    {
+        return Err(last_error("failed to move cursor"));
+    }
+
+    Ok(())
+}
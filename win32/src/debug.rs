@@ -0,0 +1,60 @@
+//! Attaching a debugger and stopping in it: checking for one, breaking into it, and holding
+//! startup until one attaches, e.g. to debug a game launched by a store client or a test
+//! harness. [`DebugBreakSink`](crate::logger::DebugBreakSink) also breaks on errors as they're
+//! logged.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use win32::debug;
+//!
+//! if debug::wait_for_debugger(Duration::from_secs(60)) {
+//!     debug::debug_break();
+//! }
+//! ```
+
+use std::time::{Duration, Instant};
+
+use tracing::info;
+use windows_sys::Win32::System::{
+    Diagnostics::Debug::{DebugBreak, IsDebuggerPresent},
+    Threading::GetCurrentProcessId,
+};
+
+/// How often [`wait_for_debugger`] checks for an attached debugger.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Whether a user-mode debugger is attached to the process.
+pub fn is_debugger_present() -> bool {
+    unsafe { IsDebuggerPresent() != 0 }
+}
+
+/// Stops in the attached debugger, as if at a breakpoint on the caller. Does nothing without
+/// one, where the breakpoint would crash the process.
+pub fn debug_break() {
+    if is_debugger_present() {
+        unsafe { DebugBreak() };
+    }
+}
+
+/// Blocks until a debugger attaches or `timeout` passes, returning whether one did.
+pub fn wait_for_debugger(timeout: Duration) -> bool {
+    if is_debugger_present() {
+        return true;
+    }
+
+    let pid = unsafe { GetCurrentProcessId() };
+    info!(
+        "waiting {:.0}s for a debugger to attach to process {pid}",
+        timeout.as_secs_f64()
+    );
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        std::thread::sleep(POLL_INTERVAL);
+        if is_debugger_present() {
+            return true;
+        }
+    }
+
+    false
+}
@@ -0,0 +1,114 @@
+//! Devices being plugged in and unplugged. Every window is registered for audio, HID and monitor
+//! interfaces, and reports them coming and going as
+//! [`Event::Device`](crate::event_loop::Event::Device), so subsystems can switch devices when
+//! it happens rather than polling for them.
+//!
+//! note: a device usually exposes several interfaces of a class, e.g. a headset has a render
+//! and a capture interface, so one plug can give several events.
+
+use common::error::Error;
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        Devices::HumanInterfaceDevice::GUID_DEVINTERFACE_HID,
+        Foundation::{HWND, LPARAM, WPARAM},
+        UI::WindowsAndMessaging::{
+            RegisterDeviceNotificationW, UnregisterDeviceNotification, DBT_DEVICEARRIVAL,
+            DBT_DEVICEREMOVECOMPLETE, DBT_DEVTYP_DEVICEINTERFACE, DEVICE_NOTIFY_WINDOW_HANDLE,
+            DEV_BROADCAST_DEVICEINTERFACE_W, DEV_BROADCAST_HDR, HDEVNOTIFY,
+        },
+    },
+};
+
+use crate::{com::guid_eq, error::last_error, wide::WStr};
+
+/// `KSCATEGORY_AUDIO`: sound cards, USB and Bluetooth headsets, HDMI audio.
+const AUDIO: GUID = GUID::from_u128(0x6994ad04_93ef_11d0_a3cc_00a0c9223196);
+/// `GUID_DEVINTERFACE_MONITOR`.
+const MONITOR: GUID = GUID::from_u128(0xe6f07b5f_ee97_4a90_b076_33f57bf4eaa7);
+
+const CLASSES: [(DeviceKind, GUID); 3] = [
+    (DeviceKind::Audio, AUDIO),
+    (DeviceKind::Hid, GUID_DEVINTERFACE_HID),
+    (DeviceKind::Monitor, MONITOR),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    Audio,
+    /// Gamepads, joysticks, keyboards, mice and other human interface devices.
+    Hid,
+    Monitor,
+}
+
+/// A device interface arriving or going away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceEvent {
+    pub kind: DeviceKind,
+    /// The interface's device path, which is the same for both events.
+    pub path: String,
+    /// True when it arrived, false when it was removed.
+    pub connected: bool,
+}
+
+/// Device notifications registered for a window, unregistered when dropped.
+pub(crate) struct DeviceNotifications(Vec<HDEVNOTIFY>);
+
+impl DeviceNotifications {
+    pub(crate) fn register(hwnd: HWND) -> Result<Self, Error> {
+        let mut notifications = Self(Vec::with_capacity(CLASSES.len()));
+        for (_, class) in CLASSES {
+            let mut filter: DEV_BROADCAST_DEVICEINTERFACE_W = unsafe { std::mem::zeroed() };
+            filter.dbcc_size = std::mem::size_of::<DEV_BROADCAST_DEVICEINTERFACE_W>() as u32;
+            filter.dbcc_devicetype = DBT_DEVTYP_DEVICEINTERFACE;
+            filter.dbcc_classguid = class;
+            let handle = unsafe {
+                RegisterDeviceNotificationW(
+                    hwnd,
+                    (&filter as *const DEV_BROADCAST_DEVICEINTERFACE_W).cast(),
+                    DEVICE_NOTIFY_WINDOW_HANDLE,
+                )
+            };
+            if handle.is_null() {
+                return Err(last_error("failed to register for device notifications"));
+            }
+            notifications.0.push(handle);
+        }
+
+        Ok(notifications)
+    }
+}
+
+impl Drop for DeviceNotifications {
+    fn drop(&mut self) {
+        for handle in self.0.drain(..) {
+            unsafe { UnregisterDeviceNotification(handle) };
+        }
+    }
+}
+
+/// Reads a `WM_DEVICECHANGE` for one of the registered interface classes.
+pub(crate) fn on_device_change(wparam: WPARAM, lparam: LPARAM) -> Option<DeviceEvent> {
+    let connected = match wparam as u32 {
+        DBT_DEVICEARRIVAL => true,
+        DBT_DEVICEREMOVECOMPLETE => false,
+        _ => return None,
+    };
+    let header = unsafe { (lparam as *const DEV_BROADCAST_HDR).as_ref() }?;
+    if header.dbch_devicetype != DBT_DEVTYP_DEVICEINTERFACE {
+        return None;
+    }
+
+    let interface = lparam as *const DEV_BROADCAST_DEVICEINTERFACE_W;
+    let class = unsafe { &(*interface).dbcc_classguid };
+    let (kind, _) = CLASSES.iter().find(|(_, known)| guid_eq(known, class))?;
+    // note: the name runs past the end of the declared struct, up to dbcc_size.
+    let name = unsafe { std::ptr::addr_of!((*interface).dbcc_name) };
+    let path = unsafe { WStr::from_ptr(name.cast()) }.to_string_lossy();
+
+    Some(DeviceEvent {
+        kind: *kind,
+        path,
+        connected,
+    })
+}
@@ -0,0 +1,413 @@
+//! Native dialogs for telling the user something went wrong or asking them a question before there
+//! is, or after there no longer is, a game to show it in.
+//!
+//! ```no_run
+//! use win32::dialog::{MessageBox, Response};
+//!
+//! let answer = MessageBox::question("Overwrite the existing save?").show();
+//! if answer == Response::Yes {
+//!     // ...
+//! }
+//! ```
+
+use std::sync::OnceLock;
+
+use common::error::Error;
+use windows_sys::{
+    core::HRESULT,
+    Win32::{
+        Foundation::{BOOL, HWND},
+        System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+        UI::{
+            Controls::{
+                TASKDIALOGCONFIG, TASKDIALOG_BUTTON, TASKDIALOG_COMMON_BUTTON_FLAGS,
+                TDCBF_CANCEL_BUTTON, TDCBF_NO_BUTTON, TDCBF_OK_BUTTON, TDCBF_RETRY_BUTTON,
+                TDCBF_YES_BUTTON, TDF_ALLOW_DIALOG_CANCELLATION, TDF_POSITION_RELATIVE_TO_WINDOW,
+                TD_ERROR_ICON, TD_INFORMATION_ICON, TD_WARNING_ICON,
+            },
+            WindowsAndMessaging::{
+                MessageBoxW, IDNO, IDOK, IDRETRY, IDYES, MB_ICONERROR, MB_ICONINFORMATION,
+                MB_ICONQUESTION, MB_ICONWARNING, MB_OK, MB_OKCANCEL, MB_RETRYCANCEL,
+                MB_SETFOREGROUND, MB_YESNO, MB_YESNOCANCEL, MESSAGEBOX_STYLE,
+            },
+        },
+    },
+};
+
+use crate::{error::Hresult, wide::WString, window::Window, wstr};
+
+/// The caption used when a dialog isn't given a title.
+const DEFAULT_TITLE: &str = "Galleon";
+
+/// The id of the first button added with [`TaskDialog::button`]; lower ids are the common buttons.
+const FIRST_BUTTON_ID: i32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Icon {
+    Error,
+    Warning,
+    Question,
+    Information,
+}
+
+/// The standard buttons along the bottom of a dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buttons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+    RetryCancel,
+}
+
+/// The button the user chose. Closing a dialog that has a Cancel button, or pressing Escape in
+/// it, is `Cancel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+    Retry,
+    /// One of a [`TaskDialog`]'s own buttons, by the order they were added.
+    Button(usize),
+}
+
+impl Response {
+    fn from_id(id: i32) -> Self {
+        match id {
+            IDOK => Self::Ok,
+            IDYES => Self::Yes,
+            IDNO => Self::No,
+            IDRETRY => Self::Retry,
+            id if id >= FIRST_BUTTON_ID => Self::Button((id - FIRST_BUTTON_ID) as usize),
+            _ => Self::Cancel,
+        }
+    }
+}
+
+/// A modal `MessageBoxW`: a title, some text, an icon and standard buttons.
+#[derive(Debug, Clone)]
+pub struct MessageBox {
+    title: String,
+    text: String,
+    icon: Icon,
+    buttons: Buttons,
+    owner: HWND,
+}
+
+impl MessageBox {
+    pub fn new<S: Into<String>>(text: S, icon: Icon, buttons: Buttons) -> Self {
+        Self {
+            title: DEFAULT_TITLE.to_string(),
+            text: text.into(),
+            icon,
+            buttons,
+            owner: 0,
+        }
+    }
+
+    /// An error with an OK button.
+    pub fn error<S: Into<String>>(text: S) -> Self {
+        Self::new(text, Icon::Error, Buttons::Ok)
+    }
+
+    /// A warning with an OK button.
+    pub fn warning<S: Into<String>>(text: S) -> Self {
+        Self::new(text, Icon::Warning, Buttons::Ok)
+    }
+
+    /// A question with Yes and No buttons.
+    pub fn question<S: Into<String>>(text: S) -> Self {
+        Self::new(text, Icon::Question, Buttons::YesNo)
+    }
+
+    /// The caption. Defaults to "Galleon".
+    pub fn title<S: Into<String>>(self, title: S) -> Self {
+        Self {
+            title: title.into(),
+            ..self
+        }
+    }
+
+    pub fn buttons(self, buttons: Buttons) -> Self {
+        Self { buttons, ..self }
+    }
+
+    /// Makes the dialog modal to `window` rather than to nothing.
+    pub fn owner(self, window: &Window) -> Self {
+        Self {
+            owner: window.hwnd(),
+            ..self
+        }
+    }
+
+    /// Shows the dialog and waits for an answer. `Cancel` if it couldn't be shown.
+    pub fn show(&self) -> Response {
+        let text = wstr!("{}", self.text);
+        let title = wstr!("{}", self.title);
+        let icon = match self.icon {
+            Icon::Error => MB_ICONERROR,
+            Icon::Warning => MB_ICONWARNING,
+            Icon::Question => MB_ICONQUESTION,
+            Icon::Information => MB_ICONINFORMATION,
+        };
+        let buttons: MESSAGEBOX_STYLE = match self.buttons {
+            Buttons::Ok => MB_OK,
+            Buttons::OkCancel => MB_OKCANCEL,
+            Buttons::YesNo => MB_YESNO,
+            Buttons::YesNoCancel => MB_YESNOCANCEL,
+            Buttons::RetryCancel => MB_RETRYCANCEL,
+        };
+        let id = unsafe {
+            MessageBoxW(
+                self.owner,
+                text.as_ptr(),
+                title.as_ptr(),
+                icon | buttons | MB_SETFOREGROUND,
+            )
+        };
+
+        Response::from_id(id)
+    }
+}
+
+/// A `TaskDialogIndirect` dialog: a headline instruction above the text, optional details the user
+/// can expand, a footer and custom buttons. Meant for fatal errors, where the details can hold
+/// the full error chain without burying the message.
+///
+/// note: task dialogs need version 6 of the common controls, which a process only gets by asking
+/// for it in its manifest. Without it, [`show`](TaskDialog::show) falls back to a [`MessageBox`]
+/// with the details appended.
+#[derive(Debug, Clone)]
+pub struct TaskDialog {
+    title: String,
+    instruction: String,
+    content: Option<String>,
+    details: Option<String>,
+    footer: Option<String>,
+    icon: Icon,
+    buttons: Option<Buttons>,
+    custom_buttons: Vec<String>,
+    owner: HWND,
+}
+
+impl TaskDialog {
+    /// `instruction` is the headline, e.g. "Galleon has stopped working".
+    pub fn new<S: Into<String>>(instruction: S) -> Self {
+        Self {
+            title: DEFAULT_TITLE.to_string(),
+            instruction: instruction.into(),
+            content: None,
+            details: None,
+            footer: None,
+            icon: Icon::Information,
+            buttons: None,
+            custom_buttons: Vec::new(),
+            owner: 0,
+        }
+    }
+
+    /// The caption. Defaults to "Galleon".
+    pub fn title<S: Into<String>>(self, title: S) -> Self {
+        Self {
+            title: title.into(),
+            ..self
+        }
+    }
+
+    /// The text below the instruction.
+    pub fn content<S: Into<String>>(self, content: S) -> Self {
+        Self {
+            content: Some(content.into()),
+            ..self
+        }
+    }
+
+    /// Text hidden behind a "See details" toggle, e.g. the error chain and backtrace.
+    pub fn details<S: Into<String>>(self, details: S) -> Self {
+        Self {
+            details: Some(details.into()),
+            ..self
+        }
+    }
+
+    /// A line along the bottom, e.g. where the crash report was written.
+    pub fn footer<S: Into<String>>(self, footer: S) -> Self {
+        Self {
+            footer: Some(footer.into()),
+            ..self
+        }
+    }
+
+    /// Defaults to [`Icon::Information`]. Task dialogs have no question icon, so `Question` shows
+    /// the information icon.
+    pub fn icon(self, icon: Icon) -> Self {
+        Self { icon, ..self }
+    }
+
+    /// The standard buttons. Without any buttons at all the dialog has an OK button.
+    pub fn buttons(self, buttons: Buttons) -> Self {
+        Self {
+            buttons: Some(buttons),
+            ..self
+        }
+    }
+
+    /// Adds a button of its own, reported as [`Response::Button`] with its index among the
+    /// buttons added this way. They appear before the standard buttons.
+    pub fn button<S: Into<String>>(mut self, text: S) -> Self {
+        self.custom_buttons.push(text.into());
+        self
+    }
+
+    /// Makes the dialog modal to `window` and centres it over it.
+    pub fn owner(self, window: &Window) -> Self {
+        Self {
+            owner: window.hwnd(),
+            ..self
+        }
+    }
+
+    /// Shows the dialog and waits for an answer.
+    pub fn show(&self) -> Result<Response, Error> {
+        let Some(task_dialog_indirect) = task_dialog_indirect() else {
+            return Ok(self.fallback());
+        };
+
+        let title = wstr!("{}", self.title);
+        let instruction = wstr!("{}", self.instruction);
+        let content = self.content.as_deref().map(|text| wstr!("{text}"));
+        let details = self.details.as_deref().map(|text| wstr!("{text}"));
+        let footer = self.footer.as_deref().map(|text| wstr!("{text}"));
+        let button_texts: Vec<_> = self
+            .custom_buttons
+            .iter()
+            .map(|text| wstr!("{text}"))
+            .collect();
+        let buttons: Vec<_> = button_texts
+            .iter()
+            .zip(FIRST_BUTTON_ID..)
+            .map(|(text, id)| TASKDIALOG_BUTTON {
+                nButtonID: id,
+                pszButtonText: text.as_ptr(),
+            })
+            .collect();
+
+        let common_buttons: TASKDIALOG_COMMON_BUTTON_FLAGS = match self.buttons {
+            None if buttons.is_empty() => TDCBF_OK_BUTTON,
+            None => 0,
+            Some(Buttons::Ok) => TDCBF_OK_BUTTON,
+            Some(Buttons::OkCancel) => TDCBF_OK_BUTTON | TDCBF_CANCEL_BUTTON,
+            Some(Buttons::YesNo) => TDCBF_YES_BUTTON | TDCBF_NO_BUTTON,
+            Some(Buttons::YesNoCancel) => TDCBF_YES_BUTTON | TDCBF_NO_BUTTON | TDCBF_CANCEL_BUTTON,
+            Some(Buttons::RetryCancel) => TDCBF_RETRY_BUTTON | TDCBF_CANCEL_BUTTON,
+        };
+        let mut flags = TDF_ALLOW_DIALOG_CANCELLATION;
+        if self.owner != 0 {
+            flags |= TDF_POSITION_RELATIVE_TO_WINDOW;
+        }
+
+        let mut config: TASKDIALOGCONFIG = unsafe { std::mem::zeroed() };
+        config.cbSize = std::mem::size_of::<TASKDIALOGCONFIG>() as u32;
+        config.hwndParent = self.owner;
+        config.dwFlags = flags;
+        config.dwCommonButtons = common_buttons;
+        config.pszWindowTitle = title.as_ptr();
+        config.Anonymous1.pszMainIcon = match self.icon {
+            Icon::Error => TD_ERROR_ICON,
+            Icon::Warning => TD_WARNING_ICON,
+            Icon::Question | Icon::Information => TD_INFORMATION_ICON,
+        };
+        config.pszMainInstruction = instruction.as_ptr();
+        config.pszContent = optional_ptr(&content);
+        config.cButtons = buttons.len() as u32;
+        config.pButtons = buttons.as_ptr();
+        config.pszExpandedInformation = optional_ptr(&details);
+        config.pszFooter = optional_ptr(&footer);
+
+        let mut id = 0;
+        let hr = unsafe {
+            task_dialog_indirect(&config, &mut id, std::ptr::null_mut(), std::ptr::null_mut())
+        };
+        Hresult(hr).check("failed to show task dialog")?;
+
+        Ok(Response::from_id(id))
+    }
+
+    fn fallback(&self) -> Response {
+        let mut text = self.instruction.clone();
+        for part in [&self.content, &self.details, &self.footer]
+            .into_iter()
+            .flatten()
+        {
+            text.push_str("\n\n");
+            text.push_str(part);
+        }
+
+        // note: custom buttons can't be offered by a message box, so the first stands in for OK.
+        let buttons = match self.buttons {
+            Some(buttons) => buttons,
+            None if self.custom_buttons.is_empty() => Buttons::Ok,
+            None => Buttons::OkCancel,
+        };
+        let response = MessageBox::new(text, self.icon, buttons)
+            .title(self.title.as_str())
+            .show();
+        match response {
+            Response::Ok if self.buttons.is_none() && !self.custom_buttons.is_empty() => {
+                Response::Button(0)
+            }
+            response => response,
+        }
+    }
+}
+
+/// Shows `err` and its sources as a fatal error, with the backtrace, if it has one, in the
+/// details.
+pub fn show_fatal_error(err: &Error) {
+    let mut details = err.full_message();
+    if let Some(backtrace) = err.backtrace() {
+        details.push_str(&format!("\n\n{backtrace}"));
+    }
+
+    let dialog = TaskDialog::new(format!(
+        "{DEFAULT_TITLE} ran into a problem and has to close."
+    ))
+    .content(err.to_string())
+    .details(details)
+    .icon(Icon::Error);
+    if dialog.show().is_err() {
+        MessageBox::error(err.full_message()).show();
+    }
+}
+
+type TaskDialogIndirectFn = unsafe extern "system" fn(
+    config: *const TASKDIALOGCONFIG,
+    button: *mut i32,
+    radio_button: *mut i32,
+    verification_checked: *mut BOOL,
+) -> HRESULT;
+
+/// `TaskDialogIndirect`, looked up at run time: linking it directly would stop the process from
+/// starting at all when the common controls loaded are older than version 6.
+fn task_dialog_indirect() -> Option<TaskDialogIndirectFn> {
+    static TASK_DIALOG_INDIRECT: OnceLock<Option<TaskDialogIndirectFn>> = OnceLock::new();
+
+    *TASK_DIALOG_INDIRECT.get_or_init(|| {
+        let name = wstr!("comctl32.dll");
+        let module = unsafe { LoadLibraryW(name.as_ptr()) };
+        if module == 0 {
+            return None;
+        }
+
+        let proc = unsafe { GetProcAddress(module, c"TaskDialogIndirect".as_ptr().cast()) }?;
+        Some(unsafe {
+            std::mem::transmute::<unsafe extern "system" fn() -> isize, TaskDialogIndirectFn>(proc)
+        })
+    })
+}
+
+fn optional_ptr(s: &Option<WString>) -> *const u16 {
+    s.as_ref().map_or(std::ptr::null(), |s| s.as_ptr())
+}
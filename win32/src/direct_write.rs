@@ -0,0 +1,571 @@
+//! Glyphs from the installed fonts through DirectWrite, for [`common::text::TextRenderer`].
+//! Characters a font doesn't have come from the first fallback family that does, which by
+//! default covers Japanese, Chinese and Korean:
+//!
+//! ```no_run
+//! use common::{
+//!     error::Error,
+//!     graphics::GraphicsDevice,
+//!     sprite::{Camera2d, SpriteBatch},
+//!     text::{Font, TextRenderer},
+//! };
+//! use win32::direct_write::DirectWrite;
+//!
+//! fn draw_overlay(device: &mut dyn GraphicsDevice, frame_ms: f32) -> Result<(), Error> {
+//!     let mut text = TextRenderer::new(device, DirectWrite::new()?, 1024)?;
+//!     let mut batch = SpriteBatch::new();
+//!     let font = Font::new("Consolas", 16.0);
+//!     text.draw(&mut batch, &font, &format!("{frame_ms:.2} ms"), [8.0, 8.0], [1.0; 4], 100)?;
+//!     text.draw(&mut batch, &font, "ロード中…", [8.0, 28.0], [1.0, 0.8, 0.2, 1.0], 100)?;
+//!
+//!     text.upload(device)?;
+//!     let commands = device.begin_frame()?;
+//!     batch.flush(commands, &Camera2d::new(1280.0, 720.0));
+//!     device.end_frame()
+//! }
+//! ```
+//!
+//! note: glyphs are picked a character at a time, without shaping or kerning, which is right
+//! for Latin, CJK and symbols but not for scripts that join or reorder, such as Arabic.
+
+use std::{collections::HashMap, ffi::c_void};
+
+use common::{
+    error::{Error, ErrorKind},
+    text::{FaceId, Font, Glyph, GlyphBitmap, GlyphRasterizer, LineMetrics},
+};
+use windows_sys::{
+    core::{GUID, HRESULT, PCWSTR},
+    Win32::Foundation::{BOOL, RECT},
+};
+
+use crate::{
+    com::{Apartment, ComGuard, ComPtr, IUnknownVtbl},
+    error::Hresult,
+    wide::ToWide,
+};
+
+const IID_IDWRITE_FACTORY: GUID = GUID::from_u128(0xb859ee5a_d838_4b5b_a2e8_1adc7d93db48);
+
+const DWRITE_FACTORY_TYPE_SHARED: u32 = 0;
+const DWRITE_FONT_STRETCH_NORMAL: u32 = 5;
+const DWRITE_FONT_STYLE_NORMAL: u32 = 0;
+const DWRITE_FONT_STYLE_ITALIC: u32 = 2;
+const DWRITE_RENDERING_MODE_NATURAL_SYMMETRIC: u32 = 5;
+const DWRITE_MEASURING_MODE_NATURAL: u32 = 0;
+const DWRITE_TEXTURE_CLEARTYPE_3X1: u32 = 1;
+
+/// Families tried in order for characters the font doesn't have: the UI fonts for Japanese,
+/// Simplified Chinese and Korean, then symbols and emoji.
+const DEFAULT_FALLBACK: [&str; 6] = [
+    "Segoe UI",
+    "Yu Gothic UI",
+    "Microsoft YaHei UI",
+    "Malgun Gothic",
+    "Segoe UI Symbol",
+    "Segoe UI Emoji",
+];
+
+#[link(name = "dwrite")]
+extern "system" {
+    fn DWriteCreateFactory(kind: u32, iid: *const GUID, factory: *mut *mut c_void) -> HRESULT;
+}
+
+/// A face loaded from the system font collection.
+struct Face {
+    /// The `IDWriteFont`, which knows which characters it has.
+    font: ComPtr,
+    face: ComPtr,
+    metrics: FontMetrics,
+}
+
+/// A [`GlyphRasterizer`] for the fonts installed on the system. It has to be used on the thread
+/// that created it.
+pub struct DirectWrite {
+    factory: ComPtr,
+    collection: ComPtr,
+    fallback: Vec<String>,
+    /// The faces loaded so far, at their [`FaceId`]'s index.
+    faces: Vec<Face>,
+    /// The face of each family, weight and style asked for, or `None` if it isn't installed.
+    fonts: HashMap<(String, u16, bool), Option<usize>>,
+    /// The face, glyph index and advance per em of each character of each font's face.
+    glyphs: HashMap<(usize, char), (usize, u16, f32)>,
+    // note: declared last so that COM outlives the factory.
+    _com: ComGuard,
+}
+
+impl DirectWrite {
+    pub fn new() -> Result<Self, Error> {
+        let com = ComGuard::current_or(Apartment::SingleThreaded)?;
+        let mut factory = std::ptr::null_mut();
+        let hr = unsafe {
+            DWriteCreateFactory(
+                DWRITE_FACTORY_TYPE_SHARED,
+                &IID_IDWRITE_FACTORY,
+                &mut factory,
+            )
+        };
+        let factory =
+            unsafe { ComPtr::from_call(hr, factory, "failed to create the DirectWrite factory") }?;
+
+        let mut collection = std::ptr::null_mut();
+        let hr = unsafe {
+            (factory
+                .vtbl::<IDWriteFactoryVtbl>()
+                .get_system_font_collection)(factory.as_raw(), &mut collection, 0)
+        };
+        let collection =
+            unsafe { ComPtr::from_call(hr, collection, "failed to get the system fonts") }?;
+
+        Ok(Self {
+            factory,
+            collection,
+            fallback: DEFAULT_FALLBACK.map(String::from).to_vec(),
+            faces: Vec::new(),
+            fonts: HashMap::new(),
+            glyphs: HashMap::new(),
+            _com: com,
+        })
+    }
+
+    /// Replaces the families tried, in order, for characters a font doesn't have.
+    pub fn fallback<I, S>(self, families: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            fallback: families.into_iter().map(Into::into).collect(),
+            glyphs: HashMap::new(),
+            ..self
+        }
+    }
+
+    /// The face of `font`'s family, or else of the first fallback family that's installed.
+    fn primary(&mut self, font: &Font) -> Result<usize, Error> {
+        if let Some(face) = self.load(&font.family, font.weight, font.italic)? {
+            return Ok(face);
+        }
+        for index in 0..self.fallback.len() {
+            let family = self.fallback[index].clone();
+            if let Some(face) = self.load(&family, font.weight, font.italic)? {
+                return Ok(face);
+            }
+        }
+
+        Err(Error::new(format!(
+            "neither {:?} nor any fallback font is installed",
+            font.family
+        ))
+        .with_kind(ErrorKind::NotFound))
+    }
+
+    /// The face and glyph for `c`: `primary`'s if it has the character, then the first fallback
+    /// face's that does, or `primary`'s missing glyph if none do.
+    fn find_glyph(
+        &mut self,
+        font: &Font,
+        primary: usize,
+        c: char,
+    ) -> Result<(usize, u16, f32), Error> {
+        let mut face = primary;
+        if !self.faces[primary].has_character(c) {
+            for index in 0..self.fallback.len() {
+                let family = self.fallback[index].clone();
+                match self.load(&family, font.weight, font.italic)? {
+                    Some(fallback) if self.faces[fallback].has_character(c) => {
+                        face = fallback;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let (index, advance) = self.faces[face].glyph(c)?;
+        Ok((face, index, advance))
+    }
+
+    /// Loads the face of `family` closest to `weight` and the style, the first time it's asked
+    /// for. `None` if the family isn't installed.
+    fn load(&mut self, family: &str, weight: u16, italic: bool) -> Result<Option<usize>, Error> {
+        let key = (family.to_owned(), weight, italic);
+        if let Some(face) = self.fonts.get(&key) {
+            return Ok(*face);
+        }
+
+        let face = self.find_family(family)?;
+        let face = match face {
+            Some(font_family) => Some(self.create_face(&font_family, family, weight, italic)?),
+            None => None,
+        };
+        self.fonts.insert(key, face);
+        Ok(face)
+    }
+
+    fn find_family(&self, family: &str) -> Result<Option<ComPtr>, Error> {
+        let vtbl = unsafe { self.collection.vtbl::<IDWriteFontCollectionVtbl>() };
+        let name = family.to_wide();
+        let mut index = 0;
+        let mut exists = 0;
+        let hr = unsafe {
+            (vtbl.find_family_name)(
+                self.collection.as_raw(),
+                name.as_ptr(),
+                &mut index,
+                &mut exists,
+            )
+        };
+        Hresult(hr).check(&format!("failed to look up font family {family:?}"))?;
+        if exists == 0 {
+            return Ok(None);
+        }
+
+        let mut font_family = std::ptr::null_mut();
+        let hr =
+            unsafe { (vtbl.get_font_family)(self.collection.as_raw(), index, &mut font_family) };
+        let context = format!("failed to get font family {family:?}");
+        unsafe { ComPtr::from_call(hr, font_family, &context) }.map(Some)
+    }
+
+    fn create_face(
+        &mut self,
+        font_family: &ComPtr,
+        family: &str,
+        weight: u16,
+        italic: bool,
+    ) -> Result<usize, Error> {
+        let context = format!("failed to load font {family:?}");
+        let style = if italic {
+            DWRITE_FONT_STYLE_ITALIC
+        } else {
+            DWRITE_FONT_STYLE_NORMAL
+        };
+        let mut font = std::ptr::null_mut();
+        let hr = unsafe {
+            (font_family
+                .vtbl::<IDWriteFontFamilyVtbl>()
+                .get_first_matching_font)(
+                font_family.as_raw(),
+                u32::from(weight.clamp(1, 999)),
+                DWRITE_FONT_STRETCH_NORMAL,
+                style,
+                &mut font,
+            )
+        };
+        let font = unsafe { ComPtr::from_call(hr, font, &context) }?;
+
+        let mut face = std::ptr::null_mut();
+        let hr =
+            unsafe { (font.vtbl::<IDWriteFontVtbl>().create_font_face)(font.as_raw(), &mut face) };
+        let face = unsafe { ComPtr::from_call(hr, face, &context) }?;
+        let mut metrics = FontMetrics::default();
+        unsafe { (face.vtbl::<IDWriteFontFaceVtbl>().get_metrics)(face.as_raw(), &mut metrics) };
+
+        self.faces.push(Face {
+            font,
+            face,
+            metrics,
+        });
+        Ok(self.faces.len() - 1)
+    }
+}
+
+impl GlyphRasterizer for DirectWrite {
+    fn glyphs(&mut self, font: &Font, text: &str, glyphs: &mut Vec<Glyph>) -> Result<(), Error> {
+        let primary = self.primary(font)?;
+        for c in text.chars() {
+            let (face, index, advance) = match self.glyphs.get(&(primary, c)) {
+                Some(glyph) => *glyph,
+                None => {
+                    let glyph = self.find_glyph(font, primary, c)?;
+                    self.glyphs.insert((primary, c), glyph);
+                    glyph
+                }
+            };
+            glyphs.push(Glyph {
+                face: FaceId::from_index(face),
+                index,
+                advance: advance * font.size,
+            });
+        }
+        Ok(())
+    }
+
+    fn line_metrics(&mut self, font: &Font) -> Result<LineMetrics, Error> {
+        let primary = self.primary(font)?;
+        let metrics = &self.faces[primary].metrics;
+        let scale = font.size / f32::from(metrics.design_units_per_em.max(1));
+        Ok(LineMetrics {
+            ascent: f32::from(metrics.ascent) * scale,
+            descent: f32::from(metrics.descent) * scale,
+            line_gap: f32::from(metrics.line_gap) * scale,
+        })
+    }
+
+    fn rasterize(&mut self, face: FaceId, glyph: u16, size: f32) -> Result<GlyphBitmap, Error> {
+        let Some(face) = self.faces.get(face.index()) else {
+            return Err(Error::new(format!("there's no font face {}", face.index()))
+                .with_kind(ErrorKind::NotFound));
+        };
+
+        let advance = 0.0;
+        let run = GlyphRun {
+            font_face: face.face.as_raw(),
+            font_em_size: size,
+            glyph_count: 1,
+            glyph_indices: &glyph,
+            glyph_advances: &advance,
+            glyph_offsets: std::ptr::null(),
+            is_sideways: 0,
+            bidi_level: 0,
+        };
+        let mut analysis = std::ptr::null_mut();
+        let hr = unsafe {
+            (self
+                .factory
+                .vtbl::<IDWriteFactoryVtbl>()
+                .create_glyph_run_analysis)(
+                self.factory.as_raw(),
+                &run,
+                1.0,
+                std::ptr::null(),
+                DWRITE_RENDERING_MODE_NATURAL_SYMMETRIC,
+                DWRITE_MEASURING_MODE_NATURAL,
+                0.0,
+                0.0,
+                &mut analysis,
+            )
+        };
+        let context = format!("failed to rasterize glyph {glyph}");
+        let analysis = unsafe { ComPtr::from_call(hr, analysis, &context) }?;
+        let vtbl = unsafe { analysis.vtbl::<IDWriteGlyphRunAnalysisVtbl>() };
+
+        let mut bounds = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        let hr = unsafe {
+            (vtbl.get_alpha_texture_bounds)(
+                analysis.as_raw(),
+                DWRITE_TEXTURE_CLEARTYPE_3X1,
+                &mut bounds,
+            )
+        };
+        Hresult(hr).check(&context)?;
+        let width = (bounds.right - bounds.left).max(0) as u32;
+        let height = (bounds.bottom - bounds.top).max(0) as u32;
+        if width == 0 || height == 0 {
+            return Ok(GlyphBitmap::default());
+        }
+
+        // note: ClearType gives a byte per subpixel, which are averaged into one coverage.
+        let mut texture = vec![0u8; width as usize * height as usize * 3];
+        let hr = unsafe {
+            (vtbl.create_alpha_texture)(
+                analysis.as_raw(),
+                DWRITE_TEXTURE_CLEARTYPE_3X1,
+                &bounds,
+                texture.as_mut_ptr(),
+                texture.len() as u32,
+            )
+        };
+        Hresult(hr).check(&context)?;
+
+        Ok(GlyphBitmap {
+            width,
+            height,
+            offset: [bounds.left, bounds.top],
+            coverage: texture
+                .chunks_exact(3)
+                .map(|rgb| ((u16::from(rgb[0]) + u16::from(rgb[1]) + u16::from(rgb[2])) / 3) as u8)
+                .collect(),
+        })
+    }
+}
+
+impl Face {
+    fn has_character(&self, c: char) -> bool {
+        let mut exists = 0;
+        let hr = unsafe {
+            (self.font.vtbl::<IDWriteFontVtbl>().has_character)(
+                self.font.as_raw(),
+                c as u32,
+                &mut exists,
+            )
+        };
+        hr >= 0 && exists != 0
+    }
+
+    /// The glyph index of `c` and its advance per em.
+    fn glyph(&self, c: char) -> Result<(u16, f32), Error> {
+        let vtbl = unsafe { self.face.vtbl::<IDWriteFontFaceVtbl>() };
+        let mut index = 0;
+        let hr =
+            unsafe { (vtbl.get_glyph_indices)(self.face.as_raw(), &(c as u32), 1, &mut index) };
+        Hresult(hr).check(&format!("failed to get the glyph for {c:?}"))?;
+
+        let mut metrics = GlyphMetrics::default();
+        let hr = unsafe {
+            (vtbl.get_design_glyph_metrics)(self.face.as_raw(), &index, 1, &mut metrics, 0)
+        };
+        Hresult(hr).check(&format!("failed to measure the glyph for {c:?}"))?;
+
+        let units_per_em = f32::from(self.metrics.design_units_per_em.max(1));
+        Ok((index, metrics.advance_width as f32 / units_per_em))
+    }
+}
+
+/// `DWRITE_FONT_METRICS`, in design units.
+#[repr(C)]
+#[derive(Default)]
+struct FontMetrics {
+    design_units_per_em: u16,
+    ascent: u16,
+    descent: u16,
+    line_gap: i16,
+    cap_height: u16,
+    x_height: u16,
+    underline_position: i16,
+    underline_thickness: u16,
+    strikethrough_position: i16,
+    strikethrough_thickness: u16,
+}
+
+/// `DWRITE_GLYPH_METRICS`, in design units.
+#[repr(C)]
+#[derive(Default)]
+struct GlyphMetrics {
+    left_side_bearing: i32,
+    advance_width: u32,
+    right_side_bearing: i32,
+    top_side_bearing: i32,
+    advance_height: u32,
+    bottom_side_bearing: i32,
+    vertical_origin_y: i32,
+}
+
+/// `DWRITE_GLYPH_RUN`.
+#[repr(C)]
+struct GlyphRun {
+    font_face: *mut c_void,
+    font_em_size: f32,
+    glyph_count: u32,
+    glyph_indices: *const u16,
+    glyph_advances: *const f32,
+    glyph_offsets: *const c_void,
+    is_sideways: BOOL,
+    bidi_level: u32,
+}
+
+/// The start of `IDWriteFactory`'s vtable, up to the last method used.
+#[repr(C)]
+struct IDWriteFactoryVtbl {
+    _base: IUnknownVtbl,
+    get_system_font_collection: unsafe extern "system" fn(
+        this: *mut c_void,
+        collection: *mut *mut c_void,
+        check_for_updates: BOOL,
+    ) -> HRESULT,
+    /// CreateCustomFontCollection to CreateNumberSubstitution.
+    _create_custom_font_collection: [usize; 19],
+    create_glyph_run_analysis: unsafe extern "system" fn(
+        this: *mut c_void,
+        run: *const GlyphRun,
+        pixels_per_dip: f32,
+        transform: *const c_void,
+        rendering_mode: u32,
+        measuring_mode: u32,
+        baseline_origin_x: f32,
+        baseline_origin_y: f32,
+        analysis: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDWriteFontCollectionVtbl {
+    _base: IUnknownVtbl,
+    _get_font_family_count: usize,
+    get_font_family: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: u32,
+        family: *mut *mut c_void,
+    ) -> HRESULT,
+    find_family_name: unsafe extern "system" fn(
+        this: *mut c_void,
+        name: PCWSTR,
+        index: *mut u32,
+        exists: *mut BOOL,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDWriteFontFamilyVtbl {
+    _base: IUnknownVtbl,
+    // IDWriteFontList
+    _get_font_collection: usize,
+    _get_font_count: usize,
+    _get_font: usize,
+    // IDWriteFontFamily
+    _get_family_names: usize,
+    get_first_matching_font: unsafe extern "system" fn(
+        this: *mut c_void,
+        weight: u32,
+        stretch: u32,
+        style: u32,
+        font: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDWriteFontVtbl {
+    _base: IUnknownVtbl,
+    /// GetFontFamily to GetMetrics.
+    _get_font_family: [usize; 9],
+    has_character: unsafe extern "system" fn(
+        this: *mut c_void,
+        unicode_value: u32,
+        exists: *mut BOOL,
+    ) -> HRESULT,
+    create_font_face:
+        unsafe extern "system" fn(this: *mut c_void, face: *mut *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDWriteFontFaceVtbl {
+    _base: IUnknownVtbl,
+    /// GetType to IsSymbolFont.
+    _get_type: [usize; 5],
+    get_metrics: unsafe extern "system" fn(this: *mut c_void, metrics: *mut FontMetrics),
+    _get_glyph_count: usize,
+    get_design_glyph_metrics: unsafe extern "system" fn(
+        this: *mut c_void,
+        indices: *const u16,
+        count: u32,
+        metrics: *mut GlyphMetrics,
+        is_sideways: BOOL,
+    ) -> HRESULT,
+    get_glyph_indices: unsafe extern "system" fn(
+        this: *mut c_void,
+        code_points: *const u32,
+        count: u32,
+        indices: *mut u16,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IDWriteGlyphRunAnalysisVtbl {
+    _base: IUnknownVtbl,
+    get_alpha_texture_bounds:
+        unsafe extern "system" fn(this: *mut c_void, kind: u32, bounds: *mut RECT) -> HRESULT,
+    create_alpha_texture: unsafe extern "system" fn(
+        this: *mut c_void,
+        kind: u32,
+        bounds: *const RECT,
+        texture: *mut u8,
+        len: u32,
+    ) -> HRESULT,
+}
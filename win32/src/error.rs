@@ -0,0 +1,5 @@
+//! Turning Win32 error codes and HRESULTs into [`Error`](common::error::Error)s with the system's
+//! description of the failure. They live in `win32-base`, so the renderers report HRESULTs the
+//! same way.
+
+pub use win32_base::error::{last_error, win32_error, Hresult, Win32Error};
@@ -0,0 +1,594 @@
+//! Drives an [`App`] from the thread's message queue: pump messages, tick the clock and poll
+//! gamepads, hand the app what they turned into, then run the fixed-rate simulation steps that
+//! are due, update and render, and wait out the rest of the frame if there's a frame rate cap.
+//!
+//! Each frame also reports to [`common::metrics`]: `frame_time_ms` records the unscaled frame
+//! time, `fixed_steps` counts simulation steps and `fixed_steps_dropped` counts the steps skipped
+//! to catch up after a long frame. The loop closes the metrics frame after rendering.
+//!
+//! With a [`Watchdog`] set, each frame pets it, so a loop that stops making progress is
+//! reported as hung.
+//!
+//! With a [`SingleInstance`] set, command lines forwarded by later instances arrive as
+//! [`Event::SecondInstance`], and with a [`TrayIcon`] set, its clicks arrive as [`Event::Tray`].
+//! Clicks on toasts shown through a [`Notifier`] arrive as [`Event::Toast`], and child
+//! processes spawned through [`Processes`] exiting arrive as [`Event::Process`]. Files changing
+//! under a [`FileWatcher`]'s root arrive as [`Event::FileChanged`], e.g. to hot reload assets.
+//!
+//! With a frame stats [`RingWriter`] set, each frame also pushes its [`FrameStats`] for an
+//! external profiler or inspector to read.
+
+use std::{marker::PhantomData, time::Duration};
+
+use common::{counter, error::Error, histogram, log, metrics};
+use tracing::warn;
+use windows_sys::Win32::UI::WindowsAndMessaging::{
+    DispatchMessageW, PeekMessageW, TranslateMessage, WaitMessage, MSG, PM_REMOVE, WM_QUIT,
+};
+
+use crate::{
+    device::DeviceEvent,
+    file_drop::FileDropEvent,
+    file_watcher::{FileChangeEvent, FileWatcher},
+    gamepad::{GamepadBackend, GamepadEvent, Gamepads},
+    keyboard::{Key, KeyEvent, KeyState},
+    mouse::{MouseButtonEvent, MouseDelta, WheelDelta},
+    process::{ProcessEvent, Processes},
+    shared_memory::RingWriter,
+    single_instance::SingleInstance,
+    text_input::ImeEvent,
+    theme::Theme,
+    time::{Clock, FrameLimiter, Time},
+    toast::{Notifier, ToastEvent},
+    tray::{TrayEvent, TrayIcon},
+    watchdog::Watchdog,
+    window::{Fullscreen, PhysicalPosition, PhysicalSize, Window},
+};
+
+/// Something that happened to a window.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The user asked to close the window, e.g. with the close button or Alt+F4.
+    CloseRequested,
+    /// The client area changed size. Not sent when the window is minimized.
+    Resized(PhysicalSize),
+    /// The window moved to a monitor with a different DPI, or the user changed the scaling. A
+    /// `Resized` follows with the new physical size.
+    ScaleFactorChanged(f64),
+    /// The window gained (`true`) or lost (`false`) keyboard focus. Keys still held when focus
+    /// is lost get a `Released` event first, since their key-up goes to another window.
+    FocusChanged(bool),
+    Key(KeyEvent),
+    /// The cursor moved to a new position in client coordinates. Only sent while the cursor is
+    /// over the client area or the mouse is captured.
+    CursorMoved(PhysicalPosition),
+    /// Typed text, while text input is on. See [`Window::set_text_input`].
+    Text(String),
+    /// IME composition, while text input is on.
+    Ime(ImeEvent),
+    MouseButton(MouseButtonEvent),
+    MouseWheel(WheelDelta),
+    /// Unaccelerated relative motion, if raw mouse input is on. Keeps coming when the cursor is
+    /// confined or at the edge of the screen.
+    MouseMotion(MouseDelta),
+    Gamepad(GamepadEvent),
+    /// Files were dragged onto the window from Explorer, if it accepts them. See
+    /// [`Window::set_accept_files`].
+    FilesDropped(FileDropEvent),
+    /// An audio, HID or monitor device was plugged in or unplugged.
+    Device(DeviceEvent),
+    /// The window's theme changed, following the system's or set with [`Window::set_theme`].
+    ThemeChanged(Theme),
+    /// Another copy of the game was started and exited, handing over its arguments without the
+    /// program name. The window has already been brought to the foreground.
+    SecondInstance(Vec<String>),
+    /// The tray icon was clicked or one of its menu items chosen. See
+    /// [`EventLoop::set_tray_icon`].
+    Tray(TrayEvent),
+    /// A toast, or its action button, was clicked. See [`EventLoop::set_notifier`].
+    Toast(ToastEvent),
+    /// A child process exited. See [`EventLoop::set_processes`].
+    Process(ProcessEvent),
+    /// A file under the watched root changed. See [`EventLoop::set_file_watcher`].
+    FileChanged(FileChangeEvent),
+}
+
+/// Whether the event loop keeps running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlFlow {
+    Continue,
+    Exit,
+}
+
+/// What the event loop hands the app each frame.
+pub struct Context<'a> {
+    window: &'a Window,
+    gamepads: &'a Gamepads,
+    clock: &'a Clock,
+    tray_icon: Option<&'a TrayIcon>,
+    notifier: Option<&'a Notifier>,
+    processes: Option<&'a Processes>,
+    fixed_timestep: Duration,
+    alpha: f64,
+}
+
+impl Context<'_> {
+    pub fn window(&self) -> &Window {
+        self.window
+    }
+
+    /// The gamepads as of this frame's poll.
+    pub fn gamepads(&self) -> &Gamepads {
+        self.gamepads
+    }
+
+    /// This frame's timing.
+    pub fn time(&self) -> Time {
+        self.clock.time()
+    }
+
+    /// E.g. to change the time scale.
+    pub fn clock(&self) -> &Clock {
+        self.clock
+    }
+
+    /// The icon set with [`EventLoop::set_tray_icon`], e.g. to update its tooltip.
+    pub fn tray_icon(&self) -> Option<&TrayIcon> {
+        self.tray_icon
+    }
+
+    /// The notifier set with [`EventLoop::set_notifier`], e.g. to toast that a build finished.
+    pub fn notifier(&self) -> Option<&Notifier> {
+        self.notifier
+    }
+
+    /// The processes set with [`EventLoop::set_processes`], e.g. to start a cook.
+    pub fn processes(&self) -> Option<&Processes> {
+        self.processes
+    }
+
+    /// The simulated time each `fixed_update` advances by.
+    pub fn fixed_timestep(&self) -> Duration {
+        self.fixed_timestep
+    }
+
+    /// How far between the last fixed step and the next one this frame is, from 0 to 1. Render
+    /// blends the previous and current simulation state by it, so motion stays smooth when the
+    /// frame rate and the simulation rate differ.
+    pub fn interpolation_alpha(&self) -> f64 {
+        self.alpha
+    }
+}
+
+pub trait App {
+    /// Called for each event before the frame's update. By default closing the window exits.
+    fn event(&mut self, cx: &Context, event: &Event) -> ControlFlow {
+        _ = cx;
+        match event {
+            Event::CloseRequested => ControlFlow::Exit,
+            _ => ControlFlow::Continue,
+        }
+    }
+
+    /// Called zero or more times a frame after the events, once for each fixed timestep of scaled
+    /// time that has passed, so simulation runs at the same rate whatever the frame rate. Advance
+    /// by [`Context::fixed_timestep`] rather than the frame time.
+    fn fixed_update(&mut self, cx: &Context) -> Result<ControlFlow, Error> {
+        _ = cx;
+        Ok(ControlFlow::Continue)
+    }
+
+    /// Called once per frame after the fixed updates.
+    fn update(&mut self, cx: &Context) -> Result<ControlFlow, Error>;
+
+    /// Called once per frame after `update`, unless the window is minimized. See
+    /// [`Context::interpolation_alpha`].
+    fn render(&mut self, cx: &Context) -> Result<(), Error> {
+        _ = cx;
+        Ok(())
+    }
+}
+
+pub struct EventLoop {
+    gamepads: Gamepads,
+    clock: Clock,
+    fixed_timestep: Duration,
+    max_fixed_steps: u32,
+    accumulator: Duration,
+    frame_limiter: Option<FrameLimiter>,
+    fullscreen_toggle: Option<Fullscreen>,
+    watchdog: Option<Watchdog>,
+    single_instance: Option<SingleInstance>,
+    tray_icon: Option<TrayIcon>,
+    notifier: Option<Notifier>,
+    processes: Option<Processes>,
+    file_watcher: Option<FileWatcher>,
+    frame_stats: Option<RingWriter>,
+    // Messages are only delivered to the thread that created the window.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl EventLoop {
+    pub fn new() -> Self {
+        Self::with_gamepad_backend(GamepadBackend::default())
+    }
+
+    pub fn with_gamepad_backend(backend: GamepadBackend) -> Self {
+        Self {
+            gamepads: Gamepads::with_backend(backend),
+            clock: Clock::new(),
+            fixed_timestep: Duration::from_secs(1) / 60,
+            max_fixed_steps: 8,
+            accumulator: Duration::ZERO,
+            frame_limiter: None,
+            fullscreen_toggle: Some(Fullscreen::Borderless),
+            watchdog: None,
+            single_instance: None,
+            tray_icon: None,
+            notifier: None,
+            processes: None,
+            file_watcher: None,
+            frame_stats: None,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// E.g. to change deadzones before running.
+    pub fn gamepads_mut(&mut self) -> &mut Gamepads {
+        &mut self.gamepads
+    }
+
+    /// The simulated time each `fixed_update` advances by. Defaults to 1/60 s.
+    pub fn set_fixed_timestep(&mut self, timestep: Duration) {
+        self.fixed_timestep = timestep.max(Duration::from_micros(100));
+    }
+
+    /// The most fixed updates run in one frame. Defaults to 8. Time beyond that is dropped,
+    /// slowing the simulation down rather than letting frames that can't keep up get longer and
+    /// longer as they try to catch up.
+    pub fn set_max_fixed_steps(&mut self, max_steps: u32) {
+        self.max_fixed_steps = max_steps.max(1);
+    }
+
+    /// Caps the frame rate, e.g. to save power in menus or when vsync is off. `None`, the
+    /// default, runs frames as fast as the app renders them.
+    pub fn set_max_fps(&mut self, max_fps: Option<f64>) {
+        match (max_fps, self.frame_limiter.as_mut()) {
+            (Some(max_fps), Some(limiter)) => limiter.set_max_fps(max_fps),
+            (Some(max_fps), None) => self.frame_limiter = Some(FrameLimiter::new(max_fps)),
+            (None, _) => self.frame_limiter = None,
+        }
+    }
+
+    /// The mode Alt+Enter switches between it and windowed, `Borderless` by default. `None` leaves
+    /// Alt+Enter to the app. Handled presses aren't passed on to the app.
+    pub fn set_fullscreen_toggle(&mut self, fullscreen: Option<Fullscreen>) {
+        self.fullscreen_toggle = fullscreen;
+    }
+
+    /// Pets `watchdog` every frame, pausing it while the loop sleeps minimized. It has to have
+    /// been built on this thread.
+    pub fn set_watchdog(&mut self, watchdog: Option<Watchdog>) {
+        self.watchdog = watchdog;
+    }
+
+    /// Delivers the command lines forwarded by later instances as [`Event::SecondInstance`], and
+    /// has the window raised when they arrive.
+    pub fn set_single_instance(&mut self, single_instance: Option<SingleInstance>) {
+        self.single_instance = single_instance;
+    }
+
+    /// Delivers `tray_icon`'s clicks and menu choices as [`Event::Tray`], and keeps it in the
+    /// notification area until the loop is dropped or the icon replaced.
+    pub fn set_tray_icon(&mut self, tray_icon: Option<TrayIcon>) {
+        self.tray_icon = tray_icon;
+    }
+
+    /// Delivers clicks on the toasts `notifier` shows as [`Event::Toast`]. It has to have been
+    /// created on this thread.
+    pub fn set_notifier(&mut self, notifier: Option<Notifier>) {
+        self.notifier = notifier;
+    }
+
+    /// Delivers the exits of the processes spawned through `processes` as [`Event::Process`].
+    /// It has to have been created on this thread.
+    pub fn set_processes(&mut self, processes: Option<Processes>) {
+        self.processes = processes;
+    }
+
+    /// Delivers changes to the files under `file_watcher`'s root as [`Event::FileChanged`]. It
+    /// has to have been created on this thread.
+    pub fn set_file_watcher(&mut self, file_watcher: Option<FileWatcher>) {
+        self.file_watcher = file_watcher;
+    }
+
+    /// Pushes each frame's [`FrameStats`] to `frame_stats`. Frames the reader hasn't made room
+    /// for are dropped rather than slowing the game down.
+    pub fn set_frame_stats(&mut self, frame_stats: Option<RingWriter>) {
+        self.frame_stats = frame_stats;
+    }
+
+    /// Runs `app` against `window` until the app exits, an update or render fails, or `WM_QUIT`
+    /// is posted. While the window is minimized the loop sleeps until the next message rather
+    /// than spinning through empty frames.
+    pub fn run<A: App>(&mut self, window: &Window, app: &mut A) -> Result<(), Error> {
+        self.gamepads.attach(window)?;
+        if let Some(single_instance) = &self.single_instance {
+            single_instance.set_window(window);
+        }
+
+        loop {
+            if !pump_messages() {
+                return Ok(());
+            }
+            if let Some(watchdog) = &self.watchdog {
+                watchdog.pet();
+            }
+
+            let time = self.clock.tick();
+            if window.take_hid_arrived() {
+                self.gamepads.rescan();
+            }
+            let gamepad_events = self.gamepads.poll();
+            let forwarded = match &self.single_instance {
+                Some(single_instance) => single_instance.take_forwarded(),
+                None => Vec::new(),
+            };
+            let mut cx = Context {
+                window,
+                gamepads: &self.gamepads,
+                clock: &self.clock,
+                tray_icon: self.tray_icon.as_ref(),
+                notifier: self.notifier.as_ref(),
+                processes: self.processes.as_ref(),
+                fixed_timestep: self.fixed_timestep,
+                alpha: 0.0,
+            };
+
+            let tray_icon = self.tray_icon.as_ref();
+            let notifier = self.notifier.as_ref();
+            let processes = self.processes.as_ref();
+            let file_watcher = self.file_watcher.as_ref();
+            let events = std::iter::from_fn(|| window.next_event())
+                .chain(std::iter::from_fn(|| tray_icon?.next_event()).map(Event::Tray))
+                .chain(std::iter::from_fn(|| notifier?.next_event()).map(Event::Toast))
+                .chain(std::iter::from_fn(|| processes?.next_event()).map(Event::Process))
+                .chain(std::iter::from_fn(|| file_watcher?.next_event()).map(Event::FileChanged))
+                .chain(gamepad_events.into_iter().map(Event::Gamepad))
+                .chain(forwarded.into_iter().map(Event::SecondInstance));
+            for event in events {
+                if let Some(fullscreen) = self.fullscreen_toggle.filter(|_| is_alt_enter(&event)) {
+                    let target = match window.fullscreen() {
+                        Fullscreen::Windowed => fullscreen,
+                        _ => Fullscreen::Windowed,
+                    };
+                    if let Err(err) = window.set_fullscreen(target) {
+                        warn!(
+                            error = &err as &dyn std::error::Error,
+                            "failed to toggle fullscreen"
+                        );
+                    }
+                    continue;
+                }
+
+                if app.event(&cx, &event) == ControlFlow::Exit {
+                    return Ok(());
+                }
+            }
+
+            if window.is_minimized() {
+                if let Some(watchdog) = &self.watchdog {
+                    watchdog.pause();
+                }
+                unsafe { WaitMessage() };
+                continue;
+            }
+
+            self.accumulator += time.delta();
+            let FixedSteps {
+                steps,
+                dropped: dropped_steps,
+                remainder,
+            } = fixed_steps(self.accumulator, self.fixed_timestep, self.max_fixed_steps);
+            self.accumulator = remainder;
+            if dropped_steps > 0 {
+                counter!("fixed_steps_dropped").add(u64::from(dropped_steps));
+            }
+            for _ in 0..steps {
+                if app.fixed_update(&cx)? == ControlFlow::Exit {
+                    return Ok(());
+                }
+            }
+            counter!("fixed_steps").add(u64::from(steps));
+            cx.alpha = self.accumulator.as_secs_f64() / self.fixed_timestep.as_secs_f64();
+
+            if app.update(&cx)? == ControlFlow::Exit {
+                return Ok(());
+            }
+            app.render(&cx)?;
+            if let Some(limiter) = self.frame_limiter.as_mut() {
+                limiter.wait();
+            }
+
+            histogram!("frame_time_ms").record_duration(time.unscaled_delta());
+            log::frame_mark();
+            if let Some(writer) = &self.frame_stats {
+                let stats = FrameStats {
+                    frame: time.frame(),
+                    frame_time: time.unscaled_delta(),
+                    fixed_steps: steps,
+                    fixed_steps_dropped: dropped_steps,
+                };
+                writer.push(&stats.to_bytes());
+            }
+            metrics::end_frame();
+        }
+    }
+}
+
+/// What one frame took, as pushed to the ring buffer set with [`EventLoop::set_frame_stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameStats {
+    pub frame: u64,
+    /// The unscaled frame time.
+    pub frame_time: Duration,
+    pub fixed_steps: u32,
+    /// Steps skipped to catch up after a long frame.
+    pub fixed_steps_dropped: u32,
+}
+
+impl FrameStats {
+    /// The size of a record, which starts with `frame` and `frame_time` in nanoseconds as
+    /// little-endian `u64`s, then the steps as little-endian `u32`s.
+    pub const SIZE: usize = 24;
+
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        bytes[0..8].copy_from_slice(&self.frame.to_le_bytes());
+        bytes[8..16].copy_from_slice(&(self.frame_time.as_nanos() as u64).to_le_bytes());
+        bytes[16..20].copy_from_slice(&self.fixed_steps.to_le_bytes());
+        bytes[20..24].copy_from_slice(&self.fixed_steps_dropped.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a record, or `None` if it's too short to be one.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; Self::SIZE] = bytes.get(..Self::SIZE)?.try_into().ok()?;
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+
+        Some(Self {
+            frame: u64_at(0),
+            frame_time: Duration::from_nanos(u64_at(8)),
+            fixed_steps: u32_at(16),
+            fixed_steps_dropped: u32_at(20),
+        })
+    }
+}
+
+/// The fixed steps a frame runs for the time accumulated since the last one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FixedSteps {
+    steps: u32,
+    /// Whole steps beyond `max_steps`, which are skipped.
+    dropped: u32,
+    /// Time left over for the next frame, less than one timestep.
+    remainder: Duration,
+}
+
+/// Splits `accumulator` into whole `timestep`s, running at most `max_steps` of them.
+fn fixed_steps(accumulator: Duration, timestep: Duration, max_steps: u32) -> FixedSteps {
+    let (accumulator, timestep) = (accumulator.as_nanos(), timestep.as_nanos());
+    let total = u32::try_from(accumulator / timestep).unwrap_or(u32::MAX);
+    let steps = total.min(max_steps);
+
+    FixedSteps {
+        steps,
+        dropped: total - steps,
+        remainder: Duration::from_nanos((accumulator % timestep) as u64),
+    }
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_alt_enter(event: &Event) -> bool {
+    match event {
+        Event::Key(KeyEvent {
+            key: Key::Enter,
+            state: KeyState::Pressed,
+            modifiers,
+            ..
+        }) => modifiers.alt && !modifiers.control && !modifiers.shift && !modifiers.logo,
+        _ => false,
+    }
+}
+
+/// Dispatches every queued message on this thread. Returns false once `WM_QUIT` arrives.
+fn pump_messages() -> bool {
+    let mut msg: MSG = unsafe { std::mem::zeroed() };
+    while unsafe { PeekMessageW(&mut msg, 0, 0, 0, PM_REMOVE) } != 0 {
+        if msg.message == WM_QUIT {
+            return false;
+        }
+        unsafe {
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMESTEP: Duration = Duration::from_millis(10);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn runs_whole_steps_and_carries_the_rest() {
+        assert_eq!(
+            fixed_steps(ms(25), TIMESTEP, 8),
+            FixedSteps {
+                steps: 2,
+                dropped: 0,
+                remainder: ms(5),
+            }
+        );
+        assert_eq!(fixed_steps(ms(9), TIMESTEP, 8).steps, 0);
+        assert_eq!(fixed_steps(ms(9), TIMESTEP, 8).remainder, ms(9));
+        assert_eq!(fixed_steps(ms(10), TIMESTEP, 8).remainder, Duration::ZERO);
+    }
+
+    #[test]
+    fn remainders_add_up_over_frames() {
+        let mut accumulator = Duration::ZERO;
+        let mut steps = Vec::new();
+        for _ in 0..4 {
+            accumulator += ms(6);
+            let frame = fixed_steps(accumulator, TIMESTEP, 8);
+            steps.push(frame.steps);
+            accumulator = frame.remainder;
+        }
+        // 24 ms in all: two steps, with 4 ms left for the next frame.
+        assert_eq!(steps, [0, 1, 0, 1]);
+        assert_eq!(accumulator, ms(4));
+    }
+
+    #[test]
+    fn long_frames_are_clamped_to_max_steps() {
+        assert_eq!(
+            fixed_steps(ms(1003), TIMESTEP, 8),
+            FixedSteps {
+                steps: 8,
+                dropped: 92,
+                remainder: ms(3),
+            }
+        );
+        assert_eq!(
+            fixed_steps(ms(80), TIMESTEP, 8),
+            FixedSteps {
+                steps: 8,
+                dropped: 0,
+                remainder: Duration::ZERO,
+            }
+        );
+    }
+
+    #[test]
+    fn absurd_frames_saturate_rather_than_wrap() {
+        let frame = fixed_steps(Duration::from_secs(u64::MAX), Duration::from_micros(100), 8);
+        assert_eq!(frame.steps, 8);
+        assert_eq!(frame.dropped, u32::MAX - 8);
+        assert!(frame.remainder < Duration::from_micros(100));
+    }
+}
@@ -0,0 +1,67 @@
+//! Files dragged onto a window from Explorer, for windows that accept them. See
+//! [`Window::set_accept_files`](crate::window::Window::set_accept_files).
+
+use std::path::PathBuf;
+
+use windows_sys::Win32::{
+    Foundation::{HWND, POINT},
+    UI::{
+        Shell::{DragAcceptFiles, DragFinish, DragQueryFileW, DragQueryPoint, HDROP},
+        WindowsAndMessaging::{
+            ChangeWindowMessageFilterEx, MSGFLT_ALLOW, WM_COPYDATA, WM_DROPFILES,
+        },
+    },
+};
+
+use crate::{wide, window::PhysicalPosition};
+
+/// Not in the Windows headers, but part of how a drop reaches the window.
+const WM_COPYGLOBALDATA: u32 = 0x0049;
+
+/// Files dropped on the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDropEvent {
+    pub paths: Vec<PathBuf>,
+    /// Where the files were dropped, in client coordinates.
+    pub position: PhysicalPosition,
+}
+
+pub(crate) fn accept_files(hwnd: HWND, accept: bool) {
+    unsafe { DragAcceptFiles(hwnd, i32::from(accept)) };
+
+    // note: an elevated process doesn't get drops from a non-elevated Explorer unless it lets
+    // these messages through.
+    if accept {
+        for message in [WM_DROPFILES, WM_COPYDATA, WM_COPYGLOBALDATA] {
+            unsafe {
+                ChangeWindowMessageFilterEx(hwnd, message, MSGFLT_ALLOW, std::ptr::null_mut())
+            };
+        }
+    }
+}
+
+/// Reads and releases the drop handle from `WM_DROPFILES`.
+pub(crate) fn take_drop(hdrop: HDROP) -> FileDropEvent {
+    let count = unsafe { DragQueryFileW(hdrop, u32::MAX, std::ptr::null_mut(), 0) };
+    let mut paths = Vec::with_capacity(count as usize);
+    let mut buffer = Vec::new();
+    for index in 0..count {
+        let len = unsafe { DragQueryFileW(hdrop, index, std::ptr::null_mut(), 0) };
+        buffer.resize(len as usize + 1, 0);
+        let len = unsafe { DragQueryFileW(hdrop, index, buffer.as_mut_ptr(), len + 1) };
+        if len > 0 {
+            paths.push(PathBuf::from(wide::from_wide_os(&buffer[..len as usize])));
+        }
+    }
+
+    let mut point = POINT { x: 0, y: 0 };
+    unsafe {
+        DragQueryPoint(hdrop, &mut point);
+        DragFinish(hdrop);
+    }
+
+    FileDropEvent {
+        paths,
+        position: PhysicalPosition::new(point.x, point.y),
+    }
+}
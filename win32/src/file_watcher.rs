@@ -0,0 +1,313 @@
+//! Watches a directory tree, e.g. the asset root, for files being written, created, renamed or
+//! deleted, so shaders, textures and configs can be reloaded while the game runs. Changes arrive
+//! as [`Event::FileChanged`](crate::event_loop::Event::FileChanged) once the [`FileWatcher`] is
+//! handed to [`EventLoop::set_file_watcher`](crate::event_loop::EventLoop::set_file_watcher).
+//!
+//! Editors tend to save in several steps, e.g. write a temporary file then rename it over the
+//! original, so changes are held until the tree has been quiet for a moment and each path is
+//! reported once.
+//!
+//! ```no_run
+//! use win32::{event_loop::EventLoop, file_watcher::FileWatcher};
+//!
+//! let mut event_loop = EventLoop::new();
+//! event_loop.set_file_watcher(Some(FileWatcher::new("assets")?));
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use common::error::Error;
+use tracing::warn;
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, FALSE, HANDLE, INVALID_HANDLE_VALUE, TRUE, WAIT_OBJECT_0},
+    Storage::FileSystem::{
+        CreateFileW, ReadDirectoryChangesW, FILE_ACTION_REMOVED, FILE_ACTION_RENAMED_OLD_NAME,
+        FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OVERLAPPED, FILE_LIST_DIRECTORY,
+        FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE, FILE_NOTIFY_CHANGE_SIZE,
+        FILE_NOTIFY_INFORMATION, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+        OPEN_EXISTING,
+    },
+    System::{
+        Threading::{CreateEventW, GetCurrentThreadId, SetEvent, WaitForMultipleObjects, INFINITE},
+        IO::{CancelIoEx, GetOverlappedResult, OVERLAPPED},
+    },
+    UI::WindowsAndMessaging::{PostThreadMessageW, WM_NULL},
+};
+
+use crate::{error::last_error, wide::ToWide};
+
+/// How long the tree has to be quiet before the changes so far are reported.
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Size of the buffer the system fills with changes. If more pile up between reads than fit, they
+/// are lost and a warning is logged.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+    /// The file was created, written to or renamed to this path.
+    Modified,
+    /// The file was deleted or renamed away from this path.
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChangeEvent {
+    /// The file's path, starting with the root the watcher was created with.
+    pub path: PathBuf,
+    pub change: FileChange,
+}
+
+/// Watches a directory and everything under it on a background thread. Events are delivered to
+/// the thread it was created on, which has to pump messages for them to wake it. Dropping it stops
+/// watching.
+pub struct FileWatcher {
+    root: PathBuf,
+    stop: HANDLE,
+    thread: Option<JoinHandle<()>>,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    events: Mutex<VecDeque<FileChangeEvent>>,
+    /// The thread to wake when files change.
+    thread: u32,
+}
+
+impl FileWatcher {
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self, Error> {
+        let root = root.as_ref().to_path_buf();
+        let name = root.to_wide();
+        let directory = unsafe {
+            CreateFileW(
+                name.as_ptr(),
+                FILE_LIST_DIRECTORY,
+                FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+                std::ptr::null(),
+                OPEN_EXISTING,
+                FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OVERLAPPED,
+                0,
+            )
+        };
+        if directory == INVALID_HANDLE_VALUE {
+            return Err(last_error(&format!("failed to open {}", root.display())));
+        }
+
+        let Some(stop) = create_event() else {
+            let err = last_error("failed to create file watcher stop event");
+            unsafe { CloseHandle(directory) };
+            return Err(err);
+        };
+
+        let shared = Arc::new(Shared {
+            events: Mutex::new(VecDeque::new()),
+            thread: unsafe { GetCurrentThreadId() },
+        });
+        let watcher = Watcher {
+            root: root.clone(),
+            directory,
+            stop,
+            shared: shared.clone(),
+        };
+        let thread = thread::Builder::new()
+            .name("galleon-file-watcher".to_string())
+            .spawn(move || watcher.run());
+        let thread = match thread {
+            Ok(thread) => thread,
+            Err(err) => {
+                // note: the directory was closed with the watcher the closure owned.
+                unsafe { CloseHandle(stop) };
+                return Err(Error::new("failed to spawn file watcher").with_source(err));
+            }
+        };
+
+        Ok(Self {
+            root,
+            stop,
+            thread: Some(thread),
+            shared,
+        })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The oldest change since the last call.
+    pub(crate) fn next_event(&self) -> Option<FileChangeEvent> {
+        self.shared.events.lock().unwrap().pop_front()
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        unsafe { SetEvent(self.stop) };
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+        unsafe { CloseHandle(self.stop) };
+    }
+}
+
+/// The background thread's half, which owns the directory handle.
+struct Watcher {
+    root: PathBuf,
+    directory: HANDLE,
+    /// Signalled when the [`FileWatcher`] is dropped. Closed by it, after this has finished.
+    stop: HANDLE,
+    shared: Arc<Shared>,
+}
+
+impl Watcher {
+    fn run(self) {
+        let Some(completed) = create_event() else {
+            warn!(
+                error =
+                    &last_error("failed to create file watcher event") as &dyn std::error::Error,
+                "stopped watching {}",
+                self.root.display()
+            );
+            return;
+        };
+
+        // note: the system writes DWORD-aligned records into the buffer.
+        let mut buffer = vec![0u32; BUFFER_SIZE / 4];
+        let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+        let mut pending: Vec<FileChangeEvent> = Vec::new();
+
+        loop {
+            overlapped.hEvent = completed;
+            let read = unsafe {
+                ReadDirectoryChangesW(
+                    self.directory,
+                    buffer.as_mut_ptr().cast(),
+                    BUFFER_SIZE as u32,
+                    TRUE,
+                    FILE_NOTIFY_CHANGE_FILE_NAME
+                        | FILE_NOTIFY_CHANGE_LAST_WRITE
+                        | FILE_NOTIFY_CHANGE_SIZE,
+                    std::ptr::null_mut(),
+                    &mut overlapped,
+                    None,
+                )
+            };
+            if read == 0 {
+                warn!(
+                    error =
+                        &last_error("failed to read directory changes") as &dyn std::error::Error,
+                    "stopped watching {}",
+                    self.root.display()
+                );
+                break;
+            }
+
+            // Wait for the read to complete, flushing what's pending once things are quiet.
+            let handles = [self.stop, completed];
+            let stopped = loop {
+                let timeout = match pending.is_empty() {
+                    true => INFINITE,
+                    false => SETTLE_TIME.as_millis() as u32,
+                };
+                let wait = unsafe { WaitForMultipleObjects(2, handles.as_ptr(), FALSE, timeout) };
+                if wait == WAIT_OBJECT_0 + 1 {
+                    break false;
+                } else if wait == WAIT_OBJECT_0 {
+                    break true;
+                } else if !pending.is_empty() {
+                    self.publish(&mut pending);
+                }
+            };
+            if stopped {
+                // note: the buffer and overlapped have to outlive the read, so wait for the
+                // cancellation to land before they're dropped.
+                unsafe {
+                    CancelIoEx(self.directory, &overlapped);
+                    let mut transferred = 0;
+                    GetOverlappedResult(self.directory, &overlapped, &mut transferred, TRUE);
+                }
+                break;
+            }
+
+            let mut transferred = 0;
+            if unsafe { GetOverlappedResult(self.directory, &overlapped, &mut transferred, FALSE) }
+                == 0
+            {
+                warn!(
+                    error =
+                        &last_error("failed to read directory changes") as &dyn std::error::Error,
+                    "stopped watching {}",
+                    self.root.display()
+                );
+                break;
+            }
+            if transferred == 0 {
+                warn!(
+                    "too many changes under {} at once, some weren't reported",
+                    self.root.display()
+                );
+                continue;
+            }
+            self.collect(&buffer, &mut pending);
+        }
+
+        unsafe { CloseHandle(completed) };
+    }
+
+    /// Adds the changes in `buffer` to `pending`, keeping one per path with the latest change.
+    fn collect(&self, buffer: &[u32], pending: &mut Vec<FileChangeEvent>) {
+        let base = buffer.as_ptr().cast::<u8>();
+        let mut offset = 0;
+        loop {
+            let info = unsafe { &*base.add(offset).cast::<FILE_NOTIFY_INFORMATION>() };
+            let name = unsafe {
+                std::slice::from_raw_parts(info.FileName.as_ptr(), info.FileNameLength as usize / 2)
+            };
+            let path = self.root.join(OsString::from_wide(name));
+            let change = match info.Action {
+                FILE_ACTION_REMOVED | FILE_ACTION_RENAMED_OLD_NAME => FileChange::Removed,
+                _ => FileChange::Modified,
+            };
+            match pending.iter_mut().find(|event| event.path == path) {
+                Some(event) => event.change = change,
+                None => pending.push(FileChangeEvent { path, change }),
+            }
+
+            if info.NextEntryOffset == 0 {
+                break;
+            }
+            offset += info.NextEntryOffset as usize;
+        }
+    }
+
+    fn publish(&self, pending: &mut Vec<FileChangeEvent>) {
+        // Writing a file also touches the directory it's in, which isn't interesting.
+        pending.retain(|event| event.change == FileChange::Removed || !event.path.is_dir());
+        if pending.is_empty() {
+            return;
+        }
+
+        self.shared.events.lock().unwrap().extend(pending.drain(..));
+        unsafe { PostThreadMessageW(self.shared.thread, WM_NULL, 0, 0) };
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.directory) };
+    }
+}
+
+/// An auto-reset event, initially unsignalled.
+fn create_event() -> Option<HANDLE> {
+    let event = unsafe { CreateEventW(std::ptr::null(), FALSE, FALSE, std::ptr::null()) };
+    (event != 0).then_some(event)
+}
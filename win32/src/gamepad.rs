@@ -0,0 +1,415 @@
+//! Gamepads, polled once per frame by the event loop. Sticks and triggers are normalized and
+//! have their deadzones applied, so game code only sees values it should act on.
+
+use std::time::{Duration, Instant};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::Foundation::HANDLE;
+
+use crate::window::Window;
+
+pub(crate) mod hid;
+mod xinput;
+
+/// How often empty slots are checked for a newly connected pad. Polling an empty XInput slot is
+/// slow enough that doing it every frame shows up in frame times.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Where gamepads are read from, chosen when the event loop is created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GamepadBackend {
+    /// Up to 4 Xbox-compatible pads.
+    #[default]
+    XInput,
+    /// Xbox-compatible pads through XInput, plus any number of other HID gamepads and joysticks,
+    /// such as PlayStation pads. Non-Sony HID pads are mapped by button number, which not every
+    /// pad follows. Rumble is only supported on the XInput pads.
+    Hid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GamepadId(u32);
+
+impl GamepadId {
+    /// The slot the pad is in, from 0. XInput pads use slots 0-3 and HID pads the ones after.
+    /// A slot is reused once its pad disconnects.
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+}
+
+/// What's known about a pad's hardware.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadInfo {
+    pub name: String,
+    /// The USB vendor ID. XInput doesn't report one.
+    pub vendor_id: Option<u16>,
+    pub product_id: Option<u16>,
+}
+
+/// A gamepad button, named by position so the same binding works across controller families:
+/// `South` is A on an Xbox pad and cross on a PlayStation pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    /// Pressing the left stick in.
+    LeftStick,
+    RightStick,
+    Start,
+    Back,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 14] = [
+        Button::South,
+        Button::East,
+        Button::West,
+        Button::North,
+        Button::LeftShoulder,
+        Button::RightShoulder,
+        Button::LeftStick,
+        Button::RightStick,
+        Button::Start,
+        Button::Back,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+    ];
+
+    fn bit(self) -> u16 {
+        1 << self as u16
+    }
+}
+
+/// A set of buttons.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Buttons(u16);
+
+impl Buttons {
+    pub fn contains(&self, button: Button) -> bool {
+        self.0 & button.bit() != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Button> + '_ {
+        Button::ALL
+            .into_iter()
+            .filter(|button| self.contains(*button))
+    }
+
+    pub(crate) fn insert(&mut self, button: Button) {
+        self.0 |= button.bit();
+    }
+}
+
+/// Deadzones as a fraction of full travel. Inside a deadzone the input reads as zero, and the
+/// rest of the range is rescaled so values still start at zero and reach one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Deadzones {
+    pub left_stick: f32,
+    pub right_stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for Deadzones {
+    /// The deadzones XInput recommends.
+    fn default() -> Self {
+        Self {
+            left_stick: 7849.0 / 32767.0,
+            right_stick: 8689.0 / 32767.0,
+            trigger: 30.0 / 255.0,
+        }
+    }
+}
+
+/// A pad's inputs as of the last poll. Sticks are `[x, y]` in -1..=1 with positive y up, and
+/// triggers are in 0..=1.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GamepadState {
+    pub buttons: Buttons,
+    pub left_stick: [f32; 2],
+    pub right_stick: [f32; 2],
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    id: GamepadId,
+    info: GamepadInfo,
+    source: Source,
+    state: GamepadState,
+    previous: Buttons,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    XInput(u32),
+    Hid(HANDLE),
+}
+
+impl Gamepad {
+    pub fn id(&self) -> GamepadId {
+        self.id
+    }
+
+    pub fn info(&self) -> &GamepadInfo {
+        &self.info
+    }
+
+    pub fn state(&self) -> &GamepadState {
+        &self.state
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.state.buttons.contains(button)
+    }
+
+    /// Whether `button` went down since the previous poll.
+    pub fn just_pressed(&self, button: Button) -> bool {
+        self.state.buttons.contains(button) && !self.previous.contains(button)
+    }
+
+    /// Whether `button` came up since the previous poll.
+    pub fn just_released(&self, button: Button) -> bool {
+        !self.state.buttons.contains(button) && self.previous.contains(button)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    /// Sent after a `Button` release for each button that was held.
+    Disconnected(GamepadId),
+    Button {
+        id: GamepadId,
+        button: Button,
+        pressed: bool,
+    },
+}
+
+pub struct Gamepads {
+    backend: GamepadBackend,
+    pads: Vec<Option<Gamepad>>,
+    deadzones: Deadzones,
+    next_scan: Instant,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        Self::with_backend(GamepadBackend::default())
+    }
+
+    pub fn with_backend(backend: GamepadBackend) -> Self {
+        Self {
+            backend,
+            pads: vec![None; xinput::MAX_PADS],
+            deadzones: Deadzones::default(),
+            next_scan: Instant::now(),
+        }
+    }
+
+    pub fn backend(&self) -> GamepadBackend {
+        self.backend
+    }
+
+    /// Starts delivering input to `window` for backends that need one.
+    pub(crate) fn attach(&mut self, window: &Window) -> Result<(), Error> {
+        match self.backend {
+            GamepadBackend::XInput => Ok(()),
+            GamepadBackend::Hid => hid::attach(window.hwnd()),
+        }
+    }
+
+    pub fn deadzones(&self) -> Deadzones {
+        self.deadzones
+    }
+
+    /// Takes effect from the next poll.
+    pub fn set_deadzones(&mut self, deadzones: Deadzones) {
+        self.deadzones = deadzones;
+    }
+
+    pub fn get(&self, id: GamepadId) -> Option<&Gamepad> {
+        self.pads.get(id.0 as usize)?.as_ref()
+    }
+
+    /// The connected pads.
+    pub fn iter(&self) -> impl Iterator<Item = &Gamepad> {
+        self.pads.iter().flatten()
+    }
+
+    /// Sets the speed of the low frequency (left, heavy) and high frequency (right, light)
+    /// motors, each in 0..=1. Zero turns a motor off; rumble otherwise lasts until changed.
+    pub fn set_rumble(
+        &self,
+        id: GamepadId,
+        low_frequency: f32,
+        high_frequency: f32,
+    ) -> Result<(), Error> {
+        let pad = self.get(id).ok_or_else(|| {
+            Error::new(format!("no gamepad connected in slot {}", id.0))
+                .with_kind(ErrorKind::NotFound)
+        })?;
+        match pad.source {
+            Source::XInput(index) => xinput::set_rumble(index, low_frequency, high_frequency),
+            Source::Hid(_) => Err(Error::new(format!(
+                "rumble isn't supported on {}",
+                pad.info.name
+            ))
+            .with_kind(ErrorKind::Unsupported)),
+        }
+    }
+
+    /// Has the next poll look for new pads in the empty slots, rather than waiting for the next
+    /// periodic check, e.g. when a HID device has just been plugged in.
+    pub(crate) fn rescan(&mut self) {
+        self.next_scan = Instant::now();
+    }
+
+    /// Reads every connected pad and returns what changed since the last poll.
+    pub fn poll(&mut self) -> Vec<GamepadEvent> {
+        let mut events = Vec::new();
+        let now = Instant::now();
+        let scan = now >= self.next_scan;
+        if scan {
+            self.next_scan = now + RESCAN_INTERVAL;
+        }
+
+        for (index, slot) in self.pads[..xinput::MAX_PADS].iter_mut().enumerate() {
+            if slot.is_none() && !scan {
+                continue;
+            }
+
+            let index = index as u32;
+            let state = xinput::read(index, &self.deadzones);
+            update(slot, GamepadId(index), state, &mut events, || {
+                (xinput::info(), Source::XInput(index))
+            });
+        }
+
+        if self.backend == GamepadBackend::Hid {
+            let mut devices = hid::devices();
+            for (index, slot) in self.pads.iter_mut().enumerate().skip(xinput::MAX_PADS) {
+                let Some(Source::Hid(handle)) = slot.as_ref().map(|pad| pad.source) else {
+                    continue;
+                };
+                devices.retain(|(device, _)| *device != handle);
+                let state = hid::read(handle, &self.deadzones);
+                update(slot, GamepadId(index as u32), state, &mut events, || {
+                    unreachable!("the slot is already connected")
+                });
+            }
+
+            for (handle, info) in devices {
+                let index = match self.pads[xinput::MAX_PADS..]
+                    .iter()
+                    .position(Option::is_none)
+                {
+                    Some(free) => xinput::MAX_PADS + free,
+                    None => {
+                        self.pads.push(None);
+                        self.pads.len() - 1
+                    }
+                };
+                let state = hid::read(handle, &self.deadzones);
+                update(
+                    &mut self.pads[index],
+                    GamepadId(index as u32),
+                    state,
+                    &mut events,
+                    || (info, Source::Hid(handle)),
+                );
+            }
+        }
+
+        events
+    }
+}
+
+/// Moves `slot` to its new `state`, where `None` means the pad is gone, and records what changed.
+/// `connect` describes the pad if it's new.
+fn update(
+    slot: &mut Option<Gamepad>,
+    id: GamepadId,
+    state: Option<GamepadState>,
+    events: &mut Vec<GamepadEvent>,
+    connect: impl FnOnce() -> (GamepadInfo, Source),
+) {
+    match (state, slot.as_mut()) {
+        (Some(state), Some(pad)) => {
+            pad.previous = pad.state.buttons;
+            pad.state = state;
+            for button in Button::ALL {
+                let pressed = pad.is_pressed(button);
+                if pressed != pad.previous.contains(button) {
+                    events.push(GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed,
+                    });
+                }
+            }
+        }
+        (Some(state), None) => {
+            // note: buttons already held when the pad connects don't count as presses.
+            let (info, source) = connect();
+            *slot = Some(Gamepad {
+                id,
+                info,
+                source,
+                state,
+                previous: state.buttons,
+            });
+            events.push(GamepadEvent::Connected(id));
+        }
+        (None, Some(pad)) => {
+            events.extend(pad.state.buttons.iter().map(|button| GamepadEvent::Button {
+                id,
+                button,
+                pressed: false,
+            }));
+            events.push(GamepadEvent::Disconnected(id));
+            *slot = None;
+        }
+        (None, None) => {}
+    }
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Applies a radial deadzone to a stick position, keeping its direction.
+fn stick(x: f32, y: f32, deadzone: f32) -> [f32; 2] {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= deadzone {
+        return [0.0, 0.0];
+    }
+
+    let scaled = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0);
+    [x / magnitude * scaled, y / magnitude * scaled]
+}
+
+fn trigger(value: f32, deadzone: f32) -> f32 {
+    if value <= deadzone {
+        return 0.0;
+    }
+
+    ((value - deadzone) / (1.0 - deadzone)).min(1.0)
+}
@@ -0,0 +1,553 @@
+//! Gamepads read as HID devices through Raw Input, for controllers XInput doesn't cover, such as
+//! DualSense and DualShock pads. Reports arrive as `WM_INPUT` on the attached window and the
+//! latest one per device is kept until the next poll. XInput devices are skipped here and read
+//! through XInput instead, since their HID reports merge the two triggers into one axis.
+
+use std::cell::RefCell;
+
+use common::error::Error;
+use windows_sys::Win32::{
+    Devices::HumanInterfaceDevice::{
+        HidD_GetProductString, HidP_GetCaps, HidP_GetUsageValue, HidP_GetUsages, HidP_GetValueCaps,
+        HidP_Input, HidP_MaxUsageListLength, HIDP_CAPS, HIDP_STATUS_SUCCESS, HIDP_VALUE_CAPS,
+    },
+    Foundation::{CloseHandle, HANDLE, HWND, INVALID_HANDLE_VALUE, LPARAM, WPARAM},
+    Storage::FileSystem::{CreateFileW, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING},
+    UI::{
+        Input::{
+            GetRawInputData, GetRawInputDeviceInfoW, GetRawInputDeviceList,
+            RegisterRawInputDevices, HRAWINPUT, RAWINPUT, RAWINPUTDEVICE, RAWINPUTDEVICELIST,
+            RAWINPUTHEADER, RIDEV_DEVNOTIFY, RIDI_DEVICEINFO, RIDI_DEVICENAME, RIDI_PREPARSEDDATA,
+            RID_DEVICE_INFO, RID_HEADER, RID_INPUT, RIM_TYPEHID,
+        },
+        WindowsAndMessaging::{GIDC_ARRIVAL, GIDC_REMOVAL},
+    },
+};
+
+use crate::{
+    error::last_error,
+    gamepad::{stick, trigger, Button, Buttons, Deadzones, GamepadInfo, GamepadState},
+    wide,
+};
+
+const USAGE_PAGE_GENERIC: u16 = 0x01;
+const USAGE_PAGE_BUTTON: u16 = 0x09;
+const USAGE_JOYSTICK: u16 = 0x04;
+const USAGE_GAMEPAD: u16 = 0x05;
+const USAGE_X: u16 = 0x30;
+const USAGE_Y: u16 = 0x31;
+const USAGE_Z: u16 = 0x32;
+const USAGE_RX: u16 = 0x33;
+const USAGE_RY: u16 = 0x34;
+const USAGE_RZ: u16 = 0x35;
+const USAGE_HAT: u16 = 0x39;
+
+const VENDOR_SONY: u16 = 0x054c;
+
+thread_local! {
+    static HID: RefCell<Hid> = const {
+        RefCell::new(Hid {
+            devices: Vec::new(),
+            buffer: Vec::new(),
+        })
+    };
+}
+
+struct Hid {
+    devices: Vec<Device>,
+    // note: u64 so raw input and preparsed data are suitably aligned.
+    buffer: Vec<u64>,
+}
+
+struct Device {
+    handle: HANDLE,
+    info: GamepadInfo,
+    preparsed: Vec<u64>,
+    axes: Vec<Axis>,
+    max_buttons: u32,
+    layout: Layout,
+    report: Vec<u8>,
+}
+
+struct Axis {
+    usage: u16,
+    min: i32,
+    max: i32,
+    bits: u16,
+}
+
+/// How button numbers map to positions. Axes are the same for both: X/Y and Z/Rz for the
+/// sticks, Rx/Ry for the triggers and a hat switch for the d-pad.
+#[derive(Clone, Copy)]
+enum Layout {
+    Sony,
+    Generic,
+}
+
+impl Layout {
+    fn button(self, usage: u16) -> Option<Button> {
+        let button = match (self, usage) {
+            (Layout::Sony, 1) => Button::West,
+            (Layout::Sony, 2) => Button::South,
+            (Layout::Sony, 3) => Button::East,
+            (Layout::Sony, 9) => Button::Back,
+            (Layout::Sony, 10) => Button::Start,
+            (Layout::Sony, 11) => Button::LeftStick,
+            (Layout::Sony, 12) => Button::RightStick,
+            (Layout::Generic, 1) => Button::South,
+            (Layout::Generic, 2) => Button::East,
+            (Layout::Generic, 3) => Button::West,
+            (Layout::Generic, 7) => Button::Back,
+            (Layout::Generic, 8) => Button::Start,
+            (Layout::Generic, 9) => Button::LeftStick,
+            (Layout::Generic, 10) => Button::RightStick,
+            (_, 4) => Button::North,
+            (_, 5) => Button::LeftShoulder,
+            (_, 6) => Button::RightShoulder,
+            _ => return None,
+        };
+
+        Some(button)
+    }
+}
+
+/// Registers `hwnd` for gamepad and joystick input and picks up pads that are already plugged in.
+pub(crate) fn attach(hwnd: HWND) -> Result<(), Error> {
+    let devices = [USAGE_JOYSTICK, USAGE_GAMEPAD].map(|usage| RAWINPUTDEVICE {
+        usUsagePage: USAGE_PAGE_GENERIC,
+        usUsage: usage,
+        dwFlags: RIDEV_DEVNOTIFY,
+        hwndTarget: hwnd,
+    });
+    if unsafe {
+        RegisterRawInputDevices(
+            devices.as_ptr(),
+            devices.len() as u32,
+            std::mem::size_of::<RAWINPUTDEVICE>() as u32,
+        )
+    } == 0
+    {
+        return Err(last_error("failed to register for raw gamepad input"));
+    }
+
+    let entry_size = std::mem::size_of::<RAWINPUTDEVICELIST>() as u32;
+    let mut count = 0;
+    unsafe { GetRawInputDeviceList(std::ptr::null_mut(), &mut count, entry_size) };
+    let mut list = vec![
+        RAWINPUTDEVICELIST {
+            hDevice: 0,
+            dwType: 0,
+        };
+        count as usize
+    ];
+    let read = unsafe { GetRawInputDeviceList(list.as_mut_ptr(), &mut count, entry_size) };
+    if read == u32::MAX {
+        return Err(last_error("failed to list raw input devices"));
+    }
+
+    for device in &list[..read as usize] {
+        if device.dwType == RIM_TYPEHID {
+            arrived(device.hDevice);
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles `WM_INPUT_DEVICE_CHANGE`.
+pub(crate) fn on_device_change(wparam: WPARAM, lparam: LPARAM) {
+    let handle = lparam as HANDLE;
+    match wparam as u32 {
+        GIDC_ARRIVAL => arrived(handle),
+        GIDC_REMOVAL => HID.with(|hid| {
+            hid.borrow_mut()
+                .devices
+                .retain(|device| device.handle != handle)
+        }),
+        _ => {}
+    }
+}
+
+/// Keeps the report from a `WM_INPUT` message if it's from a known gamepad.
+pub(crate) fn on_input(lparam: LPARAM) {
+    HID.with(|hid| {
+        let hid = &mut *hid.borrow_mut();
+        if hid.devices.is_empty() {
+            return;
+        }
+
+        let input = lparam as HRAWINPUT;
+        let header_size = std::mem::size_of::<RAWINPUTHEADER>() as u32;
+        let mut header: RAWINPUTHEADER = unsafe { std::mem::zeroed() };
+        let mut size = header_size;
+        let read = unsafe {
+            GetRawInputData(
+                input,
+                RID_HEADER,
+                &mut header as *mut RAWINPUTHEADER as *mut _,
+                &mut size,
+                header_size,
+            )
+        };
+        if read == u32::MAX || header.dwType != RIM_TYPEHID {
+            return;
+        }
+        let Some(device) = hid
+            .devices
+            .iter_mut()
+            .find(|device| device.handle == header.hDevice)
+        else {
+            return;
+        };
+
+        let mut size = 0;
+        unsafe {
+            GetRawInputData(
+                input,
+                RID_INPUT,
+                std::ptr::null_mut(),
+                &mut size,
+                header_size,
+            )
+        };
+        hid.buffer.resize((size as usize).div_ceil(8), 0);
+        let read = unsafe {
+            GetRawInputData(
+                input,
+                RID_INPUT,
+                hid.buffer.as_mut_ptr() as *mut _,
+                &mut size,
+                header_size,
+            )
+        };
+        if read == u32::MAX {
+            return;
+        }
+
+        let raw = unsafe { &*(hid.buffer.as_ptr() as *const RAWINPUT) };
+        let (report_size, count) = unsafe { (raw.data.hid.dwSizeHid, raw.data.hid.dwCount) };
+        if report_size == 0 || count == 0 {
+            return;
+        }
+
+        // Several reports can be batched into one message; only the newest matters.
+        let reports = unsafe {
+            std::slice::from_raw_parts(
+                std::ptr::addr_of!(raw.data.hid.bRawData) as *const u8,
+                (report_size * count) as usize,
+            )
+        };
+        let newest = &reports[(report_size * (count - 1)) as usize..];
+        device.report.clear();
+        device.report.extend_from_slice(newest);
+    })
+}
+
+/// The connected HID gamepads.
+pub(super) fn devices() -> Vec<(HANDLE, GamepadInfo)> {
+    HID.with(|hid| {
+        hid.borrow()
+            .devices
+            .iter()
+            .map(|device| (device.handle, device.info.clone()))
+            .collect()
+    })
+}
+
+/// The state of the pad `handle` as of its latest report, or `None` if it's gone.
+pub(super) fn read(handle: HANDLE, deadzones: &Deadzones) -> Option<GamepadState> {
+    HID.with(|hid| {
+        let hid = hid.borrow();
+        let device = hid.devices.iter().find(|device| device.handle == handle)?;
+        if device.report.is_empty() {
+            return Some(GamepadState::default());
+        }
+
+        Some(device.state(deadzones))
+    })
+}
+
+fn arrived(handle: HANDLE) {
+    let known = HID.with(|hid| {
+        hid.borrow()
+            .devices
+            .iter()
+            .any(|device| device.handle == handle)
+    });
+    if known {
+        return;
+    }
+
+    if let Some(device) = Device::open(handle) {
+        HID.with(|hid| hid.borrow_mut().devices.push(device));
+    }
+}
+
+impl Device {
+    fn open(handle: HANDLE) -> Option<Device> {
+        let mut info: RID_DEVICE_INFO = unsafe { std::mem::zeroed() };
+        info.cbSize = std::mem::size_of::<RID_DEVICE_INFO>() as u32;
+        let mut size = info.cbSize;
+        let read = unsafe {
+            GetRawInputDeviceInfoW(
+                handle,
+                RIDI_DEVICEINFO,
+                &mut info as *mut RID_DEVICE_INFO as *mut _,
+                &mut size,
+            )
+        };
+        let hid = unsafe { info.Anonymous.hid };
+        if read == u32::MAX
+            || info.dwType != RIM_TYPEHID
+            || hid.usUsagePage != USAGE_PAGE_GENERIC
+            || !matches!(hid.usUsage, USAGE_JOYSTICK | USAGE_GAMEPAD)
+        {
+            return None;
+        }
+
+        let path = device_path(handle)?;
+        if wide::from_wide_lossy(&path).to_uppercase().contains("IG_") {
+            return None;
+        }
+
+        let mut size = 0;
+        unsafe {
+            GetRawInputDeviceInfoW(handle, RIDI_PREPARSEDDATA, std::ptr::null_mut(), &mut size)
+        };
+        let mut preparsed = vec![0u64; (size as usize).div_ceil(8)];
+        let read = unsafe {
+            GetRawInputDeviceInfoW(
+                handle,
+                RIDI_PREPARSEDDATA,
+                preparsed.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if read == u32::MAX {
+            return None;
+        }
+        let preparsed_data = preparsed.as_ptr() as isize;
+
+        let mut caps: HIDP_CAPS = unsafe { std::mem::zeroed() };
+        if unsafe { HidP_GetCaps(preparsed_data, &mut caps) } != HIDP_STATUS_SUCCESS {
+            return None;
+        }
+        let mut value_caps: Vec<HIDP_VALUE_CAPS> =
+            vec![unsafe { std::mem::zeroed() }; caps.NumberInputValueCaps as usize];
+        let mut value_caps_len = caps.NumberInputValueCaps;
+        if unsafe {
+            HidP_GetValueCaps(
+                HidP_Input,
+                value_caps.as_mut_ptr(),
+                &mut value_caps_len,
+                preparsed_data,
+            )
+        } != HIDP_STATUS_SUCCESS
+        {
+            return None;
+        }
+
+        let mut axes = Vec::new();
+        for value in &value_caps[..value_caps_len as usize] {
+            if value.UsagePage != USAGE_PAGE_GENERIC {
+                continue;
+            }
+            let (first, last) = unsafe {
+                if value.IsRange != 0 {
+                    (
+                        value.Anonymous.Range.UsageMin,
+                        value.Anonymous.Range.UsageMax,
+                    )
+                } else {
+                    (
+                        value.Anonymous.NotRange.Usage,
+                        value.Anonymous.NotRange.Usage,
+                    )
+                }
+            };
+            axes.extend((first..=last).map(|usage| Axis {
+                usage,
+                min: value.LogicalMin,
+                max: value.LogicalMax,
+                bits: value.BitSize,
+            }));
+        }
+
+        let max_buttons =
+            unsafe { HidP_MaxUsageListLength(HidP_Input, USAGE_PAGE_BUTTON, preparsed_data) };
+        let vendor_id = hid.dwVendorId as u16;
+        let layout = if vendor_id == VENDOR_SONY {
+            Layout::Sony
+        } else {
+            Layout::Generic
+        };
+
+        Some(Device {
+            handle,
+            info: GamepadInfo {
+                name: product_name(&path).unwrap_or_else(|| "HID gamepad".to_string()),
+                vendor_id: Some(vendor_id),
+                product_id: Some(hid.dwProductId as u16),
+            },
+            preparsed,
+            axes,
+            max_buttons,
+            layout,
+            report: Vec::new(),
+        })
+    }
+
+    fn state(&self, deadzones: &Deadzones) -> GamepadState {
+        let preparsed_data = self.preparsed.as_ptr() as isize;
+
+        let mut buttons = Buttons::default();
+        let mut usages = vec![0u16; self.max_buttons as usize];
+        let mut len = self.max_buttons;
+        if unsafe {
+            HidP_GetUsages(
+                HidP_Input,
+                USAGE_PAGE_BUTTON,
+                0,
+                usages.as_mut_ptr(),
+                &mut len,
+                preparsed_data,
+                self.report.as_ptr() as *mut u8,
+                self.report.len() as u32,
+            )
+        } == HIDP_STATUS_SUCCESS
+        {
+            for usage in &usages[..len as usize] {
+                if let Some(button) = self.layout.button(*usage) {
+                    buttons.insert(button);
+                }
+            }
+        }
+
+        // The hat reads 0-7 clockwise from up, and anything else when centered.
+        if let Some((direction, axis)) = self.value(USAGE_HAT) {
+            let directions: &[Button] = match direction - axis.min {
+                0 => &[Button::DPadUp],
+                1 => &[Button::DPadUp, Button::DPadRight],
+                2 => &[Button::DPadRight],
+                3 => &[Button::DPadDown, Button::DPadRight],
+                4 => &[Button::DPadDown],
+                5 => &[Button::DPadDown, Button::DPadLeft],
+                6 => &[Button::DPadLeft],
+                7 => &[Button::DPadUp, Button::DPadLeft],
+                _ => &[],
+            };
+            for button in directions {
+                buttons.insert(*button);
+            }
+        }
+
+        // HID axes have y pointing down.
+        let axis = |usage| {
+            self.normalized(usage)
+                .map_or(0.0, |value| value * 2.0 - 1.0)
+        };
+        GamepadState {
+            buttons,
+            left_stick: stick(axis(USAGE_X), -axis(USAGE_Y), deadzones.left_stick),
+            right_stick: stick(axis(USAGE_Z), -axis(USAGE_RZ), deadzones.right_stick),
+            left_trigger: trigger(self.normalized(USAGE_RX).unwrap_or(0.0), deadzones.trigger),
+            right_trigger: trigger(self.normalized(USAGE_RY).unwrap_or(0.0), deadzones.trigger),
+        }
+    }
+
+    /// The value of `usage` as a fraction of its logical range.
+    fn normalized(&self, usage: u16) -> Option<f32> {
+        let (value, axis) = self.value(usage)?;
+        if axis.max <= axis.min {
+            return None;
+        }
+
+        Some(((value - axis.min) as f32 / (axis.max - axis.min) as f32).clamp(0.0, 1.0))
+    }
+
+    fn value(&self, usage: u16) -> Option<(i32, &Axis)> {
+        let axis = self.axes.iter().find(|axis| axis.usage == usage)?;
+        let mut raw = 0;
+        if unsafe {
+            HidP_GetUsageValue(
+                HidP_Input,
+                USAGE_PAGE_GENERIC,
+                0,
+                usage,
+                &mut raw,
+                self.preparsed.as_ptr() as isize,
+                self.report.as_ptr(),
+                self.report.len() as u32,
+            )
+        } != HIDP_STATUS_SUCCESS
+        {
+            return None;
+        }
+
+        // note: values come back unsigned, so signed ranges need sign-extending.
+        let value = if axis.min < 0 && axis.bits > 0 && axis.bits < 32 {
+            let shift = 32 - u32::from(axis.bits);
+            ((raw << shift) as i32) >> shift
+        } else {
+            raw as i32
+        };
+
+        Some((value, axis))
+    }
+}
+
+/// The device interface path, NUL-terminated.
+fn device_path(handle: HANDLE) -> Option<Vec<u16>> {
+    let mut len = 0;
+    unsafe { GetRawInputDeviceInfoW(handle, RIDI_DEVICENAME, std::ptr::null_mut(), &mut len) };
+    let mut path = vec![0u16; len as usize + 1];
+    let read = unsafe {
+        GetRawInputDeviceInfoW(
+            handle,
+            RIDI_DEVICENAME,
+            path.as_mut_ptr() as *mut _,
+            &mut len,
+        )
+    };
+    if read == u32::MAX {
+        return None;
+    }
+
+    Some(path)
+}
+
+fn product_name(path: &[u16]) -> Option<String> {
+    // note: no access rights are needed to query strings, so this works even while another
+    // process has the device open exclusively.
+    let file = unsafe {
+        CreateFileW(
+            path.as_ptr(),
+            0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE,
+            std::ptr::null(),
+            OPEN_EXISTING,
+            0,
+            0,
+        )
+    };
+    if file == INVALID_HANDLE_VALUE {
+        return None;
+    }
+
+    // The HID spec caps strings at 126 characters plus a terminator.
+    let mut name = [0u16; 127];
+    let ok = unsafe {
+        HidD_GetProductString(
+            file,
+            name.as_mut_ptr() as *mut _,
+            std::mem::size_of_val(&name) as u32,
+        )
+    };
+    unsafe { CloseHandle(file) };
+    if ok == 0 {
+        return None;
+    }
+
+    let name = wide::from_wide_lossy(&name).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
@@ -0,0 +1,92 @@
+use common::error::Error;
+use windows_sys::Win32::{
+    Foundation::ERROR_SUCCESS,
+    UI::Input::XboxController::{
+        XInputGetState, XInputSetState, XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK,
+        XINPUT_GAMEPAD_BUTTON_FLAGS, XINPUT_GAMEPAD_DPAD_DOWN, XINPUT_GAMEPAD_DPAD_LEFT,
+        XINPUT_GAMEPAD_DPAD_RIGHT, XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_LEFT_SHOULDER,
+        XINPUT_GAMEPAD_LEFT_THUMB, XINPUT_GAMEPAD_RIGHT_SHOULDER, XINPUT_GAMEPAD_RIGHT_THUMB,
+        XINPUT_GAMEPAD_START, XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y, XINPUT_STATE, XINPUT_VIBRATION,
+        XUSER_MAX_COUNT,
+    },
+};
+
+use crate::{
+    error::win32_error,
+    gamepad::{stick, trigger, Button, Buttons, Deadzones, GamepadInfo, GamepadState},
+};
+
+pub(super) const MAX_PADS: usize = XUSER_MAX_COUNT as usize;
+
+const BUTTONS: [(XINPUT_GAMEPAD_BUTTON_FLAGS, Button); 14] = [
+    (XINPUT_GAMEPAD_A, Button::South),
+    (XINPUT_GAMEPAD_B, Button::East),
+    (XINPUT_GAMEPAD_X, Button::West),
+    (XINPUT_GAMEPAD_Y, Button::North),
+    (XINPUT_GAMEPAD_LEFT_SHOULDER, Button::LeftShoulder),
+    (XINPUT_GAMEPAD_RIGHT_SHOULDER, Button::RightShoulder),
+    (XINPUT_GAMEPAD_LEFT_THUMB, Button::LeftStick),
+    (XINPUT_GAMEPAD_RIGHT_THUMB, Button::RightStick),
+    (XINPUT_GAMEPAD_START, Button::Start),
+    (XINPUT_GAMEPAD_BACK, Button::Back),
+    (XINPUT_GAMEPAD_DPAD_UP, Button::DPadUp),
+    (XINPUT_GAMEPAD_DPAD_DOWN, Button::DPadDown),
+    (XINPUT_GAMEPAD_DPAD_LEFT, Button::DPadLeft),
+    (XINPUT_GAMEPAD_DPAD_RIGHT, Button::DPadRight),
+];
+
+/// The pad in slot `index`, or `None` if there isn't one.
+pub(super) fn read(index: u32, deadzones: &Deadzones) -> Option<GamepadState> {
+    let mut state: XINPUT_STATE = unsafe { std::mem::zeroed() };
+    if unsafe { XInputGetState(index, &mut state) } != ERROR_SUCCESS {
+        return None;
+    }
+
+    let pad = state.Gamepad;
+    let mut buttons = Buttons::default();
+    for (flag, button) in BUTTONS {
+        if pad.wButtons & flag != 0 {
+            buttons.insert(button);
+        }
+    }
+
+    Some(GamepadState {
+        buttons,
+        left_stick: stick(axis(pad.sThumbLX), axis(pad.sThumbLY), deadzones.left_stick),
+        right_stick: stick(
+            axis(pad.sThumbRX),
+            axis(pad.sThumbRY),
+            deadzones.right_stick,
+        ),
+        left_trigger: trigger(f32::from(pad.bLeftTrigger) / 255.0, deadzones.trigger),
+        right_trigger: trigger(f32::from(pad.bRightTrigger) / 255.0, deadzones.trigger),
+    })
+}
+
+pub(super) fn info() -> GamepadInfo {
+    GamepadInfo {
+        name: "XInput controller".to_string(),
+        vendor_id: None,
+        product_id: None,
+    }
+}
+
+pub(super) fn set_rumble(index: u32, low_frequency: f32, high_frequency: f32) -> Result<(), Error> {
+    let speed = |value: f32| (value.clamp(0.0, 1.0) * f32::from(u16::MAX)) as u16;
+    let vibration = XINPUT_VIBRATION {
+        wLeftMotorSpeed: speed(low_frequency),
+        wRightMotorSpeed: speed(high_frequency),
+    };
+
+    let code = unsafe { XInputSetState(index, &vibration) };
+    if code != ERROR_SUCCESS {
+        return Err(win32_error("failed to set gamepad rumble", code));
+    }
+
+    Ok(())
+}
+
+/// Maps a raw stick axis to -1..=1. The range is one larger on the negative side.
+fn axis(value: i16) -> f32 {
+    (f32::from(value) / 32767.0).max(-1.0)
+}
@@ -0,0 +1,88 @@
+//! The boundary between the application and the OS: failures and panics end up in the log and in
+//! front of the user instead of the process silently vanishing under the GUI subsystem.
+
+use std::{
+    backtrace::Backtrace,
+    cell::RefCell,
+    panic::{self, AssertUnwindSafe},
+    sync::Once,
+};
+
+use common::error::Error;
+use tracing::error;
+
+use crate::{dialog, monitor};
+
+thread_local! {
+    /// Where the last panic on this thread happened, taken by the hook while the stack is intact.
+    static PANIC: RefCell<Option<(String, Backtrace)>> = const { RefCell::new(None) };
+}
+
+/// Runs `f`, turning a panic into an [`Error`] carrying the panic message, location and backtrace.
+/// A failure either way is logged at ERROR and shown in a dialog before it's returned, so
+/// `main` only has to pick an exit code. `f` needn't be `UnwindSafe`: the process exits after a
+/// panic, so nothing it touched is used again:
+///
+/// ```no_run
+/// fn main() -> std::process::ExitCode {
+///     match win32::guard::run_guarded(|| Ok(())) {
+///         Ok(()) => std::process::ExitCode::SUCCESS,
+///         Err(_) => std::process::ExitCode::FAILURE,
+///     }
+/// }
+/// ```
+pub fn run_guarded<F>(f: F) -> Result<(), Error>
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    install_hook();
+
+    let result = match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.as_str()
+            } else {
+                "Box<dyn Any>"
+            };
+
+            let err = match PANIC.with(|panic| panic.borrow_mut().take()) {
+                Some((location, backtrace)) => {
+                    Error::new(format!("panicked at {location}: {message}"))
+                        .with_backtrace(backtrace)
+                }
+                None => Error::new(format!("panicked: {message}")),
+            };
+            Err(err)
+        }
+    };
+
+    if let Err(err) = &result {
+        error!(error = err as &dyn std::error::Error, "fatal error");
+        monitor::restore_display_modes();
+        dialog::show_fatal_error(err);
+    }
+
+    result
+}
+
+/// Chains a hook that records where each panic happened, keeping whatever hook was installed.
+fn install_hook() {
+    static INSTALLED: Once = Once::new();
+
+    INSTALLED.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let location = info
+                .location()
+                .map_or_else(|| "unknown location".to_string(), ToString::to_string);
+            _ = PANIC.try_with(|panic| {
+                *panic.borrow_mut() = Some((location, Backtrace::force_capture()));
+            });
+
+            previous(info);
+        }));
+    });
+}
@@ -0,0 +1,148 @@
+//! Window icons, shown in the title bar, the taskbar and Alt+Tab.
+
+use std::rc::Rc;
+
+use common::error::Error;
+use windows_sys::{
+    core::PCWSTR,
+    Win32::{
+        Graphics::Gdi::{CreateBitmap, DeleteObject},
+        System::LibraryLoader::GetModuleHandleW,
+        UI::WindowsAndMessaging::{
+            CreateIconIndirect, DestroyIcon, GetSystemMetrics, LoadImageW, HICON, ICONINFO,
+            IMAGE_ICON, SM_CXICON, SM_CXSMICON, SM_CYICON, SM_CYSMICON,
+        },
+    },
+};
+
+use crate::error::last_error;
+
+/// An icon in the sizes a window needs. Clones share the same images, which are freed when the
+/// last one is dropped.
+#[derive(Clone)]
+pub struct Icon {
+    handles: Rc<IconHandles>,
+}
+
+struct IconHandles {
+    /// For Alt+Tab and the taskbar.
+    big: HICON,
+    /// For the title bar.
+    small: HICON,
+}
+
+impl Drop for IconHandles {
+    fn drop(&mut self) {
+        unsafe { DestroyIcon(self.big) };
+        if self.small != self.big {
+            unsafe { DestroyIcon(self.small) };
+        }
+    }
+}
+
+impl Icon {
+    /// Loads the icon resource `id` from the executable, picking the image closest to each size
+    /// from the .ico it was built from.
+    pub fn from_resource(id: u16) -> Result<Icon, Error> {
+        let big = load(id, SM_CXICON, SM_CYICON)?;
+        let small = match load(id, SM_CXSMICON, SM_CYSMICON) {
+            Ok(small) => small,
+            Err(err) => {
+                unsafe { DestroyIcon(big) };
+                return Err(err);
+            }
+        };
+
+        Ok(Icon {
+            handles: Rc::new(IconHandles { big, small }),
+        })
+    }
+
+    /// Builds an icon from `width` x `height` RGBA pixels, top row first, with straight (not
+    /// premultiplied) alpha. The system scales it to each size, so a square image of at least
+    /// 32x32 looks best.
+    pub fn from_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<Icon, Error> {
+        let icon = create(width, height, rgba, None)?;
+
+        Ok(Icon {
+            handles: Rc::new(IconHandles {
+                big: icon,
+                small: icon,
+            }),
+        })
+    }
+
+    pub(crate) fn big(&self) -> HICON {
+        self.handles.big
+    }
+
+    pub(crate) fn small(&self) -> HICON {
+        self.handles.small
+    }
+}
+
+fn load(id: u16, width: i32, height: i32) -> Result<HICON, Error> {
+    let module = unsafe { GetModuleHandleW(std::ptr::null()) };
+    let width = unsafe { GetSystemMetrics(width) };
+    let height = unsafe { GetSystemMetrics(height) };
+    let icon = unsafe { LoadImageW(module, id as usize as PCWSTR, IMAGE_ICON, width, height, 0) };
+    if icon == 0 {
+        return Err(last_error("failed to load icon"));
+    }
+
+    Ok(icon)
+}
+
+/// Creates an icon, or with a hotspot a cursor, from `width` x `height` RGBA pixels.
+pub(crate) fn create(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    hotspot: Option<(u32, u32)>,
+) -> Result<HICON, Error> {
+    let kind = if hotspot.is_some() { "cursor" } else { "icon" };
+    if width == 0 || height == 0 || rgba.len() != width as usize * height as usize * 4 {
+        return Err(Error::new(format!(
+            "{kind} pixels should be {width}x{height} RGBA, got {} bytes",
+            rgba.len()
+        )));
+    }
+
+    let bgra: Vec<u8> = rgba
+        .chunks_exact(4)
+        .flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]])
+        .collect();
+    // The mask is ignored for images with alpha, but one is still required. Its rows are
+    // padded to 16 bits.
+    let mask = vec![0u8; width.div_ceil(16) as usize * 2 * height as usize];
+
+    let (x_hotspot, y_hotspot) = hotspot.unwrap_or_default();
+    let (width, height) = (width as i32, height as i32);
+    let color = unsafe { CreateBitmap(width, height, 1, 32, bgra.as_ptr() as *const _) };
+    let mask = unsafe { CreateBitmap(width, height, 1, 1, mask.as_ptr() as *const _) };
+    let info = ICONINFO {
+        fIcon: i32::from(hotspot.is_none()),
+        xHotspot: x_hotspot,
+        yHotspot: y_hotspot,
+        hbmMask: mask,
+        hbmColor: color,
+    };
+    let icon = if color != 0 && mask != 0 {
+        unsafe { CreateIconIndirect(&info) }
+    } else {
+        0
+    };
+    let result = if icon == 0 {
+        Err(last_error(&format!("failed to create {kind}")))
+    } else {
+        Ok(icon)
+    };
+
+    // note: the icon keeps its own copy of the bitmaps.
+    unsafe {
+        DeleteObject(color);
+        DeleteObject(mask);
+    }
+
+    result
+}
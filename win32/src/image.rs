@@ -0,0 +1,192 @@
+//! Image files decoded with the Windows Imaging Component, for textures. Any format WIC has a
+//! codec for loads, which includes PNG, JPEG, BMP, GIF and TIFF:
+//!
+//! ```no_run
+//! use common::graphics::{GraphicsDevice, TextureFilter};
+//!
+//! fn load_ship(device: &mut dyn GraphicsDevice) -> Result<(), common::error::Error> {
+//!     let image = win32::image::load("assets/textures/ship.png")?;
+//!     device.create_texture(image, TextureFilter::Nearest)?;
+//!     Ok(())
+//! }
+//! ```
+//!
+//! note: only the first frame of animated and multi-page files is loaded.
+
+use std::{ffi::c_void, path::Path};
+
+use common::{error::Error, graphics::Image};
+use windows_sys::{
+    core::{GUID, HRESULT, PCWSTR},
+    Win32::System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER},
+};
+
+use crate::{
+    com::{Apartment, ComGuard, ComPtr, IUnknownVtbl},
+    error::Hresult,
+    wide::ToWide,
+};
+
+// note: declared here, since windows-sys is built without its imaging bindings.
+const CLSID_WIC_IMAGING_FACTORY: GUID = GUID::from_u128(0xcacaf262_9370_4615_a13b_9f5539da4c0a);
+const IID_IWIC_IMAGING_FACTORY: GUID = GUID::from_u128(0xec5ec8a9_c395_4314_9c77_54d7a935ff70);
+const GUID_WIC_PIXEL_FORMAT_32BPP_RGBA: GUID =
+    GUID::from_u128(0xf5c7ad2d_6a8d_43dd_a7a8_a29935261ae9);
+const GENERIC_READ: u32 = 0x8000_0000;
+const WIC_DECODE_METADATA_CACHE_ON_DEMAND: i32 = 0;
+const WIC_BITMAP_DITHER_TYPE_NONE: i32 = 0;
+const WIC_BITMAP_PALETTE_TYPE_CUSTOM: i32 = 0;
+
+/// Decodes the image file at `path` into straight alpha RGBA pixels.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Image, Error> {
+    let path = path.as_ref();
+    let context = format!("failed to load the image {}", path.display());
+    let _com = ComGuard::current_or(Apartment::SingleThreaded)?;
+
+    let mut factory = std::ptr::null_mut();
+    let hr = unsafe {
+        CoCreateInstance(
+            &CLSID_WIC_IMAGING_FACTORY,
+            std::ptr::null_mut(),
+            CLSCTX_INPROC_SERVER,
+            &IID_IWIC_IMAGING_FACTORY,
+            &mut factory,
+        )
+    };
+    let factory = unsafe { ComPtr::from_call(hr, factory, "failed to create the WIC factory") }?;
+    let factory_vtbl = unsafe { factory.vtbl::<IWICImagingFactoryVtbl>() };
+
+    let name = path.to_wide();
+    let mut decoder = std::ptr::null_mut();
+    let hr = unsafe {
+        (factory_vtbl.create_decoder_from_filename)(
+            factory.as_raw(),
+            name.as_ptr(),
+            std::ptr::null(),
+            GENERIC_READ,
+            WIC_DECODE_METADATA_CACHE_ON_DEMAND,
+            &mut decoder,
+        )
+    };
+    let decoder = unsafe { ComPtr::from_call(hr, decoder, &context) }?;
+
+    let mut frame = std::ptr::null_mut();
+    let hr = unsafe {
+        (decoder.vtbl::<IWICBitmapDecoderVtbl>().get_frame)(decoder.as_raw(), 0, &mut frame)
+    };
+    let frame = unsafe { ComPtr::from_call(hr, frame, &context) }?;
+
+    let mut converter = std::ptr::null_mut();
+    let hr = unsafe { (factory_vtbl.create_format_converter)(factory.as_raw(), &mut converter) };
+    let converter = unsafe { ComPtr::from_call(hr, converter, &context) }?;
+    let converter_vtbl = unsafe { converter.vtbl::<IWICFormatConverterVtbl>() };
+    let hr = unsafe {
+        (converter_vtbl.initialize)(
+            converter.as_raw(),
+            frame.as_raw(),
+            &GUID_WIC_PIXEL_FORMAT_32BPP_RGBA,
+            WIC_BITMAP_DITHER_TYPE_NONE,
+            std::ptr::null_mut(),
+            0.0,
+            WIC_BITMAP_PALETTE_TYPE_CUSTOM,
+        )
+    };
+    Hresult(hr).check(&format!("{context}: can't convert it to RGBA"))?;
+
+    let source = &converter_vtbl.source;
+    let (mut width, mut height) = (0, 0);
+    let hr = unsafe { (source.get_size)(converter.as_raw(), &mut width, &mut height) };
+    Hresult(hr).check(&context)?;
+
+    let stride = width as usize * 4;
+    let mut pixels = vec![0; stride * height as usize];
+    let hr = unsafe {
+        (source.copy_pixels)(
+            converter.as_raw(),
+            std::ptr::null(),
+            stride as u32,
+            pixels.len() as u32,
+            pixels.as_mut_ptr(),
+        )
+    };
+    Hresult(hr).check(&context)?;
+
+    Image::new(width, height, pixels)
+}
+
+/// The start of `IWICImagingFactory`'s vtable, up to the last method used. The methods this
+/// module doesn't call are left as untyped slots.
+#[repr(C)]
+struct IWICImagingFactoryVtbl {
+    _base: IUnknownVtbl,
+    create_decoder_from_filename: unsafe extern "system" fn(
+        this: *mut c_void,
+        filename: PCWSTR,
+        vendor: *const GUID,
+        desired_access: u32,
+        metadata_options: i32,
+        decoder: *mut *mut c_void,
+    ) -> HRESULT,
+    _create_decoder_from_stream: usize,
+    _create_decoder_from_file_handle: usize,
+    _create_component_info: usize,
+    _create_decoder: usize,
+    _create_encoder: usize,
+    _create_palette: usize,
+    create_format_converter:
+        unsafe extern "system" fn(this: *mut c_void, converter: *mut *mut c_void) -> HRESULT,
+}
+
+/// The start of `IWICBitmapDecoder`'s vtable, up to `GetFrame`.
+#[repr(C)]
+struct IWICBitmapDecoderVtbl {
+    _base: IUnknownVtbl,
+    _query_capability: usize,
+    _initialize: usize,
+    _get_container_format: usize,
+    _get_decoder_info: usize,
+    _copy_palette: usize,
+    _get_metadata_query_reader: usize,
+    _get_preview: usize,
+    _get_color_contexts: usize,
+    _get_thumbnail: usize,
+    _get_frame_count: usize,
+    get_frame: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: u32,
+        frame: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+/// `IWICBitmapSource`'s vtable, which frames and converters start with.
+#[repr(C)]
+struct IWICBitmapSourceVtbl {
+    _base: IUnknownVtbl,
+    get_size:
+        unsafe extern "system" fn(this: *mut c_void, width: *mut u32, height: *mut u32) -> HRESULT,
+    _get_pixel_format: usize,
+    _get_resolution: usize,
+    _copy_palette: usize,
+    copy_pixels: unsafe extern "system" fn(
+        this: *mut c_void,
+        rect: *const c_void,
+        stride: u32,
+        buffer_size: u32,
+        buffer: *mut u8,
+    ) -> HRESULT,
+}
+
+/// The start of `IWICFormatConverter`'s vtable, up to `Initialize`.
+#[repr(C)]
+struct IWICFormatConverterVtbl {
+    source: IWICBitmapSourceVtbl,
+    initialize: unsafe extern "system" fn(
+        this: *mut c_void,
+        source: *mut c_void,
+        format: *const GUID,
+        dither: i32,
+        palette: *mut c_void,
+        alpha_threshold_percent: f64,
+        palette_translate: i32,
+    ) -> HRESULT,
+}
@@ -0,0 +1,321 @@
+//! Keyboard events. Keys are identified by where they are on the keyboard rather than what they
+//! type, so bindings like WASD land on the same physical keys on QWERTY, AZERTY and Dvorak
+//! layouts. Use text input for what the user actually typed.
+
+use windows_sys::Win32::{
+    Foundation::{LPARAM, WPARAM},
+    UI::Input::KeyboardAndMouse::{
+        GetKeyState, MapVirtualKeyW, MAPVK_VK_TO_VSC_EX, VK_CONTROL, VK_LWIN, VK_MENU, VK_NUMLOCK,
+        VK_PAUSE, VK_RWIN, VK_SHIFT,
+    },
+};
+
+/// A physical key, named after the key in the same position on a US QWERTY keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Digit0,
+    Digit1,
+    Digit2,
+    Digit3,
+    Digit4,
+    Digit5,
+    Digit6,
+    Digit7,
+    Digit8,
+    Digit9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Escape,
+    Tab,
+    CapsLock,
+    ShiftLeft,
+    ShiftRight,
+    ControlLeft,
+    ControlRight,
+    AltLeft,
+    AltRight,
+    SuperLeft,
+    SuperRight,
+    ContextMenu,
+    Space,
+    Enter,
+    Backspace,
+    Minus,
+    Equal,
+    BracketLeft,
+    BracketRight,
+    Backslash,
+    /// The extra key next to left shift on ISO keyboards.
+    IntlBackslash,
+    Semicolon,
+    Quote,
+    Backquote,
+    Comma,
+    Period,
+    Slash,
+    ArrowUp,
+    ArrowDown,
+    ArrowLeft,
+    ArrowRight,
+    Insert,
+    Delete,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    PrintScreen,
+    ScrollLock,
+    Pause,
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadEnter,
+    /// A key without a layout-independent position, e.g. a media key. Match on the virtual key
+    /// instead.
+    Unknown,
+}
+
+impl Key {
+    /// Maps a set 1 scancode, with `0xE0` in the high byte for extended keys, to a key.
+    pub fn from_scancode(scancode: u16) -> Key {
+        match scancode {
+            0x01 => Key::Escape,
+            0x02 => Key::Digit1,
+            0x03 => Key::Digit2,
+            0x04 => Key::Digit3,
+            0x05 => Key::Digit4,
+            0x06 => Key::Digit5,
+            0x07 => Key::Digit6,
+            0x08 => Key::Digit7,
+            0x09 => Key::Digit8,
+            0x0a => Key::Digit9,
+            0x0b => Key::Digit0,
+            0x0c => Key::Minus,
+            0x0d => Key::Equal,
+            0x0e => Key::Backspace,
+            0x0f => Key::Tab,
+            0x10 => Key::Q,
+            0x11 => Key::W,
+            0x12 => Key::E,
+            0x13 => Key::R,
+            0x14 => Key::T,
+            0x15 => Key::Y,
+            0x16 => Key::U,
+            0x17 => Key::I,
+            0x18 => Key::O,
+            0x19 => Key::P,
+            0x1a => Key::BracketLeft,
+            0x1b => Key::BracketRight,
+            0x1c => Key::Enter,
+            0x1d => Key::ControlLeft,
+            0x1e => Key::A,
+            0x1f => Key::S,
+            0x20 => Key::D,
+            0x21 => Key::F,
+            0x22 => Key::G,
+            0x23 => Key::H,
+            0x24 => Key::J,
+            0x25 => Key::K,
+            0x26 => Key::L,
+            0x27 => Key::Semicolon,
+            0x28 => Key::Quote,
+            0x29 => Key::Backquote,
+            0x2a => Key::ShiftLeft,
+            0x2b => Key::Backslash,
+            0x2c => Key::Z,
+            0x2d => Key::X,
+            0x2e => Key::C,
+            0x2f => Key::V,
+            0x30 => Key::B,
+            0x31 => Key::N,
+            0x32 => Key::M,
+            0x33 => Key::Comma,
+            0x34 => Key::Period,
+            0x35 => Key::Slash,
+            0x36 => Key::ShiftRight,
+            0x37 => Key::NumpadMultiply,
+            0x38 => Key::AltLeft,
+            0x39 => Key::Space,
+            0x3a => Key::CapsLock,
+            0x3b => Key::F1,
+            0x3c => Key::F2,
+            0x3d => Key::F3,
+            0x3e => Key::F4,
+            0x3f => Key::F5,
+            0x40 => Key::F6,
+            0x41 => Key::F7,
+            0x42 => Key::F8,
+            0x43 => Key::F9,
+            0x44 => Key::F10,
+            0x45 => Key::Pause,
+            0x46 => Key::ScrollLock,
+            0x47 => Key::Numpad7,
+            0x48 => Key::Numpad8,
+            0x49 => Key::Numpad9,
+            0x4a => Key::NumpadSubtract,
+            0x4b => Key::Numpad4,
+            0x4c => Key::Numpad5,
+            0x4d => Key::Numpad6,
+            0x4e => Key::NumpadAdd,
+            0x4f => Key::Numpad1,
+            0x50 => Key::Numpad2,
+            0x51 => Key::Numpad3,
+            0x52 => Key::Numpad0,
+            0x53 => Key::NumpadDecimal,
+            0x56 => Key::IntlBackslash,
+            0x57 => Key::F11,
+            0x58 => Key::F12,
+            0xe01c => Key::NumpadEnter,
+            0xe01d => Key::ControlRight,
+            0xe035 => Key::NumpadDivide,
+            0xe037 => Key::PrintScreen,
+            0xe038 => Key::AltRight,
+            0xe045 => Key::NumLock,
+            0xe047 => Key::Home,
+            0xe048 => Key::ArrowUp,
+            0xe049 => Key::PageUp,
+            0xe04b => Key::ArrowLeft,
+            0xe04d => Key::ArrowRight,
+            0xe04f => Key::End,
+            0xe050 => Key::ArrowDown,
+            0xe051 => Key::PageDown,
+            0xe052 => Key::Insert,
+            0xe053 => Key::Delete,
+            0xe05b => Key::SuperLeft,
+            0xe05c => Key::SuperRight,
+            0xe05d => Key::ContextMenu,
+            _ => Key::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyState {
+    Pressed,
+    /// Held down long enough for the keyboard to auto-repeat.
+    Repeat,
+    Released,
+}
+
+/// Which modifier keys were held when an event happened. AltGr shows up as control and alt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    /// The Windows key.
+    pub logo: bool,
+}
+
+impl Modifiers {
+    /// The modifier state as of the message being handled.
+    pub(crate) fn current() -> Self {
+        let down = |vk: u16| unsafe { GetKeyState(i32::from(vk)) } < 0;
+        Self {
+            shift: down(VK_SHIFT),
+            control: down(VK_CONTROL),
+            alt: down(VK_MENU),
+            logo: down(VK_LWIN) || down(VK_RWIN),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: Key,
+    /// The Win32 virtual key, which depends on the keyboard layout.
+    pub vk: u16,
+    /// The hardware scancode, with `0xE0` in the high byte for extended keys.
+    pub scancode: u16,
+    pub state: KeyState,
+    pub modifiers: Modifiers,
+}
+
+/// Builds an event from a `WM_KEYDOWN`/`WM_KEYUP` style message.
+pub(crate) fn key_event(wparam: WPARAM, lparam: LPARAM, pressed: bool) -> KeyEvent {
+    let vk = wparam as u16;
+    let mut scancode = ((lparam >> 16) & 0xff) as u16;
+    if lparam & (1 << 24) != 0 {
+        scancode |= 0xe000;
+    }
+    if scancode == 0 {
+        // note: injected input often has no scancode, so fall back to the layout's mapping.
+        scancode = unsafe { MapVirtualKeyW(u32::from(vk), MAPVK_VK_TO_VSC_EX) } as u16;
+    }
+
+    // Pause and Num Lock share a scancode, and which one gets the extended bit isn't reliable.
+    let key = match vk {
+        VK_PAUSE => Key::Pause,
+        VK_NUMLOCK => Key::NumLock,
+        _ => Key::from_scancode(scancode),
+    };
+    let state = if !pressed {
+        KeyState::Released
+    } else if lparam & (1 << 30) != 0 {
+        KeyState::Repeat
+    } else {
+        KeyState::Pressed
+    };
+
+    KeyEvent {
+        key,
+        vk,
+        scancode,
+        state,
+        modifiers: Modifiers::current(),
+    }
+}
@@ -0,0 +1,44 @@
+#[allow(clippy::non_minimal_cfg)]
+#[cfg(all(not(target_os = "windows")))]
+compile_error!("only windows is supported");
+
+pub mod args;
+pub mod com;
+pub mod console;
+pub mod crash_report;
+pub mod cursor;
+pub mod debug;
+pub mod device;
+pub mod dialog;
+pub mod direct_write;
+pub mod error;
+pub mod event_loop;
+pub mod file_drop;
+pub mod file_watcher;
+pub mod gamepad;
+pub mod guard;
+pub mod icon;
+pub mod image;
+pub mod keyboard;
+pub mod logger;
+mod macros;
+pub mod minidump;
+pub mod monitor;
+pub mod mouse;
+pub mod paths;
+mod pipe;
+pub mod process;
+pub mod registry;
+pub mod shared_memory;
+pub mod single_instance;
+mod spy;
+pub mod taskbar;
+pub mod text_input;
+pub mod theme;
+pub mod thread;
+pub mod time;
+pub mod toast;
+pub mod tray;
+pub mod watchdog;
+pub mod wide;
+pub mod window;
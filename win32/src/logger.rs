@@ -1,60 +1,129 @@
-use common::log::Sink;
-use std::sync::{Arc, Mutex};
-use tracing::{level_filters::LevelFilter, Level};
+use common::{
+    error::Error,
+    log::{
+        format::{CompactFormatter, Formatter, Utf16Line, VisualStudioFormatter},
+        LogRecord, Sink,
+    },
+};
+use std::{path::Path, sync::Arc};
 
+use tracing::Level;
 use windows_sys::Win32::System::Diagnostics::Debug::OutputDebugStringW;
 
-use crate::wstr;
+use crate::{debug, wide::WStr};
+
+pub use self::{
+    etw::EtwSink,
+    event_log::EventLogSink,
+    pipe::{PipeCollector, PipeSink, PIPE_ENV_VAR},
+};
+
+mod etw;
+mod event_log;
+mod pipe;
 
 #[derive(Clone)]
 pub struct DebugConsoleSink {
-    max_level: Arc<Mutex<LevelFilter>>,
+    formatter: Arc<dyn Formatter>,
 }
 
 impl DebugConsoleSink {
-    pub fn new(max_level: LevelFilter) -> Self {
+    pub fn new() -> Self {
         Self {
-            max_level: Arc::new(Mutex::new(max_level)),
+            formatter: Arc::new(CompactFormatter),
         }
     }
 
-    pub fn set_max_level(&self, level: LevelFilter) {
-        *self.max_level.lock().unwrap() = level;
+    /// Formats records as `path\to\file.rs(123): [LEVEL] message` so that double-clicking a line
+    /// in Visual Studio's Output window jumps to the call site. Paths are resolved against the
+    /// workspace this binary was built from.
+    pub fn visual_studio() -> Self {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let workspace_root = manifest_dir.parent().unwrap_or(manifest_dir);
+
+        Self::new().with_formatter(VisualStudioFormatter::new().with_source_root(workspace_root))
+    }
+
+    pub fn with_formatter<F: Formatter + 'static>(self, formatter: F) -> Self {
+        Self {
+            formatter: Arc::new(formatter),
+        }
     }
 
-    pub fn output_debug_string(&self, s: &[u16]) {
+    pub fn output_debug_string(&self, s: &WStr) {
         unsafe { OutputDebugStringW(s.as_ptr()) };
     }
 }
 
+impl Default for DebugConsoleSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Sink for DebugConsoleSink {
-    fn enabled(&self, level: &Level) -> bool {
-        matches!(self.max_level.lock().unwrap().into_level(), Some(ref max_level) if level <= max_level)
-    }
-
-    fn log(
-        &self,
-        level: &Level,
-        msg: &str,
-        args: Option<&str>,
-        file: Option<&str>,
-        line: Option<u32>,
-    ) {
-        let temp = match (args, file, line) {
-            (Some(args), Some(file), Some(line)) => {
-                wstr!("[{}][{}:{}] {} {}\n", level, file, line, msg, args)
-            }
-            (None, Some(file), Some(line)) => {
-                wstr!("[{}][{}:{}] {}\n", level, file, line, msg)
-            }
-            (Some(args), None, None) => wstr!("[{}][unknown:unknown] {} {}\n", level, msg, args),
-            _ => wstr!("[{}][unknown:unknown] {}\n", level, msg),
-        };
-
-        self.output_debug_string(&temp);
-    }
-
-    fn flush(&self) {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        self.log_utf16(record, Utf16Line::new().format(&*self.formatter, record))
+    }
+
+    fn utf16_formatter(&self) -> Option<&dyn Formatter> {
+        Some(&*self.formatter)
+    }
+
+    fn log_utf16(&self, _record: &LogRecord, line: &mut Vec<u16>) -> Result<(), Error> {
+        line.extend_from_slice(&[u16::from(b'\n'), 0]);
+        if let Some(line) = WStr::from_slice_until_nul(line) {
+            self.output_debug_string(line);
+        }
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
         // Nothing to do here.
+        Ok(())
+    }
+}
+
+/// Breaks into the attached debugger when a record at `level` or above is logged, so the state
+/// that led to an error can be inspected where it happened. Does nothing without a debugger.
+///
+/// note: with an asynchronous logger, the break happens on the logger's thread, after the call
+/// that logged has moved on.
+#[derive(Clone)]
+pub struct DebugBreakSink {
+    level: Level,
+}
+
+impl DebugBreakSink {
+    /// Breaks on errors.
+    pub fn new() -> Self {
+        Self {
+            level: Level::ERROR,
+        }
+    }
+
+    pub fn with_level(self, level: Level) -> Self {
+        Self { level }
+    }
+}
+
+impl Default for DebugBreakSink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Sink for DebugBreakSink {
+    fn enabled(&self, level: &Level) -> bool {
+        *level <= self.level
+    }
+
+    fn log(&self, _record: &LogRecord) -> Result<(), Error> {
+        debug::debug_break();
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        Ok(())
     }
 }
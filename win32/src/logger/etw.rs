@@ -0,0 +1,316 @@
+use common::{
+    error::Error,
+    log::{FieldValue, LogRecord, Sink, SpanRecord},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    thread::ThreadId,
+};
+use tracing::Level;
+
+use windows_sys::{
+    core::GUID,
+    Win32::System::Diagnostics::Etw::{
+        EventActivityIdControl, EventEnabled, EventProviderSetTraits, EventRegister,
+        EventSetInformation, EventUnregister, EventWriteTransfer, EVENT_ACTIVITY_CTRL_CREATE_ID,
+        EVENT_DATA_DESCRIPTOR, EVENT_DATA_DESCRIPTOR_0, EVENT_DESCRIPTOR,
+    },
+};
+
+use crate::error::win32_error;
+
+// TraceLogging self-describing event encoding. see TraceLoggingProvider.h in the Windows SDK.
+const CHANNEL_TRACELOGGING: u8 = 11;
+const OPCODE_INFO: u8 = 0;
+const OPCODE_START: u8 = 1;
+const OPCODE_STOP: u8 = 2;
+const DESCRIPTOR_TYPE_EVENT_METADATA: u32 = 1;
+const DESCRIPTOR_TYPE_PROVIDER_METADATA: u32 = 2;
+const IN_TYPE_ANSI_STRING: u8 = 2;
+const IN_TYPE_UINT32: u8 = 8;
+const IN_TYPE_INT64: u8 = 9;
+const IN_TYPE_UINT64: u8 = 10;
+const IN_TYPE_DOUBLE: u8 = 12;
+const IN_TYPE_BOOL32: u8 = 13;
+const IN_TYPE_CHAIN_FLAG: u8 = 0x80;
+const OUT_TYPE_UTF8: u8 = 35;
+
+/// Emits records as TraceLogging events so they can be captured alongside CPU and GPU samples in
+/// Windows Performance Analyzer. Spans are written as activity start/stop pairs.
+#[derive(Clone)]
+pub struct EtwSink {
+    provider: Arc<Provider>,
+    activities: Arc<Mutex<ActivityStacks>>,
+}
+
+/// The span ids and activity ids currently entered on each thread, innermost last.
+type ActivityStacks = HashMap<ThreadId, Vec<(u64, GUID)>>;
+
+struct Provider {
+    handle: u64,
+    metadata: Vec<u8>,
+}
+
+impl Drop for Provider {
+    fn drop(&mut self) {
+        unsafe { EventUnregister(self.handle) };
+    }
+}
+
+impl EtwSink {
+    /// Registers a TraceLogging provider. `name` is what shows up in WPA; `guid` must be the
+    /// provider id the trace session is configured to enable.
+    pub fn new(name: &str, guid: GUID) -> Result<Self, Error> {
+        let mut handle = 0;
+        let status = unsafe { EventRegister(&guid, None, std::ptr::null(), &mut handle) };
+        if status != 0 {
+            return Err(win32_error(
+                &format!("failed to register etw provider {name}"),
+                status,
+            ));
+        }
+
+        // Provider traits: a u16 total size followed by the nul-terminated provider name.
+        let mut metadata = Vec::with_capacity(name.len() + 3);
+        metadata.extend_from_slice(&[0, 0]);
+        push_str(&mut metadata, name);
+        patch_size(&mut metadata);
+
+        unsafe {
+            EventSetInformation(
+                handle,
+                EventProviderSetTraits,
+                metadata.as_ptr().cast(),
+                metadata.len() as u32,
+            )
+        };
+
+        Ok(Self {
+            provider: Arc::new(Provider { handle, metadata }),
+            activities: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    fn write(
+        &self,
+        descriptor: &EVENT_DESCRIPTOR,
+        event: &EventBuilder,
+        activity_id: Option<&GUID>,
+        related_id: Option<&GUID>,
+    ) -> Result<(), Error> {
+        let data = [
+            data_descriptor(&self.provider.metadata, DESCRIPTOR_TYPE_PROVIDER_METADATA),
+            data_descriptor(&event.metadata, DESCRIPTOR_TYPE_EVENT_METADATA),
+            data_descriptor(&event.data, 0),
+        ];
+
+        let status = unsafe {
+            EventWriteTransfer(
+                self.provider.handle,
+                descriptor,
+                activity_id.map_or(std::ptr::null(), |id| id as *const GUID),
+                related_id.map_or(std::ptr::null(), |id| id as *const GUID),
+                data.len() as u32,
+                data.as_ptr(),
+            )
+        };
+        if status != 0 {
+            return Err(win32_error("failed to write ETW event", status));
+        }
+
+        Ok(())
+    }
+
+    fn is_enabled(&self, descriptor: &EVENT_DESCRIPTOR) -> bool {
+        unsafe { EventEnabled(self.provider.handle, descriptor) != 0 }
+    }
+}
+
+impl Sink for EtwSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let descriptor = event_descriptor(&record.level, OPCODE_INFO);
+        if !self.is_enabled(&descriptor) {
+            return Ok(());
+        }
+
+        let mut event = EventBuilder::new(record.target);
+        event.str("message", &record.message);
+        event.str("target", record.target);
+        if let Some(file) = record.file {
+            event.str("file", file);
+        }
+        if let Some(line) = record.line {
+            event.u32("line", line);
+        }
+        for field in &record.fields {
+            event.value(field.name, &field.value);
+        }
+        event.finish();
+
+        let activity_id = self
+            .activities
+            .lock()
+            .unwrap()
+            .get(&record.thread_id)
+            .and_then(|stack| stack.last())
+            .map(|(_, id)| *id);
+
+        self.write(&descriptor, &event, activity_id.as_ref(), None)
+    }
+
+    fn span_enter(&self, span: &SpanRecord) {
+        let descriptor = event_descriptor(&span.level, OPCODE_START);
+        if !self.is_enabled(&descriptor) {
+            return;
+        }
+
+        let mut activity_id = GUID::from_u128(0);
+        unsafe { EventActivityIdControl(EVENT_ACTIVITY_CTRL_CREATE_ID, &mut activity_id) };
+
+        let related_id = {
+            let mut activities = self.activities.lock().unwrap();
+            let stack = activities.entry(span.thread_id).or_default();
+            let related_id = stack.last().map(|(_, id)| *id);
+            stack.push((span.id, activity_id));
+            related_id
+        };
+
+        let mut event = EventBuilder::new(span.name);
+        event.str("target", span.target);
+        for field in span.fields.iter() {
+            event.value(field.name, &field.value);
+        }
+        event.finish();
+
+        _ = self.write(&descriptor, &event, Some(&activity_id), related_id.as_ref());
+    }
+
+    fn span_exit(&self, span: &SpanRecord) {
+        let activity_id = {
+            let mut activities = self.activities.lock().unwrap();
+            let Some(stack) = activities.get_mut(&span.thread_id) else {
+                return;
+            };
+            let Some(index) = stack.iter().rposition(|(id, _)| *id == span.id) else {
+                return;
+            };
+            stack.remove(index).1
+        };
+
+        let descriptor = event_descriptor(&span.level, OPCODE_STOP);
+        let mut event = EventBuilder::new(span.name);
+        event.finish();
+
+        _ = self.write(&descriptor, &event, Some(&activity_id), None);
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Nothing to do here.
+        Ok(())
+    }
+}
+
+/// Accumulates the self-describing metadata and payload for one event.
+struct EventBuilder {
+    metadata: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl EventBuilder {
+    fn new(name: &str) -> Self {
+        // Event metadata: a u16 total size, a tag byte, then the nul-terminated event name.
+        let mut metadata = Vec::with_capacity(128);
+        metadata.extend_from_slice(&[0, 0, 0]);
+        push_str(&mut metadata, name);
+
+        Self {
+            metadata,
+            data: Vec::with_capacity(256),
+        }
+    }
+
+    fn field(&mut self, name: &str, in_type: u8, out_type: Option<u8>) {
+        push_str(&mut self.metadata, name);
+        match out_type {
+            Some(out_type) => {
+                self.metadata.push(in_type | IN_TYPE_CHAIN_FLAG);
+                self.metadata.push(out_type);
+            }
+            None => self.metadata.push(in_type),
+        }
+    }
+
+    fn str(&mut self, name: &str, value: &str) {
+        self.field(name, IN_TYPE_ANSI_STRING, Some(OUT_TYPE_UTF8));
+        push_str(&mut self.data, value);
+    }
+
+    fn u32(&mut self, name: &str, value: u32) {
+        self.field(name, IN_TYPE_UINT32, None);
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn value(&mut self, name: &str, value: &FieldValue) {
+        match value {
+            FieldValue::I64(value) => {
+                self.field(name, IN_TYPE_INT64, None);
+                self.data.extend_from_slice(&value.to_le_bytes());
+            }
+            FieldValue::U64(value) => {
+                self.field(name, IN_TYPE_UINT64, None);
+                self.data.extend_from_slice(&value.to_le_bytes());
+            }
+            FieldValue::F64(value) => {
+                self.field(name, IN_TYPE_DOUBLE, None);
+                self.data.extend_from_slice(&value.to_le_bytes());
+            }
+            FieldValue::Bool(value) => {
+                self.field(name, IN_TYPE_BOOL32, None);
+                self.data.extend_from_slice(&(*value as i32).to_le_bytes());
+            }
+            value => self.str(name, &value.to_string()),
+        }
+    }
+
+    fn finish(&mut self) {
+        patch_size(&mut self.metadata);
+    }
+}
+
+fn event_descriptor(level: &Level, opcode: u8) -> EVENT_DESCRIPTOR {
+    let level = match *level {
+        Level::ERROR => 2,
+        Level::WARN => 3,
+        Level::INFO => 4,
+        _ => 5,
+    };
+
+    EVENT_DESCRIPTOR {
+        Id: 0,
+        Version: 0,
+        Channel: CHANNEL_TRACELOGGING,
+        Level: level,
+        Opcode: opcode,
+        Task: 0,
+        Keyword: 0,
+    }
+}
+
+fn data_descriptor(bytes: &[u8], kind: u32) -> EVENT_DATA_DESCRIPTOR {
+    EVENT_DATA_DESCRIPTOR {
+        Ptr: bytes.as_ptr() as u64,
+        Size: bytes.len() as u32,
+        Anonymous: EVENT_DATA_DESCRIPTOR_0 { Reserved: kind },
+    }
+}
+
+fn push_str(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend(value.bytes().filter(|b| *b != 0));
+    buffer.push(0);
+}
+
+fn patch_size(buffer: &mut [u8]) {
+    let size = (buffer.len() as u16).to_le_bytes();
+    buffer[..2].copy_from_slice(&size);
+}
@@ -0,0 +1,109 @@
+use common::{
+    error::Error,
+    log::{
+        format::{CompactFormatter, Formatter, Utf16Line},
+        LogRecord, Sink,
+    },
+};
+use std::sync::Arc;
+use tracing::Level;
+
+use windows_sys::Win32::{
+    Foundation::HANDLE,
+    System::EventLog::{
+        DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+        EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+    },
+};
+
+use crate::{error::last_error, wstr};
+
+/// Writes records to the Windows Event Log under the Application log.
+///
+/// No message file is registered for the source, so Event Viewer prefixes each entry with a note
+/// that the event description cannot be found; the record text follows it unchanged.
+#[derive(Clone)]
+pub struct EventLogSink {
+    source: Arc<EventSource>,
+    formatter: Arc<dyn Formatter>,
+}
+
+struct EventSource(HANDLE);
+
+impl Drop for EventSource {
+    fn drop(&mut self) {
+        unsafe { DeregisterEventSource(self.0) };
+    }
+}
+
+impl EventLogSink {
+    /// Registers `source_name` as an event source. Only WARN and ERROR records are written; the
+    /// event log is not the place for routine output.
+    pub fn new(source_name: &str) -> Result<Self, Error> {
+        let source_name = wstr!("{source_name}");
+        let handle = unsafe { RegisterEventSourceW(std::ptr::null(), source_name.as_ptr()) };
+        if handle == 0 {
+            return Err(last_error("failed to register event source"));
+        }
+
+        Ok(Self {
+            source: Arc::new(EventSource(handle)),
+            formatter: Arc::new(CompactFormatter),
+        })
+    }
+
+    pub fn with_formatter<F: Formatter + 'static>(self, formatter: F) -> Self {
+        Self {
+            formatter: Arc::new(formatter),
+            ..self
+        }
+    }
+}
+
+impl Sink for EventLogSink {
+    fn enabled(&self, level: &Level) -> bool {
+        *level <= Level::WARN
+    }
+
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        self.log_utf16(record, Utf16Line::new().format(&*self.formatter, record))
+    }
+
+    fn utf16_formatter(&self) -> Option<&dyn Formatter> {
+        Some(&*self.formatter)
+    }
+
+    fn log_utf16(&self, record: &LogRecord, text: &mut Vec<u16>) -> Result<(), Error> {
+        let event_type = match record.level {
+            Level::ERROR => EVENTLOG_ERROR_TYPE,
+            Level::WARN => EVENTLOG_WARNING_TYPE,
+            _ => EVENTLOG_INFORMATION_TYPE,
+        };
+
+        text.push(0);
+        let strings = [text.as_ptr()];
+        let reported = unsafe {
+            ReportEventW(
+                self.source.0,
+                event_type,
+                0,
+                0,
+                std::ptr::null_mut(),
+                strings.len() as u16,
+                0,
+                strings.as_ptr(),
+                std::ptr::null(),
+            )
+        };
+        if reported == 0 {
+            return Err(last_error("failed to report event"));
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Nothing to do here.
+        Ok(())
+    }
+}
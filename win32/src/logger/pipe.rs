@@ -0,0 +1,253 @@
+use common::{
+    error::Error,
+    log::{
+        self,
+        binary::{BinaryDecoder, BinaryEncoder},
+        Field, FieldValue, LogRecord, Sink,
+    },
+};
+use std::{
+    io::{BufReader, Write},
+    path::PathBuf,
+    process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+use tracing::warn;
+
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, FALSE},
+    System::{
+        Pipes::GetNamedPipeClientProcessId,
+        Threading::{
+            OpenProcess, QueryFullProcessImageNameW, PROCESS_NAME_WIN32,
+            PROCESS_QUERY_LIMITED_INFORMATION,
+        },
+    },
+};
+
+use crate::{pipe::Pipe, wide};
+
+/// Set by [`PipeCollector::configure`] in a child's environment and read by
+/// [`PipeSink::from_env`].
+pub const PIPE_ENV_VAR: &str = "GALLEON_LOG_PIPE";
+
+const PIPE_BUFFER_SIZE: u32 = 64 * 1024;
+
+/// Sends records to the parent process's [`PipeCollector`] in the binary log format. Writes block
+/// once the pipe's buffer is full, so a child that logs heavily should log asynchronously.
+#[derive(Clone)]
+pub struct PipeSink {
+    inner: Arc<Mutex<PipeSinkInner>>,
+}
+
+struct PipeSinkInner {
+    pipe: Pipe,
+    encoder: BinaryEncoder,
+    buffer: Vec<u8>,
+}
+
+impl PipeSink {
+    /// Connects to the collector listening on `name`.
+    pub fn connect(name: &str) -> Result<Self, Error> {
+        let mut pipe = Pipe::connect(name)?;
+        let encoder = BinaryEncoder::new();
+        let mut buffer = Vec::with_capacity(256);
+        encoder.header(&mut buffer);
+        pipe.write_all(&buffer)
+            .map_err(|err| Error::new("failed to write to log pipe").with_source(err))?;
+
+        Ok(Self {
+            inner: Arc::new(Mutex::new(PipeSinkInner {
+                pipe,
+                encoder,
+                buffer,
+            })),
+        })
+    }
+
+    /// Connects to the collector named by `GALLEON_LOG_PIPE`, which is set when the parent
+    /// spawned this process through [`PipeCollector::configure`].
+    pub fn from_env() -> Result<Self, Error> {
+        let name = std::env::var(PIPE_ENV_VAR)
+            .map_err(|err| Error::new(format!("{PIPE_ENV_VAR} is not set")).with_source(err))?;
+        Self::connect(&name)
+    }
+}
+
+impl Sink for PipeSink {
+    fn log(&self, record: &LogRecord) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        let PipeSinkInner {
+            pipe,
+            encoder,
+            buffer,
+        } = &mut *inner;
+
+        buffer.clear();
+        encoder.encode(record, buffer);
+        pipe.write_all(buffer)
+            .map_err(|err| Error::new("failed to write to log pipe").with_source(err))
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        // Nothing to do here; pipe writes aren't buffered on this side.
+        Ok(())
+    }
+}
+
+/// Listens on a named pipe and forwards the records of every child connected through a
+/// [`PipeSink`] to this process's sinks, tagged with `process`, `pid` and `thread` fields. Each
+/// child gets its own reader thread, so records from different children interleave as they
+/// arrive.
+///
+/// Dropping the collector stops accepting new children; children already connected are read
+/// until they disconnect.
+pub struct PipeCollector {
+    name: String,
+    closed: Arc<AtomicBool>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl PipeCollector {
+    /// Creates the pipe `\\.\pipe\<name>`. Fails if another process already owns it.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let first = Pipe::create(name, true, PIPE_BUFFER_SIZE)?;
+        let closed = Arc::new(AtomicBool::new(false));
+
+        let listener = {
+            let name = name.to_string();
+            let closed = closed.clone();
+            thread::Builder::new()
+                .name("galleon-pipe-collector".to_string())
+                .spawn(move || listen(&name, first, &closed))
+                .map_err(|err| {
+                    Error::new("failed to spawn pipe collector thread").with_source(err)
+                })?
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            closed,
+            listener: Some(listener),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Points a child at this collector through its environment; the child then only needs
+    /// `PipeSink::from_env()`.
+    pub fn configure<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        command.env(PIPE_ENV_VAR, &self.name)
+    }
+}
+
+impl Drop for PipeCollector {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Release);
+
+        // The listener is blocked waiting for a client, so connect to wake it up.
+        _ = Pipe::connect(&self.name);
+
+        if let Some(listener) = self.listener.take() {
+            _ = listener.join();
+        }
+    }
+}
+
+/// Accepts clients one at a time, handing each connected instance to a reader thread and creating
+/// a fresh instance for the next client.
+fn listen(name: &str, mut pipe: Pipe, closed: &AtomicBool) {
+    loop {
+        let connected = pipe.accept();
+        if closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        if connected {
+            if let Err(err) = thread::Builder::new()
+                .name("galleon-pipe-reader".to_string())
+                .spawn(move || forward(pipe))
+            {
+                warn!("failed to spawn log pipe reader: {err}");
+            }
+        }
+
+        pipe = match Pipe::create(name, false, PIPE_BUFFER_SIZE) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!("{err}");
+                return;
+            }
+        };
+    }
+}
+
+fn forward(pipe: Pipe) {
+    let mut pid = 0;
+    unsafe { GetNamedPipeClientProcessId(pipe.0, &mut pid) };
+    let process = process_name(pid).unwrap_or_else(|| "unknown".to_string());
+
+    let decoder = match BinaryDecoder::new(BufReader::new(pipe)) {
+        Ok(decoder) => decoder,
+        Err(err) => {
+            warn!("log pipe from {process} ({pid}): {err}");
+            return;
+        }
+    };
+
+    for record in decoder {
+        let mut record = match record {
+            Ok(record) => record,
+            Err(err) => {
+                warn!("log pipe from {process} ({pid}): {err}");
+                return;
+            }
+        };
+
+        let thread = std::mem::take(&mut record.thread);
+        let mut record = record.into_log_record();
+        record.fields.extend([
+            Field {
+                name: "process",
+                value: FieldValue::Str(process.clone()),
+            },
+            Field {
+                name: "pid",
+                value: FieldValue::U64(u64::from(pid)),
+            },
+            Field {
+                name: "thread",
+                value: FieldValue::Str(thread),
+            },
+        ]);
+        log::submit(record);
+    }
+}
+
+/// The executable name of `pid`, without its directory or extension.
+fn process_name(pid: u32) -> Option<String> {
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, pid) };
+    if process == 0 {
+        return None;
+    }
+
+    let mut path = [0u16; 1024];
+    let mut len = path.len() as u32;
+    let ok = unsafe {
+        QueryFullProcessImageNameW(process, PROCESS_NAME_WIN32, path.as_mut_ptr(), &mut len)
+    };
+    unsafe { CloseHandle(process) };
+    if ok == FALSE {
+        return None;
+    }
+
+    let path = PathBuf::from(wide::from_wide_os(&path[..len as usize]));
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
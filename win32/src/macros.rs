@@ -1,7 +1,59 @@
+/// Formats a [`WString`](crate::wide::WString), e.g. `wstr!("{count} files")`.
 #[macro_export]
 macro_rules! wstr {
     ($($arg:tt)*) => {{
-        let utf8 = std::fmt::format(format_args!($($arg)*));
-        utf8.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>()
+        $crate::wide::WString::from(std::fmt::format(format_args!($($arg)*)))
+    }};
+}
+
+/// Calls a Win32 function and checks for failure by its convention, returning a
+/// `Result` whose error names the function and carries the system's error code.
+///
+/// - `call!(F(..))`: `F` returns a `BOOL`, which is zero on failure. Gives `()`.
+/// - `call!(handle F(..))`: `F` returns a handle, which is null or `INVALID_HANDLE_VALUE` on
+///   failure. Gives the handle.
+/// - `call!(hresult F(..))`: `F` returns an `HRESULT`, which is negative on failure. Gives `()`.
+/// - `call!(status F(..))`: `F` returns a Win32 error code, which is nonzero on failure, as
+///   registry functions do. Gives `()`.
+///
+/// The call isn't wrapped in `unsafe`, so the call site still has to be:
+///
+/// ```no_run
+/// use windows_sys::Win32::System::Threading::{
+///     GetCurrentThread, SetThreadPriority, THREAD_PRIORITY_HIGHEST,
+/// };
+///
+/// unsafe { win32::call!(SetThreadPriority(GetCurrentThread(), THREAD_PRIORITY_HIGHEST)) }?;
+/// # Ok::<(), common::error::Error>(())
+/// ```
+#[macro_export]
+macro_rules! call {
+    (handle $($f:ident)::+ ($($arg:expr),* $(,)?)) => {{
+        let handle = $($f)::+($($arg),*);
+        if handle == 0 || handle == ::windows_sys::Win32::Foundation::INVALID_HANDLE_VALUE {
+            Err($crate::error::last_error(concat!(stringify!($($f)::+), " failed")))
+        } else {
+            Ok(handle)
+        }
+    }};
+    (hresult $($f:ident)::+ ($($arg:expr),* $(,)?)) => {{
+        $crate::error::Hresult($($f)::+($($arg),*))
+            .check(concat!(stringify!($($f)::+), " failed"))
+    }};
+    (status $($f:ident)::+ ($($arg:expr),* $(,)?)) => {{
+        match $($f)::+($($arg),*) {
+            0 => Ok(()),
+            status => Err($crate::error::win32_error(
+                concat!(stringify!($($f)::+), " failed"),
+                status,
+            )),
+        }
+    }};
+    ($($f:ident)::+ ($($arg:expr),* $(,)?)) => {{
+        if $($f)::+($($arg),*) == 0 {
+            Err($crate::error::last_error(concat!(stringify!($($f)::+), " failed")))
+        } else {
+            Ok(())
+        }
     }};
 }
@@ -1,26 +1,147 @@
 #![cfg_attr(not(test), windows_subsystem = "windows")]
 
-#[allow(clippy::non_minimal_cfg)]
-#[cfg(all(not(target_os = "windows")))]
-compile_error!("only windows is supported");
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
 
-use common::log::{self};
-use tracing::{error, info, info_span, level_filters::LevelFilter};
+use common::{
+    error::{Error, ErrorKind},
+    graphics::{Backend, GraphicsDevice, Image, TextureFilter, TextureId},
+    hot_reload::Reloadable,
+    log::{
+        self,
+        sinks::{ConsoleSink, RingBufferSink},
+        Logger,
+    },
+};
+use tracing::{error, info, info_span, level_filters::LevelFilter, warn};
+use win32::{
+    args::{Args, Flag},
+    console,
+    crash_report::{self, CrashReporter},
+    debug,
+    event_loop::{App, Context, ControlFlow, Event, EventLoop},
+    file_watcher::FileWatcher,
+    guard, image,
+    logger::{DebugBreakSink, DebugConsoleSink},
+    paths::AppPaths,
+    single_instance::SingleInstance,
+    thread,
+    watchdog::Watchdog,
+    window::Window,
+    wstr,
+};
 
-use crate::logger::DebugConsoleSink;
+const APP_NAME: &str = "Galleon";
 
-mod logger;
-mod macros;
+/// Where assets are loaded from, relative to the working directory, and watched for changes.
+const ASSETS_DIR: &str = "assets";
 
-fn main() {
-    let log_sink = DebugConsoleSink::new(LevelFilter::TRACE);
-    if let Err(err) = log::startup(LevelFilter::TRACE) {
-        let msg = wstr!("{err}\n");
-        log_sink.output_debug_string(&msg);
-        return;
+fn main() -> ExitCode {
+    _ = thread::set_current_name("galleon-main");
+    _ = thread::suppress_error_dialogs();
+    let log_sink = DebugConsoleSink::new();
+    let _logger = match Logger::builder().max_level(LevelFilter::TRACE).init() {
+        Ok(guard) => guard,
+        Err(err) => {
+            let msg = wstr!("{err}\n");
+            log_sink.output_debug_string(&msg);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    log::install_panic_hook();
+
+    let crash_reporter = crash_reporter().inspect_err(|err| error!("{err}")).ok();
+    if let Some(reporter) = &crash_reporter {
+        reporter.install_exception_filter();
+        if let Err(err) =
+            reporter.upload_pending(|reports| crash_report::ask_consent(reports.len()))
+        {
+            error!("{}", err.full_message());
+        }
+    }
+
+    match guard::run_guarded(|| run(log_sink)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            if let Some(reporter) = &crash_reporter {
+                match reporter.write_with_minidump(&err) {
+                    Ok(path) => info!("wrote crash report to {}", path.display()),
+                    Err(err) => error!("{}", err.full_message()),
+                }
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Keeps the last records for crash reports, which are uploaded to `GALLEON_CRASH_REPORT_URL` if
+/// it's set.
+fn crash_reporter() -> Result<CrashReporter, Error> {
+    let crash_log = RingBufferSink::new(256);
+    log::add_sink(&crash_log);
+
+    let builder = CrashReporter::builder(AppPaths::new(APP_NAME).crashes_dir()?).log(crash_log);
+    match std::env::var("GALLEON_CRASH_REPORT_URL") {
+        Ok(url) => builder.endpoint(url).build(),
+        Err(_) => builder.build(),
+    }
+}
+
+const FLAGS: &[Flag] = &[
+    Flag::switch("console", "open a console window for log output"),
+    Flag::switch(
+        "wait-for-debugger",
+        "wait up to a minute for a debugger to attach before starting",
+    ),
+    Flag::switch(
+        "break-on-error",
+        "break into the debugger when an error is logged",
+    ),
+    Flag::value(
+        "log-level",
+        "the most verbose level logged, e.g. debug or off",
+    ),
+    Flag::value(
+        "renderer",
+        "the graphics backend, d3d11 or d3d12, or GALLEON_RENDERER",
+    ),
+    Flag::switch(
+        "spy-messages",
+        "log every window message, with --log-level trace",
+    ),
+];
+
+fn run(log_sink: DebugConsoleSink) -> Result<(), Error> {
+    let log_sink_id = log::add_sink(&log_sink);
+    let args = Args::parse(FLAGS)?;
+    if args.is_set("wait-for-debugger") && debug::wait_for_debugger(Duration::from_secs(60)) {
+        debug::debug_break();
+    }
+    if args.is_set("break-on-error") {
+        log::add_sink(&DebugBreakSink::new());
     }
+    #[cfg(feature = "tracy")]
+    log::add_sink(&log::sinks::TracySink::new());
+    let Some(single_instance) = SingleInstance::acquire(APP_NAME)? else {
+        info!("handed the command line to the running instance");
+        return Ok(());
+    };
 
-    log::add_sink(&log_sink);
+    if args.is_set("console") {
+        match console::alloc_console() {
+            Ok(()) => {
+                let console_sink = ConsoleSink::new();
+                console_sink.set_colors(console::enable_virtual_terminal());
+                log::add_sink(&console_sink);
+            }
+            Err(err) => error!("{err}"),
+        }
+    }
 
     let greeting = wstr!("{}\n", common::greet("shipmate"));
     log_sink.output_debug_string(&greeting);
@@ -33,14 +154,225 @@ fn main() {
 
     info!(milk = 3, "Test message 1");
 
-    log::set_max_level(LevelFilter::ERROR);
-    log_sink.set_max_level(LevelFilter::INFO);
+    log::set_max_level(args.parse_value("log-level")?.unwrap_or(LevelFilter::ERROR));
+    log::set_sink_level(log_sink_id, LevelFilter::INFO);
 
     info!(cheese = 7, "Test message 2");
 
-    // logger::remove_sink(&log_sink);
+    // log::remove_sink(log_sink_id);
 
     error!("Test message 3");
 
-    log::shutdown();
+    let window = Window::builder()
+        .title("Galleon")
+        .size(1280, 720)
+        .spy_messages(args.is_set("spy-messages"))
+        .build()?;
+    let mut event_loop = EventLoop::new();
+    event_loop.set_watchdog(Some(
+        Watchdog::builder()
+            .dump_dir(AppPaths::new(APP_NAME).crashes_dir()?)
+            .build()?,
+    ));
+    event_loop.set_single_instance(Some(single_instance));
+    let mut graphics = create_graphics(renderer_backend(&args)?, &window)?;
+
+    let assets_dir = Path::new(ASSETS_DIR);
+    let assets = Assets::load(assets_dir, graphics.as_mut());
+    if assets_dir.is_dir() {
+        match FileWatcher::new(assets_dir) {
+            Ok(watcher) => event_loop.set_file_watcher(Some(watcher)),
+            Err(err) => error!("{}", err.full_message()),
+        }
+    }
+    event_loop.run(&window, &mut Game { graphics, assets })
+}
+
+/// The backends `create_graphics` can create. renderer-wgpu is built on its own for now, see its
+/// Cargo.toml, so it isn't one of them.
+const BACKENDS: &[Backend] = &[Backend::D3D11, Backend::D3D12];
+
+/// The backend from `--renderer`, or else `GALLEON_RENDERER`, defaulting to D3D11.
+fn renderer_backend(args: &Args) -> Result<Backend, Error> {
+    let backend = match args.parse_value("renderer")? {
+        Some(backend) => backend,
+        None => match std::env::var("GALLEON_RENDERER") {
+            Ok(backend) => backend.parse()?,
+            Err(_) => Backend::D3D11,
+        },
+    };
+    if !BACKENDS.contains(&backend) {
+        return Err(Error::new(format!(
+            "the {backend} renderer isn't available, expected d3d11 or d3d12"
+        ))
+        .with_kind(ErrorKind::Config));
+    }
+
+    Ok(backend)
+}
+
+fn create_graphics(backend: Backend, window: &Window) -> Result<Box<dyn GraphicsDevice>, Error> {
+    info!("using the {backend} renderer");
+    Ok(match backend {
+        Backend::D3D11 => Box::new(renderer_d3d11::Renderer::builder().build(window)?),
+        Backend::D3D12 => Box::new(renderer_d3d12::Renderer::builder().build(window)?),
+        Backend::Wgpu => unreachable!("renderer_backend only picks one of BACKENDS"),
+    })
+}
+
+/// The assets that are reloaded when their files change: the logging config, a replacement for
+/// the sprite shader and the textures. Each is optional, and one that fails to load is logged
+/// and left out.
+struct Assets {
+    log_config: Option<Reloadable<()>>,
+    sprite_shader: Option<Reloadable<String>>,
+    textures: Vec<TextureAsset>,
+}
+
+struct TextureAsset {
+    id: TextureId,
+    size: [u32; 2],
+    image: Reloadable<Image>,
+}
+
+impl Assets {
+    /// Applies `log.toml`, replaces the sprite shader with `shaders/sprite.hlsl` and creates a
+    /// texture for each image in `textures`, of those under `root` that exist.
+    fn load(root: &Path, graphics: &mut dyn GraphicsDevice) -> Self {
+        let log_config = load_asset(root.join("log.toml"), |path| log::apply_config_file(path));
+
+        let sprite_shader = load_asset(root.join("shaders").join("sprite.hlsl"), read_text);
+        if let Some(shader) = &sprite_shader {
+            if let Err(err) = graphics.set_sprite_shader(shader.get()) {
+                error!("{}", err.full_message());
+            }
+        }
+
+        let mut paths: Vec<PathBuf> = fs::read_dir(root.join("textures"))
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.is_file())
+            .collect();
+        paths.sort();
+        let textures = paths
+            .into_iter()
+            .filter_map(|path| load_asset(path, |path| image::load(path)))
+            .filter_map(|image| {
+                let size = [image.get().width(), image.get().height()];
+                graphics
+                    .create_texture(image.get().clone(), TextureFilter::Linear)
+                    .inspect_err(|err| error!("{}", err.full_message()))
+                    .ok()
+                    .map(|id| TextureAsset { id, size, image })
+            })
+            .collect();
+
+        Self {
+            log_config,
+            sprite_shader,
+            textures,
+        }
+    }
+
+    /// Reloads the assets `path` is, or is a dependency of, and hands them to `graphics`. If it
+    /// won't take one, what it had is kept.
+    fn file_changed(&mut self, path: &Path, graphics: &mut dyn GraphicsDevice) {
+        if let Some(config) = &mut self.log_config {
+            config.file_changed(path);
+        }
+
+        if let Some(shader) = &mut self.sprite_shader {
+            if shader.file_changed(path) {
+                if let Err(err) = graphics.set_sprite_shader(shader.get()) {
+                    error!(
+                        "failed to reload the sprite shader, keeping the previous one: {}",
+                        err.full_message()
+                    );
+                }
+            }
+        }
+
+        for texture in &mut self.textures {
+            if !texture.image.file_changed(path) {
+                continue;
+            }
+
+            let image = texture.image.get();
+            let size = [image.width(), image.height()];
+            // note: textures can't be resized, so one that changes size is replaced.
+            let result = if size == texture.size {
+                graphics.update_texture(texture.id, [0, 0], image)
+            } else {
+                graphics
+                    .create_texture(image.clone(), TextureFilter::Linear)
+                    .map(|id| {
+                        graphics.destroy_texture(texture.id);
+                        texture.id = id;
+                        texture.size = size;
+                    })
+            };
+            if let Err(err) = result {
+                error!(
+                    "failed to reload the texture {}, keeping the previous one: {}",
+                    texture.image.path().display(),
+                    err.full_message()
+                );
+            }
+        }
+    }
+}
+
+/// The asset at `path` if it exists, logging why if it fails to load.
+fn load_asset<T, F>(path: PathBuf, load: F) -> Option<Reloadable<T>>
+where
+    F: FnMut(&Path) -> Result<T, Error> + 'static,
+{
+    if !path.exists() {
+        return None;
+    }
+    Reloadable::new(path, load)
+        .inspect_err(|err| error!("{}", err.full_message()))
+        .ok()
+}
+
+fn read_text(path: &Path) -> Result<String, Error> {
+    fs::read_to_string(path).map_err(|err| {
+        Error::new(format!("failed to read {}", path.display()))
+            .with_kind(ErrorKind::Io)
+            .with_source(err)
+    })
+}
+
+struct Game {
+    graphics: Box<dyn GraphicsDevice>,
+    assets: Assets,
+}
+
+impl App for Game {
+    fn event(&mut self, _cx: &Context, event: &Event) -> ControlFlow {
+        match event {
+            Event::CloseRequested => return ControlFlow::Exit,
+            Event::Resized(size) => {
+                if let Err(err) = self.graphics.resize(size.width, size.height) {
+                    warn!("{}", err.full_message());
+                }
+            }
+            Event::FileChanged(change) => {
+                self.assets
+                    .file_changed(&change.path, self.graphics.as_mut());
+            }
+            _ => {}
+        }
+        ControlFlow::Continue
+    }
+
+    fn update(&mut self, _cx: &Context) -> Result<ControlFlow, Error> {
+        Ok(ControlFlow::Continue)
+    }
+
+    fn render(&mut self, _cx: &Context) -> Result<(), Error> {
+        self.graphics.begin_frame()?.clear([0.05, 0.08, 0.12, 1.0]);
+        self.graphics.end_frame()
+    }
 }
@@ -0,0 +1,69 @@
+//! Minidumps of this process, for crash and hang reports. Open them in Visual Studio or WinDbg
+//! with the matching PDBs to see every thread's stack.
+
+use std::{fs::File, os::windows::io::AsRawHandle, path::Path};
+
+use common::error::{Context, Error};
+use windows_sys::Win32::System::{
+    Diagnostics::Debug::{
+        MiniDumpWithProcessThreadData, MiniDumpWithThreadInfo, MiniDumpWithUnloadedModules,
+        MiniDumpWriteDump, EXCEPTION_POINTERS, MINIDUMP_EXCEPTION_INFORMATION,
+    },
+    Threading::{GetCurrentProcess, GetCurrentProcessId, GetCurrentThreadId},
+};
+
+use crate::error::last_error;
+
+/// Writes the stacks, registers and thread info of every thread, plus the loaded modules, to
+/// `path`. Heap memory isn't included, which keeps dumps small enough to upload. The calling
+/// thread appears in the dump writing it.
+pub fn write(path: &Path) -> Result<(), Error> {
+    write_dump(path, None)
+}
+
+/// Like [`write`], with the exception being handled on this thread recorded, so debuggers open
+/// the dump at the faulting instruction.
+///
+/// # Safety
+///
+/// `exception` must point to the exception pointers passed to the handler that's running, e.g.
+/// an unhandled exception filter.
+pub unsafe fn write_for_exception(
+    path: &Path,
+    exception: *const EXCEPTION_POINTERS,
+) -> Result<(), Error> {
+    let exception = MINIDUMP_EXCEPTION_INFORMATION {
+        ThreadId: GetCurrentThreadId(),
+        ExceptionPointers: exception.cast_mut(),
+        ClientPointers: 0,
+    };
+    write_dump(path, Some(&exception))
+}
+
+fn write_dump(
+    path: &Path,
+    exception: Option<&MINIDUMP_EXCEPTION_INFORMATION>,
+) -> Result<(), Error> {
+    let file = File::create(path)
+        .with_context(|| format!("failed to create minidump {}", path.display()))?;
+
+    let written = unsafe {
+        MiniDumpWriteDump(
+            GetCurrentProcess(),
+            GetCurrentProcessId(),
+            file.as_raw_handle() as isize,
+            MiniDumpWithThreadInfo | MiniDumpWithUnloadedModules | MiniDumpWithProcessThreadData,
+            exception.map_or(std::ptr::null(), |exception| exception),
+            std::ptr::null(),
+            std::ptr::null(),
+        )
+    };
+    if written == 0 {
+        let err = last_error(&format!("failed to write minidump {}", path.display()));
+        drop(file);
+        _ = std::fs::remove_file(path);
+        return Err(err);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,369 @@
+//! Display monitors: their bounds, work areas, DPI, refresh rates and display modes, and display
+//! mode changes for exclusive fullscreen.
+//!
+//! A video settings menu lists a monitor's modes and hands the chosen one to
+//! [`Window::set_exclusive_mode`](crate::window::Window::set_exclusive_mode):
+//!
+//! ```no_run
+//! use win32::window::{Fullscreen, Window};
+//!
+//! let window = Window::builder().title("Galleon").build()?;
+//! let monitor = window.current_monitor()?;
+//! for mode in monitor.modes() {
+//!     println!("{mode}");
+//! }
+//! window.set_exclusive_mode(monitor.modes().first().copied())?;
+//! window.set_fullscreen(Fullscreen::Exclusive)?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! note: mode changes are temporary. Windows puts the monitor back when the process exits, even
+//! if it crashes, and a failure reported through [`guard`](crate::guard) puts it back before its
+//! dialog is shown.
+
+use std::{fmt::Display, sync::Mutex};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::{
+    Devices::Display::{
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+        DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+        DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER,
+        DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO,
+        DISPLAYCONFIG_SOURCE_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+    },
+    Foundation::{BOOL, LPARAM, RECT},
+    Graphics::Gdi::{
+        ChangeDisplaySettingsExW, EnumDisplayMonitors, EnumDisplaySettingsW, GetMonitorInfoW,
+        CDS_FULLSCREEN, DEVMODEW, DISP_CHANGE_SUCCESSFUL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT,
+        DM_PELSWIDTH, ENUM_CURRENT_SETTINGS, HDC, HMONITOR, MONITORINFO, MONITORINFOEXW,
+    },
+    UI::{
+        HiDpi::{GetDpiForMonitor, MDT_EFFECTIVE_DPI},
+        WindowsAndMessaging::MONITORINFOF_PRIMARY,
+    },
+};
+
+use crate::{
+    error::{last_error, Hresult},
+    wide,
+    window::{PhysicalPosition, PhysicalRect, PhysicalSize, BASE_DPI},
+    wstr,
+};
+
+/// The monitors whose mode has been changed, by device name, so they can all be put back.
+static CHANGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// A resolution and refresh rate a monitor can be switched to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DisplayMode {
+    pub size: PhysicalSize,
+    /// In hertz.
+    pub refresh_rate: u32,
+}
+
+impl Display for DisplayMode {
+    /// E.g. `2560x1440 @ 144 Hz`, for a settings menu.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}x{} @ {} Hz",
+            self.size.width, self.size.height, self.refresh_rate
+        )
+    }
+}
+
+/// A snapshot of a monitor's settings, taken when it was enumerated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    handle: HMONITOR,
+    name: String,
+    bounds: PhysicalRect,
+    work_area: PhysicalRect,
+    dpi: u32,
+    refresh_rate: Option<u32>,
+    primary: bool,
+    hdr_supported: bool,
+    hdr_enabled: bool,
+}
+
+impl Monitor {
+    pub(crate) fn from_handle(handle: HMONITOR) -> Result<Self, Error> {
+        let mut info: MONITORINFOEXW = unsafe { std::mem::zeroed() };
+        info.monitorInfo.cbSize = std::mem::size_of::<MONITORINFOEXW>() as u32;
+        if unsafe { GetMonitorInfoW(handle, &mut info as *mut MONITORINFOEXW as *mut MONITORINFO) }
+            == 0
+        {
+            return Err(last_error("failed to get monitor info"));
+        }
+
+        let (mut dpi_x, mut dpi_y) = (0, 0);
+        Hresult(unsafe { GetDpiForMonitor(handle, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) })
+            .check("failed to get monitor dpi")?;
+
+        let mut mode: DEVMODEW = unsafe { std::mem::zeroed() };
+        mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+        // note: 0 and 1 both mean the hardware's default rate, which isn't reported.
+        let refresh_rate = (unsafe {
+            EnumDisplaySettingsW(info.szDevice.as_ptr(), ENUM_CURRENT_SETTINGS, &mut mode)
+        } != 0
+            && mode.dmDisplayFrequency > 1)
+            .then_some(mode.dmDisplayFrequency);
+
+        let name = wide::from_wide_lossy(&info.szDevice);
+        let (hdr_supported, hdr_enabled) = advanced_color(&name).unwrap_or_default();
+
+        Ok(Self {
+            handle,
+            name,
+            bounds: rect(&info.monitorInfo.rcMonitor),
+            work_area: rect(&info.monitorInfo.rcWork),
+            dpi: dpi_x,
+            refresh_rate,
+            primary: info.monitorInfo.dwFlags & MONITORINFOF_PRIMARY != 0,
+            hdr_supported,
+            hdr_enabled,
+        })
+    }
+
+    /// The primary monitor, which has its top-left corner at the screen origin.
+    pub fn primary() -> Result<Self, Error> {
+        enumerate()?
+            .into_iter()
+            .find(Monitor::is_primary)
+            .ok_or_else(|| Error::new("no primary monitor").with_kind(ErrorKind::NotFound))
+    }
+
+    pub fn handle(&self) -> HMONITOR {
+        self.handle
+    }
+
+    /// The GDI device name, e.g. `\\.\DISPLAY1`. It stays the same for a given output across
+    /// runs, so it can be saved to pick the same monitor again.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The monitor's area of the virtual screen.
+    pub fn bounds(&self) -> PhysicalRect {
+        self.bounds
+    }
+
+    /// The bounds less the taskbar and any docked toolbars.
+    pub fn work_area(&self) -> PhysicalRect {
+        self.work_area
+    }
+
+    pub fn dpi(&self) -> u32 {
+        self.dpi
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        f64::from(self.dpi) / f64::from(BASE_DPI)
+    }
+
+    /// The refresh rate in hertz, or `None` if the driver only reports a default rate.
+    pub fn refresh_rate(&self) -> Option<u32> {
+        self.refresh_rate
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.primary
+    }
+
+    /// Whether the monitor can show HDR, whether or not it's turned on.
+    pub fn is_hdr_supported(&self) -> bool {
+        self.hdr_supported
+    }
+
+    /// Whether HDR is turned on for the monitor in Windows' display settings.
+    pub fn is_hdr_enabled(&self) -> bool {
+        self.hdr_enabled
+    }
+
+    /// The modes the monitor can be switched to, largest and fastest first. Modes the driver only
+    /// offers at lower color depths are left out.
+    pub fn modes(&self) -> Vec<DisplayMode> {
+        let device = wstr!("{}", self.name);
+        let mut modes = Vec::new();
+        for index in 0.. {
+            let mut mode: DEVMODEW = unsafe { std::mem::zeroed() };
+            mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+            if unsafe { EnumDisplaySettingsW(device.as_ptr(), index, &mut mode) } == 0 {
+                break;
+            }
+            if mode.dmBitsPerPel != 32 || mode.dmDisplayFrequency <= 1 {
+                continue;
+            }
+
+            modes.push(DisplayMode {
+                size: PhysicalSize::new(mode.dmPelsWidth, mode.dmPelsHeight),
+                refresh_rate: mode.dmDisplayFrequency,
+            });
+        }
+
+        modes.sort_by(|a, b| {
+            let key = |mode: &DisplayMode| (mode.size.width, mode.size.height, mode.refresh_rate);
+            key(b).cmp(&key(a))
+        });
+        modes.dedup();
+        modes
+    }
+}
+
+/// Every monitor attached to the desktop, in the system's order.
+pub fn enumerate() -> Result<Vec<Monitor>, Error> {
+    unsafe extern "system" fn push(
+        handle: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        data: LPARAM,
+    ) -> BOOL {
+        (*(data as *mut Vec<HMONITOR>)).push(handle);
+        1
+    }
+
+    let mut handles = Vec::<HMONITOR>::new();
+    if unsafe {
+        EnumDisplayMonitors(
+            0,
+            std::ptr::null(),
+            Some(push),
+            &mut handles as *mut Vec<HMONITOR> as LPARAM,
+        )
+    } == 0
+    {
+        return Err(last_error("failed to enumerate monitors"));
+    }
+
+    handles.into_iter().map(Monitor::from_handle).collect()
+}
+
+fn rect(rect: &RECT) -> PhysicalRect {
+    PhysicalRect::new(
+        PhysicalPosition::new(rect.left, rect.top),
+        PhysicalSize::new(
+            (rect.right - rect.left).max(0) as u32,
+            (rect.bottom - rect.top).max(0) as u32,
+        ),
+    )
+}
+
+/// Whether the monitor named `device` supports HDR, and whether it's on.
+fn advanced_color(device: &str) -> Option<(bool, bool)> {
+    let (mut path_count, mut mode_count) = (0, 0);
+    let status = unsafe {
+        GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+    };
+    if status != 0 {
+        return None;
+    }
+    let mut paths: Vec<DISPLAYCONFIG_PATH_INFO> =
+        vec![unsafe { std::mem::zeroed() }; path_count as usize];
+    let mut modes: Vec<DISPLAYCONFIG_MODE_INFO> =
+        vec![unsafe { std::mem::zeroed() }; mode_count as usize];
+    let status = unsafe {
+        QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            std::ptr::null_mut(),
+        )
+    };
+    if status != 0 {
+        return None;
+    }
+
+    let path = paths[..path_count as usize].iter().find(|path| {
+        let mut source: DISPLAYCONFIG_SOURCE_DEVICE_NAME = unsafe { std::mem::zeroed() };
+        source.header = DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+            size: std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        };
+        let status = unsafe { DisplayConfigGetDeviceInfo(&mut source.header) };
+        status == 0 && wide::from_wide_lossy(&source.viewGdiDeviceName) == device
+    })?;
+
+    let mut color: DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO = unsafe { std::mem::zeroed() };
+    color.header = DISPLAYCONFIG_DEVICE_INFO_HEADER {
+        r#type: DISPLAYCONFIG_DEVICE_INFO_GET_ADVANCED_COLOR_INFO,
+        size: std::mem::size_of::<DISPLAYCONFIG_GET_ADVANCED_COLOR_INFO>() as u32,
+        adapterId: path.targetInfo.adapterId,
+        id: path.targetInfo.id,
+    };
+    if unsafe { DisplayConfigGetDeviceInfo(&mut color.header) } != 0 {
+        return None;
+    }
+
+    // Bit 0 is advancedColorSupported and bit 1 advancedColorEnabled.
+    let flags = unsafe { color.Anonymous.value };
+    Some((flags & 1 != 0, flags & 2 != 0))
+}
+
+/// Switches the monitor named `device` to `size`, at `refresh_rate` or if that's `None` at
+/// whatever rate the driver picks, until `restore_display_mode` is called or the process exits.
+pub(crate) fn set_display_mode(
+    device: &str,
+    size: PhysicalSize,
+    refresh_rate: Option<u32>,
+) -> Result<(), Error> {
+    let wide_device = wstr!("{device}");
+    let mut mode: DEVMODEW = unsafe { std::mem::zeroed() };
+    mode.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
+    mode.dmPelsWidth = size.width;
+    mode.dmPelsHeight = size.height;
+    mode.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT;
+    if let Some(refresh_rate) = refresh_rate {
+        mode.dmDisplayFrequency = refresh_rate;
+        mode.dmFields |= DM_DISPLAYFREQUENCY;
+    }
+
+    // note: CDS_FULLSCREEN makes the change temporary, so it isn't saved to the registry.
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            wide_device.as_ptr(),
+            &mode,
+            0,
+            CDS_FULLSCREEN,
+            std::ptr::null(),
+        )
+    };
+    if result != DISP_CHANGE_SUCCESSFUL {
+        return Err(Error::new(format!(
+            "failed to change display mode to {}x{}",
+            size.width, size.height
+        ))
+        .with_kind(ErrorKind::Platform)
+        .with_code(i64::from(result)));
+    }
+
+    let mut changed = CHANGED.lock().unwrap_or_else(|err| err.into_inner());
+    if !changed.iter().any(|changed| changed == device) {
+        changed.push(device.to_string());
+    }
+
+    Ok(())
+}
+
+/// Puts the monitor named `device` back in the mode saved in the registry.
+pub(crate) fn restore_display_mode(device: &str) {
+    CHANGED
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .retain(|changed| changed != device);
+    let device = wstr!("{device}");
+    unsafe { ChangeDisplaySettingsExW(device.as_ptr(), std::ptr::null(), 0, 0, std::ptr::null()) };
+}
+
+/// Puts back every monitor whose mode was changed, e.g. before a fatal error dialog that would
+/// otherwise be shown at the game's resolution.
+pub(crate) fn restore_display_modes() {
+    let changed = std::mem::take(&mut *CHANGED.lock().unwrap_or_else(|err| err.into_inner()));
+    for device in changed {
+        restore_display_mode(&device);
+    }
+}
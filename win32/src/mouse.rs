@@ -0,0 +1,129 @@
+//! Mouse events. Cursor-based events come from the usual window messages, so positions reflect
+//! pointer acceleration the same way the desktop cursor does. Raw input adds unaccelerated
+//! motion straight from the device, which is what camera controls want.
+
+use common::error::Error;
+use windows_sys::Win32::{
+    Devices::HumanInterfaceDevice::MOUSE_MOVE_ABSOLUTE,
+    Foundation::{HWND, LPARAM, WPARAM},
+    UI::{
+        Input::{
+            GetRawInputData, RegisterRawInputDevices, HRAWINPUT, RAWINPUT, RAWINPUTDEVICE,
+            RAWINPUTHEADER, RIDEV_REMOVE, RID_INPUT, RIM_TYPEMOUSE,
+        },
+        WindowsAndMessaging::{WHEEL_DELTA, XBUTTON1},
+    },
+};
+
+use crate::{error::last_error, keyboard::Modifiers, window::PhysicalPosition};
+
+const HID_USAGE_PAGE_GENERIC: u16 = 0x01;
+const HID_USAGE_GENERIC_MOUSE: u16 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// Usually "back".
+    X1,
+    /// Usually "forward".
+    X2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseButtonEvent {
+    pub button: MouseButton,
+    pub pressed: bool,
+    /// Where the cursor was, in client coordinates.
+    pub position: PhysicalPosition,
+    pub modifiers: Modifiers,
+}
+
+/// How far a wheel turned, in notches. High-resolution wheels and touchpads report fractions of
+/// a notch. Positive `y` is away from the user and positive `x` is to the right.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct WheelDelta {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Relative motion reported by the mouse itself, in device units ("mickeys"), with no pointer
+/// acceleration or screen edges applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MouseDelta {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// The client-area position packed into a mouse message's `lparam`. Coordinates can be negative
+/// while the mouse is captured and outside the window.
+pub(crate) fn position(lparam: LPARAM) -> PhysicalPosition {
+    PhysicalPosition::new(
+        i32::from((lparam & 0xffff) as u16 as i16),
+        i32::from(((lparam >> 16) & 0xffff) as u16 as i16),
+    )
+}
+
+/// Which X button a `WM_XBUTTONDOWN`/`WM_XBUTTONUP` message is for.
+pub(crate) fn x_button(wparam: WPARAM) -> MouseButton {
+    if ((wparam >> 16) & 0xffff) as u16 == XBUTTON1 {
+        MouseButton::X1
+    } else {
+        MouseButton::X2
+    }
+}
+
+/// The wheel rotation packed into a `WM_MOUSEWHEEL`/`WM_MOUSEHWHEEL` message's `wparam`.
+pub(crate) fn wheel_notches(wparam: WPARAM) -> f32 {
+    f32::from(((wparam >> 16) & 0xffff) as u16 as i16) / WHEEL_DELTA as f32
+}
+
+/// Sends raw mouse input to `hwnd`, or stops raw mouse input for the process when `enabled` is
+/// false. There's one registration per process, so the last window to enable it gets it.
+pub(crate) fn register_raw_mouse(hwnd: HWND, enabled: bool) -> Result<(), Error> {
+    let device = RAWINPUTDEVICE {
+        usUsagePage: HID_USAGE_PAGE_GENERIC,
+        usUsage: HID_USAGE_GENERIC_MOUSE,
+        dwFlags: if enabled { 0 } else { RIDEV_REMOVE },
+        hwndTarget: if enabled { hwnd } else { 0 },
+    };
+    if unsafe { RegisterRawInputDevices(&device, 1, std::mem::size_of::<RAWINPUTDEVICE>() as u32) }
+        == 0
+    {
+        return Err(last_error("failed to register for raw mouse input"));
+    }
+
+    Ok(())
+}
+
+/// Reads the relative motion from a `WM_INPUT` message, if it has any.
+pub(crate) fn raw_mouse_delta(lparam: LPARAM) -> Option<MouseDelta> {
+    let mut input: RAWINPUT = unsafe { std::mem::zeroed() };
+    let mut size = std::mem::size_of::<RAWINPUT>() as u32;
+    let read = unsafe {
+        GetRawInputData(
+            lparam as HRAWINPUT,
+            RID_INPUT,
+            &mut input as *mut RAWINPUT as *mut _,
+            &mut size,
+            std::mem::size_of::<RAWINPUTHEADER>() as u32,
+        )
+    };
+    if read == u32::MAX || input.header.dwType != RIM_TYPEMOUSE {
+        return None;
+    }
+
+    let mouse = unsafe { input.data.mouse };
+    // note: remote desktop and some tablets report absolute positions, which aren't deltas.
+    if u32::from(mouse.usFlags) & MOUSE_MOVE_ABSOLUTE != 0
+        || (mouse.lLastX == 0 && mouse.lLastY == 0)
+    {
+        return None;
+    }
+
+    Some(MouseDelta {
+        x: mouse.lLastX,
+        y: mouse.lLastY,
+    })
+}
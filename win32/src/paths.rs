@@ -0,0 +1,114 @@
+//! Where the game keeps its files: per-user data such as logs, settings and crash reports under
+//! `%LOCALAPPDATA%`, save games under the user's Saved Games folder, and scratch files under the
+//! temp directory. Each directory is created the first time it's asked for.
+//!
+//! ```no_run
+//! use win32::paths::AppPaths;
+//!
+//! let paths = AppPaths::new("Galleon");
+//! let settings = paths.config_dir()?.join("settings.toml");
+//! let save = paths.saved_games_dir()?.join("slot1.sav");
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::{
+    core::GUID,
+    Win32::{
+        System::Com::CoTaskMemFree,
+        UI::Shell::{
+            FOLDERID_LocalAppData, FOLDERID_SavedGames, SHGetKnownFolderPath, KF_FLAG_CREATE,
+        },
+    },
+};
+
+use crate::{error::Hresult, wide::WStr};
+
+/// The directory holding the executable, for assets shipped next to it.
+pub fn exe_dir() -> Result<PathBuf, Error> {
+    let exe = std::env::current_exe().map_err(|err| {
+        Error::new("failed to find the executable's path")
+            .with_kind(ErrorKind::Io)
+            .with_source(err)
+    })?;
+
+    exe.parent()
+        .map(Path::to_path_buf)
+        .ok_or_else(|| Error::new(format!("{} has no parent directory", exe.display())))
+}
+
+/// The path of a shell known folder, e.g. `FOLDERID_Documents`, created if it doesn't exist.
+pub fn known_folder(id: &GUID) -> Result<PathBuf, Error> {
+    let mut path = std::ptr::null_mut();
+    let hr = Hresult(unsafe { SHGetKnownFolderPath(id, KF_FLAG_CREATE as u32, 0, &mut path) });
+    // note: the buffer has to be freed even when the call fails.
+    let result = hr
+        .check("failed to find known folder")
+        .map(|()| PathBuf::from(unsafe { WStr::from_ptr(path) }.to_os_string()));
+    unsafe { CoTaskMemFree(path.cast()) };
+
+    result
+}
+
+/// The per-user directories for one application, each a folder named after it inside the
+/// matching system location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppPaths {
+    name: String,
+}
+
+impl AppPaths {
+    /// `name` becomes a directory name, so it shouldn't contain path separators.
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self { name: name.into() }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `%LOCALAPPDATA%\<name>`, for data that stays on this machine.
+    pub fn data_dir(&self) -> Result<PathBuf, Error> {
+        create(known_folder(&FOLDERID_LocalAppData)?.join(&self.name))
+    }
+
+    /// `data_dir\logs`, e.g. for a `FileSink`.
+    pub fn logs_dir(&self) -> Result<PathBuf, Error> {
+        create(self.data_dir()?.join("logs"))
+    }
+
+    /// `data_dir\config`, for settings and the logging config file.
+    pub fn config_dir(&self) -> Result<PathBuf, Error> {
+        create(self.data_dir()?.join("config"))
+    }
+
+    /// `data_dir\crashes`, for crash reports and hang dumps.
+    pub fn crashes_dir(&self) -> Result<PathBuf, Error> {
+        create(self.data_dir()?.join("crashes"))
+    }
+
+    /// `%USERPROFILE%\Saved Games\<name>`, where Windows expects games to keep their saves.
+    pub fn saved_games_dir(&self) -> Result<PathBuf, Error> {
+        create(known_folder(&FOLDERID_SavedGames)?.join(&self.name))
+    }
+
+    /// `%TEMP%\<name>`, for files that can be lost at any time.
+    pub fn temp_dir(&self) -> Result<PathBuf, Error> {
+        create(std::env::temp_dir().join(&self.name))
+    }
+}
+
+fn create(dir: PathBuf) -> Result<PathBuf, Error> {
+    fs::create_dir_all(&dir).map_err(|err| {
+        Error::new(format!("failed to create directory {}", dir.display()))
+            .with_kind(ErrorKind::Io)
+            .with_source(err)
+    })?;
+
+    Ok(dir)
+}
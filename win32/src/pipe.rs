@@ -0,0 +1,142 @@
+//! Inbound named pipes, read and written synchronously, as used by the log pipe and by
+//! single-instance forwarding.
+
+use std::io::{self, Read, Write};
+
+use common::error::Error;
+use windows_sys::Win32::{
+    Foundation::{
+        CloseHandle, GetLastError, ERROR_BROKEN_PIPE, ERROR_PIPE_CONNECTED, FALSE, GENERIC_WRITE,
+        HANDLE, INVALID_HANDLE_VALUE,
+    },
+    Storage::FileSystem::{
+        CreateFileW, ReadFile, WriteFile, FILE_FLAG_FIRST_PIPE_INSTANCE, OPEN_EXISTING,
+        PIPE_ACCESS_INBOUND,
+    },
+    System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+        PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    },
+};
+
+use crate::{error::last_error, wstr};
+
+/// An owned pipe handle, either end.
+pub(crate) struct Pipe(pub(crate) HANDLE);
+
+impl Pipe {
+    /// Creates an instance of the pipe `\\.\pipe\<name>` for a client to connect to. The `first`
+    /// instance fails if another process already owns the name.
+    pub(crate) fn create(name: &str, first: bool, buffer_size: u32) -> Result<Self, Error> {
+        let path = wstr!("{}", path(name));
+        let open_mode = if first {
+            PIPE_ACCESS_INBOUND | FILE_FLAG_FIRST_PIPE_INSTANCE
+        } else {
+            PIPE_ACCESS_INBOUND
+        };
+        let handle = unsafe {
+            CreateNamedPipeW(
+                path.as_ptr(),
+                open_mode,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                0,
+                buffer_size,
+                0,
+                std::ptr::null(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(last_error(&format!("failed to create pipe {name}")));
+        }
+
+        Ok(Self(handle))
+    }
+
+    /// Opens the client end of `\\.\pipe\<name>` for writing.
+    pub(crate) fn connect(name: &str) -> Result<Self, Error> {
+        let path = wstr!("{}", path(name));
+        let handle = unsafe {
+            CreateFileW(
+                path.as_ptr(),
+                GENERIC_WRITE,
+                0,
+                std::ptr::null(),
+                OPEN_EXISTING,
+                0,
+                0,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(last_error(&format!("failed to connect to pipe {name}")));
+        }
+
+        Ok(Self(handle))
+    }
+
+    /// Blocks until a client connects to this instance. Returns false if the connection failed.
+    pub(crate) fn accept(&self) -> bool {
+        let connected = unsafe { ConnectNamedPipe(self.0, std::ptr::null_mut()) } != FALSE;
+        connected || unsafe { GetLastError() } == ERROR_PIPE_CONNECTED
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unsafe { CloseHandle(self.0) };
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        let len = buf.len().min(u32::MAX as usize) as u32;
+        if unsafe {
+            ReadFile(
+                self.0,
+                buf.as_mut_ptr(),
+                len,
+                &mut read,
+                std::ptr::null_mut(),
+            )
+        } == FALSE
+        {
+            // A disconnected writer is the end of the stream.
+            return match unsafe { GetLastError() } {
+                ERROR_BROKEN_PIPE => Ok(0),
+                err => Err(io::Error::from_raw_os_error(err as i32)),
+            };
+        }
+
+        Ok(read as usize)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        let len = buf.len().min(u32::MAX as usize) as u32;
+        if unsafe {
+            WriteFile(
+                self.0,
+                buf.as_ptr(),
+                len,
+                &mut written,
+                std::ptr::null_mut(),
+            )
+        } == FALSE
+        {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn path(name: &str) -> String {
+    format!(r"\\.\pipe\{name}")
+}
@@ -0,0 +1,215 @@
+//! Child processes such as the asset cooker or a local dedicated server. Their output is logged
+//! line by line, and they're killed when the game exits, even if it crashes, so none are left
+//! running in the background. Exits come back as
+//! [`Event::Process`](crate::event_loop::Event::Process) once the [`Processes`] are handed to
+//! [`EventLoop::set_processes`](crate::event_loop::EventLoop::set_processes).
+//!
+//! ```no_run
+//! use std::process::Command;
+//!
+//! use win32::process::Processes;
+//!
+//! let processes = Processes::new()?;
+//! let cooker = processes.spawn(Command::new("galleon-cooker.exe").args(["--platform", "pc"]))?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read},
+    os::windows::{io::AsRawHandle, process::CommandExt},
+    path::Path,
+    process::{Child, Command, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use common::error::Error;
+use tracing::{info, warn};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE},
+    System::{
+        JobObjects::{
+            AssignProcessToJobObject, CreateJobObjectW, JobObjectExtendedLimitInformation,
+            SetInformationJobObject, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+        },
+        Threading::{GetCurrentThreadId, CREATE_NO_WINDOW},
+    },
+    UI::WindowsAndMessaging::{PostThreadMessageW, WM_NULL},
+};
+
+use crate::error::last_error;
+
+/// Identifies a child process in its [`ProcessEvent`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ProcessId(u32);
+
+impl ProcessId {
+    /// The system's id for the process, as shown in Task Manager.
+    pub fn pid(self) -> u32 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessEvent {
+    /// The process exited with the code, or `None` if it couldn't be waited for.
+    Exited(ProcessId, Option<i32>),
+}
+
+/// Spawns child processes into a job object, which kills them all when it's dropped or the game
+/// exits. Events are delivered to the thread it was created on, which has to pump messages for
+/// them to wake it.
+pub struct Processes {
+    job: HANDLE,
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    events: Mutex<VecDeque<ProcessEvent>>,
+    /// The thread to wake when a process exits.
+    thread: u32,
+}
+
+impl Processes {
+    pub fn new() -> Result<Self, Error> {
+        let job = unsafe { CreateJobObjectW(std::ptr::null(), std::ptr::null()) };
+        if job == 0 {
+            return Err(last_error("failed to create job object"));
+        }
+
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { std::mem::zeroed() };
+        limits.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+        if unsafe {
+            SetInformationJobObject(
+                job,
+                JobObjectExtendedLimitInformation,
+                (&limits as *const JOBOBJECT_EXTENDED_LIMIT_INFORMATION).cast(),
+                std::mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+            )
+        } == 0
+        {
+            let err = last_error("failed to set job object limits");
+            unsafe { CloseHandle(job) };
+            return Err(err);
+        }
+
+        Ok(Self {
+            job,
+            shared: Arc::new(Shared {
+                events: Mutex::new(VecDeque::new()),
+                thread: unsafe { GetCurrentThreadId() },
+            }),
+        })
+    }
+
+    /// Starts `command` without a console window, logging each line it writes to stdout as info
+    /// and to stderr as a warning, tagged with the program's name. Its stdin is closed.
+    ///
+    /// note: the child joins the job just after it starts, so anything it spawns in its first
+    /// instants may escape it.
+    pub fn spawn(&self, command: &mut Command) -> Result<ProcessId, Error> {
+        let program = Path::new(command.get_program())
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .creation_flags(CREATE_NO_WINDOW)
+            .spawn()
+            .map_err(|err| Error::new(format!("failed to start {program}")).with_source(err))?;
+
+        let id = ProcessId(child.id());
+        if unsafe { AssignProcessToJobObject(self.job, child.as_raw_handle() as HANDLE) } == 0 {
+            let err = last_error(&format!("failed to add {program} to the job object"));
+            _ = child.kill();
+            _ = child.wait();
+            return Err(err);
+        }
+
+        if let Some(stdout) = child.stdout.take() {
+            forward(&program, id, stdout, false);
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward(&program, id, stderr, true);
+        }
+        let shared = self.shared.clone();
+        let waiter = thread::Builder::new()
+            .name(format!("galleon-process-{}", id.0))
+            .spawn(move || wait(child, id, &shared));
+        if let Err(err) = waiter {
+            // note: the child is dropped with the closure, so it runs on untracked until the
+            // job is closed.
+            warn!(
+                error = &err as &dyn std::error::Error,
+                "failed to spawn a thread to wait for {program}"
+            );
+        }
+
+        Ok(id)
+    }
+
+    /// The oldest exit since the last call.
+    pub(crate) fn next_event(&self) -> Option<ProcessEvent> {
+        self.shared.events.lock().unwrap().pop_front()
+    }
+}
+
+impl Drop for Processes {
+    fn drop(&mut self) {
+        // note: this kills every child still running, which ends the threads waiting on them.
+        unsafe { CloseHandle(self.job) };
+    }
+}
+
+/// Logs `output` line by line on its own thread until the child closes it.
+fn forward<R: Read + Send + 'static>(program: &str, id: ProcessId, output: R, stderr: bool) {
+    let name = format!(
+        "galleon-process-{}-{}",
+        id.0,
+        if stderr { "stderr" } else { "stdout" }
+    );
+    let tag = program.to_string();
+    let reader = thread::Builder::new().name(name).spawn(move || {
+        for line in BufReader::new(output).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = line.trim_end();
+            if stderr {
+                warn!(process = tag, pid = id.0, "{line}");
+            } else {
+                info!(process = tag, pid = id.0, "{line}");
+            }
+        }
+    });
+    if let Err(err) = reader {
+        warn!(
+            error = &err as &dyn std::error::Error,
+            "failed to spawn a thread to log {program}'s output"
+        );
+    }
+}
+
+fn wait(mut child: Child, id: ProcessId, shared: &Shared) {
+    let code = match child.wait() {
+        Ok(status) => status.code(),
+        Err(err) => {
+            warn!(
+                error = &err as &dyn std::error::Error,
+                "failed to wait for process {}", id.0
+            );
+            None
+        }
+    };
+
+    shared
+        .events
+        .lock()
+        .unwrap()
+        .push_back(ProcessEvent::Exited(id, code));
+    unsafe { PostThreadMessageW(shared.thread, WM_NULL, 0, 0) };
+}
@@ -0,0 +1,168 @@
+//! Typed values under `HKEY_CURRENT_USER`, e.g. the preferred GPU adapter or whether the game
+//! runs at startup. Keys are paths relative to the hive, like `Software\Galleon`.
+//!
+//! ```no_run
+//! use win32::registry;
+//!
+//! const KEY: &str = r"Software\Galleon";
+//!
+//! let adapter = registry::get_u32(KEY, "Adapter")?.unwrap_or(0);
+//! registry::set_string(
+//!     r"Software\Microsoft\Windows\CurrentVersion\Run",
+//!     "Galleon",
+//!     r#""C:\Games\Galleon\galleon.exe" --minimized"#,
+//! )?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use common::error::Error;
+use windows_sys::Win32::{
+    Foundation::{ERROR_FILE_NOT_FOUND, ERROR_MORE_DATA},
+    System::Registry::{
+        RegDeleteKeyValueW, RegDeleteTreeW, RegGetValueW, RegSetKeyValueW, HKEY_CURRENT_USER,
+        REG_BINARY, REG_DWORD, REG_ROUTINE_FLAGS, REG_SZ, REG_VALUE_TYPE, RRF_RT_REG_BINARY,
+        RRF_RT_REG_DWORD, RRF_RT_REG_SZ,
+    },
+};
+
+use crate::{error::win32_error, wide, wstr};
+
+/// The string `value` of `key`, or `None` if either doesn't exist.
+pub fn get_string(key: &str, value: &str) -> Result<Option<String>, Error> {
+    let Some(data) = get(key, value, RRF_RT_REG_SZ)? else {
+        return Ok(None);
+    };
+
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    Ok(Some(wide::from_wide_lossy(&units)))
+}
+
+/// The DWORD `value` of `key`, or `None` if either doesn't exist.
+pub fn get_u32(key: &str, value: &str) -> Result<Option<u32>, Error> {
+    let Some(data) = get(key, value, RRF_RT_REG_DWORD)? else {
+        return Ok(None);
+    };
+
+    Ok(data.try_into().ok().map(u32::from_le_bytes))
+}
+
+/// The binary `value` of `key`, or `None` if either doesn't exist.
+pub fn get_binary(key: &str, value: &str) -> Result<Option<Vec<u8>>, Error> {
+    get(key, value, RRF_RT_REG_BINARY)
+}
+
+/// Sets `value` of `key` to a string, creating the key if needed.
+pub fn set_string(key: &str, value: &str, data: &str) -> Result<(), Error> {
+    let data = wstr!("{data}");
+    let bytes: Vec<u8> = data
+        .as_slice_with_nul()
+        .iter()
+        .flat_map(|unit| unit.to_le_bytes())
+        .collect();
+    set(key, value, REG_SZ, &bytes)
+}
+
+/// Sets `value` of `key` to a DWORD, creating the key if needed.
+pub fn set_u32(key: &str, value: &str, data: u32) -> Result<(), Error> {
+    set(key, value, REG_DWORD, &data.to_le_bytes())
+}
+
+/// Sets `value` of `key` to binary data, creating the key if needed.
+pub fn set_binary(key: &str, value: &str, data: &[u8]) -> Result<(), Error> {
+    set(key, value, REG_BINARY, data)
+}
+
+/// Deletes `value` of `key`. Succeeds if it's already gone.
+pub fn delete_value(key: &str, value: &str) -> Result<(), Error> {
+    let key_wide = wstr!("{key}");
+    let value_wide = wstr!("{value}");
+    let status =
+        unsafe { RegDeleteKeyValueW(HKEY_CURRENT_USER, key_wide.as_ptr(), value_wide.as_ptr()) };
+    match status {
+        0 | ERROR_FILE_NOT_FOUND => Ok(()),
+        status => Err(win32_error(
+            &format!(r"failed to delete registry value {key}\{value}"),
+            status,
+        )),
+    }
+}
+
+/// Deletes `key` with all its values and subkeys. Succeeds if it's already gone.
+pub fn delete_key(key: &str) -> Result<(), Error> {
+    let key_wide = wstr!("{key}");
+    let status = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, key_wide.as_ptr()) };
+    match status {
+        0 | ERROR_FILE_NOT_FOUND => Ok(()),
+        status => Err(win32_error(
+            &format!("failed to delete registry key {key}"),
+            status,
+        )),
+    }
+}
+
+fn get(key: &str, value: &str, flags: REG_ROUTINE_FLAGS) -> Result<Option<Vec<u8>>, Error> {
+    let key_wide = wstr!("{key}");
+    let value_wide = wstr!("{value}");
+    let mut data: Vec<u8> = Vec::new();
+    loop {
+        let mut size = data.len() as u32;
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_CURRENT_USER,
+                key_wide.as_ptr(),
+                value_wide.as_ptr(),
+                flags,
+                std::ptr::null_mut(),
+                if data.is_empty() {
+                    std::ptr::null_mut()
+                } else {
+                    data.as_mut_ptr().cast()
+                },
+                &mut size,
+            )
+        };
+
+        match status {
+            // note: without a buffer, success only means the size was written.
+            0 if !data.is_empty() || size == 0 => {
+                data.truncate(size as usize);
+                return Ok(Some(data));
+            }
+            // The value may have grown between the calls.
+            0 | ERROR_MORE_DATA => data.resize(size as usize, 0),
+            ERROR_FILE_NOT_FOUND => return Ok(None),
+            status => {
+                return Err(win32_error(
+                    &format!(r"failed to read registry value {key}\{value}"),
+                    status,
+                ))
+            }
+        }
+    }
+}
+
+fn set(key: &str, value: &str, kind: REG_VALUE_TYPE, data: &[u8]) -> Result<(), Error> {
+    let key_wide = wstr!("{key}");
+    let value_wide = wstr!("{value}");
+    let status = unsafe {
+        RegSetKeyValueW(
+            HKEY_CURRENT_USER,
+            key_wide.as_ptr(),
+            value_wide.as_ptr(),
+            kind,
+            data.as_ptr().cast(),
+            data.len() as u32,
+        )
+    };
+    if status != 0 {
+        return Err(win32_error(
+            &format!(r"failed to write registry value {key}\{value}"),
+            status,
+        ));
+    }
+
+    Ok(())
+}
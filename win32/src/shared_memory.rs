@@ -0,0 +1,313 @@
+//! Memory shared with other processes on the machine, and a single-producer single-consumer ring
+//! buffer laid out in it, so an external profiler or inspector can read what a running game
+//! writes without either side waiting on the other.
+//!
+//! ```no_run
+//! use win32::shared_memory::{RingReader, RingWriter};
+//!
+//! // In the game:
+//! let writer = RingWriter::create("galleon-frame-stats", 64 * 1024)?;
+//! writer.push(b"frame 1");
+//!
+//! // In the tool:
+//! let mut reader = RingReader::open("galleon-frame-stats")?;
+//! let mut record = Vec::new();
+//! while reader.pop(&mut record) {
+//!     println!("{}", String::from_utf8_lossy(&record));
+//! }
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! note: the memory is named in the session's `Local\` namespace, so only processes in the same
+//! login session can open it.
+
+use std::{
+    ffi::c_void,
+    ptr::NonNull,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, HANDLE, INVALID_HANDLE_VALUE},
+    System::Memory::{
+        CreateFileMappingW, MapViewOfFile, OpenFileMappingW, UnmapViewOfFile, VirtualQuery,
+        FILE_MAP_READ, FILE_MAP_WRITE, MEMORY_BASIC_INFORMATION, MEMORY_MAPPED_VIEW_ADDRESS,
+        PAGE_READWRITE,
+    },
+};
+
+use crate::{error::last_error, wstr};
+
+/// `GRNG`, marking memory laid out as a ring buffer.
+const RING_MAGIC: u32 = u32::from_le_bytes(*b"GRNG");
+const RING_VERSION: u32 = 1;
+/// Each record is its length as a little-endian `u32` followed by its bytes.
+const LENGTH_SIZE: u64 = 4;
+
+/// A named region of memory mapped into this process, unmapped when dropped. The region itself
+/// lives until every process has closed it.
+pub struct SharedMemory {
+    handle: HANDLE,
+    view: NonNull<u8>,
+    len: usize,
+}
+
+// note: the view is plain memory; what's in it is up to the caller to synchronize.
+unsafe impl Send for SharedMemory {}
+unsafe impl Sync for SharedMemory {}
+
+impl SharedMemory {
+    /// Creates the region `name` of `len` zeroed bytes. Fails if it already exists, e.g. because
+    /// another instance of the game created it.
+    pub fn create(name: &str, len: usize) -> Result<Self, Error> {
+        let path = wstr!(r"Local\{name}");
+        let size = len as u64;
+        let handle = unsafe {
+            CreateFileMappingW(
+                INVALID_HANDLE_VALUE,
+                std::ptr::null(),
+                PAGE_READWRITE,
+                (size >> 32) as u32,
+                size as u32,
+                path.as_ptr(),
+            )
+        };
+        if handle == 0 {
+            return Err(last_error(&format!(
+                "failed to create shared memory {name}"
+            )));
+        }
+        if unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            unsafe { CloseHandle(handle) };
+            return Err(Error::new(format!("shared memory {name} already exists")));
+        }
+
+        Self::map(handle, name)
+    }
+
+    /// Opens the region `name` that another process created.
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let path = wstr!(r"Local\{name}");
+        let handle = unsafe { OpenFileMappingW(FILE_MAP_READ | FILE_MAP_WRITE, 0, path.as_ptr()) };
+        if handle == 0 {
+            return Err(last_error(&format!("failed to open shared memory {name}"))
+                .with_kind(ErrorKind::NotFound));
+        }
+
+        Self::map(handle, name)
+    }
+
+    fn map(handle: HANDLE, name: &str) -> Result<Self, Error> {
+        let view = unsafe { MapViewOfFile(handle, FILE_MAP_READ | FILE_MAP_WRITE, 0, 0, 0) };
+        let Some(view) = NonNull::new(view.Value.cast::<u8>()) else {
+            let err = last_error(&format!("failed to map shared memory {name}"));
+            unsafe { CloseHandle(handle) };
+            return Err(err);
+        };
+
+        // note: the view is rounded up to whole pages, which are all usable.
+        let mut info: MEMORY_BASIC_INFORMATION = unsafe { std::mem::zeroed() };
+        unsafe {
+            VirtualQuery(
+                view.as_ptr() as *const c_void,
+                &mut info,
+                std::mem::size_of::<MEMORY_BASIC_INFORMATION>(),
+            )
+        };
+
+        Ok(Self {
+            handle,
+            view,
+            len: info.RegionSize,
+        })
+    }
+
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.view.as_ptr()
+    }
+
+    /// The size of the mapping, which may be larger than asked for.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        let view = MEMORY_MAPPED_VIEW_ADDRESS {
+            Value: self.view.as_ptr().cast(),
+        };
+        unsafe { UnmapViewOfFile(view) };
+        unsafe { CloseHandle(self.handle) };
+    }
+}
+
+/// The start of a ring buffer's memory. The indices count bytes ever written and read, and sit
+/// on their own cache lines so the two sides don't contend.
+#[repr(C)]
+struct RingHeader {
+    magic: u32,
+    version: u32,
+    /// The size of the data area that follows the header, a power of two.
+    capacity: u64,
+    write: CacheLine,
+    read: CacheLine,
+    /// Records the writer dropped because the reader had fallen behind.
+    dropped: CacheLine,
+}
+
+#[repr(C, align(64))]
+struct CacheLine(AtomicU64);
+
+/// Writes records into a ring buffer for a [`RingReader`] in another process. Pushing never
+/// blocks: records that don't fit are dropped.
+pub struct RingWriter {
+    ring: Ring,
+}
+
+impl RingWriter {
+    /// Creates the shared memory `name` with room for `capacity` bytes of records, rounded up to
+    /// a power of two.
+    pub fn create(name: &str, capacity: usize) -> Result<Self, Error> {
+        let capacity = capacity.max(64).next_power_of_two();
+        let memory = SharedMemory::create(name, std::mem::size_of::<RingHeader>() + capacity)?;
+
+        let header = memory.as_ptr().cast::<RingHeader>();
+        unsafe {
+            (*header).capacity = capacity as u64;
+            (*header).version = RING_VERSION;
+            // note: written last so a reader never sees the magic before the rest.
+            std::ptr::write_volatile(&mut (*header).magic, RING_MAGIC);
+        }
+
+        Ok(Self {
+            ring: Ring { memory },
+        })
+    }
+
+    /// Appends `record`, or returns false if the reader hasn't made room for it.
+    pub fn push(&self, record: &[u8]) -> bool {
+        let header = self.ring.header();
+        let needed = LENGTH_SIZE + record.len() as u64;
+        let write = header.write.0.load(Ordering::Relaxed);
+        let read = header.read.0.load(Ordering::Acquire);
+        if needed > header.capacity - (write - read) {
+            header.dropped.0.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        self.ring
+            .copy_in(write, &(record.len() as u32).to_le_bytes());
+        self.ring.copy_in(write + LENGTH_SIZE, record);
+        header.write.0.store(write + needed, Ordering::Release);
+        true
+    }
+
+    /// How many records have been dropped since the ring was created.
+    pub fn dropped(&self) -> u64 {
+        self.ring.header().dropped.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Reads the records a [`RingWriter`] in another process pushes. Only one reader may be open
+/// at a time.
+pub struct RingReader {
+    ring: Ring,
+}
+
+impl RingReader {
+    pub fn open(name: &str) -> Result<Self, Error> {
+        let memory = SharedMemory::open(name)?;
+        let ring = Ring { memory };
+        let header = ring.header();
+        let magic = unsafe { std::ptr::read_volatile(&header.magic) };
+        if magic != RING_MAGIC || header.version != RING_VERSION {
+            return Err(Error::new(format!(
+                "shared memory {name} isn't a version {RING_VERSION} ring buffer"
+            ))
+            .with_kind(ErrorKind::Parse));
+        }
+        let len = ring.memory.len() - std::mem::size_of::<RingHeader>();
+        if !header.capacity.is_power_of_two() || header.capacity > len as u64 {
+            return Err(
+                Error::new(format!("shared memory {name} has a corrupt ring header"))
+                    .with_kind(ErrorKind::Parse),
+            );
+        }
+
+        Ok(Self { ring })
+    }
+
+    /// Moves the oldest unread record into `record`, or returns false if there isn't one.
+    pub fn pop(&mut self, record: &mut Vec<u8>) -> bool {
+        let header = self.ring.header();
+        let read = header.read.0.load(Ordering::Relaxed);
+        let write = header.write.0.load(Ordering::Acquire);
+        if read == write {
+            return false;
+        }
+
+        let mut len = [0; LENGTH_SIZE as usize];
+        self.ring.copy_out(read, &mut len);
+        // note: clamped so a misbehaving writer can't make the reader run off the end.
+        let len = u64::from(u32::from_le_bytes(len)).min(write - read - LENGTH_SIZE);
+        record.resize(len as usize, 0);
+        self.ring.copy_out(read + LENGTH_SIZE, record);
+        header
+            .read
+            .0
+            .store(read + LENGTH_SIZE + len, Ordering::Release);
+        true
+    }
+
+    /// How many records the writer has dropped since the ring was created.
+    pub fn dropped(&self) -> u64 {
+        self.ring.header().dropped.0.load(Ordering::Relaxed)
+    }
+}
+
+struct Ring {
+    memory: SharedMemory,
+}
+
+impl Ring {
+    fn header(&self) -> &RingHeader {
+        unsafe { &*self.memory.as_ptr().cast::<RingHeader>() }
+    }
+
+    fn data(&self) -> *mut u8 {
+        unsafe { self.memory.as_ptr().add(std::mem::size_of::<RingHeader>()) }
+    }
+
+    /// Copies `bytes` to the data area at stream position `position`, wrapping at the end.
+    fn copy_in(&self, position: u64, bytes: &[u8]) {
+        let capacity = self.header().capacity;
+        let start = (position & (capacity - 1)) as usize;
+        let first = bytes.len().min(capacity as usize - start);
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), self.data().add(start), first);
+            std::ptr::copy_nonoverlapping(
+                bytes[first..].as_ptr(),
+                self.data(),
+                bytes.len() - first,
+            );
+        }
+    }
+
+    /// Copies from the data area at stream position `position` into `bytes`, wrapping at the end.
+    fn copy_out(&self, position: u64, bytes: &mut [u8]) {
+        let capacity = self.header().capacity;
+        let start = (position & (capacity - 1)) as usize;
+        let first = bytes.len().min(capacity as usize - start);
+        let len = bytes.len();
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.data().add(start), bytes.as_mut_ptr(), first);
+            std::ptr::copy_nonoverlapping(self.data(), bytes[first..].as_mut_ptr(), len - first);
+        }
+    }
+}
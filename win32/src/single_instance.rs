@@ -0,0 +1,285 @@
+//! Keeping to one running copy of the game per user session. The first instance holds a named
+//! mutex and listens on a named pipe; a later instance finds the mutex taken, sends its command
+//! line down the pipe, lets the first instance take the foreground, and exits.
+//!
+//! ```no_run
+//! use win32::single_instance::SingleInstance;
+//!
+//! let Some(instance) = SingleInstance::acquire("Galleon")? else {
+//!     // Another instance is running and has been handed our arguments.
+//!     return Ok(());
+//! };
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicIsize, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use common::error::Error;
+use tracing::warn;
+use windows_sys::Win32::{
+    Foundation::{
+        CloseHandle, GetLastError, ERROR_ALREADY_EXISTS, ERROR_FILE_NOT_FOUND, ERROR_PIPE_BUSY,
+        FALSE, HANDLE, HWND,
+    },
+    System::{
+        Pipes::{GetNamedPipeServerProcessId, WaitNamedPipeW},
+        RemoteDesktop::ProcessIdToSessionId,
+        Threading::{CreateMutexW, GetCurrentProcessId},
+    },
+    UI::WindowsAndMessaging::{
+        AllowSetForegroundWindow, IsIconic, PostMessageW, SetForegroundWindow, ShowWindow,
+        SW_RESTORE, WM_NULL,
+    },
+};
+
+use crate::{
+    args,
+    error::last_error,
+    pipe::{self, Pipe},
+    window::Window,
+    wstr,
+};
+
+/// How long a second instance keeps trying to reach the first, which may still be starting up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most bytes of command line accepted from one instance.
+const MAX_MESSAGE_SIZE: u64 = 64 * 1024;
+
+/// Proof that this is the only instance. Command lines forwarded by later instances queue up
+/// until [`take_forwarded`](SingleInstance::take_forwarded); the event loop delivers them as
+/// [`Event::SecondInstance`](crate::event_loop::Event::SecondInstance) when given the guard with
+/// [`EventLoop::set_single_instance`](crate::event_loop::EventLoop::set_single_instance).
+///
+/// Dropping it releases the name, so keep it alive until the game exits.
+pub struct SingleInstance {
+    mutex: HANDLE,
+    pipe_name: String,
+    shared: Arc<Shared>,
+    listener: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    closed: AtomicBool,
+    hwnd: AtomicIsize,
+    forwarded: Mutex<Vec<Vec<String>>>,
+}
+
+impl SingleInstance {
+    /// Claims `name` for this process. If another instance already has it, forwards this
+    /// process's arguments, without the program name, to that instance and returns `None`; the
+    /// caller should then exit.
+    ///
+    /// `name` identifies the application, e.g. its name, and is scoped to the user's session.
+    pub fn acquire(name: &str) -> Result<Option<Self>, Error> {
+        let mutex_name = wstr!("Local\\{name}");
+        let mutex = unsafe { CreateMutexW(std::ptr::null(), FALSE, mutex_name.as_ptr()) };
+        if mutex == 0 {
+            return Err(last_error(&format!("failed to create mutex {name}")));
+        }
+        // note: the handle is valid either way; the mutex is only a marker and is never waited on.
+        let already_running = unsafe { GetLastError() } == ERROR_ALREADY_EXISTS;
+
+        // Pipe names are machine-wide, unlike `Local\`, so the session keeps other users apart.
+        let pipe_name = format!("{name}-instance-{}", session_id());
+        if already_running {
+            unsafe { CloseHandle(mutex) };
+            let args = args::args();
+            forward(&pipe_name, args.get(1..).unwrap_or_default())?;
+            return Ok(None);
+        }
+
+        let first = match Pipe::create(&pipe_name, true, MAX_MESSAGE_SIZE as u32) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                unsafe { CloseHandle(mutex) };
+                return Err(err);
+            }
+        };
+        let shared = Arc::new(Shared {
+            closed: AtomicBool::new(false),
+            hwnd: AtomicIsize::new(0),
+            forwarded: Mutex::new(Vec::new()),
+        });
+
+        let listener = {
+            let pipe_name = pipe_name.clone();
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("galleon-single-instance".to_string())
+                .spawn(move || listen(&pipe_name, first, &shared))
+        };
+        let listener = match listener {
+            Ok(listener) => listener,
+            Err(err) => {
+                unsafe { CloseHandle(mutex) };
+                return Err(Error::new("failed to spawn single instance thread").with_source(err));
+            }
+        };
+
+        Ok(Some(Self {
+            mutex,
+            pipe_name,
+            shared,
+            listener: Some(listener),
+        }))
+    }
+
+    /// The window restored and brought to the foreground when another instance starts.
+    pub fn set_window(&self, window: &Window) {
+        self.shared.hwnd.store(window.hwnd(), Ordering::Release);
+    }
+
+    /// The command lines forwarded since the last call, oldest first.
+    pub fn take_forwarded(&self) -> Vec<Vec<String>> {
+        std::mem::take(&mut *self.shared.forwarded.lock().unwrap())
+    }
+}
+
+impl Drop for SingleInstance {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+
+        // The listener is blocked waiting for a client, so connect to wake it up.
+        _ = Pipe::connect(&self.pipe_name);
+
+        if let Some(listener) = self.listener.take() {
+            _ = listener.join();
+        }
+        unsafe { CloseHandle(self.mutex) };
+    }
+}
+
+fn session_id() -> u32 {
+    let mut session = 0;
+    unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) };
+    session
+}
+
+/// Sends `args` to the instance listening on `pipe_name` and lets it take the foreground.
+fn forward(pipe_name: &str, args: &[String]) -> Result<(), Error> {
+    let mut pipe = connect(pipe_name)?;
+
+    // Only the process the user just started may take the foreground, so pass the right on.
+    let mut pid = 0;
+    if unsafe { GetNamedPipeServerProcessId(pipe.0, &mut pid) } != FALSE {
+        unsafe { AllowSetForegroundWindow(pid) };
+    }
+
+    pipe.write_all(&encode(args)).map_err(|err| {
+        Error::new("failed to forward the command line to the running instance").with_source(err)
+    })
+}
+
+/// Connects to the running instance, waiting for it to create the pipe or free an instance of it.
+fn connect(pipe_name: &str) -> Result<Pipe, Error> {
+    let path = wstr!("{}", pipe::path(pipe_name));
+    let deadline = Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        let err = match Pipe::connect(pipe_name) {
+            Ok(pipe) => return Ok(pipe),
+            Err(err) => err,
+        };
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(err);
+        }
+
+        match err.code().map(|code| code as u32) {
+            Some(ERROR_PIPE_BUSY) => {
+                unsafe { WaitNamedPipeW(path.as_ptr(), remaining.as_millis() as u32) };
+            }
+            // The first instance holds the mutex but hasn't created the pipe yet.
+            Some(ERROR_FILE_NOT_FOUND) => thread::sleep(Duration::from_millis(50)),
+            _ => return Err(err),
+        }
+    }
+}
+
+/// Accepts one instance at a time, queueing its command line and raising the window.
+fn listen(pipe_name: &str, mut pipe: Pipe, shared: &Shared) {
+    loop {
+        let connected = pipe.accept();
+        if shared.closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        if connected {
+            let mut message = Vec::new();
+            match (&mut pipe).take(MAX_MESSAGE_SIZE).read_to_end(&mut message) {
+                Ok(_) => match decode(&message) {
+                    Some(args) => {
+                        shared.forwarded.lock().unwrap().push(args);
+                        raise(shared.hwnd.load(Ordering::Acquire));
+                    }
+                    None => warn!("ignored a malformed command line from another instance"),
+                },
+                Err(err) => warn!("failed to read command line from another instance: {err}"),
+            }
+        }
+
+        pipe = match Pipe::create(pipe_name, false, MAX_MESSAGE_SIZE as u32) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!("{err}");
+                return;
+            }
+        };
+    }
+}
+
+/// Restores and focuses the window, and wakes the event loop so it picks up the forwarded
+/// command line even while it's waiting for messages.
+fn raise(hwnd: HWND) {
+    if hwnd == 0 {
+        return;
+    }
+
+    if unsafe { IsIconic(hwnd) } != 0 {
+        unsafe { ShowWindow(hwnd, SW_RESTORE) };
+    }
+    unsafe { SetForegroundWindow(hwnd) };
+    unsafe { PostMessageW(hwnd, WM_NULL, 0, 0) };
+}
+
+/// The argument count, then each argument as a length and its UTF-8 bytes, all lengths `u32`
+/// little-endian.
+fn encode(args: &[String]) -> Vec<u8> {
+    let mut message = Vec::new();
+    message.extend_from_slice(&(args.len() as u32).to_le_bytes());
+    for arg in args {
+        message.extend_from_slice(&(arg.len() as u32).to_le_bytes());
+        message.extend_from_slice(arg.as_bytes());
+    }
+    message
+}
+
+fn decode(mut message: &[u8]) -> Option<Vec<String>> {
+    let count = read_u32(&mut message)?;
+    let mut args = Vec::with_capacity(count.min(256));
+    for _ in 0..count {
+        let len = read_u32(&mut message)?;
+        if len > message.len() {
+            return None;
+        }
+        let (arg, rest) = message.split_at(len);
+        args.push(String::from_utf8(arg.to_vec()).ok()?);
+        message = rest;
+    }
+
+    message.is_empty().then_some(args)
+}
+
+fn read_u32(message: &mut &[u8]) -> Option<usize> {
+    let (value, rest) = message.split_first_chunk::<4>()?;
+    *message = rest;
+    Some(u32::from_le_bytes(*value) as usize)
+}
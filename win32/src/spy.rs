@@ -0,0 +1,287 @@
+//! Logs every message a window receives at TRACE, with the parameters of the ones that matter
+//! for focus, sizing and DPI decoded, to see what an unusual setup actually sends. Turned on with
+//! [`Window::set_spy_messages`](crate::window::Window::set_spy_messages).
+
+use std::time::{Duration, Instant};
+
+use tracing::trace;
+use windows_sys::Win32::{
+    Foundation::{HWND, LPARAM, RECT, WPARAM},
+    UI::{
+        Controls::WM_MOUSELEAVE,
+        WindowsAndMessaging::{
+            MINMAXINFO, SIZE_MAXHIDE, SIZE_MAXIMIZED, SIZE_MAXSHOW, SIZE_MINIMIZED, SIZE_RESTORED,
+            WA_ACTIVE, WA_CLICKACTIVE, WA_INACTIVE, WINDOWPOS, WM_ACTIVATE, WM_ACTIVATEAPP, WM_APP,
+            WM_CANCELMODE, WM_CAPTURECHANGED, WM_CHAR, WM_CLOSE, WM_CONTEXTMENU, WM_CREATE,
+            WM_DEADCHAR, WM_DESTROY, WM_DEVICECHANGE, WM_DISPLAYCHANGE, WM_DPICHANGED,
+            WM_DROPFILES, WM_ENABLE, WM_ENDSESSION, WM_ENTERMENULOOP, WM_ENTERSIZEMOVE,
+            WM_ERASEBKGND, WM_EXITMENULOOP, WM_EXITSIZEMOVE, WM_GETDPISCALEDSIZE, WM_GETICON,
+            WM_GETMINMAXINFO, WM_GETOBJECT, WM_GETTEXT, WM_GETTEXTLENGTH, WM_IME_COMPOSITION,
+            WM_IME_ENDCOMPOSITION, WM_IME_NOTIFY, WM_IME_SETCONTEXT, WM_IME_STARTCOMPOSITION,
+            WM_INPUT, WM_INPUTLANGCHANGE, WM_INPUT_DEVICE_CHANGE, WM_KEYDOWN, WM_KEYUP,
+            WM_KILLFOCUS, WM_LBUTTONDBLCLK, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN,
+            WM_MBUTTONUP, WM_MOUSEACTIVATE, WM_MOUSEHWHEEL, WM_MOUSEMOVE, WM_MOUSEWHEEL, WM_MOVE,
+            WM_MOVING, WM_NCACTIVATE, WM_NCCALCSIZE, WM_NCCREATE, WM_NCDESTROY, WM_NCHITTEST,
+            WM_NCLBUTTONDOWN, WM_NCMOUSELEAVE, WM_NCMOUSEMOVE, WM_NCPAINT, WM_NULL, WM_PAINT,
+            WM_POWERBROADCAST, WM_QUERYENDSESSION, WM_QUIT, WM_RBUTTONDOWN, WM_RBUTTONUP,
+            WM_SETCURSOR, WM_SETFOCUS, WM_SETICON, WM_SETTEXT, WM_SETTINGCHANGE, WM_SHOWWINDOW,
+            WM_SIZE, WM_SIZING, WM_STYLECHANGED, WM_STYLECHANGING, WM_SYNCPAINT, WM_SYSCHAR,
+            WM_SYSCOMMAND, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_THEMECHANGED, WM_TIMER, WM_USER,
+            WM_WINDOWPOSCHANGED, WM_WINDOWPOSCHANGING, WM_XBUTTONDOWN, WM_XBUTTONUP,
+        },
+    },
+};
+
+use crate::wide::WStr;
+
+/// The most messages logged per window per second. Mouse moves alone can exceed it.
+const MAX_PER_SECOND: u32 = 500;
+
+/// Rate-limits one window's message log.
+pub(crate) struct MessageSpy {
+    second: Instant,
+    logged: u32,
+    suppressed: u32,
+}
+
+impl MessageSpy {
+    pub(crate) fn new() -> Self {
+        Self {
+            second: Instant::now(),
+            logged: 0,
+            suppressed: 0,
+        }
+    }
+
+    /// Logs a message as the window procedure receives it, unless the window is over its limit
+    /// for this second.
+    pub(crate) fn log(&mut self, hwnd: HWND, msg: u32, wparam: WPARAM, lparam: LPARAM) {
+        let now = Instant::now();
+        if now.duration_since(self.second) >= Duration::from_secs(1) {
+            if self.suppressed > 0 {
+                trace!(hwnd, "{} more messages not logged", self.suppressed);
+            }
+            self.second = now;
+            self.logged = 0;
+            self.suppressed = 0;
+        }
+        if self.logged == MAX_PER_SECOND {
+            self.suppressed += 1;
+            return;
+        }
+        self.logged += 1;
+
+        let name = name(msg);
+        match unsafe { decode(msg, wparam, lparam) } {
+            Some(params) => trace!(hwnd, "{name} {params}"),
+            None => trace!(hwnd, "{name} wparam={wparam:#x} lparam={lparam:#x}"),
+        }
+    }
+}
+
+fn name(msg: u32) -> String {
+    macro_rules! names {
+        ($($name:ident),* $(,)?) => {
+            match msg {
+                $($name => return stringify!($name).to_string(),)*
+                _ => {}
+            }
+        };
+    }
+
+    names!(
+        WM_ACTIVATE,
+        WM_ACTIVATEAPP,
+        WM_CANCELMODE,
+        WM_CAPTURECHANGED,
+        WM_CHAR,
+        WM_CLOSE,
+        WM_CONTEXTMENU,
+        WM_CREATE,
+        WM_DEADCHAR,
+        WM_DESTROY,
+        WM_DEVICECHANGE,
+        WM_DISPLAYCHANGE,
+        WM_DPICHANGED,
+        WM_DROPFILES,
+        WM_ENABLE,
+        WM_ENDSESSION,
+        WM_ENTERMENULOOP,
+        WM_ENTERSIZEMOVE,
+        WM_ERASEBKGND,
+        WM_EXITMENULOOP,
+        WM_EXITSIZEMOVE,
+        WM_GETDPISCALEDSIZE,
+        WM_GETICON,
+        WM_GETMINMAXINFO,
+        WM_GETOBJECT,
+        WM_GETTEXT,
+        WM_GETTEXTLENGTH,
+        WM_IME_COMPOSITION,
+        WM_IME_ENDCOMPOSITION,
+        WM_IME_NOTIFY,
+        WM_IME_SETCONTEXT,
+        WM_IME_STARTCOMPOSITION,
+        WM_INPUT,
+        WM_INPUTLANGCHANGE,
+        WM_INPUT_DEVICE_CHANGE,
+        WM_KEYDOWN,
+        WM_KEYUP,
+        WM_KILLFOCUS,
+        WM_LBUTTONDBLCLK,
+        WM_LBUTTONDOWN,
+        WM_LBUTTONUP,
+        WM_MBUTTONDOWN,
+        WM_MBUTTONUP,
+        WM_MOUSEACTIVATE,
+        WM_MOUSEHWHEEL,
+        WM_MOUSELEAVE,
+        WM_MOUSEMOVE,
+        WM_MOUSEWHEEL,
+        WM_MOVE,
+        WM_MOVING,
+        WM_NCACTIVATE,
+        WM_NCCALCSIZE,
+        WM_NCCREATE,
+        WM_NCDESTROY,
+        WM_NCHITTEST,
+        WM_NCLBUTTONDOWN,
+        WM_NCMOUSELEAVE,
+        WM_NCMOUSEMOVE,
+        WM_NCPAINT,
+        WM_NULL,
+        WM_PAINT,
+        WM_POWERBROADCAST,
+        WM_QUERYENDSESSION,
+        WM_QUIT,
+        WM_RBUTTONDOWN,
+        WM_RBUTTONUP,
+        WM_SETCURSOR,
+        WM_SETFOCUS,
+        WM_SETICON,
+        WM_SETTEXT,
+        WM_SETTINGCHANGE,
+        WM_SHOWWINDOW,
+        WM_SIZE,
+        WM_SIZING,
+        WM_STYLECHANGED,
+        WM_STYLECHANGING,
+        WM_SYNCPAINT,
+        WM_SYSCHAR,
+        WM_SYSCOMMAND,
+        WM_SYSKEYDOWN,
+        WM_SYSKEYUP,
+        WM_THEMECHANGED,
+        WM_TIMER,
+        WM_WINDOWPOSCHANGED,
+        WM_WINDOWPOSCHANGING,
+        WM_XBUTTONDOWN,
+        WM_XBUTTONUP,
+    );
+
+    match msg {
+        WM_USER..=0x7fff => format!("WM_USER+{}", msg - WM_USER),
+        WM_APP..=0xbfff => format!("WM_APP+{}", msg - WM_APP),
+        0xc000..=0xffff => format!("registered {msg:#06x}"),
+        _ => format!("{msg:#06x}"),
+    }
+}
+
+/// The parameters of the messages that matter for focus, sizing and DPI, in words.
+///
+/// # Safety
+///
+/// `wparam` and `lparam` must be as the system sent them with `msg`.
+unsafe fn decode(msg: u32, wparam: WPARAM, lparam: LPARAM) -> Option<String> {
+    let low = (lparam & 0xffff) as u16;
+    let high = ((lparam >> 16) & 0xffff) as u16;
+    let params = match msg {
+        WM_SIZE => {
+            let kind = match wparam as u32 {
+                SIZE_RESTORED => "restored",
+                SIZE_MINIMIZED => "minimized",
+                SIZE_MAXIMIZED => "maximized",
+                SIZE_MAXSHOW => "max show",
+                SIZE_MAXHIDE => "max hide",
+                _ => "unknown",
+            };
+            format!("{kind} {low}x{high}")
+        }
+        WM_MOVE => format!("({}, {})", low as i16, high as i16),
+        WM_DPICHANGED => {
+            let rect = &*(lparam as *const RECT);
+            format!("dpi={} suggested={}", wparam & 0xffff, rect_to_string(rect))
+        }
+        WM_ACTIVATE => {
+            let state = match (wparam & 0xffff) as u32 {
+                WA_INACTIVE => "inactive",
+                WA_ACTIVE => "active",
+                WA_CLICKACTIVE => "click active",
+                _ => "unknown",
+            };
+            let minimized = (wparam >> 16) & 0xffff != 0;
+            format!("{state} minimized={minimized} other={lparam:#x}")
+        }
+        WM_ACTIVATEAPP => format!("active={} thread={lparam}", wparam != 0),
+        WM_NCACTIVATE => format!("active={}", wparam != 0),
+        WM_SETFOCUS | WM_KILLFOCUS => format!("other={wparam:#x}"),
+        WM_SHOWWINDOW => format!("shown={} status={lparam}", wparam != 0),
+        WM_WINDOWPOSCHANGING | WM_WINDOWPOSCHANGED => {
+            let pos = &*(lparam as *const WINDOWPOS);
+            format!(
+                "({}, {}) {}x{} flags={:#x}",
+                pos.x, pos.y, pos.cx, pos.cy, pos.flags
+            )
+        }
+        WM_GETMINMAXINFO => {
+            let info = &*(lparam as *const MINMAXINFO);
+            format!(
+                "min={}x{} max={}x{}",
+                info.ptMinTrackSize.x,
+                info.ptMinTrackSize.y,
+                info.ptMaxTrackSize.x,
+                info.ptMaxTrackSize.y
+            )
+        }
+        WM_SIZING | WM_MOVING => {
+            let rect = &*(lparam as *const RECT);
+            format!("edge={wparam} {}", rect_to_string(rect))
+        }
+        WM_DISPLAYCHANGE => format!("{low}x{high} {wparam} bpp"),
+        WM_SYSCOMMAND => format!("command={:#06x}", wparam & 0xfff0),
+        WM_KEYDOWN | WM_KEYUP | WM_SYSKEYDOWN | WM_SYSKEYUP => format!(
+            "vk={wparam:#04x} scancode={:#04x} extended={} repeat={}",
+            (high & 0xff),
+            high & 0x100 != 0,
+            low
+        ),
+        WM_CHAR | WM_SYSCHAR | WM_DEADCHAR => format!("unit={wparam:#06x}"),
+        WM_MOUSEMOVE | WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDOWN | WM_RBUTTONUP
+        | WM_MBUTTONDOWN | WM_MBUTTONUP | WM_XBUTTONDOWN | WM_XBUTTONUP | WM_NCHITTEST => {
+            format!(
+                "({}, {}) keys={:#x}",
+                low as i16,
+                high as i16,
+                wparam & 0xffff
+            )
+        }
+        WM_SETCURSOR => format!("hit={low} mouse={}", name(u32::from(high))),
+        WM_SETTINGCHANGE if lparam != 0 => {
+            let area = WStr::from_ptr(lparam as *const u16);
+            format!("area={:?}", area.to_string_lossy())
+        }
+        _ => return None,
+    };
+
+    Some(params)
+}
+
+fn rect_to_string(rect: &RECT) -> String {
+    format!(
+        "({}, {}) {}x{}",
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top
+    )
+}
@@ -0,0 +1,236 @@
+//! The window's taskbar button: a progress bar for long operations such as asset cooking or shader
+//! compilation, a small overlay icon, and flashing to get the user's attention when they finish.
+//!
+//! ```no_run
+//! use win32::{taskbar::{Overlay, Progress, Taskbar}, window::Window};
+//!
+//! let window = Window::builder().title("Galleon").build()?;
+//! let taskbar = Taskbar::new(&window)?;
+//! for shader in 0..100 {
+//!     taskbar.set_progress(Progress::Normal(shader as f64 / 100.0))?;
+//! }
+//! taskbar.set_progress(Progress::None)?;
+//! taskbar.set_overlay(Some(Overlay::Information), "Shaders compiled")?;
+//! taskbar.flash();
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! note: the button only exists once the window has been shown, and Explorer drops the progress
+//! and overlay if it restarts, so set them again after either.
+
+use std::ffi::c_void;
+
+use common::error::Error;
+use windows_sys::{
+    core::{GUID, HRESULT, PCWSTR},
+    Win32::{
+        Foundation::HWND,
+        System::Com::{CoCreateInstance, CLSCTX_INPROC_SERVER},
+        UI::{
+            Shell::{
+                SHGetStockIconInfo, TaskbarList, SHGSI_ICON, SHGSI_SMALLICON, SHSTOCKICONINFO,
+                SIID_ERROR, SIID_INFO, SIID_WARNING, TBPFLAG, TBPF_ERROR, TBPF_INDETERMINATE,
+                TBPF_NOPROGRESS, TBPF_NORMAL, TBPF_PAUSED,
+            },
+            WindowsAndMessaging::{
+                DestroyIcon, FlashWindowEx, FLASHWINFO, FLASHW_STOP, FLASHW_TIMERNOFG, FLASHW_TRAY,
+                HICON,
+            },
+        },
+    },
+};
+
+use crate::{
+    com::{Apartment, ComGuard, ComPtr, IUnknownVtbl},
+    error::Hresult,
+    window::Window,
+    wstr,
+};
+
+/// `ITaskbarList3`, which windows-sys declares only as an opaque pointer.
+const IID_ITASKBAR_LIST3: GUID = GUID::from_u128(0xea1afb91_9e28_4b86_90e9_9e9f8a5eefaf);
+
+/// The steps the progress value is reported in.
+const PROGRESS_STEPS: u64 = 10_000;
+
+/// The state of the progress bar drawn behind the taskbar button. Fractions are clamped to 0..=1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Progress {
+    /// No progress bar.
+    None,
+    /// A pulsing bar, for work of unknown length.
+    Indeterminate,
+    /// A green bar.
+    Normal(f64),
+    /// A yellow bar.
+    Paused(f64),
+    /// A red bar.
+    Error(f64),
+}
+
+/// A system icon drawn over the corner of the taskbar button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overlay {
+    Error,
+    Warning,
+    Information,
+}
+
+/// The taskbar button of one window. It has to be used on the thread that created it.
+pub struct Taskbar {
+    taskbar: ComPtr,
+    hwnd: HWND,
+    // note: declared last so that COM outlives the taskbar list.
+    _com: ComGuard,
+}
+
+impl Taskbar {
+    pub fn new(window: &Window) -> Result<Self, Error> {
+        let com = ComGuard::current_or(Apartment::SingleThreaded)?;
+        let mut taskbar = std::ptr::null_mut();
+        let hr = unsafe {
+            CoCreateInstance(
+                &TaskbarList,
+                std::ptr::null_mut(),
+                CLSCTX_INPROC_SERVER,
+                &IID_ITASKBAR_LIST3,
+                &mut taskbar,
+            )
+        };
+        let taskbar = Self {
+            taskbar: unsafe {
+                ComPtr::from_call(hr, taskbar, "failed to create the taskbar list")
+            }?,
+            hwnd: window.hwnd(),
+            _com: com,
+        };
+
+        let hr = unsafe { (taskbar.vtbl().hr_init)(taskbar.this()) };
+        Hresult(hr).check("failed to initialize the taskbar list")?;
+
+        Ok(taskbar)
+    }
+
+    pub fn set_progress(&self, progress: Progress) -> Result<(), Error> {
+        let (state, fraction): (TBPFLAG, _) = match progress {
+            Progress::None => (TBPF_NOPROGRESS, None),
+            Progress::Indeterminate => (TBPF_INDETERMINATE, None),
+            Progress::Normal(fraction) => (TBPF_NORMAL, Some(fraction)),
+            Progress::Paused(fraction) => (TBPF_PAUSED, Some(fraction)),
+            Progress::Error(fraction) => (TBPF_ERROR, Some(fraction)),
+        };
+
+        // note: setting a value switches an indeterminate or empty bar to normal, so the state
+        // goes after it.
+        if let Some(fraction) = fraction {
+            let completed = (fraction.clamp(0.0, 1.0) * PROGRESS_STEPS as f64).round() as u64;
+            let hr = unsafe {
+                (self.vtbl().set_progress_value)(self.this(), self.hwnd, completed, PROGRESS_STEPS)
+            };
+            Hresult(hr).check("failed to set taskbar progress")?;
+        }
+        let hr = unsafe { (self.vtbl().set_progress_state)(self.this(), self.hwnd, state) };
+        Hresult(hr).check("failed to set taskbar progress state")
+    }
+
+    /// Sets or, with `None`, clears the overlay icon. `description` is read out by screen
+    /// readers in its place.
+    pub fn set_overlay(&self, overlay: Option<Overlay>, description: &str) -> Result<(), Error> {
+        let Some(overlay) = overlay else {
+            let hr = unsafe {
+                (self.vtbl().set_overlay_icon)(self.this(), self.hwnd, 0, std::ptr::null())
+            };
+            return Hresult(hr).check("failed to clear the taskbar overlay");
+        };
+
+        let icon = stock_icon(overlay)?;
+        let description = wstr!("{description}");
+        let hr = unsafe {
+            (self.vtbl().set_overlay_icon)(self.this(), self.hwnd, icon, description.as_ptr())
+        };
+        // The taskbar keeps its own copy.
+        unsafe { DestroyIcon(icon) };
+        Hresult(hr).check("failed to set the taskbar overlay")
+    }
+
+    /// Flashes the taskbar button until the window comes to the foreground, e.g. when a long
+    /// build finishes while the user is in another application. Does nothing if it's already in
+    /// the foreground.
+    pub fn flash(&self) {
+        self.flash_window(FLASHW_TRAY | FLASHW_TIMERNOFG);
+    }
+
+    pub fn stop_flashing(&self) {
+        self.flash_window(FLASHW_STOP);
+    }
+
+    fn flash_window(&self, flags: u32) {
+        let info = FLASHWINFO {
+            cbSize: std::mem::size_of::<FLASHWINFO>() as u32,
+            hwnd: self.hwnd,
+            dwFlags: flags,
+            uCount: 0,
+            dwTimeout: 0,
+        };
+        unsafe { FlashWindowEx(&info) };
+    }
+
+    fn this(&self) -> *mut c_void {
+        self.taskbar.as_raw()
+    }
+
+    fn vtbl(&self) -> &ITaskbarList3Vtbl {
+        unsafe { self.taskbar.vtbl() }
+    }
+}
+
+fn stock_icon(overlay: Overlay) -> Result<HICON, Error> {
+    let id = match overlay {
+        Overlay::Error => SIID_ERROR,
+        Overlay::Warning => SIID_WARNING,
+        Overlay::Information => SIID_INFO,
+    };
+    let mut info: SHSTOCKICONINFO = unsafe { std::mem::zeroed() };
+    info.cbSize = std::mem::size_of::<SHSTOCKICONINFO>() as u32;
+    let hr = unsafe { SHGetStockIconInfo(id, SHGSI_ICON | SHGSI_SMALLICON, &mut info) };
+    Hresult(hr).check("failed to load stock icon")?;
+
+    Ok(info.hIcon)
+}
+
+/// The start of `ITaskbarList3`'s vtable, up to the last method used. The methods this module
+/// doesn't call are left as untyped slots.
+#[repr(C)]
+struct ITaskbarList3Vtbl {
+    _base: IUnknownVtbl,
+    // ITaskbarList
+    hr_init: unsafe extern "system" fn(this: *mut c_void) -> HRESULT,
+    _add_tab: usize,
+    _delete_tab: usize,
+    _activate_tab: usize,
+    _set_active_alt: usize,
+    // ITaskbarList2
+    _mark_fullscreen_window: usize,
+    // ITaskbarList3
+    set_progress_value: unsafe extern "system" fn(
+        this: *mut c_void,
+        hwnd: HWND,
+        completed: u64,
+        total: u64,
+    ) -> HRESULT,
+    set_progress_state:
+        unsafe extern "system" fn(this: *mut c_void, hwnd: HWND, flags: TBPFLAG) -> HRESULT,
+    _register_tab: usize,
+    _unregister_tab: usize,
+    _set_tab_order: usize,
+    _set_tab_active: usize,
+    _thumb_bar_add_buttons: usize,
+    _thumb_bar_update_buttons: usize,
+    _thumb_bar_set_image_list: usize,
+    set_overlay_icon: unsafe extern "system" fn(
+        this: *mut c_void,
+        hwnd: HWND,
+        icon: HICON,
+        description: PCWSTR,
+    ) -> HRESULT,
+}
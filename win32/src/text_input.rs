@@ -0,0 +1,172 @@
+//! Text entry: typed characters and IME composition. Composition is drawn by the app, so
+//! Windows' own composition window is turned off; the IME's candidate list is still shown by
+//! Windows, next to the position the app sets.
+
+use windows_sys::Win32::{
+    Foundation::{HWND, LPARAM, POINT, RECT},
+    Globalization::HIMC,
+    UI::Input::Ime::{
+        ImmAssociateContextEx, ImmGetCompositionStringW, ImmGetContext, ImmReleaseContext,
+        ImmSetCandidateWindow, ImmSetCompositionWindow, CANDIDATEFORM, CFS_CANDIDATEPOS, CFS_POINT,
+        COMPOSITIONFORM, GCS_COMPSTR, GCS_CURSORPOS, GCS_RESULTSTR, IACE_DEFAULT,
+        IME_COMPOSITION_STRING,
+    },
+};
+
+use crate::window::PhysicalPosition;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImeEvent {
+    /// The user started composing, e.g. typed the first kana of a word. Show a preedit area.
+    Start,
+    /// The text being composed changed. `cursor` is a byte offset into `text`, if the IME has
+    /// one.
+    Update { text: String, cursor: Option<usize> },
+    /// The user settled on some text. Insert it as if it were typed.
+    Commit(String),
+    /// Composition finished or was cancelled. Hide the preedit area.
+    End,
+}
+
+/// Turns the IME on or off for `hwnd`. With it off, keys go straight to the window even when
+/// the user has e.g. a Japanese input method selected.
+pub(crate) fn set_ime_enabled(hwnd: HWND, enabled: bool) {
+    unsafe { ImmAssociateContextEx(hwnd, 0, if enabled { IACE_DEFAULT } else { 0 }) };
+}
+
+/// Puts the candidate list and the (hidden) composition window at `position`, in client
+/// coordinates, usually the text cursor.
+pub(crate) fn set_ime_position(hwnd: HWND, position: PhysicalPosition) {
+    let Some(context) = Context::get(hwnd) else {
+        return;
+    };
+
+    let point = POINT {
+        x: position.x,
+        y: position.y,
+    };
+    let area = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    let composition = COMPOSITIONFORM {
+        dwStyle: CFS_POINT,
+        ptCurrentPos: point,
+        rcArea: area,
+    };
+    let candidate = CANDIDATEFORM {
+        dwIndex: 0,
+        dwStyle: CFS_CANDIDATEPOS,
+        ptCurrentPos: point,
+        rcArea: area,
+    };
+    unsafe {
+        ImmSetCompositionWindow(context.himc, &composition);
+        ImmSetCandidateWindow(context.himc, &candidate);
+    }
+}
+
+/// The events a `WM_IME_COMPOSITION` message carries. A commit comes before the update for any
+/// composition that continues after it.
+pub(crate) fn composition_events(hwnd: HWND, lparam: LPARAM) -> Vec<ImeEvent> {
+    let Some(context) = Context::get(hwnd) else {
+        return Vec::new();
+    };
+
+    let flags = lparam as u32;
+    let mut events = Vec::new();
+    if flags & GCS_RESULTSTR != 0 {
+        if let Some(text) = context.string(GCS_RESULTSTR) {
+            events.push(ImeEvent::Commit(String::from_utf16_lossy(&text)));
+        }
+    }
+    if flags & GCS_COMPSTR != 0 {
+        if let Some(text) = context.string(GCS_COMPSTR) {
+            let cursor = (flags & GCS_CURSORPOS != 0)
+                .then(|| context.cursor())
+                .flatten()
+                .map(|units| String::from_utf16_lossy(&text[..units.min(text.len())]).len());
+            events.push(ImeEvent::Update {
+                text: String::from_utf16_lossy(&text),
+                cursor,
+            });
+        }
+    }
+
+    events
+}
+
+/// Decodes `WM_CHAR` units, pairing up surrogates that arrive as separate messages.
+#[derive(Default)]
+pub(crate) struct CharDecoder {
+    high_surrogate: Option<u16>,
+}
+
+impl CharDecoder {
+    /// The character `unit` completes, skipping control characters, which come through as key
+    /// events instead.
+    pub(crate) fn push(&mut self, unit: u16) -> Option<char> {
+        let c = match unit {
+            0xd800..=0xdbff => {
+                self.high_surrogate = Some(unit);
+                return None;
+            }
+            0xdc00..=0xdfff => {
+                let high = self.high_surrogate.take()?;
+                char::decode_utf16([high, unit]).next()?.ok()?
+            }
+            _ => {
+                self.high_surrogate = None;
+                char::from_u32(u32::from(unit))?
+            }
+        };
+
+        (!c.is_control()).then_some(c)
+    }
+}
+
+/// An input context, released when dropped.
+struct Context {
+    hwnd: HWND,
+    himc: HIMC,
+}
+
+impl Context {
+    fn get(hwnd: HWND) -> Option<Self> {
+        let himc = unsafe { ImmGetContext(hwnd) };
+        (himc != 0).then_some(Self { hwnd, himc })
+    }
+
+    fn string(&self, kind: IME_COMPOSITION_STRING) -> Option<Vec<u16>> {
+        let bytes = unsafe { ImmGetCompositionStringW(self.himc, kind, std::ptr::null_mut(), 0) };
+        if bytes < 0 {
+            return None;
+        }
+
+        let mut text = vec![0u16; bytes as usize / 2];
+        let read = unsafe {
+            ImmGetCompositionStringW(self.himc, kind, text.as_mut_ptr() as *mut _, bytes as u32)
+        };
+        if read < 0 {
+            return None;
+        }
+        text.truncate(read as usize / 2);
+
+        Some(text)
+    }
+
+    /// The cursor position in UTF-16 units.
+    fn cursor(&self) -> Option<usize> {
+        let position =
+            unsafe { ImmGetCompositionStringW(self.himc, GCS_CURSORPOS, std::ptr::null_mut(), 0) };
+        usize::try_from(position).ok()
+    }
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        unsafe { ImmReleaseContext(self.hwnd, self.himc) };
+    }
+}
@@ -0,0 +1,73 @@
+//! The system's light or dark app theme, and the dark title bar that goes with it.
+//!
+//! Windows follow the system theme unless given one with [`Window::set_theme`], and report it
+//! changing as [`Event::ThemeChanged`](crate::event_loop::Event::ThemeChanged).
+//!
+//! ```no_run
+//! use win32::{theme::{self, Theme}, window::Window};
+//!
+//! let window = Window::builder().title("Galleon Editor").build()?;
+//! if theme::system_theme() == Theme::Light {
+//!     window.set_theme(Some(Theme::Dark));
+//! }
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! [`Window::set_theme`]: crate::window::Window::set_theme
+
+use windows_sys::Win32::{
+    Foundation::{BOOL, HWND, LPARAM},
+    Graphics::Dwm::{DwmSetWindowAttribute, DWMWA_USE_IMMERSIVE_DARK_MODE},
+};
+
+use crate::{error::Hresult, registry, wide::WStr, wstr};
+
+/// What Windows 10 builds before 20H1 called `DWMWA_USE_IMMERSIVE_DARK_MODE`.
+const DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1: u32 = 19;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// The theme the user picked for apps in Settings. Light if there's no such setting, as before
+/// Windows 10 1809.
+pub fn system_theme() -> Theme {
+    let key = r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize";
+    match registry::get_u32(key, "AppsUseLightTheme") {
+        Ok(Some(0)) => Theme::Dark,
+        _ => Theme::Light,
+    }
+}
+
+/// Draws the window's title bar and borders dark or light. Does nothing before Windows 10 1809.
+pub(crate) fn set_title_bar(hwnd: HWND, theme: Theme) {
+    let dark = BOOL::from(theme == Theme::Dark);
+    for attribute in [
+        DWMWA_USE_IMMERSIVE_DARK_MODE as u32,
+        DWMWA_USE_IMMERSIVE_DARK_MODE_BEFORE_20H1,
+    ] {
+        let hr = unsafe {
+            DwmSetWindowAttribute(
+                hwnd,
+                attribute,
+                (&dark as *const BOOL).cast(),
+                std::mem::size_of::<BOOL>() as u32,
+            )
+        };
+        if Hresult(hr).is_ok() {
+            return;
+        }
+    }
+}
+
+/// Whether a `WM_SETTINGCHANGE` is the one broadcast when the app theme or accent color changes.
+pub(crate) fn is_theme_change(lparam: LPARAM) -> bool {
+    if lparam == 0 {
+        return false;
+    }
+
+    let area = unsafe { WStr::from_ptr(lparam as *const u16) };
+    area.as_slice() == wstr!("ImmersiveColorSet").as_slice()
+}
@@ -0,0 +1,328 @@
+//! Thread names, priorities and CPU affinity, and a default layout for worker threads that keeps
+//! them on the performance cores of hybrid CPUs such as Alder Lake.
+//!
+//! Threads spawned through [`ThreadBuilder`] get their name as a thread description, which is
+//! what debuggers, profilers and crash dumps show, along with the priority and affinity asked
+//! for:
+//!
+//! ```no_run
+//! use win32::thread::{self, Priority, ThreadBuilder};
+//!
+//! let layout = thread::worker_layout()?;
+//! let workers: Vec<_> = layout
+//!     .workers
+//!     .iter()
+//!     .enumerate()
+//!     .map(|(index, &affinity)| {
+//!         ThreadBuilder::new(format!("galleon-worker-{index}"))
+//!             .affinity(affinity)
+//!             .spawn(|| {})
+//!     })
+//!     .collect::<Result<_, _>>()?;
+//! ThreadBuilder::new("galleon-streaming")
+//!     .affinity(layout.background)
+//!     .priority(Priority::BelowNormal)
+//!     .spawn(|| {})?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! note: affinity masks only cover the processor group the process runs in, so at most 64
+//! logical processors.
+
+use std::thread::JoinHandle;
+
+use common::error::{Error, ErrorKind};
+use tracing::warn;
+use windows_sys::Win32::{
+    Foundation::{LocalFree, HANDLE},
+    System::{
+        Diagnostics::Debug::{SetThreadErrorMode, SEM_FAILCRITICALERRORS, SEM_NOOPENFILEERRORBOX},
+        Kernel::PROCESSOR_NUMBER,
+        SystemInformation::{
+            GetLogicalProcessorInformationEx, RelationProcessorCore,
+            SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX,
+        },
+        Threading::{
+            GetCurrentProcess, GetCurrentProcessorNumberEx, GetCurrentThread, GetThreadDescription,
+            SetPriorityClass, SetThreadAffinityMask, SetThreadDescription, SetThreadPriority,
+            ABOVE_NORMAL_PRIORITY_CLASS, BELOW_NORMAL_PRIORITY_CLASS, HIGH_PRIORITY_CLASS,
+            IDLE_PRIORITY_CLASS, NORMAL_PRIORITY_CLASS, THREAD_PRIORITY,
+            THREAD_PRIORITY_ABOVE_NORMAL, THREAD_PRIORITY_BELOW_NORMAL, THREAD_PRIORITY_HIGHEST,
+            THREAD_PRIORITY_IDLE, THREAD_PRIORITY_LOWEST, THREAD_PRIORITY_NORMAL,
+            THREAD_PRIORITY_TIME_CRITICAL,
+        },
+    },
+};
+
+use crate::{
+    call,
+    error::{last_error, Hresult},
+    wide::WStr,
+    wstr,
+};
+
+/// The priority of one thread relative to the others in the process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Priority {
+    Idle,
+    Lowest,
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    Highest,
+    /// E.g. for the audio mixer, which glitches audibly if it misses a deadline.
+    TimeCritical,
+}
+
+impl Priority {
+    fn to_raw(self) -> THREAD_PRIORITY {
+        match self {
+            Priority::Idle => THREAD_PRIORITY_IDLE,
+            Priority::Lowest => THREAD_PRIORITY_LOWEST,
+            Priority::BelowNormal => THREAD_PRIORITY_BELOW_NORMAL,
+            Priority::Normal => THREAD_PRIORITY_NORMAL,
+            Priority::AboveNormal => THREAD_PRIORITY_ABOVE_NORMAL,
+            Priority::Highest => THREAD_PRIORITY_HIGHEST,
+            Priority::TimeCritical => THREAD_PRIORITY_TIME_CRITICAL,
+        }
+    }
+}
+
+/// The priority of the whole process relative to other processes. Realtime is left out, since
+/// it can starve the input and disk threads the game itself depends on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PriorityClass {
+    Idle,
+    BelowNormal,
+    #[default]
+    Normal,
+    AboveNormal,
+    High,
+}
+
+/// One physical core.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Core {
+    /// The core's logical processors, as an affinity mask.
+    pub mask: u64,
+    /// Higher is faster. All cores share one class on CPUs that aren't hybrid.
+    pub efficiency_class: u8,
+}
+
+/// Where to put the engine's threads. See [`worker_layout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkerLayout {
+    /// An affinity mask per worker thread, one per performance core.
+    pub workers: Vec<u64>,
+    /// For streaming, logging and other background threads: the efficiency cores, or every
+    /// core if there aren't any.
+    pub background: u64,
+}
+
+pub struct ThreadBuilder {
+    name: String,
+    priority: Priority,
+    affinity: Option<u64>,
+}
+
+impl ThreadBuilder {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: name.into(),
+            priority: Priority::Normal,
+            affinity: None,
+        }
+    }
+
+    pub fn priority(self, priority: Priority) -> Self {
+        Self { priority, ..self }
+    }
+
+    /// The logical processors the thread may run on. Any, if unset.
+    pub fn affinity(self, mask: u64) -> Self {
+        Self {
+            affinity: Some(mask),
+            ..self
+        }
+    }
+
+    /// Starts the thread. A priority or affinity that can't be applied is logged and ignored,
+    /// rather than failing the spawn.
+    pub fn spawn<F, T>(self, f: F) -> Result<JoinHandle<T>, Error>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let Self {
+            name,
+            priority,
+            affinity,
+        } = self;
+        let context = format!("failed to spawn thread {name}");
+
+        std::thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                // note: std already sets the description from the thread's name.
+                if priority != Priority::Normal {
+                    if let Err(err) = set_current_priority(priority) {
+                        warn!(
+                            error = &err as &dyn std::error::Error,
+                            "ignoring thread priority"
+                        );
+                    }
+                }
+                if let Some(mask) = affinity {
+                    if let Err(err) = set_current_affinity(mask) {
+                        warn!(
+                            error = &err as &dyn std::error::Error,
+                            "ignoring thread affinity"
+                        );
+                    }
+                }
+
+                f()
+            })
+            .map_err(|err| Error::new(context).with_source(err))
+    }
+}
+
+/// Names the calling thread in debuggers, profilers and crash dumps, e.g. the main thread, or
+/// threads created by other libraries.
+pub fn set_current_name(name: &str) -> Result<(), Error> {
+    let name = wstr!("{name}");
+    let hr = unsafe { SetThreadDescription(GetCurrentThread(), name.as_ptr()) };
+    Hresult(hr).check("failed to set thread description")
+}
+
+/// The calling thread's description, if it has one.
+pub fn current_name() -> Option<String> {
+    name_of(unsafe { GetCurrentThread() })
+}
+
+pub(crate) fn name_of(thread: HANDLE) -> Option<String> {
+    let mut description = std::ptr::null_mut();
+    if unsafe { GetThreadDescription(thread, &mut description) } < 0 {
+        return None;
+    }
+
+    let name = unsafe { WStr::from_ptr(description) }.to_string_lossy();
+    unsafe { LocalFree(description.cast()) };
+
+    (!name.is_empty()).then_some(name)
+}
+
+pub fn set_current_priority(priority: Priority) -> Result<(), Error> {
+    unsafe { call!(SetThreadPriority(GetCurrentThread(), priority.to_raw())) }
+}
+
+/// Pins the calling thread to the logical processors in `mask`, returning the mask it had.
+pub fn set_current_affinity(mask: u64) -> Result<u64, Error> {
+    let previous = unsafe { SetThreadAffinityMask(GetCurrentThread(), mask as usize) };
+    if previous == 0 {
+        return Err(last_error(&format!(
+            "failed to set thread affinity to {mask:#x}"
+        )));
+    }
+
+    Ok(previous as u64)
+}
+
+pub fn set_priority_class(class: PriorityClass) -> Result<(), Error> {
+    let class = match class {
+        PriorityClass::Idle => IDLE_PRIORITY_CLASS,
+        PriorityClass::BelowNormal => BELOW_NORMAL_PRIORITY_CLASS,
+        PriorityClass::Normal => NORMAL_PRIORITY_CLASS,
+        PriorityClass::AboveNormal => ABOVE_NORMAL_PRIORITY_CLASS,
+        PriorityClass::High => HIGH_PRIORITY_CLASS,
+    };
+    unsafe { call!(SetPriorityClass(GetCurrentProcess(), class)) }
+}
+
+/// Has failures such as a missing disk in a removable drive returned to the calling thread,
+/// rather than the system asking the user to insert one.
+pub fn suppress_error_dialogs() -> Result<(), Error> {
+    let mode = SEM_FAILCRITICALERRORS | SEM_NOOPENFILEERRORBOX;
+    unsafe { call!(SetThreadErrorMode(mode, std::ptr::null_mut())) }
+}
+
+/// The physical cores in the process's processor group.
+pub fn cores() -> Result<Vec<Core>, Error> {
+    let mut len = 0;
+    unsafe {
+        GetLogicalProcessorInformationEx(RelationProcessorCore, std::ptr::null_mut(), &mut len)
+    };
+    // note: u64 so the records are suitably aligned.
+    let mut buffer = vec![0u64; (len as usize).div_ceil(8)];
+    if unsafe {
+        GetLogicalProcessorInformationEx(
+            RelationProcessorCore,
+            buffer.as_mut_ptr().cast(),
+            &mut len,
+        )
+    } == 0
+    {
+        return Err(last_error("failed to get processor information"));
+    }
+
+    let group = current_group();
+    let mut cores = Vec::new();
+    let mut offset = 0;
+    while offset < len as usize {
+        let info = unsafe {
+            &*(buffer.as_ptr().cast::<u8>().add(offset)
+                as *const SYSTEM_LOGICAL_PROCESSOR_INFORMATION_EX)
+        };
+        offset += info.Size as usize;
+
+        let processor = unsafe { &info.Anonymous.Processor };
+        // note: a core is always in a single group.
+        let affinity = processor.GroupMask[0];
+        if affinity.Group == group {
+            cores.push(Core {
+                mask: affinity.Mask as u64,
+                efficiency_class: processor.EfficiencyClass,
+            });
+        }
+    }
+
+    Ok(cores)
+}
+
+/// One worker per performance core, less one for the main thread, leaving the efficiency
+/// cores, which run at a fraction of the speed, to background threads. Without a hybrid CPU,
+/// background threads are free to run anywhere.
+pub fn worker_layout() -> Result<WorkerLayout, Error> {
+    let cores = cores()?;
+    let fastest = cores
+        .iter()
+        .map(|core| core.efficiency_class)
+        .max()
+        .ok_or_else(|| Error::new("no processor cores found").with_kind(ErrorKind::NotFound))?;
+    let (performance, efficiency): (Vec<Core>, Vec<Core>) = cores
+        .iter()
+        .partition(|core| core.efficiency_class == fastest);
+
+    let mask = |cores: &[Core]| cores.iter().fold(0, |mask, core| mask | core.mask);
+    let mut workers: Vec<u64> = performance.iter().skip(1).map(|core| core.mask).collect();
+    if workers.is_empty() {
+        workers.push(mask(&performance));
+    }
+    let background = if efficiency.is_empty() {
+        mask(&cores)
+    } else {
+        mask(&efficiency)
+    };
+
+    Ok(WorkerLayout {
+        workers,
+        background,
+    })
+}
+
+fn current_group() -> u16 {
+    let mut number: PROCESSOR_NUMBER = unsafe { std::mem::zeroed() };
+    unsafe { GetCurrentProcessorNumberEx(&mut number) };
+    number.Group
+}
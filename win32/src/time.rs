@@ -0,0 +1,276 @@
+//! Frame timing from the performance counter. The event loop ticks a [`Clock`] once per frame,
+//! and the [`Time`] it produces is what simulation code should read rather than the wall clock.
+//! A [`FrameLimiter`] holds frames to a maximum rate.
+
+use std::{cell::Cell, sync::OnceLock, time::Duration};
+
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE, WAIT_OBJECT_0},
+    Media::{timeBeginPeriod, timeEndPeriod, TIMERR_NOERROR},
+    System::{
+        Performance::{QueryPerformanceCounter, QueryPerformanceFrequency},
+        Threading::{
+            CreateWaitableTimerExW, SetWaitableTimer, Sleep, WaitForSingleObject,
+            CREATE_WAITABLE_TIMER_HIGH_RESOLUTION, INFINITE, TIMER_ALL_ACCESS,
+        },
+    },
+};
+
+/// Timing for one frame.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Time {
+    delta: Duration,
+    unscaled_delta: Duration,
+    elapsed: Duration,
+    unscaled_elapsed: Duration,
+    frame: u64,
+    time_scale: f64,
+}
+
+impl Time {
+    /// The time since the previous frame, multiplied by the time scale.
+    pub fn delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    /// The real time since the previous frame.
+    pub fn unscaled_delta(&self) -> Duration {
+        self.unscaled_delta
+    }
+
+    /// The sum of every scaled delta so far.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// The real time since the clock started.
+    pub fn unscaled_elapsed(&self) -> Duration {
+        self.unscaled_elapsed
+    }
+
+    /// The number of frames before this one, so the first frame is 0.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// The time scale the deltas were scaled by.
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+}
+
+/// Measures frames with `QueryPerformanceCounter`.
+#[derive(Debug)]
+pub struct Clock {
+    start: i64,
+    last: i64,
+    time: Time,
+    ticks: u64,
+    time_scale: Cell<f64>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        let now = counter();
+        Self {
+            start: now,
+            last: now,
+            time: Time {
+                time_scale: 1.0,
+                ..Time::default()
+            },
+            ticks: 0,
+            time_scale: Cell::new(1.0),
+        }
+    }
+
+    /// Starts a new frame, measuring it from the start of the previous one. The first tick
+    /// measures from when the clock was created.
+    pub fn tick(&mut self) -> Time {
+        let now = counter();
+        let unscaled_delta = between(self.last, now);
+        let time_scale = self.time_scale.get();
+        let delta = unscaled_delta.mul_f64(time_scale);
+        self.last = now;
+
+        self.time = Time {
+            delta,
+            unscaled_delta,
+            elapsed: self.time.elapsed + delta,
+            unscaled_elapsed: between(self.start, now),
+            frame: self.ticks,
+            time_scale,
+        };
+        self.ticks += 1;
+
+        self.time
+    }
+
+    /// The current frame's timing.
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    /// Speeds up (above 1), slows down (below 1) or pauses (0) scaled time from the next tick.
+    /// Negative scales are treated as 0.
+    pub fn set_time_scale(&self, time_scale: f64) {
+        self.time_scale.set(time_scale.max(0.0));
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale.get()
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The performance counter's current value.
+pub(crate) fn counter() -> i64 {
+    let mut count = 0;
+    unsafe { QueryPerformanceCounter(&mut count) };
+    count
+}
+
+/// Performance counter ticks per second, which is fixed at boot.
+pub(crate) fn frequency() -> i64 {
+    static FREQUENCY: OnceLock<i64> = OnceLock::new();
+    *FREQUENCY.get_or_init(|| {
+        let mut frequency = 0;
+        unsafe { QueryPerformanceFrequency(&mut frequency) };
+        frequency
+    })
+}
+
+/// The time between two counter values.
+pub(crate) fn between(start: i64, end: i64) -> Duration {
+    let ticks = (end - start).max(0) as u64;
+    let frequency = frequency() as u64;
+    Duration::new(
+        ticks / frequency,
+        ((ticks % frequency) * 1_000_000_000 / frequency) as u32,
+    )
+}
+
+/// Caps the frame rate by waiting out the rest of each frame, see
+/// [`EventLoop::set_max_fps`](crate::event_loop::EventLoop::set_max_fps).
+///
+/// Most of the wait is a sleep on a high-resolution waitable timer, or on older versions of
+/// Windows a normal one with the system timer raised to 1 ms for as long as the limiter lives.
+/// Sleeps can still wake a little late, so the last stretch is a spin on the performance
+/// counter, which lands within about 0.1 ms of the target.
+#[derive(Debug)]
+pub struct FrameLimiter {
+    period: i64,
+    next: Option<i64>,
+    timer: HANDLE,
+    /// How much of each wait is spun rather than slept.
+    spin: i64,
+    _resolution: Option<TimerResolution>,
+}
+
+impl FrameLimiter {
+    pub fn new(max_fps: f64) -> Self {
+        let high_resolution = unsafe {
+            CreateWaitableTimerExW(
+                std::ptr::null(),
+                std::ptr::null(),
+                CREATE_WAITABLE_TIMER_HIGH_RESOLUTION,
+                TIMER_ALL_ACCESS,
+            )
+        };
+        let (timer, spin, resolution) = if high_resolution != 0 {
+            (high_resolution, Duration::from_micros(1000), None)
+        } else {
+            let timer = unsafe {
+                CreateWaitableTimerExW(std::ptr::null(), std::ptr::null(), 0, TIMER_ALL_ACCESS)
+            };
+            (timer, Duration::from_micros(2000), TimerResolution::begin())
+        };
+
+        let mut limiter = Self {
+            period: 0,
+            next: None,
+            timer,
+            spin: ticks(spin),
+            _resolution: resolution,
+        };
+        limiter.set_max_fps(max_fps);
+        limiter
+    }
+
+    pub fn set_max_fps(&mut self, max_fps: f64) {
+        self.period = ticks(Duration::from_secs_f64(1.0 / max_fps.max(1.0)));
+    }
+
+    /// Waits until the current frame's deadline, one period after the last one. A frame that
+    /// runs over by more than a whole period restarts the schedule instead of being followed by
+    /// short frames that catch up.
+    pub fn wait(&mut self) {
+        let now = counter();
+        let target = match self.next {
+            Some(next) if now < next + self.period => next,
+            _ => now,
+        };
+        self.sleep_until(target);
+        self.next = Some(target + self.period);
+    }
+
+    fn sleep_until(&self, target: i64) {
+        let remaining = target - counter();
+        if remaining > self.spin {
+            let sleep = between(0, remaining - self.spin);
+            let slept = self.timer != 0 && {
+                // note: negative due times are relative, in 100 ns units.
+                let due = -((sleep.as_nanos() / 100) as i64);
+                unsafe {
+                    SetWaitableTimer(self.timer, &due, 0, None, std::ptr::null(), 0) != 0
+                        && WaitForSingleObject(self.timer, INFINITE) == WAIT_OBJECT_0
+                }
+            };
+            if !slept {
+                unsafe { Sleep(sleep.as_millis() as u32) };
+            }
+        }
+
+        while counter() < target {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+impl Drop for FrameLimiter {
+    fn drop(&mut self) {
+        if self.timer != 0 {
+            unsafe { CloseHandle(self.timer) };
+        }
+    }
+}
+
+/// Raises the system timer resolution to 1 ms until dropped.
+#[derive(Debug)]
+struct TimerResolution;
+
+impl TimerResolution {
+    fn begin() -> Option<Self> {
+        (unsafe { timeBeginPeriod(1) } == TIMERR_NOERROR).then_some(Self)
+    }
+}
+
+impl Drop for TimerResolution {
+    fn drop(&mut self) {
+        unsafe { timeEndPeriod(1) };
+    }
+}
+
+/// `duration` in performance counter ticks.
+fn ticks(duration: Duration) -> i64 {
+    (duration.as_nanos() * frequency() as u128 / 1_000_000_000) as i64
+}
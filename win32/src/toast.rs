@@ -0,0 +1,563 @@
+//! Toast notifications in the corner of the screen and the Action Center, e.g. for a long asset
+//! cook finishing while the user is in another application. Clicks come back as
+//! [`Event::Toast`](crate::event_loop::Event::Toast) once the notifier is handed to
+//! [`EventLoop::set_notifier`](crate::event_loop::EventLoop::set_notifier).
+//!
+//! ```no_run
+//! use win32::toast::{Notifier, Toast};
+//!
+//! let notifier = Notifier::new("Galleon.AssetCooker", "Galleon Asset Cooker")?;
+//! let id = notifier.show(
+//!     &Toast::new("Asset cook finished")
+//!         .body("3 warnings")
+//!         .action("Open log"),
+//! )?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+//!
+//! note: Windows only shows toasts from an application it knows, so [`Notifier::new`] registers
+//! the app id for the current user and gives it to the process, which also groups the process's
+//! taskbar buttons under it.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    ffi::c_void,
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::{
+    core::{GUID, HRESULT, HSTRING, PCWSTR},
+    Win32::{
+        Foundation::{E_NOINTERFACE, S_OK},
+        System::{
+            LibraryLoader::{GetProcAddress, LoadLibraryW},
+            Threading::GetCurrentThreadId,
+        },
+        UI::{
+            Shell::SetCurrentProcessExplicitAppUserModelID,
+            WindowsAndMessaging::{PostThreadMessageW, WM_NULL},
+        },
+    },
+};
+
+use crate::{
+    com::{guid_eq, Apartment, ComGuard, ComPtr, IInspectableVtbl, IUnknownVtbl, IID_IUNKNOWN},
+    error::Hresult,
+    registry, wstr,
+};
+
+const IID_ITOAST_NOTIFICATION_MANAGER_STATICS: GUID =
+    GUID::from_u128(0x50ac103f_d235_4598_bbef_98fe4d1a3ad4);
+const IID_ITOAST_NOTIFICATION_FACTORY: GUID =
+    GUID::from_u128(0x04124b20_82c6_4229_b109_fd9ed4662b53);
+const IID_IXML_DOCUMENT: GUID = GUID::from_u128(0xf7f3a506_1e87_42d6_bcfb_b8c809fa5494);
+const IID_IXML_DOCUMENT_IO: GUID = GUID::from_u128(0x6cd0e74e_ee65_4489_9ebf_ca43e87ba637);
+const IID_ITOAST_ACTIVATED_EVENT_ARGS: GUID =
+    GUID::from_u128(0xe3bf92f3_c197_436f_8265_0625824f8dac);
+/// `TypedEventHandler<ToastNotification, IInspectable>`.
+const IID_ACTIVATED_HANDLER: GUID = GUID::from_u128(0xab54de2d_97d9_5528_b6ad_105afe156530);
+/// Marks the handler as callable from any thread, as activation arrives on a pool thread.
+const IID_IAGILE_OBJECT: GUID = GUID::from_u128(0x94ea2b94_e9cc_49e0_c0ff_ee64ca8f5b90);
+
+/// The activation arguments of the action button; clicking the toast itself gives the `launch`
+/// arguments, which are empty.
+const ACTION_ARGUMENTS: &str = "action";
+
+/// How many shown toasts are kept alive so their clicks are still reported.
+const MAX_SHOWN: usize = 32;
+
+/// Identifies a shown toast in its [`ToastEvent`]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ToastId(u64);
+
+/// What the user did with a toast.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToastEvent {
+    /// The toast itself was clicked.
+    Clicked(ToastId),
+    /// The toast's action button was clicked.
+    Action(ToastId),
+}
+
+/// The contents of a toast.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Toast {
+    title: String,
+    body: String,
+    action: Option<String>,
+}
+
+impl Toast {
+    pub fn new<S: Into<String>>(title: S) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Text below the title.
+    pub fn body<S: Into<String>>(self, body: S) -> Self {
+        Self {
+            body: body.into(),
+            ..self
+        }
+    }
+
+    /// Adds a button labelled `label`, reported as [`ToastEvent::Action`].
+    pub fn action<S: Into<String>>(self, label: S) -> Self {
+        Self {
+            action: Some(label.into()),
+            ..self
+        }
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml =
+            String::from(r#"<toast launch=""><visual><binding template="ToastGeneric"><text>"#);
+        escape_into(&mut xml, &self.title);
+        xml.push_str("</text>");
+        if !self.body.is_empty() {
+            xml.push_str("<text>");
+            escape_into(&mut xml, &self.body);
+            xml.push_str("</text>");
+        }
+        xml.push_str("</binding></visual>");
+        if let Some(label) = &self.action {
+            xml.push_str(r#"<actions><action content=""#);
+            escape_into(&mut xml, label);
+            _ = write!(
+                xml,
+                r#"" arguments="{ACTION_ARGUMENTS}" activationType="foreground"/></actions>"#
+            );
+        }
+        xml.push_str("</toast>");
+
+        xml
+    }
+}
+
+/// Shows toasts on behalf of one application. It has to be used on the thread that created it,
+/// and that thread has to pump messages for clicks to wake it.
+pub struct Notifier {
+    winrt: &'static WinRt,
+    notifier: ComPtr,
+    factory: ComPtr,
+    shared: Arc<Shared>,
+    next_id: Cell<u64>,
+    shown: RefCell<VecDeque<ComPtr>>,
+    // note: declared last so that COM outlives the objects above.
+    _com: ComGuard,
+}
+
+struct Shared {
+    winrt: &'static WinRt,
+    events: Mutex<VecDeque<ToastEvent>>,
+    /// The thread to wake when a toast is clicked.
+    thread: u32,
+}
+
+impl Notifier {
+    /// `app_id` is the application's AppUserModelID, e.g. `Galleon.AssetCooker`, and
+    /// `display_name` is shown as the toast's source.
+    pub fn new(app_id: &str, display_name: &str) -> Result<Self, Error> {
+        let winrt = winrt().ok_or_else(|| {
+            Error::new("toast notifications need Windows 10 or later")
+                .with_kind(ErrorKind::Unsupported)
+        })?;
+        let com = ComGuard::current_or(Apartment::SingleThreaded)?;
+
+        register_app_id(app_id, display_name)?;
+        let app_id_wide = wstr!("{app_id}");
+        let hr = unsafe { SetCurrentProcessExplicitAppUserModelID(app_id_wide.as_ptr()) };
+        Hresult(hr).check("failed to set the process's app id")?;
+
+        let statics = winrt.activation_factory(
+            "Windows.UI.Notifications.ToastNotificationManager",
+            &IID_ITOAST_NOTIFICATION_MANAGER_STATICS,
+        )?;
+        let app_id = HString::new(winrt, app_id)?;
+        let mut notifier = std::ptr::null_mut();
+        let hr = unsafe {
+            (statics
+                .vtbl::<IToastNotificationManagerStaticsVtbl>()
+                .create_toast_notifier_with_id)(
+                statics.as_raw(), app_id.0, &mut notifier
+            )
+        };
+        let notifier =
+            unsafe { ComPtr::from_call(hr, notifier, "failed to create toast notifier") }?;
+
+        let factory = winrt.activation_factory(
+            "Windows.UI.Notifications.ToastNotification",
+            &IID_ITOAST_NOTIFICATION_FACTORY,
+        )?;
+
+        Ok(Self {
+            winrt,
+            notifier,
+            factory,
+            shared: Arc::new(Shared {
+                winrt,
+                events: Mutex::new(VecDeque::new()),
+                thread: unsafe { GetCurrentThreadId() },
+            }),
+            next_id: Cell::new(0),
+            shown: RefCell::new(VecDeque::new()),
+            _com: com,
+        })
+    }
+
+    pub fn show(&self, toast: &Toast) -> Result<ToastId, Error> {
+        let document = self
+            .winrt
+            .activate_instance("Windows.Data.Xml.Dom.XmlDocument")?;
+        let document_io = document.query(&IID_IXML_DOCUMENT_IO, "failed to get IXmlDocumentIO")?;
+        let xml = HString::new(self.winrt, &toast.to_xml())?;
+        let hr = unsafe {
+            (document_io.vtbl::<IXmlDocumentIoVtbl>().load_xml)(document_io.as_raw(), xml.0)
+        };
+        Hresult(hr).check("failed to parse toast xml")?;
+        let document = document.query(&IID_IXML_DOCUMENT, "failed to get IXmlDocument")?;
+
+        let mut notification = std::ptr::null_mut();
+        let hr = unsafe {
+            (self
+                .factory
+                .vtbl::<IToastNotificationFactoryVtbl>()
+                .create_toast_notification)(
+                self.factory.as_raw(),
+                document.as_raw(),
+                &mut notification,
+            )
+        };
+        let notification =
+            unsafe { ComPtr::from_call(hr, notification, "failed to create toast") }?;
+
+        let id = ToastId(self.next_id.get());
+        self.next_id.set(id.0 + 1);
+        let handler = ActivatedHandler::create(id, self.shared.clone());
+        let mut token = 0;
+        let hr = unsafe {
+            (notification.vtbl::<IToastNotificationVtbl>().add_activated)(
+                notification.as_raw(),
+                handler.as_raw(),
+                &mut token,
+            )
+        };
+        Hresult(hr).check("failed to listen for toast clicks")?;
+
+        let hr = unsafe {
+            (self.notifier.vtbl::<IToastNotifierVtbl>().show)(
+                self.notifier.as_raw(),
+                notification.as_raw(),
+            )
+        };
+        Hresult(hr).check("failed to show toast")?;
+
+        let mut shown = self.shown.borrow_mut();
+        if shown.len() == MAX_SHOWN {
+            shown.pop_front();
+        }
+        shown.push_back(notification);
+
+        Ok(id)
+    }
+
+    /// The oldest click since the last call.
+    pub(crate) fn next_event(&self) -> Option<ToastEvent> {
+        self.shared.events.lock().unwrap().pop_front()
+    }
+}
+
+/// Registers `app_id` under the current user so the shell will show its toasts.
+fn register_app_id(app_id: &str, display_name: &str) -> Result<(), Error> {
+    registry::set_string(
+        &format!(r"Software\Classes\AppUserModelId\{app_id}"),
+        "DisplayName",
+        display_name,
+    )
+    .map_err(|err| Error::new("failed to register the app id").with_source(err))
+}
+
+fn escape_into(xml: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => xml.push_str("&amp;"),
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '"' => xml.push_str("&quot;"),
+            '\'' => xml.push_str("&apos;"),
+            c => xml.push(c),
+        }
+    }
+}
+
+type Farproc = unsafe extern "system" fn() -> isize;
+type CreateStringFn = unsafe extern "system" fn(PCWSTR, u32, *mut HSTRING) -> HRESULT;
+type DeleteStringFn = unsafe extern "system" fn(HSTRING) -> HRESULT;
+type StringBufferFn = unsafe extern "system" fn(HSTRING, *mut u32) -> PCWSTR;
+type GetActivationFactoryFn =
+    unsafe extern "system" fn(HSTRING, *const GUID, *mut *mut c_void) -> HRESULT;
+type ActivateInstanceFn = unsafe extern "system" fn(HSTRING, *mut *mut c_void) -> HRESULT;
+
+/// The WinRT entry points in combase, looked up at run time since they don't exist before
+/// Windows 8 and windows-sys doesn't declare them.
+struct WinRt {
+    create_string: CreateStringFn,
+    delete_string: DeleteStringFn,
+    string_buffer: StringBufferFn,
+    get_activation_factory: GetActivationFactoryFn,
+    activate_instance: ActivateInstanceFn,
+}
+
+impl WinRt {
+    fn activation_factory(&'static self, class: &str, iid: &GUID) -> Result<ComPtr, Error> {
+        let class_name = HString::new(self, class)?;
+        let mut factory = std::ptr::null_mut();
+        let hr = unsafe { (self.get_activation_factory)(class_name.0, iid, &mut factory) };
+        unsafe { ComPtr::from_call(hr, factory, &format!("failed to get the {class} factory")) }
+    }
+
+    fn activate_instance(&'static self, class: &str) -> Result<ComPtr, Error> {
+        let class_name = HString::new(self, class)?;
+        let mut instance = std::ptr::null_mut();
+        let hr = unsafe { (self.activate_instance)(class_name.0, &mut instance) };
+        unsafe { ComPtr::from_call(hr, instance, &format!("failed to create {class}")) }
+    }
+}
+
+fn winrt() -> Option<&'static WinRt> {
+    static WINRT: OnceLock<Option<WinRt>> = OnceLock::new();
+
+    WINRT
+        .get_or_init(|| {
+            let name = wstr!("combase.dll");
+            let module = unsafe { LoadLibraryW(name.as_ptr()) };
+            if module == 0 {
+                return None;
+            }
+            let proc =
+                |name: &std::ffi::CStr| unsafe { GetProcAddress(module, name.as_ptr().cast()) };
+
+            Some(WinRt {
+                create_string: unsafe {
+                    std::mem::transmute::<Farproc, CreateStringFn>(proc(c"WindowsCreateString")?)
+                },
+                delete_string: unsafe {
+                    std::mem::transmute::<Farproc, DeleteStringFn>(proc(c"WindowsDeleteString")?)
+                },
+                string_buffer: unsafe {
+                    std::mem::transmute::<Farproc, StringBufferFn>(proc(
+                        c"WindowsGetStringRawBuffer",
+                    )?)
+                },
+                get_activation_factory: unsafe {
+                    std::mem::transmute::<Farproc, GetActivationFactoryFn>(proc(
+                        c"RoGetActivationFactory",
+                    )?)
+                },
+                activate_instance: unsafe {
+                    std::mem::transmute::<Farproc, ActivateInstanceFn>(proc(c"RoActivateInstance")?)
+                },
+            })
+        })
+        .as_ref()
+}
+
+/// An owned WinRT string.
+struct HString(HSTRING, &'static WinRt);
+
+impl HString {
+    fn new(winrt: &'static WinRt, s: &str) -> Result<Self, Error> {
+        let wide = wstr!("{s}");
+        let mut handle = std::ptr::null_mut();
+        let hr = unsafe { (winrt.create_string)(wide.as_ptr(), wide.len() as u32, &mut handle) };
+        Hresult(hr).check("failed to create string")?;
+
+        Ok(Self(handle, winrt))
+    }
+
+    fn to_string_lossy(&self) -> String {
+        let mut len = 0;
+        let buffer = unsafe { (self.1.string_buffer)(self.0, &mut len) };
+        if buffer.is_null() || len == 0 {
+            return String::new();
+        }
+
+        String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(buffer, len as usize) })
+    }
+}
+
+impl Drop for HString {
+    fn drop(&mut self) {
+        unsafe { (self.1.delete_string)(self.0) };
+    }
+}
+
+/// The COM object WinRT calls when a toast is clicked. It queues the click and wakes the thread
+/// that showed the toast.
+#[repr(C)]
+struct ActivatedHandler {
+    vtbl: &'static ActivatedHandlerVtbl,
+    refs: AtomicU32,
+    id: ToastId,
+    shared: Arc<Shared>,
+}
+
+#[repr(C)]
+struct ActivatedHandlerVtbl {
+    base: IUnknownVtbl,
+    invoke: unsafe extern "system" fn(
+        this: *mut c_void,
+        sender: *mut c_void,
+        args: *mut c_void,
+    ) -> HRESULT,
+}
+
+static ACTIVATED_HANDLER_VTBL: ActivatedHandlerVtbl = ActivatedHandlerVtbl {
+    base: IUnknownVtbl {
+        query_interface: ActivatedHandler::query_interface,
+        add_ref: ActivatedHandler::add_ref,
+        release: ActivatedHandler::release,
+    },
+    invoke: ActivatedHandler::invoke,
+};
+
+impl ActivatedHandler {
+    fn create(id: ToastId, shared: Arc<Shared>) -> ComPtr {
+        let handler = Box::new(Self {
+            vtbl: &ACTIVATED_HANDLER_VTBL,
+            refs: AtomicU32::new(1),
+            id,
+            shared,
+        });
+        let handler = Box::into_raw(handler).cast();
+        unsafe { ComPtr::from_raw(handler) }.expect("Box::into_raw is never null")
+    }
+
+    unsafe extern "system" fn query_interface(
+        this: *mut c_void,
+        iid: *const GUID,
+        object: *mut *mut c_void,
+    ) -> HRESULT {
+        let iid = &*iid;
+        let known = [IID_IUNKNOWN, IID_IAGILE_OBJECT, IID_ACTIVATED_HANDLER];
+        if !known.iter().any(|known| guid_eq(known, iid)) {
+            *object = std::ptr::null_mut();
+            return E_NOINTERFACE;
+        }
+
+        Self::add_ref(this);
+        *object = this;
+        S_OK
+    }
+
+    unsafe extern "system" fn add_ref(this: *mut c_void) -> u32 {
+        let handler = &*(this as *const Self);
+        handler.refs.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    unsafe extern "system" fn release(this: *mut c_void) -> u32 {
+        let handler = &*(this as *const Self);
+        let refs = handler.refs.fetch_sub(1, Ordering::Release) - 1;
+        if refs == 0 {
+            std::sync::atomic::fence(Ordering::Acquire);
+            drop(Box::from_raw(this as *mut Self));
+        }
+        refs
+    }
+
+    unsafe extern "system" fn invoke(
+        this: *mut c_void,
+        _sender: *mut c_void,
+        args: *mut c_void,
+    ) -> HRESULT {
+        let handler = &*(this as *const Self);
+        let event = match activation_arguments(handler.shared.winrt, args).as_deref() {
+            Some(ACTION_ARGUMENTS) => ToastEvent::Action(handler.id),
+            _ => ToastEvent::Clicked(handler.id),
+        };
+        handler.shared.events.lock().unwrap().push_back(event);
+        PostThreadMessageW(handler.shared.thread, WM_NULL, 0, 0);
+
+        S_OK
+    }
+}
+
+/// The arguments of the clicked part of the toast, from the `IInspectable` WinRT passes.
+fn activation_arguments(winrt: &'static WinRt, args: *mut c_void) -> Option<String> {
+    let args = unsafe { ComPtr::from_borrowed(args) }?;
+    let args = args
+        .query(
+            &IID_ITOAST_ACTIVATED_EVENT_ARGS,
+            "failed to get IToastActivatedEventArgs",
+        )
+        .ok()?;
+    let mut arguments = std::ptr::null_mut();
+    let hr = unsafe {
+        (args.vtbl::<IToastActivatedEventArgsVtbl>().get_arguments)(args.as_raw(), &mut arguments)
+    };
+    Hresult(hr).check("failed to get toast arguments").ok()?;
+
+    Some(HString(arguments, winrt).to_string_lossy())
+}
+
+#[repr(C)]
+struct IToastNotificationManagerStaticsVtbl {
+    _base: IInspectableVtbl,
+    _create_toast_notifier: usize,
+    create_toast_notifier_with_id: unsafe extern "system" fn(
+        this: *mut c_void,
+        app_id: HSTRING,
+        notifier: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IToastNotifierVtbl {
+    _base: IInspectableVtbl,
+    show: unsafe extern "system" fn(this: *mut c_void, notification: *mut c_void) -> HRESULT,
+}
+
+#[repr(C)]
+struct IXmlDocumentIoVtbl {
+    _base: IInspectableVtbl,
+    load_xml: unsafe extern "system" fn(this: *mut c_void, xml: HSTRING) -> HRESULT,
+}
+
+#[repr(C)]
+struct IToastNotificationFactoryVtbl {
+    _base: IInspectableVtbl,
+    create_toast_notification: unsafe extern "system" fn(
+        this: *mut c_void,
+        content: *mut c_void,
+        notification: *mut *mut c_void,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IToastNotificationVtbl {
+    _base: IInspectableVtbl,
+    _get_content: usize,
+    _put_expiration_time: usize,
+    _get_expiration_time: usize,
+    _add_dismissed: usize,
+    _remove_dismissed: usize,
+    add_activated: unsafe extern "system" fn(
+        this: *mut c_void,
+        handler: *mut c_void,
+        token: *mut i64,
+    ) -> HRESULT,
+}
+
+#[repr(C)]
+struct IToastActivatedEventArgsVtbl {
+    _base: IInspectableVtbl,
+    get_arguments: unsafe extern "system" fn(this: *mut c_void, value: *mut HSTRING) -> HRESULT,
+}
@@ -0,0 +1,388 @@
+//! An icon in the notification area, for the dedicated server and background tools that run
+//! without a visible window. Clicks and menu choices reach the app as
+//! [`Event::Tray`](crate::event_loop::Event::Tray) once the icon is handed to
+//! [`EventLoop::set_tray_icon`](crate::event_loop::EventLoop::set_tray_icon).
+//!
+//! ```no_run
+//! use win32::tray::TrayIcon;
+//!
+//! const SHOW: u32 = 1;
+//! const QUIT: u32 = 2;
+//!
+//! let tray = TrayIcon::builder()
+//!     .tooltip("Galleon server")
+//!     .item(SHOW, "Show console")
+//!     .separator()
+//!     .item(QUIT, "Quit")
+//!     .build()?;
+//! # Ok::<(), common::error::Error>(())
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    marker::PhantomData,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use common::error::{Error, ErrorKind};
+use windows_sys::Win32::{
+    Foundation::{GetLastError, HWND, LPARAM, LRESULT, WPARAM},
+    System::LibraryLoader::GetModuleHandleW,
+    UI::{
+        Shell::{
+            Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_SHOWTIP, NIF_TIP, NIM_ADD, NIM_DELETE,
+            NIM_MODIFY, NIM_SETVERSION, NIN_SELECT, NOTIFYICONDATAW, NOTIFYICON_VERSION_4,
+        },
+        WindowsAndMessaging::{
+            AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu,
+            DestroyWindow, GetWindowLongPtrW, LoadIconW, PostMessageW, RegisterClassExW,
+            RegisterWindowMessageW, SetForegroundWindow, SetWindowLongPtrW, TrackPopupMenuEx,
+            CREATESTRUCTW, GWLP_USERDATA, HICON, HMENU, IDI_APPLICATION, MF_SEPARATOR, MF_STRING,
+            TPM_BOTTOMALIGN, TPM_NONOTIFY, TPM_RETURNCMD, TPM_RIGHTBUTTON, WM_APP, WM_CONTEXTMENU,
+            WM_LBUTTONDBLCLK, WM_NCCREATE, WM_NCDESTROY, WM_NULL, WNDCLASSEXW,
+        },
+    },
+};
+
+use crate::{
+    error::{last_error, win32_error},
+    wstr,
+};
+
+const ERROR_CLASS_ALREADY_EXISTS: u32 = 1410;
+const CLASS_NAME: &str = "galleon_tray";
+
+/// The message the shell sends the icon's window about clicks.
+const WM_TRAY: u32 = WM_APP + 1;
+/// Sent instead of `NIN_SELECT` when the icon is chosen with the keyboard.
+const NIN_KEYSELECT: u32 = NIN_SELECT | 1;
+
+/// What the user did with the tray icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayEvent {
+    /// A left click, or Enter or Space while the icon has keyboard focus.
+    Click,
+    /// A double click. The first click of the two is also reported as a `Click`.
+    DoubleClick,
+    /// A menu item was chosen, by the id it was added with.
+    MenuItem(u32),
+}
+
+enum MenuEntry {
+    Item { id: u32, text: String },
+    Separator,
+}
+
+pub struct TrayIconBuilder {
+    tooltip: String,
+    menu: Vec<MenuEntry>,
+}
+
+impl TrayIconBuilder {
+    /// Shown when the pointer rests on the icon. Cut to 127 characters.
+    pub fn tooltip<S: Into<String>>(self, tooltip: S) -> Self {
+        Self {
+            tooltip: tooltip.into(),
+            ..self
+        }
+    }
+
+    /// Adds an item to the menu shown on right-click. `id` identifies it in
+    /// [`TrayEvent::MenuItem`] and can't be 0.
+    pub fn item<S: Into<String>>(mut self, id: u32, text: S) -> Self {
+        self.menu.push(MenuEntry::Item {
+            id,
+            text: text.into(),
+        });
+        self
+    }
+
+    pub fn separator(mut self) -> Self {
+        self.menu.push(MenuEntry::Separator);
+        self
+    }
+
+    /// Adds the icon, showing the executable's icon if it has one.
+    pub fn build(self) -> Result<TrayIcon, Error> {
+        if self
+            .menu
+            .iter()
+            .any(|entry| matches!(entry, MenuEntry::Item { id: 0, .. }))
+        {
+            return Err(Error::new("tray menu item ids can't be 0").with_kind(ErrorKind::Config));
+        }
+
+        register_class()?;
+        let menu = create_menu(&self.menu)?;
+        let state = Box::new(TrayState {
+            events: RefCell::new(VecDeque::new()),
+            menu,
+            taskbar_created: unsafe { RegisterWindowMessageW(wstr!("TaskbarCreated").as_ptr()) },
+            data: RefCell::new(unsafe { std::mem::zeroed() }),
+        });
+
+        // note: a hidden top-level window rather than a message-only one, since only top-level
+        // windows hear that Explorer restarted.
+        let class_name = wstr!("{CLASS_NAME}");
+        let hwnd = unsafe {
+            CreateWindowExW(
+                0,
+                class_name.as_ptr(),
+                std::ptr::null(),
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                GetModuleHandleW(std::ptr::null()),
+                &*state as *const TrayState as *const _,
+            )
+        };
+        if hwnd == 0 {
+            let err = last_error("failed to create tray icon window");
+            if menu != 0 {
+                unsafe { DestroyMenu(menu) };
+            }
+            return Err(err);
+        }
+        // From here on, dropping the icon cleans up.
+        let tray = TrayIcon {
+            hwnd,
+            state,
+            _not_send: PhantomData,
+        };
+
+        {
+            let mut data = tray.state.data.borrow_mut();
+            data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as u32;
+            data.hWnd = hwnd;
+            data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP | NIF_SHOWTIP;
+            data.uCallbackMessage = WM_TRAY;
+            data.hIcon = app_icon();
+            copy_truncated(&mut data.szTip, &self.tooltip);
+        }
+        tray.state.add()?;
+
+        Ok(tray)
+    }
+}
+
+/// An icon in the notification area, removed when dropped. It has to be used on the thread that
+/// created it, which has to pump messages for it to report anything.
+pub struct TrayIcon {
+    hwnd: HWND,
+    state: Box<TrayState>,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl TrayIcon {
+    pub fn builder() -> TrayIconBuilder {
+        TrayIconBuilder {
+            tooltip: String::new(),
+            menu: Vec::new(),
+        }
+    }
+
+    pub fn set_tooltip(&self, tooltip: &str) -> Result<(), Error> {
+        let mut data = self.state.data.borrow_mut();
+        copy_truncated(&mut data.szTip, tooltip);
+        if unsafe { Shell_NotifyIconW(NIM_MODIFY, &*data) } == 0 {
+            return Err(Error::new("failed to update tray icon tooltip"));
+        }
+
+        Ok(())
+    }
+
+    /// The oldest event since the last call.
+    pub(crate) fn next_event(&self) -> Option<TrayEvent> {
+        self.state.events.borrow_mut().pop_front()
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        unsafe { Shell_NotifyIconW(NIM_DELETE, &*self.state.data.borrow()) };
+        // note: as for `Window`, the window procedure lets go of the state in WM_NCDESTROY.
+        unsafe { DestroyWindow(self.hwnd) };
+        if self.state.menu != 0 {
+            unsafe { DestroyMenu(self.state.menu) };
+        }
+    }
+}
+
+/// What the icon's window procedure works with, reached through `GWLP_USERDATA`.
+struct TrayState {
+    events: RefCell<VecDeque<TrayEvent>>,
+    /// 0 if there are no menu items.
+    menu: HMENU,
+    /// Broadcast when Explorer starts, which drops every icon along with the old taskbar.
+    taskbar_created: u32,
+    data: RefCell<NOTIFYICONDATAW>,
+}
+
+impl TrayState {
+    fn add(&self) -> Result<(), Error> {
+        let mut data = self.data.borrow_mut();
+        if unsafe { Shell_NotifyIconW(NIM_ADD, &*data) } == 0 {
+            return Err(Error::new("failed to add tray icon"));
+        }
+
+        // Version 4 reports the click position with each message and sends keyboard selection.
+        data.Anonymous.uVersion = NOTIFYICON_VERSION_4;
+        unsafe { Shell_NotifyIconW(NIM_SETVERSION, &*data) };
+
+        Ok(())
+    }
+
+    /// Shows the menu at `x, y` in screen coordinates and waits for a choice.
+    fn show_menu(&self, hwnd: HWND, x: i32, y: i32) {
+        if self.menu == 0 {
+            return;
+        }
+
+        // note: without the foreground, the menu doesn't close when the user clicks elsewhere.
+        unsafe { SetForegroundWindow(hwnd) };
+        let id = unsafe {
+            TrackPopupMenuEx(
+                self.menu,
+                TPM_RETURNCMD | TPM_NONOTIFY | TPM_RIGHTBUTTON | TPM_BOTTOMALIGN,
+                x,
+                y,
+                hwnd,
+                std::ptr::null(),
+            )
+        };
+        unsafe { PostMessageW(hwnd, WM_NULL, 0, 0) };
+
+        if id != 0 {
+            self.push(TrayEvent::MenuItem(id as u32));
+        }
+    }
+
+    fn push(&self, event: TrayEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+fn create_menu(entries: &[MenuEntry]) -> Result<HMENU, Error> {
+    if entries.is_empty() {
+        return Ok(0);
+    }
+
+    let menu = unsafe { CreatePopupMenu() };
+    if menu == 0 {
+        return Err(last_error("failed to create tray menu"));
+    }
+    for entry in entries {
+        let appended = match entry {
+            MenuEntry::Item { id, text } => {
+                let text = wstr!("{text}");
+                unsafe { AppendMenuW(menu, MF_STRING, *id as usize, text.as_ptr()) }
+            }
+            MenuEntry::Separator => unsafe { AppendMenuW(menu, MF_SEPARATOR, 0, std::ptr::null()) },
+        };
+        if appended == 0 {
+            let err = last_error("failed to add tray menu item");
+            unsafe { DestroyMenu(menu) };
+            return Err(err);
+        }
+    }
+
+    Ok(menu)
+}
+
+/// The first icon in the executable's resources, or the stock application icon.
+fn app_icon() -> HICON {
+    // note: resource compilers give the first icon id 1 unless told otherwise.
+    let icon = unsafe { LoadIconW(GetModuleHandleW(std::ptr::null()), 1 as _) };
+    if icon != 0 {
+        return icon;
+    }
+
+    unsafe { LoadIconW(0, IDI_APPLICATION) }
+}
+
+/// Copies `s` into a fixed-size buffer, leaving room for the terminator.
+fn copy_truncated(buffer: &mut [u16], s: &str) {
+    buffer.fill(0);
+    let len = buffer.len() - 1;
+    for (dst, unit) in buffer[..len]
+        .iter_mut()
+        .zip(s.encode_utf16().take_while(|unit| *unit != 0))
+    {
+        *dst = unit;
+    }
+}
+
+fn register_class() -> Result<(), Error> {
+    static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    if REGISTERED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    let class_name = wstr!("{CLASS_NAME}");
+    let mut class: WNDCLASSEXW = unsafe { std::mem::zeroed() };
+    class.cbSize = std::mem::size_of::<WNDCLASSEXW>() as u32;
+    class.lpfnWndProc = Some(tray_proc);
+    class.hInstance = unsafe { GetModuleHandleW(std::ptr::null()) };
+    class.lpszClassName = class_name.as_ptr();
+    if unsafe { RegisterClassExW(&class) } == 0 {
+        let code = unsafe { GetLastError() };
+        if code != ERROR_CLASS_ALREADY_EXISTS {
+            return Err(win32_error("failed to register tray window class", code));
+        }
+    }
+    REGISTERED.store(true, Ordering::Release);
+
+    Ok(())
+}
+
+unsafe extern "system" fn tray_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_NCCREATE {
+        let create = &*(lparam as *const CREATESTRUCTW);
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, create.lpCreateParams as isize);
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    let state = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const TrayState;
+    if state.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let state = &*state;
+
+    match msg {
+        WM_TRAY => {
+            // With version 4 the event is in the low word of `lparam` and the anchor point, in
+            // screen coordinates, in `wparam`.
+            match (lparam & 0xffff) as u32 {
+                NIN_SELECT | NIN_KEYSELECT => state.push(TrayEvent::Click),
+                WM_LBUTTONDBLCLK => state.push(TrayEvent::DoubleClick),
+                WM_CONTEXTMENU => {
+                    let x = (wparam & 0xffff) as i16 as i32;
+                    let y = ((wparam >> 16) & 0xffff) as i16 as i32;
+                    state.show_menu(hwnd, x, y);
+                }
+                _ => {}
+            }
+            0
+        }
+        msg if msg == state.taskbar_created && msg != 0 => {
+            _ = state.add();
+            0
+        }
+        WM_NCDESTROY => {
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
@@ -0,0 +1,395 @@
+//! Hang detection. The event loop pets a [`Watchdog`] once a frame; if no heartbeat arrives for
+//! the timeout, the watchdog thread captures the stack of every thread, writes a hang dump and
+//! logs a report, so a soft-lock during a playtest can be diagnosed after the fact.
+//!
+//! note: dragging or resizing the window runs a modal loop inside Windows that stops frames too,
+//! so a hang may be reported if the user holds a drag for longer than the timeout.
+
+use std::{
+    fmt::Write as _,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use common::{error::Error, log::timestamp::Timestamp};
+use tracing::{error, info, warn};
+use windows_sys::Win32::{
+    Foundation::{CloseHandle, HANDLE, INVALID_HANDLE_VALUE},
+    System::{
+        Diagnostics::ToolHelp::{
+            CreateToolhelp32Snapshot, Thread32First, Thread32Next, TH32CS_SNAPTHREAD, THREADENTRY32,
+        },
+        LibraryLoader::{
+            GetModuleFileNameW, GetModuleHandleExW, GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS,
+            GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+        },
+        Threading::{
+            GetCurrentProcessId, GetCurrentThreadId, OpenThread, ResumeThread, SuspendThread,
+            THREAD_GET_CONTEXT, THREAD_QUERY_LIMITED_INFORMATION, THREAD_SUSPEND_RESUME,
+        },
+    },
+};
+
+use crate::{debug, minidump, wide};
+
+/// The deepest stack captured for each thread.
+const MAX_FRAMES: usize = 64;
+
+pub struct WatchdogBuilder {
+    timeout: Duration,
+    dump_dir: Option<PathBuf>,
+}
+
+impl WatchdogBuilder {
+    /// How long without a heartbeat counts as a hang. Defaults to 10 s.
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout: timeout.max(Duration::from_millis(100)),
+            ..self
+        }
+    }
+
+    /// Where hang dumps are written, as `hang-<time>.dmp`. Without one, only the report is logged.
+    pub fn dump_dir<P: Into<PathBuf>>(self, dir: P) -> Self {
+        Self {
+            dump_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Starts the watchdog thread. The calling thread is the one reported as hung, so build it on
+    /// the thread that runs the event loop.
+    pub fn build(self) -> Result<Watchdog, Error> {
+        let shared = Arc::new(Shared {
+            heartbeats: AtomicU64::new(0),
+            paused: AtomicBool::new(false),
+            stopped: Mutex::new(false),
+            stop: Condvar::new(),
+        });
+
+        let thread = {
+            let shared = shared.clone();
+            let watcher = Watcher {
+                watched: unsafe { GetCurrentThreadId() },
+                timeout: self.timeout,
+                dump_dir: self.dump_dir,
+            };
+            thread::Builder::new()
+                .name("galleon-watchdog".to_string())
+                .spawn(move || watcher.run(&shared))
+                .map_err(|err| Error::new("failed to spawn watchdog thread").with_source(err))?
+        };
+
+        Ok(Watchdog {
+            shared,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Reports the thread that built it as hung when it stops calling [`pet`](Watchdog::pet). Hand it
+/// to [`EventLoop::set_watchdog`](crate::event_loop::EventLoop::set_watchdog) to have every frame
+/// pet it. Each hang is reported once, and a recovery is logged if the heartbeats resume.
+///
+/// Nothing is reported while a debugger is attached, since breakpoints stop the loop too.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct Shared {
+    heartbeats: AtomicU64,
+    paused: AtomicBool,
+    stopped: Mutex<bool>,
+    stop: Condvar,
+}
+
+impl Watchdog {
+    pub fn builder() -> WatchdogBuilder {
+        WatchdogBuilder {
+            timeout: Duration::from_secs(10),
+            dump_dir: None,
+        }
+    }
+
+    /// Records a heartbeat. Cheap enough to call every frame.
+    pub fn pet(&self) {
+        self.shared.paused.store(false, Ordering::Relaxed);
+        self.shared.heartbeats.fetch_add(1, Ordering::Release);
+    }
+
+    /// Stops the clock until the next [`pet`](Watchdog::pet), for waits that are meant to be long,
+    /// such as sleeping while the window is minimized.
+    pub fn pause(&self) {
+        self.shared.paused.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        *self.shared.stopped.lock().unwrap() = true;
+        self.shared.stop.notify_all();
+        if let Some(thread) = self.thread.take() {
+            _ = thread.join();
+        }
+    }
+}
+
+struct Watcher {
+    watched: u32,
+    timeout: Duration,
+    dump_dir: Option<PathBuf>,
+}
+
+impl Watcher {
+    fn run(&self, shared: &Shared) {
+        let poll_interval = (self.timeout / 4).max(Duration::from_millis(10));
+        let mut heartbeats = shared.heartbeats.load(Ordering::Acquire);
+        let mut last_heartbeat = Instant::now();
+        let mut hung = false;
+
+        loop {
+            let stopped = shared.stopped.lock().unwrap();
+            let (stopped, _) = shared
+                .stop
+                .wait_timeout_while(stopped, poll_interval, |stopped| !*stopped)
+                .unwrap();
+            if *stopped {
+                return;
+            }
+            drop(stopped);
+
+            let latest = shared.heartbeats.load(Ordering::Acquire);
+            if latest != heartbeats || shared.paused.load(Ordering::Relaxed) {
+                if hung {
+                    info!(
+                        "main loop recovered after {:.1}s without a heartbeat",
+                        last_heartbeat.elapsed().as_secs_f64()
+                    );
+                    hung = false;
+                }
+                heartbeats = latest;
+                last_heartbeat = Instant::now();
+                continue;
+            }
+
+            let stalled = last_heartbeat.elapsed();
+            if !hung && stalled >= self.timeout {
+                hung = true;
+                if !debug::is_debugger_present() {
+                    self.report(stalled);
+                }
+            }
+        }
+    }
+
+    fn report(&self, stalled: Duration) {
+        let mut stacks = capture_stacks();
+        // The hung thread first, since it's the one anybody reading the report wants.
+        stacks.sort_by_key(|stack| stack.id != self.watched);
+
+        let mut report = format!(
+            "no heartbeat for {:.1}s, the main loop may be hung",
+            stalled.as_secs_f64()
+        );
+        for stack in &stacks {
+            stack.write(&mut report, stack.id == self.watched);
+        }
+
+        let Some(dir) = &self.dump_dir else {
+            error!("{report}");
+            return;
+        };
+
+        let now = Timestamp::from_system_time(SystemTime::now());
+        let path = dir.join(format!(
+            "hang-{:04}{:02}{:02}T{:02}{:02}{:02}Z.dmp",
+            now.year, now.month, now.day, now.hour, now.minute, now.second
+        ));
+        let written = std::fs::create_dir_all(dir)
+            .map_err(|err| {
+                Error::new(format!(
+                    "failed to create hang dump directory {}",
+                    dir.display()
+                ))
+                .with_source(err)
+            })
+            .and_then(|()| minidump::write(&path));
+        match written {
+            Ok(()) => error!(dump = %path.display(), "{report}"),
+            Err(err) => {
+                error!("{report}");
+                warn!(
+                    error = &err as &dyn std::error::Error,
+                    "failed to write hang dump"
+                );
+            }
+        }
+    }
+}
+
+/// One thread's return addresses, innermost first.
+struct ThreadStack {
+    id: u32,
+    name: Option<String>,
+    frames: [u64; MAX_FRAMES],
+    len: usize,
+}
+
+impl ThreadStack {
+    fn write(&self, out: &mut String, watched: bool) {
+        _ = write!(out, "\n\nthread {}", self.id);
+        if let Some(name) = &self.name {
+            _ = write!(out, " '{name}'");
+        }
+        if watched {
+            out.push_str(" (watched)");
+        }
+        out.push(':');
+        if self.len == 0 {
+            out.push_str("\n  <stack unavailable>");
+        }
+        for (i, address) in self.frames[..self.len].iter().enumerate() {
+            _ = write!(out, "\n  {i:>2}: ");
+            match module_name(*address) {
+                Some((name, base)) => _ = write!(out, "{name}+0x{:x}", address - base),
+                None => _ = write!(out, "0x{address:016x}"),
+            }
+        }
+    }
+}
+
+/// Walks the stack of every thread in the process other than the calling one.
+fn capture_stacks() -> Vec<ThreadStack> {
+    let process = unsafe { GetCurrentProcessId() };
+    let current = unsafe { GetCurrentThreadId() };
+    let snapshot = unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Vec::new();
+    }
+
+    let mut ids = Vec::new();
+    let mut entry: THREADENTRY32 = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+    let mut more = unsafe { Thread32First(snapshot, &mut entry) } != 0;
+    while more {
+        if entry.th32OwnerProcessID == process && entry.th32ThreadID != current {
+            ids.push(entry.th32ThreadID);
+        }
+        more = unsafe { Thread32Next(snapshot, &mut entry) } != 0;
+    }
+    unsafe { CloseHandle(snapshot) };
+
+    ids.into_iter()
+        .filter_map(|id| {
+            let thread = unsafe {
+                OpenThread(
+                    THREAD_SUSPEND_RESUME | THREAD_GET_CONTEXT | THREAD_QUERY_LIMITED_INFORMATION,
+                    0,
+                    id,
+                )
+            };
+            if thread == 0 {
+                return None;
+            }
+
+            let mut stack = ThreadStack {
+                id,
+                name: crate::thread::name_of(thread),
+                frames: [0; MAX_FRAMES],
+                len: 0,
+            };
+            // The thread may be holding the heap lock, so nothing is allocated while it's
+            // suspended.
+            if unsafe { SuspendThread(thread) } != u32::MAX {
+                stack.len = unsafe { walk(thread, &mut stack.frames) };
+                unsafe { ResumeThread(thread) };
+            }
+            unsafe { CloseHandle(thread) };
+
+            Some(stack)
+        })
+        .collect()
+}
+
+/// Unwinds a suspended thread with the unwind tables every x64 module carries, the same way the
+/// OS does for exceptions. Returns the number of frames written.
+#[cfg(target_arch = "x86_64")]
+unsafe fn walk(thread: HANDLE, frames: &mut [u64; MAX_FRAMES]) -> usize {
+    use windows_sys::Win32::System::Diagnostics::Debug::{
+        GetThreadContext, RtlLookupFunctionEntry, RtlVirtualUnwind, CONTEXT, CONTEXT_FULL_AMD64,
+        UNW_FLAG_NHANDLER,
+    };
+
+    #[repr(C, align(16))]
+    struct AlignedContext(CONTEXT);
+
+    let mut context: AlignedContext = std::mem::zeroed();
+    let context = &mut context.0;
+    context.ContextFlags = CONTEXT_FULL_AMD64;
+    if GetThreadContext(thread, context) == 0 {
+        return 0;
+    }
+
+    let mut len = 0;
+    while len < frames.len() && context.Rip != 0 && context.Rsp != 0 {
+        frames[len] = context.Rip;
+        len += 1;
+
+        let mut image_base = 0;
+        let function = RtlLookupFunctionEntry(context.Rip, &mut image_base, std::ptr::null_mut());
+        if function.is_null() {
+            // A leaf function: the return address is on top of the stack.
+            context.Rip = *(context.Rsp as *const u64);
+            context.Rsp += 8;
+            continue;
+        }
+
+        let mut handler_data = std::ptr::null_mut();
+        let mut establisher_frame = 0;
+        RtlVirtualUnwind(
+            UNW_FLAG_NHANDLER,
+            image_base,
+            context.Rip,
+            function,
+            context,
+            &mut handler_data,
+            &mut establisher_frame,
+            std::ptr::null_mut(),
+        );
+    }
+
+    len
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+unsafe fn walk(_thread: HANDLE, _frames: &mut [u64; MAX_FRAMES]) -> usize {
+    0
+}
+
+/// The file name and base address of the module containing `address`.
+fn module_name(address: u64) -> Option<(String, u64)> {
+    let mut module = 0;
+    if unsafe {
+        GetModuleHandleExW(
+            GET_MODULE_HANDLE_EX_FLAG_FROM_ADDRESS | GET_MODULE_HANDLE_EX_FLAG_UNCHANGED_REFCOUNT,
+            address as usize as *const u16,
+            &mut module,
+        )
+    } == 0
+    {
+        return None;
+    }
+
+    let mut path = [0u16; 260];
+    let len = unsafe { GetModuleFileNameW(module, path.as_mut_ptr(), path.len() as u32) } as usize;
+    let path = PathBuf::from(wide::from_wide_os(&path[..len]));
+    let name = path.file_name()?.to_string_lossy().into_owned();
+
+    Some((name, module as u64))
+}
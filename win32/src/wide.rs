@@ -0,0 +1,363 @@
+//! UTF-16 strings for the wide (`W`) Win32 APIs. [`WString`] and [`WStr`] are the owned and
+//! borrowed NUL-terminated strings passed in, [`StackWString`] builds short ones without
+//! allocating, and the `from_wide` functions decode what comes back out.
+//!
+//! ```
+//! use win32::wide::{ToWide, WStr};
+//!
+//! let title = "Galleon".to_wide();
+//! assert_eq!(title.as_slice_with_nul().last(), Some(&0));
+//!
+//! let buffer = [u16::from(b'h'), u16::from(b'i'), 0, 0, 0];
+//! let name = WStr::from_slice_until_nul(&buffer).unwrap();
+//! assert_eq!(name.to_string_lossy(), "hi");
+//! ```
+
+use std::{
+    borrow::Borrow,
+    ffi::{OsStr, OsString},
+    fmt::{Debug, Display},
+    ops::Deref,
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    path::Path,
+};
+
+/// A borrowed NUL-terminated UTF-16 string, the wide counterpart of [`CStr`](std::ffi::CStr).
+/// The contents needn't be valid UTF-16, since Win32 doesn't promise that either.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(transparent)]
+pub struct WStr {
+    // note: includes the terminator, and holds no other NUL.
+    units: [u16],
+}
+
+impl WStr {
+    /// Borrows a string returned by Win32.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a NUL-terminated string that stays valid and unchanged for `'a`.
+    pub unsafe fn from_ptr<'a>(ptr: *const u16) -> &'a WStr {
+        let mut len = 0;
+        while *ptr.add(len) != 0 {
+            len += 1;
+        }
+        Self::from_slice_with_nul_unchecked(std::slice::from_raw_parts(ptr, len + 1))
+    }
+
+    /// The string up to the first NUL in `units`, e.g. a fixed-size buffer in a Win32 struct.
+    /// `None` if there's no NUL.
+    pub fn from_slice_until_nul(units: &[u16]) -> Option<&WStr> {
+        let len = units.iter().position(|unit| *unit == 0)?;
+        Some(unsafe { Self::from_slice_with_nul_unchecked(&units[..=len]) })
+    }
+
+    /// # Safety
+    ///
+    /// `units` must end with its only NUL.
+    unsafe fn from_slice_with_nul_unchecked(units: &[u16]) -> &WStr {
+        &*(units as *const [u16] as *const WStr)
+    }
+
+    /// A pointer for `PCWSTR` parameters.
+    pub fn as_ptr(&self) -> *const u16 {
+        self.units.as_ptr()
+    }
+
+    /// The code units without the terminator.
+    pub fn as_slice(&self) -> &[u16] {
+        &self.units[..self.units.len() - 1]
+    }
+
+    pub fn as_slice_with_nul(&self) -> &[u16] {
+        &self.units
+    }
+
+    /// The length in code units, not counting the terminator.
+    pub fn len(&self) -> usize {
+        self.units.len() - 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decodes the string, replacing unpaired surrogates with `U+FFFD`.
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(self.as_slice())
+    }
+
+    /// Decodes the string exactly, e.g. for a path that may not be valid UTF-16.
+    pub fn to_os_string(&self) -> OsString {
+        OsString::from_wide(self.as_slice())
+    }
+
+    pub fn to_wstring(&self) -> WString {
+        WString {
+            units: self.units.to_vec(),
+        }
+    }
+}
+
+impl Display for WStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl Debug for WStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.to_string_lossy(), f)
+    }
+}
+
+impl AsRef<WStr> for WStr {
+    fn as_ref(&self) -> &WStr {
+        self
+    }
+}
+
+impl ToOwned for WStr {
+    type Owned = WString;
+
+    fn to_owned(&self) -> WString {
+        self.to_wstring()
+    }
+}
+
+/// An owned NUL-terminated UTF-16 string. Build one with [`ToWide::to_wide`], `From`, or
+/// [`wstr!`](crate::wstr) to format one. An interior NUL ends the string, as it would for any
+/// Win32 API given it.
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WString {
+    units: Vec<u16>,
+}
+
+impl WString {
+    /// An empty string, which is still a valid pointer to pass.
+    pub fn new() -> Self {
+        Self { units: vec![0] }
+    }
+
+    /// Takes code units from a buffer filled by Win32, up to the first NUL if there is one.
+    pub fn from_vec(mut units: Vec<u16>) -> Self {
+        let len = units
+            .iter()
+            .position(|unit| *unit == 0)
+            .unwrap_or(units.len());
+        units.truncate(len);
+        units.push(0);
+        Self { units }
+    }
+
+    fn from_units<I: IntoIterator<Item = u16>>(units: I) -> Self {
+        let units = units.into_iter();
+        let mut wide = Vec::with_capacity(units.size_hint().0 + 1);
+        wide.extend(units.take_while(|unit| *unit != 0));
+        wide.push(0);
+        Self { units: wide }
+    }
+
+    pub fn as_wstr(&self) -> &WStr {
+        self
+    }
+
+    /// A pointer for `PWSTR` parameters, for the APIs that want a mutable string even when they
+    /// don't change it.
+    pub fn as_mut_ptr(&mut self) -> *mut u16 {
+        self.units.as_mut_ptr()
+    }
+
+    /// The code units with the terminator.
+    pub fn into_vec(self) -> Vec<u16> {
+        self.units
+    }
+}
+
+impl Default for WString {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for WString {
+    type Target = WStr;
+
+    fn deref(&self) -> &WStr {
+        unsafe { WStr::from_slice_with_nul_unchecked(&self.units) }
+    }
+}
+
+impl AsRef<WStr> for WString {
+    fn as_ref(&self) -> &WStr {
+        self
+    }
+}
+
+impl Borrow<WStr> for WString {
+    fn borrow(&self) -> &WStr {
+        self
+    }
+}
+
+impl Display for WString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&**self, f)
+    }
+}
+
+impl Debug for WString {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+impl From<&str> for WString {
+    fn from(s: &str) -> Self {
+        Self::from_units(s.encode_utf16())
+    }
+}
+
+impl From<String> for WString {
+    fn from(s: String) -> Self {
+        Self::from(s.as_str())
+    }
+}
+
+impl From<&OsStr> for WString {
+    fn from(s: &OsStr) -> Self {
+        Self::from_units(s.encode_wide())
+    }
+}
+
+impl From<&Path> for WString {
+    fn from(path: &Path) -> Self {
+        Self::from(path.as_os_str())
+    }
+}
+
+impl From<&WStr> for WString {
+    fn from(s: &WStr) -> Self {
+        s.to_wstring()
+    }
+}
+
+/// Encodes a string as a [`WString`]: `"text".to_wide()`, `path.to_wide()`.
+pub trait ToWide {
+    fn to_wide(&self) -> WString;
+}
+
+impl ToWide for str {
+    fn to_wide(&self) -> WString {
+        WString::from(self)
+    }
+}
+
+impl ToWide for OsStr {
+    fn to_wide(&self) -> WString {
+        WString::from(self)
+    }
+}
+
+impl ToWide for Path {
+    fn to_wide(&self) -> WString {
+        WString::from(self)
+    }
+}
+
+/// A NUL-terminated UTF-16 string kept on the stack when it fits in `N` code units, terminator
+/// included, and on the heap otherwise. For the short strings passed on hot paths, such as
+/// window titles updated every frame:
+///
+/// ```
+/// use win32::wide::StackWString;
+///
+/// let title = StackWString::<64>::new("Galleon - 60 fps");
+/// assert!(title.is_inline());
+/// assert_eq!(title.len(), 16);
+/// ```
+pub struct StackWString<const N: usize = 128> {
+    storage: Storage<N>,
+}
+
+enum Storage<const N: usize> {
+    Inline { units: [u16; N], len: usize },
+    Heap(WString),
+}
+
+impl<const N: usize> StackWString<N> {
+    pub fn new(s: &str) -> Self {
+        if N == 0 {
+            return Self {
+                storage: Storage::Heap(WString::from(s)),
+            };
+        }
+
+        let mut units = [0; N];
+        let mut len = 0;
+        for unit in s.encode_utf16().take_while(|unit| *unit != 0) {
+            // Leave room for the terminator.
+            if len + 1 >= N {
+                return Self {
+                    storage: Storage::Heap(WString::from(s)),
+                };
+            }
+            units[len] = unit;
+            len += 1;
+        }
+
+        Self {
+            storage: Storage::Inline { units, len },
+        }
+    }
+
+    /// Whether the string fit in the stack buffer.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.storage, Storage::Inline { .. })
+    }
+}
+
+impl<const N: usize> Deref for StackWString<N> {
+    type Target = WStr;
+
+    fn deref(&self) -> &WStr {
+        match &self.storage {
+            Storage::Inline { units, len } => unsafe {
+                WStr::from_slice_with_nul_unchecked(&units[..=*len])
+            },
+            Storage::Heap(wide) => wide,
+        }
+    }
+}
+
+impl<const N: usize> AsRef<WStr> for StackWString<N> {
+    fn as_ref(&self) -> &WStr {
+        self
+    }
+}
+
+impl<const N: usize> Debug for StackWString<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// Decodes a buffer filled by Win32 up to the first NUL, or all of it if there's none, replacing
+/// unpaired surrogates with `U+FFFD`.
+pub fn from_wide_lossy(units: &[u16]) -> String {
+    String::from_utf16_lossy(until_nul(units))
+}
+
+/// Like [`from_wide_lossy`], but exact, for paths and other strings that may not be valid
+/// UTF-16.
+pub fn from_wide_os(units: &[u16]) -> OsString {
+    OsString::from_wide(until_nul(units))
+}
+
+fn until_nul(units: &[u16]) -> &[u16] {
+    let len = units
+        .iter()
+        .position(|unit| *unit == 0)
+        .unwrap_or(units.len());
+    &units[..len]
+}
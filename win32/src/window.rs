@@ -0,0 +1,1471 @@
+//! Top-level windows. A window belongs to the thread that created it and only receives messages
+//! while that thread pumps them. Renderers reach it through the [`Surface`] trait.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt::Display,
+    marker::PhantomData,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use common::{
+    error::{Error, ErrorKind},
+    surface::{HasWindowHandle, RawWindowHandle, Surface},
+};
+use windows_sys::Win32::{
+    Foundation::{GetLastError, HWND, LPARAM, LRESULT, POINT, RECT, WPARAM},
+    Graphics::Gdi::{MonitorFromWindow, ScreenToClient, MONITOR_DEFAULTTONEAREST},
+    System::LibraryLoader::GetModuleHandleW,
+    UI::{
+        HiDpi::{
+            AdjustWindowRectExForDpi, GetDpiForWindow, SetProcessDpiAwarenessContext,
+            DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        },
+        Input::{
+            Ime::ISC_SHOWUICOMPOSITIONWINDOW,
+            KeyboardAndMouse::{ReleaseCapture, SetCapture},
+        },
+        Shell::HDROP,
+        WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect, GetCursorPos,
+            GetWindowLongPtrW, GetWindowPlacement, GetWindowRect, GetWindowTextLengthW,
+            GetWindowTextW, IsIconic, IsWindowVisible, LoadCursorW, RegisterClassExW, SendMessageW,
+            SetCursor, SetWindowLongPtrW, SetWindowPlacement, SetWindowPos, SetWindowTextW,
+            ShowWindow, CREATESTRUCTW, CS_HREDRAW, CS_OWNDC, CS_VREDRAW, CW_USEDEFAULT,
+            GWLP_HINSTANCE, GWLP_USERDATA, GWL_STYLE, HCURSOR, HTCLIENT, HWND_TOP, ICON_BIG,
+            ICON_SMALL, IDC_ARROW, MINMAXINFO, SIZE_MINIMIZED, SWP_FRAMECHANGED, SWP_NOACTIVATE,
+            SWP_NOMOVE, SWP_NOOWNERZORDER, SWP_NOSIZE, SWP_NOZORDER, SW_HIDE, SW_MINIMIZE,
+            SW_RESTORE, SW_SHOW, SW_SHOWMAXIMIZED, SW_SHOWMINIMIZED, SW_SHOWNORMAL, UNICODE_NOCHAR,
+            WINDOWPLACEMENT, WINDOW_EX_STYLE, WINDOW_STYLE, WM_ACTIVATEAPP, WM_CHAR, WM_CLOSE,
+            WM_DEVICECHANGE, WM_DPICHANGED, WM_DROPFILES, WM_GETMINMAXINFO, WM_IME_COMPOSITION,
+            WM_IME_ENDCOMPOSITION, WM_IME_SETCONTEXT, WM_IME_STARTCOMPOSITION, WM_INPUT,
+            WM_INPUT_DEVICE_CHANGE, WM_KEYDOWN, WM_KEYUP, WM_KILLFOCUS, WM_LBUTTONDOWN,
+            WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP, WM_MOUSEHWHEEL, WM_MOUSEMOVE,
+            WM_MOUSEWHEEL, WM_MOVE, WM_NCCREATE, WM_NCDESTROY, WM_RBUTTONDOWN, WM_RBUTTONUP,
+            WM_SETCURSOR, WM_SETFOCUS, WM_SETICON, WM_SETTINGCHANGE, WM_SIZE, WM_SYSCHAR,
+            WM_SYSKEYDOWN, WM_SYSKEYUP, WM_UNICHAR, WM_XBUTTONDOWN, WM_XBUTTONUP, WNDCLASSEXW,
+            WPF_RESTORETOMAXIMIZED, WS_CAPTION, WS_EX_APPWINDOW, WS_MINIMIZEBOX, WS_OVERLAPPED,
+            WS_OVERLAPPEDWINDOW, WS_POPUP, WS_SYSMENU,
+        },
+    },
+};
+
+use crate::{
+    cursor::{self, Cursor},
+    device::{self, DeviceKind, DeviceNotifications},
+    error::{last_error, win32_error},
+    event_loop::Event,
+    file_drop, gamepad,
+    icon::Icon,
+    keyboard::{self, KeyEvent, KeyState, Modifiers},
+    monitor::{self, DisplayMode, Monitor},
+    mouse::{self, MouseButton, MouseButtonEvent, WheelDelta},
+    spy::MessageSpy,
+    text_input::{self, CharDecoder, ImeEvent},
+    theme::{self, Theme},
+    wstr,
+};
+
+const ERROR_CLASS_ALREADY_EXISTS: u32 = 1410;
+/// The DPI at which the scale factor is 1.
+pub(crate) const BASE_DPI: u32 = 96;
+
+/// A size in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PhysicalSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PhysicalSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn to_logical(&self, scale_factor: f64) -> LogicalSize {
+        LogicalSize::new(
+            f64::from(self.width) / scale_factor,
+            f64::from(self.height) / scale_factor,
+        )
+    }
+}
+
+/// A size in logical pixels, which stay the same apparent size on screen whatever the monitor's
+/// DPI. Physical pixels are logical pixels times the scale factor.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LogicalSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl LogicalSize {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    pub fn to_physical(&self, scale_factor: f64) -> PhysicalSize {
+        PhysicalSize::new(
+            (self.width * scale_factor).round() as u32,
+            (self.height * scale_factor).round() as u32,
+        )
+    }
+}
+
+/// A position in physical pixels, in screen or client coordinates depending on where it's from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PhysicalPosition {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl PhysicalPosition {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { x, y }
+    }
+}
+
+/// A rectangle in physical pixels, in screen coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct PhysicalRect {
+    pub position: PhysicalPosition,
+    pub size: PhysicalSize,
+}
+
+impl PhysicalRect {
+    pub fn new(position: PhysicalPosition, size: PhysicalSize) -> Self {
+        Self { position, size }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.position.x + self.size.width as i32
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.position.y + self.size.height as i32
+    }
+}
+
+/// A window's restored bounds and whether it's maximized, for saving on exit and restoring on the
+/// next run. It round-trips through a string like `"100,80,1280,720,maximized"`.
+///
+/// note: the bounds are in workspace coordinates, which are offset by any taskbar docked at the top
+/// or left of the primary monitor, so they're only meaningful to `Window::set_placement`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WindowPlacement {
+    pub bounds: PhysicalRect,
+    pub maximized: bool,
+}
+
+impl Display for WindowPlacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let PhysicalRect { position, size } = self.bounds;
+        write!(
+            f,
+            "{},{},{},{}",
+            position.x, position.y, size.width, size.height
+        )?;
+        if self.maximized {
+            write!(f, ",maximized")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for WindowPlacement {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            Error::new(format!(
+                "invalid window placement {s:?}, expected \"x,y,width,height\""
+            ))
+            .with_kind(ErrorKind::Parse)
+        };
+
+        let mut parts = s.split(',').map(str::trim);
+        let mut next = || parts.next().ok_or_else(invalid);
+        let x = next()?.parse().map_err(|_| invalid())?;
+        let y = next()?.parse().map_err(|_| invalid())?;
+        let width = next()?.parse().map_err(|_| invalid())?;
+        let height = next()?.parse().map_err(|_| invalid())?;
+        let maximized = match parts.next() {
+            None => false,
+            Some("maximized") => true,
+            Some(_) => return Err(invalid()),
+        };
+        if parts.next().is_some() {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            bounds: PhysicalRect::new(
+                PhysicalPosition::new(x, y),
+                PhysicalSize::new(width, height),
+            ),
+            maximized,
+        })
+    }
+}
+
+/// How a window fills the screen. See [`Window::set_fullscreen`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Fullscreen {
+    #[default]
+    Windowed,
+    /// A borderless window covering its monitor, at the monitor's current resolution.
+    Borderless,
+    /// A borderless window covering its monitor, with the monitor switched to the window's
+    /// windowed client size.
+    Exclusive,
+}
+
+pub struct WindowBuilder {
+    title: String,
+    size: LogicalSize,
+    position: Option<PhysicalPosition>,
+    resizable: bool,
+    visible: bool,
+    raw_mouse_input: bool,
+    accept_files: bool,
+    theme: Option<Theme>,
+    spy_messages: bool,
+}
+
+impl WindowBuilder {
+    fn new() -> Self {
+        Self {
+            title: "Galleon".to_string(),
+            size: LogicalSize::new(1280.0, 720.0),
+            position: None,
+            resizable: true,
+            visible: true,
+            raw_mouse_input: false,
+            accept_files: false,
+            theme: None,
+            spy_messages: false,
+        }
+    }
+
+    pub fn title<S: Into<String>>(self, title: S) -> Self {
+        Self {
+            title: title.into(),
+            ..self
+        }
+    }
+
+    /// The size of the client area in logical pixels, excluding the title bar and borders. It's
+    /// scaled by the DPI of the monitor the window opens on. Defaults to 1280x720.
+    pub fn size(self, width: u32, height: u32) -> Self {
+        Self {
+            size: LogicalSize::new(f64::from(width), f64::from(height)),
+            ..self
+        }
+    }
+
+    /// Where the top-left corner of the window frame goes on screen. Left to the system if unset.
+    pub fn position(self, x: i32, y: i32) -> Self {
+        Self {
+            position: Some(PhysicalPosition::new(x, y)),
+            ..self
+        }
+    }
+
+    /// Whether the user can resize and maximize the window. Defaults to true.
+    pub fn resizable(self, resizable: bool) -> Self {
+        Self { resizable, ..self }
+    }
+
+    /// Whether the window is shown as soon as it's created. Defaults to true.
+    pub fn visible(self, visible: bool) -> Self {
+        Self { visible, ..self }
+    }
+
+    /// Whether the window gets unaccelerated `MouseMotion` events. Defaults to false. See
+    /// [`Window::set_raw_mouse_input`].
+    pub fn raw_mouse_input(self, raw_mouse_input: bool) -> Self {
+        Self {
+            raw_mouse_input,
+            ..self
+        }
+    }
+
+    /// Whether files dragged from Explorer can be dropped on the window. Defaults to false. See
+    /// [`Window::set_accept_files`].
+    pub fn accept_files(self, accept_files: bool) -> Self {
+        Self {
+            accept_files,
+            ..self
+        }
+    }
+
+    /// The theme the title bar is drawn in. `None`, the default, follows the system. See
+    /// [`Window::set_theme`].
+    pub fn theme(self, theme: Option<Theme>) -> Self {
+        Self { theme, ..self }
+    }
+
+    /// Whether every message the window receives is logged at TRACE, from its creation on.
+    /// Defaults to false. See [`Window::set_spy_messages`].
+    pub fn spy_messages(self, spy_messages: bool) -> Self {
+        Self {
+            spy_messages,
+            ..self
+        }
+    }
+
+    pub fn build(self) -> Result<Window, Error> {
+        register_class()?;
+
+        let style = if self.resizable {
+            WS_OVERLAPPEDWINDOW
+        } else {
+            WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU | WS_MINIMIZEBOX
+        };
+        let ex_style = WS_EX_APPWINDOW;
+        let (width, height) = outer_size(self.size.to_physical(1.0), style, ex_style, BASE_DPI)?;
+        let (x, y) = self
+            .position
+            .map_or((CW_USEDEFAULT, CW_USEDEFAULT), |position| {
+                (position.x, position.y)
+            });
+
+        let state = Box::new(WindowState {
+            close_requested: Cell::new(false),
+            events: RefCell::new(VecDeque::new()),
+            held_keys: RefCell::new(Vec::new()),
+            raw_mouse_input: Cell::new(false),
+            text_input: Cell::new(false),
+            chars: RefCell::new(CharDecoder::default()),
+            ime_position: Cell::new(PhysicalPosition::default()),
+            focused: Cell::new(false),
+            cursor: RefCell::new(None),
+            cursor_visible: Cell::new(true),
+            cursor_confined: Cell::new(false),
+            maximize_on_show: Cell::new(false),
+            fullscreen: Cell::new(Fullscreen::Windowed),
+            windowed: Cell::new(None),
+            exclusive: RefCell::new(None),
+            exclusive_mode: Cell::new(None),
+            theme: Cell::new(self.theme),
+            current_theme: Cell::new(self.theme.unwrap_or_else(theme::system_theme)),
+            icon: RefCell::new(None),
+            min_inner_size: Cell::new(None),
+            max_inner_size: Cell::new(None),
+            hid_arrived: Cell::new(false),
+            spy: RefCell::new(self.spy_messages.then(MessageSpy::new)),
+        });
+        let class_name = wstr!("{CLASS_NAME}");
+        let title = wstr!("{}", self.title);
+        let hwnd = unsafe {
+            CreateWindowExW(
+                ex_style,
+                class_name.as_ptr(),
+                title.as_ptr(),
+                style,
+                x,
+                y,
+                width,
+                height,
+                0,
+                0,
+                GetModuleHandleW(std::ptr::null()),
+                &*state as *const WindowState as *const _,
+            )
+        };
+        if hwnd == 0 {
+            return Err(last_error("failed to create window"));
+        }
+
+        let devices = match DeviceNotifications::register(hwnd) {
+            Ok(devices) => devices,
+            Err(err) => {
+                unsafe { DestroyWindow(hwnd) };
+                return Err(err);
+            }
+        };
+        let window = Window {
+            hwnd,
+            state,
+            _devices: devices,
+            _not_send: PhantomData,
+        };
+        text_input::set_ime_enabled(hwnd, false);
+        // note: which monitor the window lands on, and so its DPI, is only known once it exists.
+        let scale_factor = window.scale_factor();
+        if scale_factor != 1.0 {
+            let size = self.size.to_physical(scale_factor);
+            window.set_inner_size(size.width, size.height)?;
+        }
+        if self.raw_mouse_input {
+            window.set_raw_mouse_input(true)?;
+        }
+        if self.accept_files {
+            window.set_accept_files(true);
+        }
+        theme::set_title_bar(hwnd, window.theme());
+        window.set_visible(self.visible);
+
+        Ok(window)
+    }
+}
+
+/// A top-level window, destroyed when dropped.
+pub struct Window {
+    hwnd: HWND,
+    state: Box<WindowState>,
+    _devices: DeviceNotifications,
+    // Windows belong to the thread that created them.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Window {
+    pub fn builder() -> WindowBuilder {
+        WindowBuilder::new()
+    }
+
+    pub fn hwnd(&self) -> HWND {
+        self.hwnd
+    }
+
+    pub fn title(&self) -> String {
+        let len = unsafe { GetWindowTextLengthW(self.hwnd) };
+        let mut buffer = vec![0u16; len as usize + 1];
+        let len = unsafe { GetWindowTextW(self.hwnd, buffer.as_mut_ptr(), buffer.len() as i32) };
+        String::from_utf16_lossy(&buffer[..len as usize])
+    }
+
+    /// Changes the title shown in the title bar, the taskbar and Alt+Tab, e.g. to mark unsaved
+    /// changes.
+    pub fn set_title(&self, title: &str) {
+        let title = wstr!("{title}");
+        unsafe { SetWindowTextW(self.hwnd, title.as_ptr()) };
+    }
+
+    /// The icon shown in the title bar, the taskbar and Alt+Tab. `None` goes back to the
+    /// default. The window keeps its own reference to the icon.
+    pub fn set_icon(&self, icon: Option<&Icon>) {
+        let (big, small) = icon.map_or((0, 0), |icon| (icon.big(), icon.small()));
+        unsafe { SendMessageW(self.hwnd, WM_SETICON, ICON_BIG as WPARAM, big) };
+        unsafe { SendMessageW(self.hwnd, WM_SETICON, ICON_SMALL as WPARAM, small) };
+        *self.state.icon.borrow_mut() = icon.cloned();
+    }
+
+    pub fn set_visible(&self, visible: bool) {
+        let command = match visible {
+            true if self.state.maximize_on_show.take() => SW_SHOWMAXIMIZED,
+            true => SW_SHOW,
+            false => SW_HIDE,
+        };
+        unsafe { ShowWindow(self.hwnd, command) };
+    }
+
+    pub fn is_visible(&self) -> bool {
+        unsafe { IsWindowVisible(self.hwnd) != 0 }
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        unsafe { IsIconic(self.hwnd) != 0 }
+    }
+
+    /// The ratio of physical to logical pixels for the monitor the window is on, e.g. 1.5 at
+    /// 150% scaling.
+    pub fn scale_factor(&self) -> f64 {
+        f64::from(unsafe { GetDpiForWindow(self.hwnd) }) / f64::from(BASE_DPI)
+    }
+
+    pub fn logical_inner_size(&self) -> LogicalSize {
+        self.inner_size().to_logical(self.scale_factor())
+    }
+
+    /// The size of the client area in physical pixels.
+    pub fn inner_size(&self) -> PhysicalSize {
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        unsafe { GetClientRect(self.hwnd, &mut rect) };
+        PhysicalSize::new(
+            (rect.right - rect.left).max(0) as u32,
+            (rect.bottom - rect.top).max(0) as u32,
+        )
+    }
+
+    /// Resizes the window so its client area is `width` by `height` physical pixels.
+    pub fn set_inner_size(&self, width: u32, height: u32) -> Result<(), Error> {
+        let (style, ex_style) = self.styles();
+        let dpi = unsafe { GetDpiForWindow(self.hwnd) };
+        let (width, height) = outer_size(PhysicalSize::new(width, height), style, ex_style, dpi)?;
+        if unsafe {
+            SetWindowPos(
+                self.hwnd,
+                0,
+                0,
+                0,
+                width,
+                height,
+                SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )
+        } == 0
+        {
+            return Err(last_error("failed to resize window"));
+        }
+
+        Ok(())
+    }
+
+    /// The smallest the user can make the client area, in physical pixels. `None`, the default,
+    /// leaves it to the system. The window grows now if it's smaller.
+    pub fn set_min_inner_size(&self, size: Option<PhysicalSize>) -> Result<(), Error> {
+        self.state.min_inner_size.set(size);
+        self.clamp_inner_size()
+    }
+
+    /// The largest the user can make the client area, in physical pixels, which also caps
+    /// maximizing. `None`, the default, leaves it to the system. The window shrinks now if it's
+    /// larger. Fullscreen windows ignore it.
+    pub fn set_max_inner_size(&self, size: Option<PhysicalSize>) -> Result<(), Error> {
+        self.state.max_inner_size.set(size);
+        self.clamp_inner_size()
+    }
+
+    /// The screen position of the top-left corner of the window frame.
+    pub fn outer_position(&self) -> PhysicalPosition {
+        self.outer_rect().position
+    }
+
+    /// The screen bounds of the window frame.
+    pub fn outer_rect(&self) -> PhysicalRect {
+        let mut rect = RECT {
+            left: 0,
+            top: 0,
+            right: 0,
+            bottom: 0,
+        };
+        unsafe { GetWindowRect(self.hwnd, &mut rect) };
+        PhysicalRect::new(
+            PhysicalPosition::new(rect.left, rect.top),
+            PhysicalSize::new(
+                (rect.right - rect.left).max(0) as u32,
+                (rect.bottom - rect.top).max(0) as u32,
+            ),
+        )
+    }
+
+    pub fn set_outer_position(&self, x: i32, y: i32) -> Result<(), Error> {
+        if unsafe {
+            SetWindowPos(
+                self.hwnd,
+                0,
+                x,
+                y,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            )
+        } == 0
+        {
+            return Err(last_error("failed to move window"));
+        }
+
+        Ok(())
+    }
+
+    /// The monitor that has the largest share of the window, or the nearest one if it's off
+    /// screen.
+    pub fn current_monitor(&self) -> Result<Monitor, Error> {
+        Monitor::from_handle(unsafe { MonitorFromWindow(self.hwnd, MONITOR_DEFAULTTONEAREST) })
+    }
+
+    /// Moves the window to the same place on `monitor`'s work area as it has on its current one,
+    /// keeping it inside the work area where it fits. Moving to a monitor with a different DPI
+    /// rescales the window, which is reported with `ScaleFactorChanged` and `Resized` events.
+    pub fn move_to_monitor(&self, monitor: &Monitor) -> Result<(), Error> {
+        let current = self.current_monitor()?.work_area();
+        let target = monitor.work_area();
+        let position = self.outer_position();
+        self.set_outer_position(
+            target.position.x + position.x - current.position.x,
+            target.position.y + position.y - current.position.y,
+        )?;
+
+        // note: the size is only final once the move has rescaled the window.
+        let outer = self.outer_rect();
+        let x = outer
+            .position
+            .x
+            .min(target.right() - outer.size.width as i32)
+            .max(target.position.x);
+        let y = outer
+            .position
+            .y
+            .min(target.bottom() - outer.size.height as i32)
+            .max(target.position.y);
+        if (x, y) != (outer.position.x, outer.position.y) {
+            self.set_outer_position(x, y)?;
+        }
+
+        Ok(())
+    }
+
+    /// Centers the window on `monitor`'s work area, rescaling it first if the monitor has a
+    /// different DPI.
+    pub fn center_on(&self, monitor: &Monitor) -> Result<(), Error> {
+        let target = monitor.work_area();
+        if self.current_monitor()?.handle() != monitor.handle() {
+            self.set_outer_position(target.position.x, target.position.y)?;
+        }
+
+        let size = self.outer_rect().size;
+        self.set_outer_position(
+            target.position.x + (target.size.width as i32 - size.width as i32) / 2,
+            target.position.y + (target.size.height as i32 - size.height as i32) / 2,
+        )
+    }
+
+    /// The window's restored bounds and maximized state, to save and restore with
+    /// `set_placement` on the next run.
+    pub fn placement(&self) -> Result<WindowPlacement, Error> {
+        let mut placement: WINDOWPLACEMENT = unsafe { std::mem::zeroed() };
+        placement.length = std::mem::size_of::<WINDOWPLACEMENT>() as u32;
+        if unsafe { GetWindowPlacement(self.hwnd, &mut placement) } == 0 {
+            return Err(last_error("failed to get window placement"));
+        }
+
+        let rect = placement.rcNormalPosition;
+        // note: a window minimized from maximized restores to maximized.
+        let maximized = placement.showCmd == SW_SHOWMAXIMIZED as u32
+            || (placement.showCmd == SW_SHOWMINIMIZED as u32
+                && placement.flags & WPF_RESTORETOMAXIMIZED != 0);
+
+        Ok(WindowPlacement {
+            bounds: PhysicalRect::new(
+                PhysicalPosition::new(rect.left, rect.top),
+                PhysicalSize::new(
+                    (rect.right - rect.left).max(0) as u32,
+                    (rect.bottom - rect.top).max(0) as u32,
+                ),
+            ),
+            maximized,
+        })
+    }
+
+    /// Restores a placement saved with `placement`. Bounds that would leave the window entirely
+    /// off screen, e.g. because the monitor it was on has gone, are moved back into view. A hidden
+    /// window stays hidden and is maximized when it's shown.
+    pub fn set_placement(&self, placement: &WindowPlacement) -> Result<(), Error> {
+        let visible = self.is_visible();
+        let show = match (visible, placement.maximized) {
+            (false, _) => SW_HIDE,
+            (true, true) => SW_SHOWMAXIMIZED,
+            (true, false) => SW_SHOWNORMAL,
+        };
+        let bounds = placement.bounds;
+        let raw = WINDOWPLACEMENT {
+            length: std::mem::size_of::<WINDOWPLACEMENT>() as u32,
+            flags: 0,
+            showCmd: show as u32,
+            ptMinPosition: POINT { x: -1, y: -1 },
+            ptMaxPosition: POINT { x: -1, y: -1 },
+            rcNormalPosition: RECT {
+                left: bounds.position.x,
+                top: bounds.position.y,
+                right: bounds.right(),
+                bottom: bounds.bottom(),
+            },
+        };
+        if unsafe { SetWindowPlacement(self.hwnd, &raw) } == 0 {
+            return Err(last_error("failed to set window placement"));
+        }
+        self.state
+            .maximize_on_show
+            .set(!visible && placement.maximized);
+
+        Ok(())
+    }
+
+    pub fn fullscreen(&self) -> Fullscreen {
+        self.state.fullscreen.get()
+    }
+
+    /// Switches between windowed and fullscreen. Going fullscreen covers the monitor the window
+    /// is on, and going back to windowed restores the style, bounds and maximized state the
+    /// window had before. Either way a `Resized` event follows.
+    ///
+    /// An exclusive display mode only holds while the app is in front: losing focus minimizes
+    /// the window and puts the monitor back, and restoring the window switches it again. The mode
+    /// is the one set with [`set_exclusive_mode`](Window::set_exclusive_mode), or else the
+    /// windowed client size.
+    pub fn set_fullscreen(&self, fullscreen: Fullscreen) -> Result<(), Error> {
+        let current = self.state.fullscreen.get();
+        if fullscreen == current {
+            return Ok(());
+        }
+
+        let windowed = match (current, self.state.windowed.get()) {
+            (Fullscreen::Borderless | Fullscreen::Exclusive, Some(windowed)) => windowed,
+            _ => {
+                let windowed = WindowedState {
+                    style: self.styles().0,
+                    placement: self.placement()?,
+                    inner_size: self.inner_size(),
+                };
+                self.state.windowed.set(Some(windowed));
+                windowed
+            }
+        };
+        if let Some(exclusive) = self.state.exclusive.take() {
+            monitor::restore_display_mode(&exclusive.device);
+        }
+
+        match fullscreen {
+            Fullscreen::Windowed => {
+                unsafe {
+                    SetWindowLongPtrW(self.hwnd, GWL_STYLE, windowed.style as isize);
+                    SetWindowPos(
+                        self.hwnd,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED,
+                    );
+                }
+                self.set_placement(&windowed.placement)?;
+                self.state.windowed.set(None);
+            }
+            Fullscreen::Borderless => cover_monitor(self.hwnd)?,
+            Fullscreen::Exclusive => {
+                let mode = self.state.exclusive_mode.get();
+                let size = mode.map_or(windowed.inner_size, |mode| mode.size);
+                let refresh_rate = mode.map(|mode| mode.refresh_rate);
+                let device = self.current_monitor()?.name().to_string();
+                monitor::set_display_mode(&device, size, refresh_rate)?;
+                *self.state.exclusive.borrow_mut() = Some(ExclusiveState {
+                    device,
+                    size,
+                    refresh_rate,
+                });
+                cover_monitor(self.hwnd)?;
+            }
+        }
+        self.state.fullscreen.set(fullscreen);
+
+        Ok(())
+    }
+
+    /// The display mode exclusive fullscreen switches the monitor to, e.g. from a list of the
+    /// monitor's [`modes`](Monitor::modes) in a video settings menu. `None`, the default, uses
+    /// the windowed client size at the driver's choice of refresh rate. Takes effect at once if
+    /// the window is already exclusive.
+    pub fn set_exclusive_mode(&self, mode: Option<DisplayMode>) -> Result<(), Error> {
+        self.state.exclusive_mode.set(mode);
+        if self.fullscreen() != Fullscreen::Exclusive {
+            return Ok(());
+        }
+
+        self.set_fullscreen(Fullscreen::Borderless)?;
+        self.set_fullscreen(Fullscreen::Exclusive)
+    }
+
+    pub fn exclusive_mode(&self) -> Option<DisplayMode> {
+        self.state.exclusive_mode.get()
+    }
+
+    /// Whether the user has asked to close the window, e.g. with the close button or Alt+F4. The
+    /// window stays open until it's dropped.
+    pub fn close_requested(&self) -> bool {
+        self.state.close_requested.get()
+    }
+
+    /// Keeps mouse events coming to this window while the cursor is outside it, e.g. for the
+    /// rest of a drag. Lasts until `release_capture`, or until another window takes the capture.
+    pub fn set_capture(&self) {
+        unsafe { SetCapture(self.hwnd) };
+    }
+
+    pub fn release_capture(&self) {
+        unsafe { ReleaseCapture() };
+    }
+
+    /// Turns unaccelerated `MouseMotion` events on or off, alongside the cursor-based events.
+    /// Raw input is registered per process, so enabling it on one window takes it from any other,
+    /// and disabling it turns it off for the whole process.
+    pub fn set_raw_mouse_input(&self, enabled: bool) -> Result<(), Error> {
+        mouse::register_raw_mouse(self.hwnd, enabled)?;
+        self.state.raw_mouse_input.set(enabled);
+        Ok(())
+    }
+
+    /// Turns file drops on or off. While they're on, files dragged onto the window from Explorer
+    /// arrive as a `FilesDropped` event.
+    pub fn set_accept_files(&self, accept: bool) {
+        file_drop::accept_files(self.hwnd, accept);
+    }
+
+    /// Turns text entry on or off. While it's on the window gets `Text` and `Ime` events and the
+    /// user's input method is active; while it's off, which is the default, keys are just keys.
+    /// Turn it on while e.g. a chat box has focus.
+    pub fn set_text_input(&self, enabled: bool) {
+        self.state.text_input.set(enabled);
+        text_input::set_ime_enabled(self.hwnd, enabled);
+    }
+
+    pub fn is_text_input(&self) -> bool {
+        self.state.text_input.get()
+    }
+
+    /// Where the IME shows its candidate list, in client coordinates. Set it to the text cursor.
+    pub fn set_ime_position(&self, x: i32, y: i32) {
+        let position = PhysicalPosition::new(x, y);
+        self.state.ime_position.set(position);
+        text_input::set_ime_position(self.hwnd, position);
+    }
+
+    /// The cursor shown over the client area. `None` goes back to the arrow.
+    pub fn set_cursor(&self, cursor: Option<&Cursor>) {
+        *self.state.cursor.borrow_mut() = cursor.cloned();
+        self.state.refresh_cursor(self.hwnd);
+    }
+
+    /// Hides or shows the cursor while it's over the client area.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.state.cursor_visible.set(visible);
+        self.state.refresh_cursor(self.hwnd);
+    }
+
+    /// Keeps the cursor inside the client area while the window has focus. It's released when
+    /// focus is lost and confined again when focus comes back.
+    pub fn set_cursor_confined(&self, confined: bool) -> Result<(), Error> {
+        self.state.cursor_confined.set(confined);
+        if !confined {
+            cursor::release();
+        } else if self.state.focused.get() {
+            cursor::confine(self.hwnd)?;
+        }
+
+        Ok(())
+    }
+
+    /// Moves the cursor to a position in client coordinates.
+    pub fn set_cursor_position(&self, x: i32, y: i32) -> Result<(), Error> {
+        cursor::set_position(self.hwnd, x, y)
+    }
+
+    /// Moves the cursor to the middle of the client area, e.g. each frame of a mouse-look
+    /// camera so it never reaches the edge.
+    pub fn center_cursor(&self) -> Result<(), Error> {
+        let size = self.inner_size();
+        self.set_cursor_position((size.width / 2) as i32, (size.height / 2) as i32)
+    }
+
+    /// The theme the window is drawn in: the one set with [`set_theme`](Window::set_theme), or
+    /// else the system's.
+    pub fn theme(&self) -> Theme {
+        self.state.current_theme.get()
+    }
+
+    /// Draws the title bar in `theme`, or with `None` goes back to following the system. A
+    /// `ThemeChanged` event follows if that changes the window's theme.
+    pub fn set_theme(&self, theme: Option<Theme>) {
+        self.state.theme.set(theme);
+        self.state.update_theme(self.hwnd);
+    }
+
+    /// Logs every message the window receives at TRACE, with the parameters of the ones
+    /// involved in focus, sizing and DPI decoded, to diagnose what unusual setups send. At most a
+    /// few hundred messages are logged a second.
+    pub fn set_spy_messages(&self, enabled: bool) {
+        let mut spy = self.state.spy.borrow_mut();
+        match (enabled, spy.is_some()) {
+            (true, false) => *spy = Some(MessageSpy::new()),
+            (false, true) => *spy = None,
+            _ => {}
+        }
+    }
+
+    pub fn is_spying_messages(&self) -> bool {
+        self.state.spy.borrow().is_some()
+    }
+
+    /// Whether a HID device has arrived since the last call, e.g. a gamepad being plugged in.
+    pub(crate) fn take_hid_arrived(&self) -> bool {
+        self.state.hid_arrived.take()
+    }
+
+    /// The oldest event the window procedure has queued since the last call.
+    pub(crate) fn next_event(&self) -> Option<Event> {
+        self.state.events.borrow_mut().pop_front()
+    }
+
+    fn styles(&self) -> (WINDOW_STYLE, WINDOW_EX_STYLE) {
+        styles(self.hwnd)
+    }
+
+    /// Resizes the window into its size limits, if it's outside them.
+    fn clamp_inner_size(&self) -> Result<(), Error> {
+        if self.fullscreen() != Fullscreen::Windowed || self.is_minimized() {
+            return Ok(());
+        }
+
+        let size = self.inner_size();
+        let clamped = self.state.clamp_inner_size(size);
+        if clamped == size {
+            return Ok(());
+        }
+        self.set_inner_size(clamped.width, clamped.height)
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        // note: the window procedure stops using the state in WM_NCDESTROY, which DestroyWindow
+        // sends before returning, so the state can be freed afterwards.
+        unsafe { DestroyWindow(self.hwnd) };
+        if let Some(exclusive) = self.state.exclusive.take() {
+            monitor::restore_display_mode(&exclusive.device);
+        }
+    }
+}
+
+impl HasWindowHandle for Window {
+    fn window_handle(&self) -> RawWindowHandle {
+        RawWindowHandle::Win32 {
+            hwnd: self.hwnd,
+            hinstance: unsafe { GetWindowLongPtrW(self.hwnd, GWLP_HINSTANCE) },
+        }
+    }
+}
+
+impl Surface for Window {
+    fn surface_size(&self) -> (u32, u32) {
+        let size = self.inner_size();
+        (size.width, size.height)
+    }
+
+    fn scale_factor(&self) -> f64 {
+        Window::scale_factor(self)
+    }
+}
+
+/// Per-window state the window procedure updates, reached through `GWLP_USERDATA`.
+struct WindowState {
+    close_requested: Cell<bool>,
+    events: RefCell<VecDeque<Event>>,
+    held_keys: RefCell<Vec<KeyEvent>>,
+    raw_mouse_input: Cell<bool>,
+    text_input: Cell<bool>,
+    chars: RefCell<CharDecoder>,
+    ime_position: Cell<PhysicalPosition>,
+    focused: Cell<bool>,
+    cursor: RefCell<Option<Cursor>>,
+    cursor_visible: Cell<bool>,
+    cursor_confined: Cell<bool>,
+    /// Set when a maximized placement is restored while the window is hidden.
+    maximize_on_show: Cell<bool>,
+    fullscreen: Cell<Fullscreen>,
+    /// How the window was before it went fullscreen.
+    windowed: Cell<Option<WindowedState>>,
+    /// The monitor and the mode it's switched to, while exclusive.
+    exclusive: RefCell<Option<ExclusiveState>>,
+    exclusive_mode: Cell<Option<DisplayMode>>,
+    /// The theme set by the app, if it doesn't follow the system's.
+    theme: Cell<Option<Theme>>,
+    current_theme: Cell<Theme>,
+    /// Kept alive while the window shows it.
+    icon: RefCell<Option<Icon>>,
+    min_inner_size: Cell<Option<PhysicalSize>>,
+    max_inner_size: Cell<Option<PhysicalSize>>,
+    hid_arrived: Cell<bool>,
+    spy: RefCell<Option<MessageSpy>>,
+}
+
+#[derive(Clone, Copy)]
+struct WindowedState {
+    style: WINDOW_STYLE,
+    placement: WindowPlacement,
+    inner_size: PhysicalSize,
+}
+
+#[derive(Clone)]
+struct ExclusiveState {
+    /// The monitor's device name.
+    device: String,
+    size: PhysicalSize,
+    refresh_rate: Option<u32>,
+}
+
+impl WindowState {
+    fn push(&self, event: Event) {
+        self.events.borrow_mut().push_back(event);
+    }
+
+    fn key(&self, event: KeyEvent) {
+        let mut held = self.held_keys.borrow_mut();
+        match event.state {
+            KeyState::Pressed => {
+                if !held.iter().any(|key| key.scancode == event.scancode) {
+                    held.push(event);
+                }
+            }
+            KeyState::Repeat => {}
+            KeyState::Released => held.retain(|key| key.scancode != event.scancode),
+        }
+        drop(held);
+
+        self.push(Event::Key(event));
+    }
+
+    /// The cursor handle to show over the client area.
+    fn current_cursor(&self) -> HCURSOR {
+        if !self.cursor_visible.get() {
+            return 0;
+        }
+
+        self.cursor
+            .borrow()
+            .as_ref()
+            .map_or_else(|| unsafe { LoadCursorW(0, IDC_ARROW) }, Cursor::hcursor)
+    }
+
+    /// Applies a cursor change right away rather than on the next mouse move.
+    fn refresh_cursor(&self, hwnd: HWND) {
+        if self.focused.get() && cursor_in_client(hwnd) {
+            unsafe { SetCursor(self.current_cursor()) };
+        }
+    }
+
+    /// Re-applies confinement after the client area moves or resizes.
+    fn reconfine(&self, hwnd: HWND) {
+        if self.cursor_confined.get() && self.focused.get() {
+            _ = cursor::confine(hwnd);
+        }
+    }
+
+    fn mouse_button(&self, button: MouseButton, pressed: bool, lparam: LPARAM) {
+        self.push(Event::MouseButton(MouseButtonEvent {
+            button,
+            pressed,
+            position: mouse::position(lparam),
+            modifiers: Modifiers::current(),
+        }));
+    }
+
+    /// Redraws the title bar and reports the change if the window's theme is no longer the one
+    /// it's drawn in.
+    fn update_theme(&self, hwnd: HWND) {
+        let theme = self.theme.get().unwrap_or_else(theme::system_theme);
+        if theme != self.current_theme.replace(theme) {
+            theme::set_title_bar(hwnd, theme);
+            self.push(Event::ThemeChanged(theme));
+        }
+    }
+
+    fn clamp_inner_size(&self, size: PhysicalSize) -> PhysicalSize {
+        let mut size = size;
+        if let Some(max) = self.max_inner_size.get() {
+            size = PhysicalSize::new(size.width.min(max.width), size.height.min(max.height));
+        }
+        if let Some(min) = self.min_inner_size.get() {
+            size = PhysicalSize::new(size.width.max(min.width), size.height.max(min.height));
+        }
+
+        size
+    }
+
+    /// Applies the size limits to the window frame the system is about to size or maximize.
+    fn limit_size(&self, hwnd: HWND, info: &mut MINMAXINFO) {
+        if self.fullscreen.get() != Fullscreen::Windowed {
+            return;
+        }
+
+        let (style, ex_style) = styles(hwnd);
+        let dpi = unsafe { GetDpiForWindow(hwnd) };
+        if let Some(min) = self.min_inner_size.get() {
+            if let Ok((width, height)) = outer_size(min, style, ex_style, dpi) {
+                info.ptMinTrackSize = POINT {
+                    x: width,
+                    y: height,
+                };
+            }
+        }
+        if let Some(max) = self.max_inner_size.get() {
+            if let Ok((width, height)) = outer_size(max, style, ex_style, dpi) {
+                info.ptMaxTrackSize = POINT {
+                    x: width,
+                    y: height,
+                };
+                info.ptMaxSize = POINT {
+                    x: info.ptMaxSize.x.min(width),
+                    y: info.ptMaxSize.y.min(height),
+                };
+            }
+        }
+    }
+
+    fn release_held_keys(&self) {
+        let held = std::mem::take(&mut *self.held_keys.borrow_mut());
+        for key in held {
+            self.push(Event::Key(KeyEvent {
+                state: KeyState::Released,
+                modifiers: Modifiers::default(),
+                ..key
+            }));
+        }
+    }
+}
+
+const CLASS_NAME: &str = "galleon_window";
+
+fn register_class() -> Result<(), Error> {
+    static REGISTERED: AtomicBool = AtomicBool::new(false);
+
+    if REGISTERED.load(Ordering::Acquire) {
+        return Ok(());
+    }
+
+    // note: this fails if the manifest already set an awareness, which is fine either way.
+    unsafe { SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2) };
+
+    let class_name = wstr!("{CLASS_NAME}");
+    let class = WNDCLASSEXW {
+        cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
+        style: CS_HREDRAW | CS_VREDRAW | CS_OWNDC,
+        lpfnWndProc: Some(window_proc),
+        cbClsExtra: 0,
+        cbWndExtra: 0,
+        hInstance: unsafe { GetModuleHandleW(std::ptr::null()) },
+        hIcon: 0,
+        hCursor: unsafe { LoadCursorW(0, IDC_ARROW) },
+        hbrBackground: 0,
+        lpszMenuName: std::ptr::null(),
+        lpszClassName: class_name.as_ptr(),
+        hIconSm: 0,
+    };
+    if unsafe { RegisterClassExW(&class) } == 0 {
+        let code = unsafe { GetLastError() };
+        if code != ERROR_CLASS_ALREADY_EXISTS {
+            return Err(win32_error("failed to register window class", code));
+        }
+    }
+    REGISTERED.store(true, Ordering::Release);
+
+    Ok(())
+}
+
+/// Makes the window a borderless popup covering its monitor.
+fn cover_monitor(hwnd: HWND) -> Result<(), Error> {
+    let style = unsafe { GetWindowLongPtrW(hwnd, GWL_STYLE) } as WINDOW_STYLE;
+    unsafe {
+        SetWindowLongPtrW(
+            hwnd,
+            GWL_STYLE,
+            (style & !WS_OVERLAPPEDWINDOW | WS_POPUP) as isize,
+        )
+    };
+
+    let bounds =
+        Monitor::from_handle(unsafe { MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST) })?
+            .bounds();
+    if unsafe {
+        SetWindowPos(
+            hwnd,
+            HWND_TOP,
+            bounds.position.x,
+            bounds.position.y,
+            bounds.size.width as i32,
+            bounds.size.height as i32,
+            SWP_FRAMECHANGED | SWP_NOOWNERZORDER,
+        )
+    } == 0
+    {
+        return Err(last_error("failed to make window fullscreen"));
+    }
+
+    Ok(())
+}
+
+fn styles(hwnd: HWND) -> (WINDOW_STYLE, WINDOW_EX_STYLE) {
+    use windows_sys::Win32::UI::WindowsAndMessaging::GWL_EXSTYLE;
+
+    unsafe {
+        (
+            GetWindowLongPtrW(hwnd, GWL_STYLE) as WINDOW_STYLE,
+            GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as WINDOW_EX_STYLE,
+        )
+    }
+}
+
+/// The outer window size that gives a client area of `size` at `dpi`.
+fn outer_size(
+    size: PhysicalSize,
+    style: WINDOW_STYLE,
+    ex_style: WINDOW_EX_STYLE,
+    dpi: u32,
+) -> Result<(i32, i32), Error> {
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: size.width as i32,
+        bottom: size.height as i32,
+    };
+    if unsafe { AdjustWindowRectExForDpi(&mut rect, style, 0, ex_style, dpi) } == 0 {
+        return Err(last_error("failed to compute window size"));
+    }
+
+    Ok((rect.right - rect.left, rect.bottom - rect.top))
+}
+
+unsafe extern "system" fn window_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_NCCREATE {
+        let create = &*(lparam as *const CREATESTRUCTW);
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, create.lpCreateParams as isize);
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+
+    let state = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WindowState;
+    if state.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let state = &*state;
+    if let Some(spy) = state.spy.borrow_mut().as_mut() {
+        spy.log(hwnd, msg, wparam, lparam);
+    }
+
+    match msg {
+        WM_CLOSE => {
+            // The window is destroyed when the owning `Window` is dropped, not here.
+            state.close_requested.set(true);
+            state.push(Event::CloseRequested);
+            0
+        }
+        WM_SIZE => {
+            // note: minimizing reports a 0x0 client area, which isn't worth resizing for.
+            if wparam as u32 != SIZE_MINIMIZED {
+                let width = (lparam & 0xffff) as u32;
+                let height = ((lparam >> 16) & 0xffff) as u32;
+                state.push(Event::Resized(PhysicalSize::new(width, height)));
+                state.reconfine(hwnd);
+            }
+            0
+        }
+        WM_DPICHANGED => {
+            // The new size arrives as a WM_SIZE once the window takes the suggested rect.
+            let dpi = (wparam & 0xffff) as u32;
+            state.push(Event::ScaleFactorChanged(
+                f64::from(dpi) / f64::from(BASE_DPI),
+            ));
+            let rect = &*(lparam as *const RECT);
+            SetWindowPos(
+                hwnd,
+                0,
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+            0
+        }
+        WM_GETMINMAXINFO => {
+            state.limit_size(hwnd, &mut *(lparam as *mut MINMAXINFO));
+            0
+        }
+        WM_MOVE => {
+            state.reconfine(hwnd);
+            0
+        }
+        WM_SETCURSOR if (lparam & 0xffff) as u32 == HTCLIENT => {
+            SetCursor(state.current_cursor());
+            1
+        }
+        WM_SETFOCUS => {
+            state.focused.set(true);
+            state.reconfine(hwnd);
+            state.push(Event::FocusChanged(true));
+            0
+        }
+        WM_ACTIVATEAPP => {
+            let exclusive = state.exclusive.borrow().clone();
+            if let Some(ExclusiveState {
+                device,
+                size,
+                refresh_rate,
+            }) = exclusive
+            {
+                if wparam == 0 {
+                    monitor::restore_display_mode(&device);
+                    ShowWindow(hwnd, SW_MINIMIZE);
+                } else {
+                    if IsIconic(hwnd) != 0 {
+                        ShowWindow(hwnd, SW_RESTORE);
+                    }
+                    if monitor::set_display_mode(&device, size, refresh_rate).is_ok() {
+                        _ = cover_monitor(hwnd);
+                    }
+                }
+            }
+            0
+        }
+        WM_SETTINGCHANGE if theme::is_theme_change(lparam) => {
+            state.update_theme(hwnd);
+            0
+        }
+        WM_SYSCHAR if wparam == usize::from(b'\r') => {
+            // note: DefWindowProcW beeps at Alt+Enter, since no menu item has Enter as its key.
+            0
+        }
+        WM_DEVICECHANGE => {
+            if let Some(event) = device::on_device_change(wparam, lparam) {
+                if event.kind == DeviceKind::Hid && event.connected {
+                    state.hid_arrived.set(true);
+                }
+                state.push(Event::Device(event));
+            }
+            1
+        }
+        WM_DROPFILES => {
+            state.push(Event::FilesDropped(file_drop::take_drop(wparam as HDROP)));
+            0
+        }
+        WM_KILLFOCUS => {
+            state.focused.set(false);
+            if state.cursor_confined.get() {
+                cursor::release();
+            }
+            state.release_held_keys();
+            state.push(Event::FocusChanged(false));
+            0
+        }
+        WM_KEYDOWN | WM_KEYUP => {
+            state.key(keyboard::key_event(wparam, lparam, msg == WM_KEYDOWN));
+            0
+        }
+        WM_MOUSEMOVE => {
+            state.push(Event::CursorMoved(mouse::position(lparam)));
+            0
+        }
+        WM_LBUTTONDOWN | WM_LBUTTONUP => {
+            state.mouse_button(MouseButton::Left, msg == WM_LBUTTONDOWN, lparam);
+            0
+        }
+        WM_RBUTTONDOWN | WM_RBUTTONUP => {
+            state.mouse_button(MouseButton::Right, msg == WM_RBUTTONDOWN, lparam);
+            0
+        }
+        WM_MBUTTONDOWN | WM_MBUTTONUP => {
+            state.mouse_button(MouseButton::Middle, msg == WM_MBUTTONDOWN, lparam);
+            0
+        }
+        WM_XBUTTONDOWN | WM_XBUTTONUP => {
+            state.mouse_button(mouse::x_button(wparam), msg == WM_XBUTTONDOWN, lparam);
+            // note: unlike the other buttons, X button messages should return TRUE.
+            1
+        }
+        WM_MOUSEWHEEL => {
+            state.push(Event::MouseWheel(WheelDelta {
+                x: 0.0,
+                y: mouse::wheel_notches(wparam),
+            }));
+            0
+        }
+        WM_MOUSEHWHEEL => {
+            state.push(Event::MouseWheel(WheelDelta {
+                x: mouse::wheel_notches(wparam),
+                y: 0.0,
+            }));
+            0
+        }
+        WM_INPUT => {
+            if state.raw_mouse_input.get() {
+                if let Some(delta) = mouse::raw_mouse_delta(lparam) {
+                    state.push(Event::MouseMotion(delta));
+                }
+            }
+            gamepad::hid::on_input(lparam);
+            // note: DefWindowProcW frees the input buffer for foreground input.
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        WM_CHAR => {
+            let c = state.chars.borrow_mut().push(wparam as u16);
+            if let (Some(c), true) = (c, state.text_input.get()) {
+                state.push(Event::Text(c.to_string()));
+            }
+            0
+        }
+        WM_UNICHAR => {
+            // note: returning TRUE for UNICODE_NOCHAR tells the sender we understand WM_UNICHAR.
+            if wparam as u32 == UNICODE_NOCHAR {
+                return 1;
+            }
+            let c = char::from_u32(wparam as u32).filter(|c| !c.is_control());
+            if let (Some(c), true) = (c, state.text_input.get()) {
+                state.push(Event::Text(c.to_string()));
+            }
+            0
+        }
+        WM_IME_SETCONTEXT => {
+            // The app draws the composition itself, but the candidate list is left to Windows.
+            let lparam = lparam & !(ISC_SHOWUICOMPOSITIONWINDOW as LPARAM);
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        WM_IME_STARTCOMPOSITION => {
+            text_input::set_ime_position(hwnd, state.ime_position.get());
+            state.push(Event::Ime(ImeEvent::Start));
+            0
+        }
+        WM_IME_COMPOSITION => {
+            // note: not passing this on keeps DefWindowProcW from also sending the result as
+            // WM_CHAR messages.
+            for event in text_input::composition_events(hwnd, lparam) {
+                state.push(Event::Ime(event));
+            }
+            0
+        }
+        WM_IME_ENDCOMPOSITION => {
+            state.push(Event::Ime(ImeEvent::End));
+            0
+        }
+        WM_INPUT_DEVICE_CHANGE => {
+            gamepad::hid::on_device_change(wparam, lparam);
+            0
+        }
+        WM_SYSKEYDOWN | WM_SYSKEYUP => {
+            // note: these still go to DefWindowProcW so Alt+F4 and the window menu keep working.
+            state.key(keyboard::key_event(wparam, lparam, msg == WM_SYSKEYDOWN));
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        WM_NCDESTROY => {
+            SetWindowLongPtrW(hwnd, GWLP_USERDATA, 0);
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn cursor_in_client(hwnd: HWND) -> bool {
+    let mut point = POINT { x: 0, y: 0 };
+    let mut rect = RECT {
+        left: 0,
+        top: 0,
+        right: 0,
+        bottom: 0,
+    };
+    let found = unsafe {
+        GetCursorPos(&mut point) != 0
+            && ScreenToClient(hwnd, &mut point) != 0
+            && GetClientRect(hwnd, &mut rect) != 0
+    };
+
+    found && point.x >= 0 && point.y >= 0 && point.x < rect.right && point.y < rect.bottom
+}